@@ -1,18 +1,170 @@
 from fust_orm.fust_orm import (
     Database,
+    ShardedDatabase,
+    CircuitBreaker,
+    RetryPolicy,
     QueryBuilder,
     WhereCondition,
     ColumnField,
+    F,
     Model,
+    ManyToMany,
+    RelatedManager,
+    StaleDataError,
+    Error,
+    InterfaceError,
+    DatabaseError,
+    OperationalError,
+    IntegrityError,
+    UniqueViolationError,
+    ForeignKeyViolationError,
+    NotNullViolationError,
+    CheckViolationError,
+    ProgrammingError,
+    DataError,
+    Scope,
+    BoundScope,
+    Signal,
+    Table,
+    AlterTable,
+    alter_table,
+    View,
+    Trigger,
+    FtsTable,
+    Changeset,
+    ColumnType,
+    Integer,
+    BigInteger,
+    SmallInteger,
+    Float,
+    Boolean,
+    Text,
+    Varchar,
+    Numeric,
+    Geometry,
+    Array,
+    register_type,
+    Date,
+    DateTime,
+    Blob,
+    Json,
+    Interval,
+    Int4Range,
+    Int8Range,
+    NumRange,
+    DateRange,
+    TsRange,
+    TstzRange,
+    Range,
+    scope,
     select,
+    bm25,
+    highlight,
+    snippet,
+    to_geometry,
+    as_wkb,
+    as_wkt,
+    load_geometry,
+    bind,
+    configure_runtime,
+    configure_query_cache,
+    introspect_models,
+    instrument,
+    uninstrument,
+    on_slow_query,
+    metrics,
+    tag_statements,
+    audit_statements,
+    rewrite_queries,
+    rewrite_statements,
+    pre_save,
+    post_save,
+    pre_delete,
+    post_delete,
 )
 
 
 __all__ = (
     "Database",
+    "ShardedDatabase",
+    "CircuitBreaker",
+    "RetryPolicy",
     "QueryBuilder",
     "WhereCondition",
     "ColumnField",
+    "F",
     "Model",
+    "ManyToMany",
+    "RelatedManager",
+    "StaleDataError",
+    "Error",
+    "InterfaceError",
+    "DatabaseError",
+    "OperationalError",
+    "IntegrityError",
+    "UniqueViolationError",
+    "ForeignKeyViolationError",
+    "NotNullViolationError",
+    "CheckViolationError",
+    "ProgrammingError",
+    "DataError",
+    "Scope",
+    "BoundScope",
+    "Signal",
+    "Table",
+    "AlterTable",
+    "alter_table",
+    "View",
+    "Trigger",
+    "FtsTable",
+    "Changeset",
+    "ColumnType",
+    "Integer",
+    "BigInteger",
+    "SmallInteger",
+    "Float",
+    "Boolean",
+    "Text",
+    "Varchar",
+    "Numeric",
+    "Geometry",
+    "Array",
+    "register_type",
+    "Date",
+    "DateTime",
+    "Blob",
+    "Json",
+    "Interval",
+    "Int4Range",
+    "Int8Range",
+    "NumRange",
+    "DateRange",
+    "TsRange",
+    "TstzRange",
+    "Range",
+    "scope",
     "select",
+    "bm25",
+    "highlight",
+    "snippet",
+    "to_geometry",
+    "as_wkb",
+    "as_wkt",
+    "load_geometry",
+    "bind",
+    "configure_runtime",
+    "configure_query_cache",
+    "introspect_models",
+    "instrument",
+    "uninstrument",
+    "on_slow_query",
+    "metrics",
+    "tag_statements",
+    "audit_statements",
+    "rewrite_queries",
+    "rewrite_statements",
+    "pre_save",
+    "post_save",
+    "pre_delete",
+    "post_delete",
 )
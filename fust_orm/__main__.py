@@ -0,0 +1,5 @@
+import sys
+
+from fust_orm.fust_orm import main
+
+sys.exit(main(sys.argv[1:]))
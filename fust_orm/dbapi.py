@@ -0,0 +1,57 @@
+"""A DB-API 2.0 (PEP 249) interface to fust-orm, for tools that take a
+DB-API connection, such as `pandas.read_sql()`."""
+
+from fust_orm.fust_orm import (
+    DbApiConnection as Connection,
+    DbApiCursor as Cursor,
+    DatabaseError,
+    DataError,
+    Error,
+    IntegrityError,
+    InterfaceError,
+    OperationalError,
+    ProgrammingError,
+)
+
+
+apilevel = "2.0"
+# Threads may share the module, not connections.
+threadsafety = 1
+paramstyle = "qmark"
+
+
+class Warning(Exception):
+    """Part of the DB-API; never raised."""
+
+
+class InternalError(DatabaseError):
+    """Part of the DB-API; never raised."""
+
+
+class NotSupportedError(DatabaseError):
+    """Part of the DB-API; never raised."""
+
+
+def connect(db_url: str, *, autocommit: bool = False) -> Connection:
+    """Connects to the database at `db_url`."""
+    return Connection(db_url, autocommit=autocommit)
+
+
+__all__ = (
+    "apilevel",
+    "threadsafety",
+    "paramstyle",
+    "connect",
+    "Connection",
+    "Cursor",
+    "Warning",
+    "Error",
+    "InterfaceError",
+    "DatabaseError",
+    "DataError",
+    "OperationalError",
+    "IntegrityError",
+    "InternalError",
+    "ProgrammingError",
+    "NotSupportedError",
+)
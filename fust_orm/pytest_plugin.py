@@ -0,0 +1,63 @@
+"""A pytest plugin providing databases to tests, enabled by one line in the
+root `conftest.py`::
+
+    pytest_plugins = ["fust_orm.pytest_plugin"]
+
+Its fixtures need pytest-asyncio:
+
+- `fust_db`: the database of `fust_database` in a transaction rolled back
+  after the test, bound like `Database.transaction()` binds it (see
+  `fust_orm.testing.test_database()`).
+- `fust_database`: a database connected to the `fust_orm_url` ini option, a
+  fresh in-memory SQLite database by default, with the migrations of the
+  `fust_orm_migrations` directory applied if it's set. Override it, e.g. with
+  a wider scope, to share a database between tests.
+- `fust_queries`: the statements the test runs after the fixtures above are
+  set up, recorded by `fust_orm.testing.capture_queries()`.
+"""
+
+from typing import AsyncIterator, Iterator
+
+import pytest
+import pytest_asyncio
+
+from fust_orm import Database
+from fust_orm.testing import CapturedQueries, capture_queries, test_database
+
+
+def pytest_addoption(parser: pytest.Parser) -> None:
+    parser.addini(
+        "fust_orm_url",
+        "The URL of the database of the fust_database fixture.",
+        default="sqlite::memory:",
+    )
+    parser.addini(
+        "fust_orm_migrations",
+        "The migrations directory applied to the database of the fust_database fixture, "
+        "relative to the root directory.",
+        default="",
+    )
+
+
+@pytest_asyncio.fixture
+async def fust_database(request: pytest.FixtureRequest) -> AsyncIterator[Database]:
+    db = await Database.connect(request.config.getini("fust_orm_url"))
+    try:
+        migrations = request.config.getini("fust_orm_migrations")
+        if migrations:
+            await db.migrate(str(request.config.rootpath / migrations))
+        yield db
+    finally:
+        await db.close()
+
+
+@pytest_asyncio.fixture
+async def fust_db(fust_database: Database) -> AsyncIterator[Database]:
+    async with test_database(fust_database) as db:
+        yield db
+
+
+@pytest.fixture
+def fust_queries(fust_db: Database) -> Iterator[CapturedQueries]:
+    with capture_queries() as queries:
+        yield queries
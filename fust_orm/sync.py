@@ -0,0 +1,10 @@
+"""A blocking API for code without an event loop, sharing the query building
+and decoding of the async one."""
+
+from fust_orm.fust_orm import SyncDatabase as Database, SyncTransaction as Transaction
+
+
+__all__ = (
+    "Database",
+    "Transaction",
+)
@@ -0,0 +1,57 @@
+"""Helpers for test suites.
+
+`test_database()` runs a test in a transaction rolled back when it ends, so
+that the schema is created once rather than for every test::
+
+    @pytest.fixture
+    async def tx_db(db):
+        async with test_database(db) as tx_db:
+            yield tx_db
+
+`capture_queries()`, `assert_num_queries()` and `assert_max_queries()` check
+the statements a block of code runs, e.g. against N+1 queries, and
+`Database.record_queries()` returns a `QueryRecorder` also checking their plans.
+
+`MockDatabase` answers statements with canned results, for unit tests without
+a database.
+"""
+
+import contextlib
+from typing import Iterator
+
+from fust_orm.fust_orm import CapturedQueries, MockDatabase, QueryRecorder, capture_queries, test_database
+
+
+def _report(queries: CapturedQueries) -> str:
+    return "".join(f"\n  {i}. {sql}" for i, sql in enumerate(queries.statements, 1))
+
+
+@contextlib.contextmanager
+def assert_num_queries(expected: int) -> Iterator[CapturedQueries]:
+    """Fails with `AssertionError`, listing the statements, unless the block
+    runs exactly `expected` statements."""
+    with capture_queries() as queries:
+        yield queries
+    if len(queries) != expected:
+        raise AssertionError(f"Expected {expected} queries, {len(queries)} ran:{_report(queries)}")
+
+
+@contextlib.contextmanager
+def assert_max_queries(limit: int) -> Iterator[CapturedQueries]:
+    """Fails with `AssertionError`, listing the statements, if the block runs
+    more than `limit` statements."""
+    with capture_queries() as queries:
+        yield queries
+    if len(queries) > limit:
+        raise AssertionError(f"Expected at most {limit} queries, {len(queries)} ran:{_report(queries)}")
+
+
+__all__ = (
+    "CapturedQueries",
+    "MockDatabase",
+    "QueryRecorder",
+    "assert_max_queries",
+    "assert_num_queries",
+    "capture_queries",
+    "test_database",
+)
@@ -0,0 +1,122 @@
+"""An async API for any event loop: trio, anyio or asyncio.
+
+The coroutines of `fust_orm.Database` need asyncio. The ones here run the
+blocking `fust_orm.sync` API on a worker thread of the running library
+instead, through `anyio` when it is installed and `trio` or `asyncio`
+otherwise, so they can be awaited from any of them.
+"""
+
+import asyncio
+import contextvars
+import functools
+from types import TracebackType
+from typing import Any, Callable, Dict, List, Optional, Sequence, Type, TypeVar, Union
+
+from fust_orm import sync
+from fust_orm.fust_orm import QueryBuilder
+
+
+T = TypeVar("T")
+
+Query = Union[str, QueryBuilder]
+
+
+def _current_library() -> str:
+    try:
+        import sniffio
+    except ImportError:
+        return "asyncio"
+    return sniffio.current_async_library()
+
+
+async def run_sync(function: Callable[..., T], *args: Any) -> T:
+    """Calls `function(*args)` on a worker thread of the running event loop, in
+    a copy of the current context, and waits for it without blocking the
+    loop."""
+    call = functools.partial(contextvars.copy_context().run, function, *args)
+    try:
+        import anyio.to_thread
+    except ImportError:
+        pass
+    else:
+        return await anyio.to_thread.run_sync(call)
+    library = _current_library()
+    if library == "trio":
+        import trio
+
+        return await trio.to_thread.run_sync(call)
+    if library == "asyncio":
+        return await asyncio.get_running_loop().run_in_executor(None, call)
+    raise RuntimeError(f"Running on {library} requires anyio")
+
+
+class Database:
+    """A database whose coroutines run under any event loop. `sync` is the
+    blocking `fust_orm.sync.Database` they call."""
+
+    def __init__(self, sync_db: sync.Database) -> None:
+        self.sync = sync_db
+
+    @staticmethod
+    async def connect(
+        db_url: str,
+        *,
+        echo: bool = False,
+        statement_cache_capacity: Optional[int] = None,
+        load_extensions: Sequence[str] = (),
+    ) -> "Database":
+        """Connects to a database using a connection URL, like
+        `fust_orm.Database.connect()`."""
+        connect = functools.partial(
+            sync.Database.connect,
+            echo=echo,
+            statement_cache_capacity=statement_cache_capacity,
+            load_extensions=load_extensions,
+        )
+        return Database(await run_sync(connect, db_url))
+
+    async def execute(self, query: Query) -> List[Dict[str, Any]]:
+        """Executes a raw SQL string or a QueryBuilder and returns the rows as
+        dicts."""
+        return await run_sync(self.sync.execute, query)
+
+    def transaction(self) -> "Transaction":
+        """Returns an async context manager running the statements executed on
+        it in a transaction, committed when the block exits normally and
+        rolled back when it raises."""
+        return Transaction(self.sync.transaction())
+
+
+class Transaction:
+    """A transaction of a `fust_orm.threaded.Database`, begun by entering it."""
+
+    def __init__(self, sync_tx: sync.Transaction) -> None:
+        self.sync = sync_tx
+
+    async def __aenter__(self) -> "Transaction":
+        await run_sync(self.sync.__enter__)
+        return self
+
+    async def __aexit__(
+        self,
+        exc_type: Optional[Type[BaseException]],
+        exc_value: Optional[BaseException],
+        traceback: Optional[TracebackType],
+    ) -> bool:
+        return await run_sync(self.sync.__exit__, exc_type, exc_value, traceback)
+
+    async def execute(self, query: Query) -> List[Dict[str, Any]]:
+        """Executes a raw SQL string or a QueryBuilder in the transaction and
+        returns the rows as dicts."""
+        return await run_sync(self.sync.execute, query)
+
+    async def execute_many(self, *queries: Query) -> None:
+        """Executes several statements in the transaction, in order."""
+        await run_sync(self.sync.execute_many, *queries)
+
+
+__all__ = (
+    "Database",
+    "Transaction",
+    "run_sync",
+)
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use log::debug;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyInt, PyString};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::pool::PoolConnection;
+use sqlx::{Any, Row};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::context;
+use crate::database::{Database, fetch_rows};
+use crate::error::FustOrmError;
+use crate::notify::require_postgres;
+
+/// The key of an advisory lock: a 64-bit integer, or text hashed into one by
+/// the server.
+enum LockKey {
+    Int(i64),
+    Text(String),
+}
+
+impl LockKey {
+    fn extract(key: &Bound<PyAny>) -> PyResult<Self> {
+        if let Ok(text) = key.downcast::<PyString>() {
+            return Ok(LockKey::Text(text.to_string()));
+        }
+        if key.is_instance_of::<PyInt>() {
+            return Ok(LockKey::Int(key.extract()?));
+        }
+        Err(PyTypeError::new_err(format!(
+            "An advisory lock key must be an int or a str, not {}.",
+            key.get_type().name()?
+        )))
+    }
+
+    /// The argument of the lock functions, with its parameters.
+    fn sql(&self) -> (String, Vec<String>) {
+        match self {
+            LockKey::Int(key) => (key.to_string(), Vec::new()),
+            LockKey::Text(key) => ("hashtextextended($1, 0)".to_string(), vec![key.clone()]),
+        }
+    }
+}
+
+impl std::fmt::Display for LockKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LockKey::Int(key) => write!(f, "{}", key),
+            LockKey::Text(key) => write!(f, "{:?}", key),
+        }
+    }
+}
+
+/// A PostgreSQL advisory lock, taken by `async with` and released when the
+/// block exits:
+///
+/// ```python
+/// async with db.advisory_lock("nightly-report"):
+///     ...
+/// ```
+///
+/// A session-level lock is held by a connection of its own, taken from the
+/// pool for the duration of the block. A transaction-level lock is taken in
+/// the bound transaction and only released when it commits or rolls back.
+/// Entering resolves to whether the lock was acquired, which is always the
+/// case unless `wait` is false.
+#[pyclass(module = "fust_orm")]
+pub struct AdvisoryLock {
+    db: Database,
+    key: Arc<LockKey>,
+    transaction: bool,
+    shared: bool,
+    wait: bool,
+    /// The connection holding a session-level lock while it is held.
+    conn: Arc<AsyncMutex<Option<PoolConnection<Any>>>>,
+}
+
+impl AdvisoryLock {
+    /// The name of the function taking the lock.
+    fn lock_function(&self) -> String {
+        format!(
+            "pg_{}advisory_{}lock{}",
+            if self.wait { "" } else { "try_" },
+            if self.transaction { "xact_" } else { "" },
+            if self.shared { "_shared" } else { "" }
+        )
+    }
+}
+
+/// Creates an advisory lock on `key` for `Database.advisory_lock()`.
+pub(crate) fn advisory_lock(
+    py: Python,
+    db: &Database,
+    key: &Bound<PyAny>,
+    transaction: bool,
+    shared: bool,
+    wait: bool,
+) -> PyResult<AdvisoryLock> {
+    require_postgres(db, "advisory_lock")?;
+    let key = LockKey::extract(key)?;
+    let db = context::resolve(py, Some(db))?;
+    if transaction && db.tx.is_none() {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "A transaction-level advisory lock can only be taken in a transaction.".to_string(),
+        )
+        .into());
+    }
+    Ok(AdvisoryLock {
+        db,
+        key: Arc::new(key),
+        transaction,
+        shared,
+        wait,
+        conn: Arc::new(AsyncMutex::new(None)),
+    })
+}
+
+#[pymethods]
+impl AdvisoryLock {
+    fn __aenter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let db = self.db.clone();
+        let key = self.key.clone();
+        let transaction = self.transaction;
+        let conn = self.conn.clone();
+        let (argument, params) = key.sql();
+        // The blocking functions return `void`, which can't be decoded; the
+        // others return whether they took the lock.
+        let sql = if self.wait {
+            format!(
+                "SELECT true AS acquired FROM {}({})",
+                self.lock_function(),
+                argument
+            )
+        } else {
+            format!("SELECT {}({}) AS acquired", self.lock_function(), argument)
+        };
+        future_into_py(py, async move {
+            debug!("Taking advisory lock {}", key);
+            let rows = if transaction {
+                db.fetch_rows(&sql, params).await?
+            } else {
+                let mut guard = conn.lock().await;
+                if guard.is_some() {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Advisory lock {} is already held by this object.",
+                        key
+                    ))
+                    .into());
+                }
+                let mut held = db.pool.acquire().await.map_err(FustOrmError::from)?;
+                let rows = fetch_rows(&mut *held, &sql, params).await?;
+                *guard = Some(held);
+                rows
+            };
+            let acquired = match rows.first() {
+                Some(row) => row
+                    .try_get::<bool, _>("acquired")
+                    .map_err(FustOrmError::from)?,
+                None => false,
+            };
+            if !acquired {
+                conn.lock().await.take();
+            }
+            Ok(acquired)
+        })
+    }
+
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __aexit__<'py>(
+        &self,
+        py: Python<'py>,
+        _exc_type: Option<&Bound<'py, PyAny>>,
+        _exc_value: Option<&Bound<'py, PyAny>>,
+        _traceback: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let key = self.key.clone();
+        let conn = self.conn.clone();
+        let (argument, params) = key.sql();
+        let sql = format!(
+            "SELECT pg_advisory_unlock{}({}) AS released",
+            if self.shared { "_shared" } else { "" },
+            argument
+        );
+        future_into_py(py, async move {
+            // Transaction-level locks are released by the transaction.
+            let Some(mut held) = conn.lock().await.take() else {
+                return Ok(false);
+            };
+            debug!("Releasing advisory lock {}", key);
+            if let Err(e) = fetch_rows(&mut *held, &sql, params).await {
+                // Closing the session releases its locks.
+                held.close().await.ok();
+                return Err(e.into());
+            }
+            Ok(false)
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<AdvisoryLock {}{}{}>",
+            self.key,
+            if self.transaction { " transaction" } else { "" },
+            if self.shared { " shared" } else { "" }
+        )
+    }
+}
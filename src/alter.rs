@@ -0,0 +1,871 @@
+use crate::database::{
+    Database, begin_write, execute_statement, fetch_rows, sqlite_foreign_keys_off,
+    sqlite_foreign_keys_on,
+};
+use crate::error::FustOrmError;
+use crate::introspect::{
+    generation_expression, sqlite_column_definitions, sqlite_table_constraints,
+};
+use crate::schema::{
+    CheckConstraint, ColumnDef, ColumnType, DefaultValue, Dialect, UniqueConstraint,
+};
+use log::debug;
+use pyo3::prelude::*;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::any::AnyRow;
+use sqlx::{AnyConnection, AnyPool, Connection, Row};
+
+/// The name of the table a SQLite table is rebuilt into before it replaces it.
+const REBUILD_PREFIX: &str = "_fust_rebuild_";
+
+/// A change of an `AlterTable`.
+#[derive(Debug, Clone)]
+enum AlterOp {
+    Add(ColumnDef),
+    Drop(String),
+    Rename {
+        from: String,
+        to: String,
+    },
+    Alter {
+        name: String,
+        column_type: Option<ColumnType>,
+        nullable: Option<bool>,
+    },
+    AddCheck(CheckConstraint),
+    DropConstraint(String),
+}
+
+/// A step of applying an `AlterTable` to a database: a statement, or on SQLite
+/// a change it can't make with `ALTER TABLE` and that rebuilds the table.
+#[derive(Debug)]
+enum Step {
+    Sql(String),
+    Rebuild(RebuildOp),
+}
+
+/// A change made by rebuilding a SQLite table, with SQL already rendered.
+#[derive(Debug)]
+pub(crate) enum RebuildOp {
+    Add {
+        name: String,
+        definition: String,
+    },
+    Drop(String),
+    Alter {
+        name: String,
+        sql_type: Option<String>,
+        nullable: Option<bool>,
+    },
+    AddCheck {
+        name: String,
+        definition: String,
+    },
+    DropConstraint(String),
+}
+
+impl RebuildOp {
+    fn describe(&self) -> String {
+        match self {
+            RebuildOp::Add { name, .. } => format!("Adding the column '{}'", name),
+            RebuildOp::Drop(name) => format!("Dropping the column '{}'", name),
+            RebuildOp::Alter { name, .. } => format!("Altering the column '{}'", name),
+            RebuildOp::AddCheck { name, .. } => format!("Adding the constraint '{}'", name),
+            RebuildOp::DropConstraint(name) => format!("Dropping the constraint '{}'", name),
+        }
+    }
+}
+
+/// A column of an existing SQLite table, as reported by `pragma_table_xinfo`.
+#[derive(Debug, Clone)]
+struct SqliteColumn {
+    name: String,
+    /// The full definition for columns added by the rebuild and for generated
+    /// columns.
+    definition: Option<String>,
+    /// Whether the values are computed by SQLite, so never copied.
+    generated: bool,
+    sql_type: String,
+    not_null: bool,
+    default: Option<String>,
+    /// The position in the primary key, starting at 1 (0 if not part of it).
+    pk: i64,
+    /// The column of the old table the values are copied from.
+    source: Option<String>,
+}
+
+impl SqliteColumn {
+    /// Renders the column definition, with `constraint` (such as `PRIMARY KEY`)
+    /// before the default.
+    fn definition_sql(&self, constraint: Option<&str>) -> String {
+        if let Some(definition) = &self.definition {
+            return definition.clone();
+        }
+        let mut sql = self.name.clone();
+        if !self.sql_type.is_empty() {
+            sql.push(' ');
+            sql.push_str(&self.sql_type);
+        }
+        if self.not_null {
+            sql.push_str(" NOT NULL");
+        }
+        if let Some(constraint) = constraint {
+            sql.push(' ');
+            sql.push_str(constraint);
+        }
+        if let Some(default) = &self.default {
+            sql.push_str(" DEFAULT ");
+            sql.push_str(default);
+        }
+        sql
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SqliteForeignKey {
+    from: Vec<String>,
+    table: String,
+    to: Vec<String>,
+    on_update: String,
+    on_delete: String,
+}
+
+/// The schema of an existing SQLite table, enough to recreate it.
+#[derive(Debug, Clone)]
+pub(crate) struct SqliteTable {
+    name: String,
+    columns: Vec<SqliteColumn>,
+    foreign_keys: Vec<SqliteForeignKey>,
+    /// `UNIQUE` constraints, with their name if the table declares one.
+    unique: Vec<UniqueConstraint>,
+    /// Table-level `CHECK` constraints as written, with their name.
+    checks: Vec<(Option<String>, String)>,
+    autoincrement: bool,
+    /// `CREATE INDEX` statements with the indexed columns.
+    indexes: Vec<(String, Vec<String>, String)>,
+    /// `CREATE TRIGGER` statements.
+    triggers: Vec<String>,
+}
+
+fn map_db_err(e: sqlx::Error) -> FustOrmError {
+    FustOrmError::from(e)
+}
+
+fn text(row: &AnyRow, column: &str) -> Result<String, FustOrmError> {
+    Ok(row
+        .try_get::<Option<String>, _>(column)
+        .map_err(map_db_err)?
+        .unwrap_or_default())
+}
+
+impl SqliteTable {
+    pub(crate) async fn load(conn: &mut AnyConnection, name: &str) -> Result<Self, FustOrmError> {
+        let params = || vec![name.to_string()];
+        let create = fetch_rows(
+            &mut *conn,
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?",
+            params(),
+        )
+        .await?;
+        let create = match create.first() {
+            Some(row) => text(row, "sql")?,
+            None => String::new(),
+        };
+        let constraints = sqlite_table_constraints(&create);
+        let info = fetch_rows(
+            &mut *conn,
+            "SELECT * FROM pragma_table_xinfo(?) WHERE hidden <> 1",
+            params(),
+        )
+        .await?;
+        if info.is_empty() {
+            return Err(FustOrmError::QueryError(format!(
+                "Table '{}' doesn't exist.",
+                name
+            )));
+        }
+        let definitions = sqlite_column_definitions(&create);
+        let mut columns = Vec::new();
+        for row in &info {
+            let column = text(row, "name")?;
+            let hidden = row.try_get::<i64, _>("hidden").map_err(map_db_err)?;
+            // Generated columns keep their definition as written.
+            let generated = hidden != 0;
+            let definition = match generated {
+                true => definitions
+                    .iter()
+                    .find(|(name, _)| *name == column)
+                    .map(|(_, definition)| definition.clone()),
+                false => None,
+            };
+            columns.push(SqliteColumn {
+                definition,
+                generated,
+                sql_type: text(row, "type")?,
+                not_null: row.try_get::<i64, _>("notnull").map_err(map_db_err)? != 0,
+                default: row
+                    .try_get::<Option<String>, _>("dflt_value")
+                    .map_err(map_db_err)?,
+                pk: row.try_get::<i64, _>("pk").map_err(map_db_err)?,
+                source: (!generated).then(|| column.clone()),
+                name: column,
+            });
+        }
+
+        let mut foreign_keys: Vec<(i64, SqliteForeignKey)> = Vec::new();
+        let rows = fetch_rows(
+            &mut *conn,
+            "SELECT * FROM pragma_foreign_key_list(?) ORDER BY id, seq",
+            params(),
+        )
+        .await?;
+        for row in &rows {
+            let id = row.try_get::<i64, _>("id").map_err(map_db_err)?;
+            if let Some((_, fk)) = foreign_keys.iter_mut().find(|(i, _)| *i == id) {
+                fk.from.push(text(row, "from")?);
+                fk.to.push(text(row, "to")?);
+                continue;
+            }
+            foreign_keys.push((
+                id,
+                SqliteForeignKey {
+                    from: vec![text(row, "from")?],
+                    table: text(row, "table")?,
+                    to: vec![text(row, "to")?],
+                    on_update: text(row, "on_update")?,
+                    on_delete: text(row, "on_delete")?,
+                },
+            ));
+        }
+
+        let mut unique = Vec::new();
+        let mut indexes = Vec::new();
+        let rows = fetch_rows(&mut *conn, "SELECT * FROM pragma_index_list(?)", params()).await?;
+        for row in &rows {
+            let index = text(row, "name")?;
+            let origin = text(row, "origin")?;
+            if origin == "pk" {
+                continue;
+            }
+            let indexed = fetch_rows(
+                &mut *conn,
+                "SELECT name FROM pragma_index_info(?) ORDER BY seqno",
+                vec![index.clone()],
+            )
+            .await?
+            .iter()
+            .map(|r| text(r, "name"))
+            .collect::<Result<Vec<_>, _>>()?;
+            if origin == "u" {
+                let name = constraints
+                    .iter()
+                    .find(|(_, c)| c.kind == "unique" && c.columns == indexed)
+                    .and_then(|(_, c)| c.name.clone());
+                unique.push(UniqueConstraint {
+                    name,
+                    columns: indexed,
+                });
+                continue;
+            }
+            let sql = fetch_rows(
+                &mut *conn,
+                "SELECT sql FROM sqlite_master WHERE type = 'index' AND name = ?",
+                vec![index.clone()],
+            )
+            .await?;
+            if let Some(row) = sql.first() {
+                indexes.push((index, indexed, text(row, "sql")?));
+            }
+        }
+        // Indexes are listed newest first.
+        indexes.reverse();
+
+        let triggers = fetch_rows(
+            &mut *conn,
+            "SELECT sql FROM sqlite_master WHERE type = 'trigger' AND tbl_name = ?",
+            params(),
+        )
+        .await?
+        .iter()
+        .map(|r| text(r, "sql"))
+        .collect::<Result<Vec<_>, _>>()?;
+        // Unique constraints are listed newest first too.
+        unique.reverse();
+        let checks = constraints
+            .into_iter()
+            .filter(|(_, c)| c.kind == "check")
+            .map(|(sql, c)| (c.name, sql))
+            .collect();
+
+        Ok(SqliteTable {
+            name: name.to_string(),
+            columns,
+            foreign_keys: foreign_keys.into_iter().map(|(_, fk)| fk).collect(),
+            unique,
+            checks,
+            autoincrement: create.to_uppercase().contains("AUTOINCREMENT"),
+            indexes,
+            triggers,
+        })
+    }
+
+    fn has_constraint(&self, name: &str) -> bool {
+        self.checks.iter().any(|(n, _)| n.as_deref() == Some(name))
+            || self.unique.iter().any(|u| u.name.as_deref() == Some(name))
+    }
+
+    fn column(&mut self, name: &str) -> Result<&mut SqliteColumn, FustOrmError> {
+        let table = self.name.clone();
+        self.columns
+            .iter_mut()
+            .find(|c| c.name == name)
+            .ok_or_else(|| {
+                FustOrmError::InvalidQueryArgument(format!(
+                    "'{}' is not a column of '{}'.",
+                    name, table
+                ))
+            })
+    }
+
+    pub(crate) fn apply(&mut self, op: &RebuildOp) -> Result<(), FustOrmError> {
+        match op {
+            RebuildOp::Add { name, definition } => {
+                if self.columns.iter().any(|c| &c.name == name) {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Table '{}' already has a column '{}'.",
+                        self.name, name
+                    )));
+                }
+                self.columns.push(SqliteColumn {
+                    name: name.clone(),
+                    generated: generation_expression(definition).is_some(),
+                    definition: Some(definition.clone()),
+                    sql_type: String::new(),
+                    not_null: false,
+                    default: None,
+                    pk: 0,
+                    source: None,
+                });
+            }
+            RebuildOp::Drop(name) => {
+                if self.column(name)?.pk > 0 {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Column '{}' is part of the primary key of '{}' and can't be dropped.",
+                        name, self.name
+                    )));
+                }
+                if let Some((index, _, _)) = self
+                    .indexes
+                    .iter()
+                    .find(|(_, columns, _)| columns.contains(name))
+                {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Column '{}' of '{}' is used by the index '{}'; drop the index first.",
+                        name, self.name, index
+                    )));
+                }
+                if self.unique.iter().any(|u| u.columns.contains(name)) {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Column '{}' of '{}' is part of a unique constraint and can't be dropped.",
+                        name, self.name
+                    )));
+                }
+                self.columns.retain(|c| &c.name != name);
+                self.foreign_keys.retain(|fk| !fk.from.contains(name));
+            }
+            RebuildOp::Alter {
+                name,
+                sql_type,
+                nullable,
+            } => {
+                let table = self.name.clone();
+                let column = self.column(name)?;
+                if column.generated {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Column '{}' of '{}' is generated and can't be altered; \
+                         drop it and add it again.",
+                        name, table
+                    )));
+                }
+                if let Some(sql_type) = sql_type {
+                    column.sql_type = sql_type.clone();
+                }
+                if let Some(nullable) = nullable {
+                    column.not_null = !nullable;
+                }
+            }
+            RebuildOp::AddCheck { name, definition } => {
+                if self.has_constraint(name) {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Table '{}' already has a constraint named '{}'.",
+                        self.name, name
+                    )));
+                }
+                self.checks.push((Some(name.clone()), definition.clone()));
+            }
+            RebuildOp::DropConstraint(name) => {
+                if !self.has_constraint(name) {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Table '{}' has no constraint named '{}'.",
+                        self.name, name
+                    )));
+                }
+                self.checks.retain(|(n, _)| n.as_ref() != Some(name));
+                self.unique.retain(|u| u.name.as_ref() != Some(name));
+            }
+        }
+        Ok(())
+    }
+
+    fn create_sql(&self, name: &str) -> String {
+        let mut primary_key: Vec<&SqliteColumn> =
+            self.columns.iter().filter(|c| c.pk > 0).collect();
+        primary_key.sort_by_key(|c| c.pk);
+        // A single `INTEGER` key stays declared on the column, where it is an
+        // alias of the rowid.
+        let inline_pk = match primary_key.as_slice() {
+            [column] if column.sql_type.eq_ignore_ascii_case("INTEGER") => {
+                Some(column.name.clone())
+            }
+            _ => None,
+        };
+        let mut parts = Vec::new();
+        for column in &self.columns {
+            let constraint = match inline_pk.as_ref() == Some(&column.name) {
+                true if self.autoincrement => Some("PRIMARY KEY AUTOINCREMENT"),
+                true => Some("PRIMARY KEY"),
+                false => None,
+            };
+            parts.push(column.definition_sql(constraint));
+        }
+        if inline_pk.is_none() && !primary_key.is_empty() {
+            let names: Vec<&str> = primary_key.iter().map(|c| c.name.as_str()).collect();
+            parts.push(format!("PRIMARY KEY ({})", names.join(", ")));
+        }
+        for unique in &self.unique {
+            let columns = unique.columns.join(", ");
+            parts.push(match &unique.name {
+                Some(name) => format!("CONSTRAINT {} UNIQUE ({})", name, columns),
+                None => format!("UNIQUE ({})", columns),
+            });
+        }
+        parts.extend(self.checks.iter().map(|(_, sql)| sql.clone()));
+        for fk in &self.foreign_keys {
+            let mut sql = format!(
+                "FOREIGN KEY ({}) REFERENCES {} ({})",
+                fk.from.join(", "),
+                fk.table,
+                fk.to.join(", ")
+            );
+            for (event, action) in [("UPDATE", &fk.on_update), ("DELETE", &fk.on_delete)] {
+                if action != "NO ACTION" {
+                    sql.push_str(&format!(" ON {} {}", event, action));
+                }
+            }
+            parts.push(sql);
+        }
+        format!("CREATE TABLE {} ({})", name, parts.join(", "))
+    }
+
+    /// The statements that replace the table with one of the current schema,
+    /// copying the rows of the columns that are kept.
+    pub(crate) fn rebuild_sql(&self) -> Vec<String> {
+        let temporary = format!("{}{}", REBUILD_PREFIX, self.name);
+        let (targets, sources): (Vec<&str>, Vec<&str>) = self
+            .columns
+            .iter()
+            .filter_map(|c| c.source.as_deref().map(|s| (c.name.as_str(), s)))
+            .unzip();
+        let mut statements = vec![
+            self.create_sql(&temporary),
+            format!(
+                "INSERT INTO {} ({}) SELECT {} FROM {}",
+                temporary,
+                targets.join(", "),
+                sources.join(", "),
+                self.name
+            ),
+            format!("DROP TABLE {}", self.name),
+            format!("ALTER TABLE {} RENAME TO {}", temporary, self.name),
+        ];
+        statements.extend(self.indexes.iter().map(|(_, _, sql)| sql.clone()));
+        statements.extend(self.triggers.iter().cloned());
+        statements
+    }
+
+    /// Removes the indexes using `column` from the rebuilt table, returning
+    /// their `CREATE INDEX` statements.
+    pub(crate) fn take_indexes_on(&mut self, column: &str) -> Vec<String> {
+        let (taken, kept) = std::mem::take(&mut self.indexes)
+            .into_iter()
+            .partition(|(_, columns, _)| columns.iter().any(|c| c == column));
+        self.indexes = kept;
+        taken.into_iter().map(|(_, _, sql)| sql).collect()
+    }
+
+    /// The table as it is once rebuilt, with each column holding its own values.
+    pub(crate) fn settled(&self) -> SqliteTable {
+        let mut table = self.clone();
+        for column in &mut table.columns {
+            column.source = (!column.generated).then(|| column.name.clone());
+        }
+        table
+    }
+
+    /// The change adding the column `name` back as it is declared now, with
+    /// its single-column foreign key but without its values.
+    pub(crate) fn restore_column(&self, name: &str) -> Option<RebuildOp> {
+        let column = self.columns.iter().find(|c| c.name == name)?;
+        let mut definition = column.definition_sql(None);
+        if let Some(fk) = self.foreign_keys.iter().find(|fk| fk.from == [name]) {
+            definition.push_str(&format!(" REFERENCES {} ({})", fk.table, fk.to.join(", ")));
+            if fk.on_delete != "NO ACTION" {
+                definition.push_str(&format!(" ON DELETE {}", fk.on_delete));
+            }
+        }
+        Some(RebuildOp::Add {
+            name: name.to_string(),
+            definition,
+        })
+    }
+}
+
+/// Whether SQLite can add `column` with `ALTER TABLE`: only columns without
+/// constraints it would have to check against the existing rows, and not
+/// stored generated columns.
+pub(crate) fn sqlite_adds_natively(column: &ColumnDef) -> bool {
+    !(column.unique
+        || column.generated.as_ref().is_some_and(|g| g.stored)
+        || matches!(column.default, Some(DefaultValue::Sql(_)))
+        || (!column.nullable && column.default.is_none()))
+}
+
+/// Runs the steps on SQLite in one transaction. Foreign keys are switched off
+/// while tables are rebuilt, as SQLite requires, and checked before committing.
+async fn apply_sqlite(pool: &AnyPool, table: &str, steps: Vec<Step>) -> Result<(), FustOrmError> {
+    let mut conn = pool.acquire().await.map_err(map_db_err)?;
+    let enforced = sqlite_foreign_keys_off(&mut conn).await?;
+    let result = run_sqlite_steps(&mut conn, table, steps, enforced).await;
+    if enforced {
+        sqlite_foreign_keys_on(&mut conn).await?;
+    }
+    result
+}
+
+async fn run_sqlite_steps(
+    conn: &mut AnyConnection,
+    table: &str,
+    steps: Vec<Step>,
+    enforced: bool,
+) -> Result<(), FustOrmError> {
+    let mut tx = conn
+        .begin_with("BEGIN IMMEDIATE")
+        .await
+        .map_err(map_db_err)?;
+    for step in steps {
+        match step {
+            Step::Sql(sql) => {
+                execute_statement(&mut *tx, &sql, Vec::new()).await?;
+            }
+            Step::Rebuild(op) => {
+                debug!("Rebuilding table '{}': {}", table, op.describe());
+                let mut schema = SqliteTable::load(&mut tx, table).await?;
+                schema.apply(&op)?;
+                for sql in schema.rebuild_sql() {
+                    execute_statement(&mut *tx, &sql, Vec::new()).await?;
+                }
+            }
+        }
+    }
+    if enforced
+        && !fetch_rows(
+            &mut *tx,
+            "SELECT * FROM pragma_foreign_key_check(?)",
+            vec![table.to_string()],
+        )
+        .await?
+        .is_empty()
+    {
+        return Err(FustOrmError::QueryError(format!(
+            "Altering '{}' would leave rows violating its foreign keys.",
+            table
+        )));
+    }
+    tx.commit().await.map_err(map_db_err)
+}
+
+/// A programmatic `ALTER TABLE`, created with `alter_table()`.
+///
+/// Changes are applied in order, in one transaction where the backend allows
+/// it. SQLite can't drop columns or change their type with `ALTER TABLE`, so
+/// for those the table is rebuilt: created anew, its rows copied over, and its
+/// indexes and triggers restored.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct AlterTable {
+    #[pyo3(get)]
+    name: String,
+    ops: Vec<AlterOp>,
+}
+
+impl AlterTable {
+    fn with(&self, op: AlterOp) -> AlterTable {
+        let mut alter = self.clone();
+        alter.ops.push(op);
+        alter
+    }
+
+    fn steps(&self, py: Python, dialect: Dialect) -> PyResult<Vec<Step>> {
+        let table = &self.name;
+        let mut steps = Vec::new();
+        for op in &self.ops {
+            let step = match (op, dialect) {
+                (AlterOp::Add(column), Dialect::Sqlite) if !sqlite_adds_natively(column) => {
+                    Step::Rebuild(RebuildOp::Add {
+                        name: column.name.clone(),
+                        definition: column.to_sql(py, dialect, false)?,
+                    })
+                }
+                (AlterOp::AddCheck(check), Dialect::Sqlite) => Step::Rebuild(RebuildOp::AddCheck {
+                    name: check.name.clone(),
+                    definition: check.to_sql(),
+                }),
+                (AlterOp::AddCheck(check), _) => {
+                    Step::Sql(format!("ALTER TABLE {} ADD {}", table, check.to_sql()))
+                }
+                (AlterOp::DropConstraint(name), Dialect::Sqlite) => {
+                    Step::Rebuild(RebuildOp::DropConstraint(name.clone()))
+                }
+                (AlterOp::DropConstraint(name), _) => {
+                    Step::Sql(format!("ALTER TABLE {} DROP CONSTRAINT {}", table, name))
+                }
+                (AlterOp::Add(column), _) => Step::Sql(format!(
+                    "ALTER TABLE {} ADD COLUMN {}",
+                    table,
+                    column.to_sql(py, dialect, false)?
+                )),
+                (AlterOp::Rename { from, to }, _) => Step::Sql(format!(
+                    "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                    table, from, to
+                )),
+                (AlterOp::Drop(name), Dialect::Sqlite) => {
+                    Step::Rebuild(RebuildOp::Drop(name.clone()))
+                }
+                (AlterOp::Drop(name), _) => {
+                    Step::Sql(format!("ALTER TABLE {} DROP COLUMN {}", table, name))
+                }
+                (
+                    AlterOp::Alter {
+                        name,
+                        column_type,
+                        nullable,
+                    },
+                    Dialect::Sqlite,
+                ) => Step::Rebuild(RebuildOp::Alter {
+                    name: name.clone(),
+                    sql_type: column_type.map(|t| t.to_sql(dialect)),
+                    nullable: *nullable,
+                }),
+                (
+                    AlterOp::Alter {
+                        name,
+                        column_type,
+                        nullable,
+                    },
+                    Dialect::Postgres,
+                ) => {
+                    let mut changes = Vec::new();
+                    if let Some(column_type) = column_type {
+                        changes.push(format!(
+                            "ALTER COLUMN {} TYPE {}",
+                            name,
+                            column_type.to_sql(dialect)
+                        ));
+                    }
+                    match nullable {
+                        Some(true) => changes.push(format!("ALTER COLUMN {} DROP NOT NULL", name)),
+                        Some(false) => changes.push(format!("ALTER COLUMN {} SET NOT NULL", name)),
+                        None => {}
+                    }
+                    Step::Sql(format!("ALTER TABLE {} {}", table, changes.join(", ")))
+                }
+                (
+                    AlterOp::Alter {
+                        name,
+                        column_type,
+                        nullable,
+                    },
+                    Dialect::Mysql,
+                ) => {
+                    // MODIFY COLUMN replaces the whole column definition.
+                    let Some(column_type) = column_type else {
+                        return Err(FustOrmError::BuildError(format!(
+                            "MySQL needs the type of '{}' to change whether it is nullable.",
+                            name
+                        ))
+                        .into());
+                    };
+                    Step::Sql(format!(
+                        "ALTER TABLE {} MODIFY COLUMN {} {}{}",
+                        table,
+                        name,
+                        column_type.to_sql(dialect),
+                        if *nullable == Some(false) {
+                            " NOT NULL"
+                        } else {
+                            ""
+                        }
+                    ))
+                }
+            };
+            steps.push(step);
+        }
+        Ok(steps)
+    }
+}
+
+/// Starts an `ALTER TABLE` of the table `name`.
+#[pyfunction]
+pub fn alter_table(name: String) -> AlterTable {
+    AlterTable {
+        name,
+        ops: Vec::new(),
+    }
+}
+
+#[pymethods]
+impl AlterTable {
+    /// Returns a copy with a column added, declared as in `Table.column()`.
+    #[pyo3(signature = (
+        name,
+        column_type,
+        *,
+        nullable = None,
+        unique = false,
+        default = None,
+        default_sql = None,
+        references = None,
+        on_delete = None,
+        generated = None,
+        stored = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn add_column(
+        &self,
+        name: String,
+        column_type: ColumnType,
+        nullable: Option<bool>,
+        unique: bool,
+        default: Option<Py<PyAny>>,
+        default_sql: Option<String>,
+        references: Option<&Bound<PyAny>>,
+        on_delete: Option<&str>,
+        generated: Option<String>,
+        stored: bool,
+    ) -> PyResult<AlterTable> {
+        let column = ColumnDef::new(
+            &self.name,
+            name,
+            column_type,
+            false,
+            nullable,
+            unique,
+            default,
+            default_sql,
+            false,
+            references,
+            on_delete,
+            generated,
+            stored,
+        )?;
+        Ok(self.with(AlterOp::Add(column)))
+    }
+
+    /// Returns a copy with the column `name` dropped.
+    fn drop_column(&self, name: String) -> AlterTable {
+        self.with(AlterOp::Drop(name))
+    }
+
+    /// Returns a copy with the column `old` renamed to `new`.
+    fn rename_column(&self, old: String, new: String) -> AlterTable {
+        self.with(AlterOp::Rename { from: old, to: new })
+    }
+
+    /// Returns a copy that changes the type and/or nullability of a column.
+    #[pyo3(signature = (name, column_type = None, *, nullable = None))]
+    fn alter_column(
+        &self,
+        name: String,
+        column_type: Option<ColumnType>,
+        nullable: Option<bool>,
+    ) -> PyResult<AlterTable> {
+        if column_type.is_none() && nullable.is_none() {
+            return Err(FustOrmError::BuildError(format!(
+                "alter_column('{}') needs a column_type or nullable.",
+                name
+            ))
+            .into());
+        }
+        Ok(self.with(AlterOp::Alter {
+            name,
+            column_type,
+            nullable,
+        }))
+    }
+
+    /// Returns a copy that adds the check constraint `name`, which the
+    /// existing rows must already satisfy.
+    fn add_check(&self, name: String, expression: String) -> AlterTable {
+        self.with(AlterOp::AddCheck(CheckConstraint { name, expression }))
+    }
+
+    /// Returns a copy that drops the unique or check constraint `name`.
+    fn drop_constraint(&self, name: String) -> AlterTable {
+        self.with(AlterOp::DropConstraint(name))
+    }
+
+    /// Renders the statements for `dialect` (`"sqlite"`, `"postgres"` or
+    /// `"mysql"`). Changes that rebuild a SQLite table depend on its current
+    /// schema and can only be applied with `apply()`.
+    #[pyo3(signature = (dialect = "sqlite"))]
+    fn to_sql(&self, py: Python, dialect: &str) -> PyResult<Vec<String>> {
+        self.steps(py, Dialect::parse(dialect)?)?
+            .into_iter()
+            .map(|step| match step {
+                Step::Sql(sql) => Ok(sql),
+                Step::Rebuild(op) => Err(FustOrmError::BuildError(format!(
+                    "{} rebuilds the SQLite table '{}' and can only be applied with apply().",
+                    op.describe(),
+                    self.name
+                ))
+                .into()),
+            })
+            .collect()
+    }
+
+    /// Applies the changes to the database.
+    fn apply<'py>(&self, py: Python<'py>, db: &Database) -> PyResult<Bound<'py, PyAny>> {
+        let dialect = db.dialect();
+        let steps = self.steps(py, dialect)?;
+        let pool = db.pool.clone();
+        let table = self.name.clone();
+        future_into_py(py, async move {
+            if dialect == Dialect::Sqlite {
+                apply_sqlite(&pool, &table, steps).await?;
+                return Ok(());
+            }
+            let mut tx = begin_write(&pool).await?;
+            for step in steps {
+                if let Step::Sql(sql) = step {
+                    execute_statement(&mut *tx, &sql, Vec::new()).await?;
+                }
+            }
+            tx.commit().await.map_err(map_db_err)?;
+            Ok(())
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<AlterTable {} ({} changes)>", self.name, self.ops.len())
+    }
+}
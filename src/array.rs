@@ -0,0 +1,158 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyFloat, PyInt, PyList, PyString, PyTuple, PyType};
+
+use crate::error::FustOrmError;
+
+/// Whether `value` is bound as an array: a list or a tuple.
+pub(crate) fn is_array(value: &Bound<PyAny>) -> bool {
+    value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>()
+}
+
+/// Renders a list (of lists, for more dimensions) as the text of a
+/// PostgreSQL array, e.g. `{1,2,NULL}` or `{"a b","c"}`, which array
+/// parameters are bound as and other databases store.
+pub(crate) fn array_literal(value: &Bound<PyAny>) -> PyResult<String> {
+    let mut items = Vec::new();
+    for item in value.try_iter()? {
+        let item = item?;
+        items.push(if item.is_none() {
+            "NULL".to_string()
+        } else if is_array(&item) {
+            array_literal(&item)?
+        } else if let Ok(flag) = item.downcast::<PyBool>() {
+            (if flag.is_true() { "true" } else { "false" }).to_string()
+        } else if item.is_instance_of::<PyInt>() || item.is_instance_of::<PyFloat>() {
+            item.str()?.to_string()
+        } else {
+            let text = item.str()?.to_string();
+            format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+        });
+    }
+    Ok(format!("{{{}}}", items.join(",")))
+}
+
+/// An element of a parsed array.
+enum Element {
+    Null,
+    Text(String),
+    Array(Vec<Element>),
+}
+
+/// Parses the text of a PostgreSQL array.
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn error(&self) -> FustOrmError {
+        FustOrmError::QueryError("Malformed array literal.".to_string())
+    }
+
+    fn array(&mut self) -> Result<Vec<Element>, FustOrmError> {
+        if self.chars.next() != Some('{') {
+            return Err(self.error());
+        }
+        let mut elements = Vec::new();
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(elements);
+        }
+        loop {
+            elements.push(match self.chars.peek() {
+                Some('{') => Element::Array(self.array()?),
+                Some('"') => Element::Text(self.quoted()?),
+                _ => self.unquoted(),
+            });
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(elements),
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn quoted(&mut self) -> Result<String, FustOrmError> {
+        self.chars.next();
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => text.push(self.chars.next().ok_or_else(|| self.error())?),
+                Some(c) => text.push(c),
+                None => return Err(self.error()),
+            }
+        }
+    }
+
+    fn unquoted(&mut self) -> Element {
+        let mut text = String::new();
+        while let Some(c) = self.chars.next_if(|c| !matches!(c, ',' | '}')) {
+            text.push(c);
+        }
+        let text = text.trim();
+        if text.eq_ignore_ascii_case("NULL") {
+            Element::Null
+        } else {
+            Element::Text(text.to_string())
+        }
+    }
+}
+
+/// Converts the text of a value PostgreSQL printed into `item_type`:
+/// booleans are read from `t`/`f`, dates and datetimes from ISO 8601.
+pub(crate) fn from_text<'py>(
+    py: Python<'py>,
+    text: String,
+    item_type: Option<&Bound<'py, PyType>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    match item_type {
+        Some(bool_type) if bool_type.is(py.get_type::<PyBool>()) => {
+            Ok(PyBool::new(py, matches!(text.as_str(), "t" | "true" | "1"))
+                .to_owned()
+                .into_any())
+        }
+        Some(item_type) if item_type.hasattr("fromisoformat")? => {
+            item_type.call_method1("fromisoformat", (text,))
+        }
+        Some(item_type) => item_type.call1((text,)),
+        None => Ok(PyString::new(py, &text).into_any()),
+    }
+}
+
+/// Converts parsed elements into a list, converting each text with
+/// `from_text()`.
+fn to_list<'py>(
+    py: Python<'py>,
+    elements: Vec<Element>,
+    item_type: Option<&Bound<'py, PyType>>,
+) -> PyResult<Bound<'py, PyList>> {
+    let list = PyList::empty(py);
+    for element in elements {
+        match element {
+            Element::Null => list.append(py.None())?,
+            Element::Array(elements) => list.append(to_list(py, elements, item_type)?)?,
+            Element::Text(text) => list.append(from_text(py, text, item_type)?)?,
+        }
+    }
+    Ok(list)
+}
+
+/// Parses the text of a PostgreSQL array into a list, converting its
+/// elements with `item_type`.
+pub(crate) fn parse_array<'py>(
+    text: &Bound<'py, PyString>,
+    item_type: Option<&Bound<'py, PyType>>,
+) -> PyResult<Bound<'py, PyList>> {
+    let py = text.py();
+    let text = text.to_str()?;
+    // Arrays whose indexes don't start at 1 are prefixed with their bounds,
+    // as in `[0:1]={1,2}`.
+    let text = match text.split_once('=') {
+        Some((_, array)) if text.starts_with('[') => array,
+        _ => text,
+    };
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+    };
+    let elements = parser.array()?;
+    to_list(py, elements, item_type)
+}
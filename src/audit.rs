@@ -0,0 +1,235 @@
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgConnection;
+use sqlx::{AnyConnection, Encode, Executor, IntoArguments, Type};
+
+use crate::database::Backend;
+use crate::dbapi::modifies_data;
+use crate::error::FustOrmError;
+use crate::schema::Dialect;
+use crate::seed::civil_from_days;
+use crate::sqlcomment::{self, Tag};
+
+/// Where the statements modifying data are recorded.
+struct Audit {
+    /// The table of the database the statements run on that they are
+    /// inserted into, in the transaction of the statement.
+    table: Option<String>,
+    callback: Option<Py<PyAny>>,
+    /// Who runs the statements.
+    user: Option<Tag>,
+}
+
+/// The audit log, once `audit_statements()` was called with a table or a
+/// callback.
+static AUDIT: Mutex<Option<Arc<Audit>>> = Mutex::new(None);
+
+/// Records every statement modifying data (`INSERT`, `UPDATE`, `DELETE`,
+/// `REPLACE` and `MERGE`, and the `COPY` of `copy_in()`) that any database
+/// runs, once it succeeded: its SQL, a SHA-256 hash of its parameters, the
+/// tag of the user running it, the time and the number of rows it affected.
+///
+/// `table` names a table the records are inserted into, on the connection
+/// and in the transaction of the statement, begun for the two if it runs
+/// outside of one, so that a rolled back statement leaves no record.
+/// `callback` is called with each record as a dict. If either fails, so
+/// does the statement. `user` is a string, a
+/// `contextvars.ContextVar` or a callable, read in the context that called
+/// the database. `audit_statements()` stops recording.
+#[pyfunction]
+#[pyo3(signature = (*, table = None, callback = None, user = None))]
+pub(crate) fn audit_statements(
+    table: Option<String>,
+    callback: Option<Py<PyAny>>,
+    user: Option<&Bound<PyAny>>,
+) -> PyResult<()> {
+    if let Some(table) = &table
+        && (table.is_empty()
+            || !table
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+    {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "Invalid audit table name '{}'.",
+            table
+        ))
+        .into());
+    }
+    let user = user.map(|user| Tag::new("user", user)).transpose()?;
+    let audit = (table.is_some() || callback.is_some()).then(|| {
+        Arc::new(Audit {
+            table,
+            callback,
+            user,
+        })
+    });
+    let previous = std::mem::replace(&mut *AUDIT.lock().unwrap_or_else(|e| e.into_inner()), audit);
+    drop(previous);
+    Ok(())
+}
+
+/// The current time in UTC, as ISO 8601 with milliseconds.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let time = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        time / 60 % 60,
+        time % 60,
+        now.subsec_millis()
+    )
+}
+
+/// The SHA-256 hash of the parameters of a statement, in hex, which tells
+/// apart runs with different values without storing them.
+fn params_hash(params: &[String]) -> String {
+    let mut hasher = Sha256::new();
+    for param in params {
+        hasher.update((param.len() as u64).to_le_bytes());
+        hasher.update(param.as_bytes());
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether the audit log records `sql` in a table, which a statement run on
+/// a pool then needs a transaction for, to commit with its record.
+pub(crate) fn records_in_table(sql: &str) -> bool {
+    modifies_data(sql)
+        && AUDIT
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .is_some_and(|audit| audit.table.is_some())
+}
+
+/// Records `sql`, which ran on `conn` and affected `rows_affected` rows, in
+/// the audit log, if it modifies data and there is one.
+pub(crate) async fn record(
+    conn: &mut AnyConnection,
+    sql: &str,
+    params: &[String],
+    rows_affected: u64,
+) -> Result<(), FustOrmError> {
+    if !modifies_data(sql) {
+        return Ok(());
+    }
+    let dialect = conn.dialect();
+    match entry(sql, params, rows_affected)? {
+        Some(entry) => entry.insert(conn, dialect).await,
+        None => Ok(()),
+    }
+}
+
+/// Records `sql`, a `COPY ... FROM STDIN` that loaded `rows` rows on `conn`,
+/// in the audit log, if there is one.
+pub(crate) async fn record_copy(
+    conn: &mut PgConnection,
+    sql: &str,
+    rows: u64,
+) -> Result<(), FustOrmError> {
+    match entry(sql, &[], rows)? {
+        Some(entry) => entry.insert(conn, Dialect::Postgres).await,
+        None => Ok(()),
+    }
+}
+
+/// A statement the callback of the audit log was called with, to insert
+/// into its table.
+struct Entry {
+    table: String,
+    executed_at: String,
+    user: Option<String>,
+    statement: String,
+    params_hash: String,
+    rows_affected: i64,
+}
+
+/// Calls the callback of the audit log, if any, with the record of `sql`,
+/// and returns the entry to insert into its table, if it has one.
+fn entry(sql: &str, params: &[String], rows_affected: u64) -> Result<Option<Entry>, FustOrmError> {
+    let Some(audit) = AUDIT.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return Ok(None);
+    };
+    let executed_at = timestamp();
+    let hash = params_hash(params);
+    let user = Python::attach(|py| -> PyResult<Option<String>> {
+        let user = match &audit.user {
+            Some(tag) => sqlcomment::value(py, tag)?,
+            None => None,
+        };
+        if let Some(callback) = &audit.callback {
+            let entry = PyDict::new(py);
+            entry.set_item("executed_at", &executed_at)?;
+            entry.set_item("user", &user)?;
+            entry.set_item("statement", sql)?;
+            entry.set_item("params_hash", &hash)?;
+            entry.set_item("rows_affected", rows_affected)?;
+            callback.call1(py, (entry,))?;
+        }
+        Ok(user)
+    })
+    .map_err(|e| FustOrmError::QueryError(format!("Can't audit the statement: {}", e)))?;
+    Ok(audit.table.clone().map(|table| Entry {
+        table,
+        executed_at,
+        user,
+        statement: sql.to_string(),
+        params_hash: hash,
+        rows_affected: rows_affected as i64,
+    }))
+}
+
+impl Entry {
+    /// Inserts the entry on `executor`, a connection of a database of
+    /// `dialect`.
+    async fn insert<'c, DB, E>(self, executor: E, dialect: Dialect) -> Result<(), FustOrmError>
+    where
+        DB: sqlx::Database,
+        E: Executor<'c, Database = DB>,
+        for<'q> DB::Arguments<'q>: IntoArguments<'q, DB>,
+        for<'q> String: Encode<'q, DB> + Type<DB>,
+        for<'q> Option<String>: Encode<'q, DB> + Type<DB>,
+        for<'q> i64: Encode<'q, DB> + Type<DB>,
+    {
+        let placeholders = match dialect {
+            Dialect::Postgres => "$1, $2, $3, $4, $5",
+            _ => "?, ?, ?, ?, ?",
+        };
+        let insert = format!(
+            "INSERT INTO {} (executed_at, user_tag, statement, params_hash, rows_affected) \
+             VALUES ({})",
+            self.table, placeholders
+        );
+        sqlx::query::<DB>(&insert)
+            .bind(self.executed_at)
+            .bind(self.user)
+            .bind(self.statement)
+            .bind(self.params_hash)
+            .bind(self.rows_affected)
+            .execute(executor)
+            .await
+            .map_err(|e| {
+                FustOrmError::QueryError(format!(
+                    "Can't record the statement in the audit table '{}': {}",
+                    self.table, e
+                ))
+            })?;
+        Ok(())
+    }
+}
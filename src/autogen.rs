@@ -0,0 +1,870 @@
+use crate::alter::{RebuildOp, SqliteTable, sqlite_adds_natively};
+use crate::database::{execute_statement, fetch_rows};
+use crate::error::FustOrmError;
+use crate::introspect;
+use crate::model::Model;
+use crate::relations::ManyToMany;
+use crate::schema::{CheckConstraint, Dialect, Table, index_name};
+use crate::trigger::{Trigger, drop_trigger_sql};
+use crate::view::{View, create_view_sql};
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::{AnyPool, Row};
+
+/// A column a model declares, rendered for `ALTER TABLE ... ADD COLUMN`.
+struct DesiredColumn {
+    name: String,
+    definition: String,
+    /// Whether SQLite can add it without rebuilding the table.
+    adds_natively: bool,
+}
+
+/// The table the models mapped onto it declare.
+struct DesiredTable {
+    name: String,
+    create_sql: String,
+    columns: Vec<DesiredColumn>,
+    /// The tables its foreign keys reference.
+    references: Vec<String>,
+    /// Columns of `Meta.indexes`.
+    indexes: Vec<Vec<String>>,
+    /// Columns of `Meta.unique_together`.
+    unique: Vec<Vec<String>>,
+    /// The triggers of `Meta.triggers`, `None` if no model declares any.
+    triggers: Option<Vec<DesiredTrigger>>,
+    /// The check constraints of `Meta.checks`, `None` if no model declares any.
+    checks: Option<Vec<CheckConstraint>>,
+}
+
+/// A trigger a model declares, rendered for its table.
+struct DesiredTrigger {
+    name: String,
+    create_sql: Vec<String>,
+    drop_sql: Vec<String>,
+    /// What the trigger is compared by; see `introspect::TriggerInfo`.
+    definition: String,
+}
+
+impl DesiredTrigger {
+    fn new(trigger: &Trigger, table: &str, dialect: Dialect) -> PyResult<Self> {
+        let name = trigger.name_on(table);
+        Ok(DesiredTrigger {
+            create_sql: trigger.create_sql(table, dialect)?,
+            drop_sql: drop_trigger_sql(dialect, &name, table, Some(&format!("{}_fn", name))),
+            definition: trigger.definition(table, dialect)?,
+            name,
+        })
+    }
+}
+
+/// A table as its models declare it.
+struct DeclaredTable {
+    table: Table,
+    /// Columns of `Meta.indexes`.
+    indexes: Vec<Vec<String>>,
+    /// `Meta.triggers`, `None` if no model declares any.
+    triggers: Option<Vec<Trigger>>,
+    /// Whether a model declares `Meta.checks`.
+    declares_checks: bool,
+}
+
+/// The schema declared by a set of models, rendered for one dialect.
+pub(crate) struct DesiredSchema {
+    tables: Vec<DesiredTable>,
+    /// The through tables of `ManyToMany` relations, with their `CREATE TABLE`.
+    through: Vec<(String, String)>,
+    /// The views of models with a `Meta.view`.
+    views: Vec<View>,
+}
+
+/// The statements of a generated migration.
+#[derive(Debug, Default)]
+pub(crate) struct MigrationPlan {
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+}
+
+impl MigrationPlan {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.up.is_empty()
+    }
+}
+
+/// The models to compare with the database: `models`, or every subclass of
+/// `Model` defined so far.
+pub(crate) fn models_to_compare<'py>(
+    py: Python<'py>,
+    models: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Vec<Bound<'py, PyType>>> {
+    if let Some(models) = models {
+        return models
+            .try_iter()?
+            .map(|model| {
+                let model = model?;
+                match model.downcast::<PyType>() {
+                    Ok(cls) if cls.is_subclass_of::<Model>()? => Ok(cls.clone()),
+                    _ => Err(FustOrmError::BuildError(format!(
+                        "{} is not a Model class.",
+                        model.repr()?
+                    ))
+                    .into()),
+                }
+            })
+            .collect();
+    }
+    let mut found: Vec<Bound<PyType>> = Vec::new();
+    let mut pending = vec![py.get_type::<Model>()];
+    while let Some(cls) = pending.pop() {
+        for subclass in cls.call_method0("__subclasses__")?.try_iter()? {
+            let subclass = subclass?.downcast_into::<PyType>()?;
+            if !found.iter().any(|c| c.is(&subclass)) {
+                found.push(subclass.clone());
+                pending.push(subclass);
+            }
+        }
+    }
+    Ok(found)
+}
+
+impl DesiredSchema {
+    /// Reads the tables and views of `models`. Models sharing a table
+    /// (single-table inheritance) are merged; columns only some of them have
+    /// are nullable.
+    pub(crate) fn from_models(
+        py: Python,
+        models: &[Bound<PyType>],
+        dialect: Dialect,
+    ) -> PyResult<Self> {
+        let mut tables: Vec<DeclaredTable> = Vec::new();
+        let mut through: Vec<(String, String)> = Vec::new();
+        let mut views: Vec<View> = Vec::new();
+        for model in models {
+            if !model.getattr(pyo3::intern!(py, "__view__"))?.is_none() {
+                let view = View::from_model(model)?;
+                if !views.iter().any(|v| v.name == view.name) {
+                    views.push(view);
+                }
+                continue;
+            }
+            let table = Table::from_model(model)?;
+            let indexes = model
+                .getattr(pyo3::intern!(py, "__indexes__"))?
+                .extract::<Vec<Vec<String>>>()?;
+            let triggers = model
+                .getattr(pyo3::intern!(py, "__triggers__"))?
+                .extract::<Option<Vec<Trigger>>>()?;
+            let declares_checks = !model.getattr(pyo3::intern!(py, "__checks__"))?.is_none();
+            for value in model
+                .getattr(pyo3::intern!(py, "__dict__"))?
+                .call_method0("values")?
+                .try_iter()?
+            {
+                if let Ok(relation) = value?.extract::<PyRef<ManyToMany>>() {
+                    let name = relation.through_table(py)?;
+                    if !through.iter().any(|(t, _)| *t == name) {
+                        through.push((name, relation.through_table_sql(py)?));
+                    }
+                }
+            }
+            match tables.iter_mut().find(|t| t.table.name == table.name) {
+                Some(declared) => {
+                    let existing = &mut declared.table;
+                    for column in existing.columns.iter_mut() {
+                        if !column.primary_key
+                            && !table.columns.iter().any(|c| c.name == column.name)
+                        {
+                            column.nullable = true;
+                        }
+                    }
+                    for mut column in table.columns {
+                        if !existing.columns.iter().any(|c| c.name == column.name) {
+                            column.nullable = !column.primary_key;
+                            existing.columns.push(column);
+                        }
+                    }
+                    for group in table.unique {
+                        if !existing.unique.contains(&group) {
+                            existing.unique.push(group);
+                        }
+                    }
+                    for check in table.checks {
+                        if !existing.checks.iter().any(|c| c.name == check.name) {
+                            existing.checks.push(check);
+                        }
+                    }
+                    declared.declares_checks |= declares_checks;
+                    for group in indexes {
+                        if !declared.indexes.contains(&group) {
+                            declared.indexes.push(group);
+                        }
+                    }
+                    if let Some(triggers) = triggers {
+                        let existing_triggers = declared.triggers.get_or_insert_default();
+                        for trigger in triggers {
+                            let name = trigger.name_on(&table.name);
+                            if !existing_triggers
+                                .iter()
+                                .any(|t| t.name_on(&table.name) == name)
+                            {
+                                existing_triggers.push(trigger);
+                            }
+                        }
+                    }
+                }
+                None => tables.push(DeclaredTable {
+                    table,
+                    indexes,
+                    triggers,
+                    declares_checks,
+                }),
+            }
+        }
+        let tables = tables
+            .into_iter()
+            .map(|declared| {
+                let DeclaredTable {
+                    table,
+                    indexes,
+                    triggers,
+                    declares_checks,
+                } = declared;
+                Ok(DesiredTable {
+                    triggers: triggers
+                        .map(|triggers| {
+                            triggers
+                                .iter()
+                                .map(|t| DesiredTrigger::new(t, &table.name, dialect))
+                                .collect::<PyResult<Vec<_>>>()
+                        })
+                        .transpose()?,
+                    create_sql: table.create_sql(py, dialect, false)?,
+                    columns: table
+                        .columns
+                        .iter()
+                        .map(|column| {
+                            Ok(DesiredColumn {
+                                name: column.name.clone(),
+                                definition: column.to_sql(py, dialect, false)?,
+                                adds_natively: sqlite_adds_natively(column),
+                            })
+                        })
+                        .collect::<PyResult<_>>()?,
+                    references: table
+                        .columns
+                        .iter()
+                        .filter_map(|c| c.references.as_ref().map(|(t, _)| t.clone()))
+                        .filter(|t| *t != table.name)
+                        .collect(),
+                    checks: declares_checks.then(|| table.checks.clone()),
+                    name: table.name,
+                    indexes,
+                    unique: table.unique.into_iter().map(|u| u.columns).collect(),
+                })
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(DesiredSchema {
+            tables,
+            through,
+            views,
+        })
+    }
+}
+
+/// Orders the tables so that the tables they reference come first, where
+/// the references allow it.
+fn creation_order(tables: Vec<&DesiredTable>) -> Vec<&DesiredTable> {
+    let mut ordered: Vec<&DesiredTable> = Vec::new();
+    let mut pending = tables;
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|table| {
+            table
+                .references
+                .iter()
+                .all(|r| !pending.iter().any(|t| t.name == *r))
+        });
+        // A reference cycle: create the rest in declaration order.
+        ordered.push(pending.remove(ready.unwrap_or(0)));
+    }
+    ordered
+}
+
+/// Whether one of `indexes` covers exactly `columns`, and is unique if `unique`.
+fn has_index(indexes: &[introspect::IndexInfo], columns: &[String], unique: bool) -> bool {
+    indexes.iter().any(|index| {
+        (index.unique || !unique)
+            && index.columns.len() == columns.len()
+            && index
+                .columns
+                .iter()
+                .zip(columns)
+                .all(|(c, g)| c.as_ref() == Some(g))
+    })
+}
+
+fn drop_index_sql(dialect: Dialect, name: &str, table: &str) -> String {
+    match dialect {
+        Dialect::Mysql => format!("DROP INDEX {} ON {}", name, table),
+        _ => format!("DROP INDEX {}", name),
+    }
+}
+
+/// The check constraints `table` declares and the database lacks, and the
+/// named ones it has and the table no longer declares. Checks are compared by
+/// name, and left alone on tables whose models declare no `Meta.checks`.
+async fn check_changes(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &DesiredTable,
+) -> Result<(Vec<CheckConstraint>, Vec<CheckConstraint>), FustOrmError> {
+    let Some(checks) = &table.checks else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    let live: Vec<CheckConstraint> = introspect::constraints(pool, dialect, &table.name)
+        .await?
+        .into_iter()
+        .filter(|c| c.kind == "check")
+        .filter_map(|c| {
+            Some(CheckConstraint {
+                name: c.name?,
+                expression: c.expression.unwrap_or_default(),
+            })
+        })
+        .collect();
+    let added = checks
+        .iter()
+        .filter(|c| !live.iter().any(|l| l.name == c.name))
+        .cloned()
+        .collect();
+    let dropped = live
+        .into_iter()
+        .filter(|l| !checks.iter().any(|c| c.name == l.name))
+        .collect();
+    Ok((added, dropped))
+}
+
+/// Plans adding the columns and check constraints `table` declares and the
+/// database lacks, and dropping the ones it no longer declares. Returns the
+/// statements making and reverting the change, if there is one.
+async fn plan_table(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &DesiredTable,
+) -> Result<Option<(Vec<String>, Vec<String>)>, FustOrmError> {
+    let (added_checks, dropped_checks) = check_changes(pool, dialect, table).await?;
+    let live = introspect::columns(pool, dialect, &table.name).await?;
+    let added: Vec<&DesiredColumn> = table
+        .columns
+        .iter()
+        .filter(|c| !live.iter().any(|l| l.name == c.name))
+        .collect();
+    let dropped: Vec<&introspect::ColumnInfo> = live
+        .iter()
+        .filter(|l| !table.columns.iter().any(|c| c.name == l.name))
+        .collect();
+    if added.is_empty()
+        && dropped.is_empty()
+        && added_checks.is_empty()
+        && dropped_checks.is_empty()
+    {
+        return Ok(None);
+    }
+    let mut up = Vec::new();
+    let mut down = Vec::new();
+    if dialect != Dialect::Sqlite {
+        for column in &added {
+            up.push(format!(
+                "ALTER TABLE {} ADD COLUMN {}",
+                table.name, column.definition
+            ));
+            down.push(format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                table.name, column.name
+            ));
+        }
+        for column in &dropped {
+            up.push(format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                table.name, column.name
+            ));
+            down.push(format!(
+                "ALTER TABLE {} ADD COLUMN {}",
+                table.name,
+                column.definition_sql()
+            ));
+        }
+        for check in &added_checks {
+            up.push(format!("ALTER TABLE {} ADD {}", table.name, check.to_sql()));
+            down.push(format!(
+                "ALTER TABLE {} DROP CONSTRAINT {}",
+                table.name, check.name
+            ));
+        }
+        for check in &dropped_checks {
+            up.push(format!(
+                "ALTER TABLE {} DROP CONSTRAINT {}",
+                table.name, check.name
+            ));
+            down.push(format!("ALTER TABLE {} ADD {}", table.name, check.to_sql()));
+        }
+        down.reverse();
+        // The database drops the indexes on dropped columns; recreate the
+        // ones it can on the way down.
+        if !dropped.is_empty() {
+            for index in introspect::indexes(pool, dialect, &table.name).await? {
+                let columns: Option<Vec<&String>> =
+                    index.columns.iter().map(Option::as_ref).collect();
+                match columns {
+                    Some(columns)
+                        if !index.partial
+                            && columns
+                                .iter()
+                                .any(|c| dropped.iter().any(|d| d.name == **c)) =>
+                    {
+                        down.push(format!(
+                            "CREATE {}INDEX {} ON {} ({})",
+                            if index.unique { "UNIQUE " } else { "" },
+                            index.name,
+                            table.name,
+                            columns
+                                .iter()
+                                .map(|c| c.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        return Ok(Some((up, down)));
+    }
+    if dropped.is_empty()
+        && added_checks.is_empty()
+        && dropped_checks.is_empty()
+        && added.iter().all(|c| c.adds_natively)
+    {
+        for column in &added {
+            up.push(format!(
+                "ALTER TABLE {} ADD COLUMN {}",
+                table.name, column.definition
+            ));
+            down.push(format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                table.name, column.name
+            ));
+        }
+        down.reverse();
+        return Ok(Some((up, down)));
+    }
+
+    let mut conn = pool.acquire().await.map_err(FustOrmError::from)?;
+    let current = SqliteTable::load(&mut conn, &table.name).await?;
+    let mut changed = current.clone();
+    for column in &added {
+        changed.apply(&RebuildOp::Add {
+            name: column.name.clone(),
+            definition: column.definition.clone(),
+        })?;
+    }
+    // Indexes on dropped columns go with them, and come back on the way down.
+    let mut dropped_indexes = Vec::new();
+    for column in &dropped {
+        dropped_indexes.extend(changed.take_indexes_on(&column.name));
+        changed.apply(&RebuildOp::Drop(column.name.clone()))?;
+    }
+    for check in &dropped_checks {
+        changed.apply(&RebuildOp::DropConstraint(check.name.clone()))?;
+    }
+    for check in &added_checks {
+        changed.apply(&RebuildOp::AddCheck {
+            name: check.name.clone(),
+            definition: check.to_sql(),
+        })?;
+    }
+    up.extend(changed.rebuild_sql());
+
+    let mut reverted = changed.settled();
+    for column in &added {
+        reverted.apply(&RebuildOp::Drop(column.name.clone()))?;
+    }
+    for column in &dropped {
+        if let Some(restore) = current.restore_column(&column.name) {
+            reverted.apply(&restore)?;
+        }
+    }
+    for check in &added_checks {
+        reverted.apply(&RebuildOp::DropConstraint(check.name.clone()))?;
+    }
+    for check in &dropped_checks {
+        reverted.apply(&RebuildOp::AddCheck {
+            name: check.name.clone(),
+            definition: check.to_sql(),
+        })?;
+    }
+    down.extend(reverted.rebuild_sql());
+    down.extend(dropped_indexes);
+    Ok(Some((up, down)))
+}
+
+/// Collapses the whitespace of a statement, for comparing definitions.
+fn squeeze(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The name of the scratch view a declared view's `SELECT` is normalized
+/// through.
+const SCRATCH_VIEW: &str = "_fust_view_check";
+
+/// Whether the live view is defined by `view`'s query.
+///
+/// SQLite keeps the `SELECT` as written, so the two are compared up to
+/// whitespace. PostgreSQL and MySQL normalize it, so the query is normalized
+/// the same way by creating it as a scratch view first (in a transaction that
+/// is rolled back on PostgreSQL).
+async fn view_is_current(
+    pool: &AnyPool,
+    dialect: Dialect,
+    live: &introspect::ViewInfo,
+    view: &View,
+) -> Result<bool, FustOrmError> {
+    let map_db_err = FustOrmError::from;
+    let definition = match dialect {
+        Dialect::Sqlite => view.query.clone(),
+        Dialect::Postgres => {
+            let mut tx = pool.begin().await.map_err(map_db_err)?;
+            let create = format!("CREATE TEMPORARY VIEW {} AS {}", SCRATCH_VIEW, view.query);
+            execute_statement(&mut *tx, &create, Vec::new()).await?;
+            let sql = "SELECT pg_get_viewdef(CAST($1 AS regclass)) AS definition";
+            let rows = fetch_rows(&mut *tx, sql, vec![SCRATCH_VIEW.to_string()]).await?;
+            tx.rollback().await.map_err(map_db_err)?;
+            rows.first()
+                .map(|row| row.try_get::<String, _>("definition"))
+                .transpose()
+                .map_err(map_db_err)?
+                .unwrap_or_default()
+        }
+        Dialect::Mysql => {
+            let create = format!("CREATE OR REPLACE VIEW {} AS {}", SCRATCH_VIEW, view.query);
+            execute_statement(pool, &create, Vec::new()).await?;
+            let definition = introspect::views(pool, dialect)
+                .await?
+                .into_iter()
+                .find(|v| v.name == SCRATCH_VIEW)
+                .map(|v| v.definition)
+                .unwrap_or_default();
+            execute_statement(pool, &format!("DROP VIEW {}", SCRATCH_VIEW), Vec::new()).await?;
+            definition
+        }
+    };
+    Ok(squeeze(&definition) == squeeze(&live.definition))
+}
+
+/// The statements changing the declared views. The views being changed are
+/// dropped before the tables change and created after, and the other way
+/// round on the way down.
+#[derive(Default)]
+struct ViewChanges {
+    up_before: Vec<String>,
+    up_after: Vec<String>,
+    down_before: Vec<String>,
+    down_after: Vec<String>,
+}
+
+/// Plans creating the missing views and replacing the outdated ones. Views
+/// may depend on the columns of the tables they select from, so when
+/// `tables_altered`, the existing ones are recreated around the changes.
+async fn plan_views(
+    pool: &AnyPool,
+    dialect: Dialect,
+    views: &[View],
+    tables_altered: bool,
+) -> Result<ViewChanges, FustOrmError> {
+    let live_views = introspect::views(pool, dialect).await?;
+    let mut changes = ViewChanges::default();
+    for view in views {
+        let Some(live) = live_views.iter().find(|v| v.name == view.name) else {
+            changes.up_after.extend(view.create_sql(dialect, false));
+            changes.down_before.push(format!("DROP VIEW {}", view.name));
+            continue;
+        };
+        if tables_altered {
+            changes.up_before.push(format!("DROP VIEW {}", view.name));
+            changes.up_after.extend(view.create_sql(dialect, false));
+            changes.down_before.push(format!("DROP VIEW {}", view.name));
+            changes.down_after.extend(create_view_sql(
+                &view.name,
+                &live.definition,
+                dialect,
+                false,
+            ));
+        } else if !view_is_current(pool, dialect, live, view).await? {
+            changes.up_after.extend(view.create_sql(dialect, true));
+            changes
+                .down_after
+                .extend(create_view_sql(&view.name, &live.definition, dialect, true));
+        }
+    }
+    // Views selecting from other views are declared after them, so they are
+    // dropped first.
+    changes.up_before.reverse();
+    changes.down_before.reverse();
+    Ok(changes)
+}
+
+/// Plans creating the triggers `table` declares and the database lacks,
+/// recreating the changed ones and dropping the ones it no longer declares.
+/// Tables whose models declare no `Meta.triggers` are left alone.
+async fn plan_triggers(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &DesiredTable,
+    up: &mut Vec<String>,
+    down: &mut Vec<Vec<String>>,
+) -> Result<(), FustOrmError> {
+    let Some(triggers) = &table.triggers else {
+        return Ok(());
+    };
+    let live = introspect::triggers(pool, dialect, &table.name).await?;
+    for trigger in triggers {
+        match live.iter().find(|t| t.name == trigger.name) {
+            None => {
+                up.extend(trigger.create_sql.iter().cloned());
+                down.push(trigger.drop_sql.clone());
+            }
+            Some(existing) if squeeze(&existing.definition) != squeeze(&trigger.definition) => {
+                up.extend(drop_trigger_sql(
+                    dialect,
+                    &existing.name,
+                    &table.name,
+                    existing.function.as_deref(),
+                ));
+                up.extend(trigger.create_sql.iter().cloned());
+                down.push([trigger.drop_sql.clone(), existing.create_sql.clone()].concat());
+            }
+            Some(_) => {}
+        }
+    }
+    for existing in &live {
+        if !triggers.iter().any(|t| t.name == existing.name) {
+            up.extend(drop_trigger_sql(
+                dialect,
+                &existing.name,
+                &table.name,
+                existing.function.as_deref(),
+            ));
+            down.push(existing.create_sql.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Compares the schema with the database and plans the statements that bring
+/// the database in line (`up`) and back (`down`).
+///
+/// Missing tables, columns, `Meta` indexes, unique constraints, check
+/// constraints and triggers, `ManyToMany` through tables and views are
+/// created, and outdated views and triggers replaced; columns, check
+/// constraints and triggers the models no longer declare are dropped. Column
+/// types and check conditions aren't compared, and tables and views without a
+/// model are left alone. On SQLite, changes it can't make with `ALTER TABLE`
+/// are planned as table rebuilds from the current schema.
+pub(crate) async fn plan(
+    pool: &AnyPool,
+    dialect: Dialect,
+    schema: &DesiredSchema,
+) -> Result<MigrationPlan, FustOrmError> {
+    let live_tables = introspect::tables(pool, dialect).await?;
+    let mut up: Vec<String> = Vec::new();
+    // Each change's down statements; they run in reverse order.
+    let mut down: Vec<Vec<String>> = Vec::new();
+
+    let (new, existing): (Vec<&DesiredTable>, Vec<&DesiredTable>) = schema
+        .tables
+        .iter()
+        .partition(|t| !live_tables.contains(&t.name));
+    for table in creation_order(new) {
+        up.push(table.create_sql.clone());
+        down.push(vec![format!("DROP TABLE {}", table.name)]);
+        for group in &table.indexes {
+            let name = index_name(&table.name, group, false);
+            up.push(format!(
+                "CREATE INDEX {} ON {} ({})",
+                name,
+                table.name,
+                group.join(", ")
+            ));
+        }
+        // Dropping the table drops its triggers, but not their functions.
+        for trigger in table.triggers.iter().flatten() {
+            up.extend(trigger.create_sql.iter().cloned());
+            if dialect == Dialect::Postgres {
+                down.push(trigger.drop_sql.clone());
+            }
+        }
+    }
+
+    let mut tables_altered = false;
+    for table in existing {
+        if let Some((forward, revert)) = plan_table(pool, dialect, table).await? {
+            up.extend(forward);
+            down.push(revert);
+            tables_altered = true;
+        }
+
+        let indexes = introspect::indexes(pool, dialect, &table.name).await?;
+        let declared = table
+            .indexes
+            .iter()
+            .map(|g| (g, false))
+            .chain(table.unique.iter().map(|g| (g, true)));
+        for (group, unique) in declared {
+            if has_index(&indexes, group, unique) {
+                continue;
+            }
+            let name = index_name(&table.name, group, unique);
+            up.push(format!(
+                "CREATE {}INDEX {} ON {} ({})",
+                if unique { "UNIQUE " } else { "" },
+                name,
+                table.name,
+                group.join(", ")
+            ));
+            down.push(vec![drop_index_sql(dialect, &name, &table.name)]);
+        }
+        plan_triggers(pool, dialect, table, &mut up, &mut down).await?;
+    }
+
+    for (name, sql) in &schema.through {
+        if !live_tables.contains(name) {
+            up.push(sql.clone());
+            down.push(vec![format!("DROP TABLE {}", name)]);
+        }
+    }
+
+    let views = plan_views(pool, dialect, &schema.views, tables_altered).await?;
+    Ok(MigrationPlan {
+        up: [views.up_before, up, views.up_after].concat(),
+        down: [
+            views.down_before,
+            down.into_iter().rev().flatten().collect(),
+            views.down_after,
+        ]
+        .concat(),
+    })
+}
+
+/// Lists what the database lacks of the schema: tables, columns, `Meta`
+/// indexes, unique and check constraints and triggers, `ManyToMany` through
+/// tables and views (or the current definition of the triggers and views).
+/// Extra tables, columns, constraints, triggers and views aren't reported, and
+/// column types aren't compared.
+pub(crate) async fn missing(
+    pool: &AnyPool,
+    dialect: Dialect,
+    schema: &DesiredSchema,
+) -> Result<Vec<String>, FustOrmError> {
+    let live_tables = introspect::tables(pool, dialect).await?;
+    let mut problems = Vec::new();
+    for table in &schema.tables {
+        if !live_tables.contains(&table.name) {
+            problems.push(format!("missing table '{}'", table.name));
+            continue;
+        }
+        let live = introspect::columns(pool, dialect, &table.name).await?;
+        for column in &table.columns {
+            if !live.iter().any(|l| l.name == column.name) {
+                problems.push(format!(
+                    "table '{}' is missing column '{}'",
+                    table.name, column.name
+                ));
+            }
+        }
+        let indexes = introspect::indexes(pool, dialect, &table.name).await?;
+        for group in &table.indexes {
+            if !has_index(&indexes, group, false) {
+                problems.push(format!(
+                    "table '{}' is missing an index on ({})",
+                    table.name,
+                    group.join(", ")
+                ));
+            }
+        }
+        for group in &table.unique {
+            if !has_index(&indexes, group, true) {
+                problems.push(format!(
+                    "table '{}' is missing a unique constraint on ({})",
+                    table.name,
+                    group.join(", ")
+                ));
+            }
+        }
+        if table.checks.is_some() {
+            for check in check_changes(pool, dialect, table).await?.0 {
+                problems.push(format!(
+                    "table '{}' is missing check constraint '{}'",
+                    table.name, check.name
+                ));
+            }
+        }
+        if let Some(triggers) = &table.triggers {
+            let live = introspect::triggers(pool, dialect, &table.name).await?;
+            for trigger in triggers {
+                match live.iter().find(|t| t.name == trigger.name) {
+                    None => problems.push(format!(
+                        "table '{}' is missing trigger '{}'",
+                        table.name, trigger.name
+                    )),
+                    Some(existing)
+                        if squeeze(&existing.definition) != squeeze(&trigger.definition) =>
+                    {
+                        problems.push(format!(
+                            "trigger '{}' of '{}' is out of date",
+                            trigger.name, table.name
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+    for (name, _) in &schema.through {
+        if !live_tables.contains(name) {
+            problems.push(format!("missing through table '{}'", name));
+        }
+    }
+    let live_views = introspect::views(pool, dialect).await?;
+    for view in &schema.views {
+        match live_views.iter().find(|v| v.name == view.name) {
+            None => problems.push(format!("missing view '{}'", view.name)),
+            Some(live) if !view_is_current(pool, dialect, live, view).await? => {
+                problems.push(format!("view '{}' is out of date", view.name));
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(problems)
+}
+
+/// Compares `models` (all models if `None`) with the database. Resolves to
+/// `None`, or fails listing everything the database lacks.
+pub(crate) fn validate<'py>(
+    py: Python<'py>,
+    pool: AnyPool,
+    dialect: Dialect,
+    models: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let models = models_to_compare(py, models)?;
+    let schema = DesiredSchema::from_models(py, &models, dialect)?;
+    future_into_py(py, async move {
+        let problems = missing(&pool, dialect, &schema).await?;
+        if !problems.is_empty() {
+            return Err(FustOrmError::SchemaMismatch(format!(
+                "the database doesn't match the models:\n  - {}",
+                problems.join("\n  - ")
+            ))
+            .into());
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,392 @@
+use crate::context;
+use crate::database::{Database, execute_statement, fetch_rows, row_to_dict};
+use crate::error::FustOrmError;
+use crate::model::{Model, ModelInfo, column_param};
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyType};
+use pyo3_async_runtimes::tokio::future_into_py;
+
+/// Upper bound for the parameters bound to a single statement when no
+/// `batch_size` is given; SQLite builds before 3.32 reject more than 999.
+const MAX_PARAMS_PER_STATEMENT: usize = 999;
+
+/// The values of one row, with `None` rendered as `NULL`.
+type RowValues = Vec<Option<String>>;
+
+/// One multi-row statement and the objects (by index) it writes.
+struct Batch {
+    sql: String,
+    params: Vec<String>,
+    objects: Vec<usize>,
+    /// Whether the statement ends in `RETURNING <pk>`.
+    returning: bool,
+}
+
+pub(crate) fn rows_per_statement(
+    requested: Option<usize>,
+    params_per_row: usize,
+) -> PyResult<usize> {
+    match requested {
+        Some(0) => Err(FustOrmError::InvalidQueryArgument(
+            "batch_size must be a positive integer.".to_string(),
+        )
+        .into()),
+        Some(size) => Ok(size),
+        None => Ok((MAX_PARAMS_PER_STATEMENT / params_per_row.max(1)).max(1)),
+    }
+}
+
+/// Checks that every object is an instance of `cls` and returns them.
+fn extract_objects<'py>(
+    cls: &Bound<'py, PyType>,
+    objects: &Bound<'py, PyAny>,
+) -> PyResult<Vec<Bound<'py, Model>>> {
+    objects
+        .try_iter()?
+        .map(|object| {
+            let object = object?;
+            if !object.is_instance(cls)? {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "Expected instances of '{}', got {}.",
+                    cls.name()?,
+                    object.get_type().name()?
+                ))
+                .into());
+            }
+            Ok(object.downcast_into::<Model>()?)
+        })
+        .collect()
+}
+
+/// Inserts `objects` with multi-row `INSERT` statements inside one transaction.
+///
+/// Objects without a value for a simple primary key get the generated key
+/// assigned (not on MySQL, which doesn't support `RETURNING`). Objects with and
+/// without a key are inserted in separate statements, since a multi-row
+/// `INSERT` lists the same columns for every row.
+pub(crate) fn bulk_create<'py>(
+    cls: &Bound<'py, PyType>,
+    db: &Database,
+    objects: &Bound<'py, PyAny>,
+    batch_size: Option<usize>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = cls.py();
+    let info = ModelInfo::from_class(cls)?;
+    info.check_writable()?;
+    let single_pk = info.primary_key()?.len() == 1;
+    let objects = extract_objects(cls, objects)?;
+
+    // Rows are grouped by whether the primary key is generated, keeping the
+    // order of `objects` within each group.
+    let mut groups: [(Vec<usize>, Vec<RowValues>); 2] = Default::default();
+    let mut versioned = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        if object.borrow().persisted {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "Object {} of '{}' is already saved; bulk_create() only inserts new objects.",
+                index, info.table_name
+            ))
+            .into());
+        }
+        let mut generated = false;
+        let mut row = Vec::with_capacity(info.columns.len());
+        for column in info.columns.iter().filter(|c| c.generated.is_none()) {
+            let value = object.getattr(&column.column_name)?;
+            if column.primary_key && single_pk && value.is_none() {
+                generated = true;
+                continue;
+            }
+            if column.version && value.is_none() {
+                versioned.push((index, column.column_name.clone()));
+                row.push(Some("1".to_string()));
+                continue;
+            }
+            row.push(column_param(py, &value, column)?);
+        }
+        let group = &mut groups[usize::from(generated)];
+        group.0.push(index);
+        group.1.push(row);
+    }
+
+    let pk_column = info.primary_key()?[0].column_name.clone();
+    let mut batches = Vec::new();
+    for (generated, (indices, rows)) in groups.into_iter().enumerate() {
+        let generated = generated == 1;
+        let names: Vec<&str> = info
+            .columns
+            .iter()
+            .filter(|c| !(generated && c.primary_key) && c.generated.is_none())
+            .map(|c| c.column_name.as_str())
+            .collect();
+        let returning = generated && !db.is_mysql();
+        // `DEFAULT VALUES` can't be repeated, so column-less rows go one per statement.
+        let size = if names.is_empty() {
+            1
+        } else {
+            rows_per_statement(batch_size, names.len())?
+        };
+        for (chunk_indices, chunk_rows) in indices.chunks(size).zip(rows.chunks(size)) {
+            let mut params = Vec::new();
+            let mut sql = if names.is_empty() {
+                format!("INSERT INTO {} DEFAULT VALUES", info.table_name)
+            } else {
+                let values = chunk_rows
+                    .iter()
+                    .map(|row| {
+                        let placeholders = row
+                            .iter()
+                            .map(|value| match value {
+                                Some(param) => {
+                                    params.push(param.clone());
+                                    "?"
+                                }
+                                None => "NULL",
+                            })
+                            .collect::<Vec<_>>();
+                        format!("({})", placeholders.join(", "))
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    "INSERT INTO {} ({}) VALUES {}",
+                    info.table_name,
+                    names.join(", "),
+                    values.join(", ")
+                )
+            };
+            if returning {
+                sql.push_str(&format!(" RETURNING {}", pk_column));
+            }
+            batches.push(Batch {
+                sql,
+                params,
+                objects: chunk_indices.to_vec(),
+                returning,
+            });
+        }
+    }
+    debug!(
+        "Inserting {} objects into '{}' in {} statements",
+        objects.len(),
+        info.table_name,
+        batches.len()
+    );
+
+    let db = context::resolve(py, Some(db))?;
+    let objects: Vec<Py<Model>> = objects.into_iter().map(Bound::unbind).collect();
+
+    future_into_py(py, async move {
+        let map_db_err = FustOrmError::from;
+        let mut tx = db.begin_write().await?;
+        let mut generated_keys = Vec::new();
+        for batch in batches {
+            if batch.returning {
+                let rows = fetch_rows(&mut *tx, &batch.sql, batch.params).await?;
+                generated_keys.push((batch.objects, rows));
+            } else {
+                execute_statement(&mut *tx, &batch.sql, batch.params).await?;
+            }
+        }
+        tx.commit().await.map_err(map_db_err)?;
+
+        Python::attach(|py| -> PyResult<Py<PyList>> {
+            for (indices, rows) in generated_keys {
+                for (index, row) in indices.into_iter().zip(rows.iter()) {
+                    let value = row_to_dict(py, row)?.get_item(&pk_column)?;
+                    objects[index].bind(py).setattr(&pk_column, value)?;
+                }
+            }
+            for (index, column) in versioned {
+                objects[index].bind(py).setattr(column, 1)?;
+            }
+            for object in &objects {
+                object.bind(py).borrow_mut().persisted = true;
+            }
+            Ok(PyList::new(py, objects)?.unbind())
+        })
+    })
+}
+
+/// Updates `fields` of saved `objects` with multi-row `UPDATE ... SET field =
+/// CASE ... END` statements inside one transaction.
+///
+/// For models with a version column, each row only matches the version its
+/// object was loaded with; if any row doesn't, the transaction is rolled back
+/// and `StaleDataError` is raised. Resolves to the number of updated rows.
+pub(crate) fn bulk_update<'py>(
+    cls: &Bound<'py, PyType>,
+    db: &Database,
+    objects: &Bound<'py, PyAny>,
+    fields: Vec<String>,
+    batch_size: Option<usize>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = cls.py();
+    let info = ModelInfo::from_class(cls)?;
+    info.check_writable()?;
+    let pk_len = info.primary_key()?.len();
+    let version = info.version_column().map(|c| c.column_name.clone());
+    let objects = extract_objects(cls, objects)?;
+
+    if fields.is_empty() {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "bulk_update() requires at least one field to update.".to_string(),
+        )
+        .into());
+    }
+    let mut field_columns = Vec::with_capacity(fields.len());
+    for field in &fields {
+        match info.columns.iter().find(|c| &c.column_name == field) {
+            None => {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "'{}' is not a column of '{}'.",
+                    field, info.table_name
+                ))
+                .into());
+            }
+            Some(column) if column.primary_key || column.version => {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "bulk_update() can't update the primary key or version column '{}'.",
+                    field
+                ))
+                .into());
+            }
+            Some(column) if column.generated.is_some() => {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "bulk_update() can't update the generated column '{}'.",
+                    field
+                ))
+                .into());
+            }
+            Some(column) => field_columns.push(column),
+        }
+    }
+
+    // Per object: its primary key condition with parameters, the new field
+    // values, and the version it was loaded with.
+    let mut rows = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        if !object.borrow().persisted {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "Object {} of '{}' hasn't been saved; bulk_update() only updates saved objects.",
+                index, info.table_name
+            ))
+            .into());
+        }
+        let mut pk_params = Vec::with_capacity(pk_len);
+        let condition = info.pk_condition(&info.instance_pk(object.as_any())?, &mut pk_params)?;
+        let values = field_columns
+            .iter()
+            .map(|column| column_param(py, &object.getattr(&column.column_name)?, column))
+            .collect::<PyResult<Vec<_>>>()?;
+        let current_version = match &version {
+            Some(column) => Some(
+                object
+                    .getattr(column)?
+                    .extract::<Option<i64>>()?
+                    .ok_or_else(|| {
+                        FustOrmError::BuildError(format!(
+                            "Version column '{}' of '{}' is NULL.",
+                            column, info.table_name
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+        rows.push((condition, pk_params, values, current_version));
+    }
+
+    let params_per_row = fields.len() * (pk_len + 1) + pk_len + usize::from(version.is_some());
+    let size = rows_per_statement(batch_size, params_per_row)?;
+    let mut batches = Vec::new();
+    for (chunk_index, chunk) in rows.chunks(size).enumerate() {
+        let mut params = Vec::new();
+        let mut assignments = Vec::with_capacity(fields.len() + 1);
+        for (position, field) in fields.iter().enumerate() {
+            let mut cases = Vec::with_capacity(chunk.len());
+            for (condition, pk_params, values, _) in chunk {
+                params.extend(pk_params.iter().cloned());
+                let value = match &values[position] {
+                    Some(param) => {
+                        params.push(param.clone());
+                        "?"
+                    }
+                    None => "NULL",
+                };
+                cases.push(format!("WHEN {} THEN {}", condition, value));
+            }
+            assignments.push(format!(
+                "{0} = CASE {1} ELSE {0} END",
+                field,
+                cases.join(" ")
+            ));
+        }
+        if let Some(column) = &version {
+            assignments.push(format!("{0} = {0} + 1", column));
+        }
+        let mut matches = Vec::with_capacity(chunk.len());
+        for (condition, pk_params, _, current_version) in chunk {
+            params.extend(pk_params.iter().cloned());
+            match (&version, current_version) {
+                (Some(column), Some(current)) => {
+                    params.push(current.to_string());
+                    matches.push(format!("({} AND {} = ?)", condition, column));
+                }
+                _ => matches.push(format!("({})", condition)),
+            }
+        }
+        batches.push(Batch {
+            sql: format!(
+                "UPDATE {} SET {} WHERE {}",
+                info.table_name,
+                assignments.join(", "),
+                matches.join(" OR ")
+            ),
+            params,
+            objects: (chunk_index * size..chunk_index * size + chunk.len()).collect(),
+            returning: false,
+        });
+    }
+    debug!(
+        "Updating {} objects of '{}' in {} statements",
+        objects.len(),
+        info.table_name,
+        batches.len()
+    );
+
+    let db = context::resolve(py, Some(db))?;
+    let table_name = info.table_name.clone();
+    let versions: Vec<Option<i64>> = rows.iter().map(|row| row.3).collect();
+    let objects: Vec<Py<Model>> = objects.into_iter().map(Bound::unbind).collect();
+
+    future_into_py(py, async move {
+        let map_db_err = FustOrmError::from;
+        let mut tx = db.begin_write().await?;
+        let mut updated = 0;
+        for batch in batches {
+            let result = execute_statement(&mut *tx, &batch.sql, batch.params).await?;
+            if version.is_some() && result.rows_affected() < batch.objects.len() as u64 {
+                // Dropping the transaction rolls it back.
+                return Err(FustOrmError::StaleData(format!(
+                    "{} of {} rows of '{}' were changed or deleted since they were loaded.",
+                    batch.objects.len() as u64 - result.rows_affected(),
+                    batch.objects.len(),
+                    table_name
+                ))
+                .into());
+            }
+            updated += result.rows_affected();
+        }
+        tx.commit().await.map_err(map_db_err)?;
+
+        Python::attach(|py| -> PyResult<u64> {
+            if let Some(column) = &version {
+                for (object, current) in objects.iter().zip(versions) {
+                    if let Some(current) = current {
+                        object.bind(py).setattr(column, current + 1)?;
+                    }
+                }
+            }
+            Ok(updated)
+        })
+    })
+}
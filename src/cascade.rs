@@ -0,0 +1,265 @@
+use crate::database::Database;
+use crate::error::FustOrmError;
+use crate::model::{ModelInfo, hydrate};
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple, PyType};
+
+/// What happens to the rows referencing a row that is deleted, as declared
+/// with `ColumnField(references=..., on_delete=...)`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnDelete {
+    /// The referencing rows are deleted as well.
+    Cascade,
+    /// The referencing column is set to `NULL`.
+    SetNull,
+    /// The delete fails while referencing rows exist.
+    #[default]
+    Restrict,
+    /// The referencing rows are loaded and deleted with `Model.delete()`, so
+    /// that their signals fire and their own references are handled.
+    Python,
+}
+
+impl OnDelete {
+    pub fn parse(value: &str) -> Result<Self, FustOrmError> {
+        match value {
+            "cascade" => Ok(OnDelete::Cascade),
+            "set_null" => Ok(OnDelete::SetNull),
+            "restrict" => Ok(OnDelete::Restrict),
+            "python" => Ok(OnDelete::Python),
+            _ => Err(FustOrmError::BuildError(format!(
+                "Unknown on_delete '{}'; expected 'cascade', 'set_null', 'restrict' or 'python'.",
+                value
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OnDelete::Cascade => "cascade",
+            OnDelete::SetNull => "set_null",
+            OnDelete::Restrict => "restrict",
+            OnDelete::Python => "python",
+        }
+    }
+
+    /// The `ON DELETE` action of the foreign key. Python-side cascades are
+    /// left to the ORM, so the database only checks the reference.
+    pub fn sql(self) -> Option<&'static str> {
+        match self {
+            OnDelete::Cascade => Some("CASCADE"),
+            OnDelete::SetNull => Some("SET NULL"),
+            OnDelete::Restrict => Some("RESTRICT"),
+            OnDelete::Python => None,
+        }
+    }
+}
+
+/// A column of `child` referencing another model.
+struct Reference {
+    child: Py<PyType>,
+    table: String,
+    column: String,
+    on_delete: OnDelete,
+}
+
+/// Registers the columns of `cls` that reference another model with the
+/// referenced class, in its `__referenced_by__` list.
+pub(crate) fn register_references(cls: &Bound<PyType>, columns: &[String]) -> PyResult<()> {
+    let py = cls.py();
+    for name in columns {
+        let field = cls.getattr(name)?;
+        let target = field.getattr(pyo3::intern!(py, "references"))?;
+        if target.is_none() {
+            continue;
+        }
+        let target = target.downcast::<PyType>()?;
+        if ModelInfo::from_class(target)?.primary_key()?.len() != 1 {
+            return Err(FustOrmError::BuildError(format!(
+                "Column '{}' of '{}' references '{}', which has a composite primary key.",
+                name,
+                cls.name()?,
+                target.name()?
+            ))
+            .into());
+        }
+        target
+            .getattr(pyo3::intern!(py, "__dict__"))?
+            .get_item(pyo3::intern!(py, "__referenced_by__"))?
+            .downcast::<PyList>()?
+            .append((cls, name))?;
+    }
+    Ok(())
+}
+
+/// Collects the references to `cls` and its parent models.
+fn references(cls: &Bound<PyType>) -> PyResult<Vec<Reference>> {
+    let py = cls.py();
+    let mut references = Vec::new();
+    for class in cls.getattr(pyo3::intern!(py, "__mro__"))?.try_iter()? {
+        let class = class?;
+        let Ok(registered) = class
+            .getattr(pyo3::intern!(py, "__dict__"))?
+            .get_item(pyo3::intern!(py, "__referenced_by__"))
+        else {
+            continue;
+        };
+        for entry in registered.try_iter()? {
+            let (child, column) = entry?.extract::<(Bound<PyType>, String)>()?;
+            let field = child.getattr(&column)?;
+            let on_delete = OnDelete::parse(
+                &field
+                    .getattr(pyo3::intern!(py, "on_delete"))?
+                    .extract::<String>()?,
+            )?;
+            references.push(Reference {
+                table: child
+                    .getattr(pyo3::intern!(py, "__table_name__"))?
+                    .extract()?,
+                child: child.unbind(),
+                column,
+                on_delete,
+            });
+        }
+    }
+    Ok(references)
+}
+
+/// What deleting a row entails for the rows referencing it. Every statement
+/// binds the primary key of the deleted row as its only parameter.
+#[derive(Default)]
+pub(crate) struct DeletePlan {
+    /// Queries that must not find a row, with the error raised if they do.
+    restrict: Vec<(String, String)>,
+    /// Children deleted through the ORM, as `(model, referencing column)`.
+    python: Vec<(Py<PyType>, String)>,
+    /// `UPDATE` and `DELETE` statements to run, in order, before the row itself
+    /// is deleted.
+    statements: Vec<String>,
+}
+
+impl DeletePlan {
+    /// Plans the deletion of a row of `cls`.
+    pub(crate) fn new(cls: &Bound<PyType>) -> PyResult<Self> {
+        let mut plan = DeletePlan::default();
+        let table = cls
+            .getattr(pyo3::intern!(cls.py(), "__table_name__"))?
+            .extract::<String>()?;
+        plan.add(cls, "?", &mut vec![table], true)?;
+        Ok(plan)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.restrict.is_empty() && self.python.is_empty() && self.statements.is_empty()
+    }
+
+    /// Adds the handling of rows referencing the rows of `cls` whose primary key
+    /// is in `keys` (`?` or a subquery). Rows deleted by a cascade cascade in
+    /// SQL, including their Python-side references; a model already on `path`
+    /// isn't descended into again, which leaves deeper levels of
+    /// self-references to the database.
+    fn add(
+        &mut self,
+        cls: &Bound<PyType>,
+        keys: &str,
+        path: &mut Vec<String>,
+        direct: bool,
+    ) -> PyResult<()> {
+        let parent = cls
+            .getattr(pyo3::intern!(cls.py(), "__table_name__"))?
+            .extract::<String>()?;
+        for reference in references(cls)? {
+            let condition = format!("{} IN ({})", reference.column, keys);
+            match reference.on_delete {
+                OnDelete::Restrict => self.restrict.push((
+                    format!(
+                        "SELECT 1 FROM {} WHERE {} LIMIT 1",
+                        reference.table, condition
+                    ),
+                    format!(
+                        "Cannot delete the row of '{}': it is referenced by '{}.{}' (on_delete='restrict').",
+                        parent, reference.table, reference.column
+                    ),
+                )),
+                OnDelete::SetNull => self.statements.push(format!(
+                    "UPDATE {} SET {} = NULL WHERE {}",
+                    reference.table, reference.column, condition
+                )),
+                OnDelete::Python if direct => self
+                    .python
+                    .push((reference.child.clone_ref(cls.py()), reference.column)),
+                OnDelete::Cascade | OnDelete::Python => {
+                    let child = reference.child.bind(cls.py());
+                    if !path.contains(&reference.table) {
+                        let info = ModelInfo::from_class(child)?;
+                        if let [pk] = info.primary_key()?.as_slice() {
+                            let child_keys = format!(
+                                "SELECT {} FROM {} WHERE {}",
+                                pk.column_name, reference.table, condition
+                            );
+                            path.push(reference.table.clone());
+                            self.add(child, &child_keys, path, false)?;
+                            path.pop();
+                        }
+                    }
+                    self.statements.push(format!(
+                        "DELETE FROM {} WHERE {}",
+                        reference.table, condition
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if a row referencing the deleted one with `on_delete="restrict"` exists.
+    pub(crate) async fn check_restrict(&self, db: &Database, key: &str) -> PyResult<()> {
+        for (sql, message) in &self.restrict {
+            if !db.fetch_rows(sql, vec![key.to_string()]).await?.is_empty() {
+                return Err(FustOrmError::InvalidQueryArgument(message.clone()).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the children with `on_delete="python"` one by one with
+    /// `Model.delete()`.
+    pub(crate) async fn delete_python_children(
+        &self,
+        database: &Database,
+        key: &str,
+    ) -> PyResult<()> {
+        let db = Python::attach(|py| Py::new(py, database.clone()))?;
+        for (child, column) in &self.python {
+            let table = Python::attach(|py| {
+                child
+                    .bind(py)
+                    .getattr(pyo3::intern!(py, "__table_name__"))?
+                    .extract::<String>()
+            })?;
+            let sql = format!("SELECT * FROM {} WHERE {} = ?", table, column);
+            let rows = database.fetch_rows(&sql, vec![key.to_string()]).await?;
+            debug!(
+                "Deleting {} rows of '{}' referencing the deleted row",
+                rows.len(),
+                table
+            );
+            for row in &rows {
+                let deleted = Python::attach(|py| {
+                    let dict: Bound<PyDict> = crate::database::row_to_dict(py, row)?;
+                    let instance = hydrate(child.bind(py), &dict)?;
+                    let awaitable = instance
+                        .call_method1(pyo3::intern!(py, "delete"), PyTuple::new(py, [&db])?)?;
+                    pyo3_async_runtimes::tokio::into_future(awaitable)
+                })?;
+                deleted.await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn statements(&self) -> &[String] {
+        &self.statements
+    }
+}
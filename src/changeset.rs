@@ -0,0 +1,513 @@
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use libsqlite3_sys as ffi;
+use log::{debug, warn};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3_async_runtimes::tokio::future_into_py;
+
+use crate::database::{Database, begin_write};
+use crate::error::{DatabaseError, FustOrmError, IntegrityError};
+use crate::sqlite_hooks::{self, require_sqlite};
+
+// The session extension records changes per connection, so a changeset being
+// recorded has a session on each connection of the pool. The changes a
+// session has recorded are collected when its connection goes back to the
+// pool and when the changeset is read, whenever no transaction is open on the
+// connection, and the session starts afresh.
+
+/// A session recording the changes made on one connection.
+struct Session {
+    /// The address of the connection's handle.
+    tag: usize,
+    db: *mut ffi::sqlite3,
+    session: *mut ffi::sqlite3_session,
+}
+
+// SAFETY: the connection is serialized: SQLite locks its mutex in every call
+// on the session, which is only used under it or while the connection is
+// handed out to the thread using it.
+unsafe impl Send for Session {}
+
+#[derive(Default)]
+struct RecorderState {
+    sessions: Vec<Session>,
+    /// The changesets collected from the sessions, in order.
+    changes: Vec<Vec<u8>>,
+    closed: bool,
+}
+
+/// The sessions of a changeset being recorded on the connections of a pool.
+pub(crate) struct Recorder {
+    pub(crate) pool_id: i64,
+    /// The tables recorded, or `None` for all of them.
+    tables: Option<Vec<CString>>,
+    state: Mutex<RecorderState>,
+}
+
+impl Recorder {
+    fn state(&self) -> MutexGuard<'_, RecorderState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Creates a session recording the tables on `db`.
+    ///
+    /// # Safety
+    ///
+    /// `db` must be an open connection.
+    unsafe fn create_session(
+        &self,
+        db: *mut ffi::sqlite3,
+    ) -> Result<*mut ffi::sqlite3_session, c_int> {
+        let mut session = ptr::null_mut();
+        // SAFETY: the session is deleted if attaching the tables fails.
+        unsafe {
+            let rc = ffi::sqlite3session_create(db, c"main".as_ptr(), &mut session);
+            if rc != ffi::SQLITE_OK {
+                return Err(rc);
+            }
+            let tables: Vec<*const c_char> = match &self.tables {
+                Some(tables) => tables.iter().map(|t| t.as_ptr()).collect(),
+                None => vec![ptr::null()],
+            };
+            for table in tables {
+                let rc = ffi::sqlite3session_attach(session, table);
+                if rc != ffi::SQLITE_OK {
+                    ffi::sqlite3session_delete(session);
+                    return Err(rc);
+                }
+            }
+        }
+        Ok(session)
+    }
+
+    /// Starts recording on the connection of `tag` unless it already does,
+    /// returning the SQLite result code.
+    ///
+    /// # Safety
+    ///
+    /// `db` must be the open connection of `tag`, which no other thread uses
+    /// meanwhile.
+    pub(crate) unsafe fn attach(&self, tag: usize, db: *mut ffi::sqlite3) -> c_int {
+        {
+            let state = self.state();
+            if state.closed || state.sessions.iter().any(|s| s.tag == tag) {
+                return ffi::SQLITE_OK;
+            }
+        }
+        // SAFETY: guaranteed by the caller. The session is created without
+        // the lock, which `detach` takes under the connection's mutex.
+        match unsafe { self.create_session(db) } {
+            Ok(session) => {
+                self.state().sessions.push(Session { tag, db, session });
+                ffi::SQLITE_OK
+            }
+            Err(rc) => rc,
+        }
+    }
+
+    /// Collects the changes recorded on the connection of `tag`, or on all of
+    /// them, from the sessions on connections without an open transaction.
+    /// A connection running a statement is waited for.
+    pub(crate) fn collect(&self, tag: Option<usize>) {
+        loop {
+            let mut state = self.state();
+            let RecorderState {
+                sessions, changes, ..
+            } = &mut *state;
+            let mut busy = false;
+            for session in sessions.iter_mut() {
+                if tag.is_some_and(|tag| tag != session.tag) {
+                    continue;
+                }
+                // SAFETY: the connection is open while its session is listed:
+                // `detach` removes it under the lock before it is closed. Its
+                // mutex is only tried, since `detach` is called under it.
+                unsafe {
+                    let mutex = ffi::sqlite3_db_mutex(session.db);
+                    if ffi::sqlite3_mutex_try(mutex) != ffi::SQLITE_OK {
+                        busy = true;
+                        continue;
+                    }
+                    if ffi::sqlite3_txn_state(session.db, ptr::null()) == ffi::SQLITE_TXN_NONE {
+                        self.take_changes(session, changes);
+                    }
+                    ffi::sqlite3_mutex_leave(mutex);
+                }
+            }
+            drop(state);
+            if !busy {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Appends the changeset recorded by `session` to `changes` and replaces
+    /// the session with a new one.
+    ///
+    /// # Safety
+    ///
+    /// The caller must hold the mutex of the session's connection.
+    unsafe fn take_changes(&self, session: &mut Session, changes: &mut Vec<Vec<u8>>) {
+        let mut size: c_int = 0;
+        let mut data: *mut c_void = ptr::null_mut();
+        // SAFETY: SQLite allocates the changeset, which is freed once copied.
+        unsafe {
+            let rc = ffi::sqlite3session_changeset(session.session, &mut size, &mut data);
+            if rc != ffi::SQLITE_OK {
+                warn!("Couldn't collect the changes of a session (error {})", rc);
+                return;
+            }
+            if size > 0 {
+                changes.push(std::slice::from_raw_parts(data.cast::<u8>(), size as usize).to_vec());
+            }
+            ffi::sqlite3_free(data);
+            match self.create_session(session.db) {
+                Ok(fresh) => {
+                    ffi::sqlite3session_delete(session.session);
+                    session.session = fresh;
+                }
+                // The changes are collected again with the next ones.
+                Err(rc) => warn!("Couldn't restart a session (error {})", rc),
+            }
+        }
+    }
+
+    /// Stops recording on the connection of `tag`, which is being closed, with
+    /// its mutex held.
+    pub(crate) fn detach(&self, tag: usize) {
+        let mut state = self.state();
+        if let Some(index) = state.sessions.iter().position(|s| s.tag == tag) {
+            let session = state.sessions.swap_remove(index);
+            // SAFETY: the connection is still open, its mutex held.
+            unsafe { ffi::sqlite3session_delete(session.session) };
+        }
+    }
+
+    /// Collects the recorded changes as one changeset.
+    fn changeset(&self) -> Result<Vec<u8>, c_int> {
+        self.collect(None);
+        let mut state = self.state();
+        if state.changes.len() <= 1 {
+            return Ok(state.changes.first().cloned().unwrap_or_default());
+        }
+        let combined = combine(&mut state.changes)?;
+        state.changes = vec![combined.clone()];
+        Ok(combined)
+    }
+
+    /// Stops recording, keeping the changes collected.
+    fn close(self: &Arc<Self>) {
+        sqlite_hooks::stop_recording(self);
+        self.collect(None);
+        loop {
+            let mut state = self.state();
+            state.closed = true;
+            // SAFETY: as in `collect`.
+            state.sessions.retain(|session| unsafe {
+                let mutex = ffi::sqlite3_db_mutex(session.db);
+                if ffi::sqlite3_mutex_try(mutex) != ffi::SQLITE_OK {
+                    return true;
+                }
+                ffi::sqlite3session_delete(session.session);
+                ffi::sqlite3_mutex_leave(mutex);
+                false
+            });
+            if state.sessions.is_empty() {
+                return;
+            }
+            drop(state);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// Combines changesets made one after the other into one.
+fn combine(changes: &mut [Vec<u8>]) -> Result<Vec<u8>, c_int> {
+    let mut group = ptr::null_mut();
+    // SAFETY: the group is deleted once output, and its output freed once
+    // copied.
+    unsafe {
+        let rc = ffi::sqlite3changegroup_new(&mut group);
+        if rc != ffi::SQLITE_OK {
+            return Err(rc);
+        }
+        let mut rc = ffi::SQLITE_OK;
+        for changeset in changes.iter_mut() {
+            rc = ffi::sqlite3changegroup_add(
+                group,
+                changeset.len() as c_int,
+                changeset.as_mut_ptr().cast(),
+            );
+            if rc != ffi::SQLITE_OK {
+                break;
+            }
+        }
+        let mut size: c_int = 0;
+        let mut data: *mut c_void = ptr::null_mut();
+        if rc == ffi::SQLITE_OK {
+            rc = ffi::sqlite3changegroup_output(group, &mut size, &mut data);
+        }
+        ffi::sqlite3changegroup_delete(group);
+        if rc != ffi::SQLITE_OK {
+            return Err(rc);
+        }
+        let combined = std::slice::from_raw_parts(data.cast::<u8>(), size as usize).to_vec();
+        ffi::sqlite3_free(data);
+        Ok(combined)
+    }
+}
+
+/// The description of a SQLite result code.
+fn error_string(rc: c_int) -> String {
+    // SAFETY: SQLite returns a static string.
+    unsafe { CStr::from_ptr(ffi::sqlite3_errstr(rc)) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The changes made to a SQLite database since `Database.start_changeset()`,
+/// recorded with SQLite's session extension, to be applied to another copy of
+/// the database with `Database.apply_changeset()`.
+///
+/// Only changes to tables with a primary key are recorded. Changes made in a
+/// transaction are part of the changeset once it has committed.
+#[pyclass(frozen, module = "fust_orm")]
+pub struct Changeset {
+    recorder: Arc<Recorder>,
+}
+
+#[pymethods]
+impl Changeset {
+    /// Serializes the changes recorded so far, combined so that each changed
+    /// row appears once.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let changeset = py.detach(|| self.recorder.changeset()).map_err(|rc| {
+            DatabaseError::new_err(format!(
+                "Couldn't collect the changeset: {}",
+                error_string(rc)
+            ))
+        })?;
+        Ok(PyBytes::new(py, &changeset))
+    }
+
+    /// Stops recording. The changes recorded so far are kept.
+    fn close(&self, py: Python) {
+        py.detach(|| self.recorder.close());
+    }
+
+    /// Whether recording has stopped.
+    #[getter]
+    fn closed(&self) -> bool {
+        self.recorder.state().closed
+    }
+
+    fn __repr__(&self) -> String {
+        let tables = match &self.recorder.tables {
+            Some(tables) => tables
+                .iter()
+                .map(|t| t.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => "all tables".to_string(),
+        };
+        let state = if self.closed() { "closed" } else { "recording" };
+        format!("<Changeset {} ({})>", tables, state)
+    }
+}
+
+impl Drop for Changeset {
+    fn drop(&mut self) {
+        // A connection being waited for may need the GIL to finish.
+        Python::attach(|py| py.detach(|| self.recorder.close()));
+    }
+}
+
+/// Starts recording the changes made to `tables` (all tables if `None`)
+/// through the pool of `db`.
+pub(crate) fn start_changeset(db: &Database, tables: Option<Vec<String>>) -> PyResult<Changeset> {
+    require_sqlite(db, "start_changeset")?;
+    let tables = tables
+        .map(|tables| {
+            tables
+                .into_iter()
+                .map(|table| {
+                    CString::new(table).map_err(|_| {
+                        FustOrmError::InvalidQueryArgument(
+                            "A table name can't contain NUL.".to_string(),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+    let recorder = Arc::new(Recorder {
+        pool_id: sqlite_hooks::pool_id(db)?,
+        tables,
+        state: Mutex::new(RecorderState::default()),
+    });
+    sqlite_hooks::start_recording(recorder.clone());
+    debug!("Started recording a changeset");
+    Ok(Changeset { recorder })
+}
+
+/// What the conflict callback of `sqlite3changeset_apply()` works with.
+struct Apply {
+    handler: Option<Py<PyAny>>,
+    /// The exception the handler raised or the invalid answer it gave, raised
+    /// once the changes are rolled back.
+    error: Option<PyErr>,
+    /// The conflict the changes were rolled back on, and its table.
+    aborted: Option<(&'static str, String)>,
+}
+
+unsafe extern "C" fn on_conflict(
+    context: *mut c_void,
+    conflict: c_int,
+    iterator: *mut ffi::sqlite3_changeset_iter,
+) -> c_int {
+    // SAFETY: the context is the `Apply` of the running call.
+    let apply = unsafe { &mut *context.cast::<Apply>() };
+    let kind = match conflict {
+        ffi::SQLITE_CHANGESET_DATA => "data",
+        ffi::SQLITE_CHANGESET_NOTFOUND => "notfound",
+        ffi::SQLITE_CHANGESET_CONFLICT => "conflict",
+        ffi::SQLITE_CHANGESET_CONSTRAINT => "constraint",
+        _ => "foreign_key",
+    };
+    let mut name: *const c_char = ptr::null();
+    let (mut columns, mut operation, mut indirect) = (0, 0, 0);
+    // SAFETY: the iterator points at the conflicting change, except for
+    // foreign key conflicts, which concern the whole changeset.
+    let table = unsafe {
+        if ffi::sqlite3changeset_op(
+            iterator,
+            &mut name,
+            &mut columns,
+            &mut operation,
+            &mut indirect,
+        ) == ffi::SQLITE_OK
+            && !name.is_null()
+        {
+            CStr::from_ptr(name).to_string_lossy().into_owned()
+        } else {
+            String::new()
+        }
+    };
+    let Some(handler) = &apply.handler else {
+        apply.aborted = Some((kind, table));
+        return ffi::SQLITE_CHANGESET_ABORT;
+    };
+    // A panic mustn't unwind into SQLite.
+    let answer = catch_unwind(AssertUnwindSafe(|| {
+        Python::attach(|py| {
+            handler
+                .call1(py, (kind, &table))
+                .and_then(|answer| answer.extract::<String>(py))
+        })
+    }));
+    let answer = match answer {
+        Ok(Ok(answer)) => answer,
+        Ok(Err(err)) => {
+            apply.error = Some(err);
+            return ffi::SQLITE_CHANGESET_ABORT;
+        }
+        Err(_) => {
+            apply.error = Some(PyValueError::new_err("The conflict handler panicked."));
+            return ffi::SQLITE_CHANGESET_ABORT;
+        }
+    };
+    match answer.as_str() {
+        "omit" => ffi::SQLITE_CHANGESET_OMIT,
+        "replace" if matches!(kind, "data" | "conflict") => ffi::SQLITE_CHANGESET_REPLACE,
+        "abort" => {
+            apply.aborted = Some((kind, table));
+            ffi::SQLITE_CHANGESET_ABORT
+        }
+        _ => {
+            apply.error = Some(PyValueError::new_err(format!(
+                "The conflict handler returned '{}' for a '{}' conflict; expected 'omit', {}or 'abort'.",
+                answer,
+                kind,
+                if matches!(kind, "data" | "conflict") {
+                    "'replace' "
+                } else {
+                    ""
+                }
+            )));
+            ffi::SQLITE_CHANGESET_ABORT
+        }
+    }
+}
+
+/// Applies a changeset from `Changeset.to_bytes()` in a transaction. Each
+/// conflict is passed to `conflict_handler(conflict, table)`, which returns
+/// `"omit"`, `"replace"` or `"abort"`; without one, conflicts abort.
+pub(crate) fn apply_changeset<'py>(
+    py: Python<'py>,
+    db: &Database,
+    changeset: Vec<u8>,
+    conflict_handler: Option<Py<PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    require_sqlite(db, "apply_changeset")?;
+    if let Some(handler) = &conflict_handler
+        && !handler.bind(py).is_callable()
+    {
+        return Err(PyTypeError::new_err("conflict_handler must be callable."));
+    }
+    let Ok(size) = c_int::try_from(changeset.len()) else {
+        return Err(
+            FustOrmError::InvalidQueryArgument("The changeset is too large.".to_string()).into(),
+        );
+    };
+    let pool_id = sqlite_hooks::pool_id(db)?;
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let mut changeset = changeset;
+        let mut tx = begin_write(&pool).await?;
+        let handle = sqlite_hooks::connection_handle(&mut tx, pool_id)
+            .await
+            .map_err(FustOrmError::from)?;
+        let mut apply = Apply {
+            handler: conflict_handler,
+            error: None,
+            aborted: None,
+        };
+        // SAFETY: the connection is handed out for the transaction, and the
+        // context outlives the call.
+        let rc = unsafe {
+            ffi::sqlite3changeset_apply(
+                handle as *mut ffi::sqlite3,
+                size,
+                changeset.as_mut_ptr().cast(),
+                None,
+                Some(on_conflict),
+                (&mut apply as *mut Apply).cast(),
+            )
+        };
+        if let Some(err) = apply.error {
+            return Err(err);
+        }
+        if let Some((conflict, table)) = apply.aborted {
+            return Err(IntegrityError::new_err(format!(
+                "Applying the changeset was aborted on a '{}' conflict in table '{}'.",
+                conflict, table
+            )));
+        }
+        if rc != ffi::SQLITE_OK {
+            return Err(DatabaseError::new_err(format!(
+                "Couldn't apply the changeset: {}",
+                error_string(rc)
+            )));
+        }
+        tx.commit().await.map_err(FustOrmError::from)?;
+        debug!("Applied a changeset of {} bytes", size);
+        Ok(())
+    })
+}
@@ -0,0 +1,228 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use pyo3::prelude::*;
+use sqlx::AnyPool;
+
+use crate::error::FustOrmError;
+use crate::pool_registry::PoolRegistry;
+
+/// Where a circuit breaker is.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Circuit {
+    /// Connections are opened as usual.
+    Closed,
+    /// Checking a connection out fails at once, until the cooldown is over.
+    Open,
+    /// One attempt probes whether the database is reachable again, while
+    /// the others still fail at once.
+    HalfOpen,
+}
+
+impl Circuit {
+    fn name(self) -> &'static str {
+        match self {
+            Circuit::Closed => "closed",
+            Circuit::Open => "open",
+            Circuit::HalfOpen => "half_open",
+        }
+    }
+}
+
+struct State {
+    circuit: Circuit,
+    /// The connection failures in a row.
+    consecutive: u32,
+    /// When the circuit opened, or the probe of a half-open one started.
+    since: Instant,
+}
+
+struct Breaker {
+    failures: u32,
+    cooldown: Duration,
+    callback: Option<Py<PyAny>>,
+    state: Mutex<State>,
+}
+
+/// Stops checking connections out of the pools of the databases connected
+/// with it, after `failures` connection failures or timeouts in a row, so
+/// that statements fail at once during an outage rather than each waiting
+/// for its own timeout. After `cooldown` seconds it lets one statement try
+/// again (it is "half_open"), closing if that succeeds and opening for
+/// another `cooldown` if it fails.
+///
+/// `on_state_change(state)` is called with `"open"`, `"half_open"` or
+/// `"closed"` as the breaker changes state.
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub(crate) struct CircuitBreaker {
+    breaker: Arc<Breaker>,
+}
+
+#[pymethods]
+impl CircuitBreaker {
+    #[new]
+    #[pyo3(signature = (*, failures = 5, cooldown = 30.0, on_state_change = None))]
+    fn new(failures: u32, cooldown: f64, on_state_change: Option<Py<PyAny>>) -> PyResult<Self> {
+        if failures == 0 {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "failures must be at least 1.".to_string(),
+            )
+            .into());
+        }
+        if !(cooldown > 0.0 && cooldown.is_finite()) {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "cooldown must be a positive number of seconds.".to_string(),
+            )
+            .into());
+        }
+        Ok(CircuitBreaker {
+            breaker: Arc::new(Breaker {
+                failures,
+                cooldown: Duration::from_secs_f64(cooldown),
+                callback: on_state_change,
+                state: Mutex::new(State {
+                    circuit: Circuit::Closed,
+                    consecutive: 0,
+                    since: Instant::now(),
+                }),
+            }),
+        })
+    }
+
+    /// `"closed"`, `"open"` or `"half_open"`.
+    #[getter]
+    fn state(&self) -> &'static str {
+        self.breaker.lock().circuit.name()
+    }
+
+    /// The connection failures in a row so far.
+    #[getter]
+    fn failures(&self) -> u32 {
+        self.breaker.lock().consecutive
+    }
+
+    /// Closes the breaker, e.g. once an outage is known to be over.
+    fn reset(&self) {
+        let changed = {
+            let mut state = self.breaker.lock();
+            state.consecutive = 0;
+            std::mem::replace(&mut state.circuit, Circuit::Closed) != Circuit::Closed
+        };
+        if changed {
+            self.breaker.notify(Circuit::Closed);
+        }
+    }
+}
+
+impl Breaker {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn notify(&self, circuit: Circuit) {
+        match circuit {
+            Circuit::Open => warn!(
+                "Circuit breaker opened after {} connection failures in a row",
+                self.failures
+            ),
+            _ => info!("Circuit breaker {}", circuit.name()),
+        }
+        let Some(callback) = &self.callback else {
+            return;
+        };
+        Python::attach(|py| {
+            if let Err(e) = callback.call1(py, (circuit.name(),)) {
+                e.write_unraisable(py, Some(callback.bind(py)));
+            }
+        });
+    }
+
+    /// Whether an attempt may go ahead, which makes an open breaker whose
+    /// cooldown is over half-open. A probe that never reported back, e.g.
+    /// because its task was cancelled, is replaced after a cooldown too.
+    fn admit(&self) -> Result<(), sqlx::Error> {
+        let half_opened = {
+            let mut state = self.lock();
+            match state.circuit {
+                Circuit::Closed => false,
+                _ if state.since.elapsed() < self.cooldown => {
+                    let remaining = self.cooldown - state.since.elapsed();
+                    return Err(sqlx::Error::Io(std::io::Error::other(format!(
+                        "the circuit breaker is open after {} connection failures in a row; \
+                         retrying in {:.1}s",
+                        state.consecutive,
+                        remaining.as_secs_f64()
+                    ))));
+                }
+                _ => {
+                    state.since = Instant::now();
+                    std::mem::replace(&mut state.circuit, Circuit::HalfOpen) == Circuit::Open
+                }
+            }
+        };
+        if half_opened {
+            self.notify(Circuit::HalfOpen);
+        }
+        Ok(())
+    }
+
+    /// Records the outcome of an attempt.
+    fn record(&self, failed: bool) {
+        let changed = {
+            let mut state = self.lock();
+            if failed {
+                state.consecutive += 1;
+                let open = state.circuit == Circuit::HalfOpen
+                    || (state.circuit == Circuit::Closed && state.consecutive >= self.failures);
+                if open {
+                    state.circuit = Circuit::Open;
+                    state.since = Instant::now();
+                }
+                open.then_some(Circuit::Open)
+            } else {
+                state.consecutive = 0;
+                (std::mem::replace(&mut state.circuit, Circuit::Closed) != Circuit::Closed)
+                    .then_some(Circuit::Closed)
+            }
+        };
+        if let Some(circuit) = changed {
+            self.notify(circuit);
+        }
+    }
+}
+
+/// The breakers of the pools connected with `circuit_breaker=`, by the
+/// connect options identifying them.
+static BREAKERS: PoolRegistry<Arc<Breaker>> = PoolRegistry::new();
+
+/// Makes `breaker` guard the connections checked out of `pool`.
+pub(crate) fn set(pool: &AnyPool, breaker: &CircuitBreaker) {
+    BREAKERS.insert(pool, breaker.breaker.clone());
+}
+
+fn of(pool: &AnyPool) -> Option<Arc<Breaker>> {
+    BREAKERS.get(pool)
+}
+
+/// Runs `attempt`, which checks a connection out of `pool`, unless the
+/// breaker of the pool is open, and records whether it failed to reach the
+/// database.
+pub(crate) async fn guard<T>(
+    pool: &AnyPool,
+    attempt: impl Future<Output = Result<T, sqlx::Error>>,
+) -> Result<T, sqlx::Error> {
+    let Some(breaker) = of(pool) else {
+        return attempt.await;
+    };
+    breaker.admit()?;
+    let result = attempt.await;
+    let failed = matches!(
+        &result,
+        Err(sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut)
+    );
+    breaker.record(failed);
+    result
+}
@@ -0,0 +1,345 @@
+use std::path::Path;
+
+use crate::autogen::{self, DesiredSchema};
+use crate::codegen;
+use crate::database::{Database, block_on, connect_pool};
+use crate::error::FustOrmError;
+use crate::migrations::{self, MigrationId};
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+const USAGE: &str = "\
+usage: python -m fust_orm [--database-url URL] [--migrations DIR] <command> [options]
+
+commands:
+  migrate [--dry-run]          apply the pending migrations, or print the SQL
+                               they would run
+  rollback [--steps N]         roll back the N newest migrations (default 1)
+  status                       list the migrations and whether they are applied
+  make-migration [--name NAME] [--models MODULE]...
+                               write a migration from the changes to the models
+                               defined in MODULE (repeatable)
+  introspect [--table TABLE]... [--output FILE]
+                               print model classes generated from the tables
+                               of the database (all by default), or write them
+                               to FILE
+
+The database URL is taken from --database-url, then the FUST_ORM_DATABASE_URL
+and DATABASE_URL environment variables, then `database_url` in the
+[tool.fust_orm] table of ./pyproject.toml. That table may also set
+`migrations` (the directory, \"migrations\" by default) and `models`.";
+
+/// Settings read from the `[tool.fust_orm]` table of `./pyproject.toml`.
+#[derive(Default)]
+struct Config {
+    database_url: Option<String>,
+    migrations: Option<String>,
+    models: Vec<String>,
+}
+
+impl Config {
+    fn load(py: Python) -> PyResult<Self> {
+        let path = Path::new("pyproject.toml");
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        // `tomllib` is in the standard library from Python 3.11.
+        let Ok(toml) = py.import("tomllib").or_else(|_| py.import("tomli")) else {
+            debug!("No TOML parser available, ignoring pyproject.toml");
+            return Ok(Config::default());
+        };
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            FustOrmError::BuildError(format!("Can't read '{}': {}", path.display(), e))
+        })?;
+        let document = toml.call_method1("loads", (text,))?;
+        let Some(table) = document
+            .call_method1("get", ("tool", PyDict::new(py)))?
+            .call_method1("get", ("fust_orm",))?
+            .downcast_into::<PyDict>()
+            .ok()
+        else {
+            return Ok(Config::default());
+        };
+        let get = |key: &str| -> PyResult<Option<String>> {
+            table.get_item(key)?.map(|v| v.extract()).transpose()
+        };
+        Ok(Config {
+            database_url: get("database_url")?,
+            migrations: get("migrations")?,
+            models: table
+                .get_item("models")?
+                .map(|v| v.extract())
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// A command line that couldn't be parsed, or an empty message for `--help`.
+struct UsageError(String);
+
+enum Command {
+    Migrate {
+        dry_run: bool,
+    },
+    Rollback {
+        steps: usize,
+    },
+    Status,
+    MakeMigration {
+        name: String,
+        models: Vec<String>,
+    },
+    Introspect {
+        tables: Vec<String>,
+        output: Option<String>,
+    },
+}
+
+struct Args {
+    database_url: Option<String>,
+    migrations: Option<String>,
+    command: Command,
+}
+
+/// Takes the value of `--option VALUE` or `--option=VALUE` from `args`.
+fn option_value(
+    arg: &str,
+    option: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<Option<String>, UsageError> {
+    if arg == option {
+        return args
+            .next()
+            .map(Some)
+            .ok_or_else(|| UsageError(format!("{} expects a value", option)));
+    }
+    Ok(arg
+        .strip_prefix(option)
+        .and_then(|rest| rest.strip_prefix('='))
+        .map(str::to_string))
+}
+
+fn parse_args(argv: Vec<String>) -> Result<Args, UsageError> {
+    let mut args = argv.into_iter();
+    let mut database_url = None;
+    let mut migrations = None;
+    let mut command = None;
+    let mut steps = 1;
+    let mut name = "auto".to_string();
+    let mut models = Vec::new();
+    let mut tables = Vec::new();
+    let mut output = None;
+    let mut dry_run = false;
+    while let Some(arg) = args.next() {
+        if let Some(value) = option_value(&arg, "--database-url", &mut args)? {
+            database_url = Some(value);
+        } else if let Some(value) = option_value(&arg, "--migrations", &mut args)? {
+            migrations = Some(value);
+        } else if let Some(value) = option_value(&arg, "--steps", &mut args)? {
+            steps = value
+                .parse()
+                .map_err(|_| UsageError(format!("invalid --steps value '{}'", value)))?;
+        } else if let Some(value) = option_value(&arg, "--name", &mut args)? {
+            name = value;
+        } else if let Some(value) = option_value(&arg, "--models", &mut args)? {
+            models.push(value);
+        } else if let Some(value) = option_value(&arg, "--table", &mut args)? {
+            tables.push(value);
+        } else if let Some(value) = option_value(&arg, "--output", &mut args)? {
+            output = Some(value);
+        } else if arg == "--dry-run" {
+            dry_run = true;
+        } else if arg == "-h" || arg == "--help" {
+            return Err(UsageError(String::new()));
+        } else if arg.starts_with('-') {
+            return Err(UsageError(format!("unknown option '{}'", arg)));
+        } else if command.is_some() {
+            return Err(UsageError(format!("unexpected argument '{}'", arg)));
+        } else {
+            command = Some(arg);
+        }
+    }
+    let command = match command.as_deref() {
+        Some("migrate") => Command::Migrate { dry_run },
+        Some("rollback") => Command::Rollback { steps },
+        Some("status") => Command::Status,
+        Some("make-migration") => Command::MakeMigration { name, models },
+        Some("introspect") => Command::Introspect { tables, output },
+        Some(other) => return Err(UsageError(format!("unknown command '{}'", other))),
+        None => return Err(UsageError("a command is required".to_string())),
+    };
+    Ok(Args {
+        database_url,
+        migrations,
+        command,
+    })
+}
+
+/// Writes a line to `sys.stdout` or `sys.stderr`, so that output goes
+/// wherever Python's does.
+fn write_line(py: Python, stream: &str, line: &str) -> PyResult<()> {
+    py.import("sys")?
+        .getattr(stream)?
+        .call_method1("write", (format!("{}\n", line),))?;
+    Ok(())
+}
+
+fn print_migrations(
+    py: Python,
+    verb: &str,
+    migrations: &[MigrationId],
+    none: &str,
+) -> PyResult<()> {
+    if migrations.is_empty() {
+        write_line(py, "stdout", none)?;
+    }
+    for (version, name) in migrations {
+        write_line(py, "stdout", &format!("{} {:04}_{}", verb, version, name))?;
+    }
+    Ok(())
+}
+
+fn run(py: Python, args: Args) -> PyResult<()> {
+    let config = Config::load(py)?;
+    let url = args
+        .database_url
+        .or_else(|| std::env::var("FUST_ORM_DATABASE_URL").ok())
+        .or_else(|| std::env::var("DATABASE_URL").ok())
+        .or(config.database_url)
+        .ok_or_else(|| {
+            FustOrmError::ConnectionError(
+                "No database URL; pass --database-url or set FUST_ORM_DATABASE_URL.".to_string(),
+            )
+        })?;
+    let dir = args
+        .migrations
+        .or(config.migrations)
+        .unwrap_or_else(|| "migrations".to_string());
+    let pool = block_on(py, connect_pool(&url, false, None, &[]))?;
+    let db = Database::new(pool);
+
+    if let Command::MakeMigration { .. } = args.command {
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| FustOrmError::BuildError(format!("Can't create '{}': {}", dir, e)))?;
+    }
+    // Introspection doesn't need a migrations directory.
+    let migrations = match args.command {
+        Command::Introspect { .. } => Vec::new(),
+        _ => migrations::load_migrations(py, &dir)?,
+    };
+    match args.command {
+        Command::Migrate { dry_run: true } => {
+            let plan = block_on(py, migrations::pending_plan(&db.pool, &migrations))?;
+            if plan.is_empty() {
+                write_line(py, "stdout", "No pending migrations.")?;
+            }
+            let mut current = None;
+            for (migration, statement) in plan {
+                if current.as_ref() != Some(&migration) {
+                    write_line(py, "stdout", &format!("-- {}", migration))?;
+                    current = Some(migration);
+                }
+                if statement.starts_with("--") {
+                    write_line(py, "stdout", &statement)?;
+                } else {
+                    write_line(py, "stdout", &format!("{};", statement))?;
+                }
+            }
+        }
+        Command::Migrate { dry_run: false } => {
+            let done = block_on(py, migrations::apply_pending(&db.pool, &migrations))?;
+            print_migrations(py, "Applied", &done, "No pending migrations.")?;
+        }
+        Command::Rollback { steps } => {
+            let done = block_on(py, migrations::roll_back(&db.pool, &migrations, steps))?;
+            print_migrations(py, "Rolled back", &done, "No migrations to roll back.")?;
+        }
+        Command::Status => {
+            let states = block_on(py, migrations::migration_states(&db.pool, &migrations))?;
+            let datetime = py.import("datetime")?;
+            let utc = datetime.getattr("timezone")?.getattr("utc")?;
+            let from_timestamp = datetime.getattr("datetime")?.getattr("fromtimestamp")?;
+            for (version, name, applied_at) in states {
+                let line = match applied_at {
+                    Some(ts) => format!(
+                        "[x] {:04}_{}  (applied {})",
+                        version,
+                        name,
+                        from_timestamp
+                            .call1((ts, &utc))?
+                            .call_method1("strftime", ("%Y-%m-%d %H:%M:%S UTC",))?
+                    ),
+                    None => format!("[ ] {:04}_{}", version, name),
+                };
+                write_line(py, "stdout", &line)?;
+            }
+        }
+        Command::MakeMigration { name, models } => {
+            let modules = if models.is_empty() {
+                config.models
+            } else {
+                models
+            };
+            for module in &modules {
+                py.import(module.as_str())?;
+            }
+            let dialect = db.dialect();
+            let models = autogen::models_to_compare(py, None)?;
+            let schema = DesiredSchema::from_models(py, &models, dialect)?;
+            let written = block_on(
+                py,
+                migrations::write_migration(&db.pool, dialect, &dir, &migrations, &schema, &name),
+            )?;
+            let line = match written {
+                Some((version, name)) => format!("Created {:04}_{}", version, name),
+                None => "No changes detected.".to_string(),
+            };
+            write_line(py, "stdout", &line)?;
+        }
+        Command::Introspect { tables, output } => {
+            let tables = (!tables.is_empty()).then_some(tables);
+            let source = block_on(py, codegen::models_source(&db.pool, db.dialect(), tables))?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, source).map_err(|e| {
+                        FustOrmError::BuildError(format!("Can't write '{}': {}", path, e))
+                    })?;
+                    write_line(py, "stdout", &format!("Wrote {}", path))?;
+                }
+                None => {
+                    py.import("sys")?
+                        .getattr("stdout")?
+                        .call_method1("write", (source,))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the `python -m fust_orm` command line with `argv` (without the
+/// program name) and returns its exit status: 0 on success, 1 if the command
+/// failed and 2 if the command line is invalid.
+#[pyfunction]
+pub fn main(py: Python, argv: Vec<String>) -> PyResult<i32> {
+    let args = match parse_args(argv) {
+        Ok(args) => args,
+        Err(UsageError(message)) if message.is_empty() => {
+            write_line(py, "stdout", USAGE)?;
+            return Ok(0);
+        }
+        Err(UsageError(message)) => {
+            write_line(py, "stderr", &format!("{}\n\nerror: {}", USAGE, message))?;
+            return Ok(2);
+        }
+    };
+    match run(py, args) {
+        Ok(()) => Ok(0),
+        Err(e) => {
+            write_line(py, "stderr", &format!("error: {}", e.value(py)))?;
+            Ok(1)
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use crate::database::Database;
+use crate::error::FustOrmError;
+use crate::introspect::{self, ColumnInfo, ForeignKeyInfo, IndexInfo};
+use crate::migrations::{LOCK_TABLE, MIGRATIONS_TABLE};
+use crate::schema::Dialect;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use pyo3::prelude::*;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::AnyPool;
+
+/// Python's keywords, which can't name a model attribute.
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !PYTHON_KEYWORDS.contains(&name)
+}
+
+/// The Python type a column of `sql_type` is annotated with. Types without
+/// a closer match are read as strings.
+fn python_type(sql_type: &str) -> &'static str {
+    let upper = sql_type.to_ascii_uppercase();
+    let base = upper.split('(').next().unwrap_or_default().trim();
+    if upper.starts_with("BOOL") || upper == "TINYINT(1)" {
+        "bool"
+    } else if base.ends_with("INT") || base.contains("INTEGER") || base.ends_with("SERIAL") {
+        "int"
+    } else if ["REAL", "FLOA", "DOUB", "NUMERIC", "DECIMAL"]
+        .iter()
+        .any(|t| base.contains(t))
+    {
+        "float"
+    } else if base.contains("TIMESTAMP") || base.contains("DATETIME") {
+        "datetime.datetime"
+    } else if base == "DATE" {
+        "datetime.date"
+    } else if base.contains("BLOB") || base.contains("BINARY") || base == "BYTEA" {
+        "bytes"
+    } else if base.starts_with("JSON") {
+        "dict"
+    } else {
+        "str"
+    }
+}
+
+/// The `on_delete` of a model column for a foreign key's `ON DELETE` action,
+/// if it isn't the default `restrict`.
+fn on_delete(action: &str) -> Option<&'static str> {
+    match action.to_ascii_uppercase().as_str() {
+        "SET NULL" => Some("set_null"),
+        "CASCADE" => Some("cascade"),
+        _ => None,
+    }
+}
+
+/// Renders Python's repr of a string.
+fn py_str(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Renders `Meta.indexes` / `Meta.unique_together` entries: a column name,
+/// or a tuple of them.
+fn column_groups(indexes: &[&IndexInfo]) -> String {
+    let groups: Vec<String> = indexes
+        .iter()
+        .map(|index| {
+            let columns: Vec<String> = index.columns.iter().flatten().map(|c| py_str(c)).collect();
+            match columns.as_slice() {
+                [column] => column.clone(),
+                _ => format!("({})", columns.join(", ")),
+            }
+        })
+        .collect();
+    format!("[{}]", groups.join(", "))
+}
+
+/// A table to generate a model for.
+struct TableModel {
+    table: String,
+    class_name: String,
+    columns: Vec<ColumnInfo>,
+    foreign_keys: Vec<ForeignKeyInfo>,
+    indexes: Vec<IndexInfo>,
+    /// The named check constraints, as `(name, condition)`.
+    checks: Vec<(String, String)>,
+}
+
+impl TableModel {
+    fn primary_key(&self) -> Vec<&str> {
+        self.columns
+            .iter()
+            .filter(|c| c.primary_key)
+            .map(|c| c.name.as_str())
+            .collect()
+    }
+
+    /// Renders the model class. `defined` are the models rendered before,
+    /// which its references can name.
+    fn render(&self, defined: &[TableModel], imports: &mut Vec<&'static str>) -> String {
+        let mut lines = vec![format!("class {}(Model):", self.class_name)];
+        let primary_key = self.primary_key();
+        if primary_key.is_empty() {
+            lines.push(format!(
+                "    # '{}' has no primary key; the model uses a column named 'id'.",
+                self.table
+            ));
+        }
+        for column in &self.columns {
+            if !is_identifier(&column.name) {
+                lines.push(format!(
+                    "    # Column {} isn't a valid attribute name and is left out.",
+                    py_str(&column.name)
+                ));
+                continue;
+            }
+            let annotation = python_type(&column.sql_type);
+            if annotation.starts_with("datetime.") && !imports.contains(&"import datetime") {
+                imports.push("import datetime");
+            }
+            let nullable = column.nullable && !column.primary_key;
+            let mut options = Vec::new();
+            if column.primary_key && primary_key != ["id"] {
+                options.push("primary_key=True".to_string());
+            }
+            let mut comment = None;
+            if let Some(fk) = self.foreign_keys.iter().find(|fk| fk.column == column.name) {
+                let target = if fk.table == self.table {
+                    Some((py_str("self"), self.primary_key()))
+                } else {
+                    defined
+                        .iter()
+                        .find(|m| m.table == fk.table)
+                        .map(|m| (m.class_name.clone(), m.primary_key()))
+                };
+                match target {
+                    Some((model, pk)) if fk.target.is_empty() || pk == [fk.target.as_str()] => {
+                        options.push(format!("references={}", model));
+                        if let Some(action) = on_delete(&fk.on_delete) {
+                            options.push(format!("on_delete={}", py_str(action)));
+                        }
+                    }
+                    _ => {
+                        comment = Some(format!(
+                            "  # references {}({})",
+                            fk.table,
+                            if fk.target.is_empty() {
+                                "?"
+                            } else {
+                                &fk.target
+                            }
+                        ));
+                    }
+                }
+            }
+            if let Some(generated) = &column.generated {
+                options.push(format!("generated={}", py_str(&generated.expression)));
+                if generated.stored {
+                    options.push("stored=True".to_string());
+                }
+            }
+            let annotation = if nullable {
+                format!("{} | None", annotation)
+            } else {
+                annotation.to_string()
+            };
+            let mut line = format!("    {}: ColumnField[{}]", column.name, annotation);
+            if !options.is_empty() {
+                line.push_str(&format!(" = ColumnField({})", options.join(", ")));
+            }
+            if let Some(comment) = comment {
+                line.push_str(&comment);
+            }
+            lines.push(line);
+        }
+
+        let mut meta = Vec::new();
+        if self.class_name.to_snake_case() != self.table {
+            meta.push(format!("        table_name = {}", py_str(&self.table)));
+        }
+        let usable: Vec<&IndexInfo> = self
+            .indexes
+            .iter()
+            .filter(|index| {
+                !index.partial
+                    && index
+                        .columns
+                        .iter()
+                        .all(|c| c.as_deref().is_some_and(is_identifier))
+            })
+            .collect();
+        let (unique, plain): (Vec<&IndexInfo>, Vec<&IndexInfo>) =
+            usable.into_iter().partition(|index| index.unique);
+        if !plain.is_empty() {
+            meta.push(format!("        indexes = {}", column_groups(&plain)));
+        }
+        if !unique.is_empty() {
+            meta.push(format!(
+                "        unique_together = {}",
+                column_groups(&unique)
+            ));
+        }
+        if !self.checks.is_empty() {
+            let checks: Vec<String> = self
+                .checks
+                .iter()
+                .map(|(name, condition)| format!("{}: {}", py_str(name), py_str(condition)))
+                .collect();
+            meta.push(format!("        checks = {{{}}}", checks.join(", ")));
+        }
+        if !meta.is_empty() {
+            lines.push(String::new());
+            lines.push("    class Meta:".to_string());
+            lines.extend(meta);
+        }
+        lines.join("\n")
+    }
+}
+
+/// Renders Python source defining a model for each of `tables` (all tables
+/// but the migrations tables by default), ordered so that referenced models
+/// come first.
+pub(crate) async fn models_source(
+    pool: &AnyPool,
+    dialect: Dialect,
+    tables: Option<Vec<String>>,
+) -> Result<String, FustOrmError> {
+    let tables = match tables {
+        Some(tables) => tables,
+        None => introspect::tables(pool, dialect)
+            .await?
+            .into_iter()
+            .filter(|t| t != MIGRATIONS_TABLE && t != LOCK_TABLE)
+            .collect(),
+    };
+    let mut models: Vec<TableModel> = Vec::new();
+    for table in introspect::dependency_order(pool, dialect, tables).await? {
+        let columns = introspect::columns(pool, dialect, &table).await?;
+        if columns.is_empty() {
+            return Err(FustOrmError::BuildError(format!(
+                "There is no table '{}'.",
+                table
+            )));
+        }
+        let mut class_name = table.to_upper_camel_case();
+        if !is_identifier(&class_name) {
+            class_name = format!("Table{}", class_name);
+        }
+        models.push(TableModel {
+            class_name,
+            columns,
+            foreign_keys: introspect::foreign_keys(pool, dialect, &table).await?,
+            indexes: introspect::indexes(pool, dialect, &table).await?,
+            checks: introspect::constraints(pool, dialect, &table)
+                .await?
+                .into_iter()
+                .filter_map(|c| Some((c.name?, c.expression?)))
+                .collect(),
+            table,
+        });
+    }
+
+    let mut imports = Vec::new();
+    let classes: Vec<String> = models
+        .iter()
+        .enumerate()
+        .map(|(i, model)| model.render(&models[..i], &mut imports))
+        .collect();
+    let mut source = String::from(
+        "\"\"\"Models generated from the database schema by fust_orm.introspect_models().\"\"\"\n\n",
+    );
+    if !imports.is_empty() {
+        source.push_str(&imports.join("\n"));
+        source.push_str("\n\n");
+    }
+    source.push_str("from fust_orm import ColumnField, Model\n");
+    for class in classes {
+        source.push_str("\n\n");
+        source.push_str(&class);
+        source.push('\n');
+    }
+    Ok(source)
+}
+
+/// Generates model classes from the schema of an existing database, as
+/// Python source to review and save to a module. `tables` limits it to
+/// some tables; by default all are included except the migrations tables.
+#[pyfunction]
+#[pyo3(signature = (db, tables = None))]
+pub fn introspect_models<'py>(
+    py: Python<'py>,
+    db: &Database,
+    tables: Option<Vec<String>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let dialect = db.dialect();
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        Ok(models_source(&pool, dialect, tables).await?)
+    })
+}
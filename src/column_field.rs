@@ -1,11 +1,22 @@
 use std::sync::Arc;
 
 use pyo3::{
+    IntoPyObjectExt,
     exceptions::PyTypeError,
     prelude::*,
-    types::{PyList, PyString},
+    types::{PyList, PyString, PyType},
 };
 
+use pyo3_async_runtimes::tokio::future_into_py;
+
+use crate::array;
+use crate::cascade::OnDelete;
+use crate::custom_type;
+use crate::error::FustOrmError;
+use crate::expression::{Expr, F};
+use crate::geo;
+use crate::interval;
+use crate::range;
 use crate::where_condition::WhereCondition;
 
 /// Represents a database column as a Python object.
@@ -14,29 +25,261 @@ use crate::where_condition::WhereCondition;
 /// instead, it captures operations (like comparisons) to build SQL `WHERE` clauses.
 /// When you write `MyModel.id == 5`, an instance of `WhereCondition` is created,
 /// representing the expression `id = 5`.
+///
+/// Columns can carry metadata by assigning an explicit instance in the model body,
+/// e.g. `id: ColumnField[int] = ColumnField(primary_key=True)`. Columns annotated
+/// with an `enum.Enum` subclass (`status: ColumnField[Status]`) store the members'
+/// values and load them back as members.
 #[pyclass(generic)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ColumnField {
+    #[pyo3(get)]
     pub table_name: String,
+    #[pyo3(get)]
     pub column_name: String,
+    /// Marks the column as the model's primary key.
+    #[pyo3(get)]
+    pub primary_key: bool,
+    /// Marks the column as an optimistic-locking version counter.
+    /// It is incremented on every `save()` and checked in the UPDATE's WHERE clause.
+    #[pyo3(get)]
+    pub version: bool,
+    /// The `enum.Enum` subclass of an enum column, taken from the annotation.
+    pub enum_type: Option<Arc<Py<PyType>>>,
+    /// The type of the items of an array column (`ColumnField[list[int]]`),
+    /// taken from the annotation.
+    pub array_type: Option<Arc<Py<PyType>>>,
+    /// The named tuple or dataclass of a composite column, registered with
+    /// `register_type()` and taken from the annotation.
+    pub composite_type: Option<Arc<Py<PyType>>>,
+    /// The type of the bounds of a range column (`ColumnField[Range[int]]`),
+    /// taken from the annotation.
+    pub range_type: Option<Arc<Py<PyType>>>,
+    /// Whether the column holds `datetime.timedelta` values, stored as
+    /// intervals or numbers of seconds.
+    pub interval: bool,
+    /// The model this column references (a foreign key to its primary key).
+    /// `"self"` is replaced by the owning model in `Model.__init_subclass__`.
+    pub references: Option<Arc<Py<PyAny>>>,
+    /// What deleting a referenced row does to the rows referencing it.
+    pub on_delete: OnDelete,
+    /// The SQL expression of a generated column, which the database computes
+    /// and `save()` never writes.
+    #[pyo3(get)]
+    pub generated: Option<String>,
+    /// Whether a generated column's values are stored rather than computed on read.
+    #[pyo3(get)]
+    pub stored: bool,
+}
+
+impl ColumnField {
+    /// Converts a Python value into what is stored in the column.
+    ///
+    /// For enum columns this is the member's value. Raw values are accepted if
+    /// they are the value of a member, so both `Status.PAID` and `"paid"` bind as
+    /// `"paid"`; anything else is rejected. Lists stored in array columns are
+    /// the text of a PostgreSQL array, and composite values the text of a
+    /// record. Other columns return `value` as is.
+    pub(crate) fn db_value<'py>(&self, value: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        let py = value.py();
+        if self.array_type.is_some() && array::is_array(value) {
+            return Ok(PyString::new(py, &array::array_literal(value)?).into_any());
+        }
+        if let Some(composite_type) = &self.composite_type
+            && value.is_instance(composite_type.bind(py))?
+        {
+            let literal = custom_type::composite_literal(value, composite_type.bind(py))?;
+            return Ok(PyString::new(py, &literal).into_any());
+        }
+        let Some(enum_type) = &self.enum_type else {
+            return Ok(value.clone());
+        };
+        if value.is_none() {
+            return Ok(value.clone());
+        }
+        let enum_type = enum_type.bind(py);
+        let member = if value.is_instance(enum_type)? {
+            value.clone()
+        } else {
+            enum_type.call1((value,)).map_err(|_| {
+                let allowed = enum_type
+                    .try_iter()
+                    .and_then(|members| {
+                        members
+                            .map(|m| Ok(m?.getattr("value")?.repr()?.to_string()))
+                            .collect::<PyResult<Vec<_>>>()
+                    })
+                    .unwrap_or_default();
+                FustOrmError::InvalidQueryArgument(format!(
+                    "{} is not a valid {} for column '{}'. Allowed values: {}.",
+                    value.repr().map(|r| r.to_string()).unwrap_or_default(),
+                    enum_type.name().map(|n| n.to_string()).unwrap_or_default(),
+                    self.column_name,
+                    allowed.join(", ")
+                ))
+            })?
+        };
+        member.getattr(pyo3::intern!(py, "value"))
+    }
+
+    /// Converts a value loaded from the column back into a Python value: the
+    /// enum member for enum columns, a list for array columns, an instance of
+    /// the composite type for composite columns, a `Range` for range columns,
+    /// a `datetime.timedelta` for interval columns, `value` itself otherwise.
+    pub(crate) fn py_value<'py>(&self, value: Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        if self.interval && !value.is_none() {
+            return interval::parse_interval(&value);
+        }
+        if let Some(range_type) = &self.range_type
+            && let Ok(text) = value.downcast::<PyString>()
+        {
+            return Ok(range::parse_range(text, Some(range_type.bind(value.py())))?.into_any());
+        }
+        if let Some(array_type) = &self.array_type
+            && let Ok(text) = value.downcast::<PyString>()
+        {
+            return Ok(array::parse_array(text, Some(array_type.bind(value.py())))?.into_any());
+        }
+        if let Some(composite_type) = &self.composite_type
+            && let Ok(text) = value.downcast::<PyString>()
+        {
+            return custom_type::parse_composite(text, composite_type.bind(value.py()));
+        }
+        match &self.enum_type {
+            Some(enum_type) if !value.is_none() => enum_type.bind(value.py()).call1((value,)),
+            _ => Ok(value),
+        }
+    }
 }
 
 /// A helper function to reduce boilerplate when creating WhereCondition instances.
+/// Comparison values are converted with `ColumnField::db_value`.
 fn create_where_condition(
     column_field: &ColumnField,
     operator: &str,
     value: Py<PyAny>,
 ) -> PyResult<WhereCondition> {
+    let value = Python::attach(|py| -> PyResult<Py<PyAny>> {
+        let value = value.bind(py);
+        // A list compared to an array column is one array value.
+        if column_field.array_type.is_some() && operator != "IN" && array::is_array(value) {
+            return Ok(column_field.db_value(value)?.unbind());
+        }
+        if let Some(composite_type) = &column_field.composite_type
+            && value.is_instance(composite_type.bind(py))?
+        {
+            return Ok(column_field.db_value(value)?.unbind());
+        }
+        if column_field.enum_type.is_none()
+            || matches!(operator, "LIKE" | "ILIKE" | "MATCH" | "REGEXP" | "IREGEXP")
+            || value.is_instance_of::<F>()
+            || value.is_instance_of::<ColumnField>()
+        {
+            return Ok(value.clone().unbind());
+        }
+        match value.downcast::<PyList>() {
+            Ok(list) => Ok(PyList::new(
+                py,
+                list.iter()
+                    .map(|item| column_field.db_value(&item))
+                    .collect::<PyResult<Vec<_>>>()?,
+            )?
+            .into_any()
+            .unbind()),
+            Err(_) => Ok(column_field.db_value(value)?.unbind()),
+        }
+    })?;
     Ok(WhereCondition {
         column_name: column_field.column_name.clone(),
         operator: operator.to_string(),
         value: Arc::new(value),
         select_column: false,
+        expression: None,
     })
 }
 
 #[pymethods]
 impl ColumnField {
+    /// Declares a column with explicit metadata.
+    ///
+    /// The table and column names are filled in by `Model.__init_subclass__`
+    /// once the field is attached to a model. `references` makes the column a
+    /// foreign key to another model's primary key (or `"self"`); `on_delete` is
+    /// one of `"restrict"` (the default), `"cascade"`, `"set_null"` and
+    /// `"python"`.
+    #[new]
+    #[pyo3(signature = (
+        *,
+        primary_key = false,
+        version = false,
+        references = None,
+        on_delete = None,
+        generated = None,
+        stored = false,
+    ))]
+    fn new(
+        primary_key: bool,
+        version: bool,
+        references: Option<Py<PyAny>>,
+        on_delete: Option<&str>,
+        generated: Option<String>,
+        stored: bool,
+    ) -> PyResult<Self> {
+        if on_delete.is_some() && references.is_none() {
+            return Err(FustOrmError::BuildError(
+                "on_delete requires the column to reference a model.".to_string(),
+            )
+            .into());
+        }
+        if generated.is_some() && (primary_key || version) {
+            return Err(FustOrmError::BuildError(
+                "A generated column can't be a primary key or version column.".to_string(),
+            )
+            .into());
+        }
+        if stored && generated.is_none() {
+            return Err(FustOrmError::BuildError(
+                "stored requires the column to be generated.".to_string(),
+            )
+            .into());
+        }
+        Ok(ColumnField {
+            primary_key,
+            version,
+            references: references.map(Arc::new),
+            on_delete: on_delete
+                .map(OnDelete::parse)
+                .transpose()?
+                .unwrap_or_default(),
+            generated,
+            stored,
+            ..Default::default()
+        })
+    }
+
+    /// Returns the field itself when accessed on the model class.
+    ///
+    /// Loaded column values are stored on the instance and shadow the field, so
+    /// this is only reached on an instance for a column that wasn't loaded: it
+    /// returns an awaitable loading the deferred columns of the instance, with
+    /// the bound database, and resolving to the value of this one.
+    fn __get__<'py>(
+        slf: &Bound<'py, Self>,
+        instance: Option<&Bound<'py, PyAny>>,
+        _owner: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        match instance {
+            None => Ok(slf.clone().into_any()),
+            Some(instance) => {
+                let deferred = DeferredValue {
+                    instance: instance.clone().unbind(),
+                    column: slf.borrow().column_name.clone(),
+                };
+                Ok(Bound::new(slf.py(), deferred)?.into_any())
+            }
+        }
+    }
+
     // --- Standard Comparison Operators ---
 
     /// Creates an equality condition (`=` or `IS`).
@@ -128,13 +371,80 @@ impl ColumnField {
     }
 
     /// Creates an `ILIKE` condition (case-insensitive pattern matching).
-    /// Note: `ILIKE` is specific to databases like PostgreSQL. For others,
-    /// you might need to use `LOWER(column) LIKE LOWER(pattern)`.
+    /// PostgreSQL's `ILIKE` is rendered as `LOWER(column) LIKE LOWER(pattern)`
+    /// on other databases.
     /// Example: `User.name.ilike("j%")`
     fn ilike(&self, _py: Python, pattern: Py<PyString>) -> PyResult<WhereCondition> {
         create_where_condition(self, "ILIKE", pattern.into())
     }
 
+    /// Creates a condition matching the column against a regular expression:
+    /// `~` on PostgreSQL, `REGEXP` on MySQL and SQLite, where fust-orm defines
+    /// the `regexp()` function it calls with Python's `re`.
+    /// Example: `User.email.regex(r"@example\.(com|org)$")`
+    fn regex(&self, _py: Python, pattern: Py<PyString>) -> PyResult<WhereCondition> {
+        create_where_condition(self, "REGEXP", pattern.into())
+    }
+
+    /// Like `regex()`, ignoring case: `~*` on PostgreSQL, and a pattern with
+    /// the inline `(?i)` flag elsewhere.
+    /// Example: `User.name.iregex("^jo(h)?n$")`
+    fn iregex(&self, _py: Python, pattern: Py<PyString>) -> PyResult<WhereCondition> {
+        create_where_condition(self, "IREGEXP", pattern.into())
+    }
+
+    /// Creates a `MATCH` condition searching the column of an FTS5 full-text
+    /// index (see `FtsTable`).
+    /// Example: `ArticleSearch.title.match("rust AND orm")`
+    #[pyo3(name = "match")]
+    fn match_(&self, _py: Python, query: Py<PyString>) -> PyResult<WhereCondition> {
+        create_where_condition(self, "MATCH", query.into())
+    }
+
+    /// Extracts the value at `path` from the JSON document stored in the
+    /// column, as an `F` expression to compare, select with `annotate()` or
+    /// update with. `path` is a JSON path such as `"$.address.city"`, an
+    /// object key or an array index, and is bound as a parameter. Uses
+    /// SQLite's `->>` operator, or `->` for the value as JSON text if
+    /// `as_json`.
+    /// Example: `User.settings.json("$.theme") == "dark"`
+    #[pyo3(signature = (path, *, as_json = false))]
+    fn json(&self, path: &Bound<PyAny>, as_json: bool) -> PyResult<F> {
+        F::json_path(Expr::Column(self.column_name.clone()), path, as_json)
+    }
+
+    /// Creates a condition checking that the geometry stored in the column is
+    /// within `geometry`: WKT text, a shapely-compatible object with a `wkt`
+    /// attribute, or another geometry column. Geometries given as values are
+    /// in the spatial reference system `srid` if given. Uses `ST_Within()`,
+    /// which requires PostGIS, MySQL or SpatiaLite.
+    /// Example: `Place.location.within("POLYGON ((0 0, 0 1, 1 1, 1 0, 0 0))")`
+    #[pyo3(signature = (geometry, *, srid = None))]
+    fn within(
+        &self,
+        py: Python,
+        geometry: &Bound<PyAny>,
+        srid: Option<u32>,
+    ) -> PyResult<WhereCondition> {
+        let column = Expr::Column(self.column_name.clone());
+        geo::predicate(py, "ST_Within", column, geometry, srid)
+    }
+
+    /// Creates a condition checking that the geometry stored in the column
+    /// shares any point with `geometry` (see `within()`), with
+    /// `ST_Intersects()`.
+    /// Example: `Road.path.intersects(Place.area)`
+    #[pyo3(signature = (geometry, *, srid = None))]
+    fn intersects(
+        &self,
+        py: Python,
+        geometry: &Bound<PyAny>,
+        srid: Option<u32>,
+    ) -> PyResult<WhereCondition> {
+        let column = Expr::Column(self.column_name.clone());
+        geo::predicate(py, "ST_Intersects", column, geometry, srid)
+    }
+
     /// Creates an `IN` condition to check for a value within any iterable.
     /// Example: `User.status.in_(["active", "pending"])`
     /// Example: `User.status.in_({"active", "pending"})`
@@ -147,6 +457,42 @@ impl ColumnField {
         create_where_condition(self, "IN", list_values)
     }
 
+    /// Creates a condition checking that the array stored in the column holds
+    /// `value`, with PostgreSQL's `value = ANY(column)`.
+    /// Example: `Post.tags.any("rust")`
+    fn any(&self, py: Python, value: &Bound<PyAny>) -> PyResult<WhereCondition> {
+        let condition = Expr::Binary {
+            operator: "=",
+            left: Box::new(Expr::from_operand(value)),
+            right: Box::new(Expr::Call {
+                function: "ANY",
+                args: vec![Expr::Column(self.column_name.clone())],
+            }),
+        };
+        Ok(WhereCondition::from_predicate(py, condition))
+    }
+
+    /// Like `in_()`, but binds the values as one array, with PostgreSQL's
+    /// `column = ANY(?)`, so that lists of any length share one statement.
+    /// Example: `User.id.in_array([1, 2, 3])`
+    fn in_array(&self, py: Python, values: &Bound<PyAny>) -> PyResult<WhereCondition> {
+        let values = values
+            .try_iter()
+            .map_err(|_| PyTypeError::new_err("Argument to `in_array` must be an iterable."))?
+            .map(|value| self.db_value(&value?))
+            .collect::<PyResult<Vec<_>>>()?;
+        let values = array::array_literal(PyList::new(py, values)?.as_any())?;
+        let condition = Expr::Binary {
+            operator: "=",
+            left: Box::new(Expr::Column(self.column_name.clone())),
+            right: Box::new(Expr::Call {
+                function: "ANY",
+                args: vec![Expr::Value(Arc::new(values.into_py_any(py)?))],
+            }),
+        };
+        Ok(WhereCondition::from_predicate(py, condition))
+    }
+
     /// Creates an explicit `IS` condition.
     /// This provides a more readable alternative to `==` for some cases.
     /// Example: `User.manager_id.is_(None)`
@@ -167,4 +513,80 @@ impl ColumnField {
     fn __repr__(&self) -> String {
         format!("<ColumnField: {}.{}>", self.table_name, self.column_name)
     }
+
+    /// The `enum.Enum` subclass stored in this column, or `None`.
+    #[getter(enum_type)]
+    fn get_enum_type(&self, py: Python) -> Option<Py<PyType>> {
+        self.enum_type.as_ref().map(|t| t.clone_ref(py))
+    }
+
+    /// The model this column references, or `None`.
+    #[getter(references)]
+    fn get_references(&self, py: Python) -> Option<Py<PyAny>> {
+        self.references.as_ref().map(|t| t.clone_ref(py))
+    }
+
+    /// What deleting a referenced row does to the rows referencing it.
+    #[getter(on_delete)]
+    fn get_on_delete(&self) -> &'static str {
+        self.on_delete.name()
+    }
+
+    /// Renders the foreign key clause for the column's DDL, e.g.
+    /// `REFERENCES author (id) ON DELETE CASCADE`. Python-side cascades have no
+    /// `ON DELETE` action, since the ORM deletes the rows.
+    fn references_sql(&self, py: Python) -> PyResult<String> {
+        let Some(target) = &self.references else {
+            return Err(FustOrmError::BuildError(format!(
+                "Column '{}' doesn't reference a model.",
+                self.column_name
+            ))
+            .into());
+        };
+        let target = target.bind(py).downcast::<PyType>()?;
+        let table = target
+            .getattr(pyo3::intern!(py, "__table_name__"))?
+            .extract::<String>()?;
+        let pk = target
+            .getattr(pyo3::intern!(py, "__primary_key__"))?
+            .extract::<Vec<String>>()?
+            .join(", ");
+        let mut sql = format!("REFERENCES {} ({})", table, pk);
+        if let Some(action) = self.on_delete.sql() {
+            sql.push_str(" ON DELETE ");
+            sql.push_str(action);
+        }
+        Ok(sql)
+    }
+}
+
+/// The value of a deferred column of an instance, loaded by awaiting it:
+///
+///     body = await doc.body
+#[pyclass]
+struct DeferredValue {
+    instance: Py<PyAny>,
+    column: String,
+}
+
+#[pymethods]
+impl DeferredValue {
+    fn __await__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let loaded = self
+            .instance
+            .bind(py)
+            .call_method0(pyo3::intern!(py, "load_deferred"))?;
+        let loaded = pyo3_async_runtimes::tokio::into_future(loaded)?;
+        let instance = self.instance.clone_ref(py);
+        let column = self.column.clone();
+        let value = future_into_py(py, async move {
+            loaded.await?;
+            Python::attach(|py| instance.getattr(py, &column))
+        })?;
+        value.call_method0(pyo3::intern!(py, "__await__"))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<deferred column '{}'>", self.column)
+    }
 }
@@ -0,0 +1,310 @@
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+use pyo3::exceptions::{PyStopIteration, PyValueError};
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3_async_runtimes::tokio::future_into_py;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::database::{Database, begin_write, enter_test_transaction, exit_test_transaction};
+use crate::error::FustOrmError;
+use crate::mock;
+
+/// The `ContextVar` holding the database bound with `bind()` or
+/// `transaction()`.
+static CURRENT: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+
+fn current_var(py: Python<'_>) -> PyResult<&Bound<'_, PyAny>> {
+    CURRENT
+        .get_or_try_init(py, || {
+            let var = py
+                .import("contextvars")?
+                .getattr("ContextVar")?
+                .call1(("fust_orm.database",))?;
+            Ok::<_, PyErr>(var.unbind())
+        })
+        .map(|var| var.bind(py))
+}
+
+/// The database bound in the current context, if any.
+fn current(py: Python) -> PyResult<Option<Database>> {
+    let db = current_var(py)?.call_method1("get", (py.None(),))?;
+    if db.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(db.extract::<PyRef<Database>>()?.clone()))
+}
+
+/// Binds `db` in the current context, returning the token resetting it.
+fn set_current(py: Python, db: &Bound<PyAny>) -> PyResult<Py<PyAny>> {
+    Ok(current_var(py)?.call_method1("set", (db,))?.unbind())
+}
+
+fn reset_current(py: Python, token: Option<Py<PyAny>>) -> PyResult<()> {
+    let Some(token) = token else {
+        return Ok(());
+    };
+    match current_var(py)?.call_method1("reset", (token,)) {
+        // Exiting in another context than the one entered, as pytest-asyncio
+        // may run the teardown of a fixture, leaves that one's binding alone.
+        Err(e) if e.is_instance_of::<PyValueError>(py) => Ok(()),
+        result => result.map(drop),
+    }
+}
+
+/// The database a method runs its statements on: the bound one when `db` is
+/// left out, or `db`, which joins the bound transaction when it is one of
+/// the same database.
+pub(crate) fn resolve(py: Python, db: Option<&Database>) -> PyResult<Database> {
+    let bound = current(py)?;
+    match (db, bound) {
+        (Some(db), Some(bound))
+            if db.tx.is_none() && bound.tx.is_some() && db.same_pool(&bound) =>
+        {
+            // A tenant filter of `db` applies in the transaction too.
+            Ok(Database {
+                tenant: bound.tenant.or_else(|| db.tenant.clone()),
+                ..bound
+            })
+        }
+        (Some(db), _) => Ok(db.clone()),
+        (None, Some(bound)) => Ok(bound),
+        (None, None) => Err(FustOrmError::InvalidQueryArgument(
+            "No database was passed or bound with fust_orm.bind().".to_string(),
+        )
+        .into()),
+    }
+}
+
+/// Binds `db` in the current context, so that model and query methods use it
+/// when called without a database.
+///
+/// Returns a context manager that restores the previous binding on exit:
+///
+///     with fust_orm.bind(db):
+///         await user.save()
+#[pyfunction]
+pub(crate) fn bind(db: Database) -> Binding {
+    Binding {
+        db,
+        token: Mutex::new(None),
+    }
+}
+
+/// The context manager returned by `bind()`.
+#[pyclass(module = "fust_orm")]
+pub struct Binding {
+    db: Database,
+    token: Mutex<Option<Py<PyAny>>>,
+}
+
+#[pymethods]
+impl Binding {
+    fn __enter__(&self, py: Python) -> PyResult<Py<Database>> {
+        let db = Py::new(py, self.db.clone())?;
+        let token = set_current(py, db.bind(py).as_any())?;
+        *self.token.lock().unwrap_or_else(|e| e.into_inner()) = Some(token);
+        Ok(db)
+    }
+
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        py: Python,
+        _exc_type: Option<&Bound<PyAny>>,
+        _exc_value: Option<&Bound<PyAny>>,
+        _traceback: Option<&Bound<PyAny>>,
+    ) -> PyResult<bool> {
+        let token = self.token.lock().unwrap_or_else(|e| e.into_inner()).take();
+        reset_current(py, token)?;
+        Ok(false)
+    }
+}
+
+/// The async context manager returned by `Database.transaction()`.
+///
+/// Entering it begins a transaction and binds a database running its
+/// statements in it, which it returns. Inside a transaction of the same
+/// database it joins that one instead, which its owner commits, or opens a
+/// savepoint of it if it's a test transaction.
+#[pyclass(module = "fust_orm")]
+pub struct Transaction {
+    db: Database,
+    /// Whether it's a test transaction from `test_database()`, begun even
+    /// inside another one, nesting the ones joining it in savepoints and
+    /// always rolled back.
+    test: bool,
+    /// The database bound to the transaction, and whether this context
+    /// manager began it, once entered.
+    entered: Mutex<Option<(Database, bool)>>,
+    token: Arc<Mutex<Option<Py<PyAny>>>>,
+}
+
+impl Transaction {
+    pub(crate) fn new(db: Database) -> Transaction {
+        Transaction {
+            db,
+            test: false,
+            entered: Mutex::new(None),
+            token: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+/// Returns an async context manager running the statements of `db` in a
+/// transaction rolled back on exit, for isolating tests from each other
+/// without recreating the schema:
+///
+///     async with fust_orm.testing.test_database(db) as tx_db:
+///         ...
+///
+/// It binds the transaction like `transaction()` does, and the
+/// `transaction()` blocks entered meanwhile are savepoints of it, which
+/// commit or roll back on their own, as are the transactions begun on the
+/// pool without joining it.
+#[pyfunction]
+pub(crate) fn test_database(db: Database) -> Transaction {
+    Transaction {
+        test: true,
+        ..Transaction::new(db)
+    }
+}
+
+#[pymethods]
+impl Transaction {
+    fn __aenter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let joined = resolve(py, Some(&self.db))?;
+        let (tx_db, owned) = match joined.tx {
+            Some(_) if !self.test => (joined, false),
+            // A mock has no transactions to begin; its statements are
+            // answered the same either way.
+            None if mock::is_mocked(&joined.pool) => (joined, false),
+            _ => {
+                let shared = Arc::new(AsyncMutex::new(None));
+                let tx_db = Database {
+                    pool: joined.pool.clone(),
+                    tx: Some(shared),
+                    tenant: joined.tenant.clone(),
+                    retry: joined.retry.clone(),
+                    registration: joined.registration.clone(),
+                };
+                (tx_db, true)
+            }
+        };
+        *self.entered.lock().unwrap_or_else(|e| e.into_inner()) = Some((tx_db.clone(), owned));
+        let db = tx_db.clone();
+        let test = self.test;
+        let future = future_into_py(py, async move {
+            if owned {
+                let mut tx = begin_write(&db.pool).await?;
+                let shared = db.tx.as_ref().expect("owned transaction");
+                if test {
+                    tx.nest_in_savepoints();
+                    enter_test_transaction(&db.pool, shared);
+                }
+                *shared.lock().await = Some(tx);
+                debug!("Began a transaction");
+            } else {
+                db.open_savepoint().await?;
+            }
+            Ok(db)
+        })?;
+        let awaiter = Entering {
+            awaited: future.call_method0("__await__")?.unbind(),
+            token: self.token.clone(),
+        };
+        Ok(Bound::new(py, awaiter)?.into_any())
+    }
+
+    /// Commits the transaction when the block exits normally and rolls it
+    /// back when it raises; a joined transaction is left to its owner, and a
+    /// test transaction always rolled back.
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __aexit__<'py>(
+        &self,
+        py: Python<'py>,
+        exc_type: Option<&Bound<'py, PyAny>>,
+        _exc_value: Option<&Bound<'py, PyAny>>,
+        _traceback: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let token = self.token.lock().unwrap_or_else(|e| e.into_inner()).take();
+        reset_current(py, token)?;
+        let entered = self
+            .entered
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let commit = exc_type.is_none() && !self.test;
+        let savepoint = exc_type.is_none();
+        let test = self.test;
+        future_into_py(py, async move {
+            let (db, owned) = match entered {
+                Some(entered) => entered,
+                None => return Ok(false),
+            };
+            let (Some(shared), true) = (&db.tx, owned) else {
+                db.close_savepoint(savepoint).await?;
+                return Ok(false);
+            };
+            if test {
+                exit_test_transaction(shared);
+            }
+            let tx = shared.lock().await.take();
+            match tx {
+                Some(tx) if commit => tx.commit().await.map_err(FustOrmError::from)?,
+                // Dropping the transaction rolls it back.
+                _ => debug!("Rolling back a transaction"),
+            }
+            Ok(false)
+        })
+    }
+}
+
+/// Awaits the future beginning a transaction, then binds the database it
+/// resolves to in the context awaiting it, which a callback of the future
+/// couldn't.
+#[pyclass]
+struct Entering {
+    awaited: Py<PyAny>,
+    token: Arc<Mutex<Option<Py<PyAny>>>>,
+}
+
+#[pymethods]
+impl Entering {
+    fn __await__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __iter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        match self.awaited.bind(py).call_method0("__next__") {
+            Err(e) if e.is_instance_of::<PyStopIteration>(py) => {
+                let db = e.value(py).getattr("value")?;
+                let token = set_current(py, &db)?;
+                *self.token.lock().unwrap_or_else(|e| e.into_inner()) = Some(token);
+                Err(PyStopIteration::new_err((db.unbind(),)))
+            }
+            result => result.map(Bound::unbind),
+        }
+    }
+
+    fn send(&self, py: Python, value: &Bound<PyAny>) -> PyResult<Py<PyAny>> {
+        if value.is_none() {
+            return self.__next__(py);
+        }
+        Ok(self
+            .awaited
+            .bind(py)
+            .call_method1("send", (value,))?
+            .unbind())
+    }
+
+    #[pyo3(signature = (*args))]
+    fn throw(&self, py: Python, args: &Bound<pyo3::types::PyTuple>) -> PyResult<Py<PyAny>> {
+        Ok(self.awaited.bind(py).call_method1("throw", args)?.unbind())
+    }
+}
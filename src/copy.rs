@@ -0,0 +1,309 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use futures_util::StreamExt;
+use log::debug;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyByteArray, PyBytes, PyDict, PyIterator, PyList, PyString};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::Connection;
+use sqlx::postgres::PgConnection;
+
+use crate::audit;
+use crate::context;
+use crate::database::Database;
+use crate::error::FustOrmError;
+use crate::notify::require_postgres;
+use crate::query::QueryBuilder;
+use crate::schema::table_name;
+
+/// How much data is read from a source or encoded from rows before it is
+/// sent to the server.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn io_error(path: &std::path::Path, e: std::io::Error) -> FustOrmError {
+    FustOrmError::BuildError(format!("Can't access '{}': {}", path.display(), e))
+}
+
+/// The `WITH (...)` options of a `COPY` statement.
+fn copy_options(format: &str, header: bool) -> Result<String, FustOrmError> {
+    if !["csv", "text", "binary"].contains(&format) {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "Unknown COPY format '{}': expected 'csv', 'text' or 'binary'.",
+            format
+        )));
+    }
+    if header && format != "csv" {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "A header is only supported in the 'csv' format.".to_string(),
+        ));
+    }
+    Ok(format!(
+        "(FORMAT {}{})",
+        format,
+        if header { ", HEADER true" } else { "" }
+    ))
+}
+
+/// The data `copy_in()` sends.
+enum Source {
+    /// An iterator over rows, sent as CSV.
+    Rows(Py<PyIterator>),
+    /// A file object read in chunks.
+    File(Py<PyAny>),
+    Path(PathBuf, File),
+}
+
+impl Source {
+    fn extract(source: &Bound<PyAny>) -> PyResult<Self> {
+        if source.is_instance_of::<PyString>() || source.hasattr("__fspath__")? {
+            let path: PathBuf = source.extract()?;
+            let file = File::open(&path).map_err(|e| io_error(&path, e))?;
+            return Ok(Source::Path(path, file));
+        }
+        if source.hasattr("read")? {
+            return Ok(Source::File(source.clone().unbind()));
+        }
+        Ok(Source::Rows(source.try_iter()?.unbind()))
+    }
+}
+
+/// Where `copy_out()` writes the data.
+enum Sink {
+    /// A file object, written `str` if it is a text file and `bytes`
+    /// otherwise.
+    File {
+        file: Py<PyAny>,
+        text: bool,
+    },
+    Path(PathBuf, File),
+}
+
+impl Sink {
+    fn extract(file: &Bound<PyAny>) -> PyResult<Self> {
+        if file.is_instance_of::<PyString>() || file.hasattr("__fspath__")? {
+            let path: PathBuf = file.extract()?;
+            let created = File::create(&path).map_err(|e| io_error(&path, e))?;
+            return Ok(Sink::Path(path, created));
+        }
+        let text_io = file.py().import("io")?.getattr("TextIOBase")?;
+        Ok(Sink::File {
+            file: file.clone().unbind(),
+            text: file.is_instance(&text_io)?,
+        })
+    }
+}
+
+/// A value of a row as a CSV field, quoted so that only `None` is read as
+/// `NULL`.
+fn csv_field(py: Python, value: &Bound<PyAny>, out: &mut String) -> PyResult<()> {
+    if value.is_none() {
+        return Ok(());
+    }
+    let text = if let Ok(b) = value.downcast::<PyBool>() {
+        (if b.is_true() { "t" } else { "f" }).to_string()
+    } else if let Ok(bytes) = value.downcast::<PyBytes>() {
+        format!("\\x{}", hex(bytes.as_bytes()))
+    } else if let Ok(bytes) = value.downcast::<PyByteArray>() {
+        format!("\\x{}", hex(&bytes.to_vec()))
+    } else if value.is_instance_of::<PyDict>() || value.is_instance_of::<PyList>() {
+        py.import("json")?
+            .call_method1("dumps", (value,))?
+            .extract()?
+    } else {
+        value.str()?.to_string()
+    };
+    out.push('"');
+    out.push_str(&text.replace('"', "\"\""));
+    out.push('"');
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Encodes the next rows of `rows` as CSV, up to about `CHUNK_SIZE` bytes.
+/// Returns nothing once the rows are exhausted.
+fn next_rows(py: Python, rows: &Py<PyIterator>) -> PyResult<Option<String>> {
+    let mut rows = rows.bind(py).clone();
+    let mut out = String::new();
+    while out.len() < CHUNK_SIZE {
+        let Some(row) = rows.next() else {
+            break;
+        };
+        let row = row?;
+        if row.is_instance_of::<PyString>() || row.is_instance_of::<PyDict>() {
+            return Err(PyTypeError::new_err(format!(
+                "copy_in() takes rows as sequences of values, not {}.",
+                row.get_type().name()?
+            )));
+        }
+        for (i, value) in row.try_iter()?.enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            csv_field(py, &value?, &mut out)?;
+        }
+        out.push('\n');
+    }
+    Ok((!out.is_empty()).then_some(out))
+}
+
+/// Reads the next chunk of the file object `file`, encoding text as UTF-8.
+fn read_chunk(py: Python, file: &Py<PyAny>) -> PyResult<Vec<u8>> {
+    let data = file.bind(py).call_method1("read", (CHUNK_SIZE,))?;
+    if let Ok(text) = data.downcast::<PyString>() {
+        return Ok(text.to_str()?.as_bytes().to_vec());
+    }
+    data.extract()
+}
+
+/// Connects to the PostgreSQL database of `db` outside of its pool, as the
+/// `Any` driver doesn't speak the COPY protocol.
+async fn connect(url: &str) -> Result<PgConnection, FustOrmError> {
+    PgConnection::connect(url)
+        .await
+        .map_err(FustOrmError::connection)
+}
+
+/// Loads `source` into `table` with `COPY ... FROM STDIN`: rows given as an
+/// iterable of sequences, or the data of a file object or path in `format`.
+/// Resolves to the number of rows loaded. It fails in a transaction, which
+/// its connection of its own would escape.
+pub(crate) fn copy_in<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<PyAny>,
+    source: &Bound<PyAny>,
+    columns: Option<Vec<String>>,
+    format: &str,
+    header: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    if context::resolve(py, Some(db))?.tx.is_some() {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "copy_in() runs on a connection of its own, so it can't load rows in a transaction."
+                .to_string(),
+        )
+        .into());
+    }
+    require_postgres(db, "copy_in")?;
+    let mut source = Source::extract(source)?;
+    let options = match source {
+        Source::Rows(_) => copy_options("csv", false)?,
+        _ => copy_options(format, header)?,
+    };
+    let columns = columns
+        .map(|columns| format!(" ({})", columns.join(", ")))
+        .unwrap_or_default();
+    let sql = format!(
+        "COPY {}{} FROM STDIN WITH {}",
+        table_name(table)?,
+        columns,
+        options
+    );
+    let url = db.pool.connect_options().database_url.to_string();
+    future_into_py(py, async move {
+        let mut conn = connect(&url).await?;
+        // The audit record of the COPY commits with its rows.
+        let mut tx = conn.begin().await.map_err(FustOrmError::from)?;
+        debug!("Copying in: {}", sql);
+        let mut copy = tx.copy_in_raw(&sql).await.map_err(FustOrmError::from)?;
+        loop {
+            let chunk = match &mut source {
+                Source::Rows(rows) => Python::attach(|py| next_rows(py, rows))
+                    .map(|rows| rows.map(String::into_bytes).unwrap_or_default()),
+                Source::File(file) => Python::attach(|py| read_chunk(py, file)),
+                Source::Path(path, file) => {
+                    let mut buffer = vec![0; CHUNK_SIZE];
+                    match file.read(&mut buffer) {
+                        Ok(read) => {
+                            buffer.truncate(read);
+                            Ok(buffer)
+                        }
+                        Err(e) => Err(io_error(path, e).into()),
+                    }
+                }
+            };
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    copy.abort("the data couldn't be read")
+                        .await
+                        .map_err(FustOrmError::from)?;
+                    return Err(e);
+                }
+            };
+            if chunk.is_empty() {
+                break;
+            }
+            copy.send(chunk).await.map_err(FustOrmError::from)?;
+        }
+        let rows = copy.finish().await.map_err(FustOrmError::from)?;
+        audit::record_copy(&mut tx, &sql, rows).await?;
+        tx.commit().await.map_err(FustOrmError::from)?;
+        conn.close().await.ok();
+        Ok(rows)
+    })
+}
+
+/// Writes the rows of `query` (SQL text, a `QueryBuilder` without
+/// parameters, or a table) to `file` (a file object or a path) with
+/// `COPY ... TO STDOUT`, in `format`.
+pub(crate) fn copy_out<'py>(
+    py: Python<'py>,
+    db: &Database,
+    query: &Bound<PyAny>,
+    file: &Bound<PyAny>,
+    format: &str,
+    header: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    require_postgres(db, "copy_out")?;
+    let source = if let Ok(sql) = query.downcast::<PyString>() {
+        format!("({})", sql)
+    } else if let Ok(query) = query.downcast::<QueryBuilder>() {
+        let (sql, params) = query.borrow().build(py, db)?;
+        if !params.is_empty() {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "COPY can't bind parameters: copy_out() takes a query without any.".to_string(),
+            )
+            .into());
+        }
+        format!("({})", sql)
+    } else {
+        table_name(query)?
+    };
+    let sql = format!(
+        "COPY {} TO STDOUT WITH {}",
+        source,
+        copy_options(format, header)?
+    );
+    let mut sink = Sink::extract(file)?;
+    let url = db.pool.connect_options().database_url.to_string();
+    future_into_py(py, async move {
+        let mut conn = connect(&url).await?;
+        debug!("Copying out: {}", sql);
+        let mut stream = conn.copy_out_raw(&sql).await.map_err(FustOrmError::from)?;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(FustOrmError::from)?;
+            match &mut sink {
+                Sink::File { file, text } => Python::attach(|py| {
+                    // Each chunk holds whole rows, so decoding it on its own
+                    // is safe.
+                    let data = if *text {
+                        PyString::new(py, &String::from_utf8_lossy(&chunk)).into_any()
+                    } else {
+                        PyBytes::new(py, &chunk).into_any()
+                    };
+                    file.bind(py).call_method1("write", (data,)).map(drop)
+                })?,
+                Sink::Path(path, file) => file.write_all(&chunk).map_err(|e| io_error(path, e))?,
+            }
+        }
+        drop(stream);
+        conn.close().await.ok();
+        Ok(())
+    })
+}
@@ -0,0 +1,267 @@
+use heck::ToSnakeCase;
+use log::debug;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyString, PyType};
+use pyo3_async_runtimes::tokio::future_into_py;
+
+use crate::array::from_text;
+use crate::database::Database;
+use crate::error::FustOrmError;
+use crate::notify::require_postgres;
+use crate::schema::{ColumnType, Dialect};
+
+/// Whether `cls` is an `enum.Enum` subclass.
+fn is_enum(cls: &Bound<PyType>) -> PyResult<bool> {
+    let enum_base = cls.py().import("enum")?.getattr("Enum")?;
+    cls.is_subclass(enum_base.downcast::<PyType>()?)
+}
+
+/// The name of the PostgreSQL type `cls` was registered as, if any.
+pub(crate) fn type_name(cls: &Bound<PyType>) -> PyResult<Option<String>> {
+    match cls.getattr(pyo3::intern!(cls.py(), "__type_name__")) {
+        Ok(name) => name.extract().map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Whether `cls` was registered as a composite type.
+pub(crate) fn is_composite(cls: &Bound<PyType>) -> PyResult<bool> {
+    Ok(type_name(cls)?.is_some() && !is_enum(cls)?)
+}
+
+/// The fields of a named tuple or dataclass, in order, with their annotated
+/// types if they are classes.
+fn fields<'py>(cls: &Bound<'py, PyType>) -> PyResult<Vec<(String, Option<Bound<'py, PyType>>)>> {
+    let py = cls.py();
+    let names: Vec<String> = match cls.getattr(pyo3::intern!(py, "_fields")) {
+        Ok(fields) => fields.extract()?,
+        Err(_) => py
+            .import("dataclasses")?
+            .call_method1("fields", (cls,))?
+            .try_iter()?
+            .map(|field| field?.getattr("name")?.extract())
+            .collect::<PyResult<_>>()?,
+    };
+    let typing = py.import("typing")?;
+    let hints = typing.call_method1("get_type_hints", (cls,))?;
+    let none_type = py.None().bind(py).get_type();
+    names
+        .into_iter()
+        .map(|name| {
+            let Ok(mut hint) = hints.get_item(&name) else {
+                return Ok((name, None));
+            };
+            // `str | None` fields hold text too.
+            let args = typing.call_method1("get_args", (&hint,))?;
+            if !typing.call_method1("get_origin", (&hint,))?.is_none()
+                && args.len()? == 2
+                && args.contains(&none_type)?
+            {
+                for arg in args.try_iter()? {
+                    let arg = arg?;
+                    if !arg.is(&none_type) {
+                        hint = arg;
+                    }
+                }
+            }
+            Ok((name, hint.downcast_into::<PyType>().ok()))
+        })
+        .collect()
+}
+
+/// Registers `python_type` as the Python side of the PostgreSQL type `name`
+/// (the snake_case class name by default): an `enum.Enum` subclass with text
+/// values for an `ENUM` type, a named tuple or dataclass for a composite
+/// type. Model columns annotated with it are loaded as its instances. Returns
+/// the class, so that it can be used as a decorator.
+#[pyfunction]
+#[pyo3(signature = (python_type, name = None))]
+pub fn register_type<'py>(
+    python_type: &Bound<'py, PyType>,
+    name: Option<String>,
+) -> PyResult<Bound<'py, PyType>> {
+    let py = python_type.py();
+    if is_enum(python_type)? {
+        for member in python_type.try_iter()? {
+            if !member?.getattr("value")?.is_instance_of::<PyString>() {
+                return Err(PyTypeError::new_err(format!(
+                    "The members of enum '{}' must have text values to be a PostgreSQL enum.",
+                    python_type.name()?
+                )));
+            }
+        }
+    } else if !python_type.hasattr("_fields")?
+        && !py
+            .import("dataclasses")?
+            .call_method1("is_dataclass", (python_type,))?
+            .is_truthy()?
+    {
+        return Err(PyTypeError::new_err(format!(
+            "register_type() takes an enum, a named tuple or a dataclass, not '{}'.",
+            python_type.name()?
+        )));
+    }
+    let name = match name {
+        Some(name) => name,
+        None => python_type.name()?.to_string().to_snake_case(),
+    };
+    python_type.setattr(pyo3::intern!(py, "__type_name__"), name)?;
+    Ok(python_type.clone())
+}
+
+/// Quotes a field of a composite value.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\"\""))
+}
+
+/// Renders an instance of a registered composite type as the text of a
+/// PostgreSQL record, e.g. `("1 Main St","Oslo",)`, which it is bound as and
+/// other databases store.
+pub(crate) fn composite_literal(value: &Bound<PyAny>, cls: &Bound<PyType>) -> PyResult<String> {
+    let mut items = Vec::new();
+    for (name, _) in fields(cls)? {
+        let item = value.getattr(name.as_str())?;
+        items.push(if item.is_none() {
+            String::new()
+        } else if let Ok(flag) = item.downcast::<PyBool>() {
+            (if flag.is_true() { "t" } else { "f" }).to_string()
+        } else {
+            quote(&item.str()?.to_string())
+        });
+    }
+    Ok(format!("({})", items.join(",")))
+}
+
+/// Splits the text of a PostgreSQL record into its fields, `None` for `NULL`.
+fn split_record(text: &str) -> Result<Vec<Option<String>>, FustOrmError> {
+    let malformed = || FustOrmError::QueryError(format!("Malformed record '{}'.", text));
+    let inner = text
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .ok_or_else(malformed)?;
+    let mut fields = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        let mut field = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next_if(|c| *c != ',') {
+            match c {
+                '"' => {
+                    quoted = true;
+                    loop {
+                        match chars.next().ok_or_else(malformed)? {
+                            '"' if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                            '"' => break,
+                            '\\' => field.push(chars.next().ok_or_else(malformed)?),
+                            c => field.push(c),
+                        }
+                    }
+                }
+                '\\' => field.push(chars.next().ok_or_else(malformed)?),
+                c => field.push(c),
+            }
+        }
+        fields.push((quoted || !field.is_empty()).then_some(field));
+        if chars.next().is_none() {
+            return Ok(fields);
+        }
+    }
+}
+
+/// Parses the text of a PostgreSQL record into an instance of the composite
+/// type `cls`, converting each field to its annotated type.
+pub(crate) fn parse_composite<'py>(
+    text: &Bound<'py, PyString>,
+    cls: &Bound<'py, PyType>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = text.py();
+    let values = split_record(text.to_str()?)?;
+    let fields = fields(cls)?;
+    if values.len() != fields.len() {
+        return Err(FustOrmError::QueryError(format!(
+            "A record of {} fields can't be loaded as '{}', which has {}.",
+            values.len(),
+            cls.name()?,
+            fields.len()
+        ))
+        .into());
+    }
+    let args = values
+        .into_iter()
+        .zip(&fields)
+        .map(|(value, (_, field_type))| match value {
+            Some(text) => from_text(py, text, field_type.as_ref()),
+            None => Ok(py.None().into_bound(py)),
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    cls.call1(pyo3::types::PyTuple::new(py, args)?)
+}
+
+/// Renders the `CREATE TYPE` statement of the registered type `cls`.
+fn create_type_sql(cls: &Bound<PyType>) -> PyResult<String> {
+    let Some(name) = type_name(cls)? else {
+        return Err(FustOrmError::BuildError(format!(
+            "'{}' isn't registered with register_type().",
+            cls.name()?
+        ))
+        .into());
+    };
+    if is_enum(cls)? {
+        let labels = cls
+            .try_iter()?
+            .map(|member| {
+                let value: String = member?.getattr("value")?.extract()?;
+                Ok(format!("'{}'", value.replace('\'', "''")))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(format!(
+            "CREATE TYPE {} AS ENUM ({})",
+            name,
+            labels.join(", ")
+        ));
+    }
+    let attributes = fields(cls)?
+        .into_iter()
+        .map(|(field, field_type)| {
+            let column_type = match &field_type {
+                Some(field_type) => ColumnType::for_type(field_type)?,
+                None => None,
+            };
+            let Some(column_type) = column_type else {
+                return Err(FustOrmError::BuildError(format!(
+                    "Can't infer the PostgreSQL type of field '{}' of '{}'.",
+                    field,
+                    cls.name()?
+                ))
+                .into());
+            };
+            Ok(format!(
+                "{} {}",
+                field,
+                column_type.to_sql(Dialect::Postgres)
+            ))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(format!(
+        "CREATE TYPE {} AS ({})",
+        name,
+        attributes.join(", ")
+    ))
+}
+
+/// Creates the PostgreSQL type `python_type` was registered as.
+pub(crate) fn create_type<'py>(
+    py: Python<'py>,
+    db: &Database,
+    python_type: &Bound<PyType>,
+) -> PyResult<Bound<'py, PyAny>> {
+    require_postgres(db, "create_type")?;
+    let sql = create_type_sql(python_type)?;
+    let db = db.clone();
+    future_into_py(py, async move {
+        debug!("Creating type: {}", sql);
+        db.execute_statement(&sql, Vec::new()).await?;
+        Ok(())
+    })
+}
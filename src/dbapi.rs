@@ -0,0 +1,401 @@
+use std::sync::{Mutex, MutexGuard};
+
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyTuple};
+use sqlx::{AnyConnection, AnyPool, Column, Executor, TypeInfo};
+
+use crate::database::{
+    WriteTransaction, acquire, begin_write, block_on, connect_pool, execute_statement, fetch_rows,
+};
+use crate::decode::RowBatch;
+use crate::error::FustOrmError;
+use crate::query::raw_params;
+
+/// A DB-API 2.0 (PEP 249) connection, exposed as `fust_orm.dbapi.Connection`.
+///
+/// Like `sqlite3`, a transaction is begun before the first statement that
+/// modifies data and lasts until `commit()` or `rollback()`; the statements
+/// in between run on its connection. With `autocommit`, every statement is
+/// committed on its own.
+#[pyclass(name = "Connection", module = "fust_orm.dbapi")]
+pub struct DbApiConnection {
+    /// `None` once the connection is closed.
+    pool: Mutex<Option<AnyPool>>,
+    tx: Mutex<Option<WriteTransaction>>,
+    #[pyo3(get)]
+    autocommit: bool,
+}
+
+/// What running a statement produced.
+struct Outcome {
+    description: Option<Vec<Column7>>,
+    rows: RowBatch,
+    rowcount: i64,
+    lastrowid: Option<i64>,
+}
+
+/// A column of `Cursor.description`: its name, the name of its type and
+/// whether it may be NULL, as far as the driver knows.
+type Column7 = (String, String, Option<bool>);
+
+/// Whether `sql` modifies data, which begins a transaction.
+pub(crate) fn modifies_data(sql: &str) -> bool {
+    let verb = sql.split_whitespace().next().unwrap_or_default();
+    ["INSERT", "UPDATE", "DELETE", "REPLACE", "MERGE"]
+        .iter()
+        .any(|v| verb.eq_ignore_ascii_case(v))
+}
+
+/// Runs a statement on `conn`: statements producing columns are fetched,
+/// others report the rows they affected.
+async fn run(
+    conn: &mut AnyConnection,
+    sql: &str,
+    params: Vec<String>,
+) -> Result<Outcome, FustOrmError> {
+    let describe = conn
+        .describe(sql)
+        .await
+        .map_err(|e| FustOrmError::from(e).with_statement(sql, &params))?;
+    if describe.columns().is_empty() {
+        let result = execute_statement(&mut *conn, sql, params).await?;
+        let mut lastrowid = result.last_insert_id();
+        // The Any driver doesn't report the rowid SQLite inserted at.
+        if lastrowid.is_none() && conn.backend_name() == "SQLite" && modifies_data(sql) {
+            lastrowid = sqlx::query_scalar("SELECT last_insert_rowid()")
+                .fetch_one(&mut *conn)
+                .await
+                .map_err(FustOrmError::from)?;
+        }
+        return Ok(Outcome {
+            description: None,
+            rows: RowBatch::decode(&[])?,
+            rowcount: result.rows_affected() as i64,
+            lastrowid,
+        });
+    }
+    let description = describe
+        .columns()
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let type_name = c.type_info().name().to_string();
+            (c.name().to_string(), type_name, describe.nullable(i))
+        })
+        .collect();
+    let rows = RowBatch::decode(&fetch_rows(&mut *conn, sql, params).await?)?;
+    Ok(Outcome {
+        description: Some(description),
+        rowcount: rows.len() as i64,
+        rows,
+        lastrowid: None,
+    })
+}
+
+impl DbApiConnection {
+    fn tx(&self) -> MutexGuard<'_, Option<WriteTransaction>> {
+        self.tx.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn open_pool(&self) -> Result<AnyPool, FustOrmError> {
+        self.pool
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or_else(|| FustOrmError::ConnectionError("The connection is closed.".to_string()))
+    }
+
+    /// Runs a statement, in the open transaction if there is one.
+    fn run(&self, py: Python, sql: String, params: Vec<String>) -> Result<Outcome, FustOrmError> {
+        let pool = self.open_pool()?;
+        let mut tx = self.tx();
+        if tx.is_none() && !self.autocommit && modifies_data(&sql) {
+            debug!("Beginning a transaction");
+            let begin = pool.clone();
+            *tx = Some(block_on(py, async move { begin_write(&begin).await })?);
+        }
+        let tx = tx.as_mut();
+        block_on(py, async move {
+            match tx {
+                Some(tx) => run(tx, &sql, params).await,
+                None => {
+                    let mut conn = acquire(&pool).await.map_err(FustOrmError::from)?;
+                    run(&mut conn, &sql, params).await
+                }
+            }
+        })
+    }
+}
+
+#[pymethods]
+impl DbApiConnection {
+    /// Connects to the database at `db_url`.
+    #[new]
+    #[pyo3(signature = (db_url, *, autocommit = false))]
+    fn new(py: Python, db_url: String, autocommit: bool) -> PyResult<DbApiConnection> {
+        let pool = block_on(py, connect_pool(&db_url, false, None, &[]))?;
+        Ok(DbApiConnection {
+            pool: Mutex::new(Some(pool)),
+            tx: Mutex::new(None),
+            autocommit,
+        })
+    }
+
+    fn cursor(slf: Bound<Self>) -> PyResult<DbApiCursor> {
+        slf.borrow().open_pool()?;
+        Ok(DbApiCursor::new(slf.unbind()))
+    }
+
+    /// Runs a statement on a new cursor and returns it.
+    #[pyo3(signature = (operation, parameters = None))]
+    fn execute(
+        slf: Bound<Self>,
+        operation: String,
+        parameters: Option<Vec<Py<PyAny>>>,
+    ) -> PyResult<Py<DbApiCursor>> {
+        let cursor = Bound::new(slf.py(), DbApiConnection::cursor(slf)?)?;
+        DbApiCursor::execute(cursor.borrow_mut(), operation, parameters)
+    }
+
+    /// Commits the open transaction, if there is one.
+    fn commit(&self, py: Python) -> PyResult<()> {
+        self.open_pool()?;
+        if let Some(tx) = self.tx().take() {
+            block_on(
+                py,
+                async move { tx.commit().await.map_err(FustOrmError::from) },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Rolls the open transaction back, if there is one.
+    fn rollback(&self, py: Python) -> PyResult<()> {
+        self.open_pool()?;
+        let tx = self.tx().take();
+        // Dropped in the runtime, which the connection is returned to the
+        // pool on.
+        block_on(py, async move {
+            drop(tx);
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Rolls back the open transaction and closes the pool. Using the
+    /// connection afterwards raises `InterfaceError`.
+    fn close(&self, py: Python) -> PyResult<()> {
+        let tx = self.tx().take();
+        let pool = self.pool.lock().unwrap_or_else(|e| e.into_inner()).take();
+        block_on(py, async move {
+            drop(tx);
+            if let Some(pool) = pool {
+                pool.close().await;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn __enter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    /// Commits when the block exits normally and rolls back when it raises,
+    /// leaving the connection open.
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        py: Python,
+        exc_type: Option<&Bound<PyAny>>,
+        _exc_value: Option<&Bound<PyAny>>,
+        _traceback: Option<&Bound<PyAny>>,
+    ) -> PyResult<bool> {
+        match exc_type {
+            None => self.commit(py)?,
+            Some(_) => self.rollback(py)?,
+        }
+        Ok(false)
+    }
+}
+
+impl Drop for DbApiConnection {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx().take() {
+            let _runtime = pyo3_async_runtimes::tokio::get_runtime().enter();
+            drop(tx);
+        }
+    }
+}
+
+/// A DB-API 2.0 cursor, exposed as `fust_orm.dbapi.Cursor`.
+#[pyclass(name = "Cursor", module = "fust_orm.dbapi")]
+pub struct DbApiCursor {
+    #[pyo3(get)]
+    connection: Py<DbApiConnection>,
+    #[pyo3(get, set)]
+    arraysize: usize,
+    #[pyo3(get)]
+    rowcount: i64,
+    #[pyo3(get)]
+    lastrowid: Option<i64>,
+    description: Option<Vec<Column7>>,
+    rows: Option<RowBatch>,
+    /// The index of the next row to fetch.
+    position: usize,
+    closed: bool,
+}
+
+impl DbApiCursor {
+    fn new(connection: Py<DbApiConnection>) -> DbApiCursor {
+        DbApiCursor {
+            connection,
+            arraysize: 1,
+            rowcount: -1,
+            lastrowid: None,
+            description: None,
+            rows: None,
+            position: 0,
+            closed: false,
+        }
+    }
+
+    fn check_open(&self) -> Result<(), FustOrmError> {
+        match self.closed {
+            true => Err(FustOrmError::ConnectionError(
+                "The cursor is closed.".to_string(),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn params(py: Python, parameters: Option<Vec<Py<PyAny>>>) -> PyResult<Vec<String>> {
+        raw_params(py, &parameters.unwrap_or_default())
+    }
+
+    /// Fetches up to `size` rows as tuples.
+    fn fetch<'py>(&mut self, py: Python<'py>, size: usize) -> PyResult<Vec<Bound<'py, PyTuple>>> {
+        self.check_open()?;
+        let rows = self.rows.as_ref().ok_or_else(|| {
+            FustOrmError::InvalidQueryArgument(
+                "No statement producing rows was executed.".to_string(),
+            )
+        })?;
+        let end = rows.len().min(self.position.saturating_add(size));
+        let fetched = (self.position..end)
+            .map(|index| rows.to_tuple(py, index))
+            .collect::<PyResult<Vec<_>>>()?;
+        self.position = end;
+        Ok(fetched)
+    }
+}
+
+#[pymethods]
+impl DbApiCursor {
+    /// The columns of the last statement as 7-tuples of name, type name,
+    /// display size, internal size, precision, scale and whether they may be
+    /// NULL; `None` for statements that don't produce rows.
+    #[getter]
+    fn description<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyList>>> {
+        let Some(columns) = &self.description else {
+            return Ok(None);
+        };
+        let none = || py.None();
+        let tuples = columns
+            .iter()
+            .map(|(name, type_name, null_ok)| {
+                (name, type_name, none(), none(), none(), none(), *null_ok)
+            })
+            .collect::<Vec<_>>();
+        Ok(Some(PyList::new(py, tuples)?))
+    }
+
+    /// Executes a statement with `?` placeholders bound to `parameters`.
+    #[pyo3(signature = (operation, parameters = None))]
+    fn execute(
+        mut slf: PyRefMut<Self>,
+        operation: String,
+        parameters: Option<Vec<Py<PyAny>>>,
+    ) -> PyResult<Py<DbApiCursor>> {
+        slf.check_open()?;
+        let py = slf.py();
+        let params = DbApiCursor::params(py, parameters)?;
+        let outcome = slf
+            .connection
+            .bind(py)
+            .borrow()
+            .run(py, operation, params)?;
+        slf.rowcount = outcome.rowcount;
+        slf.lastrowid = outcome.lastrowid;
+        slf.rows = outcome.description.is_some().then_some(outcome.rows);
+        slf.description = outcome.description;
+        slf.position = 0;
+        Ok(slf.into())
+    }
+
+    /// Executes a statement once for each sequence of parameters. `rowcount`
+    /// is the total of the rows they affected.
+    fn executemany(
+        mut slf: PyRefMut<Self>,
+        operation: String,
+        seq_of_parameters: &Bound<PyAny>,
+    ) -> PyResult<()> {
+        slf.check_open()?;
+        let py = slf.py();
+        let connection = slf.connection.clone_ref(py);
+        let mut rowcount = 0;
+        for parameters in seq_of_parameters.try_iter()? {
+            let params = DbApiCursor::params(py, Some(parameters?.extract()?))?;
+            let outcome = connection
+                .bind(py)
+                .borrow()
+                .run(py, operation.clone(), params)?;
+            rowcount += outcome.rowcount;
+        }
+        slf.rowcount = rowcount;
+        slf.lastrowid = None;
+        slf.description = None;
+        slf.rows = None;
+        Ok(())
+    }
+
+    /// The next row as a tuple, or `None` when there are no more.
+    fn fetchone<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyTuple>>> {
+        Ok(self.fetch(py, 1)?.pop())
+    }
+
+    /// The next `size` rows, `arraysize` by default.
+    #[pyo3(signature = (size = None))]
+    fn fetchmany<'py>(
+        &mut self,
+        py: Python<'py>,
+        size: Option<usize>,
+    ) -> PyResult<Vec<Bound<'py, PyTuple>>> {
+        self.fetch(py, size.unwrap_or(self.arraysize))
+    }
+
+    /// The remaining rows.
+    fn fetchall<'py>(&mut self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyTuple>>> {
+        self.fetch(py, usize::MAX)
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.rows = None;
+    }
+
+    #[pyo3(signature = (*_sizes))]
+    fn setinputsizes(&self, _sizes: &Bound<PyTuple>) {}
+
+    #[pyo3(signature = (_size, _column = None))]
+    fn setoutputsize(&self, _size: &Bound<PyAny>, _column: Option<&Bound<PyAny>>) {}
+
+    fn __iter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyTuple>>> {
+        self.fetchone(py)
+    }
+}
@@ -0,0 +1,254 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString, PyTuple};
+use sqlx::any::AnyRow;
+use sqlx::{Column, Row, TypeInfo};
+
+use crate::error::FustOrmError;
+
+/// A value of a column whose type varies from row to row.
+pub(crate) enum Value {
+    Null,
+    Text(String),
+    Int(i64),
+    Real(f64),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub(crate) fn to_object<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(match self {
+            Value::Null => py.None().into_bound(py),
+            Value::Text(v) => v.into_pyobject(py)?.into_any(),
+            Value::Int(v) => v.into_pyobject(py)?.into_any(),
+            Value::Real(v) => v.into_pyobject(py)?.into_any(),
+            Value::Blob(v) => v.as_slice().into_pyobject(py)?.into_any(),
+        })
+    }
+}
+
+/// The values of one column, in a buffer of the column's type as long as all
+/// rows agree on it.
+enum Values {
+    /// The number of rows so far, all `NULL`.
+    Nulls(usize),
+    Text(Vec<Option<String>>),
+    Int(Vec<Option<i64>>),
+    Real(Vec<Option<f64>>),
+    Blob(Vec<Option<Vec<u8>>>),
+    Mixed(Vec<Value>),
+}
+
+impl Values {
+    /// A buffer of the type of `value`, for `capacity` rows, after `nulls`
+    /// rows of `NULL`.
+    fn typed(value: &Value, nulls: usize, capacity: usize) -> Values {
+        fn buffer<T>(nulls: usize, capacity: usize) -> Vec<Option<T>> {
+            let mut values = Vec::with_capacity(capacity);
+            values.resize_with(nulls, || None);
+            values
+        }
+        match value {
+            Value::Null => Values::Nulls(nulls),
+            Value::Text(_) => Values::Text(buffer(nulls, capacity)),
+            Value::Int(_) => Values::Int(buffer(nulls, capacity)),
+            Value::Real(_) => Values::Real(buffer(nulls, capacity)),
+            Value::Blob(_) => Values::Blob(buffer(nulls, capacity)),
+        }
+    }
+
+    fn push(&mut self, value: Value, capacity: usize) {
+        match (&mut *self, value) {
+            (Values::Nulls(nulls), Value::Null) => *nulls += 1,
+            (Values::Nulls(nulls), value) => {
+                *self = Values::typed(&value, *nulls, capacity);
+                self.push(value, capacity);
+            }
+            (Values::Text(values), Value::Text(v)) => values.push(Some(v)),
+            (Values::Int(values), Value::Int(v)) => values.push(Some(v)),
+            (Values::Real(values), Value::Real(v)) => values.push(Some(v)),
+            (Values::Blob(values), Value::Blob(v)) => values.push(Some(v)),
+            (Values::Text(values), Value::Null) => values.push(None),
+            (Values::Int(values), Value::Null) => values.push(None),
+            (Values::Real(values), Value::Null) => values.push(None),
+            (Values::Blob(values), Value::Null) => values.push(None),
+            (Values::Mixed(values), value) => values.push(value),
+            (_, value) => {
+                self.make_mixed();
+                self.push(value, capacity);
+            }
+        }
+    }
+
+    /// Moves the values into a `Mixed` buffer, once a row disagrees on the
+    /// column's type.
+    fn make_mixed(&mut self) {
+        fn mixed<T>(values: &mut Vec<Option<T>>, value: fn(T) -> Value) -> Vec<Value> {
+            values
+                .drain(..)
+                .map(|v| v.map_or(Value::Null, value))
+                .collect()
+        }
+        let values = match self {
+            Values::Text(values) => mixed(values, Value::Text),
+            Values::Int(values) => mixed(values, Value::Int),
+            Values::Real(values) => mixed(values, Value::Real),
+            Values::Blob(values) => mixed(values, Value::Blob),
+            Values::Nulls(nulls) => (0..*nulls).map(|_| Value::Null).collect(),
+            Values::Mixed(_) => return,
+        };
+        *self = Values::Mixed(values);
+    }
+
+    fn to_object<'py>(&self, py: Python<'py>, index: usize) -> PyResult<Bound<'py, PyAny>> {
+        Ok(match self {
+            Values::Nulls(_) => py.None().into_bound(py),
+            Values::Text(values) => values[index].as_deref().into_pyobject(py)?.into_any(),
+            Values::Int(values) => values[index].into_pyobject(py)?.into_any(),
+            Values::Real(values) => values[index].into_pyobject(py)?.into_any(),
+            Values::Blob(values) => values[index].as_deref().into_pyobject(py)?.into_any(),
+            Values::Mixed(values) => values[index].to_object(py)?,
+        })
+    }
+}
+
+/// Decodes a cell by the type the driver reports for it.
+pub(crate) fn decode(row: &AnyRow, index: usize) -> Result<Value, FustOrmError> {
+    let map_db_err = FustOrmError::from;
+    let column = &row.columns()[index];
+    let value = match column.type_info().name() {
+        "TEXT" | "VARCHAR" => row
+            .try_get::<Option<String>, _>(index)
+            .map_err(map_db_err)?
+            .map(Value::Text),
+        "INTEGER" | "INT" => row
+            .try_get::<Option<i64>, _>(index)
+            .map_err(map_db_err)?
+            .map(Value::Int),
+        "REAL" => row
+            .try_get::<Option<f64>, _>(index)
+            .map_err(map_db_err)?
+            .map(Value::Real),
+        "BLOB" => row
+            .try_get::<Option<Vec<u8>>, _>(index)
+            .map_err(map_db_err)?
+            .map(Value::Blob),
+        // Other types are decoded as the first of these that fits.
+        _ => {
+            if let Ok(None) = row.try_get::<Option<String>, _>(index) {
+                None
+            } else if let Ok(val) = row.try_get::<String, _>(index) {
+                Some(Value::Text(val))
+            } else if let Ok(val) = row.try_get::<i64, _>(index) {
+                Some(Value::Int(val))
+            } else if let Ok(val) = row.try_get::<f64, _>(index) {
+                Some(Value::Real(val))
+            } else {
+                log::warn!(
+                    "Couldn't determine column type of {}, fallback to None",
+                    column.name()
+                );
+                None
+            }
+        }
+    };
+    Ok(value.unwrap_or(Value::Null))
+}
+
+/// Rows decoded column by column, without the GIL, to be turned into Python
+/// objects in one pass.
+pub(crate) struct RowBatch {
+    names: Vec<String>,
+    columns: Vec<Values>,
+    len: usize,
+}
+
+impl RowBatch {
+    /// Decodes `rows`, which share their columns.
+    pub(crate) fn decode(rows: &[AnyRow]) -> Result<RowBatch, FustOrmError> {
+        let names: Vec<String> = rows
+            .first()
+            .map(|row| row.columns().iter().map(|c| c.name().to_string()).collect())
+            .unwrap_or_default();
+        let mut columns = Vec::with_capacity(names.len());
+        for index in 0..names.len() {
+            let mut values = Values::Nulls(0);
+            for row in rows {
+                values.push(decode(row, index)?, rows.len());
+            }
+            columns.push(values);
+        }
+        Ok(RowBatch {
+            names,
+            columns,
+            len: rows.len(),
+        })
+    }
+
+    /// The rows as dicts keyed by column name.
+    pub(crate) fn to_dicts<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let names: Vec<Bound<PyString>> = self
+            .names
+            .iter()
+            .map(|name| PyString::new(py, name))
+            .collect();
+        (0..self.len)
+            .map(|row| {
+                let dict = PyDict::new(py);
+                for (name, values) in names.iter().zip(&self.columns) {
+                    dict.set_item(name, values.to_object(py, row)?)?;
+                }
+                Ok(dict)
+            })
+            .collect()
+    }
+
+    /// The number of rows.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// An estimate of the memory the values take, in bytes.
+    pub(crate) fn size(&self) -> usize {
+        let names: usize = self.names.iter().map(String::len).sum();
+        let values: usize = self
+            .columns
+            .iter()
+            .map(|values| match values {
+                Values::Nulls(_) => 0,
+                Values::Text(values) => values
+                    .iter()
+                    .map(|v| 24 + v.as_ref().map_or(0, String::len))
+                    .sum(),
+                Values::Int(values) => values.len() * 16,
+                Values::Real(values) => values.len() * 16,
+                Values::Blob(values) => values
+                    .iter()
+                    .map(|v| 24 + v.as_ref().map_or(0, Vec::len))
+                    .sum(),
+                Values::Mixed(values) => values
+                    .iter()
+                    .map(|v| match v {
+                        Value::Text(v) => 32 + v.len(),
+                        Value::Blob(v) => 32 + v.len(),
+                        _ => 32,
+                    })
+                    .sum(),
+            })
+            .sum();
+        names + values
+    }
+
+    /// The row at `index` as a tuple of its values, in column order.
+    pub(crate) fn to_tuple<'py>(
+        &self,
+        py: Python<'py>,
+        index: usize,
+    ) -> PyResult<Bound<'py, PyTuple>> {
+        let values = self
+            .columns
+            .iter()
+            .map(|values| values.to_object(py, index))
+            .collect::<PyResult<Vec<_>>>()?;
+        PyTuple::new(py, values)
+    }
+}
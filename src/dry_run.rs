@@ -0,0 +1,64 @@
+use log::info;
+use sqlx::{AnyPool, Executor};
+
+use crate::database::{Database, acquire};
+use crate::error::FustOrmError;
+use crate::logging::SQL_TARGET;
+use crate::mock;
+use crate::pool_registry::PoolRegistry;
+
+/// The pools connected with `dry_run=True`, by the connect options every
+/// clone of a pool shares.
+static DRY_RUN: PoolRegistry<()> = PoolRegistry::new();
+
+/// Makes `execute()` preview rather than run the statements of `pool`.
+pub(crate) fn set(pool: &AnyPool) {
+    DRY_RUN.insert(pool, ());
+}
+
+/// Whether `pool` was connected with `dry_run=True`.
+pub(crate) fn is_set(pool: &AnyPool) -> bool {
+    DRY_RUN.get(pool).is_some()
+}
+
+/// Fails for `what` a model or query method writes with, e.g. a statement,
+/// on a pool connected with `dry_run=True`: only `execute()` previews its
+/// statements, and the others would write for real.
+pub(crate) fn check_write(pool: &AnyPool, what: &str) -> Result<(), FustOrmError> {
+    if !is_set(pool) {
+        return Ok(());
+    }
+    Err(FustOrmError::InvalidQueryArgument(format!(
+        "Can't run {} on a database connected with dry_run=True, which only \
+         previews the statements of execute().",
+        what
+    )))
+}
+
+/// Prepares a statement without running it, in the transaction of `db` or
+/// on a connection of its pool, so that its syntax and the tables and
+/// columns it names are checked, and logs it to `fust_orm.sql`.
+pub(crate) async fn preview(
+    db: &Database,
+    sql: &str,
+    params: &[String],
+) -> Result<(), FustOrmError> {
+    let prepared = match db.lock_tx().await? {
+        Some(mut guard) => {
+            let tx = guard.as_mut().expect("the transaction is open");
+            (&mut **tx).prepare(sql).await.map(drop)
+        }
+        // A mock has no schema to check the statement against.
+        None if mock::is_mocked(&db.pool) => Ok(()),
+        None => match acquire(&db.pool).await {
+            Ok(mut conn) => (&mut *conn).prepare(sql).await.map(drop),
+            Err(e) => Err(e),
+        },
+    };
+    prepared.map_err(|e| FustOrmError::from(e).with_statement(sql, params))?;
+    info!(
+        target: SQL_TARGET,
+        "Dry run, not executed: {}; parameters: {:?}", sql, params
+    );
+    Ok(())
+}
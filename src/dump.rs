@@ -0,0 +1,518 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+use crate::audit;
+use crate::database::{
+    Database, connect_pool, fetch_rows, sqlite_foreign_keys_off, sqlite_foreign_keys_on,
+};
+use crate::error::FustOrmError;
+use crate::introspect::{self, ColumnInfo};
+use crate::schema::Dialect;
+use futures_util::TryStreamExt;
+use log::{debug, info};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::any::AnyRow;
+use sqlx::{AnyConnection, AnyPool, Column, Connection, Executor, Row, ValueRef};
+
+fn map_db_err(e: sqlx::Error) -> FustOrmError {
+    FustOrmError::from(e)
+}
+
+fn io_error(path: &str, e: std::io::Error) -> FustOrmError {
+    FustOrmError::BuildError(format!("Can't access dump '{}': {}", path, e))
+}
+
+/// How a column is read for the dump.
+#[derive(Clone, Copy)]
+enum Read {
+    /// As the value the driver decodes (SQLite, whose values all decode).
+    Native,
+    /// Cast to text, which the database casts back when it is inserted.
+    Text,
+    /// As the hex digits of a binary value.
+    Hex,
+}
+
+fn quote(text: &str, dialect: Dialect) -> String {
+    let text = text.replace('\'', "''");
+    match dialect {
+        // MySQL treats backslashes in string literals as escapes.
+        Dialect::Mysql => format!("'{}'", text.replace('\\', "\\\\")),
+        _ => format!("'{}'", text),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Renders column `index` of `row` as a SQL literal.
+fn literal(
+    row: &AnyRow,
+    index: usize,
+    read: Read,
+    dialect: Dialect,
+) -> Result<String, FustOrmError> {
+    if row.try_get_raw(index).map_err(map_db_err)?.is_null() {
+        return Ok("NULL".to_string());
+    }
+    match read {
+        Read::Text => Ok(quote(
+            &row.try_get::<String, _>(index).map_err(map_db_err)?,
+            dialect,
+        )),
+        Read::Hex => Ok(format!(
+            "X'{}'",
+            row.try_get::<String, _>(index).map_err(map_db_err)?
+        )),
+        Read::Native => {
+            if let Ok(value) = row.try_get::<i64, _>(index) {
+                Ok(value.to_string())
+            } else if let Ok(value) = row.try_get::<f64, _>(index) {
+                Ok(if value.is_finite() {
+                    format!("{:?}", value)
+                } else {
+                    // 9e999 overflows to infinity, which SQLite has no literal for.
+                    format!("{}9e999", if value < 0.0 { "-" } else { "" })
+                })
+            } else if let Ok(value) = row.try_get::<String, _>(index) {
+                Ok(quote(&value, dialect))
+            } else if let Ok(value) = row.try_get::<Vec<u8>, _>(index) {
+                Ok(format!("X'{}'", hex(&value)))
+            } else {
+                Err(FustOrmError::QueryError(format!(
+                    "Can't dump the value of column {}.",
+                    row.columns()
+                        .get(index)
+                        .map(|c| c.name().to_string())
+                        .unwrap_or_default()
+                )))
+            }
+        }
+    }
+}
+
+/// How each column of a table is read: the select expression and the `Read`.
+fn column_reads(columns: &[ColumnInfo], dialect: Dialect) -> Vec<(String, Read)> {
+    columns
+        .iter()
+        .map(|column| match dialect {
+            Dialect::Sqlite => (column.name.clone(), Read::Native),
+            // `bytea` renders as `\x...` text, which casts back.
+            Dialect::Postgres => (format!("CAST({} AS TEXT)", column.name), Read::Text),
+            Dialect::Mysql => {
+                let sql_type = column.sql_type.to_lowercase();
+                if sql_type.contains("blob") || sql_type.contains("binary") {
+                    (format!("HEX({})", column.name), Read::Hex)
+                } else {
+                    (format!("CAST({} AS CHAR)", column.name), Read::Text)
+                }
+            }
+        })
+        .collect()
+}
+
+/// The schema statements of a dump: the `CREATE TABLE` of each table, and
+/// what has to follow the data (indexes, triggers, views, foreign keys and
+/// sequence positions).
+struct DumpSchema {
+    tables: Vec<(String, String)>,
+    after_data: Vec<String>,
+}
+
+async fn sqlite_schema(pool: &AnyPool, tables: &[String]) -> Result<DumpSchema, FustOrmError> {
+    let mut schema = DumpSchema {
+        tables: Vec::new(),
+        after_data: Vec::new(),
+    };
+    for table in tables {
+        let rows = fetch_rows(
+            pool,
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?",
+            vec![table.clone()],
+        )
+        .await?;
+        let sql: Option<String> = rows
+            .first()
+            .map(|row| row.try_get("sql"))
+            .transpose()
+            .map_err(map_db_err)?;
+        schema.tables.push((table.clone(), sql.unwrap_or_default()));
+    }
+    // Auto-indexes of constraints have no SQL; they come with their table.
+    let rows = fetch_rows(
+        pool,
+        "SELECT sql FROM sqlite_master \
+         WHERE type IN ('index', 'trigger', 'view') AND sql IS NOT NULL \
+         ORDER BY CASE type WHEN 'index' THEN 0 WHEN 'view' THEN 1 ELSE 2 END, name",
+        Vec::new(),
+    )
+    .await?;
+    for row in &rows {
+        schema
+            .after_data
+            .push(row.try_get::<String, _>("sql").map_err(map_db_err)?);
+    }
+    // `sqlite_sequence` only exists once an AUTOINCREMENT table does.
+    let sequence = fetch_rows(
+        pool,
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sqlite_sequence'",
+        Vec::new(),
+    )
+    .await?;
+    if !sequence.is_empty() {
+        for row in &fetch_rows(pool, "SELECT name, seq FROM sqlite_sequence", Vec::new()).await? {
+            let name = quote(
+                &row.try_get::<String, _>("name").map_err(map_db_err)?,
+                Dialect::Sqlite,
+            );
+            let seq: i64 = row.try_get("seq").map_err(map_db_err)?;
+            schema
+                .after_data
+                .push(format!("DELETE FROM sqlite_sequence WHERE name = {}", name));
+            schema.after_data.push(format!(
+                "INSERT INTO sqlite_sequence (name, seq) VALUES ({}, {})",
+                name, seq
+            ));
+        }
+    }
+    Ok(schema)
+}
+
+async fn postgres_schema(pool: &AnyPool, tables: &[String]) -> Result<DumpSchema, FustOrmError> {
+    let mut schema = DumpSchema {
+        tables: Vec::new(),
+        after_data: Vec::new(),
+    };
+    for table in tables {
+        let columns = fetch_rows(
+            pool,
+            "SELECT CAST(a.attname AS TEXT) AS name, \
+             format_type(a.atttypid, a.atttypmod) AS sql_type, \
+             CAST(a.attnotnull AS INTEGER) AS not_null, \
+             pg_get_expr(d.adbin, d.adrelid) AS default_value, \
+             CAST(a.attidentity AS TEXT) AS identity \
+             FROM pg_attribute a \
+             JOIN pg_class t ON t.oid = a.attrelid \
+             LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+             WHERE t.relname = $1 AND pg_table_is_visible(t.oid) \
+             AND a.attnum > 0 AND NOT a.attisdropped \
+             ORDER BY a.attnum",
+            vec![table.clone()],
+        )
+        .await?;
+        let mut definitions = Vec::new();
+        for row in &columns {
+            let name: String = row.try_get("name").map_err(map_db_err)?;
+            let mut sql_type: String = row.try_get("sql_type").map_err(map_db_err)?;
+            let mut default: Option<String> = row.try_get("default_value").map_err(map_db_err)?;
+            // A serial column's sequence is recreated with the column.
+            if default
+                .as_deref()
+                .is_some_and(|d| d.starts_with("nextval("))
+            {
+                sql_type = match sql_type.as_str() {
+                    "bigint" => "BIGSERIAL".to_string(),
+                    "smallint" => "SMALLSERIAL".to_string(),
+                    _ => "SERIAL".to_string(),
+                };
+                default = None;
+            }
+            let mut definition = format!("{} {}", name, sql_type);
+            match row
+                .try_get::<String, _>("identity")
+                .map_err(map_db_err)?
+                .as_str()
+            {
+                "a" => definition.push_str(" GENERATED ALWAYS AS IDENTITY"),
+                "d" => definition.push_str(" GENERATED BY DEFAULT AS IDENTITY"),
+                _ => {}
+            }
+            if row.try_get::<i64, _>("not_null").map_err(map_db_err)? != 0 {
+                definition.push_str(" NOT NULL");
+            }
+            if let Some(default) = default {
+                definition.push_str(&format!(" DEFAULT {}", default));
+            }
+            definitions.push(definition);
+        }
+        let constraints = fetch_rows(
+            pool,
+            "SELECT CAST(c.conname AS TEXT) AS name, CAST(c.contype AS TEXT) AS kind, \
+             pg_get_constraintdef(c.oid) AS definition \
+             FROM pg_constraint c JOIN pg_class t ON t.oid = c.conrelid \
+             WHERE t.relname = $1 AND pg_table_is_visible(t.oid) \
+             AND c.contype IN ('p', 'u', 'c', 'f') \
+             ORDER BY c.contype, c.conname",
+            vec![table.clone()],
+        )
+        .await?;
+        for row in &constraints {
+            let name: String = row.try_get("name").map_err(map_db_err)?;
+            let definition: String = row.try_get("definition").map_err(map_db_err)?;
+            // Foreign keys are added once every table has its rows.
+            if row.try_get::<String, _>("kind").map_err(map_db_err)? == "f" {
+                schema.after_data.push(format!(
+                    "ALTER TABLE {} ADD CONSTRAINT {} {}",
+                    table, name, definition
+                ));
+            } else {
+                definitions.push(format!("CONSTRAINT {} {}", name, definition));
+            }
+        }
+        schema.tables.push((
+            table.clone(),
+            format!("CREATE TABLE {} ({})", table, definitions.join(", ")),
+        ));
+
+        let indexes = fetch_rows(
+            pool,
+            "SELECT pg_get_indexdef(ix.indexrelid) AS definition \
+             FROM pg_index ix JOIN pg_class t ON t.oid = ix.indrelid \
+             WHERE t.relname = $1 AND pg_table_is_visible(t.oid) \
+             AND NOT EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conindid = ix.indexrelid) \
+             ORDER BY ix.indexrelid",
+            vec![table.clone()],
+        )
+        .await?;
+        for row in &indexes {
+            schema
+                .after_data
+                .push(row.try_get::<String, _>("definition").map_err(map_db_err)?);
+        }
+        let sequences = fetch_rows(
+            pool,
+            "SELECT CAST(a.attname AS TEXT) AS name FROM pg_attribute a \
+             JOIN pg_class t ON t.oid = a.attrelid \
+             WHERE t.relname = $1 AND pg_table_is_visible(t.oid) AND a.attnum > 0 \
+             AND pg_get_serial_sequence(t.relname, a.attname) IS NOT NULL",
+            vec![table.clone()],
+        )
+        .await?;
+        for row in &sequences {
+            let column: String = row.try_get("name").map_err(map_db_err)?;
+            schema.after_data.push(format!(
+                "SELECT setval(pg_get_serial_sequence('{0}', '{1}'), MAX({1})) FROM {0} \
+                 HAVING MAX({1}) IS NOT NULL",
+                table, column
+            ));
+        }
+    }
+    Ok(schema)
+}
+
+async fn mysql_schema(pool: &AnyPool, tables: &[String]) -> Result<DumpSchema, FustOrmError> {
+    let mut schema = DumpSchema {
+        tables: Vec::new(),
+        after_data: Vec::new(),
+    };
+    for table in tables {
+        let rows = fetch_rows(pool, &format!("SHOW CREATE TABLE {}", table), Vec::new()).await?;
+        let sql: String = rows
+            .first()
+            .map(|row| row.try_get(1))
+            .transpose()
+            .map_err(map_db_err)?
+            .unwrap_or_default();
+        schema.tables.push((table.clone(), sql));
+    }
+    Ok(schema)
+}
+
+/// Writes `statement` and its terminator.
+fn write_statement(out: &mut impl Write, statement: &str) -> std::io::Result<()> {
+    out.write_all(statement.as_bytes())?;
+    out.write_all(b";\n")
+}
+
+/// Writes a SQL dump of the database (schema and rows) to `path`. Rows are
+/// streamed from one read transaction, so the data is consistent and never
+/// held in memory. Resolves to the number of rows dumped per table.
+pub(crate) fn dump<'py>(py: Python<'py>, db: &Database, path: &str) -> PyResult<Bound<'py, PyAny>> {
+    let dialect = db.dialect();
+    let pool = db.pool.clone();
+    let path = path.to_string();
+    future_into_py(py, async move {
+        let tables = introspect::tables(&pool, dialect).await?;
+        let tables = introspect::dependency_order(&pool, dialect, tables).await?;
+        let schema = match dialect {
+            Dialect::Sqlite => sqlite_schema(&pool, &tables).await?,
+            Dialect::Postgres => postgres_schema(&pool, &tables).await?,
+            Dialect::Mysql => mysql_schema(&pool, &tables).await?,
+        };
+        let file = File::create(&path).map_err(|e| io_error(&path, e))?;
+        let mut out = BufWriter::new(file);
+        let io_err = |e| io_error(&path, e);
+        writeln!(out, "-- fust-orm dump of a {} database", dialect.name()).map_err(io_err)?;
+
+        let mut conn = pool.acquire().await.map_err(map_db_err)?;
+        let mut tx = conn.begin().await.map_err(map_db_err)?;
+        let mut counts = Vec::with_capacity(schema.tables.len());
+        for (table, create_sql) in &schema.tables {
+            write_statement(&mut out, create_sql).map_err(io_err)?;
+            // Generated columns are computed again when the rows are loaded.
+            let columns: Vec<_> = introspect::columns(&pool, dialect, table)
+                .await?
+                .into_iter()
+                .filter(|c| c.generated.is_none())
+                .collect();
+            let reads = column_reads(&columns, dialect);
+            let select = format!(
+                "SELECT {} FROM {}",
+                reads
+                    .iter()
+                    .map(|(expression, _)| expression.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                table
+            );
+            let insert = format!(
+                "INSERT INTO {} ({}) VALUES (",
+                table,
+                columns
+                    .iter()
+                    .map(|c| c.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            debug!("Dumping table '{}'", table);
+            let mut count = 0;
+            let mut rows = sqlx::query(&select).fetch(&mut *tx);
+            while let Some(row) = rows.try_next().await.map_err(map_db_err)? {
+                let values = reads
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (_, read))| literal(&row, index, *read, dialect))
+                    .collect::<Result<Vec<_>, _>>()?;
+                out.write_all(insert.as_bytes()).map_err(io_err)?;
+                write_statement(&mut out, &format!("{})", values.join(", "))).map_err(io_err)?;
+                count += 1;
+            }
+            counts.push((table.clone(), count));
+        }
+        for statement in &schema.after_data {
+            write_statement(&mut out, statement).map_err(io_err)?;
+        }
+        tx.commit().await.map_err(map_db_err)?;
+        out.flush().map_err(io_err)?;
+        info!("Dumped {} tables to '{}'", counts.len(), path);
+
+        Python::attach(|py| -> PyResult<Py<PyDict>> {
+            let result = PyDict::new(py);
+            for (table, count) in counts {
+                result.set_item(table, count)?;
+            }
+            Ok(result.unbind())
+        })
+    })
+}
+
+/// Reads the statements of a dump one at a time. A statement ends with a
+/// line ending in `;` outside a string literal (and, for a trigger, in
+/// `END;`); `--` comment lines between statements are skipped.
+struct Statements<R> {
+    lines: std::io::Lines<R>,
+}
+
+impl<R: BufRead> Iterator for Statements<R> {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut statement = String::new();
+        let mut in_string = false;
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            if statement.is_empty() && (line.trim().is_empty() || line.starts_with("--")) {
+                continue;
+            }
+            // Quotes are doubled inside literals, which toggles twice.
+            in_string ^= line.chars().filter(|c| *c == '\'').count() % 2 == 1;
+            if !statement.is_empty() {
+                statement.push('\n');
+            }
+            statement.push_str(&line);
+            let end = line.trim_end();
+            let trigger = statement
+                .get(..14)
+                .is_some_and(|start| start.eq_ignore_ascii_case("CREATE TRIGGER"));
+            let ends = if trigger {
+                end.len() >= 4 && end[end.len() - 4..].eq_ignore_ascii_case("END;")
+            } else {
+                end.ends_with(';')
+            };
+            if !in_string && ends {
+                let end = statement.trim_end().len() - 1;
+                statement.truncate(end);
+                return Some(Ok(statement));
+            }
+        }
+        (!statement.trim().is_empty()).then_some(Ok(statement))
+    }
+}
+
+/// Runs the statements of the dump at `path` in one transaction, with foreign
+/// keys unchecked while the rows go in, recording them in the audit log.
+async fn run_dump(conn: &mut AnyConnection, path: &str) -> Result<usize, FustOrmError> {
+    let file = File::open(path).map_err(|e| io_error(path, e))?;
+    let statements = Statements {
+        lines: BufReader::new(file).lines(),
+    };
+    let mut tx = conn.begin().await.map_err(map_db_err)?;
+    let mut count = 0;
+    for statement in statements {
+        let statement = statement.map_err(|e| io_error(path, e))?;
+        // A plain string runs unprepared, as the literals make every statement unique.
+        let result = (&mut *tx)
+            .execute(statement.as_str())
+            .await
+            .map_err(map_db_err)?;
+        audit::record(&mut tx, &statement, &[], result.rows_affected()).await?;
+        count += 1;
+    }
+    tx.commit().await.map_err(map_db_err)?;
+    Ok(count)
+}
+
+/// Connects to `url` and runs the dump at `path` there, statement by
+/// statement. Resolves to the connected `Database`.
+pub(crate) fn restore<'py>(
+    py: Python<'py>,
+    path: &str,
+    url: String,
+) -> PyResult<Bound<'py, PyAny>> {
+    let path = path.to_string();
+    future_into_py(py, async move {
+        let pool = connect_pool(&url, false, None, &[]).await?;
+        let db = Database::new(pool);
+        let mut conn = db.pool.acquire().await.map_err(map_db_err)?;
+        let count = match db.dialect() {
+            Dialect::Sqlite => {
+                let enforced = sqlite_foreign_keys_off(&mut conn).await?;
+                let result = run_dump(&mut conn, &path).await;
+                if enforced {
+                    sqlite_foreign_keys_on(&mut conn).await?;
+                }
+                result?
+            }
+            Dialect::Mysql => {
+                conn.execute("SET FOREIGN_KEY_CHECKS = 0")
+                    .await
+                    .map_err(map_db_err)?;
+                let result = run_dump(&mut conn, &path).await;
+                conn.execute("SET FOREIGN_KEY_CHECKS = 1")
+                    .await
+                    .map_err(map_db_err)?;
+                result?
+            }
+            Dialect::Postgres => run_dump(&mut conn, &path).await?,
+        };
+        info!("Restored '{}' with {} statements", path, count);
+        drop(conn);
+        Ok(db)
+    })
+}
@@ -1,6 +1,222 @@
+use pyo3::create_exception;
 use pyo3::prelude::*;
+use pyo3::types::PyType;
+use sqlx::error::ErrorKind;
 use thiserror::Error;
 
+use crate::timing;
+
+create_exception!(
+    fust_orm,
+    StaleDataError,
+    pyo3::exceptions::PyValueError,
+    "Raised when saving a versioned row that was modified or deleted since it was loaded."
+);
+
+// The exceptions of DB-API 2.0 (PEP 249). `Error` derives from `ValueError`,
+// which database errors were raised as before.
+create_exception!(
+    fust_orm,
+    Error,
+    pyo3::exceptions::PyValueError,
+    "Base class of the errors raised by the database or its driver."
+);
+create_exception!(
+    fust_orm,
+    InterfaceError,
+    Error,
+    "Raised for errors of the driver rather than the database, such as an invalid connection URL."
+);
+create_exception!(
+    fust_orm,
+    DatabaseError,
+    Error,
+    "Raised for errors reported by the database."
+);
+create_exception!(
+    fust_orm,
+    OperationalError,
+    DatabaseError,
+    "Raised when the database can't be reached or can't run a statement for reasons outside the program, such as a lock or a lost connection."
+);
+create_exception!(
+    fust_orm,
+    IntegrityError,
+    DatabaseError,
+    "Raised when a statement violates a constraint: a unique, foreign key, not null or check constraint."
+);
+create_exception!(
+    fust_orm,
+    UniqueViolationError,
+    IntegrityError,
+    "Raised when a statement violates a unique constraint or primary key."
+);
+create_exception!(
+    fust_orm,
+    ForeignKeyViolationError,
+    IntegrityError,
+    "Raised when a statement violates a foreign key constraint."
+);
+create_exception!(
+    fust_orm,
+    NotNullViolationError,
+    IntegrityError,
+    "Raised when a statement leaves a NOT NULL column empty."
+);
+create_exception!(
+    fust_orm,
+    CheckViolationError,
+    IntegrityError,
+    "Raised when a statement violates a check constraint."
+);
+create_exception!(
+    fust_orm,
+    ProgrammingError,
+    DatabaseError,
+    "Raised for invalid statements, such as a syntax error or a missing table or column."
+);
+create_exception!(
+    fust_orm,
+    DataError,
+    DatabaseError,
+    "Raised for invalid values, such as a value out of range or that can't be decoded."
+);
+
+/// The DB-API exception a database error is raised as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbErrorKind {
+    Interface,
+    Operational,
+    Integrity,
+    UniqueViolation,
+    ForeignKeyViolation,
+    NotNullViolation,
+    CheckViolation,
+    Programming,
+    Data,
+}
+
+impl DbErrorKind {
+    fn of(err: &sqlx::Error) -> DbErrorKind {
+        match err {
+            sqlx::Error::Database(e) => match e.kind() {
+                ErrorKind::UniqueViolation => DbErrorKind::UniqueViolation,
+                ErrorKind::ForeignKeyViolation => DbErrorKind::ForeignKeyViolation,
+                ErrorKind::NotNullViolation => DbErrorKind::NotNullViolation,
+                ErrorKind::CheckViolation => DbErrorKind::CheckViolation,
+                _ if e.try_downcast_ref::<sqlx::sqlite::SqliteError>().is_some() => {
+                    let code = e.code().and_then(|c| c.parse::<i32>().ok()).unwrap_or(0);
+                    DbErrorKind::of_sqlite_code(code & 0xff)
+                }
+                _ => DbErrorKind::of_sqlstate(e.code().as_deref().unwrap_or_default()),
+            },
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed
+            | sqlx::Error::BeginFailed => DbErrorKind::Operational,
+            sqlx::Error::TypeNotFound { .. }
+            | sqlx::Error::ColumnIndexOutOfBounds { .. }
+            | sqlx::Error::ColumnNotFound(_)
+            | sqlx::Error::InvalidSavePointStatement => DbErrorKind::Programming,
+            sqlx::Error::RowNotFound
+            | sqlx::Error::ColumnDecode { .. }
+            | sqlx::Error::Encode(_)
+            | sqlx::Error::Decode(_) => DbErrorKind::Data,
+            _ => DbErrorKind::Interface,
+        }
+    }
+
+    /// Classifies a primary SQLite result code.
+    fn of_sqlite_code(code: i32) -> DbErrorKind {
+        match code {
+            // SQLITE_ERROR: a syntax error, a missing table or column;
+            // SQLITE_MISUSE.
+            1 | 21 => DbErrorKind::Programming,
+            // SQLITE_CONSTRAINT.
+            19 => DbErrorKind::Integrity,
+            // SQLITE_TOOBIG, SQLITE_MISMATCH, SQLITE_RANGE.
+            18 | 20 | 25 => DbErrorKind::Data,
+            // Busy or locked databases, I/O errors, full disks and the like.
+            _ => DbErrorKind::Operational,
+        }
+    }
+
+    /// Classifies a SQLSTATE by its class, its first two characters.
+    fn of_sqlstate(sqlstate: &str) -> DbErrorKind {
+        match sqlstate.get(..2).unwrap_or_default() {
+            "23" => DbErrorKind::Integrity,
+            "22" => DbErrorKind::Data,
+            "42" | "0A" | "26" | "34" | "3D" | "3F" => DbErrorKind::Programming,
+            _ => DbErrorKind::Operational,
+        }
+    }
+}
+
+/// An error of the database or its driver, with the statement it failed on.
+#[derive(Debug)]
+pub struct DbError {
+    pub kind: DbErrorKind,
+    pub message: String,
+    /// The code the database reported: a SQLSTATE, or a SQLite result code.
+    pub code: Option<String>,
+    /// The name of the violated constraint, as far as the database tells.
+    pub constraint: Option<String>,
+    /// The statement that failed and a summary of its parameters.
+    pub statement: Option<(String, Vec<String>)>,
+}
+
+impl DbError {
+    fn new(err: &sqlx::Error, message: String) -> Box<DbError> {
+        Box::new(DbError {
+            kind: DbErrorKind::of(err),
+            message,
+            code: err
+                .as_database_error()
+                .and_then(|e| e.code())
+                .map(|code| code.into_owned()),
+            constraint: err.as_database_error().and_then(|e| match e.constraint() {
+                Some(constraint) => Some(constraint.to_string()),
+                None => constraint_in_message(e.message()),
+            }),
+            statement: None,
+        })
+    }
+
+    /// Sets the `sql`, `params` and `code` attributes of the exception.
+    fn annotate(&self, py: Python, err: &PyErr) -> PyResult<()> {
+        let value = err.value(py);
+        if let Some((sql, params)) = &self.statement {
+            value.setattr("sql", sql)?;
+            value.setattr("params", params)?;
+        }
+        value.setattr("code", &self.code)?;
+        value.setattr("constraint", &self.constraint)?;
+        Ok(())
+    }
+}
+
+/// Finds the violated constraint in the message of a database that doesn't
+/// report it separately: after `constraint failed: ` on SQLite (the columns of
+/// unique and not null constraints, the name of check constraints), or quoted
+/// in MySQL's messages.
+fn constraint_in_message(message: &str) -> Option<String> {
+    if let Some((_, constraint)) = message.split_once("constraint failed: ") {
+        return Some(constraint.to_string());
+    }
+    [
+        ("for key '", '\''),
+        ("CONSTRAINT `", '`'),
+        ("constraint '", '\''),
+    ]
+    .iter()
+    .find_map(|(start, end)| {
+        let (_, rest) = message.split_once(start)?;
+        Some(rest.split(*end).next()?.to_string())
+    })
+}
+
 #[derive(Error, Debug)]
 pub enum FustOrmError {
     #[error("Connection error: {0}")]
@@ -9,15 +225,79 @@ pub enum FustOrmError {
     #[error("Query execution error: {0}")]
     QueryError(String),
 
+    #[error("{}", .0.message)]
+    Database(Box<DbError>),
+
     #[error("Invalid query argument: {0}")]
     InvalidQueryArgument(String),
 
     #[error("Failed to build query: {0}")]
     BuildError(String),
+
+    #[error("Stale data: {0}")]
+    StaleData(String),
+
+    #[error("Schema mismatch: {0}")]
+    SchemaMismatch(String),
+}
+
+impl FustOrmError {
+    /// The exception the error is raised as.
+    pub(crate) fn exception_type<'py>(&self, py: Python<'py>) -> Bound<'py, PyType> {
+        match self {
+            FustOrmError::StaleData(_) => py.get_type::<StaleDataError>(),
+            FustOrmError::ConnectionError(_) => py.get_type::<InterfaceError>(),
+            FustOrmError::QueryError(_) => py.get_type::<DatabaseError>(),
+            FustOrmError::Database(err) => match err.kind {
+                DbErrorKind::Interface => py.get_type::<InterfaceError>(),
+                DbErrorKind::Operational => py.get_type::<OperationalError>(),
+                DbErrorKind::Integrity => py.get_type::<IntegrityError>(),
+                DbErrorKind::UniqueViolation => py.get_type::<UniqueViolationError>(),
+                DbErrorKind::ForeignKeyViolation => py.get_type::<ForeignKeyViolationError>(),
+                DbErrorKind::NotNullViolation => py.get_type::<NotNullViolationError>(),
+                DbErrorKind::CheckViolation => py.get_type::<CheckViolationError>(),
+                DbErrorKind::Programming => py.get_type::<ProgrammingError>(),
+                DbErrorKind::Data => py.get_type::<DataError>(),
+            },
+            _ => py.get_type::<pyo3::exceptions::PyValueError>(),
+        }
+    }
+
+    /// An error connecting to the database.
+    pub(crate) fn connection(err: sqlx::Error) -> FustOrmError {
+        FustOrmError::Database(DbError::new(&err, format!("Connection error: {}", err)))
+    }
+
+    /// Records the statement a database error happened on, with a summary of
+    /// its parameters.
+    pub(crate) fn with_statement(mut self, sql: &str, params: &[String]) -> FustOrmError {
+        if let FustOrmError::Database(err) = &mut self {
+            err.statement = Some((sql.to_string(), timing::summarize(params)));
+        }
+        self
+    }
+}
+
+impl From<sqlx::Error> for FustOrmError {
+    fn from(err: sqlx::Error) -> FustOrmError {
+        FustOrmError::Database(DbError::new(
+            &err,
+            format!("Query execution error: {}", err),
+        ))
+    }
 }
 
 impl From<FustOrmError> for PyErr {
     fn from(err: FustOrmError) -> PyErr {
-        pyo3::exceptions::PyValueError::new_err(err.to_string())
+        let message = err.to_string();
+        Python::attach(|py| {
+            let py_err = PyErr::from_type(err.exception_type(py), message);
+            if let FustOrmError::Database(db_err) = &err {
+                db_err
+                    .annotate(py, &py_err)
+                    .unwrap_or_else(|e| log::debug!("Couldn't annotate the error: {}", e));
+            }
+            py_err
+        })
     }
 }
@@ -0,0 +1,259 @@
+use std::sync::Arc;
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyFloat, PyInt, PyString};
+
+use crate::column_field::ColumnField;
+use crate::geo;
+use crate::query::{SqlParam, in_list, py_any_to_string};
+use crate::where_condition::WhereCondition;
+
+/// A server-side SQL expression over column values.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    /// A reference to a column of the queried table.
+    Column(String),
+    /// A value bound as a parameter (numbers are inlined).
+    Value(Arc<Py<PyAny>>),
+    /// A SQL fragment inserted verbatim, such as a `CASE` expression or a
+    /// correlated subquery.
+    Raw(String),
+    /// An arithmetic operation such as `counter + 1`.
+    Binary {
+        operator: &'static str,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    /// A call of a SQL function such as `bm25(documents)`.
+    Call {
+        function: &'static str,
+        args: Vec<Expr>,
+    },
+}
+
+impl Expr {
+    /// Converts an operand of an arithmetic operation or comparison into an
+    /// expression: `F` objects and `ColumnField`s become column references,
+    /// anything else a value.
+    pub fn from_operand(operand: &Bound<PyAny>) -> Expr {
+        if let Ok(f) = operand.downcast::<F>() {
+            return f.get().expr.clone();
+        }
+        if let Ok(column) = operand.extract::<PyRef<ColumnField>>() {
+            return Expr::Column(column.column_name.clone());
+        }
+        Expr::Value(Arc::new(operand.clone().unbind()))
+    }
+
+    /// Renders the expression, pushing the parameters it binds.
+    pub fn to_sql(&self, py: Python, params: &mut Vec<String>) -> PyResult<String> {
+        match self {
+            Expr::Column(name) => Ok(name.clone()),
+            Expr::Raw(sql) => Ok(format!("({})", sql)),
+            Expr::Value(value) => {
+                let value = value.bind(py);
+                if value.is_none() {
+                    return Ok("NULL".to_string());
+                }
+                // Numbers are inlined so that backends infer numeric types for
+                // arithmetic (all parameters are bound as strings).
+                if !value.is_instance_of::<PyBool>()
+                    && (value.is_instance_of::<PyInt>() || value.is_instance_of::<PyFloat>())
+                {
+                    return Ok(value.str()?.to_string());
+                }
+                match py_any_to_string(py, &value.clone().unbind(), "expression")? {
+                    SqlParam::Single(s) => {
+                        params.push(s);
+                        Ok("?".to_string())
+                    }
+                    SqlParam::List(values) => Ok(in_list(values, params)),
+                }
+            }
+            Expr::Binary {
+                operator,
+                left,
+                right,
+            } => Ok(format!(
+                "({} {} {})",
+                left.to_sql(py, params)?,
+                operator,
+                right.to_sql(py, params)?
+            )),
+            Expr::Call { function, args } => Ok(format!(
+                "{}({})",
+                function,
+                args.iter()
+                    .map(|arg| arg.to_sql(py, params))
+                    .collect::<PyResult<Vec<_>>>()?
+                    .join(", ")
+            )),
+        }
+    }
+
+    fn binary(operator: &'static str, left: Expr, right: Expr) -> F {
+        F {
+            expr: Expr::Binary {
+                operator,
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+        }
+    }
+}
+
+/// A reference to a column, evaluated by the database.
+///
+/// `F("counter") + 1` makes an update increment the stored value instead of
+/// writing one computed in Python, and `F("ends_at") > F("starts_at")` compares
+/// two columns of the same row. Arithmetic (`+ - * / %`) on `F` objects builds
+/// larger expressions; comparisons produce `WhereCondition`s.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct F {
+    pub expr: Expr,
+}
+
+impl F {
+    /// Extracts the value at `path` (a JSON path such as `"$.address.city"`,
+    /// an object key or an array index) from the JSON document `expr`, with
+    /// SQLite's `->>` operator, or its `->` operator as JSON text if
+    /// `as_json`. The path is bound as a parameter.
+    pub(crate) fn json_path(expr: Expr, path: &Bound<PyAny>, as_json: bool) -> PyResult<F> {
+        if !(path.is_instance_of::<PyString>()
+            || path.is_instance_of::<PyInt>() && !path.is_instance_of::<PyBool>())
+        {
+            return Err(PyTypeError::new_err(format!(
+                "A JSON path must be a str or an int, not {}.",
+                path.get_type().name()?
+            )));
+        }
+        let operator = if as_json { "->" } else { "->>" };
+        Ok(Expr::binary(
+            operator,
+            expr,
+            Expr::Value(Arc::new(path.clone().unbind())),
+        ))
+    }
+
+    fn compare(&self, operator: &str, other: &Bound<PyAny>) -> WhereCondition {
+        let operator = match operator {
+            "=" if other.is_none() => "IS",
+            "!=" if other.is_none() => "IS NOT",
+            operator => operator,
+        };
+        WhereCondition::from_expression(self.expr.clone(), operator, other.clone().unbind())
+    }
+}
+
+#[pymethods]
+impl F {
+    #[new]
+    fn new(column: String) -> Self {
+        F {
+            expr: Expr::Column(column),
+        }
+    }
+
+    fn __add__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("+", self.expr.clone(), Expr::from_operand(other))
+    }
+
+    fn __radd__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("+", Expr::from_operand(other), self.expr.clone())
+    }
+
+    fn __sub__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("-", self.expr.clone(), Expr::from_operand(other))
+    }
+
+    fn __rsub__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("-", Expr::from_operand(other), self.expr.clone())
+    }
+
+    fn __mul__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("*", self.expr.clone(), Expr::from_operand(other))
+    }
+
+    fn __rmul__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("*", Expr::from_operand(other), self.expr.clone())
+    }
+
+    fn __truediv__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("/", self.expr.clone(), Expr::from_operand(other))
+    }
+
+    fn __rtruediv__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("/", Expr::from_operand(other), self.expr.clone())
+    }
+
+    fn __mod__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("%", self.expr.clone(), Expr::from_operand(other))
+    }
+
+    fn __rmod__(&self, other: &Bound<PyAny>) -> F {
+        Expr::binary("%", Expr::from_operand(other), self.expr.clone())
+    }
+
+    /// Extracts the value at `path` from the JSON document of the expression
+    /// (see `ColumnField.json()`).
+    #[pyo3(signature = (path, *, as_json = false))]
+    fn json(&self, path: &Bound<PyAny>, as_json: bool) -> PyResult<F> {
+        F::json_path(self.expr.clone(), path, as_json)
+    }
+
+    /// Checks that the geometry of the expression is within `geometry` (see
+    /// `ColumnField.within()`).
+    #[pyo3(signature = (geometry, *, srid = None))]
+    fn within(
+        &self,
+        py: Python,
+        geometry: &Bound<PyAny>,
+        srid: Option<u32>,
+    ) -> PyResult<WhereCondition> {
+        geo::predicate(py, "ST_Within", self.expr.clone(), geometry, srid)
+    }
+
+    /// Checks that the geometry of the expression intersects `geometry` (see
+    /// `ColumnField.intersects()`).
+    #[pyo3(signature = (geometry, *, srid = None))]
+    fn intersects(
+        &self,
+        py: Python,
+        geometry: &Bound<PyAny>,
+        srid: Option<u32>,
+    ) -> PyResult<WhereCondition> {
+        geo::predicate(py, "ST_Intersects", self.expr.clone(), geometry, srid)
+    }
+
+    fn __eq__(&self, other: &Bound<PyAny>) -> WhereCondition {
+        self.compare("=", other)
+    }
+
+    fn __ne__(&self, other: &Bound<PyAny>) -> WhereCondition {
+        self.compare("!=", other)
+    }
+
+    fn __gt__(&self, other: &Bound<PyAny>) -> WhereCondition {
+        self.compare(">", other)
+    }
+
+    fn __ge__(&self, other: &Bound<PyAny>) -> WhereCondition {
+        self.compare(">=", other)
+    }
+
+    fn __lt__(&self, other: &Bound<PyAny>) -> WhereCondition {
+        self.compare("<", other)
+    }
+
+    fn __le__(&self, other: &Bound<PyAny>) -> WhereCondition {
+        self.compare("<=", other)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let mut params = Vec::new();
+        let sql = self.expr.to_sql(py, &mut params)?;
+        Ok(format!("<F: {}>", sql))
+    }
+}
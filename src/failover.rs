@@ -0,0 +1,266 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use sqlx::any::{AnyConnectOptions, AnyPoolOptions};
+use sqlx::{AnyPool, ConnectOptions, Connection};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::database::{PoolSettings, connect_options, scheme_dialect};
+use crate::error::FustOrmError;
+use crate::schema::Dialect;
+use crate::telemetry::Span;
+
+/// How long opening a connection may take when checking that a database is
+/// reachable.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A pool connected with several URLs, which it switches between as they
+/// become unreachable.
+struct Failover {
+    /// The options the pool was opened with, which keep identifying it in
+    /// the registries of per-pool settings once it switched to another URL.
+    identity: Arc<AnyConnectOptions>,
+    /// The options the pool opens connections with now.
+    current: Mutex<Weak<AnyConnectOptions>>,
+    /// The options of each URL, the primary first.
+    targets: Vec<AnyConnectOptions>,
+    /// The index of the target the pool connects to.
+    active: AtomicUsize,
+    /// When the pool last switched targets; the connections opened before
+    /// are closed rather than reused.
+    switched: Arc<Mutex<Instant>>,
+    callback: Option<Py<PyAny>>,
+    /// Held while checking the targets, so that concurrent failures switch
+    /// once.
+    switching: AsyncMutex<()>,
+}
+
+/// The pools connected with several URLs.
+static FAILOVERS: Mutex<Vec<Arc<Failover>>> = Mutex::new(Vec::new());
+
+/// The failover of the pool whose current options are `options`.
+fn find(options: &Arc<AnyConnectOptions>) -> Option<Arc<Failover>> {
+    let failovers = FAILOVERS.lock().unwrap_or_else(|e| e.into_inner());
+    if failovers.is_empty() {
+        return None;
+    }
+    failovers
+        .iter()
+        .find(|failover| {
+            let current = failover.current.lock().unwrap_or_else(|e| e.into_inner());
+            current.as_ptr() == Arc::as_ptr(options)
+        })
+        .cloned()
+}
+
+/// The options a pool whose current options are `options` is identified
+/// by, if it switched targets since it was opened.
+pub(crate) fn identity(options: &Arc<AnyConnectOptions>) -> Option<Arc<AnyConnectOptions>> {
+    find(options).map(|failover| failover.identity.clone())
+}
+
+/// The URL of `options` without its password, for logs and callbacks.
+fn redacted(options: &AnyConnectOptions) -> String {
+    let mut url = options.database_url.clone();
+    if url.password().is_some() {
+        let _ = url.set_password(Some("***"));
+    }
+    url.to_string()
+}
+
+/// Whether a connection to `target` opens.
+async fn probe(target: &AnyConnectOptions) -> Result<(), String> {
+    match tokio::time::timeout(PROBE_TIMEOUT, target.connect()).await {
+        Ok(Ok(conn)) => {
+            let _ = conn.close().await;
+            Ok(())
+        }
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("timed out after {}s", PROBE_TIMEOUT.as_secs())),
+    }
+}
+
+/// Calls the callback of `failover` with an event: `"failover"` when
+/// switching away from the primary or between secondaries, `"failback"`
+/// when switching back to the primary.
+fn notify(failover: &Failover, from: usize, to: usize, error: Option<&str>) {
+    let event = if to == 0 { "failback" } else { "failover" };
+    let (from, to) = (
+        redacted(&failover.targets[from]),
+        redacted(&failover.targets[to]),
+    );
+    match error {
+        Some(error) => warn!("Failed over from {} to {}: {}", from, to, error),
+        None => info!("Failed back to {}", to),
+    }
+    let Some(callback) = &failover.callback else {
+        return;
+    };
+    Python::attach(|py| {
+        let called = (|| -> PyResult<()> {
+            let details = PyDict::new(py);
+            details.set_item("event", event)?;
+            details.set_item("from", &from)?;
+            details.set_item("to", &to)?;
+            details.set_item("error", error)?;
+            callback.call1(py, (details,))?;
+            Ok(())
+        })();
+        if let Err(e) = called {
+            e.write_unraisable(py, Some(callback.bind(py)));
+        }
+    });
+}
+
+/// Makes `pool` open its connections to target `to` of `failover`.
+fn switch(pool: &AnyPool, failover: &Failover, to: usize, error: Option<&str>) {
+    let from = failover.active.swap(to, Ordering::SeqCst);
+    {
+        // Looking the pool up waits for the switch.
+        let _failovers = FAILOVERS.lock().unwrap_or_else(|e| e.into_inner());
+        pool.set_connect_options(failover.targets[to].clone());
+        *failover.current.lock().unwrap_or_else(|e| e.into_inner()) =
+            Arc::downgrade(&pool.connect_options());
+        *failover.switched.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+    notify(failover, from, to, error);
+}
+
+/// Switches `pool` to the first other reachable target, after `error`
+/// failed to check a connection out of it, if it was connected with several
+/// URLs and its target is unreachable. Returns whether checking a
+/// connection out again may succeed.
+pub(crate) async fn recover(pool: &AnyPool, error: &sqlx::Error) -> bool {
+    if !matches!(
+        error,
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut
+    ) {
+        return false;
+    }
+    let Some(failover) = find(&pool.connect_options()) else {
+        return false;
+    };
+    let failed = pool.connect_options();
+    let _switching = failover.switching.lock().await;
+    if !Arc::ptr_eq(&pool.connect_options(), &failed) {
+        // Another statement switched meanwhile.
+        return true;
+    }
+    let active = failover.active.load(Ordering::SeqCst);
+    // A pool whose connections are all busy times out too.
+    if probe(&failover.targets[active]).await.is_ok() {
+        return false;
+    }
+    for (i, target) in failover.targets.iter().enumerate() {
+        if i != active && probe(target).await.is_ok() {
+            switch(pool, &failover, i, Some(&error.to_string()));
+            return true;
+        }
+    }
+    false
+}
+
+/// Switches `pool` back to the primary every `retry` once it is reachable
+/// again, until the pool is closed.
+fn watch(pool: AnyPool, failover: Arc<Failover>, retry: Duration) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(retry).await;
+            if pool.is_closed() {
+                break;
+            }
+            if failover.active.load(Ordering::SeqCst) == 0 {
+                continue;
+            }
+            let _switching = failover.switching.lock().await;
+            if failover.active.load(Ordering::SeqCst) != 0
+                && probe(&failover.targets[0]).await.is_ok()
+            {
+                switch(&pool, &failover, 0, None);
+            }
+        }
+        FAILOVERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|other| !Arc::ptr_eq(other, &failover));
+    });
+}
+
+/// Opens a pool of connections to the first reachable of `urls`, which
+/// fails over to the next reachable one when its target becomes
+/// unreachable, and back to the first every `retry` once it is reachable
+/// again, calling `callback` with each switch.
+pub(crate) async fn connect(
+    urls: &[String],
+    echo: bool,
+    statement_cache_capacity: Option<usize>,
+    settings: PoolSettings,
+    callback: Option<Py<PyAny>>,
+    retry: Duration,
+) -> Result<AnyPool, FustOrmError> {
+    let targets = urls
+        .iter()
+        .map(|url| connect_options(url, echo, statement_cache_capacity, &[]))
+        .collect::<Result<Vec<_>, _>>()?;
+    let dialect = scheme_dialect(targets[0].database_url.scheme());
+    if dialect == Dialect::Sqlite
+        || targets
+            .iter()
+            .any(|target| scheme_dialect(target.database_url.scheme()) != dialect)
+    {
+        return Err(FustOrmError::ConnectionError(
+            "Failover is only supported across PostgreSQL or MySQL databases, not a mix."
+                .to_string(),
+        ));
+    }
+    let switched = Arc::new(Mutex::new(Instant::now()));
+    let mut errors = Vec::new();
+    let mut connected = None;
+    for (i, target) in targets.iter().enumerate() {
+        let since = switched.clone();
+        let pool_options = settings
+            .apply(AnyPoolOptions::new())
+            .before_acquire(move |_, meta| {
+                let elapsed = since.lock().unwrap_or_else(|e| e.into_inner()).elapsed();
+                Box::pin(async move { Ok(meta.age <= elapsed) })
+            });
+        let span = Span::start("connect", dialect, None);
+        let pool = pool_options.connect_with(target.clone()).await;
+        span.record_result(&pool);
+        match pool {
+            Ok(pool) => {
+                connected = Some((i, pool));
+                break;
+            }
+            Err(e) => errors.push(format!("{}: {}", redacted(target), e)),
+        }
+    }
+    let Some((active, pool)) = connected else {
+        return Err(FustOrmError::ConnectionError(format!(
+            "Can't connect to any of the databases: {}",
+            errors.join("; ")
+        )));
+    };
+    let failover = Arc::new(Failover {
+        identity: pool.connect_options(),
+        current: Mutex::new(Arc::downgrade(&pool.connect_options())),
+        targets,
+        active: AtomicUsize::new(active),
+        switched,
+        callback,
+        switching: AsyncMutex::new(()),
+    });
+    FAILOVERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(failover.clone());
+    if active > 0 {
+        notify(&failover, 0, active, errors.first().map(String::as_str));
+    }
+    watch(pool.clone(), failover, retry);
+    Ok(pool)
+}
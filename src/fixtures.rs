@@ -0,0 +1,229 @@
+use std::path::{Path, PathBuf};
+
+use crate::database::{Database, begin_write, execute_statement, fetch_rows};
+use crate::error::FustOrmError;
+use crate::introspect;
+use crate::query::{SqlParam, py_any_to_string};
+use crate::schema::Dialect;
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::AnyPool;
+
+/// The rows a fixture gives for one table, each as its column names and
+/// values (`None` for `NULL`).
+pub(crate) struct TableRows {
+    table: String,
+    rows: Vec<(Vec<String>, Vec<Option<String>>)>,
+}
+
+fn fixture_error(path: &Path, message: impl std::fmt::Display) -> PyErr {
+    FustOrmError::BuildError(format!("Invalid fixture '{}': {}", path.display(), message)).into()
+}
+
+/// The fixture files of `path`: the file itself, or the `.json`, `.yaml` and
+/// `.yml` files of a directory in name order.
+fn fixture_files(path: &Path) -> PyResult<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = std::fs::read_dir(path)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|e| fixture_error(path, e))?;
+    files.retain(|file| {
+        matches!(
+            file.extension().and_then(|e| e.to_str()),
+            Some("json" | "yaml" | "yml")
+        )
+    });
+    files.sort();
+    Ok(files)
+}
+
+/// Parses a fixture file into a mapping of table names to lists of rows.
+fn parse_file<'py>(py: Python<'py>, path: &Path) -> PyResult<Bound<'py, PyAny>> {
+    let text = std::fs::read_to_string(path).map_err(|e| fixture_error(path, e))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => py.import("json")?.call_method1("loads", (text,)),
+        Some("yaml" | "yml") => {
+            let yaml = py
+                .import("yaml")
+                .map_err(|_| fixture_error(path, "loading YAML fixtures requires PyYAML"))?;
+            yaml.call_method1("safe_load", (text,))
+        }
+        _ => Err(fixture_error(path, "expected a .json, .yaml or .yml file")),
+    }
+}
+
+/// Converts a fixture value to a parameter. Mappings and lists are stored as
+/// JSON.
+pub(crate) fn fixture_param(
+    py: Python,
+    value: &Bound<PyAny>,
+    column: &str,
+) -> PyResult<Option<String>> {
+    if value.is_none() {
+        return Ok(None);
+    }
+    if value.is_instance_of::<PyDict>() || value.is_instance_of::<PyList>() {
+        let json = py.import("json")?.call_method1("dumps", (value,))?;
+        return Ok(Some(json.extract()?));
+    }
+    match py_any_to_string(py, &value.clone().unbind(), column)? {
+        SqlParam::Single(param) => Ok(Some(param)),
+        SqlParam::List(_) => unreachable!("lists are stored as JSON"),
+    }
+}
+
+/// Reads the rows of the fixture files of `path`, merging the rows given for
+/// the same table in file order.
+pub(crate) fn read_fixtures(py: Python, path: &Path) -> PyResult<Vec<TableRows>> {
+    let mut tables: Vec<TableRows> = Vec::new();
+    for file in fixture_files(path)? {
+        let data = parse_file(py, &file)?;
+        let data = data
+            .downcast::<PyDict>()
+            .map_err(|_| fixture_error(&file, "expected a mapping of table names to rows"))?;
+        for (table, rows) in data.iter() {
+            let table: String = table
+                .extract()
+                .map_err(|_| fixture_error(&file, "table names must be strings"))?;
+            let rows = rows.downcast::<PyList>().map_err(|_| {
+                fixture_error(&file, format!("the rows of '{}' must be a list", table))
+            })?;
+            let mut parsed = Vec::with_capacity(rows.len());
+            for row in rows.iter() {
+                let row = row.downcast::<PyDict>().map_err(|_| {
+                    fixture_error(&file, format!("the rows of '{}' must be mappings", table))
+                })?;
+                let mut columns = Vec::with_capacity(row.len());
+                let mut values = Vec::with_capacity(row.len());
+                for (column, value) in row.iter() {
+                    let column: String = column.extract().map_err(|_| {
+                        fixture_error(
+                            &file,
+                            format!("column names of '{}' must be strings", table),
+                        )
+                    })?;
+                    values.push(fixture_param(py, &value, &column)?);
+                    columns.push(column);
+                }
+                parsed.push((columns, values));
+            }
+            match tables.iter_mut().find(|t| t.table == table) {
+                Some(existing) => existing.rows.extend(parsed),
+                None => tables.push(TableRows {
+                    table,
+                    rows: parsed,
+                }),
+            }
+        }
+    }
+    Ok(tables)
+}
+
+/// Orders `tables` so that the tables they reference come first.
+async fn insertion_order(
+    pool: &AnyPool,
+    dialect: Dialect,
+    mut tables: Vec<TableRows>,
+) -> Result<Vec<TableRows>, FustOrmError> {
+    let live = introspect::tables(pool, dialect).await?;
+    if let Some(table) = tables.iter().find(|t| !live.contains(&t.table)) {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "Fixture table '{}' doesn't exist.",
+            table.table
+        )));
+    }
+    let names = tables.iter().map(|t| t.table.clone()).collect();
+    let order = introspect::dependency_order(pool, dialect, names).await?;
+    tables.sort_by_key(|t| order.iter().position(|name| *name == t.table));
+    Ok(tables)
+}
+
+/// Loads the fixture file (or directory of files) at `path` into the database
+/// in one transaction. Resolves to the number of rows inserted per table, in
+/// insertion order.
+pub(crate) fn load_fixtures<'py>(
+    py: Python<'py>,
+    db: &Database,
+    path: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let tables = read_fixtures(py, Path::new(path))?;
+    let dialect = db.dialect();
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let counts = insert_fixtures(&pool, dialect, tables).await?;
+        Python::attach(|py| -> PyResult<Py<PyDict>> {
+            let result = PyDict::new(py);
+            for (table, count) in counts {
+                result.set_item(table, count)?;
+            }
+            Ok(result.unbind())
+        })
+    })
+}
+
+/// Inserts the rows of `tables` in one transaction, referenced tables first.
+/// Returns the number of rows inserted per table, in insertion order.
+pub(crate) async fn insert_fixtures(
+    pool: &AnyPool,
+    dialect: Dialect,
+    tables: Vec<TableRows>,
+) -> Result<Vec<(String, usize)>, FustOrmError> {
+    let tables = insertion_order(pool, dialect, tables).await?;
+    let mut tx = begin_write(pool).await?;
+    let mut counts = Vec::with_capacity(tables.len());
+    for TableRows { table, rows } in &tables {
+        debug!("Loading {} fixture rows into '{}'", rows.len(), table);
+        for (columns, values) in rows {
+            let mut params = Vec::new();
+            let placeholders: Vec<&str> = values
+                .iter()
+                .map(|value| match value {
+                    Some(param) => {
+                        params.push(param.clone());
+                        "?"
+                    }
+                    None => "NULL",
+                })
+                .collect();
+            let sql = if columns.is_empty() {
+                format!("INSERT INTO {} DEFAULT VALUES", table)
+            } else {
+                format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table,
+                    columns.join(", "),
+                    placeholders.join(", ")
+                )
+            };
+            execute_statement(&mut *tx, &sql, params).await?;
+        }
+        // Rows inserted with explicit ids don't advance the sequence of an
+        // identity or serial column on PostgreSQL.
+        if dialect == Dialect::Postgres {
+            let primary_key: Vec<_> = introspect::columns(pool, dialect, table)
+                .await?
+                .into_iter()
+                .filter(|c| c.primary_key)
+                .collect();
+            if let [column] = primary_key.as_slice() {
+                let sql = format!(
+                    "SELECT setval(pg_get_serial_sequence($1, $2), (SELECT MAX({}) FROM {})) \
+                     WHERE pg_get_serial_sequence($1, $2) IS NOT NULL",
+                    column.name, table
+                );
+                fetch_rows(&mut *tx, &sql, vec![table.clone(), column.name.clone()]).await?;
+            }
+        }
+        counts.push((table.clone(), rows.len()));
+    }
+    tx.commit().await.map_err(FustOrmError::from)?;
+    Ok(counts)
+}
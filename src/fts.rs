@@ -0,0 +1,306 @@
+use std::sync::Arc;
+
+use crate::database::{Database, begin_write, execute_statement};
+use crate::error::FustOrmError;
+use crate::expression::{Expr, F};
+use crate::model::ModelInfo;
+use crate::schema::table_name;
+use crate::sqlite_hooks::require_sqlite;
+use log::debug;
+use pyo3::IntoPyObjectExt;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use pyo3_async_runtimes::tokio::future_into_py;
+
+/// Quotes an option of `fts5(...)` as a string literal.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// An FTS5 full-text index, a SQLite virtual table searched with `MATCH`:
+///
+/// ```python
+/// search = FtsTable("article_search", ["title", "body"], content=Article)
+/// await search.create(db)
+/// ```
+///
+/// With `content`, the index stores no copy of the text: it reads it from the
+/// rows of that table whose `content_rowid` column matches, and triggers keep
+/// it up to date as they change.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct FtsTable {
+    #[pyo3(get)]
+    pub(crate) name: String,
+    #[pyo3(get)]
+    pub(crate) columns: Vec<String>,
+    /// The table holding the indexed text, if not the index itself.
+    #[pyo3(get)]
+    pub(crate) content: Option<String>,
+    /// The column of `content` the rows are identified by.
+    #[pyo3(get)]
+    pub(crate) content_rowid: String,
+    /// The tokenizer and its arguments, e.g. `"porter unicode61"`.
+    #[pyo3(get)]
+    pub(crate) tokenize: Option<String>,
+    /// The lengths of the prefixes indexed for prefix queries such as `rus*`.
+    #[pyo3(get)]
+    pub(crate) prefix: Vec<u32>,
+}
+
+impl FtsTable {
+    /// Renders the statements creating the index and, with `content`, the
+    /// triggers keeping it up to date and the statement indexing the rows
+    /// already there.
+    pub(crate) fn create_sql(&self, if_not_exists: bool) -> Vec<String> {
+        let if_not_exists = if if_not_exists { "IF NOT EXISTS " } else { "" };
+        let mut arguments = self.columns.clone();
+        if let Some(content) = &self.content {
+            arguments.push(format!("content={}", quote(content)));
+            arguments.push(format!("content_rowid={}", quote(&self.content_rowid)));
+        }
+        if let Some(tokenize) = &self.tokenize {
+            arguments.push(format!("tokenize={}", quote(tokenize)));
+        }
+        if !self.prefix.is_empty() {
+            let lengths: Vec<String> = self.prefix.iter().map(u32::to_string).collect();
+            arguments.push(format!("prefix={}", quote(&lengths.join(" "))));
+        }
+        let mut statements = vec![format!(
+            "CREATE VIRTUAL TABLE {}{} USING fts5({})",
+            if_not_exists,
+            self.name,
+            arguments.join(", ")
+        )];
+        let Some(content) = &self.content else {
+            return statements;
+        };
+        let columns = self.columns.join(", ");
+        let values = |row: &str| {
+            std::iter::once(format!("{}.{}", row, self.content_rowid))
+                .chain(self.columns.iter().map(|c| format!("{}.{}", row, c)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let insert = format!(
+            "INSERT INTO {} (rowid, {}) VALUES ({});",
+            self.name,
+            columns,
+            values("NEW")
+        );
+        let delete = format!(
+            "INSERT INTO {} ({}, rowid, {}) VALUES ('delete', {});",
+            self.name,
+            self.name,
+            columns,
+            values("OLD")
+        );
+        for (event, body) in [
+            ("insert", insert.clone()),
+            ("delete", delete.clone()),
+            ("update", format!("{} {}", delete, insert)),
+        ] {
+            statements.push(format!(
+                "CREATE TRIGGER {}{}_after_{}_trg AFTER {} ON {} FOR EACH ROW BEGIN {} END",
+                if_not_exists,
+                self.name,
+                event,
+                event.to_uppercase(),
+                content,
+                body
+            ));
+        }
+        statements.push(format!(
+            "INSERT INTO {} ({}) VALUES ('rebuild')",
+            self.name, self.name
+        ));
+        statements
+    }
+}
+
+#[pymethods]
+impl FtsTable {
+    #[new]
+    #[pyo3(signature = (name, columns, *, content = None, content_rowid = "id".to_string(), tokenize = None, prefix = Vec::new()))]
+    fn new(
+        name: String,
+        columns: Vec<String>,
+        content: Option<&Bound<PyAny>>,
+        content_rowid: String,
+        tokenize: Option<String>,
+        prefix: Vec<u32>,
+    ) -> PyResult<Self> {
+        if columns.is_empty() {
+            return Err(FustOrmError::BuildError(format!(
+                "Full-text index '{}' has no columns.",
+                name
+            ))
+            .into());
+        }
+        if prefix.contains(&0) {
+            return Err(
+                FustOrmError::BuildError("Prefix lengths must be at least 1.".to_string()).into(),
+            );
+        }
+        Ok(FtsTable {
+            name,
+            columns,
+            content: content.map(table_name).transpose()?,
+            content_rowid,
+            tokenize,
+            prefix,
+        })
+    }
+
+    /// Describes the index a Model class maps onto, with the model's columns.
+    #[staticmethod]
+    #[pyo3(signature = (model, *, content = None, content_rowid = "id".to_string(), tokenize = None, prefix = Vec::new()))]
+    fn from_model(
+        model: &Bound<PyType>,
+        content: Option<&Bound<PyAny>>,
+        content_rowid: String,
+        tokenize: Option<String>,
+        prefix: Vec<u32>,
+    ) -> PyResult<Self> {
+        let info = ModelInfo::from_class(model)?;
+        let columns = info.columns.iter().map(|c| c.column_name.clone()).collect();
+        FtsTable::new(
+            info.table_name,
+            columns,
+            content,
+            content_rowid,
+            tokenize,
+            prefix,
+        )
+    }
+
+    /// Renders the statements creating the index, separated by `;`.
+    #[pyo3(signature = (*, if_not_exists = false))]
+    fn to_sql(&self, if_not_exists: bool) -> String {
+        self.create_sql(if_not_exists).join(";\n")
+    }
+
+    /// Creates the index in the database. Only supported on SQLite.
+    #[pyo3(signature = (db, *, if_not_exists = false))]
+    fn create<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+        if_not_exists: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        require_sqlite(db, "FtsTable.create")?;
+        let statements = self.create_sql(if_not_exists);
+        let pool = db.pool.clone();
+        future_into_py(py, async move {
+            let mut tx = begin_write(&pool).await?;
+            for sql in &statements {
+                debug!("Creating full-text index: {}", sql);
+                execute_statement(&mut *tx, sql, Vec::new()).await?;
+            }
+            tx.commit().await.map_err(FustOrmError::from)?;
+            Ok(())
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<FtsTable {} ({})>", self.name, self.columns.join(", "))
+    }
+}
+
+/// Resolves a full-text index given as a name, an `FtsTable` or a Model class.
+fn index_name(index: &Bound<PyAny>) -> PyResult<String> {
+    if let Ok(index) = index.downcast::<FtsTable>() {
+        return Ok(index.get().name.clone());
+    }
+    table_name(index)
+}
+
+fn call(function: &'static str, args: Vec<Expr>) -> F {
+    F {
+        expr: Expr::Call { function, args },
+    }
+}
+
+fn value<'py>(py: Python<'py>, value: impl IntoPyObject<'py>) -> PyResult<Expr> {
+    Ok(Expr::Value(Arc::new(value.into_py_any(py)?)))
+}
+
+/// The relevance of the rows matched in `index`, for `annotate()` or
+/// `QueryBuilder.search()`: lower is better. `weights` weigh matches in each
+/// column, in order.
+pub(crate) fn bm25_expr(py: Python, index: &str, weights: &[f64]) -> PyResult<Expr> {
+    let mut args = vec![Expr::Column(index.to_string())];
+    for weight in weights {
+        args.push(value(py, *weight)?);
+    }
+    Ok(Expr::Call {
+        function: "bm25",
+        args,
+    })
+}
+
+/// The relevance of the rows matched in `index`, lower being better, as an
+/// expression for `annotate()`. `weights` weigh matches in each column, in
+/// order.
+#[pyfunction]
+#[pyo3(signature = (index, *weights))]
+pub fn bm25(py: Python, index: &Bound<PyAny>, weights: Vec<f64>) -> PyResult<F> {
+    Ok(F {
+        expr: bm25_expr(py, &index_name(index)?, &weights)?,
+    })
+}
+
+/// The text of `column` (its index among the columns of `index`) with the
+/// matched phrases between `open` and `close`, as an expression for
+/// `annotate()`.
+#[pyfunction]
+pub fn highlight(
+    py: Python,
+    index: &Bound<PyAny>,
+    column: i64,
+    open: String,
+    close: String,
+) -> PyResult<F> {
+    Ok(call(
+        "highlight",
+        vec![
+            Expr::Column(index_name(index)?),
+            value(py, column)?,
+            value(py, open)?,
+            value(py, close)?,
+        ],
+    ))
+}
+
+/// A fragment of up to `tokens` tokens of `column` (-1 for the best one)
+/// around the matched phrases, which are put between `open` and `close`, as
+/// an expression for `annotate()`. `ellipsis` marks where text was cut.
+#[pyfunction]
+#[pyo3(signature = (index, column, open, close, ellipsis = "…".to_string(), tokens = 16))]
+pub fn snippet(
+    py: Python,
+    index: &Bound<PyAny>,
+    column: i64,
+    open: String,
+    close: String,
+    ellipsis: String,
+    tokens: i64,
+) -> PyResult<F> {
+    if !(1..=64).contains(&tokens) {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "snippet() takes 1 to 64 tokens.".to_string(),
+        )
+        .into());
+    }
+    Ok(call(
+        "snippet",
+        vec![
+            Expr::Column(index_name(index)?),
+            value(py, column)?,
+            value(py, open)?,
+            value(py, close)?,
+            value(py, ellipsis)?,
+            value(py, tokens)?,
+        ],
+    ))
+}
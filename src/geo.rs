@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::column_field::ColumnField;
+use crate::expression::{Expr, F};
+use crate::where_condition::WhereCondition;
+use pyo3::IntoPyObjectExt;
+use pyo3::exceptions::{PyImportError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+
+/// The WKT text of a geometry given as text or as an object with a `wkt`
+/// attribute, as shapely geometries have.
+fn wkt(geometry: &Bound<PyAny>) -> PyResult<String> {
+    if let Ok(text) = geometry.downcast::<PyString>() {
+        return Ok(text.to_string());
+    }
+    match geometry.getattr("wkt") {
+        Ok(text) if text.is_instance_of::<PyString>() => text.extract(),
+        _ => Err(PyTypeError::new_err(format!(
+            "A geometry must be WKT text or have a `wkt` attribute, not {}.",
+            geometry.get_type().name()?
+        ))),
+    }
+}
+
+/// Converts the operand of a spatial function into an expression: `F`
+/// objects and `ColumnField`s become column references, anything else a
+/// geometry parsed from its WKT text (bound as a parameter) by the database.
+pub(crate) fn geometry_expr(
+    py: Python,
+    geometry: &Bound<PyAny>,
+    srid: Option<u32>,
+) -> PyResult<Expr> {
+    if geometry.is_instance_of::<F>() || geometry.is_instance_of::<ColumnField>() {
+        return Ok(Expr::from_operand(geometry));
+    }
+    let mut args = vec![Expr::Value(Arc::new(wkt(geometry)?.into_py_any(py)?))];
+    if let Some(srid) = srid {
+        args.push(Expr::Value(Arc::new(srid.into_py_any(py)?)));
+    }
+    Ok(Expr::Call {
+        function: "ST_GeomFromText",
+        args,
+    })
+}
+
+/// A condition calling the spatial predicate `function` on the geometry
+/// `expr` and `other`.
+pub(crate) fn predicate(
+    py: Python,
+    function: &'static str,
+    expr: Expr,
+    other: &Bound<PyAny>,
+    srid: Option<u32>,
+) -> PyResult<WhereCondition> {
+    let args = vec![expr, geometry_expr(py, other, srid)?];
+    Ok(WhereCondition::from_predicate(
+        py,
+        Expr::Call { function, args },
+    ))
+}
+
+/// A column given as a `ColumnField`, an `F` expression or a name.
+fn column_expr(column: &Bound<PyAny>) -> PyResult<Expr> {
+    if let Ok(name) = column.downcast::<PyString>() {
+        return Ok(Expr::Column(name.to_string()));
+    }
+    if column.is_instance_of::<F>() || column.is_instance_of::<ColumnField>() {
+        return Ok(Expr::from_operand(column));
+    }
+    Err(PyTypeError::new_err(format!(
+        "Expected a column, not {}.",
+        column.get_type().name()?
+    )))
+}
+
+/// The geometry `value` (WKT text or a shapely-compatible object), in the
+/// spatial reference system `srid` if given, as an expression to update a
+/// geometry column with or to compare.
+#[pyfunction]
+#[pyo3(signature = (value, *, srid = None))]
+pub fn to_geometry(py: Python, value: &Bound<PyAny>, srid: Option<u32>) -> PyResult<F> {
+    if value.is_instance_of::<F>() || value.is_instance_of::<ColumnField>() {
+        return Err(PyTypeError::new_err(
+            "to_geometry() takes WKT text or a geometry object, not a column.",
+        ));
+    }
+    Ok(F {
+        expr: geometry_expr(py, value, srid)?,
+    })
+}
+
+/// The geometry of `column` as WKB, for `annotate()`. `load_geometry()` turns
+/// the loaded bytes into shapely geometries.
+#[pyfunction]
+pub fn as_wkb(column: &Bound<PyAny>) -> PyResult<F> {
+    Ok(F {
+        expr: Expr::Call {
+            function: "ST_AsBinary",
+            args: vec![column_expr(column)?],
+        },
+    })
+}
+
+/// The geometry of `column` as WKT text, for `annotate()`.
+#[pyfunction]
+pub fn as_wkt(column: &Bound<PyAny>) -> PyResult<F> {
+    Ok(F {
+        expr: Expr::Call {
+            function: "ST_AsText",
+            args: vec![column_expr(column)?],
+        },
+    })
+}
+
+/// Loads a geometry from WKB bytes or WKT text as a shapely geometry, which
+/// requires shapely to be installed. `None` stays `None`.
+#[pyfunction]
+pub fn load_geometry<'py>(
+    py: Python<'py>,
+    value: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    if value.is_none() {
+        return Ok(value.clone());
+    }
+    let format = if value.is_instance_of::<PyBytes>() {
+        "wkb"
+    } else if value.is_instance_of::<PyString>() {
+        "wkt"
+    } else {
+        return Err(PyTypeError::new_err(format!(
+            "load_geometry() takes WKB bytes or WKT text, not {}.",
+            value.get_type().name()?
+        )));
+    };
+    let module = py
+        .import(format!("shapely.{}", format))
+        .map_err(|_| PyImportError::new_err("load_geometry() requires shapely to be installed."))?;
+    module.call_method1("loads", (value,))
+}
@@ -0,0 +1,137 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDelta, PyDeltaAccess, PyFloat, PyInt, PyString};
+
+use crate::error::FustOrmError;
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Renders a `datetime.timedelta` as its exact number of seconds, e.g.
+/// `5400.25`, which PostgreSQL reads as an `interval` and other databases
+/// store as a number.
+pub(crate) fn seconds_literal(delta: &Bound<PyDelta>) -> String {
+    let micros = (delta.get_days() as i64 * SECONDS_PER_DAY + delta.get_seconds() as i64)
+        * MICROS_PER_SECOND
+        + delta.get_microseconds() as i64;
+    let sign = if micros < 0 { "-" } else { "" };
+    let (seconds, fraction) = (
+        micros.abs() / MICROS_PER_SECOND,
+        micros.abs() % MICROS_PER_SECOND,
+    );
+    if fraction == 0 {
+        format!("{}{}", sign, seconds)
+    } else {
+        let fraction = format!("{:06}", fraction);
+        format!("{}{}.{}", sign, seconds, fraction.trim_end_matches('0'))
+    }
+}
+
+/// Parses a decimal number of seconds, e.g. `-90.5`, into microseconds.
+fn parse_seconds(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if whole.is_empty() && fraction.is_empty()
+        || !whole
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let whole: i64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let mut fraction = fraction.to_string();
+    fraction.truncate(6);
+    let fraction: i64 = format!("{:0<6}", fraction).parse().ok()?;
+    let micros = whole
+        .checked_mul(MICROS_PER_SECOND)?
+        .checked_add(fraction)?;
+    Some(if negative { -micros } else { micros })
+}
+
+/// Parses an ISO 8601 duration, e.g. `P1DT2H30M` or `PT0.5S`, into
+/// microseconds. Years count 365 days and months 30, as in PostgreSQL's
+/// conversions of intervals to seconds.
+fn parse_iso8601(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let rest = text.strip_prefix('P')?;
+    if rest.is_empty() {
+        return None;
+    }
+    let mut micros: i64 = 0;
+    let mut in_time = false;
+    let mut number = String::new();
+    for c in rest.chars() {
+        let seconds = match c {
+            'T' if !in_time && number.is_empty() => {
+                in_time = true;
+                continue;
+            }
+            '0'..='9' | '.' | ',' => {
+                number.push(if c == ',' { '.' } else { c });
+                continue;
+            }
+            'Y' if !in_time => 365 * SECONDS_PER_DAY,
+            'M' if !in_time => 30 * SECONDS_PER_DAY,
+            'W' if !in_time => 7 * SECONDS_PER_DAY,
+            'D' if !in_time => SECONDS_PER_DAY,
+            'H' if in_time => 3_600,
+            'M' if in_time => 60,
+            'S' if in_time => 1,
+            _ => return None,
+        };
+        micros = micros.checked_add(parse_seconds(&number)?.checked_mul(seconds)?)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return None;
+    }
+    Some(if negative { -micros } else { micros })
+}
+
+/// Converts a value loaded from an interval column into a
+/// `datetime.timedelta`: a number of seconds, as a number or as text, or an
+/// ISO 8601 duration.
+pub(crate) fn parse_interval<'py>(value: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    let py = value.py();
+    if value.is_instance_of::<PyDelta>() {
+        return Ok(value.clone());
+    }
+    let micros = if value.is_instance_of::<PyInt>() || value.is_instance_of::<PyFloat>() {
+        let seconds: f64 = value.extract()?;
+        Some((seconds * MICROS_PER_SECOND as f64).round() as i64)
+    } else if let Ok(text) = value.downcast::<PyString>() {
+        let text = text.to_str()?.trim();
+        parse_seconds(text).or_else(|| parse_iso8601(text))
+    } else {
+        None
+    };
+    let Some(micros) = micros else {
+        return Err(FustOrmError::QueryError(format!(
+            "Can't load {} as a duration: expected a number of seconds or an ISO 8601 duration.",
+            value.repr()?
+        ))
+        .into());
+    };
+    let days = micros.div_euclid(SECONDS_PER_DAY * MICROS_PER_SECOND);
+    let micros = micros.rem_euclid(SECONDS_PER_DAY * MICROS_PER_SECOND);
+    Ok(PyDelta::new(
+        py,
+        i32::try_from(days).map_err(|_| {
+            FustOrmError::QueryError(format!("The duration {} is out of range.", value))
+        })?,
+        (micros / MICROS_PER_SECOND) as i32,
+        (micros % MICROS_PER_SECOND) as i32,
+        true,
+    )?
+    .into_any())
+}
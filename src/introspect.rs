@@ -0,0 +1,883 @@
+use crate::database::fetch_rows;
+use crate::error::FustOrmError;
+use crate::schema::{Dialect, Generated};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use sqlx::any::AnyRow;
+use sqlx::{AnyPool, Row};
+
+fn map_db_err(e: sqlx::Error) -> FustOrmError {
+    FustOrmError::from(e)
+}
+
+fn text(row: &AnyRow, column: &str) -> Result<Option<String>, FustOrmError> {
+    row.try_get::<Option<String>, _>(column).map_err(map_db_err)
+}
+
+fn flag(row: &AnyRow, column: &str) -> Result<bool, FustOrmError> {
+    Ok(row.try_get::<i64, _>(column).map_err(map_db_err)? != 0)
+}
+
+/// An index of an existing table. Primary key indexes are not listed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IndexInfo {
+    pub name: String,
+    /// The indexed columns in order, `None` for the parts that are expressions.
+    pub columns: Vec<Option<String>>,
+    pub unique: bool,
+    /// Whether the index has a `WHERE` clause.
+    pub partial: bool,
+}
+
+impl IndexInfo {
+    pub(crate) fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", &self.name)?;
+        dict.set_item("columns", &self.columns)?;
+        dict.set_item("unique", self.unique)?;
+        dict.set_item("partial", self.partial)?;
+        Ok(dict)
+    }
+}
+
+/// Adds a key part to the index `name`, creating the index on its first part.
+fn push_part(
+    indexes: &mut Vec<IndexInfo>,
+    name: String,
+    column: Option<String>,
+    unique: bool,
+    partial: bool,
+) {
+    match indexes.iter_mut().find(|i| i.name == name) {
+        Some(index) => index.columns.push(column),
+        None => indexes.push(IndexInfo {
+            name,
+            columns: vec![column],
+            unique,
+            partial,
+        }),
+    }
+}
+
+/// Lists the indexes of `table`, ordered by name.
+pub(crate) async fn indexes(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &str,
+) -> Result<Vec<IndexInfo>, FustOrmError> {
+    let mut indexes = Vec::new();
+    match dialect {
+        Dialect::Sqlite => {
+            let list = fetch_rows(
+                pool,
+                "SELECT name, \"unique\", partial FROM pragma_index_list(?) \
+                 WHERE origin <> 'pk' ORDER BY name",
+                vec![table.to_string()],
+            )
+            .await?;
+            for row in &list {
+                let name = text(row, "name")?.unwrap_or_default();
+                // Expression parts have no name and a cid of -2.
+                let parts = fetch_rows(
+                    pool,
+                    "SELECT name FROM pragma_index_xinfo(?) WHERE key = 1 ORDER BY seqno",
+                    vec![name.clone()],
+                )
+                .await?;
+                let index = IndexInfo {
+                    columns: parts
+                        .iter()
+                        .map(|part| text(part, "name"))
+                        .collect::<Result<_, _>>()?,
+                    unique: flag(row, "unique")?,
+                    partial: flag(row, "partial")?,
+                    name,
+                };
+                indexes.push(index);
+            }
+        }
+        Dialect::Postgres => {
+            let rows = fetch_rows(
+                pool,
+                "SELECT i.relname AS name, a.attname AS column_name, \
+                 CAST(ix.indisunique AS INTEGER) AS is_unique, \
+                 CAST(ix.indpred IS NOT NULL AS INTEGER) AS is_partial \
+                 FROM pg_index ix \
+                 JOIN pg_class t ON t.oid = ix.indrelid \
+                 JOIN pg_class i ON i.oid = ix.indexrelid \
+                 CROSS JOIN LATERAL unnest(ix.indkey) WITH ORDINALITY AS k(attnum, ord) \
+                 LEFT JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = k.attnum \
+                 WHERE t.relname = $1 AND pg_table_is_visible(t.oid) AND NOT ix.indisprimary \
+                 ORDER BY i.relname, k.ord",
+                vec![table.to_string()],
+            )
+            .await?;
+            for row in &rows {
+                push_part(
+                    &mut indexes,
+                    text(row, "name")?.unwrap_or_default(),
+                    text(row, "column_name")?,
+                    flag(row, "is_unique")?,
+                    flag(row, "is_partial")?,
+                );
+            }
+        }
+        Dialect::Mysql => {
+            let rows = fetch_rows(
+                pool,
+                "SELECT INDEX_NAME AS name, COLUMN_NAME AS column_name, \
+                 CAST(NON_UNIQUE AS SIGNED) AS non_unique \
+                 FROM information_schema.statistics \
+                 WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND INDEX_NAME <> 'PRIMARY' \
+                 ORDER BY INDEX_NAME, SEQ_IN_INDEX",
+                vec![table.to_string()],
+            )
+            .await?;
+            for row in &rows {
+                push_part(
+                    &mut indexes,
+                    text(row, "name")?.unwrap_or_default(),
+                    text(row, "column_name")?,
+                    !flag(row, "non_unique")?,
+                    false,
+                );
+            }
+        }
+    }
+    Ok(indexes)
+}
+
+/// A table-level `UNIQUE` or `CHECK` constraint of an existing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ConstraintInfo {
+    /// `None` for constraints declared without `CONSTRAINT <name>` on SQLite.
+    pub name: Option<String>,
+    /// `"unique"` or `"check"`.
+    pub kind: &'static str,
+    /// The columns of a unique constraint.
+    pub columns: Vec<String>,
+    /// The condition of a check constraint, as the database reports it.
+    pub expression: Option<String>,
+}
+
+impl ConstraintInfo {
+    pub(crate) fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", &self.name)?;
+        dict.set_item("type", self.kind)?;
+        dict.set_item("columns", &self.columns)?;
+        dict.set_item("expression", &self.expression)?;
+        Ok(dict)
+    }
+
+    /// Reads a constraint clause such as `CONSTRAINT positive CHECK (price > 0)`
+    /// or `UNIQUE (a, b)`. Other clauses (primary and foreign keys) are `None`.
+    pub(crate) fn parse(clause: &str) -> Option<Self> {
+        let mut rest = clause.trim();
+        let mut name = None;
+        if keyword(rest, "CONSTRAINT") {
+            rest = rest["CONSTRAINT".len()..].trim_start();
+            let end = rest.find(|c: char| c.is_whitespace() || c == '(')?;
+            name = Some(unquote(&rest[..end]).to_string());
+            rest = rest[end..].trim_start();
+        }
+        if keyword(rest, "CHECK") {
+            Some(ConstraintInfo {
+                name,
+                kind: "check",
+                columns: Vec::new(),
+                expression: Some(unwrap_parens(parenthesized(rest)?).to_string()),
+            })
+        } else if keyword(rest, "UNIQUE") {
+            Some(ConstraintInfo {
+                name,
+                kind: "unique",
+                columns: split_top_level(parenthesized(rest)?)
+                    .iter()
+                    .map(|c| unquote(c).to_string())
+                    .collect(),
+                expression: None,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `sql` starts with the keyword `word`, in any case.
+fn keyword(sql: &str, word: &str) -> bool {
+    sql.get(..word.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(word))
+        && !sql[word.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+fn unquote(name: &str) -> &str {
+    name.trim()
+        .trim_matches(|c| c == '"' || c == '`' || c == '[' || c == ']')
+}
+
+/// The contents of the first parenthesized group of `sql`, skipping
+/// parentheses in string literals.
+fn parenthesized(sql: &str) -> Option<&str> {
+    let start = sql.find('(')?;
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in sql.char_indices().skip_while(|(i, _)| *i < start) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&sql[start + 1..i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Strips the parentheses wrapping all of `expression`, as PostgreSQL and
+/// MySQL report check conditions.
+fn unwrap_parens(expression: &str) -> &str {
+    let mut expression = expression.trim();
+    while expression.starts_with('(')
+        && parenthesized(expression).map(str::len) == Some(expression.len() - 2)
+    {
+        expression = expression[1..expression.len() - 1].trim();
+    }
+    expression
+}
+
+/// Splits a list of definitions at the commas outside parentheses and string
+/// literals.
+pub(crate) fn split_top_level(sql: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote = None;
+    for c in sql.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
+/// The column definitions of a SQLite `CREATE TABLE` statement, as written,
+/// with the name of their column.
+pub(crate) fn sqlite_column_definitions(create_sql: &str) -> Vec<(String, String)> {
+    parenthesized(create_sql)
+        .map(split_top_level)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|part| {
+            !["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+                .iter()
+                .any(|word| keyword(part, word))
+        })
+        .filter_map(|part| {
+            let name = unquote(part.split_whitespace().next()?).to_string();
+            Some((name, part))
+        })
+        .collect()
+}
+
+/// The expression of a generated column definition: what follows its `AS`.
+pub(crate) fn generation_expression(definition: &str) -> Option<String> {
+    let upper = definition.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    let mut from = 0;
+    while let Some(found) = upper[from..].find("AS") {
+        let at = from + found;
+        let rest = definition[at + 2..].trim_start();
+        let bounded = at > 0 && !(bytes[at - 1] as char).is_ascii_alphanumeric();
+        if bounded && rest.starts_with('(') {
+            return parenthesized(rest).map(|e| e.trim().to_string());
+        }
+        from = at + 2;
+    }
+    None
+}
+
+/// The table-level constraint clauses of a SQLite `CREATE TABLE` statement,
+/// as written, with the constraints they declare.
+pub(crate) fn sqlite_table_constraints(create_sql: &str) -> Vec<(String, ConstraintInfo)> {
+    parenthesized(create_sql)
+        .map(split_top_level)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|part| {
+            ["CONSTRAINT", "CHECK", "UNIQUE"]
+                .iter()
+                .any(|word| keyword(part, word))
+        })
+        .filter_map(|part| ConstraintInfo::parse(&part).map(|info| (part, info)))
+        .collect()
+}
+
+/// Lists the table-level unique and check constraints of `table`: in
+/// declaration order on SQLite, and by name on PostgreSQL and MySQL. Unique
+/// constraints declared on a column are listed by `indexes()` only.
+pub(crate) async fn constraints(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &str,
+) -> Result<Vec<ConstraintInfo>, FustOrmError> {
+    let params = vec![table.to_string()];
+    match dialect {
+        Dialect::Sqlite => {
+            let sql = "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?";
+            Ok(match fetch_rows(pool, sql, params).await?.first() {
+                Some(row) => sqlite_table_constraints(&text(row, "sql")?.unwrap_or_default())
+                    .into_iter()
+                    .map(|(_, info)| info)
+                    .collect(),
+                None => Vec::new(),
+            })
+        }
+        Dialect::Postgres => {
+            let sql = "SELECT CAST(c.conname AS TEXT) AS name, \
+                       pg_get_constraintdef(c.oid) AS definition \
+                       FROM pg_constraint c \
+                       JOIN pg_class t ON t.oid = c.conrelid \
+                       WHERE t.relname = $1 AND pg_table_is_visible(t.oid) \
+                       AND c.contype IN ('c', 'u') \
+                       ORDER BY c.conname";
+            let mut constraints = Vec::new();
+            for row in &fetch_rows(pool, sql, params).await? {
+                let definition = text(row, "definition")?.unwrap_or_default();
+                if let Some(info) = ConstraintInfo::parse(&definition) {
+                    constraints.push(ConstraintInfo {
+                        name: text(row, "name")?,
+                        ..info
+                    });
+                }
+            }
+            Ok(constraints)
+        }
+        Dialect::Mysql => {
+            let sql = "SELECT tc.CONSTRAINT_NAME AS name, tc.CONSTRAINT_TYPE AS kind, \
+                       cc.CHECK_CLAUSE AS expression, k.COLUMN_NAME AS column_name \
+                       FROM information_schema.TABLE_CONSTRAINTS tc \
+                       LEFT JOIN information_schema.CHECK_CONSTRAINTS cc \
+                       ON cc.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA \
+                       AND cc.CONSTRAINT_NAME = tc.CONSTRAINT_NAME \
+                       LEFT JOIN information_schema.KEY_COLUMN_USAGE k \
+                       ON k.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA \
+                       AND k.TABLE_NAME = tc.TABLE_NAME \
+                       AND k.CONSTRAINT_NAME = tc.CONSTRAINT_NAME \
+                       WHERE tc.TABLE_SCHEMA = DATABASE() AND tc.TABLE_NAME = ? \
+                       AND tc.CONSTRAINT_TYPE IN ('UNIQUE', 'CHECK') \
+                       ORDER BY tc.CONSTRAINT_NAME, k.ORDINAL_POSITION";
+            let mut constraints: Vec<ConstraintInfo> = Vec::new();
+            for row in &fetch_rows(pool, sql, params).await? {
+                let name = text(row, "name")?;
+                let column = text(row, "column_name")?;
+                if let Some(existing) = constraints.iter_mut().find(|c| c.name == name) {
+                    existing.columns.extend(column);
+                    continue;
+                }
+                let check = text(row, "kind")?.as_deref() == Some("CHECK");
+                constraints.push(ConstraintInfo {
+                    name,
+                    kind: if check { "check" } else { "unique" },
+                    columns: column.into_iter().collect(),
+                    expression: text(row, "expression")?.map(|e| unwrap_parens(&e).to_string()),
+                });
+            }
+            Ok(constraints)
+        }
+    }
+}
+
+/// A column of an existing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ColumnInfo {
+    pub name: String,
+    /// The declared type, as the database reports it (e.g. `VARCHAR(255)`).
+    pub sql_type: String,
+    pub nullable: bool,
+    /// The `DEFAULT` expression.
+    pub default: Option<String>,
+    pub primary_key: bool,
+    pub generated: Option<Generated>,
+}
+
+impl ColumnInfo {
+    /// Renders the column definition, without constraints other than
+    /// `NOT NULL`, `DEFAULT` and the generation expression.
+    pub(crate) fn definition_sql(&self) -> String {
+        let mut sql = format!("{} {}", self.name, self.sql_type);
+        if let Some(generated) = &self.generated {
+            sql.push(' ');
+            sql.push_str(&generated.to_sql());
+        }
+        if !self.nullable {
+            sql.push_str(" NOT NULL");
+        }
+        if let Some(default) = &self.default {
+            sql.push_str(" DEFAULT ");
+            sql.push_str(default);
+        }
+        sql
+    }
+}
+
+/// Lists the tables of the database (of the current schema on PostgreSQL and
+/// MySQL), ordered by name. SQLite's internal tables are left out.
+pub(crate) async fn tables(pool: &AnyPool, dialect: Dialect) -> Result<Vec<String>, FustOrmError> {
+    let sql = match dialect {
+        Dialect::Sqlite => {
+            "SELECT name FROM sqlite_master \
+             WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+        }
+        Dialect::Postgres => {
+            "SELECT CAST(table_name AS TEXT) AS name FROM information_schema.tables \
+             WHERE table_schema = current_schema() AND table_type = 'BASE TABLE' \
+             ORDER BY table_name"
+        }
+        Dialect::Mysql => {
+            "SELECT TABLE_NAME AS name FROM information_schema.tables \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_TYPE = 'BASE TABLE' \
+             ORDER BY TABLE_NAME"
+        }
+    };
+    fetch_rows(pool, sql, Vec::new())
+        .await?
+        .iter()
+        .map(|row| Ok(text(row, "name")?.unwrap_or_default()))
+        .collect()
+}
+
+/// A view of the database and the `SELECT` defining it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ViewInfo {
+    pub name: String,
+    /// The `SELECT` as the database stores it: as written on SQLite,
+    /// normalized by the server on PostgreSQL and MySQL.
+    pub definition: String,
+}
+
+/// The `SELECT` of a SQLite `CREATE VIEW` statement: what follows its first
+/// `AS` keyword.
+fn view_select(sql: &str) -> String {
+    let upper = sql.to_ascii_uppercase();
+    let bytes = upper.as_bytes();
+    let start = upper
+        .match_indices("AS")
+        .map(|(i, _)| i)
+        .find(|&i| {
+            let before = i.checked_sub(1).map(|j| bytes[j]);
+            let after = bytes.get(i + 2).copied();
+            matches!(before, Some(b) if b.is_ascii_whitespace() || b == b')' || b == b'"')
+                && matches!(after, Some(b) if b.is_ascii_whitespace() || b == b'(')
+        })
+        .map_or(0, |i| i + 2);
+    sql[start..].trim().to_string()
+}
+
+/// Lists the views of the database (of the current schema on PostgreSQL and
+/// MySQL), ordered by name.
+pub(crate) async fn views(pool: &AnyPool, dialect: Dialect) -> Result<Vec<ViewInfo>, FustOrmError> {
+    let sql = match dialect {
+        Dialect::Sqlite => {
+            "SELECT name, sql AS definition FROM sqlite_master WHERE type = 'view' ORDER BY name"
+        }
+        Dialect::Postgres => {
+            "SELECT CAST(c.relname AS TEXT) AS name, pg_get_viewdef(c.oid) AS definition \
+             FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+             WHERE c.relkind = 'v' AND n.nspname = current_schema() ORDER BY c.relname"
+        }
+        Dialect::Mysql => {
+            "SELECT TABLE_NAME AS name, VIEW_DEFINITION AS definition \
+             FROM information_schema.views WHERE TABLE_SCHEMA = DATABASE() ORDER BY TABLE_NAME"
+        }
+    };
+    fetch_rows(pool, sql, Vec::new())
+        .await?
+        .iter()
+        .map(|row| {
+            let definition = text(row, "definition")?.unwrap_or_default();
+            Ok(ViewInfo {
+                name: text(row, "name")?.unwrap_or_default(),
+                definition: match dialect {
+                    Dialect::Sqlite => view_select(&definition),
+                    _ => definition.trim().to_string(),
+                },
+            })
+        })
+        .collect()
+}
+
+/// A trigger of an existing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TriggerInfo {
+    pub name: String,
+    /// `BEFORE`, `AFTER` or `INSTEAD OF`.
+    pub timing: String,
+    /// `INSERT`, `UPDATE` or `DELETE`.
+    pub event: String,
+    /// What tells two versions of the trigger apart: its `CREATE TRIGGER`
+    /// statement, or on PostgreSQL its timing, event and function body.
+    pub definition: String,
+    /// The statements recreating the trigger (and its function on PostgreSQL).
+    pub create_sql: Vec<String>,
+    /// The name of the function the trigger executes, on PostgreSQL.
+    pub function: Option<String>,
+}
+
+impl TriggerInfo {
+    pub(crate) fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", &self.name)?;
+        dict.set_item("timing", self.timing.to_lowercase())?;
+        dict.set_item("event", self.event.to_lowercase())?;
+        Ok(dict)
+    }
+}
+
+/// Reads the timing and event of a `CREATE TRIGGER` statement. SQLite
+/// triggers are `BEFORE` unless they say otherwise.
+fn trigger_timing_event(sql: &str) -> (String, String) {
+    let words: Vec<String> = sql
+        .split_whitespace()
+        .map(|w| w.to_ascii_uppercase())
+        .collect();
+    let mut timing = "BEFORE".to_string();
+    for (i, word) in words.iter().enumerate() {
+        match word.as_str() {
+            "BEFORE" | "AFTER" => timing = word.clone(),
+            "INSTEAD" => timing = "INSTEAD OF".to_string(),
+            "INSERT" | "UPDATE" | "DELETE" if i > 2 => return (timing, word.clone()),
+            _ => {}
+        }
+    }
+    (timing, String::new())
+}
+
+/// Lists the triggers of `table`, ordered by name.
+pub(crate) async fn triggers(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &str,
+) -> Result<Vec<TriggerInfo>, FustOrmError> {
+    let sql = match dialect {
+        Dialect::Sqlite => {
+            "SELECT name, sql FROM sqlite_master \
+             WHERE type = 'trigger' AND tbl_name = ? ORDER BY name"
+        }
+        Dialect::Postgres => {
+            "SELECT CAST(tg.tgname AS TEXT) AS name, pg_get_triggerdef(tg.oid) AS sql, \
+             CAST(p.proname AS TEXT) AS function, p.prosrc AS body, \
+             pg_get_functiondef(p.oid) AS function_sql \
+             FROM pg_trigger tg \
+             JOIN pg_class t ON t.oid = tg.tgrelid \
+             JOIN pg_proc p ON p.oid = tg.tgfoid \
+             WHERE t.relname = $1 AND pg_table_is_visible(t.oid) AND NOT tg.tgisinternal \
+             ORDER BY tg.tgname"
+        }
+        Dialect::Mysql => {
+            "SELECT TRIGGER_NAME AS name, ACTION_TIMING AS timing, \
+             EVENT_MANIPULATION AS event, ACTION_STATEMENT AS body \
+             FROM information_schema.triggers \
+             WHERE EVENT_OBJECT_SCHEMA = DATABASE() AND EVENT_OBJECT_TABLE = ? \
+             ORDER BY TRIGGER_NAME"
+        }
+    };
+    fetch_rows(pool, sql, vec![table.to_string()])
+        .await?
+        .iter()
+        .map(|row| {
+            let name = text(row, "name")?.unwrap_or_default();
+            Ok(match dialect {
+                Dialect::Sqlite => {
+                    let sql = text(row, "sql")?.unwrap_or_default();
+                    let (timing, event) = trigger_timing_event(&sql);
+                    TriggerInfo {
+                        name,
+                        timing,
+                        event,
+                        definition: sql.clone(),
+                        create_sql: vec![sql],
+                        function: None,
+                    }
+                }
+                Dialect::Postgres => {
+                    let sql = text(row, "sql")?.unwrap_or_default();
+                    let (timing, event) = trigger_timing_event(&sql);
+                    let body = text(row, "body")?.unwrap_or_default();
+                    TriggerInfo {
+                        name,
+                        definition: format!("{} {} {}", timing, event, body),
+                        timing,
+                        event,
+                        create_sql: vec![text(row, "function_sql")?.unwrap_or_default(), sql],
+                        function: text(row, "function")?,
+                    }
+                }
+                Dialect::Mysql => {
+                    let timing = text(row, "timing")?.unwrap_or_default();
+                    let event = text(row, "event")?.unwrap_or_default();
+                    let sql = format!(
+                        "CREATE TRIGGER {} {} {} ON {} FOR EACH ROW {}",
+                        name,
+                        timing,
+                        event,
+                        table,
+                        text(row, "body")?.unwrap_or_default()
+                    );
+                    TriggerInfo {
+                        name,
+                        timing,
+                        event,
+                        definition: sql.clone(),
+                        create_sql: vec![sql],
+                        function: None,
+                    }
+                }
+            })
+        })
+        .collect()
+}
+
+/// Lists the columns of `table` in declaration order. Empty if the table
+/// doesn't exist.
+pub(crate) async fn columns(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &str,
+) -> Result<Vec<ColumnInfo>, FustOrmError> {
+    let sql = match dialect {
+        Dialect::Sqlite => {
+            "SELECT name, type AS sql_type, 1 - \"notnull\" AS is_nullable, \
+             dflt_value AS default_value, CASE WHEN pk > 0 THEN 1 ELSE 0 END AS is_pk, \
+             NULL AS generation, CASE WHEN hidden = 3 THEN 1 ELSE 0 END AS is_stored, \
+             CASE WHEN hidden IN (2, 3) THEN 1 ELSE 0 END AS is_generated \
+             FROM pragma_table_xinfo(?) WHERE hidden <> 1 ORDER BY cid"
+        }
+        Dialect::Postgres => {
+            "SELECT CAST(a.attname AS TEXT) AS name, \
+             format_type(a.atttypid, a.atttypmod) AS sql_type, \
+             CAST(NOT a.attnotnull AS INTEGER) AS is_nullable, \
+             CASE WHEN a.attgenerated = '' THEN pg_get_expr(d.adbin, d.adrelid) END \
+             AS default_value, \
+             CAST(EXISTS (SELECT 1 FROM pg_index ix WHERE ix.indrelid = t.oid \
+             AND ix.indisprimary AND a.attnum = ANY(ix.indkey)) AS INTEGER) AS is_pk, \
+             CASE WHEN a.attgenerated <> '' THEN pg_get_expr(d.adbin, d.adrelid) END \
+             AS generation, \
+             CAST(a.attgenerated = 's' AS INTEGER) AS is_stored, \
+             CAST(a.attgenerated <> '' AS INTEGER) AS is_generated \
+             FROM pg_attribute a \
+             JOIN pg_class t ON t.oid = a.attrelid \
+             LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+             WHERE t.relname = $1 AND pg_table_is_visible(t.oid) \
+             AND a.attnum > 0 AND NOT a.attisdropped \
+             ORDER BY a.attnum"
+        }
+        Dialect::Mysql => {
+            "SELECT COLUMN_NAME AS name, COLUMN_TYPE AS sql_type, \
+             CAST(IS_NULLABLE = 'YES' AS SIGNED) AS is_nullable, \
+             COLUMN_DEFAULT AS default_value, CAST(COLUMN_KEY = 'PRI' AS SIGNED) AS is_pk, \
+             GENERATION_EXPRESSION AS generation, \
+             CAST(EXTRA LIKE '%STORED GENERATED%' AS SIGNED) AS is_stored, \
+             CAST(EXTRA LIKE '%GENERATED%' AS SIGNED) AS is_generated \
+             FROM information_schema.columns \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+             ORDER BY ORDINAL_POSITION"
+        }
+    };
+    let rows = fetch_rows(pool, sql, vec![table.to_string()]).await?;
+    // SQLite only keeps the expressions of generated columns in the table's SQL.
+    let mut definitions = Vec::new();
+    if dialect == Dialect::Sqlite
+        && rows
+            .iter()
+            .any(|row| flag(row, "is_generated").unwrap_or(false))
+    {
+        let sql = "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?";
+        if let Some(row) = fetch_rows(pool, sql, vec![table.to_string()])
+            .await?
+            .first()
+        {
+            definitions = sqlite_column_definitions(&text(row, "sql")?.unwrap_or_default());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let name = text(row, "name")?.unwrap_or_default();
+            let expression = match text(row, "generation")? {
+                Some(expression) => Some(expression),
+                None => definitions
+                    .iter()
+                    .find(|(column, _)| *column == name)
+                    .and_then(|(_, definition)| generation_expression(definition)),
+            };
+            let generated = match expression {
+                Some(expression) if flag(row, "is_generated")? => Some(Generated {
+                    expression,
+                    stored: flag(row, "is_stored")?,
+                }),
+                _ => None,
+            };
+            Ok(ColumnInfo {
+                name,
+                sql_type: text(row, "sql_type")?.unwrap_or_default(),
+                nullable: flag(row, "is_nullable")?,
+                default: text(row, "default_value")?,
+                primary_key: flag(row, "is_pk")?,
+                generated,
+            })
+        })
+        .collect()
+}
+
+/// A single-column foreign key of an existing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ForeignKeyInfo {
+    pub column: String,
+    /// The referenced table.
+    pub table: String,
+    /// The referenced column, empty if SQLite leaves it to the referenced
+    /// table's primary key.
+    pub target: String,
+    /// The `ON DELETE` action, e.g. `CASCADE` or `NO ACTION`.
+    pub on_delete: String,
+}
+
+/// Lists the single-column foreign keys of `table`, ordered by column.
+/// Composite foreign keys are left out.
+pub(crate) async fn foreign_keys(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &str,
+) -> Result<Vec<ForeignKeyInfo>, FustOrmError> {
+    let sql = match dialect {
+        Dialect::Sqlite => {
+            "SELECT \"from\" AS column_name, \"table\" AS target_table, \
+             \"to\" AS target_column, on_delete FROM pragma_foreign_key_list(?) f \
+             WHERE (SELECT COUNT(*) FROM pragma_foreign_key_list(?) g WHERE g.id = f.id) = 1 \
+             ORDER BY column_name"
+        }
+        Dialect::Postgres => {
+            "SELECT CAST(a.attname AS TEXT) AS column_name, \
+             CAST(r.relname AS TEXT) AS target_table, \
+             CAST(ra.attname AS TEXT) AS target_column, \
+             CASE c.confdeltype WHEN 'c' THEN 'CASCADE' WHEN 'n' THEN 'SET NULL' \
+             WHEN 'r' THEN 'RESTRICT' WHEN 'd' THEN 'SET DEFAULT' ELSE 'NO ACTION' END \
+             AS on_delete \
+             FROM pg_constraint c \
+             JOIN pg_class t ON t.oid = c.conrelid \
+             JOIN pg_class r ON r.oid = c.confrelid \
+             JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = c.conkey[1] \
+             JOIN pg_attribute ra ON ra.attrelid = r.oid AND ra.attnum = c.confkey[1] \
+             WHERE c.contype = 'f' AND t.relname = $1 AND pg_table_is_visible(t.oid) \
+             AND array_length(c.conkey, 1) = 1 \
+             ORDER BY column_name"
+        }
+        Dialect::Mysql => {
+            "SELECT k.COLUMN_NAME AS column_name, k.REFERENCED_TABLE_NAME AS target_table, \
+             k.REFERENCED_COLUMN_NAME AS target_column, rc.DELETE_RULE AS on_delete \
+             FROM information_schema.key_column_usage k \
+             JOIN information_schema.referential_constraints rc \
+             ON rc.CONSTRAINT_SCHEMA = k.TABLE_SCHEMA AND rc.CONSTRAINT_NAME = k.CONSTRAINT_NAME \
+             WHERE k.TABLE_SCHEMA = DATABASE() AND k.TABLE_NAME = ? \
+             AND k.REFERENCED_TABLE_NAME IS NOT NULL \
+             AND (SELECT COUNT(*) FROM information_schema.key_column_usage g \
+             WHERE g.TABLE_SCHEMA = k.TABLE_SCHEMA AND g.TABLE_NAME = ? \
+             AND g.CONSTRAINT_NAME = k.CONSTRAINT_NAME) = 1 \
+             ORDER BY column_name"
+        }
+    };
+    let params = match dialect {
+        Dialect::Postgres => vec![table.to_string()],
+        _ => vec![table.to_string(), table.to_string()],
+    };
+    fetch_rows(pool, sql, params)
+        .await?
+        .iter()
+        .map(|row| {
+            Ok(ForeignKeyInfo {
+                column: text(row, "column_name")?.unwrap_or_default(),
+                table: text(row, "target_table")?.unwrap_or_default(),
+                target: text(row, "target_column")?.unwrap_or_default(),
+                on_delete: text(row, "on_delete")?.unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Lists the tables `table` has foreign keys to, other than itself, ordered
+/// by name.
+pub(crate) async fn referenced_tables(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: &str,
+) -> Result<Vec<String>, FustOrmError> {
+    let sql = match dialect {
+        Dialect::Sqlite => {
+            "SELECT DISTINCT \"table\" AS name FROM pragma_foreign_key_list(?) \
+             WHERE \"table\" <> ? ORDER BY name"
+        }
+        Dialect::Postgres => {
+            "SELECT DISTINCT CAST(r.relname AS TEXT) AS name FROM pg_constraint c \
+             JOIN pg_class t ON t.oid = c.conrelid \
+             JOIN pg_class r ON r.oid = c.confrelid \
+             WHERE c.contype = 'f' AND t.relname = $1 AND pg_table_is_visible(t.oid) \
+             AND r.relname <> $2 ORDER BY name"
+        }
+        Dialect::Mysql => {
+            "SELECT DISTINCT REFERENCED_TABLE_NAME AS name \
+             FROM information_schema.key_column_usage \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? \
+             AND REFERENCED_TABLE_NAME IS NOT NULL AND REFERENCED_TABLE_NAME <> ? \
+             ORDER BY name"
+        }
+    };
+    fetch_rows(pool, sql, vec![table.to_string(), table.to_string()])
+        .await?
+        .iter()
+        .map(|row| Ok(text(row, "name")?.unwrap_or_default()))
+        .collect()
+}
+
+/// Orders `tables` so that the tables they have foreign keys to come first,
+/// keeping the given order otherwise (and for reference cycles).
+pub(crate) async fn dependency_order(
+    pool: &AnyPool,
+    dialect: Dialect,
+    tables: Vec<String>,
+) -> Result<Vec<String>, FustOrmError> {
+    let mut pending = Vec::with_capacity(tables.len());
+    for table in tables {
+        let references = referenced_tables(pool, dialect, &table).await?;
+        pending.push((table, references));
+    }
+    let mut ordered = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|(_, references)| {
+            references
+                .iter()
+                .all(|r| !pending.iter().any(|(t, _)| t == r))
+        });
+        ordered.push(pending.remove(ready.unwrap_or(0)).0);
+    }
+    Ok(ordered)
+}
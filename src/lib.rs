@@ -1,31 +1,211 @@
+mod advisory_lock;
+mod alter;
+mod array;
+mod audit;
+mod autogen;
+mod bulk;
+mod cascade;
+mod changeset;
+mod circuit_breaker;
+mod cli;
+mod codegen;
 mod column_field;
+mod context;
+mod copy;
+mod custom_type;
 mod database;
+mod dbapi;
+mod decode;
+mod dry_run;
+mod dump;
 mod error;
+mod expression;
+mod failover;
+mod fixtures;
+mod fts;
+mod geo;
+mod interval;
+mod introspect;
+mod logging;
+mod maintenance;
+mod metrics;
+mod migrations;
+mod mock;
 mod model;
+mod named_params;
+mod notify;
+mod pagination;
+mod pool_registry;
 mod query;
+mod query_cache;
+mod query_recorder;
+mod range;
+mod relations;
+mod result_limits;
+mod retry;
+mod rewrite;
+mod runtime;
+mod schema;
+mod scope;
+mod seed;
+mod serialize;
+mod server_cursor;
+mod sharding;
+mod shutdown;
+mod signals;
+mod spool;
+mod sqlcomment;
+mod sqlite_functions;
+mod sqlite_hooks;
+mod sync;
+mod telemetry;
+mod tenancy;
+mod testing;
+mod timing;
+mod trigger;
+mod upsert;
+mod view;
 mod where_condition;
 
 use pyo3::prelude::*;
 
+use alter::AlterTable;
 use column_field::ColumnField;
 use database::Database;
+use dbapi::{DbApiConnection, DbApiCursor};
+use expression::F;
+use fts::FtsTable;
 use model::Model;
 use query::{QueryBuilder, select};
+use relations::{ManyToMany, RelatedManager};
+use schema::{ColumnType, Table};
+use scope::{BoundScope, Scope};
+use signals::Signal;
+use sync::{SyncDatabase, SyncTransaction};
+use trigger::Trigger;
+use view::View;
 
+use crate::error::{
+    CheckViolationError, DataError, DatabaseError, Error, ForeignKeyViolationError, IntegrityError,
+    InterfaceError, NotNullViolationError, OperationalError, ProgrammingError, StaleDataError,
+    UniqueViolationError,
+};
 use crate::where_condition::WhereCondition;
 
-#[pymodule]
-fn fust_orm(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
-    pyo3_log::init();
+// Shared state is behind locks or Python objects, so the module runs without
+// the GIL on free-threaded builds.
+#[pymodule(gil_used = false)]
+fn fust_orm(py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    logging::init(py)?;
     sqlx::any::install_default_drivers();
+    shutdown::init(m)?;
 
     m.add_class::<WhereCondition>()?;
     m.add_class::<ColumnField>()?;
+    m.add_class::<F>()?;
     m.add_class::<Database>()?;
+    m.add_class::<mock::MockDatabase>()?;
+    m.add_class::<circuit_breaker::CircuitBreaker>()?;
+    m.add_class::<retry::RetryPolicy>()?;
+    m.add_class::<sharding::ShardedDatabase>()?;
+    m.add_class::<context::Transaction>()?;
+    m.add_class::<context::Binding>()?;
+    m.add_class::<notify::Listener>()?;
+    m.add_class::<notify::Notification>()?;
+    m.add_class::<advisory_lock::AdvisoryLock>()?;
+    m.add_class::<server_cursor::ServerCursor>()?;
+    m.add_class::<spool::SpooledRows>()?;
+    m.add_class::<pagination::Page>()?;
     m.add_class::<QueryBuilder>()?;
     m.add_class::<Model>()?;
+    m.add_class::<Scope>()?;
+    m.add_class::<BoundScope>()?;
+    m.add_class::<Signal>()?;
+    m.add_class::<ManyToMany>()?;
+    m.add_class::<RelatedManager>()?;
+    m.add_class::<Table>()?;
+    m.add_class::<ColumnType>()?;
+    m.add_class::<range::Range>()?;
+    m.add_class::<AlterTable>()?;
+    m.add_class::<View>()?;
+    m.add_class::<Trigger>()?;
+    m.add_class::<FtsTable>()?;
+    m.add_class::<changeset::Changeset>()?;
+    m.add_class::<timing::CapturedQueries>()?;
+    m.add_class::<query_recorder::QueryRecorder>()?;
+    // Re-exported as `fust_orm.sync.Database` and `fust_orm.sync.Transaction`.
+    m.add("SyncDatabase", py.get_type::<SyncDatabase>())?;
+    m.add("SyncTransaction", py.get_type::<SyncTransaction>())?;
+    // Re-exported as `fust_orm.dbapi.Connection` and `fust_orm.dbapi.Cursor`.
+    m.add("DbApiConnection", py.get_type::<DbApiConnection>())?;
+    m.add("DbApiCursor", py.get_type::<DbApiCursor>())?;
 
     m.add_function(wrap_pyfunction!(select, m)?)?;
+    m.add_function(wrap_pyfunction!(context::bind, m)?)?;
+    // Re-exported by `fust_orm.testing`.
+    m.add_function(wrap_pyfunction!(context::test_database, m)?)?;
+    m.add_function(wrap_pyfunction!(timing::capture_queries, m)?)?;
+    m.add_function(wrap_pyfunction!(runtime::configure_runtime, m)?)?;
+    m.add_function(wrap_pyfunction!(query_cache::configure_query_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(scope::scope, m)?)?;
+    m.add_function(wrap_pyfunction!(fts::bm25, m)?)?;
+    m.add_function(wrap_pyfunction!(fts::highlight, m)?)?;
+    m.add_function(wrap_pyfunction!(fts::snippet, m)?)?;
+    m.add_function(wrap_pyfunction!(geo::to_geometry, m)?)?;
+    m.add_function(wrap_pyfunction!(geo::as_wkb, m)?)?;
+    m.add_function(wrap_pyfunction!(geo::as_wkt, m)?)?;
+    m.add_function(wrap_pyfunction!(geo::load_geometry, m)?)?;
+    m.add_function(wrap_pyfunction!(alter::alter_table, m)?)?;
+    m.add_function(wrap_pyfunction!(cli::main, m)?)?;
+    m.add_function(wrap_pyfunction!(codegen::introspect_models, m)?)?;
+    m.add_function(wrap_pyfunction!(timing::on_slow_query, m)?)?;
+    m.add_function(wrap_pyfunction!(telemetry::instrument, m)?)?;
+    m.add_function(wrap_pyfunction!(telemetry::uninstrument, m)?)?;
+    m.add_function(wrap_pyfunction!(metrics::metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(sqlcomment::tag_statements, m)?)?;
+    m.add_function(wrap_pyfunction!(audit::audit_statements, m)?)?;
+    m.add_function(wrap_pyfunction!(rewrite::rewrite_queries, m)?)?;
+    m.add_function(wrap_pyfunction!(rewrite::rewrite_statements, m)?)?;
+    m.add_function(wrap_pyfunction!(schema::varchar, m)?)?;
+    m.add_function(wrap_pyfunction!(schema::numeric, m)?)?;
+    m.add_function(wrap_pyfunction!(schema::geometry, m)?)?;
+    m.add_function(wrap_pyfunction!(schema::array, m)?)?;
+    m.add_function(wrap_pyfunction!(custom_type::register_type, m)?)?;
+    for (name, column_type) in schema::column_types() {
+        m.add(name, column_type)?;
+    }
+
+    m.add("StaleDataError", py.get_type::<StaleDataError>())?;
+    // Errors that didn't happen on a statement have no statement to report.
+    let error = py.get_type::<Error>();
+    for attribute in ["sql", "params", "code", "constraint"] {
+        error.setattr(attribute, py.None())?;
+    }
+    m.add("Error", error)?;
+    m.add("InterfaceError", py.get_type::<InterfaceError>())?;
+    m.add("DatabaseError", py.get_type::<DatabaseError>())?;
+    m.add("OperationalError", py.get_type::<OperationalError>())?;
+    m.add("IntegrityError", py.get_type::<IntegrityError>())?;
+    m.add(
+        "UniqueViolationError",
+        py.get_type::<UniqueViolationError>(),
+    )?;
+    m.add(
+        "ForeignKeyViolationError",
+        py.get_type::<ForeignKeyViolationError>(),
+    )?;
+    m.add(
+        "NotNullViolationError",
+        py.get_type::<NotNullViolationError>(),
+    )?;
+    m.add("CheckViolationError", py.get_type::<CheckViolationError>())?;
+    m.add("ProgrammingError", py.get_type::<ProgrammingError>())?;
+    m.add("DataError", py.get_type::<DataError>())?;
+
+    m.add("pre_save", signals::pre_save(py)?)?;
+    m.add("post_save", signals::post_save(py)?)?;
+    m.add("pre_delete", signals::pre_delete(py)?)?;
+    m.add("post_delete", signals::post_delete(py)?)?;
 
     Ok(())
 }
@@ -0,0 +1,100 @@
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use pyo3::prelude::*;
+use pyo3_log::{Caching, ResetHandle};
+
+/// The Python logger the statements of connections opened with `echo=True`
+/// are logged to.
+pub(crate) const SQL_LOGGER: &str = "fust_orm.sql";
+
+/// The target sqlx logs executed statements to.
+const SQLX_TARGET: &str = "sqlx::query";
+pub(crate) const SQL_TARGET: &str = "fust_orm::sql";
+
+static RESET: OnceLock<ResetHandle> = OnceLock::new();
+
+/// Set once the interpreter is exiting, when records are no longer
+/// forwarded.
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// Forwards log records to Python's `logging`, under the `fust_orm` logger
+/// for the crate's modules and `fust_orm.sql` for the statements sqlx logs.
+struct Logger(pyo3_log::Logger);
+
+fn target(target: &str) -> &str {
+    match target {
+        SQLX_TARGET => SQL_TARGET,
+        target => target,
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if STOPPED.load(Ordering::Relaxed) {
+            return false;
+        }
+        let metadata = Metadata::builder()
+            .level(metadata.level())
+            .target(target(metadata.target()))
+            .build();
+        self.0.enabled(&metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if STOPPED.load(Ordering::Relaxed) {
+            return;
+        }
+        self.0.log(
+            &Record::builder()
+                .args(*record.args())
+                .level(record.level())
+                .target(target(record.target()))
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Installs the logger forwarding to Python's `logging`.
+pub(crate) fn init(py: Python) -> PyResult<()> {
+    let logger = pyo3_log::Logger::new(py, Caching::LoggersAndLevels)?;
+    let _ = RESET.set(logger.reset_handle());
+    // Another copy of the module may have installed its logger already.
+    if log::set_boxed_logger(Box::new(Logger(logger))).is_ok() {
+        log::set_max_level(LevelFilter::Debug);
+    }
+    Ok(())
+}
+
+/// Stops forwarding log records, as the interpreter exits.
+pub(crate) fn stop() {
+    STOPPED.store(true, Ordering::Relaxed);
+}
+
+/// Makes the statements logged to `fust_orm.sql` visible: the logger is set
+/// to `INFO` and, unless it has handlers, prints to stderr.
+pub(crate) fn enable_echo(py: Python) -> PyResult<()> {
+    let logging = py.import("logging")?;
+    let logger = logging.call_method1("getLogger", (SQL_LOGGER,))?;
+    let info = logging.getattr("INFO")?;
+    let level: i64 = logger.getattr("level")?.extract()?;
+    if level == 0 || level > info.extract::<i64>()? {
+        logger.call_method1("setLevel", (info,))?;
+    }
+    if !logger.call_method0("hasHandlers")?.is_truthy()? {
+        logger.call_method1("addHandler", (logging.call_method0("StreamHandler")?,))?;
+    }
+    // The levels of the loggers are cached once something was logged.
+    if let Some(reset) = RESET.get() {
+        reset.reset();
+    }
+    Ok(())
+}
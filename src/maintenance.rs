@@ -0,0 +1,435 @@
+use std::ffi::{CStr, CString, c_int};
+use std::path::PathBuf;
+use std::ptr;
+use std::time::Duration;
+
+use crate::context;
+use crate::database::{Database, acquire, fetch_rows, fetch_rows_uncached, row_to_dict};
+use crate::error::{FustOrmError, OperationalError};
+use crate::introspect;
+use crate::query::QueryBuilder;
+use crate::schema::{Dialect, table_name};
+use crate::sqlite_hooks::{self, require_sqlite};
+use libsqlite3_sys as ffi;
+use log::debug;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::any::AnyRow;
+use sqlx::{AnyConnection, AnyPool, Executor, Row};
+
+fn map_db_err(e: sqlx::Error) -> FustOrmError {
+    FustOrmError::from(e)
+}
+
+/// The size of the database in bytes, as the database reports it.
+async fn database_size(pool: &AnyPool, dialect: Dialect) -> Result<i64, FustOrmError> {
+    let sql = match dialect {
+        Dialect::Sqlite => {
+            "SELECT page_count * page_size AS size FROM pragma_page_count(), pragma_page_size()"
+        }
+        Dialect::Postgres => "SELECT pg_database_size(current_database()) AS size",
+        Dialect::Mysql => {
+            "SELECT CAST(COALESCE(SUM(DATA_LENGTH + INDEX_LENGTH), 0) AS SIGNED) AS size \
+             FROM information_schema.tables WHERE TABLE_SCHEMA = DATABASE()"
+        }
+    };
+    let rows = fetch_rows(pool, sql, Vec::new()).await?;
+    rows.first()
+        .map(|row| row.try_get("size"))
+        .transpose()
+        .map_err(map_db_err)
+        .map(Option::unwrap_or_default)
+}
+
+/// Runs a statement that can't run inside a transaction, unprepared.
+async fn run_outside_transaction(pool: &AnyPool, sql: &str) -> Result<(), FustOrmError> {
+    debug!("Executing SQL: \"{}\"", sql);
+    pool.execute(sql).await.map_err(map_db_err)?;
+    Ok(())
+}
+
+/// The tables to run a per-table command on: `table`, or all of them.
+async fn target_tables(
+    pool: &AnyPool,
+    dialect: Dialect,
+    table: Option<String>,
+) -> Result<Vec<String>, FustOrmError> {
+    match table {
+        Some(table) => Ok(vec![table]),
+        None => introspect::tables(pool, dialect).await,
+    }
+}
+
+/// Rebuilds the database to reclaim free space (`VACUUM`, or `OPTIMIZE TABLE`
+/// on every table on MySQL). Resolves to `{"size_before": ..., "size_after":
+/// ...}` in bytes.
+pub(crate) fn vacuum<'py>(py: Python<'py>, db: &Database) -> PyResult<Bound<'py, PyAny>> {
+    let dialect = db.dialect();
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let size_before = database_size(&pool, dialect).await?;
+        match dialect {
+            Dialect::Sqlite | Dialect::Postgres => run_outside_transaction(&pool, "VACUUM").await?,
+            Dialect::Mysql => {
+                let tables = introspect::tables(&pool, dialect).await?;
+                if !tables.is_empty() {
+                    let sql = format!("OPTIMIZE TABLE {}", tables.join(", "));
+                    fetch_rows(&pool, &sql, Vec::new()).await?;
+                }
+            }
+        }
+        let size_after = database_size(&pool, dialect).await?;
+        Python::attach(|py| -> PyResult<Py<PyDict>> {
+            let result = PyDict::new(py);
+            result.set_item("size_before", size_before)?;
+            result.set_item("size_after", size_after)?;
+            Ok(result.unbind())
+        })
+    })
+}
+
+/// Writes a compacted copy of the SQLite database to the new file at `path`
+/// with `VACUUM INTO`, from a single read transaction. Resolves to the size
+/// of the copy in bytes.
+pub(crate) fn snapshot<'py>(
+    py: Python<'py>,
+    db: &Database,
+    path: PathBuf,
+) -> PyResult<Bound<'py, PyAny>> {
+    require_sqlite(db, "snapshot")?;
+    let Some(target) = path.to_str().map(str::to_string) else {
+        return Err(PyValueError::new_err(
+            "The snapshot path must be valid UTF-8.",
+        ));
+    };
+    // SQLite can't vacuum in a transaction, and a snapshot of the pool's
+    // connections would miss the writes of the one bound.
+    let db = context::resolve(py, Some(db))?;
+    if db.tx.is_some() {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "snapshot() can't run in a transaction.".to_string(),
+        )
+        .into());
+    }
+    future_into_py(py, async move {
+        db.execute_statement("VACUUM INTO ?", vec![target]).await?;
+        let size = std::fs::metadata(&path)
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+        debug!("Wrote a snapshot of {} bytes to {}", size, path.display());
+        Ok(size)
+    })
+}
+
+/// Updates the statistics the query planner uses, for `table` or every
+/// table. Resolves to the analyzed tables.
+pub(crate) fn analyze<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: Option<&Bound<PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table.map(table_name).transpose()?;
+    let dialect = db.dialect();
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let tables = target_tables(&pool, dialect, table).await?;
+        if !tables.is_empty() {
+            match dialect {
+                Dialect::Sqlite => {
+                    for table in &tables {
+                        run_outside_transaction(&pool, &format!("ANALYZE {}", table)).await?;
+                    }
+                }
+                Dialect::Postgres => {
+                    let sql = format!("ANALYZE {}", tables.join(", "));
+                    run_outside_transaction(&pool, &sql).await?;
+                }
+                Dialect::Mysql => {
+                    let sql = format!("ANALYZE TABLE {}", tables.join(", "));
+                    fetch_rows(&pool, &sql, Vec::new()).await?;
+                }
+            }
+        }
+        Ok(tables)
+    })
+}
+
+/// A row violating a foreign key.
+struct Violation {
+    table: String,
+    rowid: Option<i64>,
+    parent: String,
+}
+
+/// Shows how the database would run `query` (a `QueryBuilder` or a SQL
+/// string): `EXPLAIN QUERY PLAN` on SQLite, `EXPLAIN` elsewhere, or `EXPLAIN
+/// ANALYZE` with `analyze`, which runs the query. Resolves to the plan rows.
+pub(crate) fn explain<'py>(
+    py: Python<'py>,
+    db: &Database,
+    query: &Bound<'py, PyAny>,
+    analyze: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let dialect = db.dialect();
+    if analyze && dialect == Dialect::Sqlite {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "explain(analyze=True) is not supported on SQLite.".to_string(),
+        )
+        .into());
+    }
+    let (sql, params) = if let Ok(builder) = query.downcast::<QueryBuilder>() {
+        builder.borrow().build(py, db)?
+    } else if let Ok(sql) = query.extract::<String>() {
+        (sql, Vec::new())
+    } else {
+        return Err(PyTypeError::new_err(
+            "'query' must be a string or a result of calling select()",
+        ));
+    };
+    let db = Database::new(db.pool.clone());
+    future_into_py(py, async move {
+        let rows = query_plan(&db, &sql, params, analyze).await?;
+        Python::attach(|py| -> PyResult<Py<PyList>> {
+            let plan = PyList::empty(py);
+            for row in &rows {
+                plan.append(row_to_dict(py, row)?)?;
+            }
+            Ok(plan.unbind())
+        })
+    })
+}
+
+/// Runs `EXPLAIN` on `sql` in the transaction of `db`, or on a connection of
+/// its pool, and returns the rows of the plan.
+pub(crate) async fn query_plan(
+    db: &Database,
+    sql: &str,
+    params: Vec<String>,
+    analyze: bool,
+) -> Result<Vec<AnyRow>, FustOrmError> {
+    let dialect = db.dialect();
+    let sql = match (dialect, analyze) {
+        (Dialect::Sqlite, _) => format!("EXPLAIN QUERY PLAN {}", sql),
+        (_, true) => format!("EXPLAIN ANALYZE {}", sql),
+        (_, false) => format!("EXPLAIN {}", sql),
+    };
+    let mut guard = db.lock_tx().await?;
+    let mut pooled;
+    let conn: &mut AnyConnection = match &mut guard {
+        Some(guard) => guard.as_mut().expect("the transaction is open"),
+        None => {
+            pooled = acquire(&db.pool).await.map_err(map_db_err)?;
+            &mut pooled
+        }
+    };
+    // SQLite connections only notice schema changes made by others when a
+    // statement reads the schema, which `EXPLAIN` doesn't: the plan would
+    // ignore indexes created since.
+    if dialect == Dialect::Sqlite {
+        fetch_rows(&mut *conn, "SELECT COUNT(*) FROM sqlite_master", Vec::new()).await?;
+    }
+    fetch_rows_uncached(&mut *conn, &sql, params).await
+}
+
+/// Checks the database for corruption and for rows violating foreign keys.
+/// Resolves to `{"ok": ..., "errors": [...], "foreign_key_violations": [...]}`.
+///
+/// SQLite runs `PRAGMA integrity_check` and `PRAGMA foreign_key_check`, MySQL
+/// `CHECK TABLE` on every table. PostgreSQL has no built-in equivalent.
+pub(crate) fn integrity_check<'py>(py: Python<'py>, db: &Database) -> PyResult<Bound<'py, PyAny>> {
+    let dialect = db.dialect();
+    if dialect == Dialect::Postgres {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "integrity_check() is not supported on PostgreSQL.".to_string(),
+        )
+        .into());
+    }
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let mut errors: Vec<String> = Vec::new();
+        let mut violations = Vec::new();
+        match dialect {
+            Dialect::Sqlite => {
+                for row in &fetch_rows(&pool, "PRAGMA integrity_check", Vec::new()).await? {
+                    let message: String = row.try_get(0).map_err(map_db_err)?;
+                    if message != "ok" {
+                        errors.push(message);
+                    }
+                }
+                let rows = fetch_rows(&pool, "PRAGMA foreign_key_check", Vec::new()).await?;
+                for row in &rows {
+                    violations.push(Violation {
+                        table: row.try_get("table").map_err(map_db_err)?,
+                        rowid: row.try_get("rowid").map_err(map_db_err)?,
+                        parent: row.try_get("parent").map_err(map_db_err)?,
+                    });
+                }
+            }
+            Dialect::Mysql => {
+                let tables = introspect::tables(&pool, dialect).await?;
+                if !tables.is_empty() {
+                    let sql = format!("CHECK TABLE {}", tables.join(", "));
+                    for row in &fetch_rows(&pool, &sql, Vec::new()).await? {
+                        let table: String = row.try_get("Table").map_err(map_db_err)?;
+                        let kind: String = row.try_get("Msg_type").map_err(map_db_err)?;
+                        let text: String = row.try_get("Msg_text").map_err(map_db_err)?;
+                        if kind == "error" || (kind == "status" && text != "OK") {
+                            errors.push(format!("{}: {}", table, text));
+                        }
+                    }
+                }
+            }
+            Dialect::Postgres => unreachable!("rejected above"),
+        }
+        Python::attach(|py| -> PyResult<Py<PyDict>> {
+            let result = PyDict::new(py);
+            result.set_item("ok", errors.is_empty() && violations.is_empty())?;
+            result.set_item("errors", &errors)?;
+            let list = PyList::empty(py);
+            for violation in &violations {
+                let item = PyDict::new(py);
+                item.set_item("table", &violation.table)?;
+                item.set_item("rowid", violation.rowid)?;
+                item.set_item("parent", &violation.parent)?;
+                list.append(item)?;
+            }
+            result.set_item("foreign_key_violations", list)?;
+            Ok(result.unbind())
+        })
+    })
+}
+
+/// A backup in progress into a destination connection, both released when
+/// dropped.
+struct Backup {
+    dest: *mut ffi::sqlite3,
+    backup: *mut ffi::sqlite3_backup,
+}
+
+// SAFETY: the destination connection is only used by the task running the
+// backup, and SQLite locks the source connection's mutex in each step.
+unsafe impl Send for Backup {}
+
+impl Backup {
+    /// The error message of the destination connection.
+    fn error(&self) -> String {
+        // SAFETY: the connection is open, and its message copied.
+        unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.dest)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Drop for Backup {
+    fn drop(&mut self) {
+        // SAFETY: both were opened by `backup` and are released once.
+        unsafe {
+            if !self.backup.is_null() {
+                ffi::sqlite3_backup_finish(self.backup);
+            }
+            ffi::sqlite3_close(self.dest);
+        }
+    }
+}
+
+/// Copies the SQLite database into the file at `dest`, `pages` pages at a
+/// time (all at once if negative), pausing `sleep` seconds between steps so
+/// that writers can go on. Writes made meanwhile by other connections restart
+/// the copy. `progress_callback(remaining, total)` is called after each step.
+pub(crate) fn backup<'py>(
+    py: Python<'py>,
+    db: &Database,
+    dest: PathBuf,
+    progress_callback: Option<Py<PyAny>>,
+    pages: i32,
+    sleep: f64,
+) -> PyResult<Bound<'py, PyAny>> {
+    require_sqlite(db, "backup")?;
+    if let Some(callback) = &progress_callback
+        && !callback.bind(py).is_callable()
+    {
+        return Err(PyTypeError::new_err("progress_callback must be callable."));
+    }
+    if pages == 0 {
+        return Err(PyValueError::new_err("pages must not be 0."));
+    }
+    let sleep = Duration::try_from_secs_f64(sleep)
+        .map_err(|_| PyValueError::new_err("sleep must be a non-negative number of seconds."))?;
+    let path = dest
+        .to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or_else(|| PyValueError::new_err("The backup path must be valid UTF-8 without NUL."))?;
+    let pool_id = sqlite_hooks::pool_id(db)?;
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let mut conn = acquire(&pool).await.map_err(map_db_err)?;
+        let source = sqlite_hooks::connection_handle(&mut conn, pool_id)
+            .await
+            .map_err(map_db_err)?;
+        let mut backup = Backup {
+            dest: ptr::null_mut(),
+            backup: ptr::null_mut(),
+        };
+        // SAFETY: the destination is closed by `Backup` even if opening it
+        // failed, and the source connection is held until the copy is done.
+        let rc = unsafe {
+            ffi::sqlite3_open_v2(
+                path.as_ptr(),
+                &mut backup.dest,
+                ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE,
+                ptr::null(),
+            )
+        };
+        if rc == ffi::SQLITE_OK {
+            backup.backup = unsafe {
+                ffi::sqlite3_backup_init(
+                    backup.dest,
+                    c"main".as_ptr(),
+                    source as *mut ffi::sqlite3,
+                    c"main".as_ptr(),
+                )
+            };
+        }
+        if backup.backup.is_null() {
+            return Err(OperationalError::new_err(format!(
+                "Couldn't back up into {}: {}",
+                dest.display(),
+                backup.error()
+            )));
+        }
+        debug!("Backing up the database into {}", dest.display());
+        loop {
+            // SAFETY: the backup is open.
+            let (rc, remaining, total): (c_int, c_int, c_int) = unsafe {
+                let rc = ffi::sqlite3_backup_step(backup.backup, pages);
+                (
+                    rc,
+                    ffi::sqlite3_backup_remaining(backup.backup),
+                    ffi::sqlite3_backup_pagecount(backup.backup),
+                )
+            };
+            match rc {
+                ffi::SQLITE_OK | ffi::SQLITE_DONE | ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED => {}
+                _ => {
+                    return Err(OperationalError::new_err(format!(
+                        "Backing up into {} failed: {}",
+                        dest.display(),
+                        backup.error()
+                    )));
+                }
+            }
+            if let Some(callback) = &progress_callback {
+                Python::attach(|py| callback.call1(py, (remaining, total)))?;
+            }
+            if rc == ffi::SQLITE_DONE {
+                break;
+            }
+            tokio::time::sleep(sleep).await;
+        }
+        drop(backup);
+        drop(conn);
+        Ok(())
+    })
+}
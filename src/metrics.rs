@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+/// The upper bounds of the histogram buckets, in seconds: the defaults of the
+/// Prometheus clients.
+const BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    /// The observations in each bucket, not cumulated.
+    counts: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.counts[bucket] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    /// Appends the `_bucket`, `_count` and `_sum` samples, with `labels`.
+    fn samples<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        labels: &[(&str, &str)],
+        samples: &Bound<'py, PyList>,
+    ) -> PyResult<()> {
+        let mut cumulated = 0;
+        let bounds = BUCKETS.iter().map(|b| b.to_string()).chain(["+Inf".into()]);
+        for (index, bound) in bounds.enumerate() {
+            cumulated += self.counts.get(index).copied().unwrap_or_default();
+            let count = if index == BUCKETS.len() {
+                self.count
+            } else {
+                cumulated
+            };
+            let mut bucket_labels = labels.to_vec();
+            bucket_labels.push(("le", &bound));
+            samples.append(sample(
+                py,
+                &format!("{}_bucket", name),
+                &bucket_labels,
+                count as f64,
+            )?)?;
+        }
+        samples.append(sample(
+            py,
+            &format!("{}_count", name),
+            labels,
+            self.count as f64,
+        )?)?;
+        samples.append(sample(py, &format!("{}_sum", name), labels, self.sum)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Metrics {
+    /// Statements run and failed, by verb and table.
+    queries: BTreeMap<(String, String), u64>,
+    errors: BTreeMap<(String, String), u64>,
+    /// How long statements took, by verb.
+    durations: BTreeMap<String, Histogram>,
+    /// How long statements waited for a connection of the pool.
+    pool_waits: Histogram,
+}
+
+static METRICS: Mutex<Option<Metrics>> = Mutex::new(None);
+
+fn with_metrics(f: impl FnOnce(&mut Metrics)) {
+    f(METRICS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(Metrics::default));
+}
+
+/// The verb of a statement and the table it reads or writes, if it names one
+/// where its verb expects it.
+fn verb_and_table(sql: &str) -> (String, String) {
+    let words: Vec<&str> = sql.split_whitespace().collect();
+    let verb = words.first().copied().unwrap_or_default().to_uppercase();
+    let after = |keyword: &str| {
+        words
+            .iter()
+            .position(|w| w.eq_ignore_ascii_case(keyword))
+            .and_then(|i| words.get(i + 1))
+    };
+    let table = match verb.as_str() {
+        "SELECT" | "DELETE" => after("FROM"),
+        "INSERT" | "REPLACE" => after("INTO"),
+        "UPDATE" => words.get(1),
+        _ => None,
+    };
+    let table = table
+        .map(|t| {
+            t.split(['(', ',', ';'])
+                .next()
+                .unwrap_or_default()
+                .trim_matches(['"', '`', '[', ']'])
+                .to_string()
+        })
+        .unwrap_or_default();
+    (verb, table)
+}
+
+/// Counts a statement and records how long it took.
+pub(crate) fn record_query(sql: &str, elapsed: Duration, failed: bool) {
+    let (verb, table) = verb_and_table(sql);
+    with_metrics(|metrics| {
+        metrics
+            .durations
+            .entry(verb.clone())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        let key = (verb, table);
+        if failed {
+            *metrics.errors.entry(key.clone()).or_default() += 1;
+        }
+        *metrics.queries.entry(key).or_default() += 1;
+    });
+}
+
+/// Records how long a statement waited for a connection.
+pub(crate) fn record_pool_wait(elapsed: Duration) {
+    with_metrics(|metrics| metrics.pool_waits.observe(elapsed.as_secs_f64()));
+}
+
+fn sample<'py>(
+    py: Python<'py>,
+    name: &str,
+    labels: &[(&str, &str)],
+    value: f64,
+) -> PyResult<Bound<'py, PyDict>> {
+    let sample = PyDict::new(py);
+    sample.set_item("name", name)?;
+    let label_dict = PyDict::new(py);
+    for (key, value) in labels {
+        label_dict.set_item(key, value)?;
+    }
+    sample.set_item("labels", label_dict)?;
+    sample.set_item("value", value)?;
+    Ok(sample)
+}
+
+fn family<'py>(
+    py: Python<'py>,
+    name: &str,
+    documentation: &str,
+    kind: &str,
+    samples: Bound<'py, PyList>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let family = PyDict::new(py);
+    family.set_item("name", name)?;
+    family.set_item("documentation", documentation)?;
+    family.set_item("type", kind)?;
+    family.set_item("samples", samples)?;
+    Ok(family)
+}
+
+/// The metrics of the statements run since the module was loaded, as metric
+/// families: dicts with the `name`, `documentation`, `type` and `samples`
+/// (each with a `name`, `labels` and `value`) of `prometheus_client`'s
+/// `Metric`.
+#[pyfunction]
+pub(crate) fn metrics(py: Python) -> PyResult<Bound<PyList>> {
+    let guard = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    let empty = Metrics::default();
+    let metrics = guard.as_ref().unwrap_or(&empty);
+    let counter = |name: &str, counts: &BTreeMap<(String, String), u64>| -> PyResult<_> {
+        let samples = PyList::empty(py);
+        for ((verb, table), count) in counts {
+            let labels = [("verb", verb.as_str()), ("table", table.as_str())];
+            samples.append(sample(
+                py,
+                &format!("{}_total", name),
+                &labels,
+                *count as f64,
+            )?)?;
+        }
+        Ok(samples)
+    };
+    let durations = PyList::empty(py);
+    for (verb, histogram) in &metrics.durations {
+        histogram.samples(
+            py,
+            "fust_orm_query_duration_seconds",
+            &[("verb", verb)],
+            &durations,
+        )?;
+    }
+    let pool_waits = PyList::empty(py);
+    metrics
+        .pool_waits
+        .samples(py, "fust_orm_pool_wait_seconds", &[], &pool_waits)?;
+    PyList::new(
+        py,
+        [
+            family(
+                py,
+                "fust_orm_queries",
+                "Statements run, by verb and table.",
+                "counter",
+                counter("fust_orm_queries", &metrics.queries)?,
+            )?,
+            family(
+                py,
+                "fust_orm_query_errors",
+                "Statements that failed, by verb and table.",
+                "counter",
+                counter("fust_orm_query_errors", &metrics.errors)?,
+            )?,
+            family(
+                py,
+                "fust_orm_query_duration_seconds",
+                "How long statements took, by verb.",
+                "histogram",
+                durations,
+            )?,
+            family(
+                py,
+                "fust_orm_pool_wait_seconds",
+                "How long statements waited for a connection of the pool.",
+                "histogram",
+                pool_waits,
+            )?,
+        ],
+    )
+}
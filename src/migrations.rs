@@ -0,0 +1,930 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::autogen::{self, DesiredSchema};
+use crate::database::{
+    Database, begin_write_on, execute_statement, fetch_rows, sqlite_foreign_keys_off,
+    sqlite_foreign_keys_on,
+};
+use crate::error::{DbErrorKind, FustOrmError};
+use crate::introspect;
+use crate::schema::Dialect;
+use log::{debug, info};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyString};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::pool::PoolConnection;
+use sqlx::{Any, AnyPool, Executor, Row};
+use tokio::sync::Mutex as AsyncMutex;
+
+/// The table recording which migrations have been applied.
+pub(crate) const MIGRATIONS_TABLE: &str = "_fust_migrations";
+
+/// The table whose row locks the migrations on SQLite.
+pub(crate) const LOCK_TABLE: &str = "_fust_migrations_lock";
+
+/// The key of the PostgreSQL advisory lock taken while migrating ("fustmigr").
+const LOCK_KEY: i64 = 0x6675_7374_6d69_6772;
+
+/// The name of the MySQL lock taken while migrating.
+const LOCK_NAME: &str = "fust_orm_migrations";
+
+/// How long to wait for the migrations another process is running.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A migration read from a migrations directory.
+///
+/// Files are named `<version>_<name>` with a numeric version, which orders the
+/// migrations:
+/// - `<version>_<name>.up.sql` with an optional `<version>_<name>.down.sql`,
+/// - `<version>_<name>.sql`, which can't be rolled back,
+/// - `<version>_<name>.py`, a module defining `up` and optionally `down` as SQL
+///   strings, Python functions or lists of them.
+#[derive(Debug, Clone)]
+pub(crate) struct Migration {
+    version: i64,
+    name: String,
+    up: Vec<Step>,
+    down: Option<Vec<Step>>,
+}
+
+/// A step of a migration.
+#[derive(Debug, Clone)]
+pub(crate) enum Step {
+    /// SQL run unprepared, so it may hold several statements.
+    Sql(String),
+    /// A Python function called with the `Database`, and awaited if it is a
+    /// coroutine function, for data migrations.
+    Python(Arc<Py<PyAny>>),
+}
+
+/// An applied migration, as recorded in the migrations table.
+struct Applied {
+    version: i64,
+    name: String,
+    applied_at: i64,
+}
+
+fn migration_error(message: String) -> PyErr {
+    FustOrmError::BuildError(message).into()
+}
+
+/// Splits a file stem like `0001_create_users` into version and name.
+fn parse_stem(stem: &str) -> Option<(i64, String)> {
+    let (version, name) = stem.split_once('_').unwrap_or((stem, ""));
+    Some((version.parse().ok()?, name.to_string()))
+}
+
+/// Reads the steps a Python migration module defines as `attribute`.
+fn module_steps(module: &Bound<PyAny>, attribute: &str) -> PyResult<Option<Vec<Step>>> {
+    let Ok(value) = module.getattr(attribute) else {
+        return Ok(None);
+    };
+    if value.is_none() {
+        return Ok(None);
+    }
+    let step = |value: Bound<PyAny>| -> PyResult<Step> {
+        if let Ok(sql) = value.downcast::<PyString>() {
+            Ok(Step::Sql(sql.to_string()))
+        } else if value.is_callable() {
+            Ok(Step::Python(Arc::new(value.unbind())))
+        } else {
+            Err(migration_error(format!(
+                "`{}` of a migration module must be a SQL string, a function or a list of them.",
+                attribute
+            )))
+        }
+    };
+    if value.downcast::<PyString>().is_err()
+        && !value.is_callable()
+        && let Ok(items) = value.try_iter()
+    {
+        return items
+            .map(|item| step(item?))
+            .collect::<PyResult<_>>()
+            .map(Some);
+    }
+    Ok(Some(vec![step(value)?]))
+}
+
+/// Loads a Python migration module from `path`.
+fn load_module<'py>(py: Python<'py>, path: &Path, name: &str) -> PyResult<Bound<'py, PyAny>> {
+    let util = py.import("importlib.util")?;
+    let spec = util.call_method1(
+        "spec_from_file_location",
+        (format!("_fust_migration_{}", name), path.to_string_lossy()),
+    )?;
+    let module = util.call_method1("module_from_spec", (&spec,))?;
+    spec.getattr("loader")?
+        .call_method1("exec_module", (&module,))?;
+    Ok(module)
+}
+
+/// Reads the migrations in `dir`, ordered by version.
+pub(crate) fn load_migrations(py: Python, dir: &str) -> PyResult<Vec<Migration>> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        migration_error(format!("Can't read migrations directory '{}': {}", dir, e))
+    })?;
+    let mut migrations: Vec<Migration> = Vec::new();
+    let mut downs = Vec::new();
+    let mut paths = entries
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            migration_error(format!("Can't read migrations directory '{}': {}", dir, e))
+        })?;
+    paths.sort();
+    for path in paths {
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let (stem, kind) = if let Some(stem) = file_name.strip_suffix(".up.sql") {
+            (stem, "up")
+        } else if let Some(stem) = file_name.strip_suffix(".down.sql") {
+            (stem, "down")
+        } else if let Some(stem) = file_name.strip_suffix(".sql") {
+            (stem, "sql")
+        } else if let Some(stem) = file_name.strip_suffix(".py") {
+            (stem, "py")
+        } else {
+            continue;
+        };
+        let Some((version, name)) = parse_stem(stem) else {
+            debug!("Skipping '{}': no numeric version prefix", file_name);
+            continue;
+        };
+        let read = || {
+            std::fs::read_to_string(&path).map_err(|e| {
+                migration_error(format!("Can't read migration '{}': {}", file_name, e))
+            })
+        };
+        let migration = match kind {
+            "down" => {
+                downs.push((version, read()?));
+                continue;
+            }
+            "py" => {
+                let module = load_module(py, &path, stem)?;
+                let up = module_steps(&module, "up")?.ok_or_else(|| {
+                    migration_error(format!("Migration '{}' doesn't define `up`.", file_name))
+                })?;
+                Migration {
+                    version,
+                    name,
+                    up,
+                    down: module_steps(&module, "down")?,
+                }
+            }
+            _ => Migration {
+                version,
+                name,
+                up: vec![Step::Sql(read()?)],
+                down: None,
+            },
+        };
+        if migrations.iter().any(|m| m.version == version) {
+            return Err(migration_error(format!(
+                "Duplicate migration version {} in '{}'.",
+                version, dir
+            )));
+        }
+        migrations.push(migration);
+    }
+    for (version, sql) in downs {
+        let migration = migrations
+            .iter_mut()
+            .find(|m| m.version == version)
+            .ok_or_else(|| {
+                migration_error(format!(
+                    "Down migration for version {} has no matching up migration.",
+                    version
+                ))
+            })?;
+        migration.down = Some(vec![Step::Sql(sql)]);
+    }
+    migrations.sort_by_key(|m| m.version);
+    Ok(migrations)
+}
+
+async fn ensure_table(pool: &AnyPool) -> Result<(), FustOrmError> {
+    let sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (\
+         version BIGINT PRIMARY KEY, \
+         name VARCHAR(255) NOT NULL, \
+         applied_at BIGINT NOT NULL)",
+        MIGRATIONS_TABLE
+    );
+    pool.execute(sql.as_str())
+        .await
+        .map_err(FustOrmError::from)?;
+    Ok(())
+}
+
+async fn applied_migrations(pool: &AnyPool) -> Result<Vec<Applied>, FustOrmError> {
+    ensure_table(pool).await?;
+    read_applied(pool).await
+}
+
+/// `applied_migrations()` for the commands that only read them, which leave
+/// a database without the migrations table unchanged: none are applied.
+async fn recorded_migrations(pool: &AnyPool) -> Result<Vec<Applied>, FustOrmError> {
+    let dialect = Database::new(pool.clone()).dialect();
+    let tables = introspect::tables(pool, dialect).await?;
+    if !tables.iter().any(|table| table == MIGRATIONS_TABLE) {
+        return Ok(Vec::new());
+    }
+    read_applied(pool).await
+}
+
+async fn read_applied(pool: &AnyPool) -> Result<Vec<Applied>, FustOrmError> {
+    let sql = format!(
+        "SELECT version, name, applied_at FROM {} ORDER BY version",
+        MIGRATIONS_TABLE
+    );
+    let map_db_err = FustOrmError::from;
+    sqlx::query(&sql)
+        .fetch_all(pool)
+        .await
+        .map_err(map_db_err)?
+        .iter()
+        .map(|row| {
+            Ok(Applied {
+                version: row.try_get("version").map_err(map_db_err)?,
+                name: row.try_get("name").map_err(map_db_err)?,
+                applied_at: row.try_get("applied_at").map_err(map_db_err)?,
+            })
+        })
+        .collect()
+}
+
+/// Runs the steps of one migration and records the change in the migrations
+/// table, in one transaction. Python steps are called with a `Database` bound
+/// to it, so that a migration failing in any step leaves nothing behind.
+///
+/// On SQLite, foreign keys are switched off while the migration runs, so
+/// that it can rebuild tables, and checked before committing. Some backends
+/// (MySQL) commit DDL implicitly, so a failing migration may leave earlier
+/// statements applied there.
+async fn run_steps(
+    pool: &AnyPool,
+    migration: &Migration,
+    steps: &[Step],
+    record: &str,
+    params: Vec<String>,
+) -> Result<(), FustOrmError> {
+    let map_db_err = |e: sqlx::Error| {
+        FustOrmError::QueryError(format!(
+            "Migration {}_{} failed: {}",
+            migration.version, migration.name, e
+        ))
+    };
+    let mut conn = pool.acquire().await.map_err(FustOrmError::from)?;
+    let sqlite = conn.backend_name() == "SQLite";
+    let enforced = sqlite && sqlite_foreign_keys_off(&mut conn).await?;
+    let (result, mut conn) = match begin_write_on(conn).await {
+        Ok(tx) => {
+            let db = Database {
+                tx: Some(Arc::new(AsyncMutex::new(Some(tx)))),
+                ..Database::new(pool.clone())
+            };
+            let result = run_in(&db, migration, steps, record, params, enforced).await;
+            let shared = db.tx.as_ref().expect("bound to the transaction");
+            let tx = shared.lock().await.take().expect("the transaction is open");
+            match tx.finish(result.is_ok()).await {
+                Ok(conn) => (result, conn),
+                Err(e) => return Err(map_db_err(e)),
+            }
+        }
+        Err((e, conn)) => (Err(e), conn),
+    };
+    if enforced {
+        sqlite_foreign_keys_on(&mut conn).await?;
+    }
+    result
+}
+
+/// Runs `steps` in the transaction of `db`, recording the change in the
+/// migrations table with `record`.
+async fn run_in(
+    db: &Database,
+    migration: &Migration,
+    steps: &[Step],
+    record: &str,
+    params: Vec<String>,
+    check_foreign_keys: bool,
+) -> Result<(), FustOrmError> {
+    let map_db_err = |e: sqlx::Error| {
+        FustOrmError::QueryError(format!(
+            "Migration {}_{} failed: {}",
+            migration.version, migration.name, e
+        ))
+    };
+    for step in steps {
+        match step {
+            Step::Sql(sql) => {
+                let mut guard = db.lock_tx().await?.expect("bound to the transaction");
+                let tx = guard.as_mut().expect("the transaction is open");
+                for statement in split_script(sql) {
+                    // Unprepared, as the statements of a script may depend on
+                    // the schema changes of those before them.
+                    (&mut **tx)
+                        .execute(statement.as_str())
+                        .await
+                        .map_err(map_db_err)?;
+                }
+            }
+            Step::Python(function) => run_function(db, migration, function).await?,
+        }
+    }
+    db.execute_statement(record, params).await?;
+    if check_foreign_keys
+        && !db
+            .fetch_rows_uncached("PRAGMA foreign_key_check", Vec::new())
+            .await?
+            .is_empty()
+    {
+        return Err(FustOrmError::QueryError(format!(
+            "Migration {}_{} would leave rows violating foreign keys.",
+            migration.version, migration.name
+        )));
+    }
+    Ok(())
+}
+
+/// Splits a SQL script into its statements, at the semicolons outside string
+/// literals, quoted identifiers, comments, PostgreSQL dollar-quoted bodies
+/// and `BEGIN ... END` blocks (of triggers). Statements holding nothing but
+/// comments are left out.
+fn split_script(script: &str) -> Vec<String> {
+    let bytes = script.as_bytes();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    // Whether the statement so far holds more than comments.
+    let mut code = false;
+    // The depth of the `BEGIN` and `CASE` blocks.
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                // A doubled quote closes and reopens the literal.
+                i += bytes[i + 1..]
+                    .iter()
+                    .position(|&b| b == quote)
+                    .map_or(bytes.len() - i, |end| end + 2);
+                code = true;
+                continue;
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i += bytes[i..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .unwrap_or(bytes.len() - i);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = script[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| i + 2 + end + 2);
+                continue;
+            }
+            b'$' => {
+                let tag_len = bytes[i + 1..]
+                    .iter()
+                    .position(|&b| !(b.is_ascii_alphanumeric() || b == b'_'))
+                    .filter(|&len| {
+                        bytes.get(i + 1 + len) == Some(&b'$')
+                            && !bytes.get(i + 1).is_some_and(u8::is_ascii_digit)
+                    });
+                if let Some(len) = tag_len {
+                    let tag = &script[i..i + len + 2];
+                    i = script[i + tag.len()..]
+                        .find(tag)
+                        .map_or(bytes.len(), |end| i + 2 * tag.len() + end);
+                    code = true;
+                    continue;
+                }
+                code = true;
+            }
+            b';' if depth == 0 => {
+                if code {
+                    statements.push(script[start..i].trim().to_string());
+                }
+                start = i + 1;
+                code = false;
+            }
+            b if b.is_ascii_alphabetic() || b == b'_' => {
+                let len = bytes[i..]
+                    .iter()
+                    .position(|&b| !(b.is_ascii_alphanumeric() || b == b'_'))
+                    .unwrap_or(bytes.len() - i);
+                let word = &script[i..i + len];
+                if word.eq_ignore_ascii_case("CASE")
+                    // A `BEGIN` starting a statement begins a transaction.
+                    || (word.eq_ignore_ascii_case("BEGIN") && code)
+                {
+                    depth += 1;
+                } else if word.eq_ignore_ascii_case("END") {
+                    depth = depth.saturating_sub(1);
+                }
+                code = true;
+                i += len;
+                continue;
+            }
+            b if !b.is_ascii_whitespace() => code = true,
+            _ => {}
+        }
+        i += 1;
+    }
+    if code {
+        statements.push(script[start..].trim().to_string());
+    }
+    statements
+}
+
+/// Calls a Python step with `db` and awaits its result if it is awaitable.
+async fn run_function(
+    db: &Database,
+    migration: &Migration,
+    function: &Py<PyAny>,
+) -> Result<(), FustOrmError> {
+    let map_py_err = |e: PyErr| {
+        FustOrmError::QueryError(format!(
+            "Migration {}_{} failed: {}",
+            migration.version, migration.name, e
+        ))
+    };
+    let future = Python::attach(|py| {
+        let result = function.bind(py).call1((db.clone(),))?;
+        if !result.hasattr(pyo3::intern!(py, "__await__"))? {
+            return Ok(None);
+        }
+        match pyo3_async_runtimes::tokio::get_current_locals(py) {
+            Ok(locals) => pyo3_async_runtimes::into_future_with_locals(&locals, result).map(Some),
+            // Without a running event loop (on the command line), the
+            // coroutine runs on one of its own.
+            Err(_) => {
+                py.import("asyncio")?.call_method1("run", (result,))?;
+                Ok(None)
+            }
+        }
+    })
+    .map_err(map_py_err)?;
+    if let Some(future) = future {
+        future.await.map_err(map_py_err)?;
+    }
+    Ok(())
+}
+
+fn migration_dict<'py>(py: Python<'py>, version: i64, name: &str) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("version", version)?;
+    dict.set_item("name", name)?;
+    Ok(dict)
+}
+
+/// A migration's version and name, as reported back to the caller.
+pub(crate) type MigrationId = (i64, String);
+
+/// A migration's version, name and application time, if applied.
+pub(crate) type MigrationState = (i64, String, Option<i64>);
+
+fn migration_list(py: Python, migrations: &[MigrationId]) -> PyResult<Py<PyList>> {
+    let result = PyList::empty(py);
+    for (version, name) in migrations {
+        result.append(migration_dict(py, *version, name)?)?;
+    }
+    Ok(result.unbind())
+}
+
+/// A lock held while migrations are applied or rolled back, so that app
+/// instances deploying at the same time run them one after the other: a
+/// session-level advisory lock on PostgreSQL, a named lock on MySQL and a
+/// row of the lock table on SQLite.
+enum MigrationLock {
+    /// The connection holding the PostgreSQL or MySQL lock.
+    Session(PoolConnection<Any>, Dialect),
+    Row,
+}
+
+impl MigrationLock {
+    /// Takes the lock, waiting for another process to release it.
+    async fn acquire(pool: &AnyPool) -> Result<Self, FustOrmError> {
+        let map_db_err = FustOrmError::from;
+        let timed_out = || {
+            FustOrmError::QueryError(format!(
+                "Timed out after {}s waiting for another process's migrations to finish.",
+                LOCK_TIMEOUT.as_secs()
+            ))
+        };
+        let dialect = Database::new(pool.clone()).dialect();
+        debug!("Taking the migration lock");
+        match dialect {
+            Dialect::Postgres => {
+                let mut conn = pool.acquire().await.map_err(map_db_err)?;
+                let sql = format!("SELECT pg_try_advisory_lock({}) AS acquired", LOCK_KEY);
+                let started = Instant::now();
+                while !fetch_rows(&mut *conn, &sql, Vec::new())
+                    .await?
+                    .first()
+                    .map(|row| row.try_get::<bool, _>("acquired"))
+                    .transpose()
+                    .map_err(map_db_err)?
+                    .unwrap_or_default()
+                {
+                    if started.elapsed() > LOCK_TIMEOUT {
+                        return Err(timed_out());
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Ok(MigrationLock::Session(conn, dialect))
+            }
+            Dialect::Mysql => {
+                let mut conn = pool.acquire().await.map_err(map_db_err)?;
+                let sql = format!(
+                    "SELECT CAST(COALESCE(GET_LOCK(?, {}), 0) AS SIGNED) AS acquired",
+                    LOCK_TIMEOUT.as_secs()
+                );
+                let acquired = fetch_rows(&mut *conn, &sql, vec![LOCK_NAME.to_string()])
+                    .await?
+                    .first()
+                    .map(|row| row.try_get::<i64, _>("acquired"))
+                    .transpose()
+                    .map_err(map_db_err)?
+                    .unwrap_or_default();
+                if acquired != 1 {
+                    return Err(timed_out());
+                }
+                Ok(MigrationLock::Session(conn, dialect))
+            }
+            Dialect::Sqlite => {
+                let create = format!(
+                    "CREATE TABLE IF NOT EXISTS {} (\
+                     id INTEGER PRIMARY KEY CHECK (id = 1), \
+                     locked_at BIGINT NOT NULL)",
+                    LOCK_TABLE
+                );
+                pool.execute(create.as_str()).await.map_err(map_db_err)?;
+                let insert = format!("INSERT INTO {} (id, locked_at) VALUES (1, ?)", LOCK_TABLE);
+                let started = Instant::now();
+                // The row exists while another process migrates.
+                while let Err(e) = execute_statement(pool, &insert, vec![now().to_string()]).await {
+                    if !lock_held(&e) {
+                        return Err(e);
+                    }
+                    if started.elapsed() > LOCK_TIMEOUT {
+                        return Err(FustOrmError::QueryError(format!(
+                            "{} If no migration is running, a crashed one left its lock \
+                             behind: delete the row of {}.",
+                            timed_out(),
+                            LOCK_TABLE
+                        )));
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Ok(MigrationLock::Row)
+            }
+        }
+    }
+
+    async fn release(self, pool: &AnyPool) -> Result<(), FustOrmError> {
+        debug!("Releasing the migration lock");
+        match self {
+            MigrationLock::Session(mut conn, Dialect::Postgres) => {
+                let sql = format!("SELECT pg_advisory_unlock({})", LOCK_KEY);
+                fetch_rows(&mut *conn, &sql, Vec::new()).await?;
+            }
+            MigrationLock::Session(mut conn, _) => {
+                let sql = "SELECT RELEASE_LOCK(?) AS released";
+                fetch_rows(&mut *conn, sql, vec![LOCK_NAME.to_string()]).await?;
+            }
+            MigrationLock::Row => {
+                let sql = format!("DELETE FROM {} WHERE id = 1", LOCK_TABLE);
+                execute_statement(pool, &sql, Vec::new()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether taking the SQLite migration lock failed as another process holds
+/// it: its row exists, or the database is busy with its migrations.
+fn lock_held(err: &FustOrmError) -> bool {
+    let FustOrmError::Database(err) = err else {
+        return false;
+    };
+    // SQLITE_BUSY and SQLITE_LOCKED, with their extended codes.
+    let busy = err
+        .code
+        .as_deref()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 5 | 6));
+    busy || matches!(
+        err.kind,
+        DbErrorKind::UniqueViolation | DbErrorKind::Integrity
+    )
+}
+
+/// Runs `migrate` while holding the migration lock.
+async fn locked<T>(
+    pool: &AnyPool,
+    migrate: impl Future<Output = Result<T, FustOrmError>>,
+) -> Result<T, FustOrmError> {
+    let lock = MigrationLock::acquire(pool).await?;
+    let result = migrate.await;
+    lock.release(pool).await?;
+    result
+}
+
+/// Applies the pending `migrations` in version order, returning the applied
+/// ones. Concurrent calls from other processes wait for each other, and
+/// don't apply the migrations applied meanwhile again.
+pub(crate) async fn apply_pending(
+    pool: &AnyPool,
+    migrations: &[Migration],
+) -> Result<Vec<MigrationId>, FustOrmError> {
+    locked(pool, apply_pending_unlocked(pool, migrations)).await
+}
+
+async fn apply_pending_unlocked(
+    pool: &AnyPool,
+    migrations: &[Migration],
+) -> Result<Vec<MigrationId>, FustOrmError> {
+    let applied = applied_migrations(pool).await?;
+    let mut done = Vec::new();
+    for migration in migrations
+        .iter()
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+    {
+        info!(
+            "Applying migration {}_{}",
+            migration.version, migration.name
+        );
+        // Integers are inlined, since parameters are bound as strings.
+        let record = format!(
+            "INSERT INTO {} (version, name, applied_at) VALUES ({}, ?, {})",
+            MIGRATIONS_TABLE,
+            migration.version,
+            now()
+        );
+        let params = vec![migration.name.clone()];
+        run_steps(pool, migration, &migration.up, &record, params).await?;
+        done.push((migration.version, migration.name.clone()));
+    }
+    Ok(done)
+}
+
+/// Describes a Python step of a migration plan as a SQL comment.
+fn describe_function(function: &Py<PyAny>) -> String {
+    Python::attach(|py| {
+        let function = function.bind(py);
+        let name = function
+            .getattr(pyo3::intern!(py, "__qualname__"))
+            .and_then(|name| name.extract::<String>())
+            .unwrap_or_else(|_| function.to_string());
+        format!("-- Python step: {}(db)", name)
+    })
+}
+
+/// Lists what applying the pending `migrations` would run, in order, without
+/// running it: the statements of each migration's SQL steps, and its Python
+/// steps as comments naming the function, with the `<version>_<name>` of the
+/// migration.
+pub(crate) async fn pending_plan(
+    pool: &AnyPool,
+    migrations: &[Migration],
+) -> Result<Vec<(String, String)>, FustOrmError> {
+    let applied = recorded_migrations(pool).await?;
+    let mut plan = Vec::new();
+    for migration in migrations
+        .iter()
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+    {
+        let id = format!("{:04}_{}", migration.version, migration.name);
+        for step in &migration.up {
+            match step {
+                Step::Sql(sql) => plan.extend(
+                    split_script(sql)
+                        .into_iter()
+                        .map(|statement| (id.clone(), statement)),
+                ),
+                Step::Python(function) => plan.push((id.clone(), describe_function(function))),
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Rolls back the `steps` most recently applied migrations with their down
+/// scripts from `migrations`, returning the rolled back ones, newest first.
+pub(crate) async fn roll_back(
+    pool: &AnyPool,
+    migrations: &[Migration],
+    steps: usize,
+) -> Result<Vec<MigrationId>, FustOrmError> {
+    locked(pool, roll_back_unlocked(pool, migrations, steps)).await
+}
+
+async fn roll_back_unlocked(
+    pool: &AnyPool,
+    migrations: &[Migration],
+    steps: usize,
+) -> Result<Vec<MigrationId>, FustOrmError> {
+    let applied = applied_migrations(pool).await?;
+    let mut done = Vec::new();
+    for applied in applied.iter().rev().take(steps) {
+        let migration = migrations
+            .iter()
+            .find(|m| m.version == applied.version)
+            .ok_or_else(|| {
+                FustOrmError::BuildError(format!(
+                    "Applied migration {}_{} is missing from the migrations directory.",
+                    applied.version, applied.name
+                ))
+            })?;
+        let down = migration.down.as_ref().ok_or_else(|| {
+            FustOrmError::BuildError(format!(
+                "Migration {}_{} has no down migration and can't be rolled back.",
+                migration.version, migration.name
+            ))
+        })?;
+        info!(
+            "Rolling back migration {}_{}",
+            migration.version, migration.name
+        );
+        let record = format!(
+            "DELETE FROM {} WHERE version = {}",
+            MIGRATIONS_TABLE, migration.version
+        );
+        run_steps(pool, migration, down, &record, Vec::new()).await?;
+        done.push((migration.version, migration.name.clone()));
+    }
+    Ok(done)
+}
+
+/// The state of `migrations` and of the applied migrations missing from
+/// them, in version order.
+pub(crate) async fn migration_states(
+    pool: &AnyPool,
+    migrations: &[Migration],
+) -> Result<Vec<MigrationState>, FustOrmError> {
+    let applied = recorded_migrations(pool).await?;
+    let mut states: Vec<MigrationState> = migrations
+        .iter()
+        .map(|m| {
+            let applied_at = applied
+                .iter()
+                .find(|a| a.version == m.version)
+                .map(|a| a.applied_at);
+            (m.version, m.name.clone(), applied_at)
+        })
+        .collect();
+    for a in &applied {
+        if !migrations.iter().any(|m| m.version == a.version) {
+            states.push((a.version, a.name.clone(), Some(a.applied_at)));
+        }
+    }
+    states.sort_by_key(|(version, _, _)| *version);
+    Ok(states)
+}
+
+/// Plans the changes bringing the database in line with `schema` and writes
+/// them as the migration after `migrations` in `dir`, named `name`. Returns
+/// the written migration, or `None` if there is nothing to change.
+pub(crate) async fn write_migration(
+    pool: &AnyPool,
+    dialect: Dialect,
+    dir: &str,
+    migrations: &[Migration],
+    schema: &DesiredSchema,
+    name: &str,
+) -> Result<Option<MigrationId>, FustOrmError> {
+    let applied = recorded_migrations(pool).await?;
+    if let Some(pending) = migrations
+        .iter()
+        .find(|m| !applied.iter().any(|a| a.version == m.version))
+    {
+        return Err(FustOrmError::BuildError(format!(
+            "Migration {}_{} isn't applied yet; apply pending migrations before generating a new one.",
+            pending.version, pending.name
+        )));
+    }
+    let plan = autogen::plan(pool, dialect, schema).await?;
+    if plan.is_empty() {
+        return Ok(None);
+    }
+    let version = migrations.last().map_or(1, |m| m.version + 1);
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let stem = Path::new(dir).join(format!("{:04}_{}", version, name));
+    for (suffix, statements) in [("up", &plan.up), ("down", &plan.down)] {
+        let path = stem.with_extension(format!("{}.sql", suffix));
+        let script: String = statements.iter().map(|s| format!("{};\n", s)).collect();
+        std::fs::write(&path, script).map_err(|e| {
+            FustOrmError::BuildError(format!("Can't write migration '{}': {}", path.display(), e))
+        })?;
+    }
+    info!("Generated migration {}_{}", version, name);
+    Ok(Some((version, name)))
+}
+
+/// Applies the pending migrations in `dir` in version order. Resolves to the
+/// applied migrations as `{"version": ..., "name": ...}` dicts.
+pub(crate) fn migrate<'py>(
+    py: Python<'py>,
+    pool: AnyPool,
+    dir: &str,
+    dry_run: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let migrations = load_migrations(py, dir)?;
+    if dry_run {
+        return future_into_py(
+            py,
+            async move { Ok(pending_plan(&pool, &migrations).await?) },
+        );
+    }
+    future_into_py(py, async move {
+        let done = apply_pending(&pool, &migrations).await?;
+        Python::attach(|py| migration_list(py, &done))
+    })
+}
+
+/// Rolls back the `steps` most recently applied migrations with their down
+/// scripts from `dir`. Resolves to the rolled back migrations, newest first.
+pub(crate) fn rollback<'py>(
+    py: Python<'py>,
+    pool: AnyPool,
+    dir: &str,
+    steps: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    let migrations = load_migrations(py, dir)?;
+    future_into_py(py, async move {
+        let done = roll_back(&pool, &migrations, steps).await?;
+        Python::attach(|py| migration_list(py, &done))
+    })
+}
+
+/// Lists the migrations in `dir` and the applied ones missing from it, in
+/// version order, with `applied` and `applied_at` (a UTC datetime or `None`).
+pub(crate) fn status<'py>(
+    py: Python<'py>,
+    pool: AnyPool,
+    dir: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let migrations = load_migrations(py, dir)?;
+    future_into_py(py, async move {
+        let states = migration_states(&pool, &migrations).await?;
+        Python::attach(|py| -> PyResult<Py<PyList>> {
+            let datetime = py.import("datetime")?;
+            let utc = datetime.getattr("timezone")?.getattr("utc")?;
+            let from_timestamp = datetime.getattr("datetime")?.getattr("fromtimestamp")?;
+            let result = PyList::empty(py);
+            for (version, name, applied_at) in states {
+                let dict = migration_dict(py, version, &name)?;
+                dict.set_item("applied", applied_at.is_some())?;
+                match applied_at {
+                    Some(ts) => dict.set_item("applied_at", from_timestamp.call1((ts, &utc))?)?,
+                    None => dict.set_item("applied_at", py.None())?,
+                }
+                result.append(dict)?;
+            }
+            Ok(result.unbind())
+        })
+    })
+}
+
+/// Compares `models` (all models if `None`) with the database and writes
+/// the statements bringing it in line as the next migration of `dir`, named
+/// `name`. Resolves to the written migration as `{"version": ..., "name": ...}`,
+/// or `None` if the database already matches the models.
+pub(crate) fn make_migration<'py>(
+    py: Python<'py>,
+    pool: AnyPool,
+    dialect: Dialect,
+    dir: &str,
+    models: Option<&Bound<'py, PyAny>>,
+    name: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let migrations = load_migrations(py, dir)?;
+    let models = autogen::models_to_compare(py, models)?;
+    let schema = DesiredSchema::from_models(py, &models, dialect)?;
+    let dir = dir.to_string();
+    let name = name.to_string();
+    future_into_py(py, async move {
+        let written = write_migration(&pool, dialect, &dir, &migrations, &schema, &name).await?;
+        Python::attach(|py| match written {
+            Some((version, name)) => Ok(migration_dict(py, version, &name)?.into_any().unbind()),
+            None => Ok(py.None()),
+        })
+    })
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,293 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString};
+use sqlx::any::{AnyConnectOptions, AnyQueryResult, AnyRow};
+use sqlx::{AnyPool, ConnectOptions};
+
+use crate::database::Database;
+use crate::error::FustOrmError;
+use crate::pool_registry::PoolRegistry;
+use crate::sqlite_hooks;
+
+/// The private SQLite database the canned rows of a mock are selected from.
+const BACKING_URL: &str = "sqlite::memory:";
+
+/// A result registered with `MockDatabase.on()`.
+struct Response {
+    /// The compiled regular expression the statements must match.
+    pattern: Py<PyAny>,
+    /// The `SELECT` producing the rows, if there are any.
+    rows_sql: Option<String>,
+    rows_affected: u64,
+    last_insert_id: Option<i64>,
+}
+
+/// The results registered with a mock and the statements it answered.
+struct MockState {
+    responses: Mutex<Vec<Response>>,
+    queries: Py<PyList>,
+}
+
+/// The mocks, by the connect options of their pools, which every clone of a
+/// pool shares.
+static MOCKS: PoolRegistry<Arc<MockState>> = PoolRegistry::new();
+
+/// The mock answering the statements run on `pool`, if it's one of a
+/// `MockDatabase`.
+fn lookup(pool: &AnyPool) -> Option<Arc<MockState>> {
+    MOCKS.get(pool)
+}
+
+/// Whether `pool` is the pool of a `MockDatabase`.
+pub(crate) fn is_mocked(pool: &AnyPool) -> bool {
+    lookup(pool).is_some()
+}
+
+impl MockState {
+    /// Records the statement and returns the response registered last whose
+    /// pattern it matches.
+    fn respond(
+        &self,
+        sql: &str,
+        params: &[String],
+    ) -> Result<(Option<String>, u64, Option<i64>), FustOrmError> {
+        Python::attach(|py| -> PyResult<_> {
+            let query = PyDict::new(py);
+            query.set_item("sql", sql)?;
+            query.set_item("params", params)?;
+            self.queries.bind(py).append(query)?;
+            let responses = self.responses.lock().unwrap_or_else(|e| e.into_inner());
+            for response in responses.iter().rev() {
+                if !response
+                    .pattern
+                    .call_method1(py, "search", (sql,))?
+                    .is_none(py)
+                {
+                    return Ok(Some((
+                        response.rows_sql.clone(),
+                        response.rows_affected,
+                        response.last_insert_id,
+                    )));
+                }
+            }
+            Ok(None)
+        })
+        .map_err(|e| FustOrmError::QueryError(e.to_string()))?
+        .ok_or_else(|| {
+            FustOrmError::QueryError(format!("No result was mocked for the statement: {}", sql))
+        })
+    }
+}
+
+/// Answers a statement fetching rows on the pool of a mock with the rows
+/// registered for it.
+pub(crate) async fn fetch(
+    pool: &AnyPool,
+    sql: &str,
+    params: &[String],
+) -> Option<Result<Vec<AnyRow>, FustOrmError>> {
+    let state = lookup(pool)?;
+    let rows_sql = match state.respond(sql, params) {
+        Ok((rows_sql, _, _)) => rows_sql,
+        Err(e) => return Some(Err(e)),
+    };
+    Some(match rows_sql {
+        Some(rows_sql) => sqlx::query(&rows_sql)
+            .fetch_all(pool)
+            .await
+            .map_err(FustOrmError::from),
+        None => Ok(Vec::new()),
+    })
+}
+
+/// Answers a statement not producing rows on the pool of a mock with the
+/// counts registered for it.
+pub(crate) fn execute(
+    pool: &AnyPool,
+    sql: &str,
+    params: &[String],
+) -> Option<Result<AnyQueryResult, FustOrmError>> {
+    let state = lookup(pool)?;
+    Some(
+        state
+            .respond(sql, params)
+            .map(|(_, rows_affected, last_insert_id)| AnyQueryResult {
+                rows_affected,
+                last_insert_id,
+            }),
+    )
+}
+
+/// Renders a value of a canned row as a SQLite literal: numbers as such,
+/// dicts and lists as JSON and other values as their text.
+fn literal(value: &Bound<PyAny>) -> PyResult<String> {
+    let py = value.py();
+    if value.is_none() {
+        Ok("NULL".to_string())
+    } else if let Ok(b) = value.downcast::<PyBool>() {
+        Ok((if b.is_true() { "1" } else { "0" }).to_string())
+    } else if value.is_instance_of::<PyInt>() {
+        Ok(value.str()?.to_string())
+    } else if let Ok(f) = value.downcast::<PyFloat>() {
+        let f = f.value();
+        Ok(if f.is_finite() {
+            format!("{:?}", f)
+        } else if f.is_nan() {
+            "NULL".to_string()
+        } else {
+            format!("{}9e999", if f < 0.0 { "-" } else { "" })
+        })
+    } else if let Ok(bytes) = value.downcast::<PyBytes>() {
+        let hex: String = bytes
+            .as_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Ok(format!("X'{}'", hex))
+    } else {
+        let text = if value.is_instance_of::<PyString>() {
+            value.str()?.to_string()
+        } else if value.is_instance_of::<PyDict>() || value.is_instance_of::<PyList>() {
+            py.import("json")?
+                .call_method1("dumps", (value,))?
+                .str()?
+                .to_string()
+        } else {
+            value.str()?.to_string()
+        };
+        Ok(format!("'{}'", text.replace('\'', "''")))
+    }
+}
+
+/// Renders canned rows, dicts of column names to values, as a `SELECT` of
+/// them; the columns are those of the first row.
+fn rows_sql(rows: &Bound<PyList>) -> PyResult<Option<String>> {
+    let Some(first) = rows.iter().next() else {
+        return Ok(None);
+    };
+    let columns: Vec<String> = first.downcast::<PyDict>()?.keys().extract()?;
+    let mut selects = Vec::with_capacity(rows.len());
+    for row in rows.iter() {
+        let row = row.downcast::<PyDict>()?;
+        let values = columns
+            .iter()
+            .map(|column| {
+                let value = match row.get_item(column)? {
+                    Some(value) => literal(&value)?,
+                    None => "NULL".to_string(),
+                };
+                Ok(format!("{} AS \"{}\"", value, column.replace('"', "\"\"")))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        selects.push(format!("SELECT {}", values.join(", ")));
+    }
+    Ok(Some(selects.join(" UNION ALL ")))
+}
+
+/// A `Database` answering its statements with canned results rather than
+/// running them, for unit tests of code using a database:
+///
+/// ```python
+/// db = MockDatabase()
+/// db.on(r"FROM user WHERE", [{"id": 1, "name": "Ann"}])
+/// db.on(r"^UPDATE user", rows_affected=1)
+/// ```
+///
+/// A statement gets the result registered last whose pattern matches it, and
+/// fails when none does. Its transactions don't begin any, and the
+/// statements of the methods running in a transaction of their own, such as
+/// bulk writes, can't be mocked.
+#[pyclass(extends = Database, module = "fust_orm")]
+pub struct MockDatabase {
+    state: Arc<MockState>,
+}
+
+#[pymethods]
+impl MockDatabase {
+    #[new]
+    fn new(py: Python) -> PyResult<(Self, Database)> {
+        let options = AnyConnectOptions::from_str(BACKING_URL)
+            .map_err(FustOrmError::connection)?
+            .disable_statement_logging();
+        let pool = {
+            let _runtime = pyo3_async_runtimes::tokio::get_runtime().enter();
+            sqlite_hooks::connect_sqlite_lazy(options).map_err(FustOrmError::connection)?
+        };
+        let state = Arc::new(MockState {
+            responses: Mutex::new(Vec::new()),
+            queries: PyList::empty(py).unbind(),
+        });
+        MOCKS.insert(&pool, state.clone());
+        Ok((MockDatabase { state }, Database::new(pool)))
+    }
+
+    /// Registers the result of the statements matching the regular
+    /// expression `pattern`: the rows `rows`, as dicts, for queries, and
+    /// `rows_affected` (the number of rows by default) and `last_insert_id`
+    /// for other statements.
+    #[pyo3(signature = (pattern, rows = None, *, rows_affected = None, last_insert_id = None))]
+    fn on(
+        &self,
+        py: Python,
+        pattern: &str,
+        rows: Option<&Bound<PyList>>,
+        rows_affected: Option<u64>,
+        last_insert_id: Option<i64>,
+    ) -> PyResult<()> {
+        let pattern = py
+            .import("re")?
+            .call_method1("compile", (pattern,))
+            .map_err(|e| {
+                FustOrmError::InvalidQueryArgument(format!(
+                    "Invalid pattern '{}': {}",
+                    pattern,
+                    e.value(py)
+                ))
+            })?;
+        let (rows_sql, count) = match rows {
+            Some(rows) => (rows_sql(rows)?, rows.len() as u64),
+            None => (None, 0),
+        };
+        self.state
+            .responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Response {
+                pattern: pattern.unbind(),
+                rows_sql,
+                rows_affected: rows_affected.unwrap_or(count),
+                last_insert_id,
+            });
+        Ok(())
+    }
+
+    /// The statements run so far, in order, as `{"sql": ..., "params": [...]}`
+    /// dicts, including those no result was mocked for.
+    #[getter]
+    fn queries(&self, py: Python) -> Py<PyList> {
+        self.state.queries.clone_ref(py)
+    }
+
+    /// Forgets the results registered and the statements run.
+    fn reset(&self, py: Python) -> PyResult<()> {
+        self.state
+            .responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.state.queries.bind(py).call_method0("clear")?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        let responses = self
+            .state
+            .responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len();
+        format!("<MockDatabase results={}>", responses)
+    }
+}
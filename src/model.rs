@@ -1,35 +1,686 @@
+use crate::bulk;
+use crate::cascade::{self, DeletePlan};
 use crate::column_field::ColumnField;
+use crate::context;
+use crate::custom_type;
+use crate::database::{Database, SharedTransaction, row_to_dict};
+use crate::decode::RowBatch;
+use crate::error::FustOrmError;
+use crate::query::{QueryBuilder, SqlParam, py_any_to_string};
+use crate::range::Range;
+use crate::relations;
+use crate::schema::{TypeKind, scalar_kind};
+use crate::scope::{model_query, scope_conditions};
+use crate::serialize::{json_to_string, model_to_dict, py_to_json};
+use crate::signals;
+use crate::trigger::Trigger;
+use crate::upsert;
 use heck::ToSnakeCase;
 use log::debug;
-use pyo3::types::{PyDict, PyType};
+use pyo3::exceptions::PyTypeError;
+use pyo3::types::{PyDict, PyList, PyType};
 use pyo3::{PyTypeInfo, prelude::*};
+use pyo3_async_runtimes::tokio::future_into_py;
+use std::sync::Arc;
+use tokio::sync::Mutex as AsyncMutex;
 
 /// A base class for user-defined models.
 ///
 /// When a new class inherits from `Model`, its `__init_subclass__` method
 /// is automatically called. This method inspects the subclass's annotations
 /// and sets up `ColumnField` descriptors for each annotated database column.
+/// Table-level options (table name, composite indexes, unique and check
+/// constraints) can be declared in an inner `class Meta:` block.
+///
+/// Instances of a model represent single rows. They are created either directly
+/// (`User(name="Alice")`) or by loading them from the database (`User.get(db, 1)`),
+/// and are written back with `save()`.
 #[pyclass(subclass)]
-pub struct Model;
+pub struct Model {
+    /// Whether this instance corresponds to a row that already exists in the database.
+    /// Decides between `INSERT` and `UPDATE` in `save()`.
+    pub(crate) persisted: bool,
+    /// Columns the instance was loaded without (see `QueryBuilder.defer()`).
+    /// They are skipped by `save()` until `load_deferred()` fetches them.
+    pub(crate) deferred: Vec<String>,
+}
+
+/// Maximum number of parameters bound to a single `in_bulk()` query.
+const IN_BULK_MAX_PARAMS: usize = 999;
+
+/// The column layout of a model class, as set up by `Model.__init_subclass__`.
+pub(crate) struct ModelInfo {
+    pub table_name: String,
+    pub columns: Vec<ColumnField>,
+    /// Whether the model maps onto a view (`Meta.view`) and is read-only.
+    pub is_view: bool,
+}
+
+impl ModelInfo {
+    /// Reads the table name and column descriptors from a model class.
+    pub fn from_class(cls: &Bound<PyType>) -> PyResult<Self> {
+        let py = cls.py();
+        let table_name = cls
+            .getattr(pyo3::intern!(py, "__table_name__"))?
+            .extract::<String>()?;
+        let column_names = cls
+            .getattr(pyo3::intern!(py, "__columns__"))?
+            .extract::<Vec<String>>()?;
+        let columns = column_names
+            .iter()
+            .map(|name| cls.getattr(name)?.extract::<ColumnField>())
+            .collect::<PyResult<Vec<_>>>()?;
+        let is_view = !cls.getattr(pyo3::intern!(py, "__view__"))?.is_none();
+        Ok(ModelInfo {
+            table_name,
+            columns,
+            is_view,
+        })
+    }
+
+    /// Fails for models mapped onto a view, which can't be written to.
+    pub fn check_writable(&self) -> PyResult<()> {
+        if self.is_view {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "'{}' is a view; its model is read-only.",
+                self.table_name
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Returns the primary key columns: one for simple keys, several for composite keys.
+    pub fn primary_key(&self) -> PyResult<Vec<&ColumnField>> {
+        let pk: Vec<&ColumnField> = self.columns.iter().filter(|c| c.primary_key).collect();
+        if pk.is_empty() {
+            return Err(FustOrmError::BuildError(format!(
+                "Model for table '{}' has no primary key. Declare one with `ColumnField(primary_key=True)`.",
+                self.table_name
+            ))
+            .into());
+        }
+        Ok(pk)
+    }
+
+    /// Renders the WHERE condition matching a primary key value and pushes its
+    /// parameters. Composite keys expect a tuple with one value per key column,
+    /// in declaration order.
+    pub fn pk_condition(
+        &self,
+        pk_value: &Bound<PyAny>,
+        params: &mut Vec<String>,
+    ) -> PyResult<String> {
+        let py = pk_value.py();
+        let pk = self.primary_key()?;
+        let values: Vec<Bound<PyAny>> = if pk.len() == 1 {
+            vec![pk_value.clone()]
+        } else {
+            pk_value
+                .try_iter()
+                .and_then(|it| it.collect::<PyResult<Vec<_>>>())
+                .ok()
+                .filter(|v| {
+                    !pk_value.is_instance_of::<pyo3::types::PyString>() && v.len() == pk.len()
+                })
+                .ok_or_else(|| {
+                    FustOrmError::InvalidQueryArgument(format!(
+                        "The primary key of '{}' is composite; expected a tuple of {} values ({}).",
+                        self.table_name,
+                        pk.len(),
+                        pk.iter()
+                            .map(|c| c.column_name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                })?
+        };
+
+        let mut conditions = Vec::with_capacity(pk.len());
+        for (column, value) in pk.iter().zip(values) {
+            let param = column_param(py, &value, column)?.ok_or_else(|| {
+                FustOrmError::InvalidQueryArgument(format!(
+                    "Primary key column '{}' of '{}' has no value.",
+                    column.column_name, self.table_name
+                ))
+            })?;
+            params.push(param);
+            conditions.push(format!("{} = ?", column.column_name));
+        }
+        Ok(conditions.join(" AND "))
+    }
+
+    /// Reads the primary key of an instance: the plain value for simple keys,
+    /// a tuple for composite keys.
+    pub fn instance_pk<'py>(&self, instance: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        let pk = self.primary_key()?;
+        if let [column] = pk.as_slice() {
+            return instance.getattr(&column.column_name);
+        }
+        let values = pk
+            .iter()
+            .map(|c| instance.getattr(&c.column_name))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(pyo3::types::PyTuple::new(instance.py(), values)?.into_any())
+    }
+
+    /// Describes a primary key value for error messages, e.g. `(tenant_id, id) = (1, 2)`.
+    pub fn describe_pk(&self, pk_value: &Bound<PyAny>) -> PyResult<String> {
+        let names: Vec<&str> = self
+            .primary_key()?
+            .iter()
+            .map(|c| c.column_name.as_str())
+            .collect();
+        Ok(match names.as_slice() {
+            [name] => format!("{} = {}", name, pk_value.repr()?),
+            _ => format!("({}) = {}", names.join(", "), pk_value.repr()?),
+        })
+    }
+
+    pub fn version_column(&self) -> Option<&ColumnField> {
+        self.columns.iter().find(|c| c.version)
+    }
+}
+
+/// Creates a model instance from a row dictionary, marking it as persisted.
+///
+/// For polymorphic models, the row's discriminator value selects the subclass
+/// to instantiate.
+pub(crate) fn hydrate<'py>(
+    cls: &Bound<'py, PyType>,
+    row: &Bound<'py, PyDict>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = cls.py();
+    let mut model = cls.clone();
+    let discriminator = cls.getattr(pyo3::intern!(py, "__discriminator__"))?;
+    if !discriminator.is_none()
+        && let Some(value) = row.get_item(&discriminator)?
+    {
+        let registry = cls.getattr(pyo3::intern!(py, "__polymorphic_map__"))?;
+        if let Some(subclass) = registry.downcast::<PyDict>()?.get_item(value)? {
+            let subclass = subclass.downcast_into::<PyType>()?;
+            if subclass.is_subclass(cls)? {
+                model = subclass;
+            }
+        }
+    }
+    let instance = model.call0()?;
+    for (key, value) in row.iter() {
+        let key = key.downcast::<pyo3::types::PyString>()?;
+        let value = match model
+            .getattr(key)
+            .ok()
+            .and_then(|field| field.downcast_into::<ColumnField>().ok())
+        {
+            Some(field) => field.borrow().py_value(value)?,
+            None => value,
+        };
+        instance.setattr(key, value)?;
+    }
+    // Columns missing from the row weren't selected; they are deferred rather
+    // than left at `None`, so that `save()` doesn't overwrite them.
+    let mut deferred = Vec::new();
+    for column in model
+        .getattr(pyo3::intern!(py, "__columns__"))?
+        .extract::<Vec<String>>()?
+    {
+        if !row.contains(&column)? {
+            instance.delattr(&column)?;
+            deferred.push(column);
+        }
+    }
+    let mut state = instance.downcast::<Model>()?.borrow_mut();
+    state.persisted = true;
+    state.deferred = deferred;
+    drop(state);
+    Ok(instance)
+}
+
+/// Resolves the `references` of a column declared on `cls`: a model class, or
+/// `"self"` for the model itself.
+fn model_reference(cls: &Bound<PyType>, target: &Bound<PyAny>) -> PyResult<Py<PyAny>> {
+    if target.extract::<String>().is_ok_and(|s| s == "self") {
+        return Ok(cls.clone().into_any().unbind());
+    }
+    match target.downcast::<PyType>() {
+        Ok(model) if model.is_subclass_of::<Model>()? => Ok(model.clone().into_any().unbind()),
+        _ => Err(FustOrmError::BuildError(format!(
+            "`references` of a column of '{}' must be a Model class or \"self\", got {}.",
+            cls.name()?,
+            target.repr()?
+        ))
+        .into()),
+    }
+}
+
+/// Returns the enum class of a `ColumnField[SomeEnum]` annotation, if any.
+fn enum_annotation(annotation: &Bound<PyAny>) -> PyResult<Option<Arc<Py<PyType>>>> {
+    let py = annotation.py();
+    let Ok(args) = annotation.getattr(pyo3::intern!(py, "__args__")) else {
+        return Ok(None);
+    };
+    let Some(arg) = args.try_iter()?.next().transpose()? else {
+        return Ok(None);
+    };
+    let Ok(arg) = arg.downcast_into::<PyType>() else {
+        return Ok(None);
+    };
+    let enum_base = py.import("enum")?.getattr("Enum")?;
+    if arg.is_subclass(enum_base.downcast::<PyType>()?)? {
+        Ok(Some(Arc::new(arg.unbind())))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Returns the type of a `ColumnField[...]` annotation, with `Optional[...]`
+/// or `... | None` unwrapped, if any.
+fn annotated_arg<'py>(annotation: &Bound<'py, PyAny>) -> PyResult<Option<Bound<'py, PyAny>>> {
+    let py = annotation.py();
+    let Ok(args) = annotation.getattr(pyo3::intern!(py, "__args__")) else {
+        return Ok(None);
+    };
+    let Some(arg) = args.try_iter()?.next().transpose()? else {
+        return Ok(None);
+    };
+    let typing = py.import("typing")?;
+    let origin = typing.call_method1("get_origin", (&arg,))?;
+    if !origin.is(typing.getattr("Union")?)
+        && !origin.is(py.import("types")?.getattr("UnionType")?)
+    {
+        return Ok(Some(arg));
+    }
+    let none_type = py.None().bind(py).get_type();
+    let mut members: Vec<Bound<PyAny>> = typing
+        .call_method1("get_args", (&arg,))?
+        .try_iter()?
+        .filter(|a| a.as_ref().map_or(true, |a| !a.is(&none_type)))
+        .collect::<PyResult<_>>()?;
+    Ok((members.len() == 1).then(|| members.remove(0)))
+}
+
+/// Returns the item type of a `ColumnField[list[int]]` annotation (or a list
+/// of another type with a column type), optional or not, if any.
+fn array_annotation(annotation: &Bound<PyAny>) -> PyResult<Option<Arc<Py<PyType>>>> {
+    let py = annotation.py();
+    let Some(arg) = annotated_arg(annotation)? else {
+        return Ok(None);
+    };
+    let typing = py.import("typing")?;
+    if !typing
+        .call_method1("get_origin", (&arg,))?
+        .is(py.get_type::<PyList>())
+    {
+        return Ok(None);
+    }
+    let Ok(item) = typing.call_method1("get_args", (&arg,))?.get_item(0) else {
+        return Ok(None);
+    };
+    match scalar_kind(&item)? {
+        Some(kind) if !matches!(kind, TypeKind::Json | TypeKind::Interval) => {
+            Ok(Some(Arc::new(item.downcast_into::<PyType>()?.unbind())))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the class of a `ColumnField[Address]` annotation, optional or not,
+/// if it was registered as a composite type with `register_type()`.
+fn composite_annotation(annotation: &Bound<PyAny>) -> PyResult<Option<Arc<Py<PyType>>>> {
+    match annotated_arg(annotation)?.map(|arg| arg.downcast_into::<PyType>()) {
+        Some(Ok(cls)) if custom_type::is_composite(&cls)? => Ok(Some(Arc::new(cls.unbind()))),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the type of the bounds of a `ColumnField[Range[int]]` annotation,
+/// optional or not, if any.
+fn range_annotation(annotation: &Bound<PyAny>) -> PyResult<Option<Arc<Py<PyType>>>> {
+    let py = annotation.py();
+    let Some(arg) = annotated_arg(annotation)? else {
+        return Ok(None);
+    };
+    let typing = py.import("typing")?;
+    if !typing
+        .call_method1("get_origin", (&arg,))?
+        .is(py.get_type::<Range>())
+    {
+        return Ok(None);
+    }
+    let item = typing.call_method1("get_args", (&arg,))?.get_item(0)?;
+    Ok(item
+        .downcast_into::<PyType>()
+        .ok()
+        .map(|t| Arc::new(t.unbind())))
+}
+
+/// Whether a `ColumnField[datetime.timedelta]` annotation, optional or not,
+/// is an interval column.
+fn interval_annotation(annotation: &Bound<PyAny>) -> PyResult<bool> {
+    let timedelta = annotation.py().import("datetime")?.getattr("timedelta")?;
+    Ok(annotated_arg(annotation)?.is_some_and(|arg| arg.is(&timedelta)))
+}
+
+/// Options accepted in a model's inner `class Meta:` block.
+const META_OPTIONS: &[&str] = &[
+    "table_name",
+    "indexes",
+    "unique_together",
+    "default_scope",
+    "discriminator",
+    "discriminator_value",
+    "view",
+    "triggers",
+    "checks",
+];
+
+/// Rejects unknown options in a `Meta` block, so typos don't go unnoticed.
+fn validate_meta(cls: &Bound<PyType>, meta: &Bound<PyAny>) -> PyResult<()> {
+    for key in meta.getattr("__dict__")?.try_iter()? {
+        let key = key?.extract::<String>()?;
+        if !key.starts_with("__") && !META_OPTIONS.contains(&key.as_str()) {
+            return Err(FustOrmError::BuildError(format!(
+                "Unknown Meta option '{}' on model '{}'. Supported options: {}.",
+                key,
+                cls.name()?,
+                META_OPTIONS.join(", ")
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Reads a `Meta` option holding groups of column names, such as `indexes`.
+///
+/// Each entry is either a single column name or a sequence of column names,
+/// and every name must refer to a declared column.
+fn meta_column_groups(
+    cls: &Bound<PyType>,
+    meta: Option<&Bound<PyAny>>,
+    option: &str,
+    columns: &[String],
+) -> PyResult<Vec<Vec<String>>> {
+    let Some(value) = meta.map(|m| m.getattr(option)).and_then(Result::ok) else {
+        return Ok(Vec::new());
+    };
+    let mut groups = Vec::new();
+    for entry in value.try_iter()? {
+        let entry = entry?;
+        let group = if let Ok(name) = entry.extract::<String>() {
+            vec![name]
+        } else {
+            entry.extract::<Vec<String>>()?
+        };
+        if group.is_empty() {
+            return Err(FustOrmError::BuildError(format!(
+                "Empty column list in `Meta.{}` of model '{}'.",
+                option,
+                cls.name()?
+            ))
+            .into());
+        }
+        if let Some(unknown) = group.iter().find(|name| !columns.contains(name)) {
+            return Err(FustOrmError::BuildError(format!(
+                "`Meta.{}` of model '{}' refers to unknown column '{}'.",
+                option,
+                cls.name()?,
+                unknown
+            ))
+            .into());
+        }
+        groups.push(group);
+    }
+    Ok(groups)
+}
+
+/// Sets up single-table inheritance for a model class.
+///
+/// The base model of a hierarchy names its discriminator column in
+/// `Meta.discriminator`; every class in the hierarchy (including the base) is
+/// identified by its `Meta.discriminator_value`, defaulting to the snake_case
+/// class name. Subclasses share the base's table, and a registry
+/// (`__polymorphic_map__`, shared by the whole hierarchy) maps discriminator
+/// values back to classes when rows are loaded.
+fn setup_inheritance(
+    cls: &Bound<PyType>,
+    meta: Option<&Bound<PyAny>>,
+    column_names: &[String],
+) -> PyResult<()> {
+    let py = cls.py();
+    let declared = meta.and_then(|m| m.getattr("discriminator").ok());
+    let inherited = cls
+        .getattr(pyo3::intern!(py, "__discriminator__"))
+        .ok()
+        .filter(|d| !d.is_none());
+
+    match (declared, inherited) {
+        (Some(_), Some(_)) => {
+            return Err(FustOrmError::BuildError(format!(
+                "Model '{}' redeclares `Meta.discriminator`; only the base model of a hierarchy declares it.",
+                cls.name()?
+            ))
+            .into());
+        }
+        (Some(declared), None) => {
+            let column = declared.extract::<String>()?;
+            if !column_names.contains(&column) {
+                return Err(FustOrmError::BuildError(format!(
+                    "`Meta.discriminator` of model '{}' refers to unknown column '{}'.",
+                    cls.name()?,
+                    column
+                ))
+                .into());
+            }
+            cls.setattr(pyo3::intern!(py, "__discriminator__"), column)?;
+            cls.setattr(pyo3::intern!(py, "__polymorphic_map__"), PyDict::new(py))?;
+        }
+        (None, Some(_)) => {}
+        (None, None) => {
+            if meta.is_some_and(|m| m.hasattr("discriminator_value").unwrap_or(false)) {
+                return Err(FustOrmError::BuildError(format!(
+                    "Model '{}' sets `Meta.discriminator_value` but no base model declares `Meta.discriminator`.",
+                    cls.name()?
+                ))
+                .into());
+            }
+            cls.setattr(pyo3::intern!(py, "__discriminator__"), py.None())?;
+            return Ok(());
+        }
+    }
+
+    let value = match meta.and_then(|m| m.getattr("discriminator_value").ok()) {
+        Some(value) => value,
+        None => cls
+            .name()?
+            .to_string()
+            .to_snake_case()
+            .into_pyobject(py)?
+            .into_any(),
+    };
+    let registry = cls.getattr(pyo3::intern!(py, "__polymorphic_map__"))?;
+    let registry = registry.downcast::<PyDict>()?;
+    if let Some(existing) = registry.get_item(&value)? {
+        return Err(FustOrmError::BuildError(format!(
+            "Discriminator value {} of model '{}' is already used by '{}'.",
+            value.repr()?,
+            cls.name()?,
+            existing.downcast::<PyType>()?.name()?
+        ))
+        .into());
+    }
+    registry.set_item(&value, cls)?;
+    cls.setattr(pyo3::intern!(py, "__discriminator_value__"), value)?;
+    Ok(())
+}
+
+/// Returns the discriminator column and the values a query on `cls` has to match:
+/// the values of `cls` and all its subclasses. `None` means no filter is needed,
+/// because the model isn't polymorphic or `cls` is the base of its hierarchy.
+pub(crate) fn discriminator_filter<'py>(
+    cls: &Bound<'py, PyType>,
+) -> PyResult<Option<(String, Vec<Bound<'py, PyAny>>)>> {
+    let py = cls.py();
+    let column = cls.getattr(pyo3::intern!(py, "__discriminator__"))?;
+    if column.is_none() {
+        return Ok(None);
+    }
+    let registry = cls.getattr(pyo3::intern!(py, "__polymorphic_map__"))?;
+    let registry = registry.downcast::<PyDict>()?;
+    let mut values = Vec::new();
+    for (value, model) in registry.iter() {
+        if model.downcast::<PyType>()?.is_subclass(cls)? {
+            values.push(value);
+        }
+    }
+    if values.len() == registry.len() {
+        return Ok(None);
+    }
+    Ok(Some((column.extract()?, values)))
+}
+
+/// Converts a single attribute value to a bindable parameter.
+/// `None` is returned for Python `None`, which callers render as a `NULL` literal.
+pub(crate) fn column_param(
+    py: Python,
+    value: &Bound<PyAny>,
+    column: &ColumnField,
+) -> PyResult<Option<String>> {
+    if value.is_none() {
+        return Ok(None);
+    }
+    let value = column.db_value(value)?;
+    match py_any_to_string(py, &value.unbind(), &column.column_name)? {
+        SqlParam::Single(s) => Ok(Some(s)),
+        SqlParam::List(_) => Err(FustOrmError::BuildError(format!(
+            "Column '{}' can't be saved from a list value.",
+            column.column_name
+        ))
+        .into()),
+    }
+}
+
+/// What `save()` has to do with the instance once the statement has run.
+enum SaveKind {
+    Insert {
+        /// Set when the primary key was left empty and should be filled from the
+        /// database-generated id.
+        generated_pk: Option<String>,
+        /// Whether the generated id is read back with `RETURNING` rather than
+        /// from the driver's last insert id (which only MySQL reports).
+        returning: bool,
+        /// Set when the version column was empty and starts counting at 1.
+        initial_version: Option<String>,
+    },
+    Update {
+        /// The version column and the version the instance was loaded with.
+        version: Option<(String, i64)>,
+        pk_repr: String,
+    },
+}
 
 #[pymethods]
 impl Model {
+    #[new]
+    #[pyo3(signature = (*_args, **_kwargs))]
+    fn new(_args: &Bound<'_, pyo3::types::PyTuple>, _kwargs: Option<&Bound<'_, PyDict>>) -> Self {
+        Model {
+            persisted: false,
+            deferred: Vec::new(),
+        }
+    }
+
+    /// Initializes a new, not yet persisted row.
+    ///
+    /// Every column starts as `None`; keyword arguments set individual columns.
+    #[pyo3(signature = (**kwargs))]
+    fn __init__(slf: &Bound<'_, Self>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<()> {
+        let py = slf.py();
+        let cls = slf.get_type();
+        let columns = cls
+            .getattr(pyo3::intern!(py, "__columns__"))?
+            .extract::<Vec<String>>()?;
+        for column in &columns {
+            slf.setattr(column, py.None())?;
+        }
+        if let Ok(value) = cls.getattr(pyo3::intern!(py, "__discriminator_value__")) {
+            let column = cls.getattr(pyo3::intern!(py, "__discriminator__"))?;
+            slf.setattr(column.downcast::<pyo3::types::PyString>()?, value)?;
+        }
+        if let Some(kwargs) = kwargs {
+            for (key, value) in kwargs.iter() {
+                let name = key.extract::<String>()?;
+                if !columns.contains(&name) {
+                    return Err(PyTypeError::new_err(format!(
+                        "'{}' is not a column of model '{}'",
+                        name,
+                        cls.name()?
+                    )));
+                }
+                slf.setattr(name, value)?;
+            }
+        }
+        Ok(())
+    }
+
     /// This special class method is called when a class inherits from `Model`.
     ///
     /// It performs the following setup steps:
-    /// 1. Determines the database table name, either from a `__table_name__`
-    ///    attribute or by converting the class name to snake_case.
+    /// 1. Determines the database table name, either from `Meta.table_name`,
+    ///    a `__table_name__` attribute or by converting the class name to snake_case.
     /// 2. Iterates through the class's `__annotations__`.
     /// 3. For each annotation that is a `ColumnField` generic (e.g., `ColumnField[int]`),
-    ///    it creates an instance of the `ColumnField` descriptor.
+    ///    it creates an instance of the `ColumnField` descriptor, keeping the metadata
+    ///    of an explicitly assigned `ColumnField(...)` if there is one.
     /// 4. This descriptor instance is then assigned as a class attribute, replacing
     ///    the original annotation. This allows for deferred query operations like `MyModel.id == 5`.
+    /// 5. The column names are stored in `__columns__`, the primary key columns in
+    ///    `__primary_key__`. If no column is marked as (part of) the primary key, a
+    ///    column named `id` is used.
+    /// 6. Composite indexes and unique constraints from the optional `Meta` block are
+    ///    validated and stored in `__indexes__` and `__unique_together__`.
+    /// 7. `Meta.default_scope`, a callable receiving the model class, is evaluated
+    ///    into the conditions stored in `__default_scope__`.
+    /// 8. Columns declared with `references` are registered in the referenced
+    ///    model's `__referenced_by__`, which `delete()` consults.
+    /// 9. `Meta.view`, the `SELECT` of a view named after the table, is stored
+    ///    in `__view__`. Such models are read-only.
+    /// 10. `Meta.triggers`, a list of `Trigger`, is stored in `__triggers__`
+    ///     for `make_migration()` to create.
+    /// 11. `Meta.checks`, a dict of check constraint names to SQL conditions,
+    ///     is stored in `__checks__` as `(name, condition)` pairs.
+    ///
+    /// Columns of a parent model are inherited. With `Meta.discriminator`, the
+    /// subclasses of a model share its table (single-table inheritance).
     #[classmethod]
     fn __init_subclass__(cls: &Bound<PyType>) -> PyResult<()> {
         let py = cls.py();
         debug!("Initializing model subclass: {}", cls.name()?);
 
-        let table_name = if let Ok(name) = cls.getattr(pyo3::intern!(py, "__table_name__")) {
+        let class_dict = cls.getattr(pyo3::intern!(py, "__dict__"))?;
+        let meta = class_dict.get_item("Meta").ok();
+        if let Some(meta) = &meta {
+            validate_meta(cls, meta)?;
+        }
+        let meta_table_name = match &meta {
+            Some(meta) if meta.hasattr("table_name")? => {
+                if class_dict.get_item("__table_name__").is_ok() {
+                    return Err(FustOrmError::BuildError(format!(
+                        "Model '{}' sets both `__table_name__` and `Meta.table_name`.",
+                        cls.name()?
+                    ))
+                    .into());
+                }
+                Some(meta.getattr("table_name")?.extract::<String>()?)
+            }
+            _ => None,
+        };
+
+        let table_name = if let Some(name) = meta_table_name {
+            debug!("Found `Meta.table_name`: '{}'", &name);
+            cls.setattr(pyo3::intern!(py, "__table_name__"), &name)?;
+            name
+        } else if let Ok(name) = cls.getattr(pyo3::intern!(py, "__table_name__")) {
             let table_name_str = name.extract::<String>()?;
             debug!("Found explicit `__table_name__`: '{}'", &table_name_str);
             table_name_str
@@ -52,6 +703,23 @@ impl Model {
             }
         };
 
+        // Columns inherited from a parent model come first, so that subclasses
+        // (e.g. in single-table inheritance) extend the parent's layout.
+        let mut columns = match cls.getattr(pyo3::intern!(py, "__columns__")) {
+            Ok(names) => names
+                .extract::<Vec<String>>()?
+                .iter()
+                .map(|name| {
+                    let inherited = cls.getattr(name)?.extract::<ColumnField>()?;
+                    Ok(ColumnField {
+                        table_name: table_name.clone(),
+                        ..inherited
+                    })
+                })
+                .collect::<PyResult<Vec<_>>>()?,
+            Err(_) => Vec::new(),
+        };
+        let mut own_columns = Vec::new();
         for (key, value) in annotations.iter() {
             let column_name = key.extract::<String>()?;
             let type_repr = value.to_string();
@@ -61,17 +729,789 @@ impl Model {
                     &column_name,
                     cls.name()?
                 );
-                let column_field = Py::new(
-                    py,
-                    ColumnField {
-                        table_name: table_name.clone(),
-                        column_name: column_name.clone(),
-                    },
+                let declared = class_dict
+                    .get_item(&column_name)
+                    .ok()
+                    .and_then(|field| field.extract::<ColumnField>().ok())
+                    .unwrap_or_default();
+                let references = match &declared.references {
+                    Some(target) => Some(Arc::new(model_reference(cls, target.bind(py))?)),
+                    None => None,
+                };
+                own_columns.push(column_name.clone());
+                let column = ColumnField {
+                    table_name: table_name.clone(),
+                    column_name,
+                    enum_type: enum_annotation(&value)?.or(declared.enum_type.clone()),
+                    array_type: array_annotation(&value)?.or(declared.array_type.clone()),
+                    composite_type: composite_annotation(&value)?
+                        .or(declared.composite_type.clone()),
+                    range_type: range_annotation(&value)?.or(declared.range_type.clone()),
+                    interval: interval_annotation(&value)? || declared.interval,
+                    references,
+                    ..declared
+                };
+                match columns
+                    .iter_mut()
+                    .find(|c| c.column_name == column.column_name)
+                {
+                    Some(existing) => *existing = column,
+                    None => columns.push(column),
+                }
+            }
+        }
+
+        if !columns.iter().any(|c| c.primary_key)
+            && let Some(id) = columns.iter_mut().find(|c| c.column_name == "id")
+        {
+            id.primary_key = true;
+        }
+        if columns.iter().any(|c| c.column_name == "pk") {
+            return Err(FustOrmError::BuildError(format!(
+                "Model '{}' can't have a column named 'pk'; it is reserved for the primary key accessor.",
+                cls.name()?
+            ))
+            .into());
+        }
+        if columns.iter().filter(|c| c.version).count() > 1 {
+            return Err(FustOrmError::BuildError(format!(
+                "Model '{}' declares more than one version column.",
+                cls.name()?
+            ))
+            .into());
+        }
+
+        let column_names: Vec<String> = columns.iter().map(|c| c.column_name.clone()).collect();
+        let pk_names: Vec<String> = columns
+            .iter()
+            .filter(|c| c.primary_key)
+            .map(|c| c.column_name.clone())
+            .collect();
+        for column in columns {
+            let name = column.column_name.clone();
+            cls.setattr(name, Py::new(py, column)?)?;
+        }
+        for option in ["indexes", "unique_together"] {
+            let groups = meta_column_groups(cls, meta.as_ref(), option, &column_names)?;
+            let groups = groups
+                .into_iter()
+                .map(|group| pyo3::types::PyTuple::new(py, group))
+                .collect::<PyResult<Vec<_>>>()?;
+            cls.setattr(
+                format!("__{}__", option),
+                pyo3::types::PyTuple::new(py, groups)?,
+            )?;
+        }
+        cls.setattr(
+            pyo3::intern!(py, "__primary_key__"),
+            pyo3::types::PyTuple::new(py, pk_names)?,
+        )?;
+        setup_inheritance(cls, meta.as_ref(), &column_names)?;
+        cls.setattr(
+            pyo3::intern!(py, "__columns__"),
+            pyo3::types::PyTuple::new(py, column_names)?,
+        )?;
+        cls.setattr(pyo3::intern!(py, "__referenced_by__"), PyList::empty(py))?;
+        let view = match meta.as_ref().map(|m| m.getattr("view")) {
+            Some(Ok(view)) => match view.extract::<String>() {
+                Ok(sql) => Some(sql),
+                Err(_) => {
+                    return Err(FustOrmError::BuildError(format!(
+                        "`Meta.view` of model '{}' must be the SQL of the view's SELECT.",
+                        cls.name()?
+                    ))
+                    .into());
+                }
+            },
+            _ => None,
+        };
+        // Rows of a view can't be deleted, so they don't hold on to the rows
+        // they reference.
+        let is_view = view.is_some();
+        cls.setattr(pyo3::intern!(py, "__view__"), view)?;
+        if !is_view {
+            cascade::register_references(cls, &own_columns)?;
+        }
+        let triggers = match meta.as_ref().map(|m| m.getattr("triggers")) {
+            Some(Ok(triggers)) => {
+                let invalid = || {
+                    FustOrmError::BuildError(format!(
+                        "`Meta.triggers` of model '{}' must be a list of Trigger.",
+                        cls.name().map(|n| n.to_string()).unwrap_or_default()
+                    ))
+                };
+                if is_view {
+                    return Err(FustOrmError::BuildError(format!(
+                        "Model '{}' maps onto a view, which can't have `Meta.triggers`.",
+                        cls.name()?
+                    ))
+                    .into());
+                }
+                let triggers = triggers
+                    .try_iter()
+                    .map_err(|_| invalid())?
+                    .map(|t| t?.extract::<Trigger>().map_err(|_| invalid().into()))
+                    .collect::<PyResult<Vec<_>>>()?;
+                Some(pyo3::types::PyTuple::new(py, triggers)?)
+            }
+            _ => None,
+        };
+        cls.setattr(pyo3::intern!(py, "__triggers__"), triggers)?;
+        let checks = match meta.as_ref().map(|m| m.getattr("checks")) {
+            Some(Ok(checks)) => {
+                if is_view {
+                    return Err(FustOrmError::BuildError(format!(
+                        "Model '{}' maps onto a view, which can't have `Meta.checks`.",
+                        cls.name()?
+                    ))
+                    .into());
+                }
+                let checks = checks
+                    .downcast::<PyDict>()
+                    .ok()
+                    .and_then(|checks| checks.items().extract::<Vec<(String, String)>>().ok())
+                    .ok_or_else(|| {
+                        FustOrmError::BuildError(format!(
+                            "`Meta.checks` of model '{}' must map constraint names to SQL conditions.",
+                            cls.name().map(|n| n.to_string()).unwrap_or_default()
+                        ))
+                    })?;
+                Some(pyo3::types::PyTuple::new(py, checks)?)
+            }
+            _ => None,
+        };
+        cls.setattr(pyo3::intern!(py, "__checks__"), checks)?;
+
+        // The default scope is evaluated once the column descriptors are in place,
+        // since it is typically written in terms of them (`model.is_active == True`).
+        match meta.as_ref().map(|m| m.getattr("default_scope")) {
+            Some(Ok(default_scope)) => {
+                let result = default_scope.call1((cls,))?;
+                let conditions = scope_conditions(&result)?;
+                cls.setattr(
+                    pyo3::intern!(py, "__default_scope__"),
+                    pyo3::types::PyTuple::new(py, conditions)?,
+                )?;
+            }
+            _ if !cls.hasattr(pyo3::intern!(py, "__default_scope__"))? => {
+                cls.setattr(
+                    pyo3::intern!(py, "__default_scope__"),
+                    pyo3::types::PyTuple::empty(py),
                 )?;
-                cls.setattr(column_name, column_field)?;
             }
+            _ => {}
         }
 
         Ok(())
     }
+
+    /// Starts a query on the model: `select(cls, *default_scope, *clauses)`.
+    ///
+    /// This is the model-level entry point that honors `Meta.default_scope`;
+    /// named scopes declared with `@scope` build on it as well.
+    #[classmethod]
+    #[pyo3(signature = (*clauses))]
+    fn query(cls: &Bound<PyType>, clauses: &Bound<pyo3::types::PyTuple>) -> PyResult<QueryBuilder> {
+        model_query(cls, &clauses.iter().collect::<Vec<_>>(), true)
+    }
+
+    /// Like `query()`, but without the model's default scope.
+    #[classmethod]
+    #[pyo3(signature = (*clauses))]
+    fn unscoped(
+        cls: &Bound<PyType>,
+        clauses: &Bound<pyo3::types::PyTuple>,
+    ) -> PyResult<QueryBuilder> {
+        model_query(cls, &clauses.iter().collect::<Vec<_>>(), false)
+    }
+
+    /// Loads a single row by its primary key.
+    ///
+    /// For composite primary keys, `pk` is a tuple with one value per key column.
+    /// Returns an awaitable resolving to a model instance, or `None` if no row matches.
+    #[classmethod]
+    fn get<'py>(
+        cls: &Bound<'py, PyType>,
+        db: &Database,
+        pk: Py<PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = cls.py();
+        let info = ModelInfo::from_class(cls)?;
+        let mut params = Vec::new();
+        let condition = info.pk_condition(pk.bind(py), &mut params)?;
+        let mut sql = format!("SELECT * FROM {} WHERE {}", info.table_name, condition);
+        if let Some((column, values)) = discriminator_filter(cls)? {
+            let placeholders = vec!["?"; values.len()].join(", ");
+            sql.push_str(&format!(" AND {} IN ({})", column, placeholders));
+            for value in &values {
+                if let SqlParam::Single(s) = py_any_to_string(py, &value.clone().unbind(), &column)?
+                {
+                    params.push(s);
+                }
+            }
+        }
+        let db = context::resolve(py, Some(db))?;
+        if let Some(tenant) = &db.tenant
+            && let Some((column, param)) = tenant.column_of(py, &info)?
+        {
+            sql.push_str(&format!(" AND {} = ?", column.column_name));
+            params.push(param);
+        }
+        let cls = cls.clone().unbind();
+
+        future_into_py(py, async move {
+            let rows = db.fetch_rows(&sql, params).await?;
+            Python::attach(|py| -> PyResult<Py<PyAny>> {
+                match rows.first() {
+                    Some(row) => {
+                        let dict = row_to_dict(py, row)?;
+                        Ok(hydrate(cls.bind(py), &dict)?.unbind())
+                    }
+                    None => Ok(py.None()),
+                }
+            })
+        })
+    }
+
+    /// Loads the rows with the given primary keys into a dict keyed by primary key.
+    ///
+    /// Keys are looked up with `IN` queries of up to 999 parameters; keys without
+    /// a row are left out of the result. Only models with a single-column
+    /// primary key are supported.
+    #[classmethod]
+    fn in_bulk<'py>(
+        cls: &Bound<'py, PyType>,
+        db: &Database,
+        ids: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = cls.py();
+        let info = ModelInfo::from_class(cls)?;
+        let pk = match info.primary_key()?.as_slice() {
+            [pk] => (*pk).clone(),
+            _ => {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "in_bulk() requires a single-column primary key, but '{}' has a composite one.",
+                    info.table_name
+                ))
+                .into());
+            }
+        };
+        let mut keys = Vec::new();
+        for id in ids.try_iter()? {
+            keys.extend(column_param(py, &id?, &pk)?);
+        }
+        keys.sort();
+        keys.dedup();
+
+        let mut filter = String::new();
+        let mut filter_params = Vec::new();
+        if let Some((column, values)) = discriminator_filter(cls)? {
+            let field = info
+                .columns
+                .iter()
+                .find(|c| c.column_name == column)
+                .expect("discriminator is a column");
+            filter = format!(
+                " AND {} IN ({})",
+                column,
+                vec!["?"; values.len()].join(", ")
+            );
+            for value in &values {
+                filter_params.extend(column_param(py, value, field)?);
+            }
+        }
+        let db = context::resolve(py, Some(db))?;
+        if let Some(tenant) = &db.tenant
+            && let Some((column, param)) = tenant.column_of(py, &info)?
+        {
+            filter.push_str(&format!(" AND {} = ?", column.column_name));
+            filter_params.push(param);
+        }
+        let queries = keys
+            .chunks(IN_BULK_MAX_PARAMS - filter_params.len())
+            .map(|chunk| {
+                let sql = format!(
+                    "SELECT * FROM {} WHERE {} IN ({}){}",
+                    info.table_name,
+                    pk.column_name,
+                    vec!["?"; chunk.len()].join(", "),
+                    filter
+                );
+                let mut params = chunk.to_vec();
+                params.extend(filter_params.iter().cloned());
+                (sql, params)
+            })
+            .collect::<Vec<_>>();
+        let cls = cls.clone().unbind();
+
+        future_into_py(py, async move {
+            let mut rows = Vec::new();
+            for (sql, params) in queries {
+                rows.extend(db.fetch_rows(&sql, params).await?);
+            }
+            let batch = RowBatch::decode(&rows)?;
+            Python::attach(|py| -> PyResult<Py<PyDict>> {
+                let result = PyDict::new(py);
+                for row in batch.to_dicts(py)? {
+                    let instance = hydrate(cls.bind(py), &row)?;
+                    result.set_item(instance.getattr(&pk.column_name)?, instance)?;
+                }
+                Ok(result.unbind())
+            })
+        })
+    }
+
+    /// Inserts many new instances at once.
+    ///
+    /// Rows are written with multi-row `INSERT` statements of up to `batch_size`
+    /// rows inside a single transaction, so either all objects are inserted or
+    /// none. Generated primary keys are assigned to the objects, except on MySQL.
+    /// Returns an awaitable resolving to the list of objects.
+    #[classmethod]
+    #[pyo3(signature = (db, objects, *, batch_size = None))]
+    fn bulk_create<'py>(
+        cls: &Bound<'py, PyType>,
+        db: &Database,
+        objects: &Bound<'py, PyAny>,
+        batch_size: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        bulk::bulk_create(cls, db, objects, batch_size)
+    }
+
+    /// Updates the given `fields` of many saved instances at once.
+    ///
+    /// Rows are written with multi-row `UPDATE` statements of up to `batch_size`
+    /// rows inside a single transaction. Versioned models are checked and bumped
+    /// like in `save()`; if any row is stale, nothing is updated and
+    /// `StaleDataError` is raised. Returns an awaitable resolving to the number
+    /// of updated rows.
+    #[classmethod]
+    #[pyo3(signature = (db, objects, fields, *, batch_size = None))]
+    fn bulk_update<'py>(
+        cls: &Bound<'py, PyType>,
+        db: &Database,
+        objects: &Bound<'py, PyAny>,
+        fields: Vec<String>,
+        batch_size: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        bulk::bulk_update(cls, db, objects, fields, batch_size)
+    }
+
+    /// Returns the row matching the `lookup` columns, creating it from the
+    /// lookup and `defaults` if it doesn't exist.
+    ///
+    /// Runs in a transaction and retries the lookup if a concurrent insert of
+    /// the same row wins (detected by a unique constraint violation, so the
+    /// lookup columns should be unique). Returns an awaitable resolving to
+    /// `(instance, created)`.
+    #[classmethod]
+    #[pyo3(signature = (db, defaults = None, **lookup))]
+    fn get_or_create<'py>(
+        cls: &Bound<'py, PyType>,
+        db: &Database,
+        defaults: Option<&Bound<'py, PyDict>>,
+        lookup: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        upsert::lookup_or_insert(cls, db, lookup, defaults, false)
+    }
+
+    /// Like `get_or_create()`, but an existing row is updated with `defaults`.
+    #[classmethod]
+    #[pyo3(signature = (db, defaults = None, **lookup))]
+    fn update_or_create<'py>(
+        cls: &Bound<'py, PyType>,
+        db: &Database,
+        defaults: Option<&Bound<'py, PyDict>>,
+        lookup: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        upsert::lookup_or_insert(cls, db, lookup, defaults, true)
+    }
+
+    /// Loads the many-to-many relations `names` of all `instances` with one
+    /// query per relation, so that iterating over `instance.<name>` needs no
+    /// further queries.
+    #[classmethod]
+    #[pyo3(signature = (db, instances, *names))]
+    fn prefetch_related<'py>(
+        cls: &Bound<'py, PyType>,
+        db: &Database,
+        instances: &Bound<'py, PyAny>,
+        names: &Bound<'py, pyo3::types::PyTuple>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        relations::prefetch_related(cls, db, instances, names)
+    }
+
+    /// The primary key of this instance: the column value for simple keys, or a
+    /// tuple of values for composite keys.
+    #[getter]
+    fn pk<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        ModelInfo::from_class(&slf.get_type())?.instance_pk(slf.as_any())
+    }
+
+    /// Converts the instance into a dictionary of column values.
+    ///
+    /// `include` restricts the result to the given attributes and may name
+    /// attributes holding related models, which are converted recursively.
+    /// `exclude` drops attributes from the result.
+    #[pyo3(signature = (*, include = None, exclude = None))]
+    fn to_dict<'py>(
+        slf: &Bound<'py, Self>,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        model_to_dict(slf.as_any(), include.as_deref(), exclude.as_deref())
+    }
+
+    /// Serializes the instance to a JSON string.
+    ///
+    /// Takes the same `include`/`exclude` arguments as `to_dict()`. Dates and
+    /// datetimes are written as ISO 8601 strings, UUIDs and decimals as strings,
+    /// enum members as their value and bytes as base64.
+    #[pyo3(signature = (*, include = None, exclude = None, indent = None))]
+    fn to_json(
+        slf: &Bound<'_, Self>,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        indent: Option<usize>,
+    ) -> PyResult<String> {
+        let dict = model_to_dict(slf.as_any(), include.as_deref(), exclude.as_deref())?;
+        json_to_string(&py_to_json(dict.as_any())?, indent)
+    }
+
+    /// Writes the instance to the database.
+    ///
+    /// New instances are inserted; instances loaded from the database (or saved
+    /// before) are updated by primary key. If the model has a version column, the
+    /// update only matches the version the instance was loaded with and increments
+    /// it, raising `StaleDataError` if another writer got there first.
+    ///
+    /// Sends `pre_save` before the column values are read (so handlers may still
+    /// change them) and `post_save` once the row is written.
+    #[pyo3(signature = (db = None))]
+    fn save<'py>(
+        slf: &Bound<'py, Self>,
+        db: Option<PyRef<'py, Database>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        let db = context::resolve(py, db.as_deref())?;
+        let info = ModelInfo::from_class(&slf.get_type())?;
+        info.check_writable()?;
+        let single_pk = info.primary_key()?.len() == 1;
+        let version = info.version_column();
+        let persisted = slf.borrow().persisted;
+        signals::send_save(signals::pre_save(py)?, slf.as_any(), !persisted)?;
+
+        let mut params = Vec::new();
+        let (sql, kind) = if !persisted {
+            let mut names = Vec::new();
+            let mut values = Vec::new();
+            let mut generated_pk = None;
+            let mut initial_version = None;
+            for column in info.columns.iter().filter(|c| c.generated.is_none()) {
+                let value = slf.getattr(&column.column_name)?;
+                if column.primary_key && single_pk && value.is_none() {
+                    generated_pk = Some(column.column_name.clone());
+                    continue;
+                }
+                names.push(column.column_name.as_str());
+                if column.version && value.is_none() {
+                    initial_version = Some(column.column_name.clone());
+                    values.push("1");
+                    continue;
+                }
+                match column_param(py, &value, column)? {
+                    Some(param) => {
+                        params.push(param);
+                        values.push("?");
+                    }
+                    None => values.push("NULL"),
+                }
+            }
+            let mut sql = if names.is_empty() {
+                format!("INSERT INTO {} DEFAULT VALUES", info.table_name)
+            } else {
+                format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    info.table_name,
+                    names.join(", "),
+                    values.join(", ")
+                )
+            };
+            let returning = generated_pk.is_some() && !db.is_mysql();
+            if let Some(column) = generated_pk.as_ref().filter(|_| returning) {
+                sql.push_str(&format!(" RETURNING {}", column));
+            }
+            (
+                sql,
+                SaveKind::Insert {
+                    generated_pk,
+                    returning,
+                    initial_version,
+                },
+            )
+        } else {
+            let mut assignments = Vec::new();
+            let deferred = slf.borrow().deferred.clone();
+            for column in info.columns.iter().filter(|c| {
+                !c.primary_key
+                    && !c.version
+                    && c.generated.is_none()
+                    && !deferred.contains(&c.column_name)
+            }) {
+                let value = slf.getattr(&column.column_name)?;
+                match column_param(py, &value, column)? {
+                    Some(param) => {
+                        params.push(param);
+                        assignments.push(format!("{} = ?", column.column_name));
+                    }
+                    None => assignments.push(format!("{} = NULL", column.column_name)),
+                }
+            }
+
+            let pk_value = info.instance_pk(slf.as_any())?;
+            let pk_repr = info.describe_pk(&pk_value)?;
+            let mut where_clause = info.pk_condition(&pk_value, &mut params)?;
+
+            let version = match version {
+                Some(column) => {
+                    let current = slf
+                        .getattr(&column.column_name)?
+                        .extract::<Option<i64>>()?
+                        .ok_or_else(|| {
+                            FustOrmError::BuildError(format!(
+                                "Version column '{}' of '{}' is NULL.",
+                                column.column_name, info.table_name
+                            ))
+                        })?;
+                    assignments.push(format!("{0} = {0} + 1", column.column_name));
+                    params.push(current.to_string());
+                    where_clause.push_str(&format!(" AND {} = ?", column.column_name));
+                    Some((column.column_name.clone(), current))
+                }
+                None => None,
+            };
+
+            if assignments.is_empty() {
+                debug!(
+                    "Nothing to update for row {} of '{}'",
+                    pk_repr, info.table_name
+                );
+                signals::send_save(signals::post_save(py)?, slf.as_any(), false)?;
+                return future_into_py(py, async move { Ok(()) });
+            }
+            let sql = format!(
+                "UPDATE {} SET {} WHERE {}",
+                info.table_name,
+                assignments.join(", "),
+                where_clause
+            );
+            (sql, SaveKind::Update { version, pk_repr })
+        };
+
+        let table_name = info.table_name.clone();
+        let instance: Py<Model> = slf.clone().unbind();
+
+        future_into_py(py, async move {
+            let (result, returned) = match kind {
+                SaveKind::Insert {
+                    returning: true, ..
+                } => (None, db.fetch_rows(&sql, params).await?),
+                _ => (Some(db.execute_statement(&sql, params).await?), Vec::new()),
+            };
+            Python::attach(|py| -> PyResult<()> {
+                let instance = instance.bind(py);
+                let created = matches!(kind, SaveKind::Insert { .. });
+                match kind {
+                    SaveKind::Insert {
+                        generated_pk,
+                        initial_version,
+                        ..
+                    } => {
+                        if let Some(column) = generated_pk {
+                            if let Some(row) = returned.first() {
+                                let value = row_to_dict(py, row)?.get_item(&column)?;
+                                instance.setattr(&column, value)?;
+                            } else if let Some(id) = result.and_then(|r| r.last_insert_id()) {
+                                instance.setattr(&column, id)?;
+                            }
+                        }
+                        if let Some(column) = initial_version {
+                            instance.setattr(column, 1)?;
+                        }
+                        instance.borrow_mut().persisted = true;
+                    }
+                    SaveKind::Update { version, pk_repr } => {
+                        let rows_affected = result.map_or(0, |r| r.rows_affected());
+                        if let Some((column, current)) = version {
+                            if rows_affected == 0 {
+                                return Err(FustOrmError::StaleData(format!(
+                                    "Row of '{}' with {} was changed or deleted since it was loaded (expected version {}).",
+                                    table_name, pk_repr, current
+                                ))
+                                .into());
+                            }
+                            instance.setattr(column, current + 1)?;
+                        }
+                    }
+                }
+                signals::send_save(signals::post_save(py)?, instance.as_any(), created)
+            })
+        })
+    }
+
+    /// Deletes the instance's row by primary key.
+    ///
+    /// For versioned models, the row only matches the version the instance was
+    /// loaded with; otherwise `StaleDataError` is raised. Sends `pre_delete`
+    /// before and `post_delete` after the row is removed. Afterwards the
+    /// instance counts as unsaved again, so `save()` would re-insert it.
+    ///
+    /// Rows of other models referencing this one are handled according to their
+    /// column's `on_delete`: `"restrict"` fails the delete, `"python"` children
+    /// are deleted with their own `delete()`, and `"set_null"` and
+    /// `"cascade"` are applied, all in the transaction deleting the row, so
+    /// that they don't depend on the database enforcing foreign keys.
+    #[pyo3(signature = (db = None))]
+    fn delete<'py>(
+        slf: &Bound<'py, Self>,
+        db: Option<PyRef<'py, Database>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        let db = context::resolve(py, db.as_deref())?;
+        let info = ModelInfo::from_class(&slf.get_type())?;
+        info.check_writable()?;
+        if !slf.borrow().persisted {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "Cannot delete an unsaved '{}' instance.",
+                slf.get_type().name()?
+            ))
+            .into());
+        }
+        signals::pre_delete(py)?.get().send(slf.as_any(), None)?;
+
+        let pk_value = info.instance_pk(slf.as_any())?;
+        let pk_repr = info.describe_pk(&pk_value)?;
+        let mut params = Vec::new();
+        let mut where_clause = info.pk_condition(&pk_value, &mut params)?;
+        let version = match info.version_column() {
+            Some(column) => {
+                let current = slf.getattr(&column.column_name)?.extract::<Option<i64>>()?;
+                if let Some(current) = current {
+                    params.push(current.to_string());
+                    where_clause.push_str(&format!(" AND {} = ?", column.column_name));
+                }
+                current
+            }
+            None => None,
+        };
+        let sql = format!("DELETE FROM {} WHERE {}", info.table_name, where_clause);
+        let plan = DeletePlan::new(&slf.get_type())?;
+        // References always target a single-column primary key, which is the
+        // first parameter of the WHERE clause.
+        let key = params.first().cloned().unwrap_or_default();
+
+        let table_name = info.table_name.clone();
+        let instance: Py<Model> = slf.clone().unbind();
+
+        future_into_py(py, async move {
+            let result = if plan.is_empty() {
+                db.execute_statement(&sql, params).await?
+            } else {
+                // The checks and the children's deletes run on a database
+                // bound to the transaction, so that they see the same rows
+                // and roll back with the row's delete.
+                let shared: SharedTransaction =
+                    Arc::new(AsyncMutex::new(Some(db.begin_write().await?)));
+                let tx_db = Database {
+                    tx: Some(shared.clone()),
+                    ..db
+                };
+                plan.check_restrict(&tx_db, &key).await?;
+                plan.delete_python_children(&tx_db, &key).await?;
+                for statement in plan.statements() {
+                    tx_db
+                        .execute_statement(statement, vec![key.clone()])
+                        .await?;
+                }
+                let result = tx_db.execute_statement(&sql, params).await?;
+                let tx = shared.lock().await.take().expect("the transaction is open");
+                if version.is_none() || result.rows_affected() > 0 {
+                    tx.commit().await.map_err(FustOrmError::from)?;
+                }
+                result
+            };
+            Python::attach(|py| -> PyResult<()> {
+                if let Some(current) = version
+                    && result.rows_affected() == 0
+                {
+                    return Err(FustOrmError::StaleData(format!(
+                        "Row of '{}' with {} was changed or deleted since it was loaded (expected version {}).",
+                        table_name, pk_repr, current
+                    ))
+                    .into());
+                }
+                let instance = instance.bind(py);
+                instance.borrow_mut().persisted = false;
+                signals::post_delete(py)?
+                    .get()
+                    .send(instance.as_any(), None)
+            })
+        })
+    }
+
+    /// Names of the columns the instance was loaded without.
+    #[getter]
+    fn deferred_columns(&self) -> Vec<String> {
+        self.deferred.clone()
+    }
+
+    /// Fetches the columns the instance was loaded without (see
+    /// `QueryBuilder.defer()`) with one query by primary key.
+    #[pyo3(signature = (db = None))]
+    fn load_deferred<'py>(
+        slf: &Bound<'py, Self>,
+        db: Option<PyRef<'py, Database>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        let db = context::resolve(py, db.as_deref())?;
+        let deferred = slf.borrow().deferred.clone();
+        if deferred.is_empty() {
+            return future_into_py(py, async move { Ok(()) });
+        }
+        let info = ModelInfo::from_class(&slf.get_type())?;
+        let pk_value = info.instance_pk(slf.as_any())?;
+        let pk_repr = info.describe_pk(&pk_value)?;
+        let mut params = Vec::new();
+        let where_clause = info.pk_condition(&pk_value, &mut params)?;
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {}",
+            deferred.join(", "),
+            info.table_name,
+            where_clause
+        );
+
+        let table_name = info.table_name.clone();
+        let instance: Py<Model> = slf.clone().unbind();
+
+        future_into_py(py, async move {
+            let rows = db.fetch_rows(&sql, params).await?;
+            Python::attach(|py| -> PyResult<()> {
+                let row = rows.first().ok_or_else(|| {
+                    FustOrmError::StaleData(format!(
+                        "Row of '{}' with {} was deleted since it was loaded.",
+                        table_name, pk_repr
+                    ))
+                })?;
+                let instance = instance.bind(py);
+                let cls = instance.get_type();
+                for (key, value) in row_to_dict(py, row)?.iter() {
+                    let key = key.downcast::<pyo3::types::PyString>()?;
+                    let field = cls.getattr(key)?.downcast_into::<ColumnField>()?;
+                    instance.setattr(key, field.borrow().py_value(value)?)?;
+                }
+                instance.borrow_mut().deferred.clear();
+                Ok(())
+            })
+        })
+    }
 }
@@ -0,0 +1,99 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::error::FustOrmError;
+use crate::schema::Dialect;
+
+/// Rewrites the `:name` placeholders of a raw SQL query into positional ones
+/// and returns the values to bind them to, in order, taken from `values`.
+///
+/// PostgreSQL's placeholders are numbered, so a name used several times
+/// reuses one `$n`; other databases get a `?` per occurrence, each bound to
+/// the value again. Text in quotes, comments and PostgreSQL's `::` casts are
+/// left alone.
+pub(crate) fn bind(
+    sql: &str,
+    dialect: Dialect,
+    values: &Bound<PyDict>,
+) -> PyResult<(String, Vec<Py<PyAny>>)> {
+    let mut rewritten = String::with_capacity(sql.len());
+    let mut names: Vec<String> = Vec::new();
+    let mut params = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                rewritten.push(c);
+                // A doubled quote escapes itself, and reads as closing and
+                // reopening the text.
+                for quoted in chars.by_ref() {
+                    rewritten.push(quoted);
+                    if quoted == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                rewritten.push(c);
+                for commented in chars.by_ref() {
+                    rewritten.push(commented);
+                    if commented == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                rewritten.push(c);
+                rewritten.extend(chars.next());
+                // The `*` of the opener doesn't close the comment.
+                let mut previous = ' ';
+                for commented in chars.by_ref() {
+                    rewritten.push(commented);
+                    if previous == '*' && commented == '/' {
+                        break;
+                    }
+                    previous = commented;
+                }
+            }
+            ':' if chars.peek() == Some(&':') => {
+                chars.next();
+                rewritten.push_str("::");
+            }
+            ':' if chars
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') =>
+            {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                let Some(value) = values.get_item(&name)? else {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "No value was given for the parameter ':{}'.",
+                        name
+                    ))
+                    .into());
+                };
+                match dialect {
+                    Dialect::Postgres => {
+                        let index = match names.iter().position(|n| *n == name) {
+                            Some(index) => index,
+                            None => {
+                                names.push(name);
+                                params.push(value.unbind());
+                                names.len() - 1
+                            }
+                        };
+                        rewritten.push_str(&format!("${}", index + 1));
+                    }
+                    Dialect::Sqlite | Dialect::Mysql => {
+                        params.push(value.unbind());
+                        rewritten.push('?');
+                    }
+                }
+            }
+            c => rewritten.push(c),
+        }
+    }
+    Ok((rewritten, params))
+}
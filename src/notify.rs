@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use log::debug;
+use pyo3::exceptions::PyStopAsyncIteration;
+use pyo3::prelude::*;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::postgres::PgListener;
+use tokio::sync::{Mutex as AsyncMutex, Notify};
+
+use crate::context;
+use crate::database::Database;
+use crate::error::FustOrmError;
+use crate::schema::Dialect;
+
+pub(crate) fn require_postgres(db: &Database, method: &str) -> Result<(), FustOrmError> {
+    if db.dialect() != Dialect::Postgres {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "{}() is only supported on PostgreSQL.",
+            method
+        )));
+    }
+    Ok(())
+}
+
+/// Opens a connection of its own listening on `channels`. Resolves to a
+/// `Listener` iterating over the notifications sent to them.
+pub(crate) fn listen<'py>(
+    py: Python<'py>,
+    db: &Database,
+    channels: Vec<String>,
+) -> PyResult<Bound<'py, PyAny>> {
+    require_postgres(db, "listen")?;
+    if channels.is_empty() {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "listen() needs at least one channel.".to_string(),
+        )
+        .into());
+    }
+    // Notifications arrive on a connection outside of the pool, which would
+    // otherwise hand it out to other statements.
+    let url = db.pool.connect_options().database_url.to_string();
+    future_into_py(py, async move {
+        let mut listener = PgListener::connect(&url)
+            .await
+            .map_err(FustOrmError::connection)?;
+        listener
+            .listen_all(channels.iter().map(String::as_str))
+            .await
+            .map_err(FustOrmError::from)?;
+        debug!("Listening on {}", channels.join(", "));
+        Ok(Listener {
+            listener: Arc::new(AsyncMutex::new(Some(listener))),
+            closed: Arc::new(Notify::new()),
+        })
+    })
+}
+
+/// Sends `payload` to the listeners of `channel`. In a transaction, it is
+/// delivered when the transaction commits.
+pub(crate) fn notify<'py>(
+    py: Python<'py>,
+    db: &Database,
+    channel: String,
+    payload: String,
+) -> PyResult<Bound<'py, PyAny>> {
+    require_postgres(db, "notify")?;
+    let db = context::resolve(py, Some(db))?;
+    future_into_py(py, async move {
+        db.execute_statement("SELECT pg_notify($1, $2)", vec![channel, payload])
+            .await?;
+        Ok(())
+    })
+}
+
+/// The notifications sent to the channels passed to `Database.listen()`, as
+/// an async iterator of `Notification`s.
+///
+/// If the connection is lost, the listener reconnects and listens again;
+/// notifications sent in the meantime are lost.
+#[pyclass(module = "fust_orm")]
+pub struct Listener {
+    listener: Arc<AsyncMutex<Option<PgListener>>>,
+    /// Wakes up an `__anext__()` waiting for a notification when the
+    /// listener is closed.
+    closed: Arc<Notify>,
+}
+
+#[pymethods]
+impl Listener {
+    fn __aiter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let listener = self.listener.clone();
+        let closed = self.closed.clone();
+        future_into_py(py, async move {
+            let notified = closed.notified();
+            let mut guard = listener.lock().await;
+            let Some(listener) = guard.as_mut() else {
+                return Err(PyStopAsyncIteration::new_err(()));
+            };
+            tokio::select! {
+                notification = listener.recv() => {
+                    let notification = notification.map_err(FustOrmError::from)?;
+                    Ok(Notification {
+                        channel: notification.channel().to_string(),
+                        payload: notification.payload().to_string(),
+                        process_id: notification.process_id(),
+                    })
+                }
+                _ = notified => Err(PyStopAsyncIteration::new_err(())),
+            }
+        })
+    }
+
+    /// Stops listening and closes the connection; iterating afterwards ends
+    /// right away.
+    fn close<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let listener = self.listener.clone();
+        self.closed.notify_waiters();
+        future_into_py(py, async move {
+            if listener.lock().await.take().is_some() {
+                debug!("Stopped listening");
+            }
+            Ok(())
+        })
+    }
+
+    fn __aenter__<'py>(slf: Py<Self>, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        future_into_py(py, async move { Ok(slf) })
+    }
+
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __aexit__<'py>(
+        &self,
+        py: Python<'py>,
+        _exc_type: Option<&Bound<'py, PyAny>>,
+        _exc_value: Option<&Bound<'py, PyAny>>,
+        _traceback: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.close(py)
+    }
+}
+
+/// A notification received by a `Listener`.
+#[pyclass(frozen, get_all, module = "fust_orm")]
+pub struct Notification {
+    /// The channel it was sent to.
+    channel: String,
+    /// The payload, empty if none was sent.
+    payload: String,
+    /// The server process of the sending session.
+    process_id: u32,
+}
+
+#[pymethods]
+impl Notification {
+    fn __repr__(&self) -> String {
+        format!(
+            "<Notification channel={:?} payload={:?} process_id={}>",
+            self.channel, self.payload, self.process_id
+        )
+    }
+}
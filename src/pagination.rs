@@ -0,0 +1,132 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyIterator, PyList, PyTuple};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::Row;
+
+use crate::context;
+use crate::database::Database;
+use crate::decode::RowBatch;
+use crate::error::FustOrmError;
+use crate::model::hydrate;
+use crate::query::{QueryBuilder, select};
+
+/// One page of the rows of a query, with the number of rows of all pages, as
+/// returned by `Database.paginate()`.
+#[pyclass(frozen, get_all, module = "fust_orm")]
+pub struct Page {
+    /// The rows of the page: model instances for a query built from a model,
+    /// dicts otherwise.
+    items: Py<PyList>,
+    /// The number of rows of all pages.
+    total: u64,
+    /// The number of the page, counting from 1.
+    page: u64,
+    per_page: u64,
+}
+
+#[pymethods]
+impl Page {
+    /// The number of pages, at least 1.
+    #[getter]
+    fn pages(&self) -> u64 {
+        self.total.div_ceil(self.per_page).max(1)
+    }
+
+    #[getter]
+    fn has_next(&self) -> bool {
+        self.page < self.pages()
+    }
+
+    #[getter]
+    fn has_prev(&self) -> bool {
+        self.page > 1
+    }
+
+    /// The page as a dict of `items`, `total`, `page`, `per_page` and
+    /// `pages`, e.g. for a JSON response.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("items", self.items.bind(py))?;
+        dict.set_item("total", self.total)?;
+        dict.set_item("page", self.page)?;
+        dict.set_item("per_page", self.per_page)?;
+        dict.set_item("pages", self.pages())?;
+        Ok(dict)
+    }
+
+    fn __len__(&self, py: Python) -> usize {
+        self.items.bind(py).len()
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        self.items.bind(py).try_iter()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Page page={} of {} per_page={} total={}>",
+            self.page,
+            self.pages(),
+            self.per_page,
+            self.total
+        )
+    }
+}
+
+/// `Database.paginate()`: counts the rows of `query`, then runs it for the
+/// `per_page` rows of page `page`.
+pub(crate) fn paginate<'py>(
+    py: Python<'py>,
+    db: &Database,
+    query: &Bound<'py, PyAny>,
+    page: u64,
+    per_page: u64,
+) -> PyResult<Bound<'py, PyAny>> {
+    if page == 0 || per_page == 0 {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "page and per_page must be at least 1.".to_string(),
+        )
+        .into());
+    }
+    let query = match query.downcast::<QueryBuilder>() {
+        Ok(query) => query.borrow().clone(),
+        Err(_) if query.extract::<String>().is_ok() => select(py, &PyTuple::new(py, [query])?)?,
+        Err(_) => {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "'query' must be a string or a result of calling select()",
+            ));
+        }
+    };
+    let offset = (page - 1).checked_mul(per_page).ok_or_else(|| {
+        FustOrmError::InvalidQueryArgument(format!("Page {} is out of range.", page))
+    })?;
+    let db = context::resolve(py, Some(db))?;
+    let (sql, params) = query.build_page(py, &db, Some((per_page, offset)))?;
+    let (count_sql, count_params) = query.build_count(py, &db)?;
+    let model = query.model_class();
+
+    future_into_py(py, async move {
+        let counted = db.fetch_rows(&count_sql, count_params).await?;
+        let total = match counted.first() {
+            Some(row) => row.try_get::<i64, _>(0).map_err(FustOrmError::from)?,
+            None => 0,
+        };
+        let rows = db.fetch_rows(&sql, params).await?;
+        let batch = RowBatch::decode(&rows)?;
+        Python::attach(|py| -> PyResult<Page> {
+            let items = PyList::empty(py);
+            for row in batch.to_dicts(py)? {
+                match &model {
+                    Some(model) => items.append(hydrate(model.bind(py), &row)?)?,
+                    None => items.append(row)?,
+                }
+            }
+            Ok(Page {
+                items: items.unbind(),
+                total: total as u64,
+                page,
+                per_page,
+            })
+        })
+    })
+}
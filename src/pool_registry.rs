@@ -0,0 +1,108 @@
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, Weak};
+
+use sqlx::AnyPool;
+use sqlx::any::AnyConnectOptions;
+
+use crate::database::pool_identity;
+
+/// Identifies a pool by the connect options every clone of it shares (see
+/// `pool_identity()`), without keeping them alive. As the key holds on to
+/// their allocation, a later pool can't be mistaken for a dropped one.
+#[derive(Clone)]
+pub(crate) struct PoolKey(Weak<AnyConnectOptions>);
+
+impl PoolKey {
+    pub(crate) fn of(pool: &AnyPool) -> PoolKey {
+        PoolKey(Arc::downgrade(&pool_identity(pool)))
+    }
+
+    /// Whether a clone of the pool is still alive.
+    pub(crate) fn is_live(&self) -> bool {
+        self.0.strong_count() > 0
+    }
+}
+
+impl PartialEq for PoolKey {
+    fn eq(&self, other: &PoolKey) -> bool {
+        Weak::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for PoolKey {}
+
+impl Hash for PoolKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state);
+    }
+}
+
+/// Values attached to pools, e.g. their settings, forgotten once every
+/// clone of their pool is dropped.
+pub(crate) struct PoolRegistry<T> {
+    entries: Mutex<Vec<(PoolKey, T)>>,
+}
+
+impl<T: Clone> PoolRegistry<T> {
+    pub(crate) const fn new() -> PoolRegistry<T> {
+        PoolRegistry {
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Attaches `value` to `pool`, over the values attached before.
+    pub(crate) fn insert(&self, pool: &AnyPool, value: T) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(key, _)| key.is_live());
+        entries.push((PoolKey::of(pool), value));
+    }
+
+    /// The value attached to `pool` last, if any.
+    pub(crate) fn get(&self, pool: &AnyPool) -> Option<T> {
+        self.find(pool, |_| true)
+    }
+
+    /// The value attached to `pool` last among those matching `matches`.
+    pub(crate) fn find(&self, pool: &AnyPool, matches: impl Fn(&T) -> bool) -> Option<T> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.is_empty() {
+            return None;
+        }
+        let pool = PoolKey::of(pool);
+        entries
+            .iter()
+            .rev()
+            .find(|(key, value)| *key == pool && key.is_live() && matches(value))
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Returns the value `find()` would, or attaches `value` to `pool` if
+    /// there is none, at once.
+    pub(crate) fn find_or_insert(
+        &self,
+        pool: &AnyPool,
+        matches: impl Fn(&T) -> bool,
+        value: T,
+    ) -> Option<T> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(key, _)| key.is_live());
+        let pool = PoolKey::of(pool);
+        let existing = entries
+            .iter()
+            .rev()
+            .find(|(key, value)| *key == pool && matches(value))
+            .map(|(_, value)| value.clone());
+        if existing.is_none() {
+            entries.push((pool, value));
+        }
+        existing
+    }
+
+    /// Detaches the values not matching `keep`, whatever their pool.
+    pub(crate) fn retain(&self, keep: impl Fn(&T) -> bool) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(_, value)| keep(value));
+    }
+}
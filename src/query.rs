@@ -1,22 +1,47 @@
-use std::collections::HashSet;
 use std::sync::Arc;
 
 use crate::column_field::ColumnField;
+use crate::context;
+use crate::custom_type;
+use crate::database::Database;
+use crate::decode::RowBatch;
 use crate::error::FustOrmError;
-use crate::model::Model;
+use crate::expression::{Expr, F};
+use crate::fts;
+use crate::interval;
+use crate::model::{Model, ModelInfo, column_param, hydrate};
+use crate::named_params;
+use crate::range::Range;
+use crate::result_limits::ResultLimits;
+use crate::rewrite;
+use crate::schema::Dialect;
 use crate::where_condition::WhereCondition;
 use log::debug;
 use pyo3::prelude::*;
-use pyo3::types::{PyBool, PyFloat, PyInt, PyList, PyString, PyTuple, PyType};
+use pyo3::types::{PyBool, PyDelta, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple, PyType};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::Row;
 
 /// Represents the two modes for building a query.
+///
+/// Parts are shared between a query and the copies its methods return, so
+/// that refining a query doesn't copy its columns and conditions.
 #[derive(Debug, Clone)]
 enum QueryType {
     /// A query built programmatically from Model fields and conditions.
     Structured {
-        table: String,
-        columns: Vec<String>,
-        where_clauses: Vec<WhereCondition>,
+        table: Arc<str>,
+        columns: Arc<[String]>,
+        where_clauses: Vec<Arc<WhereCondition>>,
+        /// Computed columns added by `annotate()`, as `(name, expression)`.
+        annotations: Vec<(String, Expr)>,
+        /// The row lock requested with `for_update()` or `for_share()`, if any.
+        lock: Option<RowLock>,
+        /// The order of the rows, set by `search()` to their relevance.
+        order_by: Option<Expr>,
+        /// The model class passed to `select()`, if any. Used to load rows as
+        /// model instances.
+        model: Option<Arc<Py<PyType>>>,
     },
     /// A raw SQL string with its associated parameters.
     Raw {
@@ -25,6 +50,54 @@ enum QueryType {
     },
 }
 
+/// A row lock requested with `for_update()` or `for_share()`.
+#[derive(Debug, Clone, Copy)]
+struct RowLock {
+    /// Whether other transactions may still lock the rows for sharing.
+    shared: bool,
+    wait: LockWait,
+}
+
+/// What a row lock does about rows locked by other transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockWait {
+    /// Wait for them.
+    Wait,
+    /// `SKIP LOCKED`: leave them out.
+    SkipLocked,
+    /// `NOWAIT`: fail.
+    NoWait,
+}
+
+impl LockWait {
+    fn from_flags(method: &str, skip_locked: bool, nowait: bool) -> Result<Self, FustOrmError> {
+        match (skip_locked, nowait) {
+            (false, false) => Ok(LockWait::Wait),
+            (true, false) => Ok(LockWait::SkipLocked),
+            (false, true) => Ok(LockWait::NoWait),
+            (true, true) => Err(FustOrmError::InvalidQueryArgument(format!(
+                "{}() accepts either skip_locked or nowait, not both.",
+                method
+            ))),
+        }
+    }
+}
+
+impl RowLock {
+    fn clause(self, dialect: Dialect) -> &'static str {
+        match (self.shared, self.wait, dialect) {
+            (false, LockWait::Wait, _) => " FOR UPDATE",
+            (false, LockWait::SkipLocked, _) => " FOR UPDATE SKIP LOCKED",
+            (false, LockWait::NoWait, _) => " FOR UPDATE NOWAIT",
+            // MariaDB only knows the older syntax.
+            (true, LockWait::Wait, Dialect::Mysql) => " LOCK IN SHARE MODE",
+            (true, LockWait::Wait, _) => " FOR SHARE",
+            (true, LockWait::SkipLocked, _) => " FOR SHARE SKIP LOCKED",
+            (true, LockWait::NoWait, _) => " FOR SHARE NOWAIT",
+        }
+    }
+}
+
 /// A builder object that accumulates parts of a SQL query.
 /// It can operate in two modes: building a query from structured components
 /// (tables, columns, conditions) or holding a raw SQL string with parameters.
@@ -40,24 +113,109 @@ impl QueryBuilder {
     /// This method inspects the `query_type` and either:
     /// 1.  Builds a structured query, automatically adding columns marked with `select_column`.
     /// 2.  Processes a raw query, converting its Python parameters into strings.
-    pub fn build(&self, py: Python) -> PyResult<(String, Vec<String>)> {
-        match &self.query_type {
+    ///
+    /// A row lock from `for_update()` or `for_share()` is rendered if `db`
+    /// supports it.
+    pub fn build(&self, py: Python, db: &Database) -> PyResult<(String, Vec<String>)> {
+        self.build_page(py, db, None)
+    }
+
+    /// `build()` for the `limit` rows from `offset` on, with `page` set to
+    /// `(limit, offset)`. A raw query gets the clause appended, so it must not
+    /// have a `LIMIT` of its own.
+    pub(crate) fn build_page(
+        &self,
+        py: Python,
+        db: &Database,
+        page: Option<(u64, u64)>,
+    ) -> PyResult<(String, Vec<String>)> {
+        let limit_clause =
+            |(limit, offset): (u64, u64)| format!(" LIMIT {} OFFSET {}", limit, offset);
+        let rewritten = self.scoped(py, db)?;
+        match &rewritten.as_ref().unwrap_or(self).query_type {
             QueryType::Structured {
                 table,
                 columns,
                 where_clauses,
-            } => self.build_structured(py, table, columns, where_clauses),
-            QueryType::Raw { sql, params } => self.build_raw(py, sql, params),
+                annotations,
+                lock,
+                order_by,
+                model,
+            } => {
+                let casts = cast_columns(py, model.as_ref(), db)?;
+                // `*` would select those columns as they are.
+                let columns = match model {
+                    Some(model) if columns.is_empty() && !casts.is_empty() => model
+                        .bind(py)
+                        .getattr(pyo3::intern!(py, "__columns__"))?
+                        .extract()?,
+                    _ => columns.to_vec(),
+                };
+                let (mut sql, mut params) = self.build_structured(
+                    py,
+                    db.dialect(),
+                    table,
+                    &columns,
+                    &casts,
+                    where_clauses,
+                    annotations,
+                )?;
+                let order = order_by_sql(py, order_by.as_ref(), &mut params)?;
+                sql.push_str(&order);
+                if let Some(page) = page {
+                    // The pages of a model query are ordered by its primary
+                    // key unless it has an order, so that they don't overlap.
+                    if order.is_empty()
+                        && let Some(model) = model
+                        && let Ok(key) = ModelInfo::from_class(model.bind(py))?.primary_key()
+                    {
+                        let key: Vec<&str> = key.iter().map(|c| c.column_name.as_str()).collect();
+                        sql.push_str(&format!(" ORDER BY {}", key.join(", ")));
+                    }
+                    sql.push_str(&limit_clause(page));
+                }
+                sql.push_str(lock_clause(*lock, db));
+                Ok((sql, params))
+            }
+            QueryType::Raw { sql, params } => {
+                let (sql, params) = self.build_raw(py, db.dialect(), sql, params)?;
+                match page {
+                    Some(page) => {
+                        let sql = sql.trim().trim_end_matches(';');
+                        // On a line of its own, past a trailing `--` comment.
+                        let limit = limit_clause(page);
+                        Ok((format!("{}\n{}", sql, limit.trim_start()), params))
+                    }
+                    None => Ok((sql, params)),
+                }
+            }
+        }
+    }
+
+    /// The statement counting the rows of the query, ignoring its order.
+    pub(crate) fn build_count(&self, py: Python, db: &Database) -> PyResult<(String, Vec<String>)> {
+        self.build_aggregate(py, db, "count(*)", "")
+    }
+
+    /// The model class rows of the query load as, if any.
+    pub(crate) fn model_class(&self) -> Option<Arc<Py<PyType>>> {
+        match &self.query_type {
+            QueryType::Structured { model, .. } => model.clone(),
+            QueryType::Raw { .. } => None,
         }
     }
 
     /// Helper to build a query from structured components.
+    #[allow(clippy::too_many_arguments)]
     fn build_structured(
         &self,
         py: Python,
+        dialect: Dialect,
         table: &str,
         columns: &[String],
-        where_clauses: &[WhereCondition],
+        casts: &[(String, String)],
+        where_clauses: &[Arc<WhereCondition>],
+        annotations: &[(String, Expr)],
     ) -> PyResult<(String, Vec<String>)> {
         debug!(
             "Building structured query for table '{}' with {} explicit columns and {} where clauses.",
@@ -66,73 +224,872 @@ impl QueryBuilder {
             where_clauses.len()
         );
 
-        // Use a HashSet to automatically handle duplicate column names.
-        let mut all_columns: HashSet<String> = columns.iter().cloned().collect();
-
-        // Add columns from where-clauses that have the `select_column` flag.
-        for cond in where_clauses {
-            if cond.select_column {
-                all_columns.insert(cond.column_name.clone());
+        // Columns keep the order they were given in, so that the same query
+        // renders the same SQL and reuses its prepared statement.
+        let mut all_columns: Vec<String> = Vec::new();
+        let selected = where_clauses
+            .iter()
+            .filter(|cond| cond.select_column)
+            .map(|cond| &cond.column_name);
+        for column in columns.iter().chain(selected) {
+            if !all_columns.contains(column) {
+                all_columns.push(column.clone());
             }
         }
 
-        let cols = if all_columns.is_empty() {
-            "*".to_string()
-        } else {
-            all_columns.into_iter().collect::<Vec<_>>().join(", ")
-        };
-
-        let mut sql = format!("SELECT {} FROM {}", cols, table);
         let mut params = Vec::new();
+        let cols = select_list(py, table, all_columns, casts, annotations, &mut params)?;
+        let mut sql = format!("SELECT {} FROM {}", cols, table);
 
         if !where_clauses.is_empty() {
             sql.push_str(" WHERE ");
-            let conditions: Result<Vec<String>, PyErr> = where_clauses
-                .iter()
-                .map(|cond| {
-                    if cond.value.is_none(py) {
-                        return Ok(format!("{} {} NULL", cond.column_name, cond.operator));
-                    }
-                    match py_any_to_string(py, &cond.value, &cond.column_name)? {
-                        SqlParam::Single(s) => {
-                            params.push(s);
-                            Ok(format!("{} {} ?", cond.column_name, cond.operator))
-                        }
-                        SqlParam::List(vec) => {
-                            let placeholders: Vec<&str> = vec.iter().map(|_| "?").collect();
-                            params.extend(vec);
-                            Ok(format!(
-                                "{} {} ({})",
-                                cond.column_name,
-                                cond.operator,
-                                placeholders.join(", ")
-                            ))
-                        }
-                    }
-                })
-                .collect();
+            sql.push_str(&where_sql(py, dialect, where_clauses, &mut params)?);
+        }
+        Ok((sql, params))
+    }
 
-            sql.push_str(&conditions?.join(" AND "));
+    /// Builds a `SELECT` of exactly `columns` (in order) and the annotations
+    /// with the query's conditions, for `values()` and `values_list()`. Without
+    /// columns, the query's own columns are used, or all columns of its model.
+    fn build_projection(
+        &self,
+        py: Python,
+        db: &Database,
+        method: &str,
+        columns: &Bound<PyTuple>,
+    ) -> PyResult<(String, Vec<String>)> {
+        let rewritten = self.scoped(py, db)?;
+        let QueryType::Structured {
+            table,
+            columns: selected,
+            where_clauses,
+            annotations,
+            model,
+            lock,
+            order_by,
+        } = &rewritten.as_ref().unwrap_or(self).query_type
+        else {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "{}() requires a structured query; use Database.execute() for raw SQL.",
+                method
+            ))
+            .into());
+        };
+        let mut names = columns
+            .iter()
+            .map(|column| match column.extract::<PyRef<ColumnField>>() {
+                Ok(column) if column.table_name != **table => {
+                    Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Column '{}' of '{}' can't be selected from '{}'.",
+                        column.column_name, column.table_name, table
+                    ))
+                    .into())
+                }
+                Ok(column) => Ok(column.column_name.clone()),
+                Err(_) => column.extract::<String>(),
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        if names.is_empty() {
+            names = match model {
+                Some(model) if selected.is_empty() => model
+                    .bind(py)
+                    .getattr(pyo3::intern!(py, "__columns__"))?
+                    .extract()?,
+                _ => selected.to_vec(),
+            };
+        }
+        let casts = cast_columns(py, model.as_ref(), db)?;
+        let mut params = Vec::new();
+        let cols = select_list(py, table, names, &casts, annotations, &mut params)?;
+        let mut sql = format!("SELECT {} FROM {}", cols, table);
+        if !where_clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&where_sql(py, db.dialect(), where_clauses, &mut params)?);
         }
+        sql.push_str(&order_by_sql(py, order_by.as_ref(), &mut params)?);
+        sql.push_str(lock_clause(*lock, db));
         Ok((sql, params))
     }
 
+    /// Builds a statement selecting `item` instead of the query's columns, e.g.
+    /// `count(*)`. Raw queries are wrapped as a subquery.
+    fn build_aggregate(
+        &self,
+        py: Python,
+        db: &Database,
+        item: &str,
+        suffix: &str,
+    ) -> PyResult<(String, Vec<String>)> {
+        let dialect = db.dialect();
+        let rewritten = self.scoped(py, db)?;
+        match &rewritten.as_ref().unwrap_or(self).query_type {
+            QueryType::Structured {
+                table,
+                where_clauses,
+                ..
+            } => {
+                let mut sql = format!("SELECT {} FROM {}", item, table);
+                let mut params = Vec::new();
+                if !where_clauses.is_empty() {
+                    sql.push_str(" WHERE ");
+                    sql.push_str(&where_sql(py, dialect, where_clauses, &mut params)?);
+                }
+                sql.push_str(suffix);
+                Ok((sql, params))
+            }
+            QueryType::Raw { sql, params } => {
+                let (sql, params) = self.build_raw(py, dialect, sql, params)?;
+                let sql = sql.trim().trim_end_matches(';');
+                // The parenthesis closes on a line of its own, past a trailing
+                // `--` comment.
+                Ok((
+                    format!("SELECT {} FROM ({}\n) AS _fust_rows{}", item, sql, suffix),
+                    params,
+                ))
+            }
+        }
+    }
+
+    /// The query rendered instead of this one for `db`: the one the hook of
+    /// `rewrite_queries()` returns, restricted to the tenant of a database
+    /// returned by `for_tenant(..., strategy="filter")` if its model has the
+    /// tenant column. `None` renders this query as it is.
+    fn scoped(&self, py: Python, db: &Database) -> PyResult<Option<QueryBuilder>> {
+        let rewritten = rewrite::query(py, self)?;
+        // `db` may still resolve to a transaction bound meanwhile.
+        let Some(tenant) = context::resolve(py, Some(db))?.tenant else {
+            return Ok(rewritten);
+        };
+        let mut query = rewritten.unwrap_or_else(|| self.clone());
+        let QueryType::Structured {
+            where_clauses,
+            model: Some(model),
+            ..
+        } = &mut query.query_type
+        else {
+            return Ok(Some(query));
+        };
+        if ModelInfo::from_class(model.bind(py))?
+            .columns
+            .iter()
+            .any(|c| c.column_name == tenant.column)
+        {
+            where_clauses.push(Arc::new(tenant.condition(py)));
+        }
+        Ok(Some(query))
+    }
+
+    /// The value a structured query compares `column` to with `=`, which
+    /// every row it matches has.
+    pub(crate) fn equal_value(&self, column: &str) -> Option<Arc<Py<PyAny>>> {
+        let QueryType::Structured { where_clauses, .. } = &self.query_type else {
+            return None;
+        };
+        where_clauses
+            .iter()
+            .find(|c| c.expression.is_none() && c.operator == "=" && c.column_name == column)
+            .map(|c| c.value.clone())
+    }
+
+    /// Returns the model of a structured query and the columns it currently
+    /// loads, for `defer()` and `only()`.
+    fn loaded_columns(&self, py: Python, method: &str) -> PyResult<(ModelInfo, Vec<String>)> {
+        let QueryType::Structured {
+            columns,
+            model: Some(model),
+            ..
+        } = &self.query_type
+        else {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "{}() requires a query built from a Model class.",
+                method
+            ))
+            .into());
+        };
+        let info = ModelInfo::from_class(model.bind(py))?;
+        let selected = if columns.is_empty() {
+            info.columns.iter().map(|c| c.column_name.clone()).collect()
+        } else {
+            columns.to_vec()
+        };
+        Ok((info, selected))
+    }
+
+    /// Returns a copy of the query selecting `columns`.
+    fn with_columns(&self, columns: Vec<String>) -> QueryBuilder {
+        let mut query = self.clone();
+        if let QueryType::Structured { columns: c, .. } = &mut query.query_type {
+            *c = columns.into();
+        }
+        query
+    }
+
+    /// Returns a copy of the query with the row lock `make` derives from the
+    /// current one, for `for_update()` and the like.
+    fn with_lock(
+        &self,
+        method: &str,
+        make: impl FnOnce(Option<RowLock>) -> PyResult<RowLock>,
+    ) -> PyResult<QueryBuilder> {
+        let mut query = self.clone();
+        match &mut query.query_type {
+            QueryType::Structured { lock, .. } => *lock = Some(make(*lock)?),
+            QueryType::Raw { .. } => {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "{}() can't lock a raw SQL query; add the clause to the SQL.",
+                    method
+                ))
+                .into());
+            }
+        }
+        Ok(query)
+    }
+
+    /// Returns a copy of the query whose row lock does `wait` about rows
+    /// locked by other transactions, for `skip_locked()` and `nowait()`.
+    fn with_wait(&self, method: &str, wait: LockWait) -> PyResult<QueryBuilder> {
+        self.with_lock(method, |lock| match lock {
+            Some(lock) if lock.wait == LockWait::Wait || lock.wait == wait => {
+                Ok(RowLock { wait, ..lock })
+            }
+            Some(_) => Err(FustOrmError::InvalidQueryArgument(format!(
+                "{}() can't be combined with {}().",
+                method,
+                if wait == LockWait::NoWait {
+                    "skip_locked"
+                } else {
+                    "nowait"
+                }
+            ))
+            .into()),
+            None => Err(FustOrmError::InvalidQueryArgument(format!(
+                "{}() applies to a row lock; call for_update() or for_share() first.",
+                method
+            ))
+            .into()),
+        })
+    }
+
     /// Helper to process a raw SQL query and its parameters.
     fn build_raw(
         &self,
         py: Python,
+        dialect: Dialect,
         sql: &str,
         params: &[Py<PyAny>],
     ) -> PyResult<(String, Vec<String>)> {
+        // A dict of values binds `:name` placeholders.
+        if let [values] = params
+            && let Ok(values) = values.bind(py).downcast::<PyDict>()
+        {
+            debug!("Building raw query with {} named parameters.", values.len());
+            let (sql, params) = named_params::bind(sql, dialect, values)?;
+            return Ok((sql, raw_params(py, &params)?));
+        }
         debug!("Building raw query with {} parameters.", params.len());
-        let string_params: Vec<String> = params
+        Ok((sql.to_string(), raw_params(py, params)?))
+    }
+}
+
+/// Converts the parameters of a raw SQL query to the strings they are bound as.
+pub(crate) fn raw_params(py: Python, params: &[Py<PyAny>]) -> PyResult<Vec<String>> {
+    params
+        .iter()
+        .map(|p| match py_any_to_string(py, p, "raw query parameter")? {
+            SqlParam::Single(s) => Ok(s),
+            SqlParam::List(vec) => Ok(vec.join(", ")),
+        })
+        .collect()
+}
+
+#[pymethods]
+impl QueryBuilder {
+    /// Executes the query and loads the rows as instances of the model it was built from.
+    ///
+    /// For models using single-table inheritance, each row is loaded as the
+    /// subclass matching its discriminator value. `max_rows` and
+    /// `max_result_bytes` override the result limits of the database.
+    #[pyo3(signature = (db = None, *, max_rows = None, max_result_bytes = None))]
+    fn all<'py>(
+        &self,
+        py: Python<'py>,
+        db: Option<PyRef<'py, Database>>,
+        max_rows: Option<usize>,
+        max_result_bytes: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let db = context::resolve(py, db.as_deref())?;
+        let model = match &self.query_type {
+            QueryType::Structured {
+                model: Some(model), ..
+            } => model.clone(),
+            _ => {
+                return Err(FustOrmError::InvalidQueryArgument(
+                    "all() requires a query built from a Model class; use Database.execute() for plain rows.".to_string(),
+                )
+                .into());
+            }
+        };
+        let (sql, params) = self.build(py, &db)?;
+        let limits = ResultLimits {
+            max_rows,
+            max_result_bytes,
+        };
+
+        future_into_py(py, async move {
+            let rows = db.fetch_rows_limited(&sql, params, limits).await?;
+            let batch = RowBatch::decode(&rows)?;
+            Python::attach(|py| -> PyResult<Py<PyList>> {
+                let model = model.bind(py);
+                let results = PyList::empty(py);
+                for row in batch.to_dicts(py)? {
+                    results.append(hydrate(model, &row)?)?;
+                }
+                Ok(results.unbind())
+            })
+        })
+    }
+
+    /// Executes the query for the given columns only and returns the rows as
+    /// dicts, without loading model instances.
+    ///
+    /// Columns may be `ColumnField`s or names; without any, the query's
+    /// selected columns (or all columns of its model) are returned.
+    #[pyo3(signature = (db, *columns, max_rows = None, max_result_bytes = None))]
+    fn values<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+        columns: &Bound<'py, PyTuple>,
+        max_rows: Option<usize>,
+        max_result_bytes: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let (sql, params) = self.build_projection(py, db, "values", columns)?;
+        let db = context::resolve(py, Some(db))?;
+        let limits = ResultLimits {
+            max_rows,
+            max_result_bytes,
+        };
+
+        future_into_py(py, async move {
+            let rows = db.fetch_rows_limited(&sql, params, limits).await?;
+            let batch = RowBatch::decode(&rows)?;
+            Python::attach(|py| -> PyResult<Py<PyList>> {
+                Ok(PyList::new(py, batch.to_dicts(py)?)?.unbind())
+            })
+        })
+    }
+
+    /// Like `values()`, but returns each row as a tuple in column order. With
+    /// `flat=True`, a query for a single column returns a list of its values.
+    #[pyo3(signature = (db, *columns, flat = false, max_rows = None, max_result_bytes = None))]
+    fn values_list<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+        columns: &Bound<'py, PyTuple>,
+        flat: bool,
+        max_rows: Option<usize>,
+        max_result_bytes: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let (sql, params) = self.build_projection(py, db, "values_list", columns)?;
+        if flat && columns.len() != 1 {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "values_list(flat=True) requires exactly one column.".to_string(),
+            )
+            .into());
+        }
+        let db = context::resolve(py, Some(db))?;
+        let limits = ResultLimits {
+            max_rows,
+            max_result_bytes,
+        };
+
+        future_into_py(py, async move {
+            let rows = db.fetch_rows_limited(&sql, params, limits).await?;
+            let batch = RowBatch::decode(&rows)?;
+            Python::attach(|py| -> PyResult<Py<PyList>> {
+                let results = PyList::empty(py);
+                for row in batch.to_dicts(py)? {
+                    let values = row.values();
+                    if flat {
+                        results.append(values.get_item(0)?)?;
+                    } else {
+                        results.append(PyTuple::new(py, values.iter())?)?;
+                    }
+                }
+                Ok(results.unbind())
+            })
+        })
+    }
+
+    /// Counts the rows matching the query with `SELECT count(*)`, without
+    /// fetching them.
+    #[pyo3(signature = (db = None))]
+    fn count<'py>(
+        &self,
+        py: Python<'py>,
+        db: Option<PyRef<'py, Database>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let db = context::resolve(py, db.as_deref())?;
+        let (sql, params) = self.build_count(py, &db)?;
+
+        future_into_py(py, async move {
+            let rows = db.fetch_rows(&sql, params).await?;
+            let count = match rows.first() {
+                Some(row) => row.try_get::<i64, _>(0).map_err(FustOrmError::from)?,
+                None => 0,
+            };
+            Ok(count)
+        })
+    }
+
+    /// Checks whether any row matches the query with `SELECT 1 ... LIMIT 1`.
+    #[pyo3(signature = (db = None))]
+    fn exists<'py>(
+        &self,
+        py: Python<'py>,
+        db: Option<PyRef<'py, Database>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let db = context::resolve(py, db.as_deref())?;
+        let (sql, params) = self.build_aggregate(py, &db, "1", " LIMIT 1")?;
+
+        future_into_py(py, async move {
+            let rows = db.fetch_rows(&sql, params).await?;
+            Ok(!rows.is_empty())
+        })
+    }
+
+    /// Returns a copy of the query that also selects computed columns, set as
+    /// attributes on loaded instances (or keys of `values()` rows).
+    ///
+    /// Each keyword maps a name to an `F` expression, a `ColumnField` or a SQL
+    /// string that is inserted verbatim, such as a `CASE` expression or a
+    /// subquery aggregating a related table.
+    #[pyo3(signature = (**expressions))]
+    fn annotate(&self, expressions: Option<&Bound<PyDict>>) -> PyResult<QueryBuilder> {
+        let mut query = self.clone();
+        let QueryType::Structured {
+            annotations, model, ..
+        } = &mut query.query_type
+        else {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "annotate() can't add columns to a raw SQL query.".to_string(),
+            )
+            .into());
+        };
+        for (name, value) in expressions.into_iter().flatten() {
+            let name = name.extract::<String>()?;
+            if let Some(model) = model
+                && ModelInfo::from_class(model.bind(value.py()))?
+                    .columns
+                    .iter()
+                    .any(|c| c.column_name == name)
+            {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "The annotation '{}' conflicts with a column of the model.",
+                    name
+                ))
+                .into());
+            }
+            let expr = match value.extract::<String>() {
+                Ok(sql) => Expr::Raw(sql),
+                Err(_) => Expr::from_operand(&value),
+            };
+            annotations.push((name, expr));
+        }
+        Ok(query)
+    }
+
+    /// Returns a copy of the query that loads instances without the given
+    /// columns, e.g. large text or binary data for list views.
+    ///
+    /// Deferred columns read as awaitables loading them with the bound database
+    /// until they're fetched with `instance.load_deferred(db)`, and `save()`
+    /// leaves them unchanged.
+    #[pyo3(signature = (*columns))]
+    fn defer(&self, py: Python, columns: &Bound<PyTuple>) -> PyResult<QueryBuilder> {
+        let (info, selected) = self.loaded_columns(py, "defer")?;
+        let deferred = column_names(&info, columns)?;
+        if let Some(column) = info
+            .columns
+            .iter()
+            .find(|c| deferred.contains(&c.column_name) && always_loaded(py, self, c))
+        {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "Column '{}' is needed to save instances and can't be deferred.",
+                column.column_name
+            ))
+            .into());
+        }
+        let columns = selected
+            .into_iter()
+            .filter(|name| !deferred.contains(name))
+            .collect();
+        Ok(self.with_columns(columns))
+    }
+
+    /// Returns a copy of the query that loads instances with the given columns
+    /// only, deferring all others (see `defer()`). Primary key, version and
+    /// discriminator columns are always loaded.
+    #[pyo3(signature = (*columns))]
+    fn only(&self, py: Python, columns: &Bound<PyTuple>) -> PyResult<QueryBuilder> {
+        let (info, _) = self.loaded_columns(py, "only")?;
+        let names = column_names(&info, columns)?;
+        let columns = info
+            .columns
             .iter()
-            .map(|p| match py_any_to_string(py, p, "raw query parameter")? {
-                SqlParam::Single(s) => Ok(s),
-                SqlParam::List(vec) => Ok(vec.join(", ")),
+            .filter(|c| names.contains(&c.column_name) || always_loaded(py, self, c))
+            .map(|c| c.column_name.clone())
+            .collect();
+        Ok(self.with_columns(columns))
+    }
+
+    /// Returns a copy of the query that locks the selected rows until the end of
+    /// the surrounding transaction (`SELECT ... FOR UPDATE`).
+    ///
+    /// With `skip_locked`, rows locked by other transactions are left out, so
+    /// that concurrent workers each claim different rows; with `nowait`, the
+    /// query fails instead of waiting for them. SQLite has no row locks, so the
+    /// clause is omitted there.
+    #[pyo3(signature = (*, skip_locked = false, nowait = false))]
+    fn for_update(&self, skip_locked: bool, nowait: bool) -> PyResult<QueryBuilder> {
+        let wait = LockWait::from_flags("for_update", skip_locked, nowait)?;
+        self.with_lock("for_update", |_| {
+            Ok(RowLock {
+                shared: false,
+                wait,
             })
-            .collect::<PyResult<Vec<String>>>()?;
-        Ok((sql.to_string(), string_params))
+        })
+    }
+
+    /// Returns a copy of the query that locks the selected rows against
+    /// updates and deletes by other transactions, which may still read and
+    /// share-lock them (`SELECT ... FOR SHARE`), with the same options as
+    /// `for_update()`.
+    #[pyo3(signature = (*, skip_locked = false, nowait = false))]
+    fn for_share(&self, skip_locked: bool, nowait: bool) -> PyResult<QueryBuilder> {
+        let wait = LockWait::from_flags("for_share", skip_locked, nowait)?;
+        self.with_lock("for_share", |_| Ok(RowLock { shared: true, wait }))
+    }
+
+    /// Returns a copy of the query whose row lock leaves out the rows locked
+    /// by other transactions, as `for_update(skip_locked=True)` does.
+    fn skip_locked(&self) -> PyResult<QueryBuilder> {
+        self.with_wait("skip_locked", LockWait::SkipLocked)
+    }
+
+    /// Returns a copy of the query whose row lock fails instead of waiting for
+    /// rows locked by other transactions, as `for_update(nowait=True)` does.
+    fn nowait(&self) -> PyResult<QueryBuilder> {
+        self.with_wait("nowait", LockWait::NoWait)
+    }
+
+    /// Returns a copy of the query with additional conditions, such as
+    /// `F("ends_at") > F("starts_at")`.
+    #[pyo3(signature = (*conditions))]
+    fn where_(&self, conditions: Vec<WhereCondition>) -> PyResult<QueryBuilder> {
+        let mut query = self.clone();
+        match &mut query.query_type {
+            QueryType::Structured { where_clauses, .. } => {
+                where_clauses.extend(conditions.into_iter().map(Arc::new))
+            }
+            QueryType::Raw { .. } => {
+                return Err(FustOrmError::InvalidQueryArgument(
+                    "where_() can't add conditions to a raw SQL query.".to_string(),
+                )
+                .into());
+            }
+        }
+        Ok(query)
+    }
+
+    /// Returns a copy of the query combined with `other`, a query of the same
+    /// table such as a reusable filter: the rows match the conditions of
+    /// both, with their parameters in order, and those they share once.
+    ///
+    /// The columns either query chose are selected (a query of all columns
+    /// leaves the other's choice), and the annotations of both are added.
+    /// The order and row lock of `other` apply if the query has none. With
+    /// queries of a model and of its subclass, the subclass is loaded.
+    fn merge(&self, py: Python, other: &QueryBuilder) -> PyResult<QueryBuilder> {
+        let mut query = self.clone();
+        let (
+            QueryType::Structured {
+                table,
+                columns,
+                where_clauses,
+                annotations,
+                lock,
+                order_by,
+                model,
+            },
+            QueryType::Structured {
+                table: other_table,
+                columns: other_columns,
+                where_clauses: other_where,
+                annotations: other_annotations,
+                lock: other_lock,
+                order_by: other_order,
+                model: other_model,
+            },
+        ) = (&mut query.query_type, &other.query_type)
+        else {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "merge() can't combine raw SQL queries.".to_string(),
+            )
+            .into());
+        };
+        let invalid = |message: String| -> PyResult<QueryBuilder> {
+            Err(FustOrmError::InvalidQueryArgument(message).into())
+        };
+        if table != other_table {
+            return invalid(format!(
+                "merge() can't combine queries of '{}' and '{}'.",
+                table, other_table
+            ));
+        }
+        if let (Some(ours), Some(theirs)) = (&model, other_model) {
+            let (ours, theirs) = (ours.bind(py), theirs.bind(py));
+            if theirs.is_subclass(ours)? {
+                *model = other_model.clone();
+            } else if !ours.is_subclass(theirs)? {
+                return invalid(format!(
+                    "merge() can't combine queries of models {} and {}.",
+                    ours.name()?,
+                    theirs.name()?
+                ));
+            }
+        } else if model.is_none() {
+            *model = other_model.clone();
+        }
+        if columns.is_empty() {
+            *columns = other_columns.clone();
+        } else if !other_columns.is_empty() {
+            let mut merged = columns.to_vec();
+            for column in other_columns.iter() {
+                if !merged.contains(column) {
+                    merged.push(column.clone());
+                }
+            }
+            *columns = merged.into();
+        }
+        // Both queries may have the default scope of their model, or have
+        // been built from the same fragment.
+        let render = |condition: &WhereCondition| {
+            let mut params = Vec::new();
+            let sql = condition.to_sql(py, Dialect::Sqlite, &mut params).ok()?;
+            Some((sql, params))
+        };
+        let mut rendered: Vec<_> = where_clauses.iter().map(|c| render(c)).collect();
+        for condition in other_where {
+            let sql = render(condition);
+            if sql.is_none() || !rendered.contains(&sql) {
+                where_clauses.push(condition.clone());
+                rendered.push(sql);
+            }
+        }
+        for (name, expr) in other_annotations {
+            if annotations.iter().any(|(existing, _)| existing == name) {
+                return invalid(format!(
+                    "merge() can't combine two annotations named '{}'.",
+                    name
+                ));
+            }
+            annotations.push((name.clone(), expr.clone()));
+        }
+        match (&lock, other_lock) {
+            (Some(_), Some(_)) => {
+                return invalid("merge() can't combine two row locks.".to_string());
+            }
+            (None, Some(_)) => *lock = *other_lock,
+            _ => {}
+        }
+        match (&order_by, other_order) {
+            (Some(_), Some(_)) => {
+                return invalid("merge() can't combine two orders.".to_string());
+            }
+            (None, Some(_)) => *order_by = other_order.clone(),
+            _ => {}
+        }
+        Ok(query)
+    }
+
+    /// Returns `function(query, *args, **kwargs)`, which builds on the query,
+    /// so that reusable fragments chain with the query's own methods:
+    /// `User.query().apply(active).apply(created_between, start, end)`.
+    #[pyo3(signature = (function, *args, **kwargs))]
+    fn apply(
+        slf: &Bound<Self>,
+        function: &Bound<PyAny>,
+        args: &Bound<PyTuple>,
+        kwargs: Option<&Bound<PyDict>>,
+    ) -> PyResult<QueryBuilder> {
+        let args: Vec<_> = std::iter::once(slf.as_any().clone())
+            .chain(args.iter())
+            .collect();
+        let args = PyTuple::new(slf.py(), args)?;
+        let result = function.call(args, kwargs)?;
+        match result.extract::<QueryBuilder>() {
+            Ok(query) => Ok(query),
+            Err(_) => Err(pyo3::exceptions::PyTypeError::new_err(format!(
+                "The function passed to apply() must return a QueryBuilder, not {}.",
+                result.get_type().name()?
+            ))),
+        }
+    }
+
+    /// The table a structured query selects from, `None` for raw SQL.
+    #[getter]
+    fn table(&self) -> Option<&str> {
+        match &self.query_type {
+            QueryType::Structured { table, .. } => Some(table),
+            QueryType::Raw { .. } => None,
+        }
+    }
+
+    /// The model class the query was built from, if any.
+    #[getter]
+    fn model(&self, py: Python) -> Option<Py<PyType>> {
+        match &self.query_type {
+            QueryType::Structured {
+                model: Some(model), ..
+            } => Some(model.clone_ref(py)),
+            _ => None,
+        }
+    }
+
+    /// Returns a copy of the query keeping the rows of an FTS5 full-text index
+    /// (see `FtsTable`) that match `text`, a full-text query such as
+    /// `"rust AND orm*"`, most relevant first.
+    ///
+    /// Relevance is FTS5's `rank`, the bm25 score of the row; `weights` weigh
+    /// matches in each column of the index, in order.
+    #[pyo3(signature = (text, *, weights = None))]
+    fn search(
+        &self,
+        py: Python,
+        text: Py<PyString>,
+        weights: Option<Vec<f64>>,
+    ) -> PyResult<QueryBuilder> {
+        let mut query = self.clone();
+        let QueryType::Structured {
+            table,
+            where_clauses,
+            order_by,
+            ..
+        } = &mut query.query_type
+        else {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "search() can't search a raw SQL query; use MATCH in the SQL.".to_string(),
+            )
+            .into());
+        };
+        where_clauses.push(Arc::new(WhereCondition::from_expression(
+            Expr::Column(table.to_string()),
+            "MATCH",
+            text.into_any(),
+        )));
+        *order_by = Some(match weights {
+            Some(weights) => fts::bm25_expr(py, table, &weights)?,
+            None => Expr::Column("rank".to_string()),
+        });
+        Ok(query)
+    }
+
+    /// Updates all rows matching the query's conditions in a single statement.
+    ///
+    /// `values` maps column names to new values, which may be
+    /// `F` expressions evaluated by the database: `{"counter": F("counter") + 1}`
+    /// increments without reading the rows first. If the model has a version
+    /// column, it's incremented as well. No save signals are sent. Resolves to
+    /// the number of updated rows.
+    fn update<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+        values: &Bound<'py, PyDict>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let rewritten = self.scoped(py, db)?;
+        let QueryType::Structured {
+            table,
+            where_clauses,
+            model,
+            ..
+        } = &rewritten.as_ref().unwrap_or(self).query_type
+        else {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "update() requires a structured query; use Database.execute() for raw SQL."
+                    .to_string(),
+            )
+            .into());
+        };
+        if values.is_empty() {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "update() needs at least one column to set.".to_string(),
+            )
+            .into());
+        }
+        let info = model
+            .as_ref()
+            .map(|model| ModelInfo::from_class(model.bind(py)))
+            .transpose()?;
+        if let Some(info) = &info {
+            info.check_writable()?;
+        }
+
+        let mut assignments = Vec::new();
+        let mut params = Vec::new();
+        for (key, value) in values.iter() {
+            let name = key.extract::<String>()?;
+            let column = info
+                .as_ref()
+                .and_then(|info| info.columns.iter().find(|c| c.column_name == name));
+            if let Some(column) = column
+                && column.version
+            {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "The version column '{}' is managed automatically and can't be updated.",
+                    name
+                ))
+                .into());
+            }
+            if info.is_some() && column.is_none() {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "'{}' is not a column of '{}'.",
+                    name, table
+                ))
+                .into());
+            }
+            let sql = if value.is_instance_of::<F>() || value.is_instance_of::<ColumnField>() {
+                Expr::from_operand(&value).to_sql(py, &mut params)?
+            } else if let Some(column) = column {
+                match column_param(py, &value, column)? {
+                    Some(param) => {
+                        params.push(param);
+                        "?".to_string()
+                    }
+                    None => "NULL".to_string(),
+                }
+            } else {
+                Expr::Value(Arc::new(value.unbind())).to_sql(py, &mut params)?
+            };
+            assignments.push(format!("{} = {}", name, sql));
+        }
+        if let Some(column) = info.as_ref().and_then(|info| info.version_column()) {
+            assignments.push(format!("{0} = {0} + 1", column.column_name));
+        }
+
+        let mut sql = format!("UPDATE {} SET {}", table, assignments.join(", "));
+        if !where_clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&where_sql(py, db.dialect(), where_clauses, &mut params)?);
+        }
+        let db = context::resolve(py, Some(db))?;
+
+        future_into_py(py, async move {
+            let result = db.execute_statement(&sql, params).await?;
+            Ok(result.rows_affected())
+        })
     }
 }
 
@@ -177,6 +1134,7 @@ pub fn select(_py: Python, args: &Bound<'_, PyTuple>) -> PyResult<QueryBuilder>
         args.len()
     );
     let mut table_name = None;
+    let mut model = None;
     let mut columns = Vec::new();
     let mut where_clauses = Vec::new();
 
@@ -186,9 +1144,17 @@ pub fn select(_py: Python, args: &Bound<'_, PyTuple>) -> PyResult<QueryBuilder>
         {
             // If a Model class is passed, select all its columns (represented by '*')
             // and determine the table name from it.
-            if table_name.is_none() {
+            if model.is_none() {
                 let name_attr = py_type.getattr("__table_name__")?;
-                table_name = Some(name_attr.extract::<String>()?);
+                let model_table = name_attr.extract::<String>()?;
+                if table_name.as_ref().is_some_and(|t| t != &model_table) {
+                    return Err(FustOrmError::InvalidQueryArgument(
+                        "Cannot select columns from multiple tables in one query.".to_string(),
+                    )
+                    .into());
+                }
+                table_name = Some(model_table);
+                model = Some(Arc::new(py_type.clone().unbind()));
             } else {
                 return Err(FustOrmError::InvalidQueryArgument(
                         "Cannot select from multiple tables by passing multiple Model classes. For joins, use raw SQL.".to_string(),
@@ -209,7 +1175,7 @@ pub fn select(_py: Python, args: &Bound<'_, PyTuple>) -> PyResult<QueryBuilder>
             }
             columns.push(col_field.column_name.clone());
         } else if let Ok(where_cond) = arg.extract::<PyRef<WhereCondition>>() {
-            where_clauses.push(where_cond.clone());
+            where_clauses.push(Arc::new(where_cond.clone()));
         } else {
             return Err(FustOrmError::InvalidQueryArgument(format!(
                 "Unsupported argument type in select(): {}",
@@ -234,42 +1200,203 @@ pub fn select(_py: Python, args: &Bound<'_, PyTuple>) -> PyResult<QueryBuilder>
 
     Ok(QueryBuilder {
         query_type: QueryType::Structured {
-            table: final_table_name,
-            columns,
+            table: final_table_name.into(),
+            columns: columns.into(),
             where_clauses,
+            annotations: Vec::new(),
+            lock: None,
+            order_by: None,
+            model,
         },
     })
 }
 
+/// Resolves `ColumnField`s and names to columns of the model.
+fn column_names(info: &ModelInfo, columns: &Bound<PyTuple>) -> PyResult<Vec<String>> {
+    columns
+        .iter()
+        .map(|column| {
+            let name = match column.extract::<PyRef<ColumnField>>() {
+                Ok(column) => column.column_name.clone(),
+                Err(_) => column.extract::<String>()?,
+            };
+            if !info.columns.iter().any(|c| c.column_name == name) {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "'{}' is not a column of '{}'.",
+                    name, info.table_name
+                ))
+                .into());
+            }
+            Ok(name)
+        })
+        .collect()
+}
+
+/// Whether a column has to be loaded for instances to be saved and loaded as
+/// the right class: primary key, version and discriminator columns.
+fn always_loaded(py: Python, query: &QueryBuilder, column: &ColumnField) -> bool {
+    if column.primary_key || column.version {
+        return true;
+    }
+    let QueryType::Structured {
+        model: Some(model), ..
+    } = &query.query_type
+    else {
+        return false;
+    };
+    model
+        .bind(py)
+        .getattr(pyo3::intern!(py, "__discriminator__"))
+        .and_then(|d| d.extract::<Option<String>>())
+        .is_ok_and(|d| d.as_deref() == Some(column.column_name.as_str()))
+}
+
+/// Renders the row lock of a query, if the backend supports it.
+fn lock_clause(lock: Option<RowLock>, db: &Database) -> &'static str {
+    match lock {
+        Some(lock) if db.supports_row_locks() => lock.clause(db.dialect()),
+        Some(_) => {
+            debug!("Omitting row lock: the backend doesn't support row locks.");
+            ""
+        }
+        None => "",
+    }
+}
+
+/// Renders the `ORDER BY` clause of a query, pushing its parameters.
+fn order_by_sql(py: Python, order_by: Option<&Expr>, params: &mut Vec<String>) -> PyResult<String> {
+    match order_by {
+        Some(expr) => Ok(format!(" ORDER BY {}", expr.to_sql(py, params)?)),
+        None => Ok(String::new()),
+    }
+}
+
+/// The columns of `model` when querying PostgreSQL whose values the Any
+/// driver can't decode, with the expressions selecting them: arrays, ranges,
+/// and enum and composite types registered with `register_type()` are
+/// selected as text, intervals as their number of seconds, which their
+/// `ColumnField` parses.
+fn cast_columns(
+    py: Python,
+    model: Option<&Arc<Py<PyType>>>,
+    db: &Database,
+) -> PyResult<Vec<(String, String)>> {
+    let Some(model) = model.filter(|_| db.dialect() == Dialect::Postgres) else {
+        return Ok(Vec::new());
+    };
+    Ok(ModelInfo::from_class(model.bind(py))?
+        .columns
+        .into_iter()
+        .filter_map(|column| {
+            let name = column.column_name;
+            if column.interval {
+                let cast = format!("EXTRACT(EPOCH FROM {})::text", name);
+                return Some((name, cast));
+            }
+            let as_text = column.array_type.is_some()
+                || column.range_type.is_some()
+                || column.composite_type.is_some()
+                || column.enum_type.as_ref().is_some_and(|enum_type| {
+                    matches!(custom_type::type_name(enum_type.bind(py)), Ok(Some(_)))
+                });
+            if !as_text {
+                return None;
+            }
+            let cast = format!("{}::text", name);
+            Some((name, cast))
+        })
+        .collect())
+}
+
+/// Renders the select list: the columns (all of the table's if empty),
+/// followed by the annotations.
+fn select_list(
+    py: Python,
+    table: &str,
+    columns: Vec<String>,
+    casts: &[(String, String)],
+    annotations: &[(String, Expr)],
+    params: &mut Vec<String>,
+) -> PyResult<String> {
+    let mut items: Vec<String> = columns
+        .into_iter()
+        .map(
+            |column| match casts.iter().find(|(name, _)| *name == column) {
+                Some((_, cast)) => format!("{} AS {}", cast, column),
+                None => column,
+            },
+        )
+        .collect();
+    if items.is_empty() {
+        // A bare `*` can't be followed by other items on every backend.
+        items.push(if annotations.is_empty() {
+            "*".to_string()
+        } else {
+            format!("{}.*", table)
+        });
+    }
+    for (name, expr) in annotations {
+        items.push(format!("{} AS {}", expr.to_sql(py, params)?, name));
+    }
+    Ok(items.join(", "))
+}
+
+/// Renders the placeholders of a list of values, pushing them. The list is
+/// padded to a power of two by repeating its last value, so that lists of
+/// similar lengths share one statement in the statement cache.
+pub(crate) fn in_list(mut values: Vec<String>, params: &mut Vec<String>) -> String {
+    if let Some(last) = values.last().cloned() {
+        values.resize(values.len().next_power_of_two(), last);
+    }
+    let placeholders = vec!["?"; values.len()].join(", ");
+    params.extend(values);
+    format!("({})", placeholders)
+}
+
+/// Renders conditions joined with `AND`, pushing their parameters.
+pub(crate) fn where_sql(
+    py: Python,
+    dialect: Dialect,
+    where_clauses: &[Arc<WhereCondition>],
+    params: &mut Vec<String>,
+) -> PyResult<String> {
+    Ok(where_clauses
+        .iter()
+        .map(|cond| cond.to_sql(py, dialect, params))
+        .collect::<PyResult<Vec<_>>>()?
+        .join(" AND "))
+}
+
 pub enum SqlParam {
     Single(String),
     List(Vec<String>),
 }
 
 /// Converts a Python object (`Py<PyAny>`) into a String for SQL parameter binding.
-fn py_any_to_string(py: Python, value: &Py<PyAny>, context: &str) -> PyResult<SqlParam> {
+pub(crate) fn py_any_to_string(py: Python, value: &Py<PyAny>, context: &str) -> PyResult<SqlParam> {
     let bound_val = value.bind(py);
-    if let Ok(s) = bound_val.downcast::<PyString>() {
+    // `bool` is a subclass of `int` in Python, so it has to be checked first.
+    if let Ok(b) = bound_val.downcast::<PyBool>() {
+        Ok(SqlParam::Single(
+            (if b.is_true() { "1" } else { "0" }).to_string(),
+        ))
+    } else if let Ok(s) = bound_val.downcast::<PyString>() {
         Ok(SqlParam::Single(s.to_string()))
     } else if let Ok(i) = bound_val.downcast::<PyInt>() {
         Ok(SqlParam::Single(i.to_string()))
     } else if let Ok(f) = bound_val.downcast::<PyFloat>() {
         Ok(SqlParam::Single(f.to_string()))
-    } else if let Ok(b) = bound_val.downcast::<PyBool>() {
-        Ok(SqlParam::Single(
-            (if b.is_true() { "1" } else { "0" }).to_string(),
-        ))
     } else if let Ok(list) = bound_val.downcast::<PyList>() {
         let mut string_vec: Vec<String> = Vec::with_capacity(list.len());
         for item in list.iter() {
-            if let Ok(s) = item.downcast::<PyString>() {
+            if let Ok(b) = item.downcast::<PyBool>() {
+                string_vec.push((if b.is_true() { "1" } else { "0" }).to_string());
+            } else if let Ok(s) = item.downcast::<PyString>() {
                 string_vec.push(s.to_string());
             } else if let Ok(i) = item.downcast::<PyInt>() {
                 string_vec.push(i.to_string());
             } else if let Ok(f) = item.downcast::<PyFloat>() {
                 string_vec.push(f.to_string());
-            } else if let Ok(b) = item.downcast::<PyBool>() {
-                string_vec.push(b.to_string());
             } else {
                 return Err(FustOrmError::BuildError(format!(
                     "Unsupported parameter type in list for '{}': {}",
@@ -280,6 +1407,10 @@ fn py_any_to_string(py: Python, value: &Py<PyAny>, context: &str) -> PyResult<Sq
             }
         }
         Ok(SqlParam::List(string_vec))
+    } else if let Ok(delta) = bound_val.downcast::<PyDelta>() {
+        Ok(SqlParam::Single(interval::seconds_literal(delta)))
+    } else if let Ok(range) = bound_val.downcast::<Range>() {
+        Ok(SqlParam::Single(range.get().to_literal(py)?))
     } else {
         Err(FustOrmError::BuildError(format!(
             "Unsupported parameter type for '{}': {}",
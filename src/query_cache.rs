@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+use pyo3_async_runtimes::tokio::future_into_py;
+
+use crate::context;
+use crate::database::Database;
+use crate::decode::RowBatch;
+use crate::error::FustOrmError;
+use crate::pool_registry::PoolKey;
+
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// A pool, the SQL of a query and its parameters.
+type Key = (PoolKey, String, Vec<String>);
+
+struct Entry {
+    /// The tables the query reads, lowercase.
+    tables: Vec<String>,
+    rows: Arc<RowBatch>,
+    size: usize,
+    expires: Instant,
+    /// When the entry was last read, on the clock of the cache.
+    used: u64,
+}
+
+struct Cache {
+    entries: HashMap<Key, Entry>,
+    /// The size of the entries, in bytes.
+    size: usize,
+    max_entries: usize,
+    max_bytes: usize,
+    /// Counts the reads and writes, to find the entry used least recently.
+    clock: u64,
+}
+
+impl Cache {
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.size -= entry.size;
+        }
+    }
+
+    /// Drops the expired entries and those of closed pools, then the ones
+    /// used least recently until the cache fits its limits.
+    fn evict(&mut self) {
+        let now = Instant::now();
+        let stale: Vec<Key> = self
+            .entries
+            .iter()
+            .filter(|(key, entry)| entry.expires <= now || !key.0.is_live())
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &stale {
+            self.remove(key);
+        }
+        while self.entries.len() > self.max_entries || self.size > self.max_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+}
+
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+fn with_cache<T>(f: impl FnOnce(&mut Cache) -> T) -> T {
+    let mut guard: MutexGuard<Option<Cache>> = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    f(guard.get_or_insert_with(|| Cache {
+        entries: HashMap::new(),
+        size: 0,
+        max_entries: DEFAULT_MAX_ENTRIES,
+        max_bytes: DEFAULT_MAX_BYTES,
+        clock: 0,
+    }))
+}
+
+/// The tables a query names after `FROM` and `JOIN`, lowercase and without
+/// their schema.
+fn referenced_tables(sql: &str) -> Vec<String> {
+    let spaced = sql.replace(['(', ')'], " ( ").replace(',', " , ");
+    let words: Vec<&str> = spaced.split_whitespace().collect();
+    let mut tables: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let keyword = words[i];
+        i += 1;
+        if !keyword.eq_ignore_ascii_case("FROM") && !keyword.eq_ignore_ascii_case("JOIN") {
+            continue;
+        }
+        // A list of tables, each possibly aliased, until a keyword or a
+        // subquery.
+        while let Some(word) = words.get(i) {
+            if *word == "(" {
+                break;
+            }
+            let name = word
+                .rsplit('.')
+                .next()
+                .unwrap_or_default()
+                .trim_matches(['"', '`', '[', ']', ';'])
+                .to_lowercase();
+            if !name.is_empty() && !tables.contains(&name) {
+                tables.push(name);
+            }
+            i += 1;
+            // Skips the alias.
+            while words.get(i).is_some_and(|w| *w != "," && *w != "(") {
+                let next = words[i];
+                if next.eq_ignore_ascii_case("AS") || is_alias(next) {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            if words.get(i) == Some(&",") {
+                i += 1;
+            } else {
+                break;
+            }
+        }
+    }
+    tables
+}
+
+/// Whether a word following a table name is its alias rather than the
+/// keyword of the next clause.
+fn is_alias(word: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "WHERE",
+        "JOIN",
+        "INNER",
+        "LEFT",
+        "RIGHT",
+        "FULL",
+        "CROSS",
+        "NATURAL",
+        "ON",
+        "USING",
+        "GROUP",
+        "ORDER",
+        "HAVING",
+        "LIMIT",
+        "OFFSET",
+        "UNION",
+        "INTERSECT",
+        "EXCEPT",
+        "WINDOW",
+        "FOR",
+        "RETURNING",
+        "SET",
+        "VALUES",
+        "FETCH",
+    ];
+    !KEYWORDS.iter().any(|k| word.eq_ignore_ascii_case(k)) && !word.ends_with(';')
+}
+
+/// Sets the limits of the cache of `Database.cached()`, shared by all
+/// databases, evicting the entries used least recently to fit them.
+///
+/// Args:
+///     max_entries (int): The most results kept; 1024 by default.
+///     max_bytes (int): The largest estimated size of the results kept, in
+///         bytes; 64 MiB by default. A larger result isn't cached.
+#[pyfunction]
+#[pyo3(signature = (*, max_entries = DEFAULT_MAX_ENTRIES, max_bytes = DEFAULT_MAX_BYTES))]
+pub(crate) fn configure_query_cache(max_entries: usize, max_bytes: usize) {
+    with_cache(|cache| {
+        cache.max_entries = max_entries;
+        cache.max_bytes = max_bytes;
+        cache.evict();
+    });
+}
+
+/// Runs `query` for `Database.cached()`, unless a result of it younger than
+/// its `ttl` is cached.
+pub(crate) fn cached<'py>(
+    py: Python<'py>,
+    db: &Database,
+    query: &Bound<'py, PyAny>,
+    ttl: f64,
+) -> PyResult<Bound<'py, PyAny>> {
+    if !(ttl > 0.0 && ttl.is_finite()) {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "ttl must be a positive number of seconds.".to_string(),
+        )
+        .into());
+    }
+    let ttl = Duration::from_secs_f64(ttl);
+    let (sql, params) = db.render(py, query)?;
+    let db = context::resolve(py, Some(db))?;
+    future_into_py(py, async move {
+        let key = (PoolKey::of(&db.pool), sql, params);
+        // A transaction sees its own writes, which mustn't be shared.
+        let cached = match db.tx {
+            Some(_) => None,
+            None => with_cache(|cache| {
+                cache.clock += 1;
+                let now = cache.clock;
+                let entry = cache
+                    .entries
+                    .get_mut(&key)
+                    .filter(|entry| entry.expires > Instant::now())?;
+                entry.used = now;
+                Some(entry.rows.clone())
+            }),
+        };
+        let rows = match cached {
+            Some(rows) => {
+                debug!("Cached result of: \"{}\"", key.1);
+                rows
+            }
+            None => {
+                let (_, sql, params) = &key;
+                let fetched = db.fetch_rows(sql, params.clone()).await?;
+                let rows = Arc::new(RowBatch::decode(&fetched)?);
+                if db.tx.is_none() {
+                    let size = rows.size() + sql.len();
+                    with_cache(|cache| {
+                        if size > cache.max_bytes || cache.max_entries == 0 {
+                            return;
+                        }
+                        cache.clock += 1;
+                        let entry = Entry {
+                            tables: referenced_tables(sql),
+                            rows: rows.clone(),
+                            size,
+                            expires: Instant::now() + ttl,
+                            used: cache.clock,
+                        };
+                        cache.remove(&key);
+                        cache.size += size;
+                        cache.entries.insert(key.clone(), entry);
+                        cache.evict();
+                    });
+                }
+                rows
+            }
+        };
+        Python::attach(|py| -> PyResult<Py<PyList>> {
+            Ok(PyList::new(py, rows.to_dicts(py)?)?.unbind())
+        })
+    })
+}
+
+/// Drops the cached results of the queries of `db` reading any of `tables`,
+/// or all of them without tables. Returns how many were dropped.
+pub(crate) fn invalidate(db: &Database, tables: &[String]) -> usize {
+    let pool = PoolKey::of(&db.pool);
+    let tables: Vec<String> = tables
+        .iter()
+        .map(|t| t.rsplit('.').next().unwrap_or_default().to_lowercase())
+        .collect();
+    with_cache(|cache| {
+        let stale: Vec<Key> = cache
+            .entries
+            .iter()
+            .filter(|(key, entry)| {
+                key.0 == pool
+                    && (tables.is_empty() || entry.tables.iter().any(|t| tables.contains(t)))
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &stale {
+            cache.remove(key);
+        }
+        stale.len()
+    })
+}
@@ -0,0 +1,207 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pyo3::exceptions::PyAssertionError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::Row;
+use sqlx::any::AnyRow;
+
+use crate::database::Database;
+use crate::error::FustOrmError;
+use crate::maintenance::query_plan;
+use crate::schema::Dialect;
+
+/// The query lists of the `record_queries()` blocks entered, which the
+/// statements run are appended to.
+static RECORDERS: Mutex<Vec<Arc<Py<PyList>>>> = Mutex::new(Vec::new());
+
+/// Creates a recorder for `Database.record_queries()`.
+pub(crate) fn record_queries(py: Python, db: &Database) -> QueryRecorder {
+    QueryRecorder {
+        db: db.clone(),
+        queries: Arc::new(PyList::empty(py).unbind()),
+    }
+}
+
+/// Appends a statement that ran to the queries of the recorders entered.
+pub(crate) fn record(sql: &str, params: &[String], elapsed: Duration) {
+    let recorders = RECORDERS.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if recorders.is_empty() {
+        return;
+    }
+    Python::attach(|py| {
+        let query = || -> PyResult<Bound<PyDict>> {
+            let query = PyDict::new(py);
+            query.set_item("sql", sql)?;
+            query.set_item("params", params)?;
+            query.set_item("elapsed_ms", elapsed.as_secs_f64() * 1000.0)?;
+            Ok(query)
+        };
+        for queries in recorders {
+            // Building and appending a dict of strings and numbers can't fail.
+            if let Ok(query) = query() {
+                let _ = queries.bind(py).append(query);
+            }
+        }
+    });
+}
+
+/// Whether the plan of a statement is worth checking for table scans.
+fn reads_tables(sql: &str) -> bool {
+    let keyword = sql.split_whitespace().next().unwrap_or("");
+    ["SELECT", "WITH", "UPDATE", "DELETE"]
+        .iter()
+        .any(|k| keyword.eq_ignore_ascii_case(k))
+}
+
+/// The steps of a plan reading a whole table: `SCAN` steps not using an
+/// index on SQLite, `Seq Scan` nodes on PostgreSQL and accesses of type `ALL`
+/// on MySQL.
+fn table_scans(dialect: Dialect, plan: &[AnyRow]) -> Result<Vec<String>, FustOrmError> {
+    let mut scans = Vec::new();
+    for row in plan {
+        match dialect {
+            Dialect::Sqlite => {
+                let detail: String = row.try_get("detail")?;
+                if detail.starts_with("SCAN ")
+                    && !detail.contains(" INDEX ")
+                    && detail != "SCAN CONSTANT ROW"
+                {
+                    scans.push(detail);
+                }
+            }
+            Dialect::Postgres => {
+                let line: String = row.try_get(0)?;
+                if line.contains("Seq Scan on ") {
+                    scans.push(line.trim().trim_start_matches("-> ").to_string());
+                }
+            }
+            Dialect::Mysql => {
+                let access: Option<String> = row.try_get("type")?;
+                if access.as_deref() == Some("ALL") {
+                    let table: Option<String> = row.try_get("table")?;
+                    scans.push(format!("ALL on {}", table.unwrap_or_default()));
+                }
+            }
+        }
+    }
+    Ok(scans)
+}
+
+/// The context manager returned by `Database.record_queries()`, which records
+/// the statements run while it's entered, with their parameters and timings.
+#[pyclass(module = "fust_orm")]
+pub struct QueryRecorder {
+    db: Database,
+    queries: Arc<Py<PyList>>,
+}
+
+impl QueryRecorder {
+    /// Lists the statements recorded, numbered, for an assertion message.
+    fn report(&self, py: Python) -> PyResult<String> {
+        let mut report = String::new();
+        for (i, query) in self.queries.bind(py).iter().enumerate() {
+            let sql = query.get_item("sql")?;
+            report.push_str(&format!("\n  {}. {}", i + 1, sql));
+        }
+        Ok(report)
+    }
+}
+
+#[pymethods]
+impl QueryRecorder {
+    /// The statements run so far, in order, as `{"sql": ..., "params": [...],
+    /// "elapsed_ms": ...}` dicts.
+    #[getter]
+    fn queries(&self, py: Python) -> Py<PyList> {
+        self.queries.clone_ref(py)
+    }
+
+    /// The time the statements took in total, in milliseconds.
+    #[getter]
+    fn elapsed_ms(&self, py: Python) -> PyResult<f64> {
+        let mut total = 0.0;
+        for query in self.queries.bind(py).iter() {
+            total += query.get_item("elapsed_ms")?.extract::<f64>()?;
+        }
+        Ok(total)
+    }
+
+    fn __len__(&self, py: Python) -> usize {
+        self.queries.bind(py).len()
+    }
+
+    /// Fails with `AssertionError`, listing the statements, unless exactly
+    /// `expected` statements ran.
+    fn assert_count(&self, py: Python, expected: usize) -> PyResult<()> {
+        let count = self.queries.bind(py).len();
+        if count != expected {
+            return Err(PyAssertionError::new_err(format!(
+                "Expected {} queries, {} ran:{}",
+                expected,
+                count,
+                self.report(py)?
+            )));
+        }
+        Ok(())
+    }
+
+    /// Explains the `SELECT`, `UPDATE` and `DELETE` statements recorded, and
+    /// fails with `AssertionError`, listing them, if any of them reads a whole
+    /// table rather than using an index.
+    fn assert_no_table_scans<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let mut statements: Vec<(String, Vec<String>)> = Vec::new();
+        for query in self.queries.bind(py).iter() {
+            let sql: String = query.get_item("sql")?.extract()?;
+            if reads_tables(&sql) {
+                statements.push((sql, query.get_item("params")?.extract()?));
+            }
+        }
+        let db = self.db.clone();
+        future_into_py(py, async move {
+            let dialect = db.dialect();
+            let mut report = String::new();
+            for (sql, params) in statements {
+                let plan = query_plan(&db, &sql, params, false).await?;
+                for scan in table_scans(dialect, &plan)? {
+                    report.push_str(&format!("\n  {}\n    {}", sql, scan));
+                }
+            }
+            if !report.is_empty() {
+                return Err(PyAssertionError::new_err(format!(
+                    "Expected no table scans, found:{}",
+                    report
+                )));
+            }
+            Ok(())
+        })
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        RECORDERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(slf.queries.clone());
+        slf
+    }
+
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        _exc_type: Option<&Bound<PyAny>>,
+        _exc_value: Option<&Bound<PyAny>>,
+        _traceback: Option<&Bound<PyAny>>,
+    ) -> bool {
+        RECORDERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|queries| !Arc::ptr_eq(queries, &self.queries));
+        false
+    }
+
+    fn __repr__(&self, py: Python) -> String {
+        format!("<QueryRecorder queries={}>", self.queries.bind(py).len())
+    }
+}
@@ -0,0 +1,297 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyFloat, PyInt, PyString, PyTuple, PyType};
+
+use crate::array::from_text;
+use crate::error::FustOrmError;
+
+/// A range of values, as stored in PostgreSQL's range types (`int4range`,
+/// `tstzrange`, ...): between `lower` and `upper`, either of them included or
+/// not as `bounds` says, and unbounded on a side whose bound is `None`.
+///
+/// ```python
+/// Range(1, 10)  # [1,10): 1 to 9
+/// Range(date(2024, 1, 1), None, "[]")  # from 2024-01-01 on
+/// Range(empty=True)
+/// ```
+///
+/// `Range[int]`, `Range[datetime.datetime]` and the like annotate range
+/// columns.
+#[pyclass(frozen, module = "fust_orm")]
+pub struct Range {
+    lower: Option<Py<PyAny>>,
+    upper: Option<Py<PyAny>>,
+    lower_inc: bool,
+    upper_inc: bool,
+    empty: bool,
+}
+
+impl Range {
+    fn empty() -> Self {
+        Range {
+            lower: None,
+            upper: None,
+            lower_inc: false,
+            upper_inc: false,
+            empty: true,
+        }
+    }
+
+    /// Renders the range as PostgreSQL's text of it, e.g. `[1,10)` or `empty`,
+    /// which it is bound as and other databases store.
+    pub(crate) fn to_literal(&self, py: Python) -> PyResult<String> {
+        if self.empty {
+            return Ok("empty".to_string());
+        }
+        let bound = |value: &Option<Py<PyAny>>| -> PyResult<String> {
+            let Some(value) = value else {
+                return Ok(String::new());
+            };
+            let value = value.bind(py);
+            let text = value.str()?.to_string();
+            if value.is_instance_of::<PyInt>() || value.is_instance_of::<PyFloat>() {
+                Ok(text)
+            } else {
+                Ok(format!(
+                    "\"{}\"",
+                    text.replace('\\', "\\\\").replace('"', "\\\"")
+                ))
+            }
+        };
+        Ok(format!(
+            "{}{},{}{}",
+            if self.lower_inc { '[' } else { '(' },
+            bound(&self.lower)?,
+            bound(&self.upper)?,
+            if self.upper_inc { ']' } else { ')' }
+        ))
+    }
+}
+
+/// Splits the inside of a range's text into its two bounds, `None` for an
+/// unbounded side.
+fn split_bounds(inner: &str) -> Option<(Option<String>, Option<String>)> {
+    let mut bounds = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        let mut bound = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next_if(|c| *c != ',') {
+            match c {
+                '"' => {
+                    quoted = true;
+                    loop {
+                        match chars.next()? {
+                            '"' if chars.next_if_eq(&'"').is_some() => bound.push('"'),
+                            '"' => break,
+                            '\\' => bound.push(chars.next()?),
+                            c => bound.push(c),
+                        }
+                    }
+                }
+                '\\' => bound.push(chars.next()?),
+                c => bound.push(c),
+            }
+        }
+        bounds.push((quoted || !bound.is_empty()).then_some(bound));
+        if chars.next().is_none() {
+            break;
+        }
+    }
+    match <[_; 2]>::try_from(bounds) {
+        Ok([lower, upper]) => Some((lower, upper)),
+        Err(_) => None,
+    }
+}
+
+/// Parses PostgreSQL's text of a range into a `Range`, converting its bounds
+/// with `item_type`.
+pub(crate) fn parse_range<'py>(
+    text: &Bound<'py, PyString>,
+    item_type: Option<&Bound<'py, PyType>>,
+) -> PyResult<Bound<'py, Range>> {
+    let py = text.py();
+    let text = text.to_str()?.trim();
+    if text.eq_ignore_ascii_case("empty") {
+        return Bound::new(py, Range::empty());
+    }
+    let malformed = || FustOrmError::QueryError(format!("Malformed range '{}'.", text));
+    let mut chars = text.chars();
+    let (Some(first), Some(last)) = (chars.next(), chars.next_back()) else {
+        return Err(malformed().into());
+    };
+    let (lower, upper) = split_bounds(chars.as_str()).ok_or_else(malformed)?;
+    let convert = |bound: Option<String>| -> PyResult<Option<Py<PyAny>>> {
+        bound
+            .map(|bound| Ok(from_text(py, bound, item_type)?.unbind()))
+            .transpose()
+    };
+    Bound::new(
+        py,
+        Range {
+            lower: convert(lower)?,
+            upper: convert(upper)?,
+            lower_inc: match first {
+                '[' => true,
+                '(' => false,
+                _ => return Err(malformed().into()),
+            },
+            upper_inc: match last {
+                ']' => true,
+                ')' => false,
+                _ => return Err(malformed().into()),
+            },
+            empty: false,
+        },
+    )
+}
+
+#[pymethods]
+impl Range {
+    #[new]
+    #[pyo3(signature = (lower = None, upper = None, bounds = "[)", *, empty = false))]
+    fn new(
+        lower: Option<Py<PyAny>>,
+        upper: Option<Py<PyAny>>,
+        bounds: &str,
+        empty: bool,
+    ) -> PyResult<Self> {
+        if empty {
+            if lower.is_some() || upper.is_some() {
+                return Err(FustOrmError::InvalidQueryArgument(
+                    "An empty range has no bounds.".to_string(),
+                )
+                .into());
+            }
+            return Ok(Range::empty());
+        }
+        let (lower_inc, upper_inc) = match bounds {
+            "[)" => (true, false),
+            "[]" => (true, true),
+            "()" => (false, false),
+            "(]" => (false, true),
+            _ => {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "Unknown range bounds '{}': expected '[)', '[]', '()' or '(]'.",
+                    bounds
+                ))
+                .into());
+            }
+        };
+        // An unbounded side never includes its bound.
+        Ok(Range {
+            lower_inc: lower_inc && lower.is_some(),
+            upper_inc: upper_inc && upper.is_some(),
+            lower,
+            upper,
+            empty: false,
+        })
+    }
+
+    /// `Range[int]` and the like, to annotate range columns.
+    #[classmethod]
+    fn __class_getitem__<'py>(
+        cls: &Bound<'py, PyType>,
+        item: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        cls.py()
+            .import("types")?
+            .getattr("GenericAlias")?
+            .call1((cls, item))
+    }
+
+    #[getter]
+    fn lower(&self, py: Python) -> Option<Py<PyAny>> {
+        self.lower.as_ref().map(|v| v.clone_ref(py))
+    }
+
+    #[getter]
+    fn upper(&self, py: Python) -> Option<Py<PyAny>> {
+        self.upper.as_ref().map(|v| v.clone_ref(py))
+    }
+
+    #[getter]
+    fn lower_inc(&self) -> bool {
+        self.lower_inc
+    }
+
+    #[getter]
+    fn upper_inc(&self) -> bool {
+        self.upper_inc
+    }
+
+    #[getter]
+    fn is_empty(&self) -> bool {
+        self.empty
+    }
+
+    /// Whether `value` lies within the range.
+    fn __contains__(&self, value: &Bound<PyAny>) -> PyResult<bool> {
+        let py = value.py();
+        if self.empty {
+            return Ok(false);
+        }
+        if let Some(lower) = &self.lower {
+            let lower = lower.bind(py);
+            if value.lt(lower)? || (!self.lower_inc && value.eq(lower)?) {
+                return Ok(false);
+            }
+        }
+        if let Some(upper) = &self.upper {
+            let upper = upper.bind(py);
+            if value.gt(upper)? || (!self.upper_inc && value.eq(upper)?) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn __eq__(&self, py: Python, other: &Bound<PyAny>) -> PyResult<bool> {
+        let Ok(other) = other.downcast::<Range>() else {
+            return Ok(false);
+        };
+        let other = other.get();
+        let same = |a: &Option<Py<PyAny>>, b: &Option<Py<PyAny>>| match (a, b) {
+            (Some(a), Some(b)) => a.bind(py).eq(b),
+            (a, b) => Ok(a.is_none() && b.is_none()),
+        };
+        Ok(self.empty == other.empty
+            && self.lower_inc == other.lower_inc
+            && self.upper_inc == other.upper_inc
+            && same(&self.lower, &other.lower)?
+            && same(&self.upper, &other.upper)?)
+    }
+
+    fn __hash__(&self, py: Python) -> PyResult<isize> {
+        let bound =
+            |value: &Option<Py<PyAny>>| value.as_ref().map_or(py.None(), |v| v.clone_ref(py));
+        PyTuple::new(
+            py,
+            [
+                bound(&self.lower),
+                bound(&self.upper),
+                (self.lower_inc, self.upper_inc, self.empty)
+                    .into_pyobject(py)?
+                    .into_any()
+                    .unbind(),
+            ],
+        )?
+        .hash()
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        if self.empty {
+            return Ok("Range(empty=True)".to_string());
+        }
+        let repr = |value: &Option<Py<PyAny>>| match value {
+            Some(value) => Ok(value.bind(py).repr()?.to_string()),
+            None => Ok::<_, PyErr>("None".to_string()),
+        };
+        Ok(format!(
+            "Range({}, {}, '{}{}')",
+            repr(&self.lower)?,
+            repr(&self.upper)?,
+            if self.lower_inc { '[' } else { '(' },
+            if self.upper_inc { ']' } else { ')' }
+        ))
+    }
+}
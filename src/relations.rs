@@ -0,0 +1,632 @@
+use crate::column_field::ColumnField;
+use crate::context;
+use crate::database::{Database, row_to_dict};
+use crate::decode::RowBatch;
+use crate::error::FustOrmError;
+use crate::model::{Model, ModelInfo, column_param, hydrate};
+use log::debug;
+use pyo3::exceptions::{PyAttributeError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyString, PyTuple, PyType};
+use pyo3_async_runtimes::tokio::future_into_py;
+
+/// Maximum number of owner keys bound to a single prefetch query.
+const PREFETCH_CHUNK_SIZE: usize = 999;
+
+/// Column alias carrying the owner's key in prefetch queries.
+const OWNER_KEY_ALIAS: &str = "_fust_owner_pk";
+
+/// A many-to-many relationship to another model, stored in a through table.
+///
+/// Declared on the owning model, e.g. `tags: ManyToMany[Tag] = ManyToMany(Tag)`.
+/// The through table is named `<owner table>_<attribute>` unless `through` is
+/// given, and holds one row per link with the columns `<owner table>_id` and
+/// `<target table>_id` (`from_<table>_id` / `to_<table>_id` for relations of a
+/// model to itself, declared with `ManyToMany("self")`).
+///
+/// On a model instance the attribute is a `RelatedManager` for that row's links.
+#[pyclass(generic)]
+pub struct ManyToMany {
+    /// The target model class, or `"self"`.
+    target: Py<PyAny>,
+    through: Option<String>,
+    /// Set by `__set_name__` when the owning class is created.
+    owner: Option<Py<PyType>>,
+    name: Option<String>,
+}
+
+/// A `ManyToMany` resolved against its owner and target models.
+struct Relation<'py> {
+    name: String,
+    owner: Bound<'py, PyType>,
+    owner_table: String,
+    owner_pk: ColumnField,
+    target: Bound<'py, PyType>,
+    target_table: String,
+    target_pk: ColumnField,
+    through: String,
+    owner_column: String,
+    target_column: String,
+}
+
+impl<'py> Relation<'py> {
+    /// The instance attribute caching prefetched related objects.
+    fn cache_key(&self) -> String {
+        format!("_prefetched_{}", self.name)
+    }
+
+    /// Binds the owner key of `instance`, which has to be saved.
+    fn owner_param(&self, instance: &Bound<'py, PyAny>) -> PyResult<String> {
+        let value = instance.getattr(&self.owner_pk.column_name)?;
+        column_param(instance.py(), &value, &self.owner_pk)?.ok_or_else(|| {
+            FustOrmError::InvalidQueryArgument(format!(
+                "Cannot use relation '{}' of an unsaved '{}' instance.",
+                self.name, self.owner_table
+            ))
+            .into()
+        })
+    }
+
+    /// Binds the keys of related objects, given as target instances or key values.
+    fn target_params(&self, objects: &Bound<'py, PyTuple>) -> PyResult<Vec<String>> {
+        let py = objects.py();
+        objects
+            .iter()
+            .map(|object| {
+                let value = if object.is_instance(&self.target)? {
+                    object.getattr(&self.target_pk.column_name)?
+                } else {
+                    object
+                };
+                column_param(py, &value, &self.target_pk)?.ok_or_else(|| {
+                    FustOrmError::InvalidQueryArgument(format!(
+                        "Cannot link an unsaved '{}' instance through '{}'.",
+                        self.target_table, self.through
+                    ))
+                    .into()
+                })
+            })
+            .collect()
+    }
+
+    /// `SELECT <target>.* FROM <target> JOIN <through> ...`, optionally with the
+    /// owner key as an extra column.
+    fn select_related(&self, with_owner_key: bool) -> String {
+        let owner_key = if with_owner_key {
+            format!(
+                "{}.{} AS {}, ",
+                self.through, self.owner_column, OWNER_KEY_ALIAS
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "SELECT {owner_key}{target}.* FROM {target} JOIN {through} ON {through}.{target_column} = {target}.{target_pk}",
+            target = self.target_table,
+            through = self.through,
+            target_column = self.target_column,
+            target_pk = self.target_pk.column_name,
+        )
+    }
+}
+
+/// Returns the single primary key column of a model used in a relation.
+fn relation_pk(info: &ModelInfo) -> PyResult<ColumnField> {
+    match info.primary_key()?.as_slice() {
+        [column] => Ok((*column).clone()),
+        _ => Err(FustOrmError::BuildError(format!(
+            "Many-to-many relations require a single-column primary key on '{}'.",
+            info.table_name
+        ))
+        .into()),
+    }
+}
+
+/// The SQL type of a primary key column for the through table, derived from its
+/// annotation: `TEXT` for `ColumnField[str]`, `INTEGER` otherwise.
+fn pk_sql_type(cls: &Bound<PyType>, column: &str) -> PyResult<&'static str> {
+    let py = cls.py();
+    for klass in cls.getattr(pyo3::intern!(py, "__mro__"))?.try_iter()? {
+        let Ok(annotations) = klass?.getattr(pyo3::intern!(py, "__annotations__")) else {
+            continue;
+        };
+        let Ok(annotation) = annotations.get_item(column) else {
+            continue;
+        };
+        let is_str = annotation
+            .getattr(pyo3::intern!(py, "__args__"))
+            .and_then(|args| args.get_item(0))
+            .is_ok_and(|arg| arg.is(py.get_type::<PyString>()));
+        return Ok(if is_str { "TEXT" } else { "INTEGER" });
+    }
+    Ok("INTEGER")
+}
+
+impl ManyToMany {
+    fn resolve<'py>(&self, py: Python<'py>) -> PyResult<Relation<'py>> {
+        let (Some(owner), Some(name)) = (&self.owner, &self.name) else {
+            return Err(FustOrmError::BuildError(
+                "ManyToMany must be declared on a Model class.".to_string(),
+            )
+            .into());
+        };
+        let owner = owner.bind(py);
+        let target = self.target.bind(py);
+        let target = if target.extract::<String>().is_ok_and(|t| t == "self") {
+            owner.clone()
+        } else {
+            target.downcast::<PyType>()?.clone()
+        };
+        let owner_info = ModelInfo::from_class(owner)?;
+        let target_info = ModelInfo::from_class(&target)?;
+        let (owner_column, target_column) = if owner_info.table_name == target_info.table_name {
+            (
+                format!("from_{}_id", owner_info.table_name),
+                format!("to_{}_id", target_info.table_name),
+            )
+        } else {
+            (
+                format!("{}_id", owner_info.table_name),
+                format!("{}_id", target_info.table_name),
+            )
+        };
+        Ok(Relation {
+            name: name.clone(),
+            through: self
+                .through
+                .clone()
+                .unwrap_or_else(|| format!("{}_{}", owner_info.table_name, name)),
+            owner_pk: relation_pk(&owner_info)?,
+            target_pk: relation_pk(&target_info)?,
+            owner_table: owner_info.table_name,
+            target_table: target_info.table_name,
+            owner: owner.clone(),
+            target,
+            owner_column,
+            target_column,
+        })
+    }
+}
+
+#[pymethods]
+impl ManyToMany {
+    /// Declares a many-to-many relation to `target` (a model class or `"self"`).
+    #[new]
+    #[pyo3(signature = (target, *, through = None))]
+    fn new(target: &Bound<PyAny>, through: Option<String>) -> PyResult<Self> {
+        let is_model = target
+            .downcast::<PyType>()
+            .is_ok_and(|t| t.is_subclass_of::<Model>().unwrap_or(false));
+        if !is_model && !target.extract::<String>().is_ok_and(|t| t == "self") {
+            return Err(PyTypeError::new_err(
+                "ManyToMany target must be a Model subclass or \"self\".",
+            ));
+        }
+        Ok(ManyToMany {
+            target: target.clone().unbind(),
+            through,
+            owner: None,
+            name: None,
+        })
+    }
+
+    fn __set_name__(&mut self, owner: Py<PyType>, name: String) {
+        self.owner = Some(owner);
+        self.name = Some(name);
+    }
+
+    /// Returns the relation itself on the class and a `RelatedManager` on instances.
+    fn __get__(
+        slf: Bound<'_, Self>,
+        instance: Option<Bound<'_, PyAny>>,
+        _owner: Option<Bound<'_, PyType>>,
+    ) -> PyResult<Py<PyAny>> {
+        let py = slf.py();
+        match instance {
+            None => Ok(slf.into_any().unbind()),
+            Some(instance) => Ok(Py::new(
+                py,
+                RelatedManager {
+                    field: slf.unbind(),
+                    instance: instance.unbind(),
+                },
+            )?
+            .into_any()),
+        }
+    }
+
+    fn __set__(&self, _instance: &Bound<PyAny>, _value: &Bound<PyAny>) -> PyResult<()> {
+        Err(PyAttributeError::new_err(format!(
+            "Relation '{}' can't be assigned; use add(), remove() or clear().",
+            self.name.as_deref().unwrap_or_default()
+        )))
+    }
+
+    /// The name of the through table.
+    #[getter]
+    pub(crate) fn through_table(&self, py: Python) -> PyResult<String> {
+        Ok(self.resolve(py)?.through)
+    }
+
+    /// The `CREATE TABLE` statement for the through table.
+    pub(crate) fn through_table_sql(&self, py: Python) -> PyResult<String> {
+        let relation = self.resolve(py)?;
+        let owner_type = pk_sql_type(&relation.owner, &relation.owner_pk.column_name)?;
+        let target_type = pk_sql_type(&relation.target, &relation.target_pk.column_name)?;
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {through} (\
+            {owner_column} {owner_type} NOT NULL REFERENCES {owner_table} ({owner_pk}) ON DELETE CASCADE, \
+            {target_column} {target_type} NOT NULL REFERENCES {target_table} ({target_pk}) ON DELETE CASCADE, \
+            PRIMARY KEY ({owner_column}, {target_column}))",
+            through = relation.through,
+            owner_column = relation.owner_column,
+            owner_table = relation.owner_table,
+            owner_pk = relation.owner_pk.column_name,
+            target_column = relation.target_column,
+            target_table = relation.target_table,
+            target_pk = relation.target_pk.column_name,
+        ))
+    }
+
+    /// Creates the through table if it doesn't exist yet.
+    fn create_through_table<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let sql = self.through_table_sql(py)?;
+        let db = context::resolve(py, Some(db))?;
+        future_into_py(py, async move {
+            db.execute_statement(&sql, Vec::new()).await?;
+            Ok(())
+        })
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let owner = match &self.owner {
+            Some(owner) => owner.bind(py).name()?.to_string(),
+            None => "?".to_string(),
+        };
+        Ok(format!(
+            "<ManyToMany: {}.{}>",
+            owner,
+            self.name.as_deref().unwrap_or("?")
+        ))
+    }
+}
+
+/// The links of one model instance through a `ManyToMany` relation.
+///
+/// `all()`, `add()`, `remove()` and `clear()` query or change the through table.
+/// After `Model.prefetch_related()` or `all()`, the related objects are also
+/// available without a query by iterating over the manager.
+#[pyclass]
+pub struct RelatedManager {
+    field: Py<ManyToMany>,
+    instance: Py<PyAny>,
+}
+
+impl RelatedManager {
+    fn cached<'py>(&self, py: Python<'py>, key: &str) -> PyResult<Option<Bound<'py, PyList>>> {
+        let dict = self
+            .instance
+            .bind(py)
+            .getattr(pyo3::intern!(py, "__dict__"))?;
+        match dict.downcast::<PyDict>()?.get_item(key)? {
+            Some(list) => Ok(Some(list.downcast_into::<PyList>()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn loaded<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let relation = self.field.borrow(py).resolve(py)?;
+        self.cached(py, &relation.cache_key())?.ok_or_else(|| {
+            FustOrmError::InvalidQueryArgument(format!(
+                "Relation '{}' isn't loaded; await `.all(db)` or use `prefetch_related()` first.",
+                relation.name
+            ))
+            .into()
+        })
+    }
+}
+
+#[pymethods]
+impl RelatedManager {
+    /// Loads the related objects. Returns an awaitable resolving to a list.
+    #[pyo3(signature = (db = None))]
+    fn all<'py>(
+        &self,
+        py: Python<'py>,
+        db: Option<PyRef<'py, Database>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let db = context::resolve(py, db.as_deref())?;
+        let relation = self.field.borrow(py).resolve(py)?;
+        let instance = self.instance.bind(py);
+        let sql = format!(
+            "{} WHERE {}.{} = ?",
+            relation.select_related(false),
+            relation.through,
+            relation.owner_column
+        );
+        let params = vec![relation.owner_param(instance)?];
+        let target = relation.target.clone().unbind();
+        let cache_key = relation.cache_key();
+        let instance = instance.clone().unbind();
+
+        future_into_py(py, async move {
+            let rows = db.fetch_rows(&sql, params).await?;
+            let batch = RowBatch::decode(&rows)?;
+            Python::attach(|py| -> PyResult<Py<PyList>> {
+                let related = PyList::empty(py);
+                for row in batch.to_dicts(py)? {
+                    related.append(hydrate(target.bind(py), &row)?)?;
+                }
+                instance.bind(py).setattr(cache_key, &related)?;
+                Ok(related.unbind())
+            })
+        })
+    }
+
+    /// Links the given objects (target instances or primary key values).
+    /// Links that already exist are left alone.
+    #[pyo3(signature = (db, *objects))]
+    fn add<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+        objects: &Bound<'py, PyTuple>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let relation = self.field.borrow(py).resolve(py)?;
+        let owner = relation.owner_param(self.instance.bind(py))?;
+        let targets = relation.target_params(objects)?;
+        if targets.is_empty() {
+            return future_into_py(py, async move { Ok(()) });
+        }
+        let mut params = Vec::with_capacity(targets.len() * 2);
+        for target in targets {
+            params.push(owner.clone());
+            params.push(target);
+        }
+        let (prefix, suffix) = if db.is_mysql() {
+            ("INSERT IGNORE", "")
+        } else {
+            ("INSERT", " ON CONFLICT DO NOTHING")
+        };
+        let sql = format!(
+            "{} INTO {} ({}, {}) VALUES {}{}",
+            prefix,
+            relation.through,
+            relation.owner_column,
+            relation.target_column,
+            vec!["(?, ?)"; objects.len()].join(", "),
+            suffix
+        );
+
+        // The cache stays valid if all objects are instances; otherwise it's dropped.
+        let cache_key = relation.cache_key();
+        let cached = self.cached(py, &cache_key)?;
+        let added = objects
+            .iter()
+            .filter(|o| o.is_instance(&relation.target).unwrap_or(false))
+            .collect::<Vec<_>>();
+        let keep_cache = added.len() == objects.len();
+        let added = PyList::new(py, added)?.unbind();
+        let cached = cached.map(Bound::unbind);
+        let instance = self.instance.clone_ref(py);
+        let target_pk = relation.target_pk.column_name.clone();
+        let db = context::resolve(py, Some(db))?;
+
+        future_into_py(py, async move {
+            db.execute_statement(&sql, params).await?;
+            Python::attach(|py| -> PyResult<()> {
+                let Some(cached) = cached else {
+                    return Ok(());
+                };
+                let cached = cached.bind(py);
+                if !keep_cache {
+                    return instance
+                        .bind(py)
+                        .getattr(pyo3::intern!(py, "__dict__"))?
+                        .del_item(cache_key);
+                }
+                let existing = cached
+                    .iter()
+                    .map(|o| o.getattr(&target_pk))
+                    .collect::<PyResult<Vec<_>>>()?;
+                for object in added.bind(py).iter() {
+                    let pk = object.getattr(&target_pk)?;
+                    if !existing.iter().any(|e| e.eq(&pk).unwrap_or(false)) {
+                        cached.append(object)?;
+                    }
+                }
+                Ok(())
+            })
+        })
+    }
+
+    /// Unlinks the given objects (target instances or primary key values).
+    #[pyo3(signature = (db, *objects))]
+    fn remove<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+        objects: &Bound<'py, PyTuple>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let relation = self.field.borrow(py).resolve(py)?;
+        let mut params = vec![relation.owner_param(self.instance.bind(py))?];
+        let targets = relation.target_params(objects)?;
+        if targets.is_empty() {
+            return future_into_py(py, async move { Ok(()) });
+        }
+        let sql = format!(
+            "DELETE FROM {} WHERE {} = ? AND {} IN ({})",
+            relation.through,
+            relation.owner_column,
+            relation.target_column,
+            vec!["?"; targets.len()].join(", ")
+        );
+        params.extend(targets.iter().cloned());
+
+        let cached = self.cached(py, &relation.cache_key())?.map(Bound::unbind);
+        let target_pk = relation.target_pk.clone();
+        let db = context::resolve(py, Some(db))?;
+
+        future_into_py(py, async move {
+            db.execute_statement(&sql, params).await?;
+            Python::attach(|py| -> PyResult<()> {
+                let Some(cached) = cached else {
+                    return Ok(());
+                };
+                let cached = cached.bind(py);
+                for index in (0..cached.len()).rev() {
+                    let pk = cached.get_item(index)?.getattr(&target_pk.column_name)?;
+                    if column_param(py, &pk, &target_pk)?.is_some_and(|pk| targets.contains(&pk)) {
+                        cached.del_item(index)?;
+                    }
+                }
+                Ok(())
+            })
+        })
+    }
+
+    /// Unlinks all related objects.
+    #[pyo3(signature = (db = None))]
+    fn clear<'py>(
+        &self,
+        py: Python<'py>,
+        db: Option<PyRef<'py, Database>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let db = context::resolve(py, db.as_deref())?;
+        let relation = self.field.borrow(py).resolve(py)?;
+        let params = vec![relation.owner_param(self.instance.bind(py))?];
+        let sql = format!(
+            "DELETE FROM {} WHERE {} = ?",
+            relation.through, relation.owner_column
+        );
+        let cache_key = relation.cache_key();
+        let instance = self.instance.clone_ref(py);
+
+        future_into_py(py, async move {
+            db.execute_statement(&sql, params).await?;
+            Python::attach(|py| -> PyResult<()> {
+                instance.bind(py).setattr(cache_key, PyList::empty(py))
+            })
+        })
+    }
+
+    /// Iterates over the loaded related objects.
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(self.loaded(py)?.try_iter()?.into_any())
+    }
+
+    fn __len__(&self, py: Python) -> PyResult<usize> {
+        Ok(self.loaded(py)?.len())
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "<RelatedManager: {}>",
+            self.field.borrow(py).__repr__(py)?
+        ))
+    }
+}
+
+/// Loads the related objects of the relations `names` for all `instances` with
+/// one query per relation (per 999 instances), and caches them on the instances.
+pub(crate) fn prefetch_related<'py>(
+    cls: &Bound<'py, PyType>,
+    db: &Database,
+    instances: &Bound<'py, PyAny>,
+    names: &Bound<'py, PyTuple>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = cls.py();
+    let instances = instances.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+
+    // Per relation: the target class, the cache attribute and the queries to run.
+    let mut plans = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        let name = name.extract::<String>()?;
+        let field = cls
+            .getattr(&name)
+            .ok()
+            .and_then(|f| f.downcast_into::<ManyToMany>().ok())
+            .ok_or_else(|| {
+                FustOrmError::InvalidQueryArgument(format!(
+                    "'{}' is not a many-to-many relation of '{}'.",
+                    name,
+                    cls.name().map(|n| n.to_string()).unwrap_or_default()
+                ))
+            })?;
+        let relation = field.borrow().resolve(py)?;
+        let keys = instances
+            .iter()
+            .map(|instance| relation.owner_param(instance))
+            .collect::<PyResult<Vec<_>>>()?;
+        let queries = keys
+            .chunks(PREFETCH_CHUNK_SIZE)
+            .map(|chunk| {
+                let sql = format!(
+                    "{} WHERE {}.{} IN ({})",
+                    relation.select_related(true),
+                    relation.through,
+                    relation.owner_column,
+                    vec!["?"; chunk.len()].join(", ")
+                );
+                (sql, chunk.to_vec())
+            })
+            .collect::<Vec<_>>();
+        debug!(
+            "Prefetching '{}' for {} instances of '{}'",
+            name,
+            instances.len(),
+            relation.owner_table
+        );
+        plans.push((
+            relation.target.clone().unbind(),
+            relation.cache_key(),
+            relation.owner_pk.column_name.clone(),
+            queries,
+        ));
+    }
+
+    let db = context::resolve(py, Some(db))?;
+    let instances: Vec<Py<PyAny>> = instances.into_iter().map(Bound::unbind).collect();
+
+    future_into_py(py, async move {
+        let mut results = Vec::with_capacity(plans.len());
+        for (target, cache_key, owner_pk, queries) in plans {
+            let mut rows = Vec::new();
+            for (sql, params) in queries {
+                rows.extend(db.fetch_rows(&sql, params).await?);
+            }
+            results.push((target, cache_key, owner_pk, rows));
+        }
+
+        Python::attach(|py| -> PyResult<()> {
+            for (target, cache_key, owner_pk, rows) in results {
+                let by_owner = PyDict::new(py);
+                for row in &rows {
+                    let row = row_to_dict(py, row)?;
+                    let owner = row
+                        .get_item(OWNER_KEY_ALIAS)?
+                        .unwrap_or(py.None().into_bound(py));
+                    row.del_item(OWNER_KEY_ALIAS)?;
+                    let related = hydrate(target.bind(py), &row)?;
+                    match by_owner.get_item(&owner)? {
+                        Some(list) => list.downcast::<PyList>()?.append(related)?,
+                        None => by_owner.set_item(owner, PyList::new(py, [related])?)?,
+                    }
+                }
+                for instance in &instances {
+                    let instance = instance.bind(py);
+                    let related = match by_owner.get_item(instance.getattr(&owner_pk)?)? {
+                        Some(list) => list.downcast_into::<PyList>()?,
+                        None => PyList::empty(py),
+                    };
+                    instance.setattr(&cache_key, related)?;
+                }
+            }
+            Ok(())
+        })
+    })
+}
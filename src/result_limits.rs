@@ -0,0 +1,140 @@
+use futures_util::TryStreamExt;
+use futures_util::stream::BoxStream;
+use sqlx::any::{AnyQueryResult, AnyRow};
+use sqlx::{AnyPool, Either, Row, ValueRef};
+
+use crate::error::FustOrmError;
+use crate::pool_registry::PoolRegistry;
+
+/// Caps on the size of a result, which a fetch exceeding them fails on
+/// rather than loading it all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ResultLimits {
+    pub(crate) max_rows: Option<usize>,
+    /// The size of the values of the rows, in bytes.
+    pub(crate) max_result_bytes: Option<usize>,
+}
+
+impl ResultLimits {
+    /// These limits, each falling back to the one of `defaults` when unset.
+    pub(crate) fn or(self, defaults: ResultLimits) -> ResultLimits {
+        ResultLimits {
+            max_rows: self.max_rows.or(defaults.max_rows),
+            max_result_bytes: self.max_result_bytes.or(defaults.max_result_bytes),
+        }
+    }
+}
+
+/// The limits of the pools connected with them, by the connect options every
+/// clone of a pool shares.
+static LIMITS: PoolRegistry<ResultLimits> = PoolRegistry::new();
+
+/// Sets the limits of the results fetched on `pool`.
+pub(crate) fn set(pool: &AnyPool, limits: ResultLimits) {
+    if limits != ResultLimits::default() {
+        LIMITS.insert(pool, limits);
+    }
+}
+
+/// The limits of the results fetched on `pool`.
+pub(crate) fn of(pool: &AnyPool) -> ResultLimits {
+    LIMITS.get(pool).unwrap_or_default()
+}
+
+/// The size of the values of a row, in bytes: that of text and blobs, and 8
+/// for numbers.
+fn row_size(row: &AnyRow) -> usize {
+    (0..row.len())
+        .map(|i| {
+            if let Ok(Some(text)) = row.try_get::<Option<&str>, _>(i) {
+                text.len()
+            } else if let Ok(Some(blob)) = row.try_get::<Option<&[u8]>, _>(i) {
+                blob.len()
+            } else if row.try_get_raw(i).is_ok_and(|value| value.is_null()) {
+                0
+            } else {
+                8
+            }
+        })
+        .sum()
+}
+
+/// Counts the rows of a result against its limits as they arrive.
+pub(crate) struct Budget {
+    limits: ResultLimits,
+    rows: usize,
+    bytes: usize,
+    /// The rows the statement inserted, updated or deleted.
+    pub(crate) rows_affected: u64,
+    /// The limit the result went over, if it did.
+    exceeded: Option<String>,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: ResultLimits) -> Budget {
+        Budget {
+            limits,
+            rows: 0,
+            bytes: 0,
+            rows_affected: 0,
+            exceeded: None,
+        }
+    }
+
+    /// Counts `row`, and returns whether the result is still within its
+    /// limits.
+    pub(crate) fn admit(&mut self, row: &AnyRow) -> bool {
+        self.rows += 1;
+        if let Some(max) = self.limits.max_rows
+            && self.rows > max
+        {
+            self.exceeded = Some(format!("more than {} rows (max_rows)", max));
+            return false;
+        }
+        if let Some(max) = self.limits.max_result_bytes {
+            self.bytes += row_size(row);
+            if self.bytes > max {
+                self.exceeded = Some(format!(
+                    "more than {} bytes in {} rows (max_result_bytes)",
+                    max, self.rows
+                ));
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Collects the rows of `stream`, a `fetch_many()`, stopping at the first
+    /// going over the limits, which cancels the rest of the query.
+    pub(crate) async fn collect(
+        &mut self,
+        mut stream: BoxStream<'_, Result<Either<AnyQueryResult, AnyRow>, sqlx::Error>>,
+    ) -> Result<Vec<AnyRow>, sqlx::Error> {
+        let mut rows = Vec::new();
+        while let Some(item) = stream.try_next().await? {
+            let row = match item {
+                Either::Left(result) => {
+                    self.rows_affected += result.rows_affected();
+                    continue;
+                }
+                Either::Right(row) => row,
+            };
+            if !self.admit(&row) {
+                break;
+            }
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// Fails if the result went over its limits.
+    pub(crate) fn check(&self, sql: &str) -> Result<(), FustOrmError> {
+        match &self.exceeded {
+            Some(exceeded) => Err(FustOrmError::QueryError(format!(
+                "The result has {}; add a LIMIT to the query or raise the limit: {}",
+                exceeded, sql
+            ))),
+            None => Ok(()),
+        }
+    }
+}
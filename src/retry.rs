@@ -0,0 +1,198 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+use pyo3::exceptions::PyBaseException;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use sqlx::AnyPool;
+
+use crate::error::{FustOrmError, OperationalError};
+use crate::pool_registry::PoolRegistry;
+
+/// How statements failing with a transient error are retried.
+pub(crate) struct Policy {
+    /// The most times a statement runs, the first included.
+    attempts: u32,
+    /// The exceptions retried, and their subclasses.
+    retry_on: Vec<Py<PyType>>,
+    /// The delay before the first retry.
+    backoff: Duration,
+    /// What each delay is multiplied by for the next one.
+    multiplier: f64,
+    max_backoff: Duration,
+    /// The fraction of each delay that is random.
+    jitter: f64,
+}
+
+impl Policy {
+    /// Whether `err` is one of the exceptions retried.
+    fn retries(&self, err: &FustOrmError) -> bool {
+        Python::attach(|py| {
+            let raised = err.exception_type(py);
+            self.retry_on
+                .iter()
+                .any(|class| raised.is_subclass(class.bind(py)).unwrap_or(false))
+        })
+    }
+
+    /// The delay before retry `retry`, counting from 1.
+    fn delay(&self, retry: u32) -> Duration {
+        let delay = (self.backoff.as_secs_f64() * self.multiplier.powi(retry as i32 - 1))
+            .min(self.max_backoff.as_secs_f64());
+        // A random number from 0 to 1, from the random keys of a hasher.
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        Duration::from_secs_f64(delay * (1.0 - self.jitter * random))
+    }
+}
+
+/// Retries the statements of the databases it is attached to, with
+/// `Database.connect(..., retry=policy)`, or of one call, with
+/// `execute(query, retry=policy)`, when they fail with one of `retry_on`
+/// (`OperationalError` by default, raised for lost connections, timeouts
+/// and locked databases), up to `attempts` runs in all.
+///
+/// The delay before retry `n` is `backoff * multiplier ** (n - 1)`, capped
+/// at `max_backoff`, less a random part of up to `jitter` of it, so that
+/// clients failing together don't retry together. Statements in a
+/// transaction aren't retried, as the transaction fails with them.
+#[pyclass(frozen)]
+#[derive(Clone)]
+pub(crate) struct RetryPolicy {
+    pub(crate) policy: Arc<Policy>,
+}
+
+#[pymethods]
+impl RetryPolicy {
+    #[new]
+    #[pyo3(signature = (*, attempts = 3, retry_on = None, backoff = 0.1, multiplier = 2.0, max_backoff = 5.0, jitter = 0.5))]
+    fn new(
+        py: Python,
+        attempts: u32,
+        retry_on: Option<Vec<Bound<PyType>>>,
+        backoff: f64,
+        multiplier: f64,
+        max_backoff: f64,
+        jitter: f64,
+    ) -> PyResult<Self> {
+        let invalid = |message: &str| -> PyResult<Self> {
+            Err(FustOrmError::InvalidQueryArgument(message.to_string()).into())
+        };
+        if attempts == 0 {
+            return invalid("attempts must be at least 1.");
+        }
+        if !(backoff >= 0.0 && backoff.is_finite() && max_backoff >= 0.0 && max_backoff.is_finite())
+        {
+            return invalid("backoff and max_backoff must be numbers of seconds, at least 0.");
+        }
+        if !(multiplier >= 1.0 && multiplier.is_finite()) {
+            return invalid("multiplier must be at least 1.");
+        }
+        if !(0.0..=1.0).contains(&jitter) {
+            return invalid("jitter must be from 0 to 1.");
+        }
+        let retry_on = match retry_on {
+            Some(classes) => classes,
+            None => vec![py.get_type::<OperationalError>()],
+        };
+        for class in &retry_on {
+            if !class.is_subclass_of::<PyBaseException>()? {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "retry_on must be exception classes, not {}.",
+                    class.name()?
+                ))
+                .into());
+            }
+        }
+        Ok(RetryPolicy {
+            policy: Arc::new(Policy {
+                attempts,
+                retry_on: retry_on.into_iter().map(Bound::unbind).collect(),
+                backoff: Duration::from_secs_f64(backoff),
+                multiplier,
+                max_backoff: Duration::from_secs_f64(max_backoff),
+                jitter,
+            }),
+        })
+    }
+
+    #[getter]
+    fn attempts(&self) -> u32 {
+        self.policy.attempts
+    }
+}
+
+/// The policies of the pools connected with `retry=`, by the connect options
+/// identifying them.
+static POLICIES: PoolRegistry<Arc<Policy>> = PoolRegistry::new();
+
+/// Attaches `policy` to `pool`.
+pub(crate) fn set(pool: &AnyPool, policy: Arc<Policy>) {
+    POLICIES.insert(pool, policy);
+}
+
+/// The policy attached to `pool`, if any.
+pub(crate) fn of(pool: &AnyPool) -> Option<Arc<Policy>> {
+    POLICIES.get(pool)
+}
+
+tokio::task_local! {
+    /// The policy of the statements run by `with_policy()`, over the policy
+    /// of their pool.
+    static CALL_POLICY: Option<Arc<Policy>>;
+}
+
+/// Runs `future`, retrying the statements it runs on a pool with `policy`
+/// instead of the policy of the pool, if given.
+pub(crate) async fn with_policy<F: Future>(policy: Option<Arc<Policy>>, future: F) -> F::Output {
+    match policy {
+        Some(_) => CALL_POLICY.scope(policy, future).await,
+        None => future.await,
+    }
+}
+
+/// The policy retrying the statements run on `pool`: that of the
+/// `with_policy()` call running them, or that attached to the pool.
+pub(crate) fn current(pool: &AnyPool) -> Option<Arc<Policy>> {
+    CALL_POLICY
+        .try_with(Clone::clone)
+        .ok()
+        .flatten()
+        .or_else(|| of(pool))
+}
+
+/// Runs `attempt` until it succeeds, fails with an error `policy` doesn't
+/// retry, or has run as many times as `policy` allows.
+pub(crate) async fn run<T, F, Fut>(
+    policy: Option<Arc<Policy>>,
+    mut attempt: F,
+) -> Result<T, FustOrmError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, FustOrmError>>,
+{
+    let Some(policy) = policy else {
+        return attempt().await;
+    };
+    let mut retry = 1;
+    loop {
+        match attempt().await {
+            Err(e) if retry < policy.attempts && policy.retries(&e) => {
+                let delay = policy.delay(retry);
+                warn!(
+                    "Retrying in {:.3}s (attempt {} of {}) after: {}",
+                    delay.as_secs_f64(),
+                    retry + 1,
+                    policy.attempts,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                retry += 1;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+
+use crate::error::FustOrmError;
+use crate::query::{QueryBuilder, raw_params};
+use crate::sqlcomment::call_in_caller_context;
+
+/// Called with every query builder before it is rendered.
+static QUERY_HOOK: Mutex<Option<Arc<Py<PyAny>>>> = Mutex::new(None);
+
+/// Called with every statement before it runs.
+static STATEMENT_HOOK: Mutex<Option<Arc<Py<PyAny>>>> = Mutex::new(None);
+
+fn replace(hook: &Mutex<Option<Arc<Py<PyAny>>>>, new: Option<Py<PyAny>>) {
+    // The previous hook is released after the lock.
+    let previous = std::mem::replace(
+        &mut *hook.lock().unwrap_or_else(|e| e.into_inner()),
+        new.map(Arc::new),
+    );
+    drop(previous);
+}
+
+fn current(hook: &Mutex<Option<Arc<Py<PyAny>>>>) -> Option<Arc<Py<PyAny>>> {
+    hook.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Passes every `QueryBuilder` to `hook(query)` before it is rendered, by
+/// `Database.execute()`, `all()`, `values()`, `count()`, `update()` and the
+/// like. The hook returns the query to render instead, typically with more
+/// conditions such as `query.where_(Order.tenant_id == tenant.get())`, or
+/// `None` to keep it. `rewrite_queries(None)` removes the hook.
+#[pyfunction]
+pub(crate) fn rewrite_queries(hook: Option<Py<PyAny>>) {
+    replace(&QUERY_HOOK, hook);
+}
+
+/// Passes every statement any database runs, including those model methods
+/// render, to `hook(sql, params)` before it runs, in the context that called
+/// the database. The hook returns the `(sql, params)` to run instead, or
+/// `None` to keep them; an error it raises fails the statement.
+/// `rewrite_statements(None)` removes the hook.
+#[pyfunction]
+pub(crate) fn rewrite_statements(hook: Option<Py<PyAny>>) {
+    replace(&STATEMENT_HOOK, hook);
+}
+
+/// The query the hook of `rewrite_queries()` renders instead of `query`, if
+/// there is a hook and it returned one.
+pub(crate) fn query(py: Python, query: &QueryBuilder) -> PyResult<Option<QueryBuilder>> {
+    let Some(hook) = current(&QUERY_HOOK) else {
+        return Ok(None);
+    };
+    let rewritten = hook.call1(py, (query.clone(),))?;
+    if rewritten.is_none(py) {
+        return Ok(None);
+    }
+    rewritten.extract(py).map(Some).map_err(|_| {
+        PyTypeError::new_err("The query rewrite hook must return a QueryBuilder or None")
+    })
+}
+
+/// The statement the hook of `rewrite_statements()` runs instead of `sql`
+/// with `params`, which are returned as they are without a hook.
+pub(crate) fn statement(
+    sql: &str,
+    params: Vec<String>,
+) -> Result<(Cow<'_, str>, Vec<String>), FustOrmError> {
+    let Some(hook) = current(&STATEMENT_HOOK) else {
+        return Ok((Cow::Borrowed(sql), params));
+    };
+    Python::attach(|py| -> PyResult<_> {
+        let args = [
+            sql.into_pyobject(py)?.into_any(),
+            PyList::new(py, &params)?.into_any(),
+        ];
+        let rewritten = call_in_caller_context(py, hook.bind(py).clone(), &args)?;
+        if rewritten.is_none() {
+            return Ok((Cow::Borrowed(sql), params));
+        }
+        let (sql, params): (String, Vec<Py<PyAny>>) = rewritten.extract().map_err(|_| {
+            PyTypeError::new_err("The statement rewrite hook must return (sql, params) or None")
+        })?;
+        Ok((Cow::Owned(sql), raw_params(py, &params)?))
+    })
+    .map_err(|e| FustOrmError::QueryError(format!("Can't rewrite the statement: {}", e)))
+}
@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::debug;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::error::FustOrmError;
+
+/// Whether `configure_runtime()` has found the runtime started, by starting
+/// it or too late, so that further calls fail without building another one.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Configures the tokio runtime that runs the statements of all databases.
+///
+/// Without a call, the runtime is multi-threaded with a worker per CPU. It
+/// starts with the first database operation, so this must be called before:
+///
+///     fust_orm.configure_runtime(worker_threads=2, thread_name="orm")
+///
+/// Args:
+///     worker_threads (int | None): How many worker threads the runtime runs
+///         tasks on; by default one per CPU.
+///     thread_name (str | None): The name of the runtime's threads.
+///     current_thread (bool): Runs every task on a single background
+///         thread instead, for a small footprint, e.g. in command line tools.
+#[pyfunction]
+#[pyo3(signature = (*, worker_threads = None, thread_name = None, current_thread = false))]
+pub(crate) fn configure_runtime(
+    worker_threads: Option<usize>,
+    thread_name: Option<String>,
+    current_thread: bool,
+) -> PyResult<()> {
+    let mut builder = match (current_thread, worker_threads) {
+        (true, Some(_)) => {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "worker_threads can't be combined with current_thread.".to_string(),
+            )
+            .into());
+        }
+        (true, None) => Builder::new_current_thread(),
+        (false, Some(0)) => {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "worker_threads must be at least 1.".to_string(),
+            )
+            .into());
+        }
+        (false, Some(threads)) => {
+            let mut builder = Builder::new_multi_thread();
+            builder.worker_threads(threads);
+            builder
+        }
+        (false, None) => Builder::new_multi_thread(),
+    };
+    builder.enable_all();
+    if let Some(name) = &thread_name {
+        builder.thread_name(name);
+    }
+    let too_late = || {
+        PyRuntimeError::new_err(
+            "The runtime has already started; configure_runtime() must be called before \
+             the first database operation.",
+        )
+    };
+    if STARTED.load(Ordering::Acquire) {
+        return Err(too_late());
+    }
+    let runtime = builder
+        .build()
+        .map_err(|e| PyRuntimeError::new_err(format!("Couldn't build the runtime: {}", e)))?;
+    let runtime: &'static Runtime = Box::leak(Box::new(runtime));
+    if pyo3_async_runtimes::tokio::init_with_runtime(runtime).is_err() {
+        // SAFETY: the runtime was leaked just above, and a failed
+        // `init_with_runtime()` keeps no reference to it.
+        let runtime = unsafe { Box::from_raw(runtime as *const Runtime as *mut Runtime) };
+        runtime.shutdown_background();
+        STARTED.store(true, Ordering::Release);
+        return Err(too_late());
+    }
+    STARTED.store(true, Ordering::Release);
+    if current_thread {
+        // A current-thread runtime only runs tasks while a thread blocks on
+        // it, which this one does for the rest of the process.
+        std::thread::Builder::new()
+            .name(thread_name.unwrap_or_else(|| "tokio-runtime-worker".to_string()))
+            .spawn(move || runtime.block_on(std::future::pending::<()>()))
+            .map_err(|e| PyRuntimeError::new_err(format!("Couldn't start the runtime: {}", e)))?;
+    }
+    debug!(
+        "Configured the runtime: current_thread={}, worker_threads={:?}",
+        current_thread, worker_threads
+    );
+    Ok(())
+}
@@ -0,0 +1,1346 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use crate::cascade::OnDelete;
+use crate::column_field::ColumnField;
+use crate::context;
+use crate::custom_type;
+use crate::database::{Database, execute_statement, fetch_rows};
+use crate::error::FustOrmError;
+use crate::introspect;
+use crate::mock;
+use crate::model::ModelInfo;
+use crate::range::Range;
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyString, PyTuple, PyType};
+use pyo3_async_runtimes::tokio::future_into_py;
+
+/// The SQL dialect DDL is rendered for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+    Mysql,
+}
+
+impl Dialect {
+    pub fn parse(value: &str) -> Result<Self, FustOrmError> {
+        match value {
+            "sqlite" => Ok(Dialect::Sqlite),
+            "postgres" | "postgresql" => Ok(Dialect::Postgres),
+            "mysql" | "mariadb" => Ok(Dialect::Mysql),
+            _ => Err(FustOrmError::BuildError(format!(
+                "Unknown dialect '{}'; expected 'sqlite', 'postgres' or 'mysql'.",
+                value
+            ))),
+        }
+    }
+
+    /// The name `parse` accepts for the dialect.
+    pub fn name(self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "sqlite",
+            Dialect::Postgres => "postgres",
+            Dialect::Mysql => "mysql",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TypeKind {
+    Integer,
+    BigInteger,
+    SmallInteger,
+    Float,
+    Boolean,
+    Text,
+    Varchar(u32),
+    Numeric(u32, u32),
+    Date,
+    DateTime,
+    Blob,
+    Json,
+    /// A duration: `INTERVAL` on PostgreSQL, a number of seconds elsewhere.
+    Interval,
+    /// One of `RANGE_KINDS`, PostgreSQL's range types.
+    Range(&'static str),
+    /// A geometry of one of `GEOMETRY_KINDS`, with an optional spatial
+    /// reference system id.
+    Geometry(&'static str, Option<u32>),
+    /// A PostgreSQL enum or composite type registered with `register_type()`.
+    Custom(&'static str),
+}
+
+/// The range types, by the names of their column types.
+const RANGE_KINDS: [&str; 6] = [
+    "Int4Range",
+    "Int8Range",
+    "NumRange",
+    "DateRange",
+    "TsRange",
+    "TstzRange",
+];
+
+/// Keeps the names of the types registered with `register_type()` for
+/// `TypeKind::Custom`, which is `Copy`: each distinct name is leaked once.
+fn intern(name: String) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut names = NAMES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(name) = names.get(name.as_str()) {
+        return name;
+    }
+    let name = Box::leak(name.into_boxed_str());
+    names.insert(name);
+    name
+}
+
+/// The kinds of geometry a `Geometry` column can be restricted to.
+const GEOMETRY_KINDS: [&str; 8] = [
+    "GEOMETRY",
+    "POINT",
+    "LINESTRING",
+    "POLYGON",
+    "MULTIPOINT",
+    "MULTILINESTRING",
+    "MULTIPOLYGON",
+    "GEOMETRYCOLLECTION",
+];
+
+/// The type of a column declared with `Table.column()`, rendered as the
+/// matching type of each dialect (e.g. `Json` is `JSONB` on PostgreSQL and
+/// `TEXT` on SQLite).
+#[pyclass(frozen, eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnType {
+    kind: TypeKind,
+    /// Whether the column holds arrays of `kind`.
+    array: bool,
+}
+
+impl ColumnType {
+    fn scalar(kind: TypeKind) -> Self {
+        ColumnType { kind, array: false }
+    }
+
+    /// The column type of values of the Python type `arg`: a type registered
+    /// with `register_type()` or a scalar type.
+    pub(crate) fn for_type(arg: &Bound<PyAny>) -> PyResult<Option<ColumnType>> {
+        if let Ok(cls) = arg.downcast::<PyType>()
+            && let Some(name) = custom_type::type_name(cls)?
+        {
+            return Ok(Some(ColumnType::scalar(TypeKind::Custom(intern(name)))));
+        }
+        Ok(scalar_kind(arg)?.map(ColumnType::scalar))
+    }
+
+    pub(crate) fn is_integer(&self) -> bool {
+        !self.array
+            && matches!(
+                self.kind,
+                TypeKind::Integer | TypeKind::BigInteger | TypeKind::SmallInteger
+            )
+    }
+
+    pub(crate) fn to_sql(self, dialect: Dialect) -> String {
+        use Dialect::*;
+        if self.array {
+            // Other databases store the text of the PostgreSQL array.
+            return match dialect {
+                Postgres => format!("{}[]", ColumnType::scalar(self.kind).to_sql(dialect)),
+                _ => "TEXT".to_string(),
+            };
+        }
+        match (self.kind, dialect) {
+            // Only `INTEGER PRIMARY KEY` is an alias of the rowid in SQLite,
+            // and the Any driver can't decode SQLite's boolean and date types,
+            // so SQLite gets the storage classes instead.
+            (
+                TypeKind::Integer
+                | TypeKind::BigInteger
+                | TypeKind::SmallInteger
+                | TypeKind::Boolean,
+                Sqlite,
+            ) => "INTEGER".to_string(),
+            (TypeKind::Date | TypeKind::DateTime | TypeKind::Json, Sqlite) => "TEXT".to_string(),
+            (TypeKind::Integer, _) => "INTEGER".to_string(),
+            (TypeKind::BigInteger, _) => "BIGINT".to_string(),
+            (TypeKind::SmallInteger, _) => "SMALLINT".to_string(),
+            (TypeKind::Float, Sqlite) => "REAL".to_string(),
+            (TypeKind::Float, Postgres) => "DOUBLE PRECISION".to_string(),
+            (TypeKind::Float, Mysql) => "DOUBLE".to_string(),
+            (TypeKind::Boolean, _) => "BOOLEAN".to_string(),
+            (TypeKind::Text, _) => "TEXT".to_string(),
+            (TypeKind::Varchar(length), _) => format!("VARCHAR({})", length),
+            (TypeKind::Numeric(precision, scale), _) => {
+                format!("NUMERIC({}, {})", precision, scale)
+            }
+            (TypeKind::Date, _) => "DATE".to_string(),
+            (TypeKind::DateTime, Postgres) => "TIMESTAMP".to_string(),
+            (TypeKind::DateTime, _) => "DATETIME".to_string(),
+            (TypeKind::Blob, Postgres) => "BYTEA".to_string(),
+            (TypeKind::Blob, _) => "BLOB".to_string(),
+            (TypeKind::Json, Postgres) => "JSONB".to_string(),
+            (TypeKind::Json, _) => "JSON".to_string(),
+            // SpatiaLite stores geometries as blobs of its own format.
+            (TypeKind::Geometry(..), Sqlite) => "BLOB".to_string(),
+            (TypeKind::Geometry(kind, srid), Postgres) => match (kind, srid) {
+                ("GEOMETRY", None) => "geometry".to_string(),
+                (kind, None) => format!("geometry({})", kind),
+                (kind, Some(srid)) => format!("geometry({}, {})", kind, srid),
+            },
+            (TypeKind::Geometry(kind, None), Mysql) => kind.to_string(),
+            (TypeKind::Geometry(kind, Some(srid)), Mysql) => format!("{} SRID {}", kind, srid),
+            (TypeKind::Interval, Postgres) => "INTERVAL".to_string(),
+            (TypeKind::Interval, _) => ColumnType::scalar(TypeKind::Float).to_sql(dialect),
+            // Other databases store the text of ranges, enum labels and
+            // records.
+            (TypeKind::Range(kind), Postgres) => kind.to_lowercase(),
+            (TypeKind::Custom(name), Postgres) => name.to_string(),
+            (TypeKind::Range(_) | TypeKind::Custom(_), _) => "TEXT".to_string(),
+        }
+    }
+}
+
+#[pymethods]
+impl ColumnType {
+    /// Renders the type for `dialect` (`"sqlite"`, `"postgres"` or `"mysql"`).
+    #[pyo3(signature = (dialect = "sqlite"))]
+    fn sql(&self, dialect: &str) -> PyResult<String> {
+        Ok(self.to_sql(Dialect::parse(dialect)?))
+    }
+
+    fn __repr__(&self) -> String {
+        if self.array {
+            return format!("Array({})", ColumnType::scalar(self.kind).__repr__());
+        }
+        match self.kind {
+            TypeKind::Varchar(length) => format!("Varchar({})", length),
+            TypeKind::Numeric(precision, scale) => format!("Numeric({}, {})", precision, scale),
+            TypeKind::Geometry(kind, None) => format!("Geometry('{}')", kind),
+            TypeKind::Geometry(kind, Some(srid)) => format!("Geometry('{}', {})", kind, srid),
+            TypeKind::Range(kind) => kind.to_string(),
+            TypeKind::Custom(name) => format!("<type {}>", name),
+            kind => format!("{:?}", kind),
+        }
+    }
+}
+
+/// The column types exported as constants of the module.
+pub(crate) fn column_types() -> impl Iterator<Item = (&'static str, ColumnType)> {
+    [
+        ("Integer", TypeKind::Integer),
+        ("BigInteger", TypeKind::BigInteger),
+        ("SmallInteger", TypeKind::SmallInteger),
+        ("Float", TypeKind::Float),
+        ("Boolean", TypeKind::Boolean),
+        ("Text", TypeKind::Text),
+        ("Date", TypeKind::Date),
+        ("DateTime", TypeKind::DateTime),
+        ("Blob", TypeKind::Blob),
+        ("Json", TypeKind::Json),
+        ("Interval", TypeKind::Interval),
+    ]
+    .into_iter()
+    .chain(RANGE_KINDS.map(|kind| (kind, TypeKind::Range(kind))))
+    .map(|(name, kind)| (name, ColumnType::scalar(kind)))
+}
+
+/// A `VARCHAR` column type of at most `length` characters.
+#[pyfunction(name = "Varchar")]
+pub fn varchar(length: u32) -> ColumnType {
+    ColumnType::scalar(TypeKind::Varchar(length))
+}
+
+/// An exact decimal column type with `precision` digits, `scale` of them after
+/// the decimal point.
+#[pyfunction(name = "Numeric")]
+#[pyo3(signature = (precision, scale = 0))]
+pub fn numeric(precision: u32, scale: u32) -> PyResult<ColumnType> {
+    if scale > precision {
+        return Err(FustOrmError::BuildError(format!(
+            "The scale of Numeric({}, {}) can't exceed its precision.",
+            precision, scale
+        ))
+        .into());
+    }
+    Ok(ColumnType::scalar(TypeKind::Numeric(precision, scale)))
+}
+
+/// A geometry column type, restricted to one `kind` of geometry (`"point"`,
+/// `"polygon"`, ...) in the spatial reference system `srid` if given. It is
+/// PostGIS's `geometry` on PostgreSQL, a spatial type on MySQL and a blob
+/// holding SpatiaLite geometries on SQLite.
+#[pyfunction(name = "Geometry")]
+#[pyo3(signature = (kind = "GEOMETRY", srid = None))]
+pub fn geometry(kind: &str, srid: Option<u32>) -> PyResult<ColumnType> {
+    let upper = kind.to_uppercase();
+    let Some(kind) = GEOMETRY_KINDS.into_iter().find(|k| *k == upper) else {
+        return Err(FustOrmError::BuildError(format!(
+            "Unknown geometry kind '{}'; expected one of {}.",
+            kind,
+            GEOMETRY_KINDS.join(", ").to_lowercase()
+        ))
+        .into());
+    };
+    Ok(ColumnType::scalar(TypeKind::Geometry(kind, srid)))
+}
+
+/// An array column type of `item` values: `item[]` on PostgreSQL, and text
+/// holding PostgreSQL's array syntax on other databases. Arrays of any
+/// number of dimensions fit in it.
+#[pyfunction(name = "Array")]
+pub fn array(item: ColumnType) -> PyResult<ColumnType> {
+    if item.array {
+        return Err(FustOrmError::BuildError(
+            "Array() takes the type of the items; an array column holds arrays of any \
+             number of dimensions."
+                .to_string(),
+        )
+        .into());
+    }
+    Ok(ColumnType {
+        kind: item.kind,
+        array: true,
+    })
+}
+
+/// Renders a Python value as a SQL literal for a `DEFAULT` clause.
+pub(crate) fn sql_literal(value: &Bound<PyAny>, dialect: Dialect) -> PyResult<String> {
+    if value.is_none() {
+        return Ok("NULL".to_string());
+    }
+    if let Ok(flag) = value.downcast::<PyBool>() {
+        let flag = flag.is_true();
+        return Ok(match dialect {
+            Dialect::Postgres if flag => "TRUE",
+            Dialect::Postgres => "FALSE",
+            _ if flag => "1",
+            _ => "0",
+        }
+        .to_string());
+    }
+    if value.is_instance_of::<PyInt>() || value.is_instance_of::<PyFloat>() {
+        return Ok(value.str()?.to_string());
+    }
+    if let Ok(text) = value.downcast::<PyString>() {
+        return Ok(format!("'{}'", text.to_str()?.replace('\'', "''")));
+    }
+    Err(FustOrmError::BuildError(format!(
+        "Can't use {} as a column default; pass a SQL expression as default_sql instead.",
+        value.repr()?
+    ))
+    .into())
+}
+
+/// A column of a `Table`.
+#[derive(Debug, Clone)]
+pub(crate) struct ColumnDef {
+    pub(crate) name: String,
+    pub(crate) column_type: ColumnType,
+    pub(crate) primary_key: bool,
+    pub(crate) nullable: bool,
+    pub(crate) unique: bool,
+    pub(crate) autoincrement: bool,
+    /// The rendered `DEFAULT` expression.
+    pub(crate) default: Option<DefaultValue>,
+    /// The referenced `(table, column)`.
+    pub(crate) references: Option<(String, String)>,
+    pub(crate) on_delete: Option<OnDelete>,
+    pub(crate) generated: Option<Generated>,
+}
+
+/// How the database computes a generated column from the other columns of
+/// its row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Generated {
+    pub(crate) expression: String,
+    /// Whether the values are computed on write and stored, rather than
+    /// computed on read.
+    pub(crate) stored: bool,
+}
+
+impl Generated {
+    pub(crate) fn to_sql(&self) -> String {
+        format!(
+            "GENERATED ALWAYS AS ({}) {}",
+            self.expression,
+            if self.stored { "STORED" } else { "VIRTUAL" }
+        )
+    }
+}
+
+/// A column default: a Python literal, rendered per dialect, or raw SQL.
+#[derive(Debug, Clone)]
+pub(crate) enum DefaultValue {
+    Literal(Arc<Py<PyAny>>),
+    Sql(String),
+}
+
+impl ColumnDef {
+    /// Validates the options of a column declared on `table`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        table: &str,
+        name: String,
+        column_type: ColumnType,
+        primary_key: bool,
+        nullable: Option<bool>,
+        unique: bool,
+        default: Option<Py<PyAny>>,
+        default_sql: Option<String>,
+        autoincrement: bool,
+        references: Option<&Bound<PyAny>>,
+        on_delete: Option<&str>,
+        generated: Option<String>,
+        stored: bool,
+    ) -> PyResult<Self> {
+        let generated = match generated {
+            Some(_)
+                if primary_key || autoincrement || default.is_some() || default_sql.is_some() =>
+            {
+                return Err(FustOrmError::BuildError(format!(
+                    "Column '{}' of '{}' is generated and can't be a primary key or have a default.",
+                    name, table
+                ))
+                .into());
+            }
+            Some(expression) => Some(Generated { expression, stored }),
+            None if stored => {
+                return Err(FustOrmError::BuildError(format!(
+                    "Column '{}' of '{}' is stored but not generated.",
+                    name, table
+                ))
+                .into());
+            }
+            None => None,
+        };
+        if autoincrement && !column_type.is_integer() {
+            return Err(FustOrmError::BuildError(format!(
+                "Column '{}' of '{}' is autoincremented and must have an integer type.",
+                name, table
+            ))
+            .into());
+        }
+        let default = match (default, default_sql) {
+            (Some(_), Some(_)) => {
+                return Err(FustOrmError::BuildError(format!(
+                    "Column '{}' of '{}' accepts either default or default_sql, not both.",
+                    name, table
+                ))
+                .into());
+            }
+            (Some(value), None) => Some(DefaultValue::Literal(Arc::new(value))),
+            (None, Some(sql)) => Some(DefaultValue::Sql(sql)),
+            (None, None) => None,
+        };
+        let references = references.map(resolve_reference).transpose()?;
+        let on_delete = match on_delete {
+            Some(_) if references.is_none() => {
+                return Err(FustOrmError::BuildError(format!(
+                    "Column '{}' of '{}' sets on_delete without references.",
+                    name, table
+                ))
+                .into());
+            }
+            Some(value) => Some(OnDelete::parse(value)?),
+            None => None,
+        };
+        Ok(ColumnDef {
+            name,
+            column_type,
+            primary_key,
+            nullable: nullable.unwrap_or(!primary_key),
+            unique,
+            autoincrement,
+            default,
+            references,
+            on_delete,
+            generated,
+        })
+    }
+
+    /// Renders the column definition. `inline_pk` marks the column as the
+    /// table's only primary key column, declared on the column itself.
+    pub(crate) fn to_sql(&self, py: Python, dialect: Dialect, inline_pk: bool) -> PyResult<String> {
+        let mut sql = format!("{} {}", self.name, self.column_type.to_sql(dialect));
+        if let Some(generated) = &self.generated {
+            sql.push(' ');
+            sql.push_str(&generated.to_sql());
+        }
+        if self.autoincrement && dialect == Dialect::Postgres {
+            sql.push_str(" GENERATED BY DEFAULT AS IDENTITY");
+        }
+        if !self.nullable {
+            sql.push_str(" NOT NULL");
+        }
+        if self.autoincrement && dialect == Dialect::Mysql {
+            sql.push_str(" AUTO_INCREMENT");
+        }
+        if inline_pk {
+            sql.push_str(" PRIMARY KEY");
+            if self.autoincrement && dialect == Dialect::Sqlite {
+                sql.push_str(" AUTOINCREMENT");
+            }
+        }
+        if self.unique {
+            sql.push_str(" UNIQUE");
+        }
+        match &self.default {
+            Some(DefaultValue::Literal(value)) => {
+                sql.push_str(" DEFAULT ");
+                sql.push_str(&sql_literal(value.bind(py), dialect)?);
+            }
+            Some(DefaultValue::Sql(expression)) => {
+                sql.push_str(&format!(" DEFAULT ({})", expression));
+            }
+            None => {}
+        }
+        if let Some((table, column)) = &self.references {
+            sql.push_str(&format!(" REFERENCES {} ({})", table, column));
+            if let Some(action) = self.on_delete.and_then(OnDelete::sql) {
+                sql.push_str(" ON DELETE ");
+                sql.push_str(action);
+            }
+        }
+        Ok(sql)
+    }
+}
+
+/// Resolves the `references` argument of `Table.column()`: a Model class (its
+/// single primary key column) or a `"table.column"` string.
+fn resolve_reference(target: &Bound<PyAny>) -> PyResult<(String, String)> {
+    if let Ok(cls) = target.downcast::<PyType>() {
+        let info = ModelInfo::from_class(cls)?;
+        return match info.primary_key()?.as_slice() {
+            [pk] => Ok((info.table_name.clone(), pk.column_name.clone())),
+            _ => Err(FustOrmError::BuildError(format!(
+                "'{}' has a composite primary key and can't be referenced by a single column.",
+                info.table_name
+            ))
+            .into()),
+        };
+    }
+    let target = target.extract::<String>()?;
+    match target.split_once('.') {
+        Some((table, column)) if !table.is_empty() && !column.is_empty() => {
+            Ok((table.to_string(), column.to_string()))
+        }
+        _ => Err(FustOrmError::BuildError(format!(
+            "references='{}' must be a Model class or a 'table.column' string.",
+            target
+        ))
+        .into()),
+    }
+}
+
+/// Resolves a table given as a name, a `Table` or a Model class.
+pub(crate) fn table_name(target: &Bound<PyAny>) -> PyResult<String> {
+    if let Ok(cls) = target.downcast::<PyType>() {
+        return Ok(ModelInfo::from_class(cls)?.table_name);
+    }
+    if let Ok(table) = target.extract::<Table>() {
+        return Ok(table.name);
+    }
+    target.extract::<String>().map_err(|_| {
+        FustOrmError::BuildError("The table must be a name, a Table or a Model class.".to_string())
+            .into()
+    })
+}
+
+/// Executes the schema change `sql` in the transaction of `db`, or in one of
+/// its own, which in a `test_database()` block of the pool is a savepoint of
+/// the test's transaction, rolled back with it.
+async fn execute_ddl(db: &Database, sql: &str) -> Result<(), FustOrmError> {
+    if mock::is_mocked(&db.pool) {
+        db.execute_statement(sql, Vec::new()).await?;
+        return Ok(());
+    }
+    let mut tx = db.begin_write().await?;
+    execute_statement(&mut *tx, sql, Vec::new()).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Drops `table` (a name, a `Table` or a Model class).
+pub(crate) fn drop_table<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<PyAny>,
+    if_exists: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let sql = format!(
+        "DROP TABLE {}{}",
+        if if_exists { "IF EXISTS " } else { "" },
+        table_name(table)?
+    );
+    let db = context::resolve(py, Some(db))?;
+    future_into_py(py, async move {
+        debug!("Dropping table: {}", sql);
+        execute_ddl(&db, &sql).await?;
+        Ok(())
+    })
+}
+
+/// Deletes all rows of `table` and restarts its autoincrement sequence.
+///
+/// SQLite has no `TRUNCATE`, so its rows are deleted and the table's entry in
+/// `sqlite_sequence` removed, in one transaction.
+pub(crate) fn truncate<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table_name(table)?;
+    let dialect = db.dialect();
+    let db = context::resolve(py, Some(db))?;
+    future_into_py(py, async move {
+        debug!("Truncating table: {}", table);
+        match dialect {
+            Dialect::Postgres => {
+                let sql = format!("TRUNCATE TABLE {} RESTART IDENTITY", table);
+                execute_ddl(&db, &sql).await?;
+            }
+            Dialect::Mysql => {
+                let sql = format!("TRUNCATE TABLE {}", table);
+                execute_ddl(&db, &sql).await?;
+            }
+            Dialect::Sqlite => {
+                let mut tx = db.begin_write().await?;
+                execute_statement(&mut *tx, &format!("DELETE FROM {}", table), Vec::new()).await?;
+                // `sqlite_sequence` only exists once an AUTOINCREMENT table does.
+                let sequence = fetch_rows(
+                    &mut *tx,
+                    "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sqlite_sequence'",
+                    Vec::new(),
+                )
+                .await?;
+                if !sequence.is_empty() {
+                    execute_statement(
+                        &mut *tx,
+                        "DELETE FROM sqlite_sequence WHERE name = ?",
+                        vec![table],
+                    )
+                    .await?;
+                }
+                tx.commit().await.map_err(FustOrmError::from)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Renders a key part of an index: a column (`ColumnField` or name, with an
+/// optional `ASC`/`DESC`) as is, anything else as a parenthesized expression.
+fn index_part(part: &Bound<PyAny>) -> PyResult<String> {
+    if let Ok(field) = part.extract::<ColumnField>() {
+        return Ok(field.column_name);
+    }
+    let part = part.extract::<String>().map_err(|_| {
+        FustOrmError::BuildError(
+            "Index columns must be column names, ColumnFields or SQL expressions.".to_string(),
+        )
+    })?;
+    let part = part.trim();
+    let column = match part.rsplit_once(' ') {
+        Some((column, order))
+            if order.eq_ignore_ascii_case("ASC") || order.eq_ignore_ascii_case("DESC") =>
+        {
+            column.trim_end()
+        }
+        _ => part,
+    };
+    let is_identifier = column.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && column
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if part.is_empty() || is_identifier {
+        Ok(part.to_string())
+    } else {
+        Ok(format!("({})", part))
+    }
+}
+
+/// Names an index after its table and key parts, e.g. `users_lower_email_idx`.
+pub(crate) fn index_name(table: &str, parts: &[String], unique: bool) -> String {
+    let mut name = table.to_string();
+    for part in parts {
+        for word in part
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .filter(|w| !w.is_empty())
+        {
+            name.push('_');
+            name.push_str(&word.to_lowercase());
+        }
+    }
+    name.push_str(if unique { "_key" } else { "_idx" });
+    name
+}
+
+/// Creates an index on `table` over `columns` and returns its name.
+///
+/// `columns` is a column or a list of them, where entries that are not plain
+/// column names are indexed as expressions. `where_sql` makes a partial index,
+/// which MySQL lacks. MySQL has no `IF NOT EXISTS` for indexes either, so
+/// there the existing indexes are looked up first.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_index<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<PyAny>,
+    columns: &Bound<PyAny>,
+    unique: bool,
+    where_sql: Option<String>,
+    name: Option<String>,
+    if_not_exists: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table_name(table)?;
+    let parts = if columns.is_instance_of::<PyString>() || columns.extract::<ColumnField>().is_ok()
+    {
+        vec![index_part(columns)?]
+    } else {
+        columns
+            .try_iter()?
+            .map(|part| index_part(&part?))
+            .collect::<PyResult<Vec<_>>>()?
+    };
+    if parts.is_empty() || parts.iter().any(|p| p.is_empty()) {
+        return Err(FustOrmError::BuildError(format!(
+            "An index of '{}' needs at least one column.",
+            table
+        ))
+        .into());
+    }
+    let dialect = db.dialect();
+    if where_sql.is_some() && dialect == Dialect::Mysql {
+        return Err(
+            FustOrmError::BuildError("MySQL doesn't support partial indexes.".to_string()).into(),
+        );
+    }
+    let name = name.unwrap_or_else(|| index_name(&table, &parts, unique));
+    let mut sql = format!(
+        "CREATE {}INDEX {}{} ON {} ({})",
+        if unique { "UNIQUE " } else { "" },
+        if if_not_exists && dialect != Dialect::Mysql {
+            "IF NOT EXISTS "
+        } else {
+            ""
+        },
+        name,
+        table,
+        parts.join(", ")
+    );
+    if let Some(where_sql) = where_sql {
+        sql.push_str(&format!(" WHERE {}", where_sql));
+    }
+    let db = context::resolve(py, Some(db))?;
+    future_into_py(py, async move {
+        if if_not_exists && dialect == Dialect::Mysql {
+            let existing = introspect::indexes(&db.pool, dialect, &table).await?;
+            if existing.iter().any(|index| index.name == name) {
+                return Ok(name);
+            }
+        }
+        debug!("Creating index: {}", sql);
+        execute_ddl(&db, &sql).await?;
+        Ok(name)
+    })
+}
+
+/// Drops the index `name`. MySQL names indexes per table, so it needs `table`.
+pub(crate) fn drop_index<'py>(
+    py: Python<'py>,
+    db: &Database,
+    name: String,
+    table: Option<&Bound<PyAny>>,
+    if_exists: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table.map(table_name).transpose()?;
+    let dialect = db.dialect();
+    let sql = match (dialect, &table) {
+        (Dialect::Mysql, Some(table)) => format!("DROP INDEX {} ON {}", name, table),
+        (Dialect::Mysql, None) => {
+            return Err(FustOrmError::BuildError(format!(
+                "Dropping the index '{}' on MySQL needs its table.",
+                name
+            ))
+            .into());
+        }
+        _ => format!(
+            "DROP INDEX {}{}",
+            if if_exists { "IF EXISTS " } else { "" },
+            name
+        ),
+    };
+    let db = context::resolve(py, Some(db))?;
+    future_into_py(py, async move {
+        if let (Dialect::Mysql, Some(table), true) = (dialect, &table, if_exists) {
+            let existing = introspect::indexes(&db.pool, dialect, table).await?;
+            if !existing.iter().any(|index| index.name == name) {
+                return Ok(());
+            }
+        }
+        debug!("Dropping index: {}", sql);
+        execute_ddl(&db, &sql).await?;
+        Ok(())
+    })
+}
+
+/// Lists the indexes of `table` as dictionaries, except primary key indexes.
+pub(crate) fn list_indexes<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table_name(table)?;
+    let dialect = db.dialect();
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let indexes = introspect::indexes(&pool, dialect, &table).await?;
+        Python::attach(|py| -> PyResult<Py<PyList>> {
+            let list = PyList::empty(py);
+            for index in &indexes {
+                list.append(index.to_dict(py)?)?;
+            }
+            Ok(list.into())
+        })
+    })
+}
+
+/// Lists the table-level unique and check constraints of `table` as
+/// dictionaries.
+pub(crate) fn list_constraints<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table_name(table)?;
+    let dialect = db.dialect();
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let constraints = introspect::constraints(&pool, dialect, &table).await?;
+        Python::attach(|py| -> PyResult<Py<PyList>> {
+            let list = PyList::empty(py);
+            for constraint in &constraints {
+                list.append(constraint.to_dict(py)?)?;
+            }
+            Ok(list.into())
+        })
+    })
+}
+
+/// Infers the column type of `column` of a model from its `ColumnField[...]`
+/// annotation, and whether it is nullable (`Optional[...]` / `... | None`).
+fn annotated_type(cls: &Bound<PyType>, column: &ColumnField) -> PyResult<(ColumnType, bool)> {
+    let py = cls.py();
+    let mut annotation = None;
+    for klass in cls.getattr(pyo3::intern!(py, "__mro__"))?.try_iter()? {
+        let Ok(annotations) = klass?.getattr(pyo3::intern!(py, "__annotations__")) else {
+            continue;
+        };
+        if let Ok(found) = annotations.get_item(&column.column_name) {
+            annotation = Some(found);
+            break;
+        }
+    }
+    let typing = py.import("typing")?;
+    let unknown = || {
+        FustOrmError::BuildError(format!(
+            "Can't infer the type of column '{}' of '{}'; annotate it as ColumnField[int], \
+             ColumnField[str], ColumnField[datetime.date] and the like.",
+            column.column_name, column.table_name
+        ))
+    };
+    let mut arg = annotation
+        .and_then(|a| typing.call_method1("get_args", (a,)).ok())
+        .and_then(|args| args.get_item(0).ok())
+        .ok_or_else(unknown)?;
+    let mut nullable = false;
+    let none_type = py.None().bind(py).get_type();
+    let args = typing.call_method1("get_args", (&arg,))?;
+    let origin = typing.call_method1("get_origin", (&arg,))?;
+    let is_union = origin.is(typing.getattr("Union")?)
+        || origin.is(py.import("types")?.getattr("UnionType")?);
+    if is_union {
+        let members: Vec<Bound<PyAny>> = args
+            .try_iter()?
+            .filter(|a| a.as_ref().map_or(true, |a| !a.is(&none_type)))
+            .collect::<PyResult<_>>()?;
+        match members.as_slice() {
+            [member] => {
+                arg = member.clone();
+                nullable = true;
+            }
+            _ => return Err(unknown().into()),
+        }
+    }
+    if column.enum_type.is_some() {
+        // Enums store their members' values, unless they are PostgreSQL enums.
+        let enum_type = arg.downcast::<PyType>()?;
+        if let Some(column_type) = ColumnType::for_type(enum_type)? {
+            return Ok((column_type, nullable));
+        }
+        let all_int = enum_type.try_iter()?.all(|member| {
+            member
+                .and_then(|m| m.getattr("value"))
+                .is_ok_and(|v| v.is_instance_of::<PyInt>() && !v.is_instance_of::<PyBool>())
+        });
+        let kind = if all_int {
+            TypeKind::Integer
+        } else {
+            TypeKind::Text
+        };
+        return Ok((ColumnType::scalar(kind), nullable));
+    }
+    let origin = typing.call_method1("get_origin", (&arg,))?;
+    // `list[int]` and the like are arrays, other lists JSON.
+    if origin.is(py.get_type::<PyList>()) {
+        let items = typing.call_method1("get_args", (&arg,))?;
+        if let Ok(item) = items.get_item(0)
+            && let Some(kind) = scalar_kind(&item)?
+            && !matches!(kind, TypeKind::Json | TypeKind::Interval)
+        {
+            return Ok((ColumnType { kind, array: true }, nullable));
+        }
+    }
+    if origin.is(py.get_type::<Range>()) {
+        let item = typing.call_method1("get_args", (&arg,))?.get_item(0)?;
+        let kind = range_kind(&item)?.ok_or_else(unknown)?;
+        return Ok((ColumnType::scalar(kind), nullable));
+    }
+    let arg = if origin.is_none() { arg } else { origin };
+    Ok((ColumnType::for_type(&arg)?.ok_or_else(unknown)?, nullable))
+}
+
+/// The column type of values of the Python type `arg`, if it has one.
+pub(crate) fn scalar_kind(arg: &Bound<PyAny>) -> PyResult<Option<TypeKind>> {
+    let py = arg.py();
+    let datetime = py.import("datetime")?;
+    Ok(Some(if arg.is(py.get_type::<PyBool>()) {
+        TypeKind::Boolean
+    } else if arg.is(py.get_type::<PyInt>()) {
+        TypeKind::Integer
+    } else if arg.is(py.get_type::<PyFloat>()) {
+        TypeKind::Float
+    } else if arg.is(py.get_type::<PyString>()) {
+        TypeKind::Text
+    } else if arg.is(py.get_type::<PyBytes>()) {
+        TypeKind::Blob
+    } else if arg.is(datetime.getattr("datetime")?) {
+        TypeKind::DateTime
+    } else if arg.is(datetime.getattr("date")?) {
+        TypeKind::Date
+    } else if arg.is(datetime.getattr("timedelta")?) {
+        TypeKind::Interval
+    } else if arg.is(py.get_type::<PyDict>()) || arg.is(py.get_type::<PyList>()) {
+        TypeKind::Json
+    } else {
+        return Ok(None);
+    }))
+}
+
+/// The range type of ranges of the Python type `item`: `int4range` for
+/// `int`, `numrange` for `float` and `decimal.Decimal`, `daterange` for
+/// `datetime.date` and `tstzrange` for `datetime.datetime`.
+pub(crate) fn range_kind(item: &Bound<PyAny>) -> PyResult<Option<TypeKind>> {
+    let decimal = item.py().import("decimal")?.getattr("Decimal")?;
+    let kind = match scalar_kind(item)? {
+        Some(TypeKind::Integer) => "Int4Range",
+        Some(TypeKind::Float) => "NumRange",
+        Some(TypeKind::Date) => "DateRange",
+        Some(TypeKind::DateTime) => "TstzRange",
+        None if item.is(&decimal) => "NumRange",
+        _ => return Ok(None),
+    };
+    Ok(Some(TypeKind::Range(kind)))
+}
+
+/// A table-level unique constraint of a `Table`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UniqueConstraint {
+    pub(crate) name: Option<String>,
+    pub(crate) columns: Vec<String>,
+}
+
+/// A named `CHECK` constraint of a `Table`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CheckConstraint {
+    pub(crate) name: String,
+    /// The condition each row must meet, as SQL.
+    pub(crate) expression: String,
+}
+
+impl CheckConstraint {
+    pub(crate) fn to_sql(&self) -> String {
+        format!("CONSTRAINT {} CHECK ({})", self.name, self.expression)
+    }
+}
+
+/// Extracts the column names passed as `*columns`.
+pub(crate) fn name_list(columns: &Bound<PyTuple>) -> PyResult<Vec<String>> {
+    columns.iter().map(|c| c.extract::<String>()).collect()
+}
+
+/// A programmatic `CREATE TABLE` statement.
+///
+/// Each method returns a copy of the table with the change applied:
+///
+/// ```python
+/// users = (
+///     Table("users")
+///     .column("id", BigInteger, primary_key=True, autoincrement=True)
+///     .column("email", Varchar(255), nullable=False, unique=True)
+///     .column("created_at", DateTime, default_sql="CURRENT_TIMESTAMP")
+///     .check("email_has_at", "email LIKE '%@%'")
+/// )
+/// await users.create(db)
+/// ```
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct Table {
+    #[pyo3(get)]
+    pub(crate) name: String,
+    pub(crate) columns: Vec<ColumnDef>,
+    /// The columns of a table-level primary key, set with `primary_key()`.
+    pub(crate) primary_key: Vec<String>,
+    /// Table-level unique constraints.
+    pub(crate) unique: Vec<UniqueConstraint>,
+    pub(crate) checks: Vec<CheckConstraint>,
+}
+
+impl Table {
+    fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    fn check_columns(&self, columns: &[String], what: &str) -> PyResult<()> {
+        if columns.is_empty() {
+            return Err(FustOrmError::BuildError(format!(
+                "{} of '{}' needs at least one column.",
+                what, self.name
+            ))
+            .into());
+        }
+        if let Some(column) = columns.iter().find(|c| self.column_index(c).is_none()) {
+            return Err(FustOrmError::BuildError(format!(
+                "{} of '{}' names '{}', which is not a column of the table.",
+                what, self.name, column
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Rejects a constraint `name` the table already uses.
+    fn check_constraint_name(&self, name: &str) -> PyResult<()> {
+        let taken = self.checks.iter().any(|c| c.name == name)
+            || self.unique.iter().any(|u| u.name.as_deref() == Some(name));
+        if name.trim().is_empty() || taken {
+            return Err(FustOrmError::BuildError(format!(
+                "Table '{}' {} a constraint named '{}'.",
+                self.name,
+                if taken { "already has" } else { "can't have" },
+                name
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// The primary key columns, whether declared on the columns or on the table.
+    pub(crate) fn primary_key_columns(&self) -> Vec<String> {
+        if !self.primary_key.is_empty() {
+            return self.primary_key.clone();
+        }
+        self.columns
+            .iter()
+            .filter(|c| c.primary_key)
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    /// Renders the column definitions and table constraints.
+    pub(crate) fn body_sql(&self, py: Python, dialect: Dialect) -> PyResult<String> {
+        if self.columns.is_empty() {
+            return Err(
+                FustOrmError::BuildError(format!("Table '{}' has no columns.", self.name)).into(),
+            );
+        }
+        let primary_key = self.primary_key_columns();
+        let inline_pk = self.primary_key.is_empty() && primary_key.len() == 1;
+        if let Some(column) = self
+            .columns
+            .iter()
+            .find(|c| c.autoincrement && !(inline_pk && c.primary_key))
+        {
+            return Err(FustOrmError::BuildError(format!(
+                "Column '{}' of '{}' is autoincremented, which requires it to be the only primary key column.",
+                column.name, self.name
+            ))
+            .into());
+        }
+        let mut parts = self
+            .columns
+            .iter()
+            .map(|c| c.to_sql(py, dialect, inline_pk && c.primary_key))
+            .collect::<PyResult<Vec<_>>>()?;
+        if !inline_pk && !primary_key.is_empty() {
+            parts.push(format!("PRIMARY KEY ({})", primary_key.join(", ")));
+        }
+        for unique in &self.unique {
+            let columns = unique.columns.join(", ");
+            parts.push(match &unique.name {
+                Some(name) => format!("CONSTRAINT {} UNIQUE ({})", name, columns),
+                None => format!("UNIQUE ({})", columns),
+            });
+        }
+        parts.extend(self.checks.iter().map(CheckConstraint::to_sql));
+        Ok(parts.join(", "))
+    }
+
+    /// Describes the table of a model from its columns' annotations and
+    /// metadata: `Optional[...]` columns are nullable, a single integer primary
+    /// key is autoincremented, references become foreign keys,
+    /// `Meta.unique_together` unique constraints and `Meta.checks` check
+    /// constraints.
+    pub(crate) fn from_model(cls: &Bound<PyType>) -> PyResult<Table> {
+        let py = cls.py();
+        let info = ModelInfo::from_class(cls)?;
+        if info.is_view {
+            return Err(FustOrmError::BuildError(format!(
+                "Model '{}' maps onto a view; describe it with View.from_model().",
+                cls.name()?
+            ))
+            .into());
+        }
+        let primary_key = info.primary_key()?.len();
+        let mut columns = Vec::with_capacity(info.columns.len());
+        for field in &info.columns {
+            let (column_type, nullable) = annotated_type(cls, field)?;
+            let references = match &field.references {
+                Some(target) => Some(resolve_reference(target.bind(py))?),
+                None => None,
+            };
+            let on_delete = references.as_ref().map(|_| field.on_delete);
+            columns.push(ColumnDef {
+                name: field.column_name.clone(),
+                column_type,
+                primary_key: field.primary_key,
+                nullable: !field.primary_key && (nullable || on_delete == Some(OnDelete::SetNull)),
+                unique: false,
+                autoincrement: field.primary_key && primary_key == 1 && column_type.is_integer(),
+                default: None,
+                references,
+                on_delete,
+                generated: field.generated.clone().map(|expression| Generated {
+                    expression,
+                    stored: field.stored,
+                }),
+            });
+        }
+        let unique = cls
+            .getattr(pyo3::intern!(py, "__unique_together__"))?
+            .extract::<Vec<Vec<String>>>()?
+            .into_iter()
+            .map(|columns| UniqueConstraint {
+                name: None,
+                columns,
+            })
+            .collect();
+        let checks = cls
+            .getattr(pyo3::intern!(py, "__checks__"))?
+            .extract::<Option<Vec<(String, String)>>>()?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, expression)| CheckConstraint { name, expression })
+            .collect();
+        Ok(Table {
+            name: info.table_name,
+            columns,
+            primary_key: Vec::new(),
+            unique,
+            checks,
+        })
+    }
+
+    pub(crate) fn create_sql(
+        &self,
+        py: Python,
+        dialect: Dialect,
+        if_not_exists: bool,
+    ) -> PyResult<String> {
+        Ok(format!(
+            "CREATE TABLE {}{} ({})",
+            if if_not_exists { "IF NOT EXISTS " } else { "" },
+            self.name,
+            self.body_sql(py, dialect)?
+        ))
+    }
+}
+
+#[pymethods]
+impl Table {
+    #[new]
+    fn new(name: String) -> Self {
+        Table {
+            name,
+            columns: Vec::new(),
+            primary_key: Vec::new(),
+            unique: Vec::new(),
+            checks: Vec::new(),
+        }
+    }
+
+    /// Describes the table of a Model class, inferring column types from the
+    /// `ColumnField[...]` annotations.
+    #[staticmethod]
+    #[pyo3(name = "from_model")]
+    fn py_from_model(model: &Bound<PyType>) -> PyResult<Table> {
+        Table::from_model(model)
+    }
+
+    /// Returns a copy of the table with a column added.
+    ///
+    /// Primary key columns are `NOT NULL` unless `nullable` says otherwise.
+    /// `default` is a Python literal; `default_sql` a SQL expression such as
+    /// `CURRENT_TIMESTAMP`. `references` is a Model class or a `"table.column"`
+    /// string, with `on_delete` as in `ColumnField`. `generated` makes it a
+    /// column the database computes with that SQL expression, on read or
+    /// (if `stored`) on write.
+    #[pyo3(signature = (
+        name,
+        column_type,
+        *,
+        primary_key = false,
+        nullable = None,
+        unique = false,
+        default = None,
+        default_sql = None,
+        autoincrement = false,
+        references = None,
+        on_delete = None,
+        generated = None,
+        stored = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn column(
+        &self,
+        name: String,
+        column_type: ColumnType,
+        primary_key: bool,
+        nullable: Option<bool>,
+        unique: bool,
+        default: Option<Py<PyAny>>,
+        default_sql: Option<String>,
+        autoincrement: bool,
+        references: Option<&Bound<PyAny>>,
+        on_delete: Option<&str>,
+        generated: Option<String>,
+        stored: bool,
+    ) -> PyResult<Table> {
+        if self.column_index(&name).is_some() {
+            return Err(FustOrmError::BuildError(format!(
+                "Table '{}' already has a column '{}'.",
+                self.name, name
+            ))
+            .into());
+        }
+        let column = ColumnDef::new(
+            &self.name,
+            name,
+            column_type,
+            primary_key,
+            nullable,
+            unique,
+            default,
+            default_sql,
+            autoincrement,
+            references,
+            on_delete,
+            generated,
+            stored,
+        )?;
+        let mut table = self.clone();
+        table.columns.push(column);
+        Ok(table)
+    }
+
+    /// Returns a copy of the table with a (composite) primary key over `columns`.
+    #[pyo3(signature = (*columns))]
+    fn primary_key(&self, columns: &Bound<PyTuple>) -> PyResult<Table> {
+        let columns = name_list(columns)?;
+        self.check_columns(&columns, "The primary key")?;
+        if let Some(column) = self.columns.iter().find(|c| c.primary_key) {
+            return Err(FustOrmError::BuildError(format!(
+                "Column '{}' of '{}' is already declared as the primary key.",
+                column.name, self.name
+            ))
+            .into());
+        }
+        let mut table = self.clone();
+        for column in table.columns.iter_mut() {
+            if columns.contains(&column.name) {
+                column.nullable = false;
+            }
+        }
+        table.primary_key = columns;
+        Ok(table)
+    }
+
+    /// Returns a copy of the table with a unique constraint over `columns`,
+    /// optionally named `name`.
+    #[pyo3(signature = (*columns, name = None))]
+    fn unique(&self, columns: &Bound<PyTuple>, name: Option<String>) -> PyResult<Table> {
+        let columns = name_list(columns)?;
+        self.check_columns(&columns, "A unique constraint")?;
+        if let Some(name) = &name {
+            self.check_constraint_name(name)?;
+        }
+        let mut table = self.clone();
+        table.unique.push(UniqueConstraint { name, columns });
+        Ok(table)
+    }
+
+    /// Returns a copy of the table with the check constraint `name`, which
+    /// rows must meet the SQL condition `expression` to satisfy.
+    fn check(&self, name: String, expression: String) -> PyResult<Table> {
+        self.check_constraint_name(&name)?;
+        let mut table = self.clone();
+        table.checks.push(CheckConstraint { name, expression });
+        Ok(table)
+    }
+
+    /// The names of the table's columns, in order.
+    #[getter]
+    fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// Renders the `CREATE TABLE` statement for `dialect` (`"sqlite"`,
+    /// `"postgres"` or `"mysql"`).
+    #[pyo3(signature = (dialect = "sqlite", *, if_not_exists = false))]
+    fn to_sql(&self, py: Python, dialect: &str, if_not_exists: bool) -> PyResult<String> {
+        self.create_sql(py, Dialect::parse(dialect)?, if_not_exists)
+    }
+
+    /// Creates the table in the database, in its dialect.
+    #[pyo3(signature = (db, *, if_not_exists = false))]
+    fn create<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+        if_not_exists: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let sql = self.create_sql(py, db.dialect(), if_not_exists)?;
+        let db = context::resolve(py, Some(db))?;
+        future_into_py(py, async move {
+            debug!("Creating table: {}", sql);
+            execute_ddl(&db, &sql).await?;
+            Ok(())
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Table {} ({})>", self.name, self.column_names().join(", "))
+    }
+}
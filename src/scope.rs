@@ -0,0 +1,179 @@
+use crate::error::FustOrmError;
+use crate::model::discriminator_filter;
+use crate::query::{QueryBuilder, select};
+use crate::where_condition::WhereCondition;
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple, PyType};
+
+/// A named query scope, created with the `@scope` decorator on a model method.
+///
+/// The decorated function receives the model class (like a classmethod) plus any
+/// call arguments, and returns a `WhereCondition`, a list of conditions, or a
+/// complete `QueryBuilder`. Accessing the scope on a model binds it to that model,
+/// so `Orders.recent(days=7)` returns a `QueryBuilder` for `Orders` with the model's
+/// default scope and the returned conditions applied.
+#[pyclass]
+pub struct Scope {
+    func: Py<PyAny>,
+}
+
+/// A `Scope` bound to the model class it was accessed on.
+#[pyclass]
+pub struct BoundScope {
+    func: Py<PyAny>,
+    model: Py<PyType>,
+}
+
+#[pymethods]
+impl Scope {
+    #[new]
+    fn new(func: Py<PyAny>) -> Self {
+        Scope { func }
+    }
+
+    /// Descriptor protocol: binds the scope to the model class.
+    fn __get__(
+        &self,
+        py: Python,
+        instance: Option<&Bound<PyAny>>,
+        owner: Option<Bound<PyType>>,
+    ) -> PyResult<BoundScope> {
+        let model = match (owner, instance) {
+            (Some(owner), _) => owner,
+            (None, Some(instance)) => instance.get_type(),
+            (None, None) => {
+                return Err(pyo3::exceptions::PyTypeError::new_err(
+                    "scope.__get__ requires an instance or an owner class",
+                ));
+            }
+        };
+        Ok(BoundScope {
+            func: self.func.clone_ref(py),
+            model: model.unbind(),
+        })
+    }
+
+    #[getter]
+    fn __doc__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        Ok(self.func.bind(py).getattr("__doc__")?.unbind())
+    }
+
+    #[getter]
+    fn __name__(&self, py: Python) -> PyResult<Py<PyAny>> {
+        Ok(self.func.bind(py).getattr("__name__")?.unbind())
+    }
+}
+
+#[pymethods]
+impl BoundScope {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &Bound<PyTuple>,
+        kwargs: Option<&Bound<PyDict>>,
+    ) -> PyResult<QueryBuilder> {
+        let model = self.model.bind(py);
+        let mut call_args = vec![model.clone().into_any()];
+        call_args.extend(args.iter());
+        let result = self
+            .func
+            .bind(py)
+            .call(PyTuple::new(py, call_args)?, kwargs)?;
+
+        if let Ok(query) = result.extract::<QueryBuilder>() {
+            return Ok(query);
+        }
+        model_query(model, &scope_conditions(&result)?, true)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "<scope {}.{}>",
+            self.model.bind(py).name()?,
+            self.func.bind(py).getattr("__name__")?
+        ))
+    }
+}
+
+/// Decorator declaring a named query scope on a model.
+///
+/// Example:
+/// ```python
+/// class Order(Model):
+///     @scope
+///     def recent(cls, days: int = 7):
+///         return cls.created_at > days_ago(days)
+/// ```
+#[pyfunction]
+pub fn scope(func: Py<PyAny>) -> Scope {
+    Scope { func }
+}
+
+/// Normalizes what a scope function returned into a list of conditions.
+pub(crate) fn scope_conditions<'py>(
+    result: &Bound<'py, PyAny>,
+) -> PyResult<Vec<Bound<'py, PyAny>>> {
+    if result.is_none() {
+        return Ok(Vec::new());
+    }
+    if result.extract::<PyRef<WhereCondition>>().is_ok() {
+        return Ok(vec![result.clone()]);
+    }
+    let items = result
+        .try_iter()
+        .map_err(|_| {
+            FustOrmError::InvalidQueryArgument(format!(
+                "A scope must return a condition, a list of conditions or a query, not {}",
+                result
+                    .get_type()
+                    .name()
+                    .map(|n| n.to_string())
+                    .unwrap_or_default()
+            ))
+        })?
+        .collect::<PyResult<Vec<_>>>()?;
+    for item in &items {
+        if item.extract::<PyRef<WhereCondition>>().is_err() {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "A scope returned an unsupported item: {}",
+                item.get_type().name()?
+            ))
+            .into());
+        }
+    }
+    Ok(items)
+}
+
+/// Builds `select(model, *default_scope, *clauses)`.
+///
+/// The default scope is read from `__default_scope__`, which `Model.__init_subclass__`
+/// fills from `Meta.default_scope`. Queries on a subclass in a single-table
+/// inheritance hierarchy are also restricted to its discriminator values.
+pub(crate) fn model_query(
+    model: &Bound<PyType>,
+    clauses: &[Bound<PyAny>],
+    apply_default_scope: bool,
+) -> PyResult<QueryBuilder> {
+    let py = model.py();
+    let mut args = vec![model.clone().into_any()];
+    if apply_default_scope {
+        let default_scope = model.getattr(pyo3::intern!(py, "__default_scope__"))?;
+        let default_scope = default_scope.downcast::<PyTuple>()?;
+        debug!(
+            "Applying {} default scope conditions for model '{}'",
+            default_scope.len(),
+            model.name()?
+        );
+        args.extend(default_scope.iter());
+    }
+    if let Some((column, values)) = discriminator_filter(model)? {
+        let condition = model
+            .getattr(&column)?
+            .call_method1("in_", (PyList::new(py, values)?,))?;
+        args.push(condition);
+    }
+    args.extend(clauses.iter().cloned());
+    select(py, &PyTuple::new(py, args)?)
+}
@@ -0,0 +1,604 @@
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::Row;
+
+use crate::bulk::rows_per_statement;
+use crate::context;
+use crate::database::{Database, execute_statement};
+use crate::error::FustOrmError;
+use crate::fixtures::fixture_param;
+use crate::introspect::{self, ColumnInfo};
+use crate::schema::{Dialect, table_name};
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada",
+    "Alan",
+    "Barbara",
+    "Carlos",
+    "Chen",
+    "Dmitri",
+    "Edsger",
+    "Fatima",
+    "Grace",
+    "Hedy",
+    "Ingrid",
+    "John",
+    "Katherine",
+    "Linus",
+    "Margaret",
+    "Nikolai",
+    "Olga",
+    "Priya",
+    "Radia",
+    "Yuki",
+];
+const LAST_NAMES: &[&str] = &[
+    "Allen", "Brown", "Garcia", "Hopper", "Ivanova", "Johnson", "Kim", "Lamarr", "Liskov",
+    "Lovelace", "Martin", "Nguyen", "Patel", "Perlman", "Ritchie", "Silva", "Tanaka", "Turing",
+    "Wang", "Wilson",
+];
+const WORDS: &[&str] = &[
+    "amber", "anchor", "breeze", "canyon", "cedar", "comet", "delta", "ember", "falcon", "harbor",
+    "horizon", "island", "lantern", "meadow", "nebula", "orchid", "pebble", "quartz", "river",
+    "saffron", "summit", "timber", "velvet", "willow",
+];
+const CITIES: &[&str] = &[
+    "Amsterdam",
+    "Austin",
+    "Berlin",
+    "Buenos Aires",
+    "Cape Town",
+    "Kyoto",
+    "Lisbon",
+    "Montreal",
+    "Nairobi",
+    "Oslo",
+    "Seoul",
+    "Sydney",
+];
+const COUNTRIES: &[&str] = &[
+    "Argentina",
+    "Australia",
+    "Canada",
+    "Germany",
+    "Japan",
+    "Kenya",
+    "Netherlands",
+    "Norway",
+    "Portugal",
+    "South Africa",
+    "South Korea",
+    "United States",
+];
+const STREETS: &[&str] = &[
+    "Elm Street",
+    "High Street",
+    "Maple Avenue",
+    "Market Street",
+    "Oak Lane",
+    "Park Road",
+    "River Road",
+    "Station Road",
+];
+const DOMAINS: &[&str] = &["example.com", "example.net", "example.org"];
+
+/// The day generated dates and timestamps start from, 2023-01-01, in days
+/// since the Unix epoch; they span two years.
+const FIRST_DAY: i64 = 19_358;
+const DAYS: u64 = 731;
+
+/// A splitmix64 generator, whose values only depend on its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number from `low` to `high`, both included.
+    fn between(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[(self.next() % items.len() as u64) as usize]
+    }
+
+    fn words(&mut self, count: usize) -> String {
+        (0..count)
+            .map(|_| self.pick(WORDS))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The civil date of a number of days since the Unix epoch.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// What kind of value a column gets, from its name and type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Email,
+    FirstName,
+    LastName,
+    FullName,
+    Username,
+    Phone,
+    City,
+    Country,
+    Address,
+    PostalCode,
+    Url,
+    Slug,
+    Title,
+    Text,
+    Uuid,
+    Age,
+    Year,
+    Quantity,
+    Integer,
+    Price,
+    Decimal,
+    Boolean,
+    Timestamp,
+    Date,
+    Time,
+    Json,
+    Word,
+}
+
+fn kind(column: &ColumnInfo) -> Kind {
+    let name = column.name.to_ascii_lowercase();
+    let sql_type = column.sql_type.to_ascii_uppercase();
+    let is = |names: &[&str]| {
+        names
+            .iter()
+            .any(|n| name == *n || name.ends_with(&format!("_{}", n)))
+    };
+    if sql_type.contains("BOOL") {
+        return Kind::Boolean;
+    }
+    if sql_type.contains("UUID") || is(&["uuid", "guid"]) {
+        return Kind::Uuid;
+    }
+    if sql_type.contains("TIMESTAMP") || sql_type.contains("DATETIME") {
+        return Kind::Timestamp;
+    }
+    if sql_type.contains("DATE") {
+        return Kind::Date;
+    }
+    if sql_type.contains("TIME") && !sql_type.contains("INTERVAL") {
+        return Kind::Time;
+    }
+    if sql_type.contains("JSON") {
+        return Kind::Json;
+    }
+    if sql_type.contains("INT") || sql_type.contains("SERIAL") {
+        return if is(&["age"]) {
+            Kind::Age
+        } else if is(&["year"]) {
+            Kind::Year
+        } else if is(&["count", "quantity", "qty", "stock"]) {
+            Kind::Quantity
+        } else {
+            Kind::Integer
+        };
+    }
+    if ["REAL", "FLOA", "DOUB", "NUMERIC", "DECIMAL", "MONEY"]
+        .iter()
+        .any(|t| sql_type.contains(t))
+    {
+        return if is(&["price", "amount", "total", "cost", "balance"]) {
+            Kind::Price
+        } else {
+            Kind::Decimal
+        };
+    }
+    if is(&["email", "mail"]) {
+        Kind::Email
+    } else if is(&["first_name", "given_name"]) {
+        Kind::FirstName
+    } else if is(&["last_name", "surname", "family_name"]) {
+        Kind::LastName
+    } else if is(&["username", "login", "handle"]) {
+        Kind::Username
+    } else if is(&["name"]) {
+        Kind::FullName
+    } else if is(&["phone", "mobile"]) {
+        Kind::Phone
+    } else if is(&["city"]) {
+        Kind::City
+    } else if is(&["country"]) {
+        Kind::Country
+    } else if is(&["address", "street"]) {
+        Kind::Address
+    } else if is(&["zip", "zipcode", "postcode", "postal_code"]) {
+        Kind::PostalCode
+    } else if is(&["url", "website", "homepage", "link"]) {
+        Kind::Url
+    } else if is(&["slug"]) {
+        Kind::Slug
+    } else if is(&["title", "subject", "headline"]) {
+        Kind::Title
+    } else if is(&[
+        "description",
+        "body",
+        "bio",
+        "content",
+        "summary",
+        "text",
+        "notes",
+        "comment",
+    ]) {
+        Kind::Text
+    } else if name.ends_with("_at") {
+        Kind::Timestamp
+    } else if name.ends_with("_on") || is(&["date", "birthday", "dob"]) {
+        Kind::Date
+    } else {
+        Kind::Word
+    }
+}
+
+/// The length limit of a `VARCHAR(n)` or `CHAR(n)` column.
+fn max_length(sql_type: &str) -> Option<usize> {
+    let upper = sql_type.to_ascii_uppercase();
+    if !upper.contains("CHAR") {
+        return None;
+    }
+    let (_, rest) = upper.split_once('(')?;
+    rest.split(')').next()?.trim().parse().ok()
+}
+
+/// Generates a value of `kind` for the row numbered `n`, which makes the
+/// values of unique columns differ.
+fn generate(rng: &mut Rng, kind: Kind, n: u64, unique: bool) -> String {
+    let first = rng.pick(FIRST_NAMES);
+    let last = rng.pick(LAST_NAMES);
+    let value = match kind {
+        Kind::Email => format!(
+            "{}.{}{}@{}",
+            first.to_ascii_lowercase(),
+            last.to_ascii_lowercase(),
+            n,
+            rng.pick(DOMAINS)
+        ),
+        Kind::FirstName => first.to_string(),
+        Kind::LastName => last.to_string(),
+        Kind::FullName => format!("{} {}", first, last),
+        Kind::Username => format!(
+            "{}_{}{}",
+            first.to_ascii_lowercase(),
+            last.to_ascii_lowercase(),
+            n
+        ),
+        Kind::Phone => format!(
+            "+1-555-{:03}-{:04}",
+            rng.between(100, 999),
+            rng.between(0, 9999)
+        ),
+        Kind::City => rng.pick(CITIES).to_string(),
+        Kind::Country => rng.pick(COUNTRIES).to_string(),
+        Kind::Address => format!("{} {}", rng.between(1, 999), rng.pick(STREETS)),
+        Kind::PostalCode => format!("{:05}", rng.between(1000, 99999)),
+        Kind::Url => format!("https://{}/{}", rng.pick(DOMAINS), rng.pick(WORDS)),
+        Kind::Slug => format!("{}-{}-{}", rng.pick(WORDS), rng.pick(WORDS), n),
+        Kind::Title => {
+            let mut title = rng.words(3);
+            title[..1].make_ascii_uppercase();
+            title
+        }
+        Kind::Text => {
+            let length = rng.between(8, 16) as usize;
+            let mut text = rng.words(length);
+            text[..1].make_ascii_uppercase();
+            text.push('.');
+            text
+        }
+        Kind::Uuid => {
+            let (high, low) = (rng.next(), rng.next());
+            format!(
+                "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+                high >> 32,
+                (high >> 16) & 0xFFFF,
+                high & 0xFFF,
+                0x8000 | (low >> 48) & 0x3FFF,
+                low & 0xFFFF_FFFF_FFFF
+            )
+        }
+        Kind::Age => rng.between(18, 90).to_string(),
+        Kind::Year => rng.between(1990, 2024).to_string(),
+        Kind::Quantity => rng.between(0, 100).to_string(),
+        Kind::Integer if unique => n.to_string(),
+        Kind::Integer => rng.between(1, 1000).to_string(),
+        Kind::Price => format!("{}.{:02}", rng.between(1, 499), rng.between(0, 99)),
+        Kind::Decimal => format!("{}.{:02}", rng.between(0, 999), rng.between(0, 99)),
+        Kind::Boolean => rng.between(0, 1).to_string(),
+        Kind::Timestamp => {
+            let (year, month, day) = civil_from_days(FIRST_DAY + (rng.next() % DAYS) as i64);
+            let seconds = rng.between(0, 86_399);
+            format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                year,
+                month,
+                day,
+                seconds / 3_600,
+                seconds / 60 % 60,
+                seconds % 60
+            )
+        }
+        Kind::Date => {
+            let (year, month, day) = civil_from_days(FIRST_DAY + (rng.next() % DAYS) as i64);
+            format!("{:04}-{:02}-{:02}", year, month, day)
+        }
+        Kind::Time => {
+            let seconds = rng.between(0, 86_399);
+            format!(
+                "{:02}:{:02}:{:02}",
+                seconds / 3_600,
+                seconds / 60 % 60,
+                seconds % 60
+            )
+        }
+        Kind::Json => format!("{{\"tag\": \"{}\"}}", rng.pick(WORDS)),
+        Kind::Word => rng.pick(WORDS).to_string(),
+    };
+    // Values unique by construction carry the row number already.
+    let numbered = matches!(
+        kind,
+        Kind::Email | Kind::Username | Kind::Slug | Kind::Uuid | Kind::Integer
+    );
+    if unique && !numbered {
+        format!("{}-{}", value, n)
+    } else {
+        value
+    }
+}
+
+/// What an override of `seed()` sets a column to.
+enum Override {
+    Value(Option<String>),
+    /// Called with the index of the row.
+    Call(Py<PyAny>),
+}
+
+/// How the values of a column are chosen.
+enum Source {
+    Generated {
+        kind: Kind,
+        unique: bool,
+        max_length: Option<usize>,
+    },
+    /// The keys of the rows the column references, `NULL` if there are none.
+    Reference(Vec<String>),
+    Override(Override),
+}
+
+/// Inserts `count` rows of generated values into `table` for
+/// `Database.seed()`. Resolves to the number of rows inserted.
+pub(crate) fn seed<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<'py, PyAny>,
+    count: usize,
+    overrides: Option<&Bound<'py, PyDict>>,
+    seed: u64,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table_name(table)?;
+    let mut parsed = Vec::new();
+    for (column, value) in overrides.into_iter().flatten() {
+        let column: String = column.extract()?;
+        let value = if value.is_callable() {
+            Override::Call(value.unbind())
+        } else {
+            Override::Value(fixture_param(py, &value, &column)?)
+        };
+        parsed.push((column, value));
+    }
+    let db = context::resolve(py, Some(db))?;
+    let dialect = db.dialect();
+    future_into_py(py, async move {
+        let columns = introspect::columns(&db.pool, dialect, &table).await?;
+        if columns.is_empty() {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "Table '{}' doesn't exist.",
+                table
+            ))
+            .into());
+        }
+        for (column, _) in &parsed {
+            if !columns.iter().any(|c| c.name == *column) {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "'{}' is not a column of '{}'.",
+                    column, table
+                ))
+                .into());
+            }
+        }
+        let sources = sources(&db, dialect, &table, &columns, parsed).await?;
+        let existing = db
+            .fetch_rows(&format!("SELECT COUNT(*) AS n FROM {}", table), Vec::new())
+            .await?
+            .first()
+            .map(|row| row.try_get::<i64, _>("n"))
+            .transpose()
+            .map_err(FustOrmError::from)?
+            .unwrap_or(0) as u64;
+
+        let names: Vec<&str> = sources.iter().map(|(name, _)| name.as_str()).collect();
+        let mut rng = Rng(seed);
+        let rows = Python::attach(|py| -> PyResult<Vec<Vec<Option<String>>>> {
+            (0..count)
+                .map(|index| {
+                    let n = existing + index as u64 + 1;
+                    sources
+                        .iter()
+                        .map(|(name, source)| {
+                            Ok(match source {
+                                Source::Generated {
+                                    kind,
+                                    unique,
+                                    max_length,
+                                } => {
+                                    let mut value = generate(&mut rng, *kind, n, *unique);
+                                    if let Some(length) = max_length {
+                                        value = value.chars().take(*length).collect();
+                                    }
+                                    Some(value)
+                                }
+                                Source::Reference(keys) if keys.is_empty() => None,
+                                Source::Reference(keys) => {
+                                    Some(keys[(rng.next() % keys.len() as u64) as usize].clone())
+                                }
+                                Source::Override(Override::Value(value)) => value.clone(),
+                                Source::Override(Override::Call(callable)) => {
+                                    let value = callable.call1(py, (index,))?;
+                                    fixture_param(py, value.bind(py), name)?
+                                }
+                            })
+                        })
+                        .collect()
+                })
+                .collect()
+        })?;
+
+        debug!("Seeding {} rows into '{}'", count, table);
+        let mut tx = db.begin_write().await?;
+        let size = rows_per_statement(None, names.len())?;
+        for chunk in rows.chunks(size) {
+            let mut params = Vec::new();
+            let values: Vec<String> = chunk
+                .iter()
+                .map(|row| {
+                    let placeholders: Vec<&str> = row
+                        .iter()
+                        .map(|value| match value {
+                            Some(param) => {
+                                params.push(param.clone());
+                                "?"
+                            }
+                            None => "NULL",
+                        })
+                        .collect();
+                    format!("({})", placeholders.join(", "))
+                })
+                .collect();
+            let sql = if names.is_empty() {
+                format!("INSERT INTO {} DEFAULT VALUES", table)
+            } else {
+                format!(
+                    "INSERT INTO {} ({}) VALUES {}",
+                    table,
+                    names.join(", "),
+                    values.join(", ")
+                )
+            };
+            if names.is_empty() {
+                for _ in chunk {
+                    execute_statement(&mut *tx, &sql, Vec::new()).await?;
+                }
+            } else {
+                execute_statement(&mut *tx, &sql, params).await?;
+            }
+        }
+        tx.commit().await.map_err(FustOrmError::from)?;
+        Ok(count)
+    })
+}
+
+/// Chooses where the values of each column of `table` come from. A single
+/// integer primary key and generated columns are left to the database.
+async fn sources(
+    db: &Database,
+    dialect: Dialect,
+    table: &str,
+    columns: &[ColumnInfo],
+    mut overrides: Vec<(String, Override)>,
+) -> Result<Vec<(String, Source)>, FustOrmError> {
+    let foreign_keys = introspect::foreign_keys(&db.pool, dialect, table).await?;
+    let unique: Vec<String> = introspect::indexes(&db.pool, dialect, table)
+        .await?
+        .into_iter()
+        .filter(|index| index.unique && index.columns.len() == 1)
+        .filter_map(|index| index.columns.into_iter().next().flatten())
+        .collect();
+    let primary_key: Vec<&ColumnInfo> = columns.iter().filter(|c| c.primary_key).collect();
+    let mut sources = Vec::with_capacity(columns.len());
+    for column in columns {
+        if let Some(position) = overrides.iter().position(|(name, _)| *name == column.name) {
+            let (name, value) = overrides.remove(position);
+            sources.push((name, Source::Override(value)));
+            continue;
+        }
+        let generated_key = column.primary_key
+            && primary_key.len() == 1
+            && matches!(
+                kind(column),
+                Kind::Integer | Kind::Age | Kind::Year | Kind::Quantity
+            );
+        if column.generated.is_some() || generated_key {
+            continue;
+        }
+        let source = match foreign_keys.iter().find(|fk| fk.column == column.name) {
+            Some(fk) => {
+                let target = match fk.target.as_str() {
+                    "" => introspect::columns(&db.pool, dialect, &fk.table)
+                        .await?
+                        .into_iter()
+                        .find(|c| c.primary_key)
+                        .map(|c| c.name)
+                        .unwrap_or_else(|| "rowid".to_string()),
+                    target => target.to_string(),
+                };
+                let text_type = if dialect == Dialect::Mysql {
+                    "CHAR"
+                } else {
+                    "TEXT"
+                };
+                let sql = format!(
+                    "SELECT CAST({} AS {}) AS k FROM {} ORDER BY {} LIMIT 1000",
+                    target, text_type, fk.table, target
+                );
+                let keys = db
+                    .fetch_rows(&sql, Vec::new())
+                    .await?
+                    .iter()
+                    .map(|row| row.try_get::<String, _>("k"))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(FustOrmError::from)?;
+                if keys.is_empty() && !column.nullable {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "Can't seed '{}': '{}' has no rows for '{}' to reference.",
+                        table, fk.table, column.name
+                    )));
+                }
+                Source::Reference(keys)
+            }
+            None => Source::Generated {
+                kind: kind(column),
+                unique: unique.contains(&column.name)
+                    || (column.primary_key && primary_key.len() == 1),
+                max_length: max_length(&column.sql_type),
+            },
+        };
+        sources.push((column.name.clone(), source));
+    }
+    Ok(sources)
+}
@@ -0,0 +1,174 @@
+use base64::Engine;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::{
+    PyBool, PyByteArray, PyBytes, PyDate, PyDict, PyFloat, PyInt, PyList, PySet, PyString, PyTime,
+    PyTuple, PyType,
+};
+use serde_json::{Map, Number, Value};
+
+use crate::model::Model;
+
+static UUID_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static DECIMAL_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static ENUM_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+
+/// Converts a model instance into a dictionary.
+///
+/// By default the dictionary holds all column values. `include` restricts it to
+/// the given attributes and may also name non-column attributes holding related
+/// models (an instance or a list of instances), which are converted recursively.
+/// `exclude` drops attributes from the result.
+pub(crate) fn model_to_dict<'py>(
+    instance: &Bound<'py, PyAny>,
+    include: Option<&[String]>,
+    exclude: Option<&[String]>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let py = instance.py();
+    let names = match include {
+        Some(names) => names.to_vec(),
+        None => {
+            let deferred = &instance.downcast::<Model>()?.borrow().deferred;
+            instance
+                .get_type()
+                .getattr(pyo3::intern!(py, "__columns__"))?
+                .extract::<Vec<String>>()?
+                .into_iter()
+                .filter(|name| !deferred.contains(name))
+                .collect()
+        }
+    };
+    let dict = PyDict::new(py);
+    for name in names {
+        if exclude.is_some_and(|exclude| exclude.contains(&name)) {
+            continue;
+        }
+        let value = instance.getattr(&name)?;
+        dict.set_item(name, nested_value(&value)?)?;
+    }
+    Ok(dict)
+}
+
+/// Converts related model instances (and lists of them) into dictionaries,
+/// leaving every other value unchanged.
+fn nested_value<'py>(value: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    let py = value.py();
+    if value.is_instance_of::<Model>() {
+        return Ok(model_to_dict(value, None, None)?.into_any());
+    }
+    if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+        let items = value
+            .try_iter()?
+            .map(|item| nested_value(&item?))
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(PyList::new(py, items)?.into_any());
+    }
+    Ok(value.clone())
+}
+
+/// Converts a Python value into JSON.
+///
+/// Values without a JSON counterpart are converted consistently: dates, times
+/// and datetimes become ISO 8601 strings, UUIDs and decimals their string form,
+/// enum members their value, and bytes a base64 string.
+pub(crate) fn py_to_json(value: &Bound<PyAny>) -> PyResult<Value> {
+    let py = value.py();
+    if value.is_none() {
+        return Ok(Value::Null);
+    }
+    // `bool` is a subclass of `int` in Python, so it has to be checked first.
+    if let Ok(b) = value.downcast::<PyBool>() {
+        return Ok(Value::Bool(b.is_true()));
+    }
+    if value.is_instance_of::<PyInt>() {
+        if let Ok(i) = value.extract::<i64>() {
+            return Ok(Value::Number(i.into()));
+        }
+        if let Ok(u) = value.extract::<u64>() {
+            return Ok(Value::Number(u.into()));
+        }
+        return Err(PyValueError::new_err(format!(
+            "Integer {} is too large to serialize to JSON",
+            value
+        )));
+    }
+    if let Ok(f) = value.downcast::<PyFloat>() {
+        return Number::from_f64(f.value())
+            .map(Value::Number)
+            .ok_or_else(|| PyValueError::new_err(format!("Cannot serialize {} to JSON", f)));
+    }
+    if let Ok(s) = value.downcast::<PyString>() {
+        return Ok(Value::String(s.to_str()?.to_owned()));
+    }
+    // `datetime` is a subclass of `date`, so this covers both.
+    if value.is_instance_of::<PyDate>() || value.is_instance_of::<PyTime>() {
+        return Ok(Value::String(
+            value
+                .call_method0(pyo3::intern!(py, "isoformat"))?
+                .extract()?,
+        ));
+    }
+    if value.is_instance(UUID_TYPE.import(py, "uuid", "UUID")?)?
+        || value.is_instance(DECIMAL_TYPE.import(py, "decimal", "Decimal")?)?
+    {
+        return Ok(Value::String(value.str()?.to_str()?.to_owned()));
+    }
+    if value.is_instance(ENUM_TYPE.import(py, "enum", "Enum")?)? {
+        return py_to_json(&value.getattr(pyo3::intern!(py, "value"))?);
+    }
+    if let Ok(bytes) = value.downcast::<PyBytes>() {
+        return Ok(Value::String(
+            base64::engine::general_purpose::STANDARD.encode(bytes.as_bytes()),
+        ));
+    }
+    if let Ok(bytes) = value.downcast::<PyByteArray>() {
+        return Ok(Value::String(
+            base64::engine::general_purpose::STANDARD.encode(bytes.to_vec()),
+        ));
+    }
+    if value.is_instance_of::<Model>() {
+        return py_to_json(model_to_dict(value, None, None)?.as_any());
+    }
+    if let Ok(dict) = value.downcast::<PyDict>() {
+        let mut map = Map::with_capacity(dict.len());
+        for (key, item) in dict.iter() {
+            let key = match key.downcast::<PyString>() {
+                Ok(s) => s.to_str()?.to_owned(),
+                Err(_) => key.str()?.to_str()?.to_owned(),
+            };
+            map.insert(key, py_to_json(&item)?);
+        }
+        return Ok(Value::Object(map));
+    }
+    if value.is_instance_of::<PyList>()
+        || value.is_instance_of::<PyTuple>()
+        || value.is_instance_of::<PySet>()
+    {
+        let items = value
+            .try_iter()?
+            .map(|item| py_to_json(&item?))
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(Value::Array(items));
+    }
+    Err(PyTypeError::new_err(format!(
+        "Object of type {} is not JSON serializable",
+        value.get_type().name()?
+    )))
+}
+
+/// Renders JSON, pretty-printed with `indent` spaces if given.
+pub(crate) fn json_to_string(value: &Value, indent: Option<usize>) -> PyResult<String> {
+    let to_err = |e: serde_json::Error| PyValueError::new_err(e.to_string());
+    match indent {
+        None => serde_json::to_string(value).map_err(to_err),
+        Some(indent) => {
+            let indent = " ".repeat(indent);
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut out = Vec::new();
+            let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+            serde::Serialize::serialize(value, &mut serializer).map_err(to_err)?;
+            String::from_utf8(out).map_err(|e| PyValueError::new_err(e.to_string()))
+        }
+    }
+}
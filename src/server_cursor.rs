@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use log::debug;
+use pyo3::exceptions::PyStopAsyncIteration;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::any::AnyRow;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::context;
+use crate::database::{Database, WriteTransaction, fetch_rows_uncached};
+use crate::decode::RowBatch;
+use crate::error::FustOrmError;
+use crate::notify::require_postgres;
+
+/// Numbers the cursors, whose names must differ within a session.
+static NEXT_CURSOR: AtomicU64 = AtomicU64::new(1);
+
+/// Where the statements of a cursor run.
+enum Session {
+    /// Not declared yet.
+    Pending,
+    /// The transaction bound when the cursor was created, which the cursor
+    /// shares with other statements.
+    Bound,
+    /// A transaction of its own, as cursors only live as long as the
+    /// transaction declaring them.
+    Own(WriteTransaction),
+    /// Exhausted or closed.
+    Done,
+}
+
+struct CursorState {
+    session: Session,
+    /// The rows of the last batch not iterated yet.
+    rows: VecDeque<Py<PyDict>>,
+}
+
+/// A PostgreSQL server-side cursor over the rows of a query, returned by
+/// `Database.server_cursor()`: an async iterator of rows, as dicts, which it
+/// fetches `batch_size` at a time, so that neither the server nor the client
+/// ever holds the whole result.
+///
+/// ```python
+/// async with db.server_cursor(select("SELECT * FROM events"), 5000) as cursor:
+///     async for row in cursor:
+///         ...
+/// ```
+///
+/// The cursor is declared in the bound transaction if there is one, and in a
+/// transaction of its own otherwise, which holds a connection of the pool
+/// until the rows are exhausted or the cursor is closed.
+#[pyclass(module = "fust_orm")]
+pub struct ServerCursor {
+    db: Database,
+    name: String,
+    sql: String,
+    params: Vec<String>,
+    batch_size: usize,
+    state: Arc<AsyncMutex<CursorState>>,
+}
+
+/// Creates a cursor over `query` for `Database.server_cursor()`.
+pub(crate) fn server_cursor(
+    py: Python,
+    db: &Database,
+    query: &Bound<PyAny>,
+    batch_size: usize,
+) -> PyResult<ServerCursor> {
+    require_postgres(db, "server_cursor")?;
+    if batch_size == 0 {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "batch_size must be a positive integer.".to_string(),
+        )
+        .into());
+    }
+    let (sql, params) = db.render(py, query)?;
+    Ok(ServerCursor {
+        db: context::resolve(py, Some(db))?,
+        name: format!(
+            "fust_cursor_{}",
+            NEXT_CURSOR.fetch_add(1, Ordering::Relaxed)
+        ),
+        sql,
+        params,
+        batch_size,
+        state: Arc::new(AsyncMutex::new(CursorState {
+            session: Session::Pending,
+            rows: VecDeque::new(),
+        })),
+    })
+}
+
+/// Runs a statement of the cursor in its session.
+async fn run(
+    db: &Database,
+    session: &mut Session,
+    sql: &str,
+    params: Vec<String>,
+) -> Result<Vec<AnyRow>, FustOrmError> {
+    // Cursor names differ, so their statements aren't worth preparing once
+    // for all.
+    match session {
+        Session::Own(tx) => fetch_rows_uncached(&mut **tx, sql, params).await,
+        _ => db.fetch_rows_uncached(sql, params).await,
+    }
+}
+
+/// Closes the cursor and ends its own transaction, if it has one.
+async fn finish(db: &Database, name: &str, session: &mut Session) -> Result<(), FustOrmError> {
+    match std::mem::replace(session, Session::Done) {
+        Session::Own(tx) => {
+            // Ending the transaction closes the cursor.
+            tx.commit().await.map_err(FustOrmError::from)?;
+        }
+        Session::Bound => {
+            db.fetch_rows_uncached(&format!("CLOSE {}", name), Vec::new())
+                .await?;
+        }
+        Session::Pending | Session::Done => {}
+    }
+    Ok(())
+}
+
+/// Fetches the next batch of rows, declaring the cursor first if needed.
+/// Returns no rows once the cursor is exhausted.
+async fn fetch_batch(
+    db: &Database,
+    name: &str,
+    sql: &str,
+    params: Vec<String>,
+    batch_size: usize,
+    session: &mut Session,
+) -> Result<Vec<AnyRow>, FustOrmError> {
+    if let Session::Done = session {
+        return Ok(Vec::new());
+    }
+    if let Session::Pending = session {
+        *session = match db.tx {
+            Some(_) => Session::Bound,
+            None => Session::Own(db.begin_write().await?),
+        };
+        debug!("Declaring cursor {}: {}", name, sql);
+        let declare = format!("DECLARE {} NO SCROLL CURSOR FOR {}", name, sql);
+        run(db, session, &declare, params).await?;
+    }
+    let fetch = format!("FETCH FORWARD {} FROM {}", batch_size, name);
+    let rows = run(db, session, &fetch, Vec::new()).await?;
+    if rows.len() < batch_size {
+        finish(db, name, session).await?;
+    }
+    Ok(rows)
+}
+
+#[pymethods]
+impl ServerCursor {
+    fn __aiter__(slf: Py<Self>) -> Py<Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let db = self.db.clone();
+        let name = self.name.clone();
+        let sql = self.sql.clone();
+        let params = self.params.clone();
+        let batch_size = self.batch_size;
+        let state = self.state.clone();
+        future_into_py(py, async move {
+            let mut state = state.lock().await;
+            if let Some(row) = state.rows.pop_front() {
+                return Ok(row);
+            }
+            let fetched =
+                fetch_batch(&db, &name, &sql, params, batch_size, &mut state.session).await;
+            let rows = match fetched {
+                Ok(rows) => rows,
+                Err(e) => {
+                    // Dropping its own transaction rolls it back.
+                    state.session = Session::Done;
+                    return Err(e.into());
+                }
+            };
+            let batch = RowBatch::decode(&rows)?;
+            Python::attach(|py| -> PyResult<()> {
+                for row in batch.to_dicts(py)? {
+                    state.rows.push_back(row.unbind());
+                }
+                Ok(())
+            })?;
+            state
+                .rows
+                .pop_front()
+                .ok_or_else(|| PyStopAsyncIteration::new_err(()))
+        })
+    }
+
+    /// Closes the cursor before its rows are exhausted, releasing its
+    /// connection; iterating afterwards ends right away.
+    fn close<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let db = self.db.clone();
+        let name = self.name.clone();
+        let state = self.state.clone();
+        future_into_py(py, async move {
+            let mut state = state.lock().await;
+            state.rows.clear();
+            finish(&db, &name, &mut state.session).await?;
+            Ok(())
+        })
+    }
+
+    fn __aenter__<'py>(slf: Py<Self>, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        future_into_py(py, async move { Ok(slf) })
+    }
+
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __aexit__<'py>(
+        &self,
+        py: Python<'py>,
+        _exc_type: Option<&Bound<'py, PyAny>>,
+        _exc_value: Option<&Bound<'py, PyAny>>,
+        _traceback: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.close(py)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<ServerCursor {} batch_size={}>",
+            self.name, self.batch_size
+        )
+    }
+}
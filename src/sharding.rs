@@ -0,0 +1,241 @@
+use futures_util::future::try_join_all;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3_async_runtimes::tokio::future_into_py;
+
+use crate::column_field::ColumnField;
+use crate::context;
+use crate::database::Database;
+use crate::decode::RowBatch;
+use crate::error::FustOrmError;
+use crate::query::{QueryBuilder, SqlParam, py_any_to_string};
+
+/// How a shard key picks its shard.
+enum Router {
+    /// By a stable hash of the key, modulo the number of shards.
+    Hash,
+    /// By the first of the ascending bounds the key is below, the last shard
+    /// taking the keys at or above every bound.
+    Range(Vec<Py<PyAny>>),
+}
+
+/// Databases holding disjoint parts of the same tables, split by the value
+/// of a shard key column.
+///
+/// Queries comparing the key to a value with `==` run on the one shard
+/// holding it; `scatter_gather()` reads from all of them.
+#[pyclass(frozen)]
+pub struct ShardedDatabase {
+    shards: Vec<Database>,
+    /// The shard key column.
+    key: String,
+    router: Router,
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, which unlike the hasher of the
+/// standard library is the same in every process.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+impl ShardedDatabase {
+    /// The index of the shard holding the rows whose key is `value`.
+    fn index(&self, value: &Bound<PyAny>) -> PyResult<usize> {
+        match &self.router {
+            Router::Hash => {
+                let py = value.py();
+                let SqlParam::Single(key) =
+                    py_any_to_string(py, &value.clone().unbind(), &self.key)?
+                else {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "A shard key can't be a list: {}",
+                        value.repr()?
+                    ))
+                    .into());
+                };
+                Ok((fnv1a(key.as_bytes()) % self.shards.len() as u64) as usize)
+            }
+            Router::Range(bounds) => {
+                for (i, bound) in bounds.iter().enumerate() {
+                    if value.lt(bound)? {
+                        return Ok(i);
+                    }
+                }
+                Ok(bounds.len())
+            }
+        }
+    }
+
+    /// The shard a query runs on: the one of `shard_key`, or of the value a
+    /// QueryBuilder compares the key column to.
+    fn shard_of(
+        &self,
+        query: &Bound<PyAny>,
+        shard_key: Option<&Bound<PyAny>>,
+    ) -> PyResult<&Database> {
+        let index = match shard_key {
+            Some(value) => self.index(value)?,
+            None => {
+                let value = query
+                    .downcast::<QueryBuilder>()
+                    .ok()
+                    .and_then(|qb| qb.borrow().equal_value(&self.key));
+                match value {
+                    Some(value) => self.index(value.bind(query.py()))?,
+                    None => {
+                        return Err(FustOrmError::InvalidQueryArgument(format!(
+                            "Can't route a query without a condition '{} == value' to a \
+                             shard; pass shard_key= or use scatter_gather().",
+                            self.key
+                        ))
+                        .into());
+                    }
+                }
+            }
+        };
+        Ok(&self.shards[index])
+    }
+}
+
+#[pymethods]
+impl ShardedDatabase {
+    #[new]
+    #[pyo3(signature = (shards, *, key, ranges = None))]
+    fn new(
+        shards: Vec<Database>,
+        key: &Bound<PyAny>,
+        ranges: Option<Vec<Bound<PyAny>>>,
+    ) -> PyResult<Self> {
+        if shards.is_empty() {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "A sharded database needs at least one shard.".to_string(),
+            )
+            .into());
+        }
+        let key = match key.extract::<PyRef<ColumnField>>() {
+            Ok(column) => column.column_name.clone(),
+            Err(_) => key.extract::<String>()?,
+        };
+        let router = match ranges {
+            None => Router::Hash,
+            Some(bounds) => {
+                if bounds.len() + 1 != shards.len() {
+                    return Err(FustOrmError::InvalidQueryArgument(format!(
+                        "{} shards need {} range bounds, not {}.",
+                        shards.len(),
+                        shards.len() - 1,
+                        bounds.len()
+                    ))
+                    .into());
+                }
+                for pair in bounds.windows(2) {
+                    if !pair[0].lt(&pair[1])? {
+                        return Err(FustOrmError::InvalidQueryArgument(
+                            "Range bounds must be ascending.".to_string(),
+                        )
+                        .into());
+                    }
+                }
+                Router::Range(bounds.into_iter().map(Bound::unbind).collect())
+            }
+        };
+        Ok(ShardedDatabase {
+            shards,
+            key,
+            router,
+        })
+    }
+
+    /// The databases of the shards, in order.
+    #[getter]
+    fn shards(&self) -> Vec<Database> {
+        self.shards.clone()
+    }
+
+    /// The shard key column.
+    #[getter]
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the shard holding the rows whose key is `value`, to use with
+    /// model methods: `await user.save(sharded.shard_for(user.id))`.
+    fn shard_for(&self, value: &Bound<PyAny>) -> PyResult<Database> {
+        Ok(self.shards[self.index(value)?].clone())
+    }
+
+    /// Returns the shard a QueryBuilder comparing the key column to a value
+    /// with `==` runs on.
+    fn route(&self, query: &Bound<PyAny>) -> PyResult<Database> {
+        self.shard_of(query, None).cloned()
+    }
+
+    /// Executes `query` on a single shard, picked by `shard_key` or, for a
+    /// QueryBuilder, by the value it compares the key column to with `==`.
+    /// Other arguments are passed on to `Database.execute()`.
+    #[pyo3(signature = (query, *, shard_key = None, **kwargs))]
+    fn execute<'py>(
+        &self,
+        query: &Bound<'py, PyAny>,
+        shard_key: Option<&Bound<'py, PyAny>>,
+        kwargs: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let shard = self.shard_of(query, shard_key)?.clone();
+        shard
+            .into_pyobject(query.py())?
+            .call_method("execute", (query,), kwargs)
+    }
+
+    /// Runs a read query on every shard concurrently and resolves to the
+    /// rows of all of them as dicts, in the order of the shards. If a shard
+    /// fails, the others are cancelled and its error is raised.
+    fn scatter_gather<'py>(
+        &self,
+        py: Python<'py>,
+        query: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let statements = self
+            .shards
+            .iter()
+            .map(|shard| {
+                let (sql, params) = shard.render(py, query)?;
+                Ok((context::resolve(py, Some(shard))?, sql, params))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        future_into_py(py, async move {
+            let batches =
+                try_join_all(statements.into_iter().map(|(db, sql, params)| async move {
+                    RowBatch::decode(&db.fetch_rows(&sql, params).await?)
+                }))
+                .await?;
+
+            Python::attach(|py| -> PyResult<Py<PyList>> {
+                let rows = PyList::empty(py);
+                for batch in &batches {
+                    for row in batch.to_dicts(py)? {
+                        rows.append(row)?;
+                    }
+                }
+                Ok(rows.unbind())
+            })
+        })
+    }
+
+    /// Closes the pools of all shards.
+    fn close<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let pools: Vec<_> = self.shards.iter().map(|shard| shard.pool.clone()).collect();
+        future_into_py(py, async move {
+            for pool in pools {
+                pool.close().await;
+            }
+            Ok(())
+        })
+    }
+
+    fn __len__(&self) -> usize {
+        self.shards.len()
+    }
+}
@@ -0,0 +1,106 @@
+use std::ptr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use log::{debug, warn};
+use pyo3::prelude::*;
+use sqlx::AnyPool;
+
+use crate::logging;
+use crate::pool_registry::PoolKey;
+use crate::sqlite_hooks;
+
+/// How long closing the pools at exit may take before the interpreter exits
+/// regardless, e.g. while a connection is still held by a transaction.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The registrations of the pools of the databases alive. Only the databases
+/// hold them, so that a pool is dropped, closing its connections, with its
+/// last database.
+static POOLS: Mutex<Vec<(PoolKey, Weak<Registration>)>> = Mutex::new(Vec::new());
+
+/// Keeps a pool to be closed at interpreter exit while a database of it is
+/// alive.
+pub(crate) struct Registration {
+    pool: AnyPool,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        POOLS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(_, registration)| !ptr::eq(registration.as_ptr(), self));
+    }
+}
+
+/// Remembers `pool` to close it at interpreter exit, until the returned
+/// registration, which the databases of the pool share, is dropped.
+pub(crate) fn register(pool: &AnyPool) -> Arc<Registration> {
+    let key = PoolKey::of(pool);
+    let mut pools = POOLS.lock().unwrap_or_else(|e| e.into_inner());
+    let existing = pools
+        .iter()
+        .filter(|(registered, _)| *registered == key)
+        .find_map(|(_, registration)| registration.upgrade());
+    if let Some(registration) = existing {
+        return registration;
+    }
+    let registration = Arc::new(Registration { pool: pool.clone() });
+    pools.push((key, Arc::downgrade(&registration)));
+    registration
+}
+
+/// Registers `close_pools()` with `atexit`.
+pub(crate) fn init(m: &Bound<PyModule>) -> PyResult<()> {
+    let close = wrap_pyfunction!(close_pools, m)?;
+    m.py()
+        .import("atexit")?
+        .call_method1("register", (close,))?;
+    Ok(())
+}
+
+/// Closes the open pools at interpreter exit, so their connections are shut
+/// down while the runtime still runs instead of being cut off with the
+/// process.
+#[pyfunction]
+fn close_pools(py: Python) {
+    let registrations: Vec<Arc<Registration>> = POOLS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(|(_, registration)| registration.upgrade())
+        .collect();
+    let pools: Vec<AnyPool> = registrations
+        .iter()
+        .map(|registration| registration.pool.clone())
+        .filter(|pool| !pool.is_closed())
+        .collect();
+    if !pools.is_empty() {
+        debug!("Closing {} pool(s) at exit", pools.len());
+    }
+    let runtime = pyo3_async_runtimes::tokio::get_runtime();
+    let closed = py.detach(|| {
+        runtime.block_on(async {
+            tokio::time::timeout(CLOSE_TIMEOUT, async {
+                join_all(pools.iter().map(AnyPool::close)).await;
+                // The SQLite connections of the pools dropped before close
+                // on threads of their own.
+                while sqlite_hooks::open_connections() > 0 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+        })
+    });
+    if closed.is_err() {
+        warn!(
+            "Gave up closing the database pools at exit after {}s; connections are still in use.",
+            CLOSE_TIMEOUT.as_secs()
+        );
+    }
+    // A thread attaching to the interpreter to log while it finalizes would
+    // crash it.
+    logging::stop();
+}
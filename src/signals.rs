@@ -0,0 +1,142 @@
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::sync::{PyOnceLock, with_critical_section};
+use pyo3::types::{PyDict, PyList, PyType};
+
+static PRE_SAVE: PyOnceLock<Py<Signal>> = PyOnceLock::new();
+static POST_SAVE: PyOnceLock<Py<Signal>> = PyOnceLock::new();
+static PRE_DELETE: PyOnceLock<Py<Signal>> = PyOnceLock::new();
+static POST_DELETE: PyOnceLock<Py<Signal>> = PyOnceLock::new();
+
+/// A model lifecycle event that Python handlers can subscribe to.
+///
+/// Handlers are called synchronously as `handler(sender, instance, **kwargs)`,
+/// where `sender` is the instance's model class. An exception raised by a
+/// handler propagates to the caller of `save()` / `delete()`; for `pre_*`
+/// signals this aborts the operation.
+#[pyclass(frozen)]
+pub struct Signal {
+    name: &'static str,
+    /// `(handler, sender)` pairs in registration order; `sender` is `None` for
+    /// handlers receiving events of all models.
+    receivers: Py<PyList>,
+}
+
+#[pymethods]
+impl Signal {
+    /// Registers `handler`, optionally only for instances of `sender` (and its
+    /// subclasses). Returns the handler, so `connect` works as a decorator.
+    #[pyo3(signature = (handler, sender = None))]
+    fn connect(
+        &self,
+        py: Python,
+        handler: Py<PyAny>,
+        sender: Option<Py<PyType>>,
+    ) -> PyResult<Py<PyAny>> {
+        debug!("Connecting handler to signal '{}'", self.name);
+        self.receivers
+            .bind(py)
+            .append((handler.clone_ref(py), sender))?;
+        Ok(handler)
+    }
+
+    /// Unregisters `handler`. Returns whether it was registered.
+    fn disconnect(&self, py: Python, handler: &Bound<PyAny>) -> PyResult<bool> {
+        let receivers = self.receivers.bind(py);
+        // Comparing handlers can run Python code, which may change the list;
+        // the matches are found on a snapshot and removed by identity.
+        let mut matching = Vec::new();
+        for receiver in receivers.to_tuple().iter() {
+            if receiver.get_item(0)?.eq(handler)? {
+                matching.push(receiver);
+            }
+        }
+        with_critical_section(receivers, || {
+            let mut removed = false;
+            for index in (0..receivers.len()).rev() {
+                let receiver = receivers.get_item(index)?;
+                if matching.iter().any(|matched| matched.is(&receiver)) {
+                    receivers.del_item(index)?;
+                    removed = true;
+                }
+            }
+            Ok(removed)
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Signal {}>", self.name)
+    }
+}
+
+impl Signal {
+    fn new(py: Python, name: &'static str) -> PyResult<Py<Signal>> {
+        Py::new(
+            py,
+            Signal {
+                name,
+                receivers: PyList::empty(py).unbind(),
+            },
+        )
+    }
+
+    /// Calls the handlers registered for the instance's model class.
+    pub(crate) fn send(
+        &self,
+        instance: &Bound<PyAny>,
+        kwargs: Option<&Bound<PyDict>>,
+    ) -> PyResult<()> {
+        let py = instance.py();
+        let sender = instance.get_type();
+        // Handlers may connect or disconnect others; iterate over a snapshot.
+        let receivers = self.receivers.bind(py).to_tuple();
+        for receiver in receivers.iter() {
+            let handler = receiver.get_item(0)?;
+            let filter = receiver.get_item(1)?;
+            if !filter.is_none() && !sender.is_subclass(filter.downcast::<PyType>()?)? {
+                continue;
+            }
+            handler.call((&sender, instance), kwargs)?;
+        }
+        Ok(())
+    }
+}
+
+fn signal<'py>(
+    py: Python<'py>,
+    cell: &'static PyOnceLock<Py<Signal>>,
+    name: &'static str,
+) -> PyResult<&'py Bound<'py, Signal>> {
+    Ok(cell.get_or_try_init(py, || Signal::new(py, name))?.bind(py))
+}
+
+/// Sent before an instance is written by `save()`, with `created=True` for inserts.
+pub(crate) fn pre_save(py: Python<'_>) -> PyResult<&Bound<'_, Signal>> {
+    signal(py, &PRE_SAVE, "pre_save")
+}
+
+/// Sent after `save()` has written an instance, with `created=True` for inserts.
+pub(crate) fn post_save(py: Python<'_>) -> PyResult<&Bound<'_, Signal>> {
+    signal(py, &POST_SAVE, "post_save")
+}
+
+/// Sent before `delete()` removes an instance's row.
+pub(crate) fn pre_delete(py: Python<'_>) -> PyResult<&Bound<'_, Signal>> {
+    signal(py, &PRE_DELETE, "pre_delete")
+}
+
+/// Sent after `delete()` has removed an instance's row.
+pub(crate) fn post_delete(py: Python<'_>) -> PyResult<&Bound<'_, Signal>> {
+    signal(py, &POST_DELETE, "post_delete")
+}
+
+/// Sends a save signal with the `created` keyword argument.
+pub(crate) fn send_save(
+    signal: &Bound<Signal>,
+    instance: &Bound<PyAny>,
+    created: bool,
+) -> PyResult<()> {
+    let kwargs = PyDict::new(instance.py());
+    kwargs.set_item(pyo3::intern!(instance.py(), "created"), created)?;
+    signal.get().send(instance, Some(&kwargs))
+}
@@ -0,0 +1,296 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures_util::TryStreamExt;
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyString};
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::any::AnyRow;
+use sqlx::{Column, Row};
+
+use crate::context;
+use crate::database::{Database, acquire, bind_params};
+use crate::decode::{Value, decode};
+use crate::error::FustOrmError;
+use crate::metrics;
+use crate::mock;
+use crate::rewrite;
+use crate::sqlcomment;
+use crate::timing;
+
+/// Numbers the spool files, whose names must differ within the process.
+static NEXT_SPOOL: AtomicU64 = AtomicU64::new(1);
+
+const NULL: u8 = 0;
+const TEXT: u8 = 1;
+const INT: u8 = 2;
+const REAL: u8 = 3;
+const BLOB: u8 = 4;
+
+fn io_error(path: &Path, e: std::io::Error) -> FustOrmError {
+    FustOrmError::BuildError(format!(
+        "Can't access spool file '{}': {}",
+        path.display(),
+        e
+    ))
+}
+
+/// A spool file, deleted once neither its rows nor an iterator over them
+/// use it any more.
+struct SpoolFile {
+    path: PathBuf,
+}
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Appends the values of `row` to a spool file: a tag byte per value,
+/// followed by the little-endian number or the length and bytes of the text
+/// or blob.
+fn write_row(out: &mut impl Write, row: &AnyRow) -> Result<(), FustOrmError> {
+    let spool_error = |e: std::io::Error| FustOrmError::BuildError(e.to_string());
+    for index in 0..row.len() {
+        match decode(row, index)? {
+            Value::Null => out.write_all(&[NULL]),
+            Value::Text(text) => out
+                .write_all(&[TEXT])
+                .and_then(|_| out.write_all(&(text.len() as u64).to_le_bytes()))
+                .and_then(|_| out.write_all(text.as_bytes())),
+            Value::Int(int) => out
+                .write_all(&[INT])
+                .and_then(|_| out.write_all(&int.to_le_bytes())),
+            Value::Real(real) => out
+                .write_all(&[REAL])
+                .and_then(|_| out.write_all(&real.to_le_bytes())),
+            Value::Blob(blob) => out
+                .write_all(&[BLOB])
+                .and_then(|_| out.write_all(&(blob.len() as u64).to_le_bytes()))
+                .and_then(|_| out.write_all(&blob)),
+        }
+        .map_err(spool_error)?;
+    }
+    Ok(())
+}
+
+/// Reads a value written by `write_row()`.
+fn read_value(input: &mut impl Read) -> std::io::Result<Value> {
+    fn eight(input: &mut impl Read) -> std::io::Result<[u8; 8]> {
+        let mut bytes = [0; 8];
+        input.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+    fn bytes(input: &mut impl Read) -> std::io::Result<Vec<u8>> {
+        let mut bytes = vec![0; u64::from_le_bytes(eight(input)?) as usize];
+        input.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+    let mut tag = [0; 1];
+    input.read_exact(&mut tag)?;
+    Ok(match tag[0] {
+        NULL => Value::Null,
+        TEXT => Value::Text(
+            String::from_utf8(bytes(input)?)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+        ),
+        INT => Value::Int(i64::from_le_bytes(eight(input)?)),
+        REAL => Value::Real(f64::from_le_bytes(eight(input)?)),
+        BLOB => Value::Blob(bytes(input)?),
+        tag => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown value tag {}", tag),
+            ));
+        }
+    })
+}
+
+/// Runs `query` for `Database.spool()`, writing its rows to a file in `dir`
+/// as they arrive rather than keeping them in memory.
+pub(crate) fn spool<'py>(
+    py: Python<'py>,
+    db: &Database,
+    query: &Bound<'py, PyAny>,
+    dir: Option<PathBuf>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let (sql, params) = db.render(py, query)?;
+    let db = context::resolve(py, Some(db))?;
+    let dir = dir.unwrap_or_else(std::env::temp_dir);
+    future_into_py(py, async move {
+        let file = SpoolFile {
+            path: dir.join(format!(
+                "fust-spool-{}-{}",
+                std::process::id(),
+                NEXT_SPOOL.fetch_add(1, Ordering::Relaxed)
+            )),
+        };
+        let created = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file.path)
+            .map_err(|e| io_error(&file.path, e))?;
+        let mut out = BufWriter::new(created);
+        let mut names: Option<Vec<String>> = None;
+        let mut len = 0;
+        let mut spool_row = |row: &AnyRow| -> Result<(), FustOrmError> {
+            if names.is_none() {
+                names = Some(row.columns().iter().map(|c| c.name().to_string()).collect());
+            }
+            write_row(&mut out, row)?;
+            len += 1;
+            Ok(())
+        };
+
+        let (sql, params) = rewrite::statement(&sql, params)?;
+        debug!("Spooling the rows of: \"{}\"", sql);
+        let started = Instant::now();
+        let tagged = sqlcomment::tag(&sql);
+        let query = bind_params(&tagged, &params);
+        let spooled = async {
+            let mut guard = db.lock_tx().await?;
+            let mut conn;
+            let mut rows = match guard.as_mut() {
+                Some(guard) => {
+                    let tx = guard.as_mut().expect("the transaction is open");
+                    query.fetch(&mut **tx)
+                }
+                None => {
+                    if let Some(rows) = mock::fetch(&db.pool, &sql, &params).await {
+                        return rows?.iter().try_for_each(&mut spool_row);
+                    }
+                    conn = acquire(&db.pool).await?;
+                    query.fetch(&mut *conn)
+                }
+            };
+            while let Some(row) = rows.try_next().await? {
+                spool_row(&row)?;
+            }
+            Ok::<_, FustOrmError>(())
+        }
+        .await;
+        metrics::record_query(&sql, started.elapsed(), spooled.is_err());
+        let spooled = spooled.map_err(|e| e.with_statement(&sql, &params));
+        timing::record(&sql, &params, started.elapsed(), spooled.as_ref().err());
+        spooled?;
+        out.flush().map_err(|e| io_error(&file.path, e))?;
+        drop(out);
+        debug!("Spooled {} rows to '{}'", len, file.path.display());
+        Ok(SpooledRows {
+            file: Mutex::new(Some(Arc::new(file))),
+            names: Arc::new(names.unwrap_or_default()),
+            len,
+        })
+    })
+}
+
+/// The rows of a query written to a temporary file by `Database.spool()`,
+/// which iterating reads back one at a time, as dicts, so that a result
+/// larger than memory can be exported.
+///
+/// ```python
+/// with await db.spool(select("SELECT * FROM events")) as rows:
+///     for row in rows:
+///         writer.writerow(row)
+/// ```
+///
+/// Each iteration reads the rows from the start. The file is deleted by
+/// `close()`, or once the rows and their iterators are garbage collected.
+#[pyclass(module = "fust_orm")]
+pub struct SpooledRows {
+    file: Mutex<Option<Arc<SpoolFile>>>,
+    names: Arc<Vec<String>>,
+    len: usize,
+}
+
+#[pymethods]
+impl SpooledRows {
+    /// The names of the columns, empty if the query returned no rows.
+    #[getter]
+    fn columns<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.names.iter())
+    }
+
+    fn __len__(&self) -> usize {
+        self.len
+    }
+
+    fn __iter__(&self) -> PyResult<SpooledRowIterator> {
+        let Some(file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "The spooled rows are closed.".to_string(),
+            )
+            .into());
+        };
+        let reader = File::open(&file.path).map_err(|e| io_error(&file.path, e))?;
+        Ok(SpooledRowIterator {
+            reading: Some((BufReader::new(reader), file)),
+            names: self.names.clone(),
+            remaining: self.len,
+        })
+    }
+
+    /// Deletes the file, once the iterations in progress end.
+    fn close(&self) {
+        self.file.lock().unwrap_or_else(|e| e.into_inner()).take();
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        _exc_type: Option<&Bound<PyAny>>,
+        _exc_value: Option<&Bound<PyAny>>,
+        _traceback: Option<&Bound<PyAny>>,
+    ) -> bool {
+        self.close();
+        false
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<SpooledRows rows={}>", self.len)
+    }
+}
+
+/// An iteration over `SpooledRows`.
+#[pyclass(module = "fust_orm")]
+pub struct SpooledRowIterator {
+    /// The reader of the file, and the file, which it keeps until the
+    /// iteration ends; `None` then.
+    reading: Option<(BufReader<File>, Arc<SpoolFile>)>,
+    names: Arc<Vec<String>>,
+    remaining: usize,
+}
+
+#[pymethods]
+impl SpooledRowIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__<'py>(&mut self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let Some((reader, file)) = &mut self.reading else {
+            return Ok(None);
+        };
+        if self.remaining == 0 {
+            self.reading = None;
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let row = PyDict::new(py);
+        for name in self.names.iter() {
+            let value = read_value(reader).map_err(|e| io_error(&file.path, e))?;
+            row.set_item(PyString::new(py, name), value.to_object(py)?)?;
+        }
+        Ok(Some(row))
+    }
+}
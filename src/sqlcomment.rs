@@ -0,0 +1,136 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString, PyTuple};
+
+/// The tags appended to statements, sorted by key.
+type Tags = Vec<(String, Tag)>;
+
+/// The current tags, once `tag_statements()` was called with some.
+static TAGS: Mutex<Option<Arc<Tags>>> = Mutex::new(None);
+
+/// Where the value of a tag comes from.
+pub(crate) enum Tag {
+    Value(String),
+    /// A `ContextVar`, read in the context that called the database.
+    Var(Py<PyAny>),
+    /// A callable, called in the context that called the database.
+    Callable(Py<PyAny>),
+}
+
+/// Appends a comment tagging statements to every statement, in the format of
+/// sqlcommenter: `/*key='value',...*/`.
+///
+/// Each value is a string, a `contextvars.ContextVar` or a callable returning
+/// a string; the last two are read in the context the database was called
+/// from, and tags without a value are left out. `tag_statements()` stops
+/// tagging.
+#[pyfunction]
+#[pyo3(signature = (**tags))]
+pub(crate) fn tag_statements(tags: Option<&Bound<PyDict>>) -> PyResult<()> {
+    let mut parsed = Vec::new();
+    for (key, value) in tags.into_iter().flatten() {
+        let key: String = key.extract()?;
+        let tag = Tag::new(&key, &value)?;
+        parsed.push((key, tag));
+    }
+    parsed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let tags = (!parsed.is_empty()).then(|| Arc::new(parsed));
+    let previous = std::mem::replace(&mut *TAGS.lock().unwrap_or_else(|e| e.into_inner()), tags);
+    drop(previous);
+    Ok(())
+}
+
+impl Tag {
+    /// The tag `key` with `value`: a string, a `ContextVar` or a callable.
+    pub(crate) fn new(key: &str, value: &Bound<PyAny>) -> PyResult<Tag> {
+        let context_var = value.py().import("contextvars")?.getattr("ContextVar")?;
+        if let Ok(value) = value.downcast::<PyString>() {
+            Ok(Tag::Value(value.to_string()))
+        } else if value.is_instance(&context_var)? {
+            Ok(Tag::Var(value.clone().unbind()))
+        } else if value.is_callable() {
+            Ok(Tag::Callable(value.clone().unbind()))
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "The tag '{}' must be a string, a ContextVar or a callable",
+                key
+            )))
+        }
+    }
+}
+
+/// Percent-encodes everything but unreserved URL characters, which also keeps
+/// quotes and the end of the comment out of it.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Calls `function` with `args` in the context that called the database, or
+/// the current one outside of a task.
+pub(crate) fn call_in_caller_context<'py>(
+    py: Python<'py>,
+    function: Bound<'py, PyAny>,
+    args: &[Bound<'py, PyAny>],
+) -> PyResult<Bound<'py, PyAny>> {
+    match pyo3_async_runtimes::tokio::get_current_locals(py) {
+        Ok(locals) => {
+            let mut run_args = vec![function];
+            run_args.extend_from_slice(args);
+            locals
+                .context(py)
+                .call_method1("run", PyTuple::new(py, run_args)?)
+        }
+        Err(_) => function.call1(PyTuple::new(py, args)?),
+    }
+}
+
+/// The value of a tag where the database was called, if it has one.
+pub(crate) fn value(py: Python, tag: &Tag) -> PyResult<Option<String>> {
+    let value = match tag {
+        Tag::Value(value) => return Ok(Some(value.clone())),
+        Tag::Var(var) => call_in_caller_context(
+            py,
+            var.bind(py).getattr("get")?,
+            &[py.None().into_bound(py)],
+        )?,
+        Tag::Callable(callable) => call_in_caller_context(py, callable.bind(py).clone(), &[])?,
+    };
+    if value.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(value.str()?.to_string()))
+}
+
+/// `sql` with the comment of the current tags appended, if there are any.
+pub(crate) fn tag(sql: &str) -> Cow<'_, str> {
+    let Some(tags) = TAGS.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return Cow::Borrowed(sql);
+    };
+    let pairs: Vec<String> = Python::attach(|py| {
+        tags.iter()
+            .filter_map(|(key, tag)| match value(py, tag) {
+                Ok(value) => value.map(|value| format!("{}='{}'", encode(key), encode(&value))),
+                Err(e) => {
+                    debug!("Couldn't read the tag '{}': {}", key, e);
+                    None
+                }
+            })
+            .collect()
+    });
+    if pairs.is_empty() {
+        return Cow::Borrowed(sql);
+    }
+    Cow::Owned(format!("{} /*{}*/", sql, pairs.join(",")))
+}
@@ -0,0 +1,519 @@
+use std::ffi::{CString, c_int, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+use std::sync::Arc;
+
+use libsqlite3_sys as ffi;
+use log::debug;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyBytes, PyFloat, PyInt, PyString, PyTuple};
+
+use crate::database::Database;
+use crate::error::FustOrmError;
+use crate::sqlite_hooks::{self, Definition};
+
+/// The longest function name SQLite accepts, in bytes.
+const MAX_NAME_LEN: usize = 255;
+/// The most arguments SQLite allows a function.
+const MAX_ARGS: c_int = 127;
+
+/// What a SQL function calls.
+enum Implementation {
+    /// A callable returning the result.
+    Scalar(Py<PyAny>),
+    /// A class whose instances are fed the rows with `step()` and return the
+    /// result from `finalize()`.
+    Aggregate(Py<PyAny>),
+}
+
+/// A SQL function defined on every connection of a pool.
+struct Function {
+    name: CString,
+    nargs: c_int,
+    flags: c_int,
+    implementation: Implementation,
+}
+
+impl Definition for Function {
+    unsafe fn install(self: Arc<Self>, db: *mut ffi::sqlite3) -> c_int {
+        let name = self.name.as_ptr();
+        let (nargs, flags) = (self.nargs, self.flags);
+        let (func, step, last): (XFunc, XFunc, XFinal) = match self.implementation {
+            Implementation::Scalar(_) => (Some(call_scalar), None, None),
+            Implementation::Aggregate(_) => (None, Some(call_step), Some(call_final)),
+        };
+        // SAFETY: the caller has `db` to itself. The connection holds a
+        // reference to the function until SQLite destroys it.
+        unsafe {
+            ffi::sqlite3_create_function_v2(
+                db,
+                name,
+                nargs,
+                flags,
+                Arc::into_raw(self) as *mut c_void,
+                func,
+                step,
+                last,
+                Some(release),
+            )
+        }
+    }
+}
+
+type XFunc =
+    Option<unsafe extern "C" fn(*mut ffi::sqlite3_context, c_int, *mut *mut ffi::sqlite3_value)>;
+type XFinal = Option<unsafe extern "C" fn(*mut ffi::sqlite3_context)>;
+
+fn validate(name: &str, nargs: i32, implementation: &Bound<'_, PyAny>) -> PyResult<CString> {
+    if !implementation.is_callable() {
+        return Err(PyTypeError::new_err(format!(
+            "The implementation of {}() must be callable.",
+            name
+        )));
+    }
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "A function name must be 1 to {} bytes long.",
+            MAX_NAME_LEN
+        ))
+        .into());
+    }
+    if !(-1..=MAX_ARGS).contains(&nargs) {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "nargs must be between -1 (any number) and {}.",
+            MAX_ARGS
+        ))
+        .into());
+    }
+    CString::new(name).map_err(|_| {
+        FustOrmError::InvalidQueryArgument("A function name can't contain NUL.".to_string()).into()
+    })
+}
+
+fn define(
+    db: &Database,
+    method: &str,
+    name: String,
+    nargs: i32,
+    deterministic: bool,
+    implementation: Implementation,
+) -> PyResult<()> {
+    sqlite_hooks::require_sqlite(db, method)?;
+    let object = match &implementation {
+        Implementation::Scalar(object) | Implementation::Aggregate(object) => object,
+    };
+    let c_name = Python::attach(|py| validate(&name, nargs, object.bind(py)))?;
+    let mut flags = ffi::SQLITE_UTF8;
+    if deterministic {
+        flags |= ffi::SQLITE_DETERMINISTIC;
+    }
+    sqlite_hooks::define(
+        db,
+        Arc::new(Function {
+            name: c_name,
+            nargs,
+            flags,
+            implementation,
+        }),
+    )?;
+    debug!("Defined the SQL function {}()", name);
+    Ok(())
+}
+
+/// Defines the SQL function `name` on the connections of `db`, calling `func`
+/// with `nargs` arguments (-1 for any number).
+pub(crate) fn create_function(
+    db: &Database,
+    name: String,
+    nargs: i32,
+    func: Py<PyAny>,
+    deterministic: bool,
+) -> PyResult<()> {
+    define(
+        db,
+        "create_function",
+        name,
+        nargs,
+        deterministic,
+        Implementation::Scalar(func),
+    )
+}
+
+/// Defines the aggregate SQL function `name` on the connections of `db`,
+/// instantiating `aggregate_class` for each group.
+pub(crate) fn create_aggregate(
+    db: &Database,
+    name: String,
+    nargs: i32,
+    aggregate_class: Py<PyAny>,
+    deterministic: bool,
+) -> PyResult<()> {
+    define(
+        db,
+        "create_aggregate",
+        name,
+        nargs,
+        deterministic,
+        Implementation::Aggregate(aggregate_class),
+    )
+}
+
+/// Whether `value` matches `pattern`, for SQLite's `REGEXP` operator, which
+/// calls `regexp(pattern, value)`. `NULL` matches nothing.
+#[pyfunction]
+fn regexp(
+    py: Python,
+    pattern: Option<String>,
+    value: Option<Bound<PyAny>>,
+) -> PyResult<Option<bool>> {
+    let (Some(pattern), Some(value)) = (pattern, value) else {
+        return Ok(None);
+    };
+    // `re` caches the patterns it compiles.
+    let found = py
+        .import("re")?
+        .call_method1("search", (pattern, value.str()?))?;
+    Ok(Some(!found.is_none()))
+}
+
+/// The `regexp()` function every SQLite pool defines, for
+/// `ColumnField.regex()`. `create_function("regexp", ...)` replaces it.
+pub(crate) fn builtin_regexp(py: Python) -> PyResult<Arc<dyn Definition>> {
+    Ok(Arc::new(Function {
+        name: c"regexp".to_owned(),
+        nargs: 2,
+        flags: ffi::SQLITE_UTF8 | ffi::SQLITE_DETERMINISTIC,
+        implementation: Implementation::Scalar(wrap_pyfunction!(regexp, py)?.into_any().unbind()),
+    }))
+}
+
+unsafe extern "C" fn release(function: *mut c_void) {
+    // SAFETY: the pointer was made by `Arc::into_raw` in `install()`.
+    drop(unsafe { Arc::from_raw(function as *const Function) });
+}
+
+/// The function whose call `context` is.
+///
+/// # Safety
+///
+/// `context` must be a call of a function defined by `install()`.
+unsafe fn function<'a>(context: *mut ffi::sqlite3_context) -> &'a Function {
+    unsafe { &*(ffi::sqlite3_user_data(context) as *const Function) }
+}
+
+/// Runs `f` with the GIL, reporting its error, or a panic, as the error of
+/// the call. SQLite calls it on the connection's thread while the statement
+/// is executing.
+fn call(
+    context: *mut ffi::sqlite3_context,
+    name: &CString,
+    f: impl FnOnce(Python<'_>) -> PyResult<()>,
+) {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        Python::attach(|py| f(py).map_err(|err| err.to_string()))
+    }));
+    let message = match result {
+        Ok(Ok(())) => return,
+        Ok(Err(err)) => format!("{}() raised {}", name.to_string_lossy(), err),
+        Err(_) => format!("{}() panicked", name.to_string_lossy()),
+    };
+    // SAFETY: `context` is the call in progress; SQLite copies the message.
+    unsafe {
+        ffi::sqlite3_result_error(context, message.as_ptr().cast(), message.len() as c_int);
+    }
+}
+
+/// Converts the arguments of a call to Python.
+///
+/// # Safety
+///
+/// `argv` must hold `argc` values of the call in progress.
+unsafe fn arguments<'py>(
+    py: Python<'py>,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let values = (0..argc as usize).map(|i| unsafe { to_python(py, *argv.add(i)) });
+    PyTuple::new(py, values)
+}
+
+/// # Safety
+///
+/// `value` must be a value of the call in progress.
+unsafe fn to_python<'py>(py: Python<'py>, value: *mut ffi::sqlite3_value) -> Bound<'py, PyAny> {
+    // SAFETY: SQLite keeps the text and blob until the call returns; they are
+    // copied before.
+    unsafe {
+        match ffi::sqlite3_value_type(value) {
+            ffi::SQLITE_INTEGER => ffi::sqlite3_value_int64(value)
+                .into_pyobject(py)
+                .unwrap()
+                .into_any(),
+            ffi::SQLITE_FLOAT => PyFloat::new(py, ffi::sqlite3_value_double(value)).into_any(),
+            ffi::SQLITE_TEXT => {
+                let text = ffi::sqlite3_value_text(value);
+                let len = ffi::sqlite3_value_bytes(value) as usize;
+                let bytes = if text.is_null() {
+                    &[][..]
+                } else {
+                    std::slice::from_raw_parts(text, len)
+                };
+                PyString::new(py, &String::from_utf8_lossy(bytes)).into_any()
+            }
+            ffi::SQLITE_BLOB => {
+                let blob = ffi::sqlite3_value_blob(value).cast::<u8>();
+                let len = ffi::sqlite3_value_bytes(value) as usize;
+                let bytes = if blob.is_null() {
+                    &[][..]
+                } else {
+                    std::slice::from_raw_parts(blob, len)
+                };
+                PyBytes::new(py, bytes).into_any()
+            }
+            _ => py.None().into_bound(py),
+        }
+    }
+}
+
+/// Sets the result of the call in progress to `value`.
+fn set_result(context: *mut ffi::sqlite3_context, value: &Bound<'_, PyAny>) -> PyResult<()> {
+    // SAFETY: `context` is the call in progress; SQLite copies text and blobs
+    // marked transient.
+    unsafe {
+        if value.is_none() {
+            ffi::sqlite3_result_null(context);
+        } else if value.is_instance_of::<PyBool>() || value.is_instance_of::<PyInt>() {
+            ffi::sqlite3_result_int64(context, value.extract()?);
+        } else if value.is_instance_of::<PyFloat>() {
+            ffi::sqlite3_result_double(context, value.extract()?);
+        } else if let Ok(text) = value.downcast::<PyString>() {
+            let text = text.to_str()?;
+            ffi::sqlite3_result_text(
+                context,
+                text.as_ptr().cast(),
+                text.len() as c_int,
+                ffi::SQLITE_TRANSIENT(),
+            );
+        } else if let Ok(bytes) = value.downcast::<PyBytes>() {
+            let bytes = bytes.as_bytes();
+            ffi::sqlite3_result_blob(
+                context,
+                bytes.as_ptr().cast(),
+                bytes.len() as c_int,
+                ffi::SQLITE_TRANSIENT(),
+            );
+        } else {
+            return Err(PyTypeError::new_err(format!(
+                "unsupported result type {}",
+                value.get_type().name()?
+            )));
+        }
+    }
+    Ok(())
+}
+
+unsafe extern "C" fn call_scalar(
+    context: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    // SAFETY: SQLite calls it for a scalar function defined by `install()`.
+    let function = unsafe { function(context) };
+    let Implementation::Scalar(func) = &function.implementation else {
+        return;
+    };
+    call(context, &function.name, |py| {
+        let args = unsafe { arguments(py, argc, argv) }?;
+        set_result(context, &func.call1(py, args)?.into_bound(py))
+    });
+}
+
+/// The slot of the aggregate's instance for the group of `context`, null if
+/// none has been allocated and `allocate` is false.
+///
+/// # Safety
+///
+/// `context` must be a call of an aggregate defined by `install()`.
+unsafe fn instance_slot(context: *mut ffi::sqlite3_context, allocate: bool) -> *mut *mut Py<PyAny> {
+    let size = if allocate {
+        size_of::<*mut Py<PyAny>>()
+    } else {
+        0
+    };
+    // SAFETY: SQLite zeroes the slot when it allocates it.
+    unsafe { ffi::sqlite3_aggregate_context(context, size as c_int).cast() }
+}
+
+unsafe extern "C" fn call_step(
+    context: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    // SAFETY: SQLite calls it for an aggregate defined by `install()`.
+    let (function, slot) = unsafe { (function(context), instance_slot(context, true)) };
+    if slot.is_null() {
+        unsafe { ffi::sqlite3_result_error_nomem(context) };
+        return;
+    }
+    let Implementation::Aggregate(class) = &function.implementation else {
+        return;
+    };
+    call(context, &function.name, |py| {
+        // SAFETY: the slot holds null or the instance made by a previous step.
+        let instance = unsafe {
+            if (*slot).is_null() {
+                *slot = Box::into_raw(Box::new(class.call0(py)?));
+            }
+            &**slot
+        };
+        let args = unsafe { arguments(py, argc, argv) }?;
+        instance.call_method1(py, "step", args)?;
+        Ok(())
+    });
+}
+
+unsafe extern "C" fn call_final(context: *mut ffi::sqlite3_context) {
+    // SAFETY: SQLite calls it for an aggregate defined by `install()`, once
+    // per group.
+    let (function, slot) = unsafe { (function(context), instance_slot(context, false)) };
+    let Implementation::Aggregate(class) = &function.implementation else {
+        return;
+    };
+    // The instance is taken out of the slot, which SQLite frees after this.
+    let instance = if slot.is_null() || unsafe { (*slot).is_null() } {
+        None
+    } else {
+        let instance = unsafe { Box::from_raw(*slot) };
+        unsafe { *slot = ptr::null_mut() };
+        Some(instance)
+    };
+    call(context, &function.name, |py| {
+        // Without rows there was no step to make the instance.
+        let instance = match instance {
+            Some(instance) => *instance,
+            None => class.call0(py)?,
+        };
+        let result = instance.call_method0(py, "finalize")?;
+        set_result(context, result.bind(py))
+    });
+}
+
+/// A collation defined on every connection of a pool, or removed when it has
+/// no comparator.
+struct Collation {
+    name: CString,
+    comparator: Option<Py<PyAny>>,
+}
+
+impl Definition for Collation {
+    unsafe fn install(self: Arc<Self>, db: *mut ffi::sqlite3) -> c_int {
+        let name = self.name.as_ptr();
+        // SAFETY: the caller has `db` to itself. The connection holds a
+        // reference to the collation until SQLite destroys it.
+        unsafe {
+            if self.comparator.is_none() {
+                return ffi::sqlite3_create_collation_v2(
+                    db,
+                    name,
+                    ffi::SQLITE_UTF8,
+                    ptr::null_mut(),
+                    None,
+                    None,
+                );
+            }
+            ffi::sqlite3_create_collation_v2(
+                db,
+                name,
+                ffi::SQLITE_UTF8,
+                Arc::into_raw(self) as *mut c_void,
+                Some(compare),
+                Some(release_collation),
+            )
+        }
+    }
+}
+
+/// Defines the collation `name` on the connections of `db`, ordering text by
+/// `comparator(a, b)`, or with `None` removes it.
+pub(crate) fn create_collation(
+    py: Python,
+    db: &Database,
+    name: String,
+    comparator: Option<Py<PyAny>>,
+) -> PyResult<()> {
+    sqlite_hooks::require_sqlite(db, "create_collation")?;
+    if let Some(comparator) = &comparator
+        && !comparator.bind(py).is_callable()
+    {
+        return Err(PyTypeError::new_err(format!(
+            "The comparator of the collation {} must be callable.",
+            name
+        )));
+    }
+    let c_name = CString::new(name.as_str()).map_err(|_| {
+        FustOrmError::InvalidQueryArgument("A collation name can't contain NUL.".to_string())
+    })?;
+    if name.is_empty() {
+        return Err(
+            FustOrmError::InvalidQueryArgument("A collation needs a name.".to_string()).into(),
+        );
+    }
+    match comparator {
+        Some(_) => debug!("Defined the collation {}", name),
+        None => debug!("Removed the collation {}", name),
+    }
+    sqlite_hooks::define(
+        db,
+        Arc::new(Collation {
+            name: c_name,
+            comparator,
+        }),
+    )?;
+    Ok(())
+}
+
+unsafe extern "C" fn release_collation(collation: *mut c_void) {
+    // SAFETY: the pointer was made by `Arc::into_raw` in `install()`.
+    drop(unsafe { Arc::from_raw(collation as *const Collation) });
+}
+
+/// Compares two texts with the comparator. An exception it raises is reported
+/// with `sys.unraisablehook` and the texts compare equal, since SQLite can't
+/// fail a comparison.
+unsafe extern "C" fn compare(
+    collation: *mut c_void,
+    len_a: c_int,
+    a: *const c_void,
+    len_b: c_int,
+    b: *const c_void,
+) -> c_int {
+    // SAFETY: the collation was installed with itself as its user data, and
+    // SQLite passes the texts with their lengths.
+    let (collation, a, b) = unsafe {
+        let collation = &*(collation as *const Collation);
+        let a = std::slice::from_raw_parts(a.cast::<u8>(), len_a as usize);
+        let b = std::slice::from_raw_parts(b.cast::<u8>(), len_b as usize);
+        (collation, a, b)
+    };
+    let Some(comparator) = &collation.comparator else {
+        return 0;
+    };
+    // A panic mustn't unwind into SQLite.
+    catch_unwind(AssertUnwindSafe(|| {
+        Python::attach(|py| {
+            let (a, b) = (String::from_utf8_lossy(a), String::from_utf8_lossy(b));
+            let order = comparator
+                .call1(py, (a, b))
+                .and_then(|order| order.extract::<i64>(py));
+            match order {
+                Ok(order) => order.signum() as c_int,
+                Err(err) => {
+                    err.write_unraisable(py, Some(comparator.bind(py)));
+                    0
+                }
+            }
+        })
+    }))
+    .unwrap_or(0)
+}
@@ -0,0 +1,572 @@
+use std::collections::HashMap;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::ptr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Once};
+
+use libsqlite3_sys as ffi;
+use log::{debug, warn};
+use pyo3::prelude::*;
+use sqlx::any::{AnyConnectOptions, AnyPoolOptions};
+use sqlx::{AnyConnection, AnyPool, ConnectOptions, Connection};
+
+use crate::changeset::Recorder;
+use crate::database::{Database, PoolSettings};
+use crate::error::FustOrmError;
+use crate::schema::Dialect;
+use crate::sqlite_functions;
+
+// The Any driver doesn't expose SQLite's connection handles, so they are
+// reached through an auto extension, which SQLite runs on every connection it
+// opens. It registers a tag for the connection's handle, installs the update
+// hook and defines a SQL function that tags the connection with the id of its
+// pool and returns the handle. The handle is only looked up in the registry,
+// never dereferenced as returned, so a value from SQL can't reach anything
+// but a tagged connection. The pool's definitions are then installed on it
+// while the pool hands the connection out, when no other thread uses it.
+
+/// The SQL function tagging a connection with the id of its pool. It is
+/// direct-only, so triggers and views can't call it.
+const TAG_FUNCTION: &CStr = c"fust_orm_tag_connection";
+const TAG_SQL: &str = "SELECT fust_orm_tag_connection(?)";
+
+static REGISTER_EXTENSION: Once = Once::new();
+static NEXT_POOL_ID: AtomicI64 = AtomicI64::new(1);
+
+/// Something defined on every connection of a pool, such as a function.
+pub(crate) trait Definition: Send + Sync {
+    /// Defines it on `db`, returning the SQLite result code.
+    ///
+    /// # Safety
+    ///
+    /// `db` must be an open connection no other thread uses meanwhile.
+    unsafe fn install(self: Arc<Self>, db: *mut ffi::sqlite3) -> c_int;
+}
+
+/// What the SQLite pools have registered, by pool id.
+#[derive(Default)]
+struct PoolHooks {
+    change_callbacks: Vec<Arc<Py<PyAny>>>,
+    /// The definitions in the order they were made; a later one with the
+    /// same name replaces an earlier one.
+    definitions: Vec<Arc<dyn Definition>>,
+    /// The handles of the pool's open connections.
+    connections: Vec<usize>,
+    /// The changesets being recorded.
+    recorders: Vec<Arc<Recorder>>,
+}
+
+struct Registry {
+    /// The ids of the pools, by the address of their connect options, which
+    /// the clones of a pool share.
+    pool_ids: HashMap<usize, i64>,
+    pools: HashMap<i64, PoolHooks>,
+    /// The tags of the open connections, by the address of their handle.
+    tags: HashMap<usize, Arc<ConnectionTag>>,
+}
+
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+fn registry() -> MutexGuard<'static, Option<Registry>> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn with_registry<T>(f: impl FnOnce(&mut Registry) -> T) -> T {
+    let mut guard = registry();
+    f(guard.get_or_insert_with(|| Registry {
+        pool_ids: HashMap::new(),
+        pools: HashMap::new(),
+        tags: HashMap::new(),
+    }))
+}
+
+/// What a connection's hooks read, registered by the auto extension and
+/// removed with the connection.
+struct ConnectionTag {
+    /// The address of the connection's handle, open while the tag is
+    /// registered.
+    handle: usize,
+    /// The id of the pool, 0 until tagged; a connection is tagged once.
+    pool_id: AtomicI64,
+    /// How many of the pool's definitions are installed.
+    installed: Mutex<usize>,
+}
+
+fn options_key(pool: &AnyPool) -> usize {
+    Arc::as_ptr(&pool.connect_options()) as usize
+}
+
+/// The id of a SQLite pool opened by `connect_sqlite()`.
+pub(crate) fn pool_id(db: &Database) -> Result<i64, FustOrmError> {
+    with_registry(|registry| registry.pool_ids.get(&options_key(&db.pool)).copied()).ok_or_else(
+        || {
+            FustOrmError::InvalidQueryArgument(
+                "The database isn't a SQLite database connected by fust_orm.".to_string(),
+            )
+        },
+    )
+}
+
+/// The number of SQLite connections open.
+pub(crate) fn open_connections() -> usize {
+    with_registry(|registry| registry.tags.len())
+}
+
+/// Fails unless `db` is a SQLite database.
+pub(crate) fn require_sqlite(db: &Database, method: &str) -> Result<(), FustOrmError> {
+    if db.dialect() != Dialect::Sqlite {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "{}() is only supported on SQLite.",
+            method
+        )));
+    }
+    Ok(())
+}
+
+/// Registers SQLite's auto extension, once for the process.
+fn register_extension() {
+    REGISTER_EXTENSION.call_once(|| {
+        // SAFETY: `init_connection` has the signature of an extension entry
+        // point and stays valid for the life of the process.
+        let rc = unsafe { ffi::sqlite3_auto_extension(Some(init_connection)) };
+        if rc != ffi::SQLITE_OK {
+            warn!(
+                "Couldn't register the SQLite connection hooks (error {})",
+                rc
+            );
+        }
+    });
+}
+
+/// Opens a pool of SQLite connections, each tagged for the hooks and
+/// definitions of the pool, with `extensions` loaded, recycled as `settings`
+/// describes.
+pub(crate) async fn connect_sqlite(
+    options: AnyConnectOptions,
+    extensions: &[String],
+    settings: PoolSettings,
+) -> Result<AnyPool, sqlx::Error> {
+    let extensions = extensions
+        .iter()
+        .map(|name| {
+            CString::new(name.as_str()).map_err(|_| {
+                sqlx::Error::Configuration("An extension name can't contain NUL.".into())
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let extensions = Arc::new(extensions);
+    register_extension();
+    if !extensions.is_empty() {
+        // The pool retries connections failing in `after_connect` until it
+        // times out, hiding the error, so the extensions are tried on a
+        // connection of their own first.
+        let mut conn = options.connect().await?;
+        let loaded = match connection_tag(&mut conn, 0).await {
+            Ok(tag) => load_extensions(&tag, &extensions),
+            Err(e) => Err(e),
+        };
+        conn.close().await?;
+        loaded?;
+    }
+    let (id, pool_options) = pool_options(extensions)?;
+    let pool = settings.apply(pool_options).connect_with(options).await?;
+    with_registry(|registry| registry.pool_ids.insert(options_key(&pool), id));
+    Ok(pool)
+}
+
+/// Like `connect_sqlite()` without extensions, but opens the connections
+/// when they are first needed.
+pub(crate) fn connect_sqlite_lazy(options: AnyConnectOptions) -> Result<AnyPool, sqlx::Error> {
+    register_extension();
+    let (id, pool_options) = pool_options(Arc::new(Vec::new()))?;
+    let pool = pool_options.connect_lazy_with(options);
+    with_registry(|registry| registry.pool_ids.insert(options_key(&pool), id));
+    Ok(pool)
+}
+
+/// Registers the hooks of a new pool, returning its id and the options of a
+/// pool tagging its connections with it.
+fn pool_options(extensions: Arc<Vec<CString>>) -> Result<(i64, AnyPoolOptions), sqlx::Error> {
+    let id = NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed);
+    let regexp = Python::attach(sqlite_functions::builtin_regexp)
+        .map_err(|e| sqlx::Error::Configuration(e.to_string().into()))?;
+    let hooks = PoolHooks {
+        definitions: vec![regexp],
+        ..PoolHooks::default()
+    };
+    with_registry(|registry| registry.pools.insert(id, hooks));
+    let pool_options = AnyPoolOptions::new()
+        .after_connect(move |conn, _| {
+            let extensions = extensions.clone();
+            Box::pin(async move {
+                let tag = connection_tag(conn, id).await?;
+                load_extensions(&tag, &extensions)?;
+                install_definitions(id, &tag)
+            })
+        })
+        .before_acquire(move |conn, _| {
+            Box::pin(async move {
+                if needs_catch_up(id) {
+                    catch_up(conn, id).await?;
+                }
+                Ok(true)
+            })
+        })
+        .after_release(move |conn, _| {
+            Box::pin(async move {
+                let recorders = recorders(id);
+                if !recorders.is_empty() {
+                    let tag = connection_tag(conn, id).await?;
+                    for recorder in recorders {
+                        recorder.collect(Some(tag.handle));
+                    }
+                }
+                Ok(true)
+            })
+        });
+    Ok((id, pool_options))
+}
+/// Loads `extensions` on the connection of `tag`, whose statements have all
+/// finished. Loading is enabled only meanwhile, and never for the
+/// `load_extension()` SQL function, which would let any statement load a
+/// library.
+fn load_extensions(tag: &ConnectionTag, extensions: &[CString]) -> Result<(), sqlx::Error> {
+    if extensions.is_empty() {
+        return Ok(());
+    }
+    // SAFETY: the tag is registered, so the connection is still open, and it
+    // isn't handed out yet.
+    let db = tag.handle as *mut ffi::sqlite3;
+    let enable = |on: c_int| unsafe {
+        ffi::sqlite3_db_config(
+            db,
+            ffi::SQLITE_DBCONFIG_ENABLE_LOAD_EXTENSION,
+            on,
+            ptr::null_mut::<c_int>(),
+        )
+    };
+    enable(1);
+    let mut result = Ok(());
+    for extension in extensions {
+        let mut error: *mut c_char = ptr::null_mut();
+        // SAFETY: SQLite sets `error` to a message it allocated, if any.
+        let rc =
+            unsafe { ffi::sqlite3_load_extension(db, extension.as_ptr(), ptr::null(), &mut error) };
+        if rc != ffi::SQLITE_OK {
+            let message = if error.is_null() {
+                format!("error {}", rc)
+            } else {
+                // SAFETY: the message is freed once copied.
+                unsafe {
+                    let message = CStr::from_ptr(error).to_string_lossy().into_owned();
+                    ffi::sqlite3_free(error.cast());
+                    message
+                }
+            };
+            result = Err(sqlx::Error::Configuration(
+                format!(
+                    "couldn't load the SQLite extension {}: {}",
+                    extension.to_string_lossy(),
+                    message
+                )
+                .into(),
+            ));
+            break;
+        }
+        debug!(
+            "Loaded the SQLite extension {}",
+            extension.to_string_lossy()
+        );
+    }
+    enable(0);
+    result
+}
+
+/// Whether connections of the pool have to be caught up when handed out:
+/// changesets are being recorded, which connections may lack sessions for, or
+/// a connection lacks some of the definitions.
+fn needs_catch_up(pool_id: i64) -> bool {
+    with_registry(|registry| {
+        let Some(hooks) = registry.pools.get(&pool_id) else {
+            return false;
+        };
+        !hooks.recorders.is_empty()
+            || hooks.connections.iter().any(|handle| {
+                registry.tags.get(handle).is_some_and(|tag| {
+                    *tag.installed.lock().unwrap_or_else(|e| e.into_inner())
+                        < hooks.definitions.len()
+                })
+            })
+    })
+}
+
+/// Installs the pool's missing definitions on `conn`, which is being handed
+/// out.
+async fn catch_up(conn: &mut AnyConnection, pool_id: i64) -> Result<(), sqlx::Error> {
+    let tag = connection_tag(conn, pool_id).await?;
+    install_definitions(pool_id, &tag)
+}
+
+/// The tag of `conn`, a connection of the pool `pool_id` (0 for a connection
+/// of no pool), tagging it if it isn't yet.
+async fn connection_tag(
+    conn: &mut AnyConnection,
+    pool_id: i64,
+) -> Result<Arc<ConnectionTag>, sqlx::Error> {
+    let handle: i64 = sqlx::query_scalar(TAG_SQL)
+        .bind(pool_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    let tag = with_registry(|registry| registry.tags.get(&(handle as usize)).cloned())
+        .ok_or_else(|| sqlx::Error::Protocol("the SQLite connection isn't tagged".into()))?;
+    if tag.pool_id.load(Ordering::Relaxed) != pool_id {
+        return Err(sqlx::Error::Protocol(
+            "the SQLite connection is tagged for another pool".into(),
+        ));
+    }
+    Ok(tag)
+}
+
+/// The address of the SQLite handle of `conn`, a connection of the pool
+/// `pool_id`, valid while it is open.
+pub(crate) async fn connection_handle(
+    conn: &mut AnyConnection,
+    pool_id: i64,
+) -> Result<usize, sqlx::Error> {
+    Ok(connection_tag(conn, pool_id).await?.handle)
+}
+
+/// Installs the definitions of the pool missing on the connection of `tag`,
+/// whose statements have all finished, and the sessions of the changesets
+/// being recorded.
+fn install_definitions(pool_id: i64, tag: &ConnectionTag) -> Result<(), sqlx::Error> {
+    let (definitions, recorders) = with_registry(|registry| {
+        let hooks = registry.pools.entry(pool_id).or_default();
+        if !hooks.connections.contains(&tag.handle) {
+            hooks.connections.push(tag.handle);
+        }
+        (hooks.definitions.clone(), hooks.recorders.clone())
+    });
+    // SAFETY: the tag is registered, so the connection is still open.
+    let db = tag.handle as *mut ffi::sqlite3;
+    for recorder in recorders {
+        // SAFETY: the pool hands the connection out only once this returns.
+        let rc = unsafe { recorder.attach(tag.handle, db) };
+        if rc != ffi::SQLITE_OK {
+            return Err(sqlx::Error::Protocol(format!(
+                "couldn't record changes on the connection (error {})",
+                rc
+            )));
+        }
+    }
+    let mut installed = tag.installed.lock().unwrap_or_else(|e| e.into_inner());
+    for definition in &definitions[*installed..] {
+        // SAFETY: the pool hands the connection out only once this returns.
+        let rc = unsafe { definition.clone().install(db) };
+        if rc != ffi::SQLITE_OK {
+            return Err(sqlx::Error::Protocol(format!(
+                "couldn't define a function or collation on the connection (error {})",
+                rc
+            )));
+        }
+        *installed += 1;
+    }
+    Ok(())
+}
+
+/// Adds `definition` to the pool of `db`. Its connections install it when
+/// they are next handed out.
+pub(crate) fn define(db: &Database, definition: Arc<dyn Definition>) -> Result<(), FustOrmError> {
+    let id = pool_id(db)?;
+    with_registry(|registry| {
+        registry
+            .pools
+            .entry(id)
+            .or_default()
+            .definitions
+            .push(definition)
+    });
+    Ok(())
+}
+
+/// The changesets being recorded on the pool `pool_id`.
+fn recorders(pool_id: i64) -> Vec<Arc<Recorder>> {
+    with_registry(|registry| {
+        registry
+            .pools
+            .get(&pool_id)
+            .map(|hooks| hooks.recorders.clone())
+            .unwrap_or_default()
+    })
+}
+
+/// Starts recording a changeset on the connections of its pool, as they are
+/// next handed out.
+pub(crate) fn start_recording(recorder: Arc<Recorder>) {
+    with_registry(|registry| {
+        registry
+            .pools
+            .entry(recorder.pool_id)
+            .or_default()
+            .recorders
+            .push(recorder)
+    });
+}
+
+/// Stops handing out connections to `recorder`.
+pub(crate) fn stop_recording(recorder: &Arc<Recorder>) {
+    with_registry(|registry| {
+        if let Some(hooks) = registry.pools.get_mut(&recorder.pool_id) {
+            hooks.recorders.retain(|r| !Arc::ptr_eq(r, recorder));
+        }
+    });
+}
+
+/// Registers `callback` to be called as `callback(operation, table, rowid)`
+/// for every row inserted, updated or deleted through the pool of `db`, or
+/// with `None` removes the callbacks.
+pub(crate) fn on_change(db: &Database, callback: Option<Py<PyAny>>) -> PyResult<()> {
+    require_sqlite(db, "on_change")?;
+    let id = pool_id(db)?;
+    let removed = with_registry(|registry| {
+        let hooks = registry.pools.entry(id).or_default();
+        match callback {
+            Some(callback) => {
+                debug!("Registered a change callback");
+                hooks.change_callbacks.push(Arc::new(callback));
+                Vec::new()
+            }
+            None => std::mem::take(&mut hooks.change_callbacks),
+        }
+    });
+    // The removed callbacks are released after the lock.
+    drop(removed);
+    Ok(())
+}
+
+/// Registers the tag of a new connection, defines the tagging function on it
+/// and installs the hook.
+unsafe extern "C" fn init_connection(
+    db: *mut ffi::sqlite3,
+    _error: *mut *mut c_char,
+    _api: *const ffi::sqlite3_api_routines,
+) -> c_int {
+    let tag = Arc::new(ConnectionTag {
+        handle: db as usize,
+        pool_id: AtomicI64::new(0),
+        installed: Mutex::new(0),
+    });
+    with_registry(|registry| registry.tags.insert(tag.handle, tag.clone()));
+    let tag = Arc::into_raw(tag).cast_mut();
+    // SAFETY: `db` is the connection being opened. The function holds a
+    // reference to the tag until SQLite drops it, when the connection is
+    // closed, and the hook is only called while it is open.
+    unsafe {
+        let rc = ffi::sqlite3_create_function_v2(
+            db,
+            TAG_FUNCTION.as_ptr(),
+            1,
+            ffi::SQLITE_UTF8 | ffi::SQLITE_DIRECTONLY,
+            tag.cast(),
+            Some(tag_connection),
+            None,
+            None,
+            Some(drop_tag),
+        );
+        if rc != ffi::SQLITE_OK {
+            // SQLite has called `drop_tag` already.
+            return rc;
+        }
+        ffi::sqlite3_update_hook(db, Some(row_changed), tag.cast());
+    }
+    ffi::SQLITE_OK
+}
+
+unsafe extern "C" fn tag_connection(
+    context: *mut ffi::sqlite3_context,
+    _argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) {
+    // SAFETY: the function is defined with one argument and the tag as its
+    // user data.
+    unsafe {
+        let tag = &*ffi::sqlite3_user_data(context).cast::<ConnectionTag>();
+        let pool_id = ffi::sqlite3_value_int64(*argv);
+        // A tagged connection keeps its pool; `connection_tag()` rejects it
+        // for another.
+        let _ = tag
+            .pool_id
+            .compare_exchange(0, pool_id, Ordering::Relaxed, Ordering::Relaxed);
+        ffi::sqlite3_result_int64(context, tag.handle as i64);
+    }
+}
+
+unsafe extern "C" fn drop_tag(tag: *mut c_void) {
+    // SAFETY: the reference was given to the function by `init_connection`
+    // and SQLite drops it once.
+    let tag = unsafe { Arc::from_raw(tag.cast_const().cast::<ConnectionTag>()) };
+    let pool_id = tag.pool_id.load(Ordering::Relaxed);
+    let recorders = with_registry(|registry| {
+        registry.tags.remove(&tag.handle);
+        let Some(hooks) = registry.pools.get_mut(&pool_id) else {
+            return Vec::new();
+        };
+        hooks
+            .connections
+            .retain(|&connection| connection != tag.handle);
+        hooks.recorders.clone()
+    });
+    // The sessions of the connection go with it.
+    for recorder in recorders {
+        recorder.detach(tag.handle);
+    }
+}
+
+/// Calls the change callbacks of the connection's pool. It runs on the
+/// connection's thread while the statement is executing.
+unsafe extern "C" fn row_changed(
+    tag: *mut c_void,
+    operation: c_int,
+    _database: *const c_char,
+    table: *const c_char,
+    rowid: ffi::sqlite3_int64,
+) {
+    // SAFETY: the hook is installed with the tag of the open connection.
+    let pool_id = unsafe { &*tag.cast::<ConnectionTag>() }
+        .pool_id
+        .load(Ordering::Relaxed);
+    let callbacks = with_registry(|registry| {
+        registry
+            .pools
+            .get(&pool_id)
+            .map(|hooks| hooks.change_callbacks.clone())
+            .unwrap_or_default()
+    });
+    if callbacks.is_empty() {
+        return;
+    }
+    let operation = match operation {
+        ffi::SQLITE_INSERT => "insert",
+        ffi::SQLITE_UPDATE => "update",
+        ffi::SQLITE_DELETE => "delete",
+        _ => return,
+    };
+    // SAFETY: SQLite passes the name of the changed table.
+    let table = unsafe { CStr::from_ptr(table) }
+        .to_string_lossy()
+        .into_owned();
+    // A panic mustn't unwind into SQLite.
+    let called = catch_unwind(AssertUnwindSafe(|| {
+        Python::attach(|py| {
+            for callback in &callbacks {
+                if let Err(err) = callback.call1(py, (operation, &table, rowid)) {
+                    err.write_unraisable(py, Some(callback.bind(py)));
+                }
+            }
+        })
+    }));
+    if called.is_err() {
+        warn!("A change callback panicked");
+    }
+}
@@ -0,0 +1,173 @@
+use std::sync::{Mutex, MutexGuard};
+
+use log::{debug, info};
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyTuple};
+
+use crate::database::{
+    Backend, Database, WriteTransaction, begin_write, block_on, connect_pool, fetch_rows,
+};
+use crate::decode::RowBatch;
+use crate::error::FustOrmError;
+use crate::logging;
+
+/// A blocking counterpart of `Database` for code without an event loop,
+/// exposed as `fust_orm.sync.Database`.
+///
+/// Statements run on the same runtime as the async API, which the calling
+/// thread drives until they complete, without holding the GIL.
+#[pyclass(name = "Database", module = "fust_orm.sync")]
+pub struct SyncDatabase {
+    db: Database,
+}
+
+/// Renders `query` and fetches its rows as dicts on `executor`.
+fn execute_on<'c, E>(
+    py: Python,
+    db: &Database,
+    executor: E,
+    query: &Bound<PyAny>,
+) -> PyResult<Py<PyList>>
+where
+    E: sqlx::Executor<'c, Database = sqlx::Any> + Backend + Send,
+{
+    let (sql, params) = db.render(py, query)?;
+    let batch = block_on(py, async move {
+        RowBatch::decode(&fetch_rows(executor, &sql, params).await?)
+    })?;
+    Ok(PyList::new(py, batch.to_dicts(py)?)?.unbind())
+}
+
+#[pymethods]
+impl SyncDatabase {
+    /// Connects to the database at `db_url`, like `Database.connect()`.
+    #[staticmethod]
+    #[pyo3(signature = (db_url, *, echo = false, statement_cache_capacity = None, load_extensions = Vec::new()))]
+    fn connect(
+        py: Python,
+        db_url: String,
+        echo: bool,
+        statement_cache_capacity: Option<usize>,
+        load_extensions: Vec<String>,
+    ) -> PyResult<SyncDatabase> {
+        debug!("Connecting to the database at URL: {}", &db_url);
+        if echo {
+            logging::enable_echo(py)?;
+        }
+        let pool = block_on(
+            py,
+            connect_pool(&db_url, echo, statement_cache_capacity, &load_extensions),
+        )?;
+        info!("Connected to the database: {}", &db_url);
+        Ok(SyncDatabase {
+            db: Database::new(pool),
+        })
+    }
+
+    /// Executes a raw SQL string or a QueryBuilder and returns the rows as
+    /// dicts.
+    fn execute(&self, py: Python, query: &Bound<PyAny>) -> PyResult<Py<PyList>> {
+        execute_on(py, &self.db, &self.db.pool, query)
+    }
+
+    /// Returns a context manager running the statements executed on it in a
+    /// transaction, committed when the block exits normally and rolled back
+    /// when it raises.
+    fn transaction(&self) -> SyncTransaction {
+        SyncTransaction {
+            db: self.db.clone(),
+            tx: Mutex::new(None),
+        }
+    }
+
+    /// The async `Database` sharing this one's connections, for the APIs
+    /// that only exist as coroutines.
+    #[getter]
+    fn aio(&self) -> Database {
+        self.db.clone()
+    }
+}
+
+/// A transaction of a `fust_orm.sync.Database`, begun by entering it.
+#[pyclass(name = "Transaction", module = "fust_orm.sync")]
+pub struct SyncTransaction {
+    db: Database,
+    tx: Mutex<Option<WriteTransaction>>,
+}
+
+impl SyncTransaction {
+    fn tx(&self) -> MutexGuard<'_, Option<WriteTransaction>> {
+        self.tx.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[pymethods]
+impl SyncTransaction {
+    fn __enter__(slf: PyRef<Self>, py: Python) -> PyResult<Py<SyncTransaction>> {
+        let mut tx = slf.tx();
+        if tx.is_some() {
+            return Err(FustOrmError::InvalidQueryArgument(
+                "The transaction is already open.".to_string(),
+            )
+            .into());
+        }
+        let pool = slf.db.pool.clone();
+        *tx = Some(block_on(py, async move { begin_write(&pool).await })?);
+        drop(tx);
+        Ok(slf.into())
+    }
+
+    /// Commits the transaction, or rolls it back if the block raised.
+    #[pyo3(signature = (exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        py: Python,
+        exc_type: Option<&Bound<PyAny>>,
+        _exc_value: Option<&Bound<PyAny>>,
+        _traceback: Option<&Bound<PyAny>>,
+    ) -> PyResult<bool> {
+        let tx = self.tx().take();
+        let commit = exc_type.is_none();
+        block_on(py, async move {
+            match tx {
+                Some(tx) if commit => tx.commit().await.map_err(FustOrmError::from),
+                // Dropped in the runtime, which the connection is returned
+                // to the pool on.
+                _ => Ok(()),
+            }
+        })?;
+        Ok(false)
+    }
+
+    /// Executes a raw SQL string or a QueryBuilder in the transaction and
+    /// returns the rows as dicts.
+    fn execute(&self, py: Python, query: &Bound<PyAny>) -> PyResult<Py<PyList>> {
+        let mut tx = self.tx();
+        let tx = tx.as_mut().ok_or_else(|| {
+            FustOrmError::InvalidQueryArgument(
+                "The transaction isn't open; use it in a `with` block.".to_string(),
+            )
+        })?;
+        execute_on(py, &self.db, &mut **tx, query)
+    }
+
+    /// Executes several statements in the transaction, in order.
+    #[pyo3(signature = (*queries))]
+    fn execute_many(&self, py: Python, queries: &Bound<PyTuple>) -> PyResult<()> {
+        for query in queries.iter() {
+            self.execute(py, &query)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SyncTransaction {
+    fn drop(&mut self) {
+        // A transaction left open is rolled back; its connection goes back to
+        // the pool from a task of the runtime.
+        if let Some(tx) = self.tx().take() {
+            let _runtime = pyo3_async_runtimes::tokio::get_runtime().enter();
+            drop(tx);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::schema::Dialect;
+
+/// The OpenTelemetry tracer spans are started with, once `instrument()` was
+/// called.
+static TRACER: Mutex<Option<Arc<Py<PyAny>>>> = Mutex::new(None);
+
+/// Traces connections, statements and transactions with OpenTelemetry.
+///
+/// Spans are started with the tracer `fust_orm` of `tracer_provider` (the
+/// global provider by default), as children of the span current where the
+/// database was called.
+#[pyfunction]
+#[pyo3(signature = (tracer_provider = None))]
+pub(crate) fn instrument(py: Python, tracer_provider: Option<Py<PyAny>>) -> PyResult<()> {
+    let trace = py.import("opentelemetry.trace")?;
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("tracer_provider", tracer_provider)?;
+    let tracer = trace.call_method(
+        "get_tracer",
+        ("fust_orm", env!("CARGO_PKG_VERSION")),
+        Some(&kwargs),
+    )?;
+    let previous = TRACER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .replace(Arc::new(tracer.unbind()));
+    drop(previous);
+    Ok(())
+}
+
+/// Stops tracing.
+#[pyfunction]
+pub(crate) fn uninstrument() {
+    let previous = TRACER.lock().unwrap_or_else(|e| e.into_inner()).take();
+    drop(previous);
+}
+
+/// The `db.system` attribute of a dialect.
+fn system(dialect: Dialect) -> &'static str {
+    match dialect {
+        Dialect::Sqlite => "sqlite",
+        Dialect::Postgres => "postgresql",
+        Dialect::Mysql => "mysql",
+    }
+}
+
+/// A client span, or nothing when tracing is off. It ends when dropped.
+pub(crate) struct Span(Option<Py<PyAny>>);
+
+impl Span {
+    /// No span, for work traced by an enclosing one.
+    pub(crate) fn none() -> Span {
+        Span(None)
+    }
+
+    /// Starts a span named `name`, with the `db.statement` attribute for a
+    /// statement.
+    pub(crate) fn start(name: &str, dialect: Dialect, statement: Option<&str>) -> Span {
+        let Some(tracer) = TRACER.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+            return Span(None);
+        };
+        Python::attach(|py| {
+            let span = Self::start_with(py, &tracer, name, dialect, statement);
+            Span(
+                span.inspect_err(|e| debug!("Couldn't start a span: {}", e))
+                    .ok(),
+            )
+        })
+    }
+
+    /// Starts a span for a statement, named after its operation.
+    pub(crate) fn statement(sql: &str, dialect: Dialect) -> Span {
+        let operation = sql
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_uppercase();
+        Span::start(&operation, dialect, Some(sql))
+    }
+
+    fn start_with(
+        py: Python,
+        tracer: &Py<PyAny>,
+        name: &str,
+        dialect: Dialect,
+        statement: Option<&str>,
+    ) -> PyResult<Py<PyAny>> {
+        let attributes = PyDict::new(py);
+        attributes.set_item("db.system", system(dialect))?;
+        if let Some(statement) = statement {
+            attributes.set_item("db.statement", statement)?;
+        }
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("attributes", attributes)?;
+        kwargs.set_item(
+            "kind",
+            py.import("opentelemetry.trace")?
+                .getattr("SpanKind")?
+                .getattr("CLIENT")?,
+        )?;
+        // The statement runs on a runtime thread; the span's parent is the
+        // one current in the task that called the database.
+        if let Ok(locals) = pyo3_async_runtimes::tokio::get_current_locals(py) {
+            let get_current = py.import("opentelemetry.context")?.getattr("get_current")?;
+            let parent = locals.context(py).call_method1("run", (get_current,))?;
+            kwargs.set_item("context", parent)?;
+        }
+        Ok(tracer
+            .bind(py)
+            .call_method("start_span", (name,), Some(&kwargs))?
+            .unbind())
+    }
+
+    pub(crate) fn set_attribute(&self, key: &str, value: u64) {
+        if let Some(span) = &self.0 {
+            Python::attach(|py| {
+                if let Err(e) = span.call_method1(py, "set_attribute", (key, value)) {
+                    debug!("Couldn't set the span attribute '{}': {}", key, e);
+                }
+            });
+        }
+    }
+
+    /// Marks the span as failed with `description`.
+    pub(crate) fn fail(&self, description: &str) {
+        if let Some(span) = &self.0 {
+            Python::attach(|py| -> PyResult<()> {
+                let trace = py.import("opentelemetry.trace")?;
+                let error = trace.getattr("StatusCode")?.getattr("ERROR")?;
+                let status = trace.getattr("Status")?.call1((error, description))?;
+                span.call_method1(py, "set_status", (status,))?;
+                Ok(())
+            })
+            .unwrap_or_else(|e| debug!("Couldn't set the span status: {}", e));
+        }
+    }
+
+    /// Marks the span as failed if `result` is an error.
+    pub(crate) fn record_result<T, E: std::fmt::Display>(&self, result: &Result<T, E>) {
+        if let Err(e) = result {
+            self.fail(&e.to_string());
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(span) = self.0.take() {
+            Python::attach(|py| {
+                if let Err(e) = span.call_method0(py, "end") {
+                    debug!("Couldn't end a span: {}", e);
+                }
+            });
+        }
+    }
+}
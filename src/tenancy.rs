@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use log::info;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::AnyPool;
+use sqlx::any::AnyConnectOptions;
+
+use crate::column_field::ColumnField;
+use crate::database::{Database, PoolSettings, connect_pool_with, pool_identity, scheme_dialect};
+use crate::dry_run;
+use crate::error::FustOrmError;
+use crate::expression::Expr;
+use crate::model::{ModelInfo, column_param};
+use crate::pool_registry::PoolRegistry;
+use crate::result_limits;
+use crate::retry;
+use crate::schema::Dialect;
+use crate::testing::{sqlite_file, worker_name};
+use crate::where_condition::WhereCondition;
+
+/// The tenant the queries of a database returned by
+/// `for_tenant(..., strategy="filter")` are restricted to.
+pub(crate) struct TenantFilter {
+    /// The column of the models holding the tenant of their rows.
+    pub(crate) column: String,
+    pub(crate) tenant: String,
+}
+
+impl TenantFilter {
+    /// The condition keeping the rows of the tenant.
+    pub(crate) fn condition(&self, py: Python) -> WhereCondition {
+        WhereCondition::from_expression(
+            Expr::Column(self.column.clone()),
+            "=",
+            PyString::new(py, &self.tenant).into_any().unbind(),
+        )
+    }
+
+    /// The tenant column of the model `info`, if it has one, with the
+    /// parameter comparing it to the tenant.
+    pub(crate) fn column_of<'a>(
+        &self,
+        py: Python,
+        info: &'a ModelInfo,
+    ) -> PyResult<Option<(&'a ColumnField, String)>> {
+        let Some(column) = info.columns.iter().find(|c| c.column_name == self.column) else {
+            return Ok(None);
+        };
+        let param = column_param(py, PyString::new(py, &self.tenant).as_any(), column)?;
+        Ok(param.map(|param| (column, param)))
+    }
+}
+
+/// The pools connected for tenants, by the connect options of the pool they
+/// were derived from and their URL, reused by later `for_tenant()` calls.
+static TENANT_POOLS: PoolRegistry<(String, AnyPool)> = PoolRegistry::new();
+
+fn tenant_pool(base: &AnyPool, url: &str) -> Option<AnyPool> {
+    TENANT_POOLS
+        .find(base, |(tenant_url, pool)| {
+            tenant_url == url && !pool.is_closed()
+        })
+        .map(|(_, pool)| pool)
+}
+
+/// How `for_tenant()` separates the data of tenants.
+enum Strategy {
+    /// A PostgreSQL schema per tenant, set as the `search_path`.
+    Schema,
+    /// A database per tenant: a SQLite file next to the database's, or a
+    /// database named after the database's on a server.
+    Database,
+    /// One database, with the tenant of each row in `column`.
+    Filter,
+}
+
+/// The URL of the database of `tenant` under `strategy`.
+fn tenant_url(
+    options: &AnyConnectOptions,
+    strategy: &Strategy,
+    tenant: &str,
+) -> Result<String, FustOrmError> {
+    let url = &options.database_url;
+    let dialect = scheme_dialect(url.scheme());
+    match (strategy, dialect) {
+        (Strategy::Schema, Dialect::Postgres) => {
+            // `search_path` is set by every connection as it starts.
+            let mut url = url.clone();
+            let mut pairs: Vec<(String, String)> = url
+                .query_pairs()
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+            let setting = format!("-c search_path={}", tenant);
+            match pairs.iter_mut().find(|(key, _)| key == "options") {
+                Some((_, value)) => *value = format!("{} {}", value, setting),
+                None => pairs.push(("options".to_string(), setting)),
+            }
+            url.query_pairs_mut().clear().extend_pairs(pairs);
+            Ok(url.to_string())
+        }
+        (Strategy::Schema, _) => Err(FustOrmError::InvalidQueryArgument(
+            "The 'schema' tenant strategy is only supported on PostgreSQL.".to_string(),
+        )),
+        (Strategy::Database, Dialect::Sqlite) => {
+            let Some((path, query)) = sqlite_file(url.as_str()) else {
+                return Err(FustOrmError::InvalidQueryArgument(
+                    "An in-memory SQLite database has no file to put tenants next to.".to_string(),
+                ));
+            };
+            let file = worker_name(path, tenant);
+            Ok(match query {
+                "" => format!("sqlite://{}", file),
+                query => format!("sqlite://{}?{}", file, query),
+            })
+        }
+        (Strategy::Database, _) => {
+            let name = url.path().trim_start_matches('/');
+            if name.is_empty() {
+                return Err(FustOrmError::InvalidQueryArgument(
+                    "The URL of the database doesn't name a database.".to_string(),
+                ));
+            }
+            let mut url = url.clone();
+            url.set_path(&format!("/{}_{}", name, tenant));
+            Ok(url.to_string())
+        }
+        (Strategy::Filter, _) => unreachable!("a filter doesn't connect"),
+    }
+}
+
+/// Returns the database of `tenant` for `Database.for_tenant()`.
+pub(crate) fn for_tenant<'py>(
+    py: Python<'py>,
+    db: &Database,
+    tenant: String,
+    strategy: Option<&Bound<'py, PyAny>>,
+    column: String,
+) -> PyResult<Bound<'py, PyAny>> {
+    if tenant.is_empty()
+        || !tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "Invalid tenant '{}': expected letters, digits and '_'.",
+            tenant
+        ))
+        .into());
+    }
+    let strategy = match strategy {
+        // A strategy of the application's own.
+        Some(strategy) if strategy.is_callable() => {
+            let tenant_db = strategy.call1((db.clone(), tenant))?;
+            return match tenant_db.extract::<Database>() {
+                Ok(tenant_db) => future_into_py(py, async move { Ok(tenant_db) }),
+                Err(_) => Ok(tenant_db),
+            };
+        }
+        Some(strategy) => match strategy.extract::<String>().as_deref() {
+            Ok("schema") => Strategy::Schema,
+            Ok("database") => Strategy::Database,
+            Ok("filter") => Strategy::Filter,
+            _ => {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "Unknown tenant strategy {}: expected 'schema', 'database', 'filter' or a \
+                     callable.",
+                    strategy.repr()?
+                ))
+                .into());
+            }
+        },
+        None => match db.dialect() {
+            Dialect::Postgres => Strategy::Schema,
+            Dialect::Sqlite | Dialect::Mysql => Strategy::Database,
+        },
+    };
+    if let Strategy::Filter = strategy {
+        let tenant_db = Database {
+            tenant: Some(Arc::new(TenantFilter { column, tenant })),
+            ..db.clone()
+        };
+        return future_into_py(py, async move { Ok(tenant_db) });
+    }
+    if db.tx.is_some() {
+        return Err(FustOrmError::InvalidQueryArgument(
+            "A database in a transaction can't switch to the database of a tenant.".to_string(),
+        )
+        .into());
+    }
+    let base = db.pool.clone();
+    let options = pool_identity(&base);
+    let url = tenant_url(&base.connect_options(), &strategy, &tenant)?;
+    future_into_py(py, async move {
+        if let Some(pool) = tenant_pool(&base, &url) {
+            return Ok(Database::new(pool));
+        }
+        let echo = options.log_settings.statements_level != log::LevelFilter::Off;
+        let pool = connect_pool_with(&url, echo, None, &[], PoolSettings::of(&base)).await?;
+        result_limits::set(&pool, result_limits::of(&base));
+        if dry_run::is_set(&base) {
+            dry_run::set(&pool);
+        }
+        if let Some(policy) = retry::of(&base) {
+            retry::set(&pool, policy);
+        }
+        // Another call may have connected meanwhile.
+        TENANT_POOLS.retain(|(_, pool)| !pool.is_closed());
+        let existing = TENANT_POOLS.find_or_insert(
+            &base,
+            |(tenant_url, _)| *tenant_url == url,
+            (url.clone(), pool.clone()),
+        );
+        let pool = match existing {
+            Some((_, existing)) => {
+                pool.close().await;
+                existing
+            }
+            None => {
+                info!("Connected to the database of tenant '{}'", tenant);
+                pool
+            }
+        };
+        Ok(Database::new(pool))
+    })
+}
@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use log::info;
+use pyo3::prelude::*;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::any::AnyConnectOptions;
+
+use crate::autogen::{self, DesiredSchema};
+use crate::database::{Database, connect_pool, execute_statement, scheme_dialect};
+use crate::error::FustOrmError;
+use crate::fixtures::{insert_fixtures, read_fixtures};
+use crate::migrations::{Migration, apply_pending, load_migrations};
+use crate::schema::Dialect;
+
+/// Numbers the test databases, whose names must differ within the process.
+static NEXT_DATABASE: AtomicU64 = AtomicU64::new(1);
+
+/// How the schema of a test database is created.
+enum Schema {
+    /// By applying the migrations of a directory.
+    Migrations(Vec<Migration>),
+    /// By creating the tables, indexes and views of models.
+    Models(DesiredSchema),
+}
+
+/// Connects to a new in-memory SQLite database with the schema of `schema`, a
+/// migrations directory or an iterable of Model classes, and the rows of the
+/// fixtures at `fixtures`, for `Database.create_test()`.
+pub(crate) fn create_test<'py>(
+    py: Python<'py>,
+    schema: &Bound<'py, PyAny>,
+    fixtures: Option<PathBuf>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let schema = match schema.extract::<PathBuf>() {
+        Ok(dir) => Schema::Migrations(load_migrations(py, &dir.to_string_lossy())?),
+        Err(_) => {
+            let models = autogen::models_to_compare(py, Some(schema))?;
+            Schema::Models(DesiredSchema::from_models(py, &models, Dialect::Sqlite)?)
+        }
+    };
+    let fixtures = fixtures
+        .map(|path| read_fixtures(py, Path::new(&path)))
+        .transpose()?;
+    future_into_py(py, async move {
+        // The Any driver parses the URL again for each connection, so
+        // `sqlite::memory:` would give each one a database of its own.
+        let url = format!(
+            "sqlite:file:fust-test-{}?mode=memory&cache=shared",
+            NEXT_DATABASE.fetch_add(1, Ordering::Relaxed)
+        );
+        let pool = connect_pool(&url, false, None, &[]).await?;
+        match &schema {
+            Schema::Migrations(migrations) => {
+                apply_pending(&pool, migrations).await?;
+            }
+            Schema::Models(schema) => {
+                let plan = autogen::plan(&pool, Dialect::Sqlite, schema).await?;
+                for sql in &plan.up {
+                    execute_statement(&pool, sql, Vec::new()).await?;
+                }
+            }
+        }
+        if let Some(tables) = fixtures {
+            insert_fixtures(&pool, Dialect::Sqlite, tables).await?;
+        }
+        info!("Created a test database");
+        Ok(Database::new(pool))
+    })
+}
+
+/// Splits a SQLite URL into the path of its database file and its query
+/// string, `None` for in-memory databases.
+pub(crate) fn sqlite_file(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("sqlite:")?;
+    let rest = rest.strip_prefix("//").unwrap_or(rest);
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let in_memory = matches!(path, "" | ":memory:" | "memory:" | "memory")
+        || query.split('&').any(|pair| pair == "mode=memory");
+    (!in_memory).then_some((path, query))
+}
+
+/// The name of the copy of the database `name` for `worker_id`: the worker
+/// id is appended to the name, before the extension of a file.
+pub(crate) fn worker_name(name: &str, worker_id: &str) -> String {
+    let path = Path::new(name);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!(
+                "{}_{}.{}",
+                stem.to_string_lossy(),
+                worker_id,
+                extension.to_string_lossy()
+            ))
+            .to_string_lossy()
+            .into_owned(),
+        _ => format!("{}_{}", name, worker_id),
+    }
+}
+
+/// Copies the template database at `template` for the test worker
+/// `worker_id`, replacing an earlier copy, and connects to the copy, for
+/// `Database.create_test_worker()`.
+pub(crate) fn create_test_worker(
+    py: Python,
+    template: String,
+    worker_id: String,
+) -> PyResult<Bound<PyAny>> {
+    if worker_id.is_empty()
+        || !worker_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "Invalid worker id '{}': expected letters, digits, '_' and '-'.",
+            worker_id
+        ))
+        .into());
+    }
+    future_into_py(py, async move {
+        let options = AnyConnectOptions::from_str(&template).map_err(FustOrmError::connection)?;
+        let url = match scheme_dialect(options.database_url.scheme()) {
+            Dialect::Sqlite => {
+                let Some((path, query)) = sqlite_file(&template) else {
+                    return Err(FustOrmError::InvalidQueryArgument(
+                        "An in-memory SQLite database can't be a template.".to_string(),
+                    )
+                    .into());
+                };
+                let copy = worker_name(path, &worker_id);
+                // A leftover journal of an earlier copy would be replayed
+                // into the new one.
+                for suffix in ["-wal", "-shm", "-journal"] {
+                    let _ = tokio::fs::remove_file(format!("{}{}", copy, suffix)).await;
+                }
+                tokio::fs::copy(path, &copy).await.map_err(|e| {
+                    FustOrmError::ConnectionError(format!(
+                        "Can't copy the template database '{}': {}",
+                        path, e
+                    ))
+                })?;
+                match query {
+                    "" => format!("sqlite://{}", copy),
+                    query => format!("sqlite://{}?{}", copy, query),
+                }
+            }
+            Dialect::Postgres => {
+                let template_name = options.database_url.path().trim_start_matches('/');
+                if template_name.is_empty() {
+                    return Err(FustOrmError::InvalidQueryArgument(
+                        "The template URL doesn't name a database.".to_string(),
+                    )
+                    .into());
+                }
+                let copy = format!("{}_{}", template_name, worker_id);
+                // PostgreSQL copies a template nobody is connected to, so the
+                // copy is made from the maintenance database.
+                let mut maintenance = options.database_url.clone();
+                maintenance.set_path("/postgres");
+                let pool = connect_pool(maintenance.as_str(), false, None, &[]).await?;
+                let quote = |name: &str| format!("\"{}\"", name.replace('"', "\"\""));
+                execute_statement(
+                    &pool,
+                    &format!("DROP DATABASE IF EXISTS {}", quote(&copy)),
+                    Vec::new(),
+                )
+                .await?;
+                execute_statement(
+                    &pool,
+                    &format!(
+                        "CREATE DATABASE {} TEMPLATE {}",
+                        quote(&copy),
+                        quote(template_name)
+                    ),
+                    Vec::new(),
+                )
+                .await?;
+                pool.close().await;
+                let mut url = options.database_url.clone();
+                url.set_path(&format!("/{}", copy));
+                url.to_string()
+            }
+            Dialect::Mysql => {
+                return Err(FustOrmError::InvalidQueryArgument(
+                    "create_test_worker() isn't supported on MySQL, which has no template \
+                     databases."
+                        .to_string(),
+                )
+                .into());
+            }
+        };
+        let pool = connect_pool(&url, false, None, &[]).await?;
+        info!("Created the test database of worker '{}'", worker_id);
+        Ok(Database::new(pool))
+    })
+}
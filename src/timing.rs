@@ -0,0 +1,196 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, warn};
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+
+use crate::error::FustOrmError;
+use crate::logging::SQL_TARGET;
+use crate::query_recorder;
+
+/// How many parameters a slow query report includes, and how many characters
+/// of each.
+const SUMMARY_PARAMS: usize = 20;
+const SUMMARY_LENGTH: usize = 64;
+
+/// What happens to statements running for longer than `threshold`.
+struct SlowQueryHook {
+    threshold: Duration,
+    /// Called as `callback(sql, params, elapsed_ms, error)`; without one, the
+    /// statement is logged as a warning.
+    callback: Option<Arc<Py<PyAny>>>,
+}
+
+static SLOW_QUERY: Mutex<Option<SlowQueryHook>> = Mutex::new(None);
+
+/// Reports statements taking at least `threshold_ms` milliseconds to run.
+///
+/// `callback` is called as `callback(sql, params, elapsed_ms, error)`, with a
+/// summary of the parameters and the message of the error the statement
+/// failed with, if it did (e.g. a timeout); without one, the statement is
+/// logged to the `fust_orm.sql` logger as a warning. `on_slow_query(None)`
+/// stops reporting.
+#[pyfunction]
+#[pyo3(signature = (threshold_ms, callback = None))]
+pub(crate) fn on_slow_query(
+    threshold_ms: Option<f64>,
+    callback: Option<Py<PyAny>>,
+) -> PyResult<()> {
+    let hook = match threshold_ms {
+        Some(ms) if !ms.is_finite() || ms < 0.0 => {
+            return Err(FustOrmError::InvalidQueryArgument(format!(
+                "The slow query threshold must be zero or more milliseconds, got {}.",
+                ms
+            ))
+            .into());
+        }
+        Some(ms) => Some(SlowQueryHook {
+            threshold: Duration::from_secs_f64(ms / 1000.0),
+            callback: callback.map(Arc::new),
+        }),
+        None => None,
+    };
+    // The previous callback is released after the lock.
+    let previous = std::mem::replace(
+        &mut *SLOW_QUERY.lock().unwrap_or_else(|e| e.into_inner()),
+        hook,
+    );
+    drop(previous);
+    Ok(())
+}
+
+/// The statement lists of the `capture_queries()` blocks entered, which the
+/// statements run are appended to.
+static CAPTURES: Mutex<Vec<Arc<Py<PyList>>>> = Mutex::new(Vec::new());
+
+/// Records the SQL of the statements run on any database while it's entered,
+/// in `statements`:
+///
+///     with capture_queries() as queries:
+///         await User.query().all(db)
+///     assert len(queries) == 1
+#[pyfunction]
+pub(crate) fn capture_queries(py: Python) -> CapturedQueries {
+    CapturedQueries {
+        statements: Arc::new(PyList::empty(py).unbind()),
+    }
+}
+
+/// The context manager returned by `capture_queries()`.
+#[pyclass(module = "fust_orm")]
+pub struct CapturedQueries {
+    statements: Arc<Py<PyList>>,
+}
+
+#[pymethods]
+impl CapturedQueries {
+    /// The statements run so far, in order.
+    #[getter]
+    fn statements(&self, py: Python) -> Py<PyList> {
+        self.statements.clone_ref(py)
+    }
+
+    fn __len__(&self, py: Python) -> usize {
+        self.statements.bind(py).len()
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        CAPTURES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(slf.statements.clone());
+        slf
+    }
+
+    #[pyo3(signature = (_exc_type, _exc_value, _traceback))]
+    fn __exit__(
+        &self,
+        _exc_type: Option<&Bound<PyAny>>,
+        _exc_value: Option<&Bound<PyAny>>,
+        _traceback: Option<&Bound<PyAny>>,
+    ) -> bool {
+        CAPTURES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|statements| !Arc::ptr_eq(statements, &self.statements));
+        false
+    }
+}
+
+/// The parameters of a statement as reported: the first ones, each shortened.
+pub(crate) fn summarize(params: &[String]) -> Vec<String> {
+    let mut summary: Vec<String> = params
+        .iter()
+        .take(SUMMARY_PARAMS)
+        .map(|param| match param.char_indices().nth(SUMMARY_LENGTH) {
+            Some((end, _)) => format!("{}...", &param[..end]),
+            None => param.clone(),
+        })
+        .collect();
+    if params.len() > SUMMARY_PARAMS {
+        summary.push(format!("... ({} more)", params.len() - SUMMARY_PARAMS));
+    }
+    summary
+}
+
+/// Records how long a statement took, reporting it if it was slow, including
+/// when it failed with `error`: a statement cut short by a timeout is as
+/// slow as they come.
+pub(crate) fn record(
+    sql: &str,
+    params: &[String],
+    elapsed: Duration,
+    error: Option<&FustOrmError>,
+) {
+    let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+    debug!("Statement took {:.3} ms", elapsed_ms);
+    if error.is_none() {
+        record_run(sql, params, elapsed);
+    }
+    let callback = match SLOW_QUERY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+    {
+        Some(hook) if elapsed >= hook.threshold => hook.callback.clone(),
+        _ => return,
+    };
+    let params = summarize(params);
+    let error = error.map(ToString::to_string);
+    match (callback, error) {
+        (Some(callback), error) => Python::attach(|py| {
+            // The statement has run; an error in the callback can't fail it.
+            if let Err(e) = callback.call1(py, (sql, params, elapsed_ms, error)) {
+                e.write_unraisable(py, Some(callback.bind(py)));
+            }
+        }),
+        (None, None) => warn!(
+            target: SQL_TARGET,
+            "Slow query ({:.1} ms): {}; parameters: {:?}", elapsed_ms, sql, params
+        ),
+        (None, Some(error)) => warn!(
+            target: SQL_TARGET,
+            "Slow query ({:.1} ms), failed with {}: {}; parameters: {:?}",
+            elapsed_ms,
+            error,
+            sql,
+            params
+        ),
+    }
+}
+
+/// Appends a statement that ran to the `capture_queries()` blocks entered
+/// and the query recorders.
+fn record_run(sql: &str, params: &[String], elapsed: Duration) {
+    let captures = CAPTURES.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    if !captures.is_empty() {
+        Python::attach(|py| {
+            for statements in captures {
+                // Appending a string to a list can't fail.
+                let _ = statements.bind(py).append(sql);
+            }
+        });
+    }
+    query_recorder::record(sql, params, elapsed);
+}
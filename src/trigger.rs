@@ -0,0 +1,319 @@
+use crate::database::{Database, begin_write};
+use crate::error::FustOrmError;
+use crate::introspect;
+use crate::schema::{Dialect, table_name};
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+use pyo3_async_runtimes::tokio::future_into_py;
+use sqlx::Executor;
+
+fn map_db_err(e: sqlx::Error) -> FustOrmError {
+    FustOrmError::from(e)
+}
+
+/// A row-level trigger, run `timing` (`"before"`, `"after"` or `"instead
+/// of"`) each `event` (`"insert"`, `"update"` or `"delete"`) on a table:
+///
+/// ```python
+/// audit = Trigger(
+///     "after", "update",
+///     "INSERT INTO audit_log (user_id, changed_at) VALUES (NEW.id, CURRENT_TIMESTAMP);",
+/// )
+/// ```
+///
+/// `body` holds the statements to run, which see the row as `NEW` and `OLD`,
+/// and `when` an optional condition on them. PostgreSQL runs them in a
+/// PL/pgSQL function named after the trigger (`<name>_fn`).
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    /// `None` names the trigger after its table, timing and event, e.g.
+    /// `users_after_update_trg`.
+    #[pyo3(get)]
+    pub(crate) name: Option<String>,
+    /// `BEFORE`, `AFTER` or `INSTEAD OF`.
+    #[pyo3(get)]
+    pub(crate) timing: String,
+    /// `INSERT`, `UPDATE` or `DELETE`.
+    #[pyo3(get)]
+    pub(crate) event: String,
+    /// The statements to run, each ending with `;`.
+    #[pyo3(get)]
+    pub(crate) body: String,
+    #[pyo3(get)]
+    pub(crate) when: Option<String>,
+}
+
+impl Trigger {
+    /// The name of the trigger on `table`.
+    pub(crate) fn name_on(&self, table: &str) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            format!(
+                "{}_{}_{}_trg",
+                table,
+                self.timing.to_lowercase().replace(' ', "_"),
+                self.event.to_lowercase()
+            )
+        })
+    }
+
+    /// The body, guarded by `when` where the dialect has no `WHEN` clause.
+    fn guarded_body(&self) -> String {
+        match &self.when {
+            Some(when) => format!("IF {} THEN {} END IF;", when, self.body),
+            None => self.body.clone(),
+        }
+    }
+
+    /// The source of the PostgreSQL trigger function. A `BEFORE` or `INSTEAD
+    /// OF` trigger returns the row to go on with; the result of an `AFTER`
+    /// trigger is ignored.
+    fn function_body(&self) -> String {
+        let result = match (self.timing.as_str(), self.event.as_str()) {
+            ("AFTER", _) => "NULL",
+            (_, "DELETE") => "OLD",
+            _ => "NEW",
+        };
+        format!("BEGIN {} RETURN {}; END", self.guarded_body(), result)
+    }
+
+    /// What `introspect::triggers` reports as the definition of this trigger
+    /// once created on `table`.
+    pub(crate) fn definition(&self, table: &str, dialect: Dialect) -> PyResult<String> {
+        Ok(match dialect {
+            Dialect::Postgres => format!("{} {} {}", self.timing, self.event, self.function_body()),
+            _ => self.create_sql(table, dialect)?.join(";\n"),
+        })
+    }
+
+    /// Renders the statements creating the trigger on `table`.
+    pub(crate) fn create_sql(&self, table: &str, dialect: Dialect) -> PyResult<Vec<String>> {
+        let name = self.name_on(table);
+        Ok(match dialect {
+            Dialect::Sqlite => vec![format!(
+                "CREATE TRIGGER {} {} {} ON {} FOR EACH ROW{} BEGIN {} END",
+                name,
+                self.timing,
+                self.event,
+                table,
+                self.when
+                    .as_ref()
+                    .map(|when| format!(" WHEN {}", when))
+                    .unwrap_or_default(),
+                self.body
+            )],
+            Dialect::Postgres => vec![
+                format!(
+                    "CREATE OR REPLACE FUNCTION {}_fn() RETURNS trigger LANGUAGE plpgsql AS $${}$$",
+                    name,
+                    self.function_body()
+                ),
+                format!(
+                    "CREATE TRIGGER {} {} {} ON {} FOR EACH ROW EXECUTE FUNCTION {}_fn()",
+                    name, self.timing, self.event, table, name
+                ),
+            ],
+            Dialect::Mysql if self.timing == "INSTEAD OF" => {
+                return Err(FustOrmError::BuildError(
+                    "MySQL doesn't support INSTEAD OF triggers.".to_string(),
+                )
+                .into());
+            }
+            Dialect::Mysql => vec![format!(
+                "CREATE TRIGGER {} {} {} ON {} FOR EACH ROW BEGIN {} END",
+                name,
+                self.timing,
+                self.event,
+                table,
+                self.guarded_body()
+            )],
+        })
+    }
+}
+
+/// Renders the statements dropping the trigger `name` of `table`, and on
+/// PostgreSQL its function `function`.
+pub(crate) fn drop_trigger_sql(
+    dialect: Dialect,
+    name: &str,
+    table: &str,
+    function: Option<&str>,
+) -> Vec<String> {
+    match dialect {
+        Dialect::Postgres => {
+            let mut statements = vec![format!("DROP TRIGGER {} ON {}", name, table)];
+            if let Some(function) = function {
+                statements.push(format!("DROP FUNCTION {}()", function));
+            }
+            statements
+        }
+        _ => vec![format!("DROP TRIGGER {}", name)],
+    }
+}
+
+#[pymethods]
+impl Trigger {
+    #[new]
+    #[pyo3(signature = (timing, event, body, *, name = None, when = None))]
+    pub(crate) fn new(
+        timing: &str,
+        event: &str,
+        body: &str,
+        name: Option<String>,
+        when: Option<String>,
+    ) -> PyResult<Self> {
+        let timing = timing.split_whitespace().collect::<Vec<_>>().join(" ");
+        let timing = timing.to_uppercase();
+        if !matches!(timing.as_str(), "BEFORE" | "AFTER" | "INSTEAD OF") {
+            return Err(FustOrmError::BuildError(format!(
+                "Unknown trigger timing '{}'; use 'before', 'after' or 'instead of'.",
+                timing.to_lowercase()
+            ))
+            .into());
+        }
+        let event = event.trim().to_uppercase();
+        if !matches!(event.as_str(), "INSERT" | "UPDATE" | "DELETE") {
+            return Err(FustOrmError::BuildError(format!(
+                "Unknown trigger event '{}'; use 'insert', 'update' or 'delete'.",
+                event.to_lowercase()
+            ))
+            .into());
+        }
+        let mut body = body.trim().to_string();
+        if body.is_empty() {
+            return Err(FustOrmError::BuildError("A trigger needs a body.".to_string()).into());
+        }
+        if !body.ends_with(';') {
+            body.push(';');
+        }
+        Ok(Trigger {
+            name,
+            timing,
+            event,
+            body,
+            when: when.map(|w| w.trim().to_string()),
+        })
+    }
+
+    /// Renders the statements creating the trigger on `table` (a name, a
+    /// `Table` or a Model class) for `dialect`.
+    #[pyo3(signature = (table, dialect = "sqlite"))]
+    fn to_sql(&self, table: &Bound<PyAny>, dialect: &str) -> PyResult<Vec<String>> {
+        self.create_sql(&table_name(table)?, Dialect::parse(dialect)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Trigger {}{} {}>",
+            self.name
+                .as_ref()
+                .map(|name| format!("{} ", name))
+                .unwrap_or_default(),
+            self.timing,
+            self.event
+        )
+    }
+}
+
+/// Creates `trigger` on `table` and returns its name. The statements run
+/// unprepared, as MySQL can't prepare `CREATE TRIGGER`.
+pub(crate) fn create_trigger<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<PyAny>,
+    trigger: Trigger,
+    if_not_exists: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table_name(table)?;
+    let dialect = db.dialect();
+    let name = trigger.name_on(&table);
+    let statements = trigger.create_sql(&table, dialect)?;
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        if if_not_exists {
+            let existing = introspect::triggers(&pool, dialect, &table).await?;
+            if existing.iter().any(|t| t.name == name) {
+                return Ok(name);
+            }
+        }
+        let mut tx = begin_write(&pool).await?;
+        for sql in &statements {
+            debug!("Creating trigger: {}", sql);
+            (&mut *tx).execute(sql.as_str()).await.map_err(map_db_err)?;
+        }
+        tx.commit().await.map_err(map_db_err)?;
+        Ok(name)
+    })
+}
+
+/// Drops the trigger `name`, with its function on PostgreSQL, which also
+/// needs the trigger's `table`.
+pub(crate) fn drop_trigger<'py>(
+    py: Python<'py>,
+    db: &Database,
+    name: String,
+    table: Option<&Bound<PyAny>>,
+    if_exists: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table.map(table_name).transpose()?;
+    let dialect = db.dialect();
+    let statements = match (dialect, &table) {
+        (Dialect::Postgres, Some(table)) => vec![
+            format!(
+                "DROP TRIGGER {}{} ON {}",
+                if if_exists { "IF EXISTS " } else { "" },
+                name,
+                table
+            ),
+            format!(
+                "DROP FUNCTION {}{}_fn()",
+                if if_exists { "IF EXISTS " } else { "" },
+                name
+            ),
+        ],
+        (Dialect::Postgres, None) => {
+            return Err(FustOrmError::BuildError(format!(
+                "Dropping the trigger '{}' on PostgreSQL needs its table.",
+                name
+            ))
+            .into());
+        }
+        _ => vec![format!(
+            "DROP TRIGGER {}{}",
+            if if_exists { "IF EXISTS " } else { "" },
+            name
+        )],
+    };
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let mut tx = begin_write(&pool).await?;
+        for sql in &statements {
+            debug!("Dropping trigger: {}", sql);
+            (&mut *tx).execute(sql.as_str()).await.map_err(map_db_err)?;
+        }
+        tx.commit().await.map_err(map_db_err)?;
+        Ok(())
+    })
+}
+
+/// Lists the triggers of `table` as dictionaries.
+pub(crate) fn list_triggers<'py>(
+    py: Python<'py>,
+    db: &Database,
+    table: &Bound<PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let table = table_name(table)?;
+    let dialect = db.dialect();
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        let triggers = introspect::triggers(&pool, dialect, &table).await?;
+        Python::attach(|py| -> PyResult<Py<PyList>> {
+            let list = PyList::empty(py);
+            for trigger in &triggers {
+                list.append(trigger.to_dict(py)?)?;
+            }
+            Ok(list.into())
+        })
+    })
+}
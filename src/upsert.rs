@@ -0,0 +1,322 @@
+use crate::audit;
+use crate::column_field::ColumnField;
+use crate::context;
+use crate::database::{Database, bind_params, execute_statement, fetch_rows, row_to_dict};
+use crate::error::FustOrmError;
+use crate::model::{ModelInfo, column_param, discriminator_filter, hydrate};
+use crate::rewrite;
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyString, PyType};
+use pyo3_async_runtimes::tokio::future_into_py;
+
+/// How often a lookup is retried after the insert lost a race against a
+/// concurrent insert of the same row (a unique constraint violation).
+const CONFLICT_RETRIES: usize = 1;
+
+/// Resolves keyword arguments to `(column, value)` pairs of the model.
+fn column_values<'a, 'py>(
+    info: &'a ModelInfo,
+    values: Option<&Bound<'py, PyDict>>,
+    argument: &str,
+) -> PyResult<Vec<(&'a ColumnField, Bound<'py, PyAny>)>> {
+    let Some(values) = values else {
+        return Ok(Vec::new());
+    };
+    values
+        .iter()
+        .map(|(key, value)| {
+            let name = key.extract::<String>()?;
+            let column = info
+                .columns
+                .iter()
+                .find(|c| c.column_name == name)
+                .ok_or_else(|| {
+                    FustOrmError::InvalidQueryArgument(format!(
+                        "'{}' in {} is not a column of '{}'.",
+                        name, argument, info.table_name
+                    ))
+                })?;
+            if column.version {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "The version column '{}' is managed automatically and can't be passed in {}.",
+                    name, argument
+                ))
+                .into());
+            }
+            if column.generated.is_some() {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "The generated column '{}' is computed by the database and can't be passed in {}.",
+                    name, argument
+                ))
+                .into());
+            }
+            Ok((column, value))
+        })
+        .collect()
+}
+
+/// Looks up the row matching `lookup`, inserting it from `lookup` and `defaults`
+/// if there is none. With `update`, an existing row is updated with `defaults`.
+///
+/// Lookup, insert and update run in one transaction. If the insert violates a
+/// unique constraint because a concurrent caller inserted the row first, the
+/// transaction is retried, which then finds that row. Resolves to
+/// `(instance, created)`.
+pub(crate) fn lookup_or_insert<'py>(
+    cls: &Bound<'py, PyType>,
+    db: &Database,
+    lookup: Option<&Bound<'py, PyDict>>,
+    defaults: Option<&Bound<'py, PyDict>>,
+    update: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = cls.py();
+    let info = ModelInfo::from_class(cls)?;
+    info.check_writable()?;
+    let lookup = column_values(&info, lookup, "the lookup")?;
+    let defaults = column_values(&info, defaults, "defaults")?;
+    if lookup.is_empty() {
+        return Err(FustOrmError::InvalidQueryArgument(format!(
+            "A lookup needs at least one column of '{}'.",
+            info.table_name
+        ))
+        .into());
+    }
+
+    let db = context::resolve(py, Some(db))?;
+    let tenant = match &db.tenant {
+        Some(tenant) => tenant.column_of(py, &info)?,
+        None => None,
+    };
+    let mut conditions = Vec::new();
+    let mut select_params = Vec::new();
+    for (column, value) in &lookup {
+        match column_param(py, value, column)? {
+            Some(param) => {
+                conditions.push(format!("{} = ?", column.column_name));
+                select_params.push(param);
+            }
+            None => conditions.push(format!("{} IS NULL", column.column_name)),
+        }
+    }
+    if let Some((column, values)) = discriminator_filter(cls)? {
+        let discriminator = info
+            .columns
+            .iter()
+            .find(|c| c.column_name == column)
+            .expect("discriminator is a column");
+        conditions.push(format!(
+            "{} IN ({})",
+            column,
+            vec!["?"; values.len()].join(", ")
+        ));
+        for value in &values {
+            select_params.extend(column_param(py, value, discriminator)?);
+        }
+    }
+    if let Some((column, param)) = &tenant {
+        conditions.push(format!("{} = ?", column.column_name));
+        select_params.push(param.clone());
+    }
+    let select_sql = format!(
+        "SELECT * FROM {} WHERE {} LIMIT 2",
+        info.table_name,
+        conditions.join(" AND ")
+    );
+
+    // Values for the insert: the lookup, overridden by `defaults`, plus the
+    // discriminator, the tenant and the initial version if the model has
+    // them.
+    let mut names: Vec<&str> = Vec::new();
+    let mut values: Vec<&str> = Vec::new();
+    let mut insert_params = Vec::new();
+    let mut merged: Vec<(&ColumnField, Bound<PyAny>)> = Vec::new();
+    for (column, value) in lookup.iter().chain(defaults.iter()) {
+        match merged
+            .iter_mut()
+            .find(|(c, _)| c.column_name == column.column_name)
+        {
+            Some(entry) => entry.1 = value.clone(),
+            None => merged.push((column, value.clone())),
+        }
+    }
+    if let Ok(value) = cls.getattr(pyo3::intern!(py, "__discriminator_value__")) {
+        let column = cls
+            .getattr(pyo3::intern!(py, "__discriminator__"))?
+            .extract::<String>()?;
+        if !merged.iter().any(|(c, _)| c.column_name == column)
+            && let Some(field) = info.columns.iter().find(|c| c.column_name == column)
+        {
+            merged.push((field, value));
+        }
+    }
+    if let (Some(filter), Some((column, _))) = (&db.tenant, &tenant) {
+        let value = PyString::new(py, &filter.tenant).into_any();
+        match merged
+            .iter_mut()
+            .find(|(c, _)| c.column_name == column.column_name)
+        {
+            Some(entry) => entry.1 = value,
+            None => merged.push((column, value)),
+        }
+    }
+    for (column, value) in &merged {
+        names.push(&column.column_name);
+        match column_param(py, value, column)? {
+            Some(param) => {
+                insert_params.push(param);
+                values.push("?");
+            }
+            None => values.push("NULL"),
+        }
+    }
+    let version = info.version_column().map(|c| c.column_name.clone());
+    if let Some(column) = &version {
+        names.push(column);
+        values.push("1");
+    }
+    let mut insert_sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        info.table_name,
+        names.join(", "),
+        values.join(", ")
+    );
+    let mysql = db.is_mysql();
+    if !mysql {
+        insert_sql.push_str(" RETURNING *");
+    }
+
+    // For updates, the assignments are known up front; the row's key is added
+    // to the WHERE clause once the row is found.
+    let mut assignments = Vec::new();
+    let mut update_params = Vec::new();
+    for (column, value) in defaults.iter().filter(|_| update) {
+        match column_param(py, value, column)? {
+            Some(param) => {
+                assignments.push(format!("{} = ?", column.column_name));
+                update_params.push(param);
+            }
+            None => assignments.push(format!("{} = NULL", column.column_name)),
+        }
+    }
+    if let Some(column) = version.as_ref().filter(|_| !assignments.is_empty()) {
+        assignments.push(format!("{0} = {0} + 1", column));
+    }
+    let pk_columns: Vec<ColumnField> = info.primary_key()?.into_iter().cloned().collect();
+    // Python values the found instance gets from `defaults` after an update.
+    let updated_values = PyDict::new(py);
+    if !assignments.is_empty() {
+        for (column, value) in &defaults {
+            updated_values.set_item(
+                &column.column_name,
+                column.py_value(column.db_value(value)?)?,
+            )?;
+        }
+    }
+    let updated_values = updated_values.unbind();
+
+    let cls = cls.clone().unbind();
+    let table_name = info.table_name.clone();
+
+    future_into_py(py, async move {
+        let map_db_err = FustOrmError::from;
+        let mut attempt = 0;
+        loop {
+            let mut tx = db.begin_write().await?;
+            let rows = fetch_rows(&mut *tx, &select_sql, select_params.clone()).await?;
+            if rows.len() > 1 {
+                return Err(FustOrmError::InvalidQueryArgument(format!(
+                    "The lookup matches more than one row of '{}'.",
+                    table_name
+                ))
+                .into());
+            }
+
+            if let Some(row) = rows.first() {
+                if !assignments.is_empty() {
+                    let (where_clause, params) = Python::attach(|py| {
+                        let dict = row_to_dict(py, row)?;
+                        let mut params = update_params.clone();
+                        let mut conditions = Vec::new();
+                        for column in &pk_columns {
+                            let value = dict.get_item(&column.column_name)?.ok_or_else(|| {
+                                FustOrmError::QueryError(format!(
+                                    "Primary key column '{}' is missing from the row.",
+                                    column.column_name
+                                ))
+                            })?;
+                            conditions.push(format!("{} = ?", column.column_name));
+                            params.extend(column_param(py, &value, column)?);
+                        }
+                        if let Some(column) = &version {
+                            let current = dict.get_item(column)?.map(|v| v.to_string());
+                            conditions.push(format!("{} = ?", column));
+                            params.extend(current);
+                        }
+                        PyResult::Ok((conditions.join(" AND "), params))
+                    })?;
+                    let sql = format!(
+                        "UPDATE {} SET {} WHERE {}",
+                        table_name,
+                        assignments.join(", "),
+                        where_clause
+                    );
+                    let result = execute_statement(&mut *tx, &sql, params).await?;
+                    if result.rows_affected() == 0 {
+                        return Err(FustOrmError::StaleData(format!(
+                            "Row of '{}' was changed or deleted while it was being updated.",
+                            table_name
+                        ))
+                        .into());
+                    }
+                }
+                tx.commit().await.map_err(map_db_err)?;
+                return Python::attach(|py| -> PyResult<(Py<PyAny>, bool)> {
+                    let instance = hydrate(cls.bind(py), &row_to_dict(py, row)?)?;
+                    for (key, value) in updated_values.bind(py).iter() {
+                        instance.setattr(key.downcast::<pyo3::types::PyString>()?, value)?;
+                    }
+                    if let Some(column) = version.as_ref().filter(|_| !assignments.is_empty()) {
+                        let current = instance.getattr(column)?.extract::<i64>()?;
+                        instance.setattr(column, current + 1)?;
+                    }
+                    Ok((instance.unbind(), false))
+                });
+            }
+
+            let (sql, params) = rewrite::statement(&insert_sql, insert_params.clone())?;
+            let inserted = bind_params(&sql, &params).fetch_all(&mut *tx).await;
+            let rows = match inserted {
+                Ok(rows) => rows,
+                Err(sqlx::Error::Database(e))
+                    if e.is_unique_violation() && attempt < CONFLICT_RETRIES =>
+                {
+                    debug!(
+                        "Insert into '{}' conflicted with a concurrent insert, retrying the lookup",
+                        table_name
+                    );
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(map_db_err(e).into()),
+            };
+            audit::record(&mut tx, &sql, &params, 1).await?;
+            let rows = if mysql {
+                fetch_rows(&mut *tx, &select_sql, select_params.clone()).await?
+            } else {
+                rows
+            };
+            tx.commit().await.map_err(map_db_err)?;
+            return Python::attach(|py| -> PyResult<(Py<PyAny>, bool)> {
+                let row = rows.first().ok_or_else(|| {
+                    FustOrmError::QueryError(format!(
+                        "The row inserted into '{}' could not be read back.",
+                        table_name
+                    ))
+                })?;
+                let instance = hydrate(cls.bind(py), &row_to_dict(py, row)?)?;
+                Ok((instance.unbind(), true))
+            });
+        }
+    })
+}
@@ -0,0 +1,158 @@
+use crate::database::{Database, begin_write, execute_statement};
+use crate::error::FustOrmError;
+use crate::model::{Model, ModelInfo};
+use crate::schema::Dialect;
+use log::debug;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use pyo3_async_runtimes::tokio::future_into_py;
+
+/// Renders the statements creating the view `name` as `query`. With
+/// `or_replace`, an existing view of that name is replaced: with `CREATE OR
+/// REPLACE VIEW`, or on SQLite, which lacks it, by dropping it first.
+pub(crate) fn create_view_sql(
+    name: &str,
+    query: &str,
+    dialect: Dialect,
+    or_replace: bool,
+) -> Vec<String> {
+    match (dialect, or_replace) {
+        (_, false) => vec![format!("CREATE VIEW {} AS {}", name, query)],
+        (Dialect::Sqlite, true) => vec![
+            format!("DROP VIEW IF EXISTS {}", name),
+            format!("CREATE VIEW {} AS {}", name, query),
+        ],
+        (_, true) => vec![format!("CREATE OR REPLACE VIEW {} AS {}", name, query)],
+    }
+}
+
+/// A programmatic `CREATE VIEW` statement:
+///
+/// ```python
+/// active_users = View("active_users", "SELECT id, name FROM users WHERE is_active = 1")
+/// await active_users.create(db, or_replace=True)
+/// ```
+///
+/// A Model whose `Meta.view` holds the `SELECT` maps onto the view named
+/// after its table, read-only.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct View {
+    #[pyo3(get)]
+    pub(crate) name: String,
+    /// The `SELECT` the view is defined as.
+    #[pyo3(get)]
+    pub(crate) query: String,
+}
+
+impl View {
+    /// Describes the view a model declares in `Meta.view`.
+    pub(crate) fn from_model(cls: &Bound<PyType>) -> PyResult<View> {
+        let py = cls.py();
+        let info = ModelInfo::from_class(cls)?;
+        let query = cls.getattr(pyo3::intern!(py, "__view__"))?;
+        if query.is_none() {
+            return Err(FustOrmError::BuildError(format!(
+                "Model '{}' doesn't declare a view in `Meta.view`.",
+                cls.name()?
+            ))
+            .into());
+        }
+        View::new(info.table_name, query.extract()?)
+    }
+
+    pub(crate) fn create_sql(&self, dialect: Dialect, or_replace: bool) -> Vec<String> {
+        create_view_sql(&self.name, &self.query, dialect, or_replace)
+    }
+}
+
+#[pymethods]
+impl View {
+    #[new]
+    fn new(name: String, query: String) -> PyResult<Self> {
+        let query = query.trim().trim_end_matches(';').trim_end().to_string();
+        if query.is_empty() {
+            return Err(FustOrmError::BuildError(format!("View '{}' has no query.", name)).into());
+        }
+        Ok(View { name, query })
+    }
+
+    /// Describes the view of a Model class with a `Meta.view`.
+    #[staticmethod]
+    #[pyo3(name = "from_model")]
+    fn py_from_model(model: &Bound<PyType>) -> PyResult<View> {
+        View::from_model(model)
+    }
+
+    /// Renders the `CREATE VIEW` statement for `dialect` (`"sqlite"`,
+    /// `"postgres"` or `"mysql"`). SQLite replaces a view with two statements,
+    /// which are separated by `;`.
+    #[pyo3(signature = (dialect = "sqlite", *, or_replace = false))]
+    fn to_sql(&self, dialect: &str, or_replace: bool) -> PyResult<String> {
+        Ok(self
+            .create_sql(Dialect::parse(dialect)?, or_replace)
+            .join(";\n"))
+    }
+
+    /// Creates the view in the database, replacing an existing view of the
+    /// same name if `or_replace`.
+    #[pyo3(signature = (db, *, or_replace = false))]
+    fn create<'py>(
+        &self,
+        py: Python<'py>,
+        db: &Database,
+        or_replace: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let statements = self.create_sql(db.dialect(), or_replace);
+        let pool = db.pool.clone();
+        future_into_py(py, async move {
+            let mut tx = begin_write(&pool).await?;
+            for sql in &statements {
+                debug!("Creating view: {}", sql);
+                execute_statement(&mut *tx, sql, Vec::new()).await?;
+            }
+            tx.commit().await.map_err(FustOrmError::from)?;
+            Ok(())
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<View {}>", self.name)
+    }
+}
+
+/// Resolves a view given as a name, a `View` or a Model class.
+fn view_name(target: &Bound<PyAny>) -> PyResult<String> {
+    if let Ok(cls) = target.downcast::<PyType>()
+        && cls.is_subclass_of::<Model>()?
+    {
+        return Ok(ModelInfo::from_class(cls)?.table_name);
+    }
+    if let Ok(view) = target.downcast::<View>() {
+        return Ok(view.get().name.clone());
+    }
+    target.extract::<String>().map_err(|_| {
+        FustOrmError::BuildError("The view must be a name, a View or a Model class.".to_string())
+            .into()
+    })
+}
+
+/// Drops `view` (a name, a `View` or a Model class).
+pub(crate) fn drop_view<'py>(
+    py: Python<'py>,
+    db: &Database,
+    view: &Bound<PyAny>,
+    if_exists: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let sql = format!(
+        "DROP VIEW {}{}",
+        if if_exists { "IF EXISTS " } else { "" },
+        view_name(view)?
+    );
+    let pool = db.pool.clone();
+    future_into_py(py, async move {
+        debug!("Dropping view: {}", sql);
+        execute_statement(&pool, &sql, Vec::new()).await?;
+        Ok(())
+    })
+}
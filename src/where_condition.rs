@@ -1,7 +1,13 @@
 use std::sync::Arc;
 
+use pyo3::exceptions::PyTypeError;
 use pyo3::prelude::*;
 
+use crate::column_field::ColumnField;
+use crate::expression::{Expr, F};
+use crate::query::{SqlParam, in_list, py_any_to_string};
+use crate::schema::Dialect;
+
 /// Represents a single condition in a SQL WHERE clause (e.g., "id = 5").
 ///
 /// Instances of this struct are typically created by applying comparison
@@ -19,6 +25,98 @@ pub struct WhereCondition {
     /// For example, in `select(User.id, +(User.age > 30))`, `select_column` for the
     /// resulting condition will be `true`.
     pub select_column: bool,
+    /// An expression compared instead of `column_name`, as in
+    /// `F("stock") - F("reserved") > 0`.
+    pub expression: Option<Expr>,
+}
+
+impl WhereCondition {
+    /// Creates a condition comparing an `F` expression to `value`. Plain column
+    /// references are stored as `column_name`, like conditions on a `ColumnField`.
+    pub fn from_expression(expr: Expr, operator: &str, value: Py<PyAny>) -> Self {
+        let (column_name, expression) = match expr {
+            Expr::Column(name) => (name, None),
+            expr => (String::new(), Some(expr)),
+        };
+        WhereCondition {
+            column_name,
+            operator: operator.to_string(),
+            value: Arc::new(value),
+            select_column: false,
+            expression,
+        }
+    }
+
+    /// Creates a condition that is an expression itself, such as a call of
+    /// `ST_Within()`, rather than a comparison. It has no operator.
+    pub fn from_predicate(py: Python, expr: Expr) -> Self {
+        WhereCondition {
+            column_name: String::new(),
+            operator: String::new(),
+            value: Arc::new(py.None()),
+            select_column: false,
+            expression: Some(expr),
+        }
+    }
+
+    /// Renders the comparison of `left` to `right` in `dialect`. `ILIKE` is
+    /// emulated with `LOWER()` outside PostgreSQL, and the regex operators
+    /// are `~` and `~*` on PostgreSQL and `REGEXP` elsewhere.
+    fn compare(&self, dialect: Dialect, left: &str, right: &str) -> String {
+        match (self.operator.as_str(), dialect) {
+            ("ILIKE", Dialect::Postgres) => format!("{} ILIKE {}", left, right),
+            ("ILIKE", _) => format!("LOWER({}) LIKE LOWER({})", left, right),
+            ("REGEXP", Dialect::Postgres) => format!("{} ~ {}", left, right),
+            ("IREGEXP", Dialect::Postgres) => format!("{} ~* {}", left, right),
+            ("IREGEXP", _) => format!("{} REGEXP {}", left, right),
+            (operator, _) => format!("{} {} {}", left, operator, right),
+        }
+    }
+
+    /// Renders the condition for `dialect`, pushing the parameters it binds.
+    ///
+    /// The compared value may itself reference columns (an `F` expression or a
+    /// `ColumnField`), which are rendered in place instead of being bound.
+    pub fn to_sql(
+        &self,
+        py: Python,
+        dialect: Dialect,
+        params: &mut Vec<String>,
+    ) -> PyResult<String> {
+        let left = match &self.expression {
+            Some(expr) => expr.to_sql(py, params)?,
+            None => self.column_name.clone(),
+        };
+        if self.operator.is_empty() {
+            return Ok(left);
+        }
+        let value = self.value.bind(py);
+        if value.is_none() {
+            return Ok(format!("{} {} NULL", left, self.operator));
+        }
+        // Values compared to an expression are rendered like its operands:
+        // expression results have no column type to convert bound strings to.
+        if self.expression.is_some()
+            || value.is_instance_of::<F>()
+            || value.is_instance_of::<ColumnField>()
+        {
+            let right = Expr::from_operand(value).to_sql(py, params)?;
+            return Ok(self.compare(dialect, &left, &right));
+        }
+        match py_any_to_string(py, &self.value, &self.column_name)? {
+            SqlParam::Single(s) => {
+                // Outside PostgreSQL, a case-insensitive regex is a pattern
+                // with the inline `i` flag, which SQLite's `regexp()` (Python's
+                // `re`) and MySQL both read.
+                params.push(match (self.operator.as_str(), dialect) {
+                    ("IREGEXP", Dialect::Sqlite | Dialect::Mysql) => format!("(?i){}", s),
+                    _ => s,
+                });
+                Ok(self.compare(dialect, &left, "?"))
+            }
+            SqlParam::List(vec) => Ok(self.compare(dialect, &left, &in_list(vec, params))),
+        }
+    }
 }
 
 #[pymethods]
@@ -29,9 +127,14 @@ impl WhereCondition {
     /// this column should also be included in the final SELECT statement.
     /// This allows for expressive queries like `select(User.id, +(User.age > 30))`,
     /// which both filters by `age` and selects it.
-    fn __pos__(&self) -> Self {
+    fn __pos__(&self) -> PyResult<Self> {
+        if self.expression.is_some() {
+            return Err(PyTypeError::new_err(
+                "Only conditions on a plain column can be selected with `+`.",
+            ));
+        }
         let mut new_condition = self.clone();
         new_condition.select_column = true;
-        new_condition
+        Ok(new_condition)
     }
 }
@@ -1,8 +1,86 @@
 import asyncio
+import concurrent.futures
+import contextvars
+import dataclasses
+import datetime
+import enum
+import io
+import json
+import logging
+import os
+import pathlib
+import sqlite3
+import subprocess
+import sys
+import uuid
 from collections.abc import Generator
 import pytest
 
-from fust_orm import Database, Model, ColumnField, select
+from typing import Any, NamedTuple
+
+from fust_orm import (
+    CheckViolationError,
+    CircuitBreaker,
+    RetryPolicy,
+    ForeignKeyViolationError,
+    NotNullViolationError,
+    UniqueViolationError,
+    DatabaseError,
+    IntegrityError,
+    InterfaceError,
+    OperationalError,
+    ProgrammingError,
+    QueryBuilder,
+    BigInteger,
+    Boolean,
+    ColumnField,
+    Database,
+    F,
+    Float,
+    FtsTable,
+    Geometry,
+    Array,
+    Range,
+    TstzRange,
+    register_type,
+    Integer,
+    ManyToMany,
+    Model,
+    Numeric,
+    ShardedDatabase,
+    StaleDataError,
+    Table,
+    Text,
+    Trigger,
+    Varchar,
+    View,
+    alter_table,
+    audit_statements,
+    as_wkt,
+    bind,
+    bm25,
+    configure_query_cache,
+    configure_runtime,
+    highlight,
+    instrument,
+    introspect_models,
+    load_geometry,
+    metrics,
+    on_slow_query,
+    post_delete,
+    post_save,
+    pre_delete,
+    pre_save,
+    rewrite_queries,
+    rewrite_statements,
+    scope,
+    select,
+    snippet,
+    tag_statements,
+    to_geometry,
+    uninstrument,
+)
+from fust_orm import dbapi, sync, testing, threaded
 
 
 class User(Model):
@@ -17,6 +95,84 @@ class Product(Model):
     product_name: ColumnField[str]
 
 
+class Member(Model):
+    id: ColumnField[int]
+    name: ColumnField[str]
+    age: ColumnField[int]
+    is_active: ColumnField[bool]
+
+    class Meta:
+        table_name = "user_account"
+        default_scope = lambda model: model.is_active == True  # noqa: E712, E731
+
+    @scope
+    def older_than(cls, age: int) -> Any:
+        return cls.age > age
+
+    @scope
+    def named(cls, *names: str) -> Any:
+        return [cls.name.in_(names)]
+
+
+class Vehicle(Model):
+    id: ColumnField[int]
+    kind: ColumnField[str]
+    name: ColumnField[str]
+
+    class Meta:
+        discriminator = "kind"
+
+
+class Car(Vehicle):
+    doors: ColumnField[int]
+
+
+class Truck(Vehicle):
+    payload: ColumnField[int]
+
+    class Meta:
+        discriminator_value = "lorry"
+
+
+class Membership(Model):
+    tenant_id: ColumnField[int] = ColumnField(primary_key=True)
+    user_id: ColumnField[int] = ColumnField(primary_key=True)
+    role: ColumnField[str]
+
+
+class Document(Model):
+    id: ColumnField[int] = ColumnField(primary_key=True)
+    title: ColumnField[str]
+    version: ColumnField[int] = ColumnField(version=True)
+
+
+class Status(enum.Enum):
+    OPEN = "open"
+    CLOSED = "closed"
+
+
+class Priority(enum.IntEnum):
+    LOW = 1
+    HIGH = 2
+
+
+class Ticket(Model):
+    id: ColumnField[int]
+    status: ColumnField[Status]
+    priority: ColumnField[Priority]
+
+
+class Tag(Model):
+    id: ColumnField[int]
+    name: ColumnField[str]
+
+
+class Article(Model):
+    id: ColumnField[int]
+    title: ColumnField[str]
+    tags: ManyToMany[Tag] = ManyToMany(Tag)
+
+
 @pytest.fixture(scope="session")
 def event_loop() -> Generator[asyncio.AbstractEventLoop]:
     try:
@@ -48,6 +204,58 @@ async def db() -> Database:
         );
         """
     )
+    await database.execute(
+        """
+        CREATE TABLE user_account (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            age INTEGER,
+            is_active INTEGER NOT NULL
+        );
+        """
+    )
+    await database.execute(
+        """
+        CREATE TABLE vehicle (
+            id INTEGER PRIMARY KEY,
+            kind TEXT NOT NULL,
+            name TEXT NOT NULL,
+            doors INTEGER,
+            payload INTEGER
+        );
+        """
+    )
+    await database.execute(
+        """
+        CREATE TABLE membership (
+            tenant_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            role TEXT NOT NULL,
+            PRIMARY KEY (tenant_id, user_id)
+        );
+        """
+    )
+    await database.execute(
+        """
+        CREATE TABLE ticket (
+            id INTEGER PRIMARY KEY,
+            status TEXT NOT NULL,
+            priority INTEGER
+        );
+        """
+    )
+    await database.execute("CREATE TABLE tag (id INTEGER PRIMARY KEY, name TEXT NOT NULL);")
+    await database.execute("CREATE TABLE article (id INTEGER PRIMARY KEY, title TEXT NOT NULL);")
+    await Article.tags.create_through_table(database)
+    await database.execute(
+        """
+        CREATE TABLE document (
+            id INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            version INTEGER NOT NULL
+        );
+        """
+    )
     return database
 
 
@@ -58,6 +266,897 @@ async def test_db_connection(db: Database) -> None:
     assert result == [{"1": 1}]
 
 
+async def test_echo(tmp_path: pathlib.Path) -> None:
+    records: list[logging.LogRecord] = []
+    handler = logging.Handler()
+    handler.emit = records.append  # type: ignore[method-assign]
+    logging.getLogger("fust_orm.sql").addHandler(handler)
+    url = f"sqlite://{tmp_path / 'echo.db'}?mode=rwc"
+    try:
+        echoed = await Database.connect(url, echo=True)
+        quiet = await Database.connect(url)
+        await echoed.execute("SELECT 1 AS echoed")
+        await quiet.execute("SELECT 2 AS quiet")
+    finally:
+        logging.getLogger("fust_orm.sql").removeHandler(handler)
+    statements = [r.getMessage() for r in records]
+    assert any("SELECT 1 AS echoed" in s for s in statements)
+    assert not any("SELECT 2 AS quiet" in s for s in statements)
+    assert logging.getLogger("fust_orm.sql").level == logging.INFO
+
+
+async def test_exception_classes(db: Database, tmp_path: pathlib.Path) -> None:
+    assert issubclass(IntegrityError, DatabaseError) and issubclass(DatabaseError, ValueError)
+    with pytest.raises(ProgrammingError, match="no such table"):
+        await db.execute("SELECT * FROM missing")
+    with pytest.raises(ProgrammingError, match="syntax error"):
+        await db.execute("SELEC 1")
+    await db.execute("CREATE TABLE badge (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE)")
+    await db.execute("INSERT INTO badge (name) VALUES ('gold')")
+    with pytest.raises(IntegrityError, match="UNIQUE constraint failed"):
+        await db.execute("INSERT INTO badge (name) VALUES ('gold')")
+    with pytest.raises(IntegrityError, match="NOT NULL constraint failed"):
+        await db.execute("INSERT INTO badge (name) VALUES (NULL)")
+    with pytest.raises(OperationalError):
+        await Database.connect(f"sqlite://{tmp_path / 'missing' / 'x.db'}")
+    with pytest.raises(InterfaceError, match="Connection error"):
+        await Database.connect("nosuchdriver://localhost")
+
+
+async def test_error_context(db: Database) -> None:
+    await db.execute("CREATE TABLE stamp (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE)")
+    await db.execute(select("INSERT INTO stamp (name) VALUES (?)", "x" * 100))
+    with pytest.raises(IntegrityError) as info:
+        await db.execute(select("INSERT INTO stamp (name) VALUES (?)", "x" * 100))
+    assert info.value.sql == "INSERT INTO stamp (name) VALUES (?)"
+    assert info.value.params == ["x" * 64 + "..."]
+    assert info.value.code == "2067"  # SQLITE_CONSTRAINT_UNIQUE
+    with pytest.raises(ValueError) as info:
+        await db.execute(select("SELECT * FROM missing"))
+    assert info.value.code == "1"
+    with pytest.raises(InterfaceError) as info:
+        await Database.connect("nosuchdriver://localhost")
+    assert info.value.sql is None and info.value.params is None
+
+
+async def test_constraint_violations(db: Database) -> None:
+    await db.execute("PRAGMA foreign_keys = ON")
+    await db.execute("CREATE TABLE shelf (id INTEGER PRIMARY KEY)")
+    await db.execute(
+        "CREATE TABLE volume (id INTEGER PRIMARY KEY, title TEXT NOT NULL UNIQUE, "
+        "shelf_id INTEGER REFERENCES shelf (id), "
+        "pages INTEGER CONSTRAINT positive_pages CHECK (pages > 0))"
+    )
+    await db.execute("INSERT INTO volume (title) VALUES ('Dune')")
+    with pytest.raises(UniqueViolationError) as unique:
+        await db.execute("INSERT INTO volume (title) VALUES ('Dune')")
+    assert unique.value.constraint == "volume.title"
+    assert isinstance(unique.value, IntegrityError)
+    with pytest.raises(NotNullViolationError) as not_null:
+        await db.execute("INSERT INTO volume (title) VALUES (NULL)")
+    assert not_null.value.constraint == "volume.title"
+    with pytest.raises(CheckViolationError) as check:
+        await db.execute("INSERT INTO volume (title, pages) VALUES ('Emma', 0)")
+    assert check.value.constraint == "positive_pages"
+    with pytest.raises(ForeignKeyViolationError) as foreign_key:
+        await db.execute("INSERT INTO volume (title, shelf_id) VALUES ('Ulysses', 9)")
+    assert foreign_key.value.constraint is None
+    with pytest.raises(ProgrammingError) as info:
+        await db.execute("SELECT * FROM missing")
+    assert info.value.constraint is None
+
+
+async def test_bound_database_and_transaction(db: Database) -> None:
+    with pytest.raises(ValueError, match="No database was passed"):
+        await User(name="Unbound", age=1).save()
+    with bind(db):
+        ada = User(name="Ada", age=36, manager_id=None)
+        await ada.save()
+        assert [u.name for u in await select(User, User.age == 36).all()] == ["Ada"]
+
+        async with db.transaction() as tx:
+            await User(name="Grace", age=45, manager_id=None).save()
+            # Nested transactions and explicitly passed databases join it.
+            async with db.transaction() as inner:
+                assert inner is not db
+                await User(name="Alan", age=41, manager_id=None).save(db)
+            assert await tx.execute("SELECT count(*) AS n FROM user") == [{"n": 3}]
+        assert await select(User).count() == 3
+
+        with pytest.raises(RuntimeError):
+            async with db.transaction():
+                await ada.delete()
+                assert await select(User).count() == 2
+                raise RuntimeError("roll back")
+        assert await select(User).count() == 3
+    with pytest.raises(ValueError, match="No database was passed"):
+        await select(User).count()
+
+
+async def test_test_database(db: Database) -> None:
+    async with testing.test_database(db) as tx_db:
+        assert tx_db is not db
+        await User(name="Ann", age=30, manager_id=None).save()
+        # Transactions of the code under test are savepoints.
+        async with db.transaction():
+            await User(name="Bob", age=31, manager_id=None).save()
+        with pytest.raises(RuntimeError):
+            async with db.transaction():
+                await User(name="Cat", age=32, manager_id=None).save(db)
+                async with db.transaction():
+                    await User(name="Dan", age=33, manager_id=None).save()
+                raise RuntimeError("roll back to the savepoint")
+        assert await select(User.name).values_list(db, "name", flat=True) == ["Ann", "Bob"]
+    assert await select(User).count(db) == 0
+
+    # Without a test transaction, nested ones still join the outer one.
+    async with db.transaction():
+        await User(name="Eve", age=34, manager_id=None).save(db)
+        with pytest.raises(RuntimeError):
+            async with db.transaction():
+                raise RuntimeError("left to the outer transaction")
+    assert await select(User).count(db) == 1
+
+
+async def test_test_database_contains_unbound_transactions(db: Database, tmp_path: pathlib.Path) -> None:
+    fixture = tmp_path / "users.json"
+    fixture.write_text(json.dumps({"user": [{"name": "Ann", "age": 30, "manager_id": None}]}))
+
+    async def save_unbound(name: str, fail: bool) -> None:
+        # Code running outside the context of the test doesn't see its binding.
+        async with db.transaction():
+            await User(name=name, age=31, manager_id=None).save(db)
+            if fail:
+                raise RuntimeError("roll back to the savepoint")
+
+    async with testing.test_database(db) as tx_db:
+        # Transactions begun on the pool, not joining the bound one, are
+        # savepoints of the test's transaction too.
+        await db.load_fixtures(str(fixture))
+        loop = asyncio.get_running_loop()
+        await loop.create_task(save_unbound("Bob", False), context=contextvars.Context())
+        with pytest.raises(RuntimeError):
+            await loop.create_task(save_unbound("Cat", True), context=contextvars.Context())
+        async with testing.test_database(db):
+            await User(name="Dan", age=33, manager_id=None).save()
+        assert await select(User.name).values_list(tx_db, "name", flat=True) == ["Ann", "Bob"]
+    assert await select(User).count(db) == 0
+
+
+async def test_test_database_rolls_back_schema_changes(db: Database) -> None:
+    scratch = Table("scratch").column("id", Integer, primary_key=True)
+
+    async def index_unbound() -> None:
+        assert await db.create_index("scratch", "id") == "scratch_id_idx"
+
+    async with testing.test_database(db):
+        await scratch.create(db)
+        await db.execute("INSERT INTO scratch VALUES (1)")
+        loop = asyncio.get_running_loop()
+        await loop.create_task(index_unbound(), context=contextvars.Context())
+        await db.truncate("scratch")
+        await db.drop_table(User)
+        assert await db.execute("SELECT name FROM sqlite_master WHERE name LIKE 'scratch%'") == [
+            {"name": "scratch"},
+            {"name": "scratch_id_idx"},
+        ]
+    assert await db.execute("SELECT name FROM sqlite_master WHERE name LIKE 'scratch%'") == []
+    assert await select(User).count(db) == 0
+
+
+async def test_capture_queries(db: Database) -> None:
+    with testing.capture_queries() as queries:
+        await User(name="Ann", age=30, manager_id=None).save(db)
+        with testing.capture_queries() as inner:
+            assert await select(User).count(db) == 1
+    await select(User).count(db)
+    assert len(queries) == 2
+    assert queries.statements[0].startswith("INSERT INTO user")
+    assert inner.statements == ["SELECT count(*) FROM user"]
+
+    with testing.assert_num_queries(1):
+        await select(User).count(db)
+    with testing.assert_max_queries(1):
+        pass
+    with pytest.raises(AssertionError, match="Expected at most 0 queries, 1 ran:\n  1. SELECT count"):
+        with testing.assert_max_queries(0):
+            await select(User).count(db)
+
+
+async def test_record_queries(db: Database) -> None:
+    with db.record_queries() as rec:
+        await User(name="Ann", age=30, manager_id=None).save(db)
+        await User.query().where_(User.name == "Ann").all(db)
+    await select(User).count(db)
+    assert len(rec) == 2
+    assert rec.queries[1]["sql"].startswith("SELECT")
+    assert rec.queries[1]["params"] == ["Ann"]
+    assert rec.elapsed_ms == sum(query["elapsed_ms"] for query in rec.queries) > 0
+    rec.assert_count(2)
+    with pytest.raises(AssertionError, match="Expected 1 queries, 2 ran:\n  1. INSERT INTO user"):
+        rec.assert_count(1)
+
+    with pytest.raises(AssertionError, match="Expected no table scans, found:\n  SELECT .* FROM user WHERE"):
+        await rec.assert_no_table_scans()
+    await db.create_index(User, "name")
+    await rec.assert_no_table_scans()
+
+
+async def test_mock_database() -> None:
+    db = testing.MockDatabase()
+    assert isinstance(db, Database)
+    db.on(r"FROM user", [{"id": 1, "name": "O'Neil", "age": 30, "manager_id": None}])
+    db.on(r"FROM user WHERE", [])
+    db.on(r"^INSERT INTO user", [{"id": 7}])
+    db.on(r"^UPDATE user", rows_affected=1)
+
+    users = await User.query().all(db)
+    assert [(user.id, user.name, user.age) for user in users] == [(1, "O'Neil", 30)]
+    assert await User.query().where_(User.age > 40).all(db) == []
+    async with db.transaction():
+        user = User(name="Bob", age=31, manager_id=0)
+        await user.save()
+    assert user.id == 7
+    user.age = 32
+    await user.save(db)
+    assert db.queries[-1]["sql"].startswith("UPDATE user")
+    assert db.queries[-1]["params"][-1] == "7"
+    assert len(db.queries) == 4
+    with pytest.raises(ValueError, match="can't run statements in a transaction of their own"):
+        await User.bulk_create(db, [User(name="Cat", age=33, manager_id=0)])
+
+    with pytest.raises(DatabaseError, match="No result was mocked for the statement: DROP TABLE user"):
+        await db.execute("DROP TABLE user")
+    with pytest.raises(ValueError, match="Invalid pattern"):
+        db.on("(")
+    db.reset()
+    assert db.queries == []
+
+
+def test_pytest_plugin(tmp_path: pathlib.Path) -> None:
+    pytest.importorskip("pytest_asyncio")
+    (tmp_path / "migrations").mkdir()
+    (tmp_path / "migrations" / "0001_create_item.up.sql").write_text(
+        "CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT)"
+    )
+    (tmp_path / "pytest.ini").write_text(
+        f"""[pytest]
+asyncio_mode = auto
+fust_orm_url = sqlite://{tmp_path / "plugin.db"}?mode=rwc
+fust_orm_migrations = migrations
+"""
+    )
+    (tmp_path / "conftest.py").write_text('pytest_plugins = ["fust_orm.pytest_plugin"]\n')
+    (tmp_path / "test_items.py").write_text(
+        """
+import pytest
+
+@pytest.mark.parametrize("run", [1, 2])
+async def test_rolled_back(fust_db, fust_queries, run):
+    assert await fust_db.execute("SELECT count(*) AS n FROM item") == [{"n": 0}]
+    await fust_db.execute("INSERT INTO item (name) VALUES ('a')")
+    assert fust_queries.statements[1] == "INSERT INTO item (name) VALUES ('a')"
+"""
+    )
+    result = subprocess.run(
+        [sys.executable, "-m", "pytest", "-q", "-p", "no:cacheprovider", str(tmp_path)],
+        cwd=tmp_path,
+        capture_output=True,
+        text=True,
+    )
+    assert result.returncode == 0, result.stdout + result.stderr
+    assert "2 passed" in result.stdout
+
+
+async def test_gather(db: Database) -> None:
+    await User.bulk_create(db, [User(name="Gathered", age=41, manager_id=None)])
+    names, count, empty = await db.gather(
+        select(User.name, User.age == 41),
+        "SELECT count(*) AS n FROM user",
+        select("SELECT name FROM user WHERE age = ?", -1),
+    )
+    assert names == [{"name": "Gathered"}]
+    assert count[0]["n"] >= 1
+    assert empty == []
+    assert await db.gather() == []
+    with pytest.raises(ValueError):
+        await db.gather("SELECT 1", "SELECT * FROM missing")
+    with pytest.raises(TypeError, match="'query' must be a string"):
+        await db.gather("SELECT 1", 1)
+
+
+async def test_close(tmp_path: pathlib.Path) -> None:
+    db = await Database.connect(f"sqlite://{tmp_path / 'closed.db'}?mode=rwc")
+    await db.execute("SELECT 1")
+    await db.close()
+    with pytest.raises(OperationalError, match="closed pool"):
+        await db.execute("SELECT 1")
+
+
+@pytest.mark.skipif(not os.path.isdir("/proc/self/fd"), reason="needs /proc")
+async def test_dropped_database_closes_its_pool(tmp_path: pathlib.Path) -> None:
+    path = tmp_path / "dropped.db"
+
+    def open_files() -> int:
+        fds = os.listdir("/proc/self/fd")
+        return sum(1 for fd in fds if str(path) in os.path.realpath(f"/proc/self/fd/{fd}"))
+
+    for _ in range(20):
+        db = await Database.connect(f"sqlite://{path}?mode=rwc")
+        await db.execute("SELECT 1")
+        del db
+    for _ in range(50):
+        if open_files() == 0:
+            break
+        await asyncio.sleep(0.02)
+    assert open_files() == 0
+
+
+def test_pools_closed_at_exit(tmp_path: pathlib.Path) -> None:
+    path = tmp_path / "exit.db"
+    script = f"""
+import asyncio
+from fust_orm import Database
+
+async def main():
+    db = await Database.connect("sqlite://{path}?mode=rwc")
+    await db.execute("PRAGMA journal_mode=WAL")
+    await db.execute("CREATE TABLE t (id INTEGER)")
+    await db.execute("INSERT INTO t VALUES (1)")
+
+asyncio.run(main())
+"""
+    result = subprocess.run(
+        [sys.executable, "-c", script],
+        capture_output=True,
+        text=True,
+        timeout=30,
+        env={**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)},
+    )
+    assert result.returncode == 0, result.stderr
+    assert result.stderr == ""
+    # SQLite removes the write-ahead log when the last connection is closed.
+    assert not (tmp_path / "exit.db-wal").exists()
+    assert sqlite3.connect(path).execute("SELECT id FROM t").fetchall() == [(1,)]
+
+
+def test_configure_runtime(tmp_path: pathlib.Path) -> None:
+    with pytest.raises(ValueError, match="at least 1"):
+        configure_runtime(worker_threads=0)
+    with pytest.raises(ValueError, match="current_thread"):
+        configure_runtime(worker_threads=2, current_thread=True)
+    sync.Database.connect("sqlite::memory:")  # starts the runtime
+    with pytest.raises(RuntimeError, match="already started"):
+        configure_runtime(worker_threads=2)
+    with pytest.raises(RuntimeError, match="already started"):
+        configure_runtime(current_thread=True)
+
+    script = f"""
+import asyncio
+from fust_orm import Database, configure_runtime, sync
+
+configure_runtime(current_thread=True, thread_name="orm")
+
+async def main():
+    db = await Database.connect("sqlite://{tmp_path / 'runtime.db'}?mode=rwc")
+    await db.execute("CREATE TABLE t (id INTEGER)")
+    await db.gather("INSERT INTO t VALUES (1)", "INSERT INTO t VALUES (2)")
+
+asyncio.run(main())
+db = sync.Database.connect("sqlite://{tmp_path / 'runtime.db'}")
+print(db.execute("SELECT count(*) AS n FROM t")[0]["n"])
+"""
+    result = subprocess.run(
+        [sys.executable, "-c", script],
+        capture_output=True,
+        text=True,
+        timeout=30,
+        env={**os.environ, "PYTHONPATH": os.pathsep.join(sys.path)},
+    )
+    assert result.returncode == 0, result.stderr
+    assert result.stdout == "2\n"
+
+
+def test_sync_database(tmp_path: pathlib.Path) -> None:
+    db = sync.Database.connect(f"sqlite://{tmp_path / 'sync.db'}?mode=rwc")
+    db.execute("CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT NOT NULL, age INTEGER)")
+    db.execute(select("INSERT INTO user (name, age) VALUES (?, ?)", "Ada", 36))
+    assert db.execute(select(User.name, User.age > 30)) == [{"name": "Ada"}]
+
+    with db.transaction() as tx:
+        tx.execute_many(
+            select("INSERT INTO user (name, age) VALUES (?, ?)", "Grace", 45),
+            "UPDATE user SET age = age + 1",
+        )
+        assert tx.execute("SELECT count(*) AS n FROM user") == [{"n": 2}]
+    with pytest.raises(RuntimeError):
+        with db.transaction() as tx:
+            tx.execute("DELETE FROM user")
+            raise RuntimeError("roll back")
+    assert db.execute("SELECT name, age FROM user ORDER BY id") == [
+        {"name": "Ada", "age": 37},
+        {"name": "Grace", "age": 46},
+    ]
+
+    with pytest.raises(ValueError):
+        db.transaction().execute("SELECT 1")
+    with pytest.raises(ProgrammingError):
+        db.execute("SELECT * FROM missing")
+    assert isinstance(db.aio, Database)
+
+
+async def _use_threaded_database(url: str) -> list[dict[str, Any]]:
+    db = await threaded.Database.connect(url)
+    await db.execute("CREATE TABLE IF NOT EXISTS user (id INTEGER PRIMARY KEY, name TEXT, age INTEGER)")
+    async with db.transaction() as tx:
+        await tx.execute_many(
+            select("INSERT INTO user (name, age) VALUES (?, ?)", "Ada", 36),
+            select("INSERT INTO user (name, age) VALUES (?, ?)", "Alan", 41),
+        )
+    with pytest.raises(RuntimeError):
+        async with db.transaction() as tx:
+            await tx.execute("DELETE FROM user")
+            raise RuntimeError("roll back")
+    return await db.execute(select(User.name, User.age > 40))
+
+
+def test_threaded_database_under_trio(tmp_path: pathlib.Path) -> None:
+    trio = pytest.importorskip("trio")
+    url = f"sqlite://{tmp_path / 'trio.db'}?mode=rwc"
+    assert trio.run(_use_threaded_database, url) == [{"name": "Alan"}]
+
+
+async def test_threaded_database_under_asyncio(tmp_path: pathlib.Path) -> None:
+    url = f"sqlite://{tmp_path / 'asyncio.db'}?mode=rwc"
+    assert await _use_threaded_database(url) == [{"name": "Alan"}]
+
+
+def test_concurrent_threads(tmp_path: pathlib.Path) -> None:
+    # Without the GIL on free-threaded builds, the threads really run at once.
+    url = f"sqlite://{tmp_path / 'threads.db'}?mode=rwc"
+    db = sync.Database.connect(url)
+    db.execute("PRAGMA journal_mode=WAL")
+    db.execute("CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT NOT NULL, age INTEGER)")
+    threads, rounds = 8, 25
+
+    def write_and_read(thread: int) -> None:
+        for i in range(rounds):
+            with db.transaction() as tx:
+                tx.execute(select("INSERT INTO user (name, age) VALUES (?, ?)", f"t{thread}", i))
+            rows = db.execute(select(User.name, User.age, User.name == f"t{thread}"))
+            assert rows == [{"name": f"t{thread}", "age": age} for age in range(i + 1)]
+
+    def on_pre_save(sender: type, instance: Any, created: bool) -> None: ...
+
+    def toggle_handler(thread: int) -> None:
+        for _ in range(rounds):
+            pre_save.connect(on_pre_save)
+            pre_save.disconnect(on_pre_save)
+
+    def run_loop(thread: int) -> None:
+        # Each thread runs its own event loop on the shared async database.
+        async def count() -> int:
+            rows = await db.aio.gather(*["SELECT count(*) AS n FROM user"] * 4)
+            return sum(row[0]["n"] for row in rows)
+
+        for _ in range(rounds):
+            assert asyncio.run(count()) >= 0
+
+    with concurrent.futures.ThreadPoolExecutor(max_workers=3 * threads) as pool:
+        futures = [
+            pool.submit(work, thread)
+            for thread in range(threads)
+            for work in (write_and_read, toggle_handler, run_loop)
+        ]
+        for future in futures:
+            future.result()
+    assert not pre_save.disconnect(on_pre_save)
+    assert db.execute("SELECT count(*) AS n FROM user") == [{"n": threads * rounds}]
+
+
+def test_dbapi(tmp_path: pathlib.Path) -> None:
+    assert (dbapi.apilevel, dbapi.paramstyle) == ("2.0", "qmark")
+    con = dbapi.connect(f"sqlite://{tmp_path / 'dbapi.db'}?mode=rwc")
+    cur = con.cursor()
+    cur.execute("CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT NOT NULL, price REAL)")
+    assert cur.description is None
+    cur.executemany("INSERT INTO item (name, price) VALUES (?, ?)", [("pen", 1.5), ("ink", 4)])
+    assert cur.rowcount == 2
+    cur.execute("INSERT INTO item (name) VALUES (?)", ["cap"])
+    assert (cur.rowcount, cur.lastrowid) == (1, 3)
+    con.commit()
+
+    cur.execute("SELECT id, name, price FROM item WHERE id > ? ORDER BY id", (0,))
+    assert [column[0] for column in cur.description] == ["id", "name", "price"]
+    assert cur.description[1][1] == "TEXT" and cur.description[1][6] is False
+    assert cur.fetchone() == (1, "pen", 1.5)
+    assert cur.fetchmany() == [(2, "ink", 4.0)]
+    assert cur.fetchall() == [(3, "cap", None)]
+    assert cur.fetchone() is None
+    cur.execute("SELECT name FROM item WHERE 0")
+    assert [column[0] for column in cur.description] == ["name"]
+    assert cur.fetchall() == []
+
+    con.execute("DELETE FROM item")
+    con.rollback()
+    with pytest.raises(RuntimeError):
+        with con:
+            con.execute("DELETE FROM item")
+            raise RuntimeError("roll back")
+    assert list(con.execute("SELECT count(*) FROM item")) == [(3,)]
+    with pytest.raises(dbapi.IntegrityError):
+        cur.execute("INSERT INTO item (id, name) VALUES (1, 'pen')")
+
+    con.close()
+    with pytest.raises(dbapi.InterfaceError):
+        cur.execute("SELECT 1")
+
+
+async def test_decode_mixed_columns(db: Database) -> None:
+    rows = await db.execute(
+        "SELECT NULL AS v, 1 AS n UNION ALL SELECT 1, NULL UNION ALL SELECT 'a', 2 "
+        "UNION ALL SELECT 2.5, 3 UNION ALL SELECT 'b', NULL"
+    )
+    assert [r["v"] for r in rows] == [None, 1, "a", 2.5, "b"]
+    assert [r["n"] for r in rows] == [1, None, 2, 3, None]
+
+
+async def test_tag_statements(tmp_path: pathlib.Path) -> None:
+    route: contextvars.ContextVar[str] = contextvars.ContextVar("route")
+    records: list[logging.LogRecord] = []
+    handler = logging.Handler()
+    handler.emit = records.append  # type: ignore[method-assign]
+    logging.getLogger("fust_orm.sql").addHandler(handler)
+    tag_statements(app="billing", route=route, trace_id=lambda: None)
+    try:
+        db = await Database.connect(f"sqlite://{tmp_path / 'tags.db'}?mode=rwc", echo=True)
+        await db.execute("SELECT 1 AS untagged_route")
+        route.set("/invoices/it's")
+        await db.execute("SELECT 2 AS tagged_route")
+    finally:
+        tag_statements()
+        logging.getLogger("fust_orm.sql").removeHandler(handler)
+    await db.execute("SELECT 3 AS untagged")
+    statements = [r.getMessage() for r in records]
+    assert any("SELECT 1 AS untagged_route /*app='billing'*/" in s for s in statements)
+    assert any(
+        "SELECT 2 AS tagged_route /*app='billing',route='%2Finvoices%2Fit%27s'*/" in s for s in statements
+    )
+    assert not any("SELECT 3 AS untagged /*" in s for s in statements)
+    with pytest.raises(TypeError, match="The tag 'route' must be a string, a ContextVar or a callable"):
+        tag_statements(route=1)
+
+
+async def test_audit_statements(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute(
+        "CREATE TABLE audit_log (id INTEGER PRIMARY KEY, executed_at TEXT, user_tag TEXT, "
+        "statement TEXT, params_hash TEXT, rows_affected INTEGER)"
+    )
+    user: contextvars.ContextVar[str] = contextvars.ContextVar("user")
+    records: list[dict[str, Any]] = []
+    audit_statements(table="audit_log", callback=records.append, user=user)
+    try:
+        user.set("ann")
+        await User(name="Ann", age=30, manager_id=None).save(db)
+        await db.execute(select("UPDATE user SET age = ? WHERE name = ?", 31, "Ann"))
+        await db.execute("SELECT * FROM user")
+        with pytest.raises(RuntimeError):
+            async with db.transaction() as tx:
+                await tx.execute("DELETE FROM user")
+                raise RuntimeError
+        user.set("bob")
+        await User.query(User.name == "Ann").update(db, {"age": 32})
+    finally:
+        audit_statements()
+    await db.execute("DELETE FROM user")
+
+    rows = await db.execute("SELECT * FROM audit_log ORDER BY id")
+    assert [(r["user_tag"], r["statement"].split()[0], r["rows_affected"]) for r in rows] == [
+        ("ann", "INSERT", 1),
+        ("ann", "UPDATE", 1),
+        ("bob", "UPDATE", 1),
+    ]
+    assert rows[1]["statement"] == "UPDATE user SET age = ? WHERE name = ?"
+    assert len(rows[1]["params_hash"]) == 64 and rows[1]["params_hash"] != rows[2]["params_hash"]
+    assert rows[0]["executed_at"].endswith("Z")
+    # The callback also saw the rolled back DELETE.
+    assert [r["statement"].split()[0] for r in records] == ["INSERT", "UPDATE", "DELETE", "UPDATE"]
+
+    # A statement whose record fails is rolled back with it.
+    await db.execute("INSERT INTO user (name, age) VALUES ('Cy', 50)")
+    audit_statements(table="missing_log")
+    try:
+        with pytest.raises(DatabaseError, match="audit table 'missing_log'"):
+            await db.execute("DELETE FROM user")
+    finally:
+        audit_statements()
+    assert await db.execute("SELECT name FROM user") == [{"name": "Cy"}]
+    with pytest.raises(ValueError, match="Invalid audit table"):
+        audit_statements(table="log; DROP TABLE user")
+
+    path = tmp_path / "dump.sql"
+    await db.dump(str(path))
+    records.clear()
+    audit_statements(callback=records.append)
+    try:
+        await Database.restore(str(path), f"sqlite://{tmp_path / 'copy.db'}?mode=rwc")
+    finally:
+        audit_statements()
+    assert any("'Cy'" in r["statement"] for r in records)
+
+
+async def test_rewrite_queries(db: Database) -> None:
+    await db.execute("INSERT INTO user (name, age) VALUES ('Ann', 30), ('Bob', 12), ('Cid', 50)")
+    seen: list[tuple[str | None, Any]] = []
+
+    def adults_only(query: QueryBuilder) -> QueryBuilder | None:
+        seen.append((query.table, query.model))
+        if query.model is User:
+            return query.where_(User.age >= 18)
+        return None
+
+    rewrite_queries(adults_only)
+    try:
+        assert [u.name for u in await User.query().all(db)] == ["Ann", "Cid"]
+        assert await User.query(User.age < 40).count(db) == 1
+        assert await User.query().values(db, User.name) == [{"name": "Ann"}, {"name": "Cid"}]
+        assert await User.query().update(db, {"age": F("age") + 1}) == 2
+        assert len(await db.execute(select("SELECT * FROM user"))) == 3
+    finally:
+        rewrite_queries(None)
+    assert seen[0] == ("user", User) and seen[-1] == (None, None)
+    assert [r["age"] for r in await db.execute("SELECT age FROM user ORDER BY id")] == [31, 12, 51]
+
+    rewrite_queries(lambda query: "SELECT 1")
+    try:
+        with pytest.raises(TypeError, match="must return a QueryBuilder or None"):
+            await User.query().all(db)
+    finally:
+        rewrite_queries(None)
+
+
+async def test_rewrite_statements(db: Database) -> None:
+    await db.execute("ALTER TABLE user ADD COLUMN tenant_id INTEGER")
+    await db.execute("INSERT INTO user (name, age, tenant_id) VALUES ('Ann', 30, 1), ('Bob', 40, 2)")
+    tenant: contextvars.ContextVar[int] = contextvars.ContextVar("tenant")
+
+    def scope_to_tenant(sql: str, params: list[str]) -> tuple[str, list[Any]] | None:
+        if sql.startswith("SELECT") and " FROM user" in sql and "WHERE" not in sql:
+            return f"{sql} WHERE tenant_id = ?", [*params, tenant.get()]
+        return None
+
+    rewrite_statements(scope_to_tenant)
+    try:
+        tenant.set(2)
+        assert [u.name for u in await User.query().all(db)] == ["Bob"]
+        assert await db.execute("SELECT name FROM user") == [{"name": "Bob"}]
+        assert len(await db.execute("SELECT name FROM user WHERE age > 0")) == 2
+        tenant.set(1)
+        assert await db.execute("SELECT name FROM user") == [{"name": "Ann"}]
+
+        async def without_tenant() -> Any:
+            return await db.execute("SELECT name FROM user")
+
+        with pytest.raises(DatabaseError, match="Can't rewrite the statement"):
+            await asyncio.create_task(without_tenant(), context=contextvars.Context())
+    finally:
+        rewrite_statements(None)
+    assert len(await db.execute("SELECT name FROM user")) == 2
+
+
+async def test_for_tenant(db: Database, tmp_path: pathlib.Path) -> None:
+    class Account(Model):
+        id: ColumnField[int]
+        name: ColumnField[str]
+        tenant_id: ColumnField[str]
+
+    await db.execute("CREATE TABLE account (id INTEGER PRIMARY KEY, name TEXT, tenant_id TEXT)")
+    await db.execute("INSERT INTO account (name, tenant_id) VALUES ('a1', 'acme'), ('b1', 'bolt')")
+    acme = await db.for_tenant("acme", strategy="filter")
+    assert [a.name for a in await Account.query().all(acme)] == ["a1"]
+    assert await Account.query().count(acme) == 1
+    assert await Account.query().update(acme, {"name": "a2"}) == 1
+    assert len(await acme.execute("SELECT * FROM account")) == 2
+    async with acme.transaction():
+        assert await Account.query().values(db, Account.name) == [{"name": "a2"}]
+    assert await User.query().count(acme) == 0
+
+    base = await Database.connect(f"sqlite://{tmp_path / 'app.db'}?mode=rwc")
+    tenant = await base.for_tenant("acme")
+    await tenant.execute("CREATE TABLE item (name TEXT)")
+    await tenant.execute("INSERT INTO item (name) VALUES ('widget')")
+    assert (tmp_path / "app_acme.db").exists()
+    again = await base.for_tenant("acme")
+    assert await again.execute("SELECT name FROM item") == [{"name": "widget"}]
+
+    async def connect(db: Database, tenant: str) -> Database:
+        return await base.for_tenant(tenant)
+
+    assert len(await (await db.for_tenant("acme", strategy=connect)).execute("SELECT * FROM item")) == 1
+    assert await db.for_tenant("x", strategy=lambda db, tenant: db) is not None
+    with pytest.raises(ValueError, match="only supported on PostgreSQL"):
+        await base.for_tenant("acme", strategy="schema")
+    with pytest.raises(ValueError, match="in-memory"):
+        await db.for_tenant("acme")
+    with pytest.raises(ValueError, match="Invalid tenant"):
+        await base.for_tenant("acme; DROP")
+    with pytest.raises(ValueError, match="Unknown tenant strategy"):
+        await base.for_tenant("acme", strategy="rows")
+
+
+async def test_tenant_filter_class_lookups(db: Database) -> None:
+    class Account(Model):
+        id: ColumnField[int]
+        name: ColumnField[str]
+        tenant_id: ColumnField[str]
+
+    await db.execute("CREATE TABLE account (id INTEGER PRIMARY KEY, name TEXT, tenant_id TEXT)")
+    await db.execute("INSERT INTO account (name, tenant_id) VALUES ('a1', 'acme'), ('b1', 'bolt')")
+    acme = await db.for_tenant("acme", strategy="filter")
+    assert (await Account.get(acme, 1)).name == "a1"
+    assert await Account.get(acme, 2) is None
+    assert list(await Account.in_bulk(acme, [1, 2])) == [1]
+    account, created = await Account.get_or_create(acme, name="b1")
+    assert created and account.tenant_id == "acme"
+    account, created = await Account.update_or_create(acme, name="a1", tenant_id="bolt", defaults={"name": "a3"})
+    assert created and account.tenant_id == "acme"
+    rows = await db.execute("SELECT name, tenant_id FROM account ORDER BY id")
+    assert rows == [
+        {"name": "a1", "tenant_id": "acme"},
+        {"name": "b1", "tenant_id": "bolt"},
+        {"name": "b1", "tenant_id": "acme"},
+        {"name": "a3", "tenant_id": "acme"},
+    ]
+
+
+async def test_sharded_database(tmp_path: pathlib.Path) -> None:
+    shards = [await Database.connect(f"sqlite://{tmp_path / f'shard{i}.db'}?mode=rwc") for i in range(3)]
+    for shard in shards:
+        await shard.execute("CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT, age INTEGER, manager_id INTEGER)")
+    sharded = ShardedDatabase(shards, key=User.id)
+    assert len(sharded) == 3 and sharded.key == "id"
+    for i in range(1, 31):
+        await User(id=i, name=f"u{i}", age=i).save(sharded.shard_for(i))
+    counts = [len(await shard.execute("SELECT id FROM user")) for shard in shards]
+    assert sum(counts) == 30 and all(counts)
+
+    assert await sharded.execute(User.query(User.id == 17)) == [
+        {"id": 17, "name": "u17", "age": 17, "manager_id": None}
+    ]
+    assert sharded.route(User.query(User.id == 17, User.age > 0)) is not None
+    assert await sharded.execute(select("SELECT name FROM user WHERE id = ?", 5), shard_key=5) == [{"name": "u5"}]
+    rows = await sharded.scatter_gather(User.query(User.age > 25))
+    assert sorted(r["id"] for r in rows) == [26, 27, 28, 29, 30]
+    with pytest.raises(ValueError, match="pass shard_key= or use scatter_gather"):
+        await sharded.execute(User.query(User.age > 25))
+
+    ranged = ShardedDatabase(shards[:2], key="id", ranges=[100])
+    assert await ranged.shard_for(99).execute("SELECT 1 AS one") == [{"one": 1}]
+    with pytest.raises(ValueError, match="3 shards need 2 range bounds, not 1"):
+        ShardedDatabase(shards, key="id", ranges=[100])
+    with pytest.raises(ValueError, match="ascending"):
+        ShardedDatabase(shards, key="id", ranges=[200, 100])
+    await sharded.close()
+
+
+async def test_slow_query(db: Database) -> None:
+    reported: list[tuple[str, list[str], float, str | None]] = []
+    on_slow_query(0, lambda sql, params, ms, error: reported.append((sql, params, ms, error)))
+    try:
+        await db.execute(select("SELECT name FROM user WHERE name = ?", "x" * 100))
+        with pytest.raises(DatabaseError):
+            await db.execute("SELECT * FROM missing")
+    finally:
+        on_slow_query(None)
+    [(sql, params, ms, error), failed] = reported
+    assert sql == "SELECT name FROM user WHERE name = ?"
+    assert params == ["x" * 64 + "..."]
+    assert ms >= 0 and error is None
+    assert failed[0] == "SELECT * FROM missing" and "no such table" in failed[3]
+    await db.execute("SELECT 1")
+    assert len(reported) == 2
+
+    records: list[logging.LogRecord] = []
+    handler = logging.Handler()
+    handler.emit = records.append  # type: ignore[method-assign]
+    logging.getLogger("fust_orm.sql").addHandler(handler)
+    on_slow_query(0)
+    try:
+        await db.execute("SELECT 2 AS slow")
+    finally:
+        on_slow_query(None)
+        logging.getLogger("fust_orm.sql").removeHandler(handler)
+    assert any(r.levelno == logging.WARNING and "SELECT 2 AS slow" in r.getMessage() for r in records)
+    with pytest.raises(ValueError, match="zero or more milliseconds"):
+        on_slow_query(-1)
+
+
+async def test_tracing(db: Database) -> None:
+    pytest.importorskip("opentelemetry.sdk")
+    from opentelemetry.sdk.trace import TracerProvider
+    from opentelemetry.sdk.trace.export import SimpleSpanProcessor
+    from opentelemetry.sdk.trace.export.in_memory_span_exporter import InMemorySpanExporter
+    from opentelemetry.trace import SpanKind, StatusCode
+
+    exporter = InMemorySpanExporter()
+    provider = TracerProvider()
+    provider.add_span_processor(SimpleSpanProcessor(exporter))
+    instrument(tracer_provider=provider)
+    try:
+        with provider.get_tracer("test").start_as_current_span("request") as request:
+            await User.bulk_create(db, [User(name="Traced", age=30, manager_id=None)])
+            await db.execute(select("SELECT name FROM user WHERE name = ?", "Traced"))
+            with pytest.raises(ValueError):
+                await db.execute("SELECT * FROM missing")
+    finally:
+        uninstrument()
+    await db.execute("SELECT 1")
+
+    spans = exporter.get_finished_spans()
+    assert [s.name for s in spans] == ["INSERT", "transaction", "SELECT", "SELECT", "request"]
+    insert, transaction, query, failed, _ = spans
+    assert query.kind == SpanKind.CLIENT
+    assert query.attributes["db.system"] == "sqlite"
+    assert query.attributes["db.statement"] == "SELECT name FROM user WHERE name = ?"
+    assert query.attributes["db.response.returned_rows"] == 1
+    assert insert.attributes["db.response.returned_rows"] == 1
+    assert transaction.status.status_code == StatusCode.UNSET
+    assert failed.status.status_code == StatusCode.ERROR
+    assert all(s.parent.span_id == request.get_span_context().span_id for s in spans[:4])
+
+
+async def test_statement_cache(db: Database) -> None:
+    # The same query renders the same SQL, and IN lists of similar lengths
+    # share a statement.
+    executed: list[tuple[str, list[str]]] = []
+    on_slow_query(0, lambda sql, params, ms, error: executed.append((sql, params)))
+    try:
+        for ages in ([30], [30, 25, 40], [30, 25, 40, 35]):
+            await db.execute(select(User.name, User.id, User.manager_id, User.age.in_(ages)))
+    finally:
+        on_slow_query(None)
+    assert [sql for sql, _ in executed] == [
+        "SELECT name, id, manager_id FROM user WHERE age IN (?)",
+        "SELECT name, id, manager_id FROM user WHERE age IN (?, ?, ?, ?)",
+        "SELECT name, id, manager_id FROM user WHERE age IN (?, ?, ?, ?)",
+    ]
+    assert executed[1][1] == ["30", "25", "40", "40"]
+    with pytest.raises(ValueError, match="statement_cache_capacity isn't supported on SQLite"):
+        await Database.connect("sqlite::memory:", statement_cache_capacity=10)
+
+
+def _samples(name: str) -> dict[tuple[tuple[str, str], ...], float]:
+    [family] = [f for f in metrics() if f["name"] == name]
+    return {tuple(sorted([*s["labels"].items(), ("sample", s["name"])])): s["value"] for s in family["samples"]}
+
+
+async def test_metrics(db: Database) -> None:
+    families = {f["name"]: f["type"] for f in metrics()}
+    assert families == {
+        "fust_orm_queries": "counter",
+        "fust_orm_query_errors": "counter",
+        "fust_orm_query_duration_seconds": "histogram",
+        "fust_orm_pool_wait_seconds": "histogram",
+    }
+    selects = (("sample", "fust_orm_queries_total"), ("table", "user"), ("verb", "SELECT"))
+    errors = (("sample", "fust_orm_query_errors_total"), ("table", "missing"), ("verb", "SELECT"))
+    waits = (("sample", "fust_orm_pool_wait_seconds_count"),)
+    before = {**_samples("fust_orm_queries"), **_samples("fust_orm_query_errors"), **_samples("fust_orm_pool_wait_seconds")}
+
+    await db.execute(select(User.name))
+    await db.execute(select(User.name, User.age > 20))
+    with pytest.raises(ValueError):
+        await db.execute("SELECT * FROM missing")
+
+    assert _samples("fust_orm_queries")[selects] == before.get(selects, 0) + 2
+    assert _samples("fust_orm_query_errors")[errors] == before.get(errors, 0) + 1
+    assert _samples("fust_orm_pool_wait_seconds")[waits] == before[waits] + 3
+    durations = _samples("fust_orm_query_duration_seconds")
+    inf = (("le", "+Inf"), ("sample", "fust_orm_query_duration_seconds_bucket"), ("verb", "SELECT"))
+    count = (("sample", "fust_orm_query_duration_seconds_count"), ("verb", "SELECT"))
+    assert durations[inf] == durations[count] >= 3
+
+
 def test_model_structure() -> None:
     assert isinstance(User.id, ColumnField)
     assert isinstance(User.name, ColumnField)
@@ -93,6 +1192,21 @@ async def test_select_function_with_raw_sql_and_params(db: Database) -> None:
     assert result[0]["name"] == "Alice"
 
 
+async def test_raw_sql_named_params(db: Database) -> None:
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Alice', 30), (2, 'Bob', 25), (3, 'Carl', 35)")
+
+    query = select(
+        "SELECT name, ':age' AS text /*/ :x */ FROM user -- :skipped\n"
+        "WHERE age >= :age AND (name = :name OR :name = 'all') AND CAST(id AS TEXT) <> :id ORDER BY id",
+        {"age": 25, "name": "all", "id": "3", "unused": 1},
+    )
+    assert await db.execute(query) == [{"name": "Alice", "text": ":age"}, {"name": "Bob", "text": ":age"}]
+    assert await query.count(db) == 2
+
+    with pytest.raises(ValueError, match="':name'"):
+        await db.execute(select("SELECT * FROM user WHERE name = :name", {"age": 1}))
+
+
 async def test_select_all_from_model(db: Database) -> None:
     await db.execute(
         """
@@ -270,3 +1384,2532 @@ async def test_select_from_multiple_tables_raises_error() -> None:
 async def test_select_with_no_arguments_raises_error() -> None:
     with pytest.raises(ValueError):
         select()
+
+
+async def test_save_inserts_and_starts_version(db: Database) -> None:
+    doc = Document(title="Draft")
+    await doc.save(db)
+    assert doc.id == 1
+    assert doc.version == 1
+
+    loaded = await Document.get(db, 1)
+    assert loaded is not None
+    assert loaded.title == "Draft"
+    assert loaded.version == 1
+
+
+async def test_save_increments_version(db: Database) -> None:
+    doc = Document(title="Draft")
+    await doc.save(db)
+    doc.title = "Final"
+    await doc.save(db)
+    assert doc.version == 2
+
+    rows = await db.execute("SELECT title, version FROM document")
+    assert rows == [{"title": "Final", "version": 2}]
+
+
+async def test_stale_save_raises_stale_data_error(db: Database) -> None:
+    await Document(title="Draft").save(db)
+    first = await Document.get(db, 1)
+    second = await Document.get(db, 1)
+    assert first is not None and second is not None
+
+    first.title = "Edited by first"
+    await first.save(db)
+
+    second.title = "Edited by second"
+    with pytest.raises(StaleDataError):
+        await second.save(db)
+
+    rows = await db.execute("SELECT title, version FROM document")
+    assert rows == [{"title": "Edited by first", "version": 2}]
+
+
+async def test_get_missing_row_returns_none(db: Database) -> None:
+    assert await Document.get(db, 42) is None
+
+
+def test_model_meta_options() -> None:
+    class Account(Model):
+        id: ColumnField[int]
+        tenant_id: ColumnField[int]
+        email: ColumnField[str]
+        last_name: ColumnField[str]
+        first_name: ColumnField[str]
+
+        class Meta:
+            table_name = "accounts"
+            indexes = ["email", ("last_name", "first_name")]
+            unique_together = [("tenant_id", "email")]
+
+    assert Account.__table_name__ == "accounts"
+    assert Account.id.table_name == "accounts"
+    assert Account.__indexes__ == (("email",), ("last_name", "first_name"))
+    assert Account.__unique_together__ == (("tenant_id", "email"),)
+
+
+def test_model_meta_rejects_unknown_columns_and_options() -> None:
+    with pytest.raises(ValueError, match="unknown column 'missing'"):
+
+        class BadIndex(Model):
+            id: ColumnField[int]
+
+            class Meta:
+                indexes = [("id", "missing")]
+
+    with pytest.raises(ValueError, match="Unknown Meta option 'ordering'"):
+
+        class BadOption(Model):
+            id: ColumnField[int]
+
+            class Meta:
+                ordering = ["id"]
+
+
+async def test_default_scope_and_named_scopes(db: Database) -> None:
+    await db.execute(
+        """
+        INSERT INTO user_account (id, name, age, is_active) VALUES
+        (1, 'Alice', 30, 1),
+        (2, 'Bob', 25, 0),
+        (3, 'Charlie', 35, 1);
+        """
+    )
+    active = await db.execute(Member.query())
+    assert sorted(r["name"] for r in active) == ["Alice", "Charlie"]
+
+    everyone = await db.execute(Member.unscoped())
+    assert len(everyone) == 3
+
+    older = await db.execute(Member.older_than(32))
+    assert [r["name"] for r in older] == ["Charlie"]
+
+    named = await db.execute(Member.named("Alice", "Bob"))
+    assert [r["name"] for r in named] == ["Alice"]
+
+    filtered = await db.execute(Member.query(Member.age < 32))
+    assert [r["name"] for r in filtered] == ["Alice"]
+
+
+async def test_query_merge_and_apply(db: Database) -> None:
+    await db.execute(
+        "INSERT INTO user_account (id, name, age, is_active) VALUES "
+        "(1, 'Alice', 30, 1), (2, 'Bob', 25, 0), (3, 'Charlie', 35, 1), (4, 'Dora', 40, 1)"
+    )
+    adults = Member.query(Member.age >= 30)
+    with db.record_queries() as rec:
+        merged = Member.query().merge(adults).merge(Member.older_than(32))
+        assert [m.name for m in await merged.all(db)] == ["Charlie", "Dora"]
+    # The default scope of the three queries is rendered once.
+    assert rec.queries[0]["sql"].count("is_active") == 1
+    assert rec.queries[0]["params"] == ["1", "30", "32"]
+
+    names = select(Member.name).merge(Member.query(Member.age > 32))
+    assert await db.execute(names) == [{"name": "Charlie"}, {"name": "Dora"}]
+    assert [m.name for m in await adults.merge(Member.query().only(Member.name)).all(db)] == [
+        "Alice",
+        "Charlie",
+        "Dora",
+    ]
+    with pytest.raises(ValueError, match="can't combine queries of 'user_account' and 'user'"):
+        Member.query().merge(User.query())
+    with pytest.raises(ValueError, match="can't combine raw SQL queries"):
+        select("SELECT 1").merge(adults)
+    with pytest.raises(ValueError, match="can't combine two row locks"):
+        adults.for_update().merge(Member.query().for_share())
+
+    def aged_between(query: Any, low: int, high: int) -> Any:
+        return query.where_(Member.age >= low, Member.age < high)
+
+    between = Member.query().apply(aged_between, 30, high=36)
+    assert [m.name for m in await between.all(db)] == ["Alice", "Charlie"]
+    with pytest.raises(TypeError, match="must return a QueryBuilder, not NoneType"):
+        Member.query().apply(lambda query: None)
+
+
+async def test_single_table_inheritance(db: Database) -> None:
+    assert Car.__table_name__ == "vehicle"
+    assert Car.__columns__ == ("id", "kind", "name", "doors")
+
+    await Car(name="Beetle", doors=2).save(db)
+    await Truck(name="Actros", payload=18).save(db)
+    await Vehicle(name="Unicycle").save(db)
+
+    rows = await db.execute("SELECT kind FROM vehicle ORDER BY id")
+    assert [r["kind"] for r in rows] == ["car", "lorry", "vehicle"]
+
+    vehicles = await Vehicle.query().all(db)
+    assert [type(v) for v in vehicles] == [Car, Truck, Vehicle]
+    assert isinstance(vehicles[0], Car) and vehicles[0].doors == 2
+
+    cars = await Car.query().all(db)
+    assert [c.name for c in cars] == ["Beetle"]
+
+    assert isinstance(await Vehicle.get(db, 2), Truck)
+    assert await Car.get(db, 2) is None
+
+
+async def test_composite_primary_key(db: Database) -> None:
+    assert Membership.__primary_key__ == ("tenant_id", "user_id")
+
+    await Membership(tenant_id=1, user_id=7, role="member").save(db)
+    await Membership(tenant_id=2, user_id=7, role="owner").save(db)
+
+    membership = await Membership.get(db, (1, 7))
+    assert membership is not None
+    assert membership.pk == (1, 7)
+    assert membership.role == "member"
+
+    membership.role = "admin"
+    await membership.save(db)
+    rows = await db.execute("SELECT tenant_id, role FROM membership ORDER BY tenant_id")
+    assert rows == [{"tenant_id": 1, "role": "admin"}, {"tenant_id": 2, "role": "owner"}]
+
+    with pytest.raises(ValueError, match="composite"):
+        await Membership.get(db, 1)
+
+
+def test_to_dict_include_exclude() -> None:
+    user = User(id=1, name="Alice", age=30)
+    assert user.to_dict() == {"id": 1, "name": "Alice", "age": 30, "manager_id": None}
+    assert user.to_dict(include=["id", "name"]) == {"id": 1, "name": "Alice"}
+    assert user.to_dict(exclude=["manager_id", "age"]) == {"id": 1, "name": "Alice"}
+
+
+def test_to_json_handles_nested_models_and_special_types() -> None:
+    doc = Document(id=1, title="Spec", version=3)
+    author = User(id=7, name="Bob")
+    created = datetime.datetime(2024, 5, 1, 12, 30)
+    token = uuid.UUID("12345678-1234-5678-1234-567812345678")
+    doc.author = author  # type: ignore[attr-defined]
+    doc.meta = {"created": created, "token": token, "raw": b"hi"}  # type: ignore[attr-defined]
+
+    payload = json.loads(doc.to_json(include=["id", "author", "meta"]))
+    assert payload == {
+        "id": 1,
+        "author": {"id": 7, "name": "Bob", "age": None, "manager_id": None},
+        "meta": {
+            "created": "2024-05-01T12:30:00",
+            "token": "12345678-1234-5678-1234-567812345678",
+            "raw": "aGk=",
+        },
+    }
+    assert doc.to_json(include=["id"], indent=2) == '{\n  "id": 1\n}'
+
+
+async def test_bulk_create_assigns_keys_in_batches(db: Database) -> None:
+    docs = [Document(title=f"Doc {i}") for i in range(5)]
+    created = await Document.bulk_create(db, docs, batch_size=2)
+    assert created == docs
+    assert [doc.id for doc in docs] == [1, 2, 3, 4, 5]
+    assert all(doc.version == 1 for doc in docs)
+
+    await Membership.bulk_create(
+        db,
+        [
+            Membership(tenant_id=1, user_id=1, role="owner"),
+            Membership(tenant_id=1, user_id=2, role="member"),
+        ],
+    )
+    rows = await db.execute("SELECT user_id, role FROM membership ORDER BY user_id")
+    assert rows == [{"user_id": 1, "role": "owner"}, {"user_id": 2, "role": "member"}]
+
+    await User.bulk_create(db, [User(id=10, name="Alice"), User(name="Bob", age=30)])
+    rows = await db.execute("SELECT id, name, age FROM user ORDER BY id")
+    assert rows == [
+        {"id": 10, "name": "Alice", "age": None},
+        {"id": 11, "name": "Bob", "age": 30},
+    ]
+
+    with pytest.raises(ValueError, match="already saved"):
+        await Document.bulk_create(db, [docs[0]])
+
+
+async def test_bulk_create_rolls_back_on_error(db: Database) -> None:
+    with pytest.raises(ValueError):
+        await Product.bulk_create(
+            db,
+            [Product(product_name="Laptop"), Product(product_name=None)],
+            batch_size=1,
+        )
+    assert await db.execute("SELECT * FROM product") == []
+
+
+async def test_bulk_update(db: Database) -> None:
+    docs = await Document.bulk_create(db, [Document(title=f"Doc {i}") for i in range(3)])
+    for doc in docs:
+        doc.title = doc.title.upper()
+    assert await Document.bulk_update(db, docs, ["title"], batch_size=2) == 3
+    assert [doc.version for doc in docs] == [2, 2, 2]
+
+    rows = await db.execute("SELECT title, version FROM document ORDER BY id")
+    assert rows == [{"title": f"DOC {i}", "version": 2} for i in range(3)]
+
+    with pytest.raises(ValueError, match="primary key or version"):
+        await Document.bulk_update(db, docs, ["version"])
+
+
+async def test_bulk_update_with_stale_row_updates_nothing(db: Database) -> None:
+    docs = await Document.bulk_create(db, [Document(title="A"), Document(title="B")])
+    concurrent = await Document.get(db, 2)
+    assert concurrent is not None
+    concurrent.title = "Changed elsewhere"
+    await concurrent.save(db)
+
+    for doc in docs:
+        doc.title = "Bulk"
+    with pytest.raises(StaleDataError):
+        await Document.bulk_update(db, docs, ["title"])
+
+    rows = await db.execute("SELECT title FROM document ORDER BY id")
+    assert rows == [{"title": "A"}, {"title": "Changed elsewhere"}]
+
+
+async def test_lifecycle_signals(db: Database) -> None:
+    events: list[tuple[str, str, Any]] = []
+
+    def on_pre_save(sender: type, instance: Document, created: bool) -> None:
+        instance.title = instance.title.strip()
+        events.append(("pre_save", sender.__name__, created))
+
+    def on_post_save(sender: type, instance: Document, created: bool) -> None:
+        events.append(("post_save", sender.__name__, (created, instance.id)))
+
+    def on_delete(sender: type, instance: Model) -> None:
+        events.append(("delete", sender.__name__, instance.pk))
+
+    pre_save.connect(on_pre_save, sender=Document)
+    post_save.connect(on_post_save, sender=Document)
+    pre_delete.connect(on_delete)
+    post_delete.connect(on_delete)
+    try:
+        await Product(product_name="Laptop").save(db)
+        doc = Document(title="  Draft  ")
+        await doc.save(db)
+        doc.title = "Final"
+        await doc.save(db)
+        await doc.delete(db)
+    finally:
+        assert pre_save.disconnect(on_pre_save)
+        assert post_save.disconnect(on_post_save)
+        assert pre_delete.disconnect(on_delete)
+        assert post_delete.disconnect(on_delete)
+    assert not pre_save.disconnect(on_pre_save)
+
+    assert events == [
+        ("pre_save", "Document", True),
+        ("post_save", "Document", (True, 1)),
+        ("pre_save", "Document", False),
+        ("post_save", "Document", (False, 1)),
+        ("delete", "Document", 1),
+        ("delete", "Document", 1),
+    ]
+    assert await db.execute("SELECT * FROM document") == []
+
+
+async def test_pre_save_handler_can_abort_save(db: Database) -> None:
+    @pre_save.connect
+    def reject(sender: type, instance: Model, created: bool) -> None:
+        raise RuntimeError("read-only")
+
+    try:
+        with pytest.raises(RuntimeError, match="read-only"):
+            await Product(product_name="Laptop").save(db)
+    finally:
+        pre_save.disconnect(reject)
+    assert await db.execute("SELECT * FROM product") == []
+
+
+async def test_delete_stale_row_raises_stale_data_error(db: Database) -> None:
+    await Document(title="Draft").save(db)
+    first = await Document.get(db, 1)
+    second = await Document.get(db, 1)
+    assert first is not None and second is not None
+    first.title = "Edited"
+    await first.save(db)
+
+    with pytest.raises(StaleDataError):
+        await second.delete(db)
+    await first.delete(db)
+    assert await db.execute("SELECT * FROM document") == []
+
+
+async def test_enum_columns(db: Database) -> None:
+    assert Ticket.status.enum_type is Status
+    assert Ticket.id.enum_type is None
+
+    await Ticket(status=Status.OPEN, priority=Priority.HIGH).save(db)
+    await Ticket(status="closed").save(db)
+    rows = await db.execute("SELECT status, priority FROM ticket ORDER BY id")
+    assert rows == [{"status": "open", "priority": 2}, {"status": "closed", "priority": None}]
+
+    ticket = await Ticket.get(db, 1)
+    assert ticket is not None
+    assert ticket.status is Status.OPEN
+    assert ticket.priority is Priority.HIGH
+
+    closed = await Ticket.query(Ticket.status == Status.CLOSED).all(db)
+    assert [t.id for t in closed] == [2]
+    both = await Ticket.query(Ticket.status.in_(["open", Status.CLOSED])).all(db)
+    assert len(both) == 2
+
+    with pytest.raises(ValueError, match="not a valid Status"):
+        Ticket.status == "archived"
+    with pytest.raises(ValueError, match="Allowed values: 'open', 'closed'"):
+        await Ticket(status="archived").save(db)
+
+
+async def test_many_to_many(db: Database) -> None:
+    assert Article.tags.through_table == "article_tags"
+    python, rust, sql = await Tag.bulk_create(
+        db, [Tag(name="python"), Tag(name="rust"), Tag(name="sql")]
+    )
+    first, second = await Article.bulk_create(db, [Article(title="First"), Article(title="Second")])
+
+    await first.tags.add(db, python, rust)
+    await first.tags.add(db, python, sql.id)  # existing links are ignored
+    await second.tags.add(db, sql)
+    assert sorted(t.name for t in await first.tags.all(db)) == ["python", "rust", "sql"]
+    assert len(first.tags) == 3
+
+    await first.tags.remove(db, rust)
+    assert sorted(t.name for t in first.tags) == ["python", "sql"]
+    await second.tags.clear(db)
+    assert list(second.tags) == []
+
+    articles = await Article.query().all(db)
+    with pytest.raises(ValueError, match="isn't loaded"):
+        len(articles[0].tags)
+    await Article.prefetch_related(db, articles, "tags")
+    assert {a.title: sorted(t.name for t in a.tags) for a in articles} == {
+        "First": ["python", "sql"],
+        "Second": [],
+    }
+
+    with pytest.raises(AttributeError):
+        first.tags = []  # type: ignore[misc]
+    with pytest.raises(ValueError, match="unsaved"):
+        await Article(title="Draft").tags.add(db, python)
+
+
+def test_many_to_many_through_table_sql() -> None:
+    class Person(Model):
+        id: ColumnField[str]
+        friends: ManyToMany["Person"] = ManyToMany("self", through="friendship")
+
+    assert Person.friends.through_table_sql() == (
+        "CREATE TABLE IF NOT EXISTS friendship ("
+        "from_person_id TEXT NOT NULL REFERENCES person (id) ON DELETE CASCADE, "
+        "to_person_id TEXT NOT NULL REFERENCES person (id) ON DELETE CASCADE, "
+        "PRIMARY KEY (from_person_id, to_person_id))"
+    )
+
+
+async def test_get_or_create(db: Database) -> None:
+    class Label(Model):
+        id: ColumnField[int]
+        name: ColumnField[str]
+
+    # The unique name lets the racing inserts below conflict.
+    await db.execute("CREATE TABLE label (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE)")
+    label, created = await Label.get_or_create(db, name="python")
+    assert created and label.id == 1
+
+    same, created = await Label.get_or_create(db, name="python")
+    assert not created and same.id == 1
+
+    results = await asyncio.gather(*(Label.get_or_create(db, name="rust") for _ in range(3)))
+    assert sum(created for _, created in results) == 1
+    assert len({label.id for label, _ in results}) == 1
+
+    with pytest.raises(ValueError, match="not a column"):
+        await Label.get_or_create(db, title="python")
+
+
+async def test_update_or_create(db: Database) -> None:
+    doc, created = await Document.update_or_create(db, title="Spec", defaults={"id": 5})
+    assert created and (doc.id, doc.version) == (5, 1)
+
+    doc, created = await Document.update_or_create(db, defaults={"title": "Final"}, id=5)
+    assert not created
+    assert (doc.title, doc.version) == ("Final", 2)
+    assert await db.execute("SELECT title, version FROM document") == [
+        {"title": "Final", "version": 2}
+    ]
+
+
+async def test_update_with_f_expressions(db: Database) -> None:
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30), (2, 'Bob', 40)")
+
+    updated = await User.query(User.id == 1).update(db, {"age": F("age") + 1})
+    assert updated == 1
+    await asyncio.gather(*(User.query().update(db, {"age": F("age") * 2}) for _ in range(2)))
+    assert await db.execute("SELECT age FROM user ORDER BY id") == [{"age": 124}, {"age": 160}]
+
+    await Document(id=1, title="Spec").save(db)
+    await Document.query().update(db, {"title": "Final"})
+    assert await db.execute("SELECT title, version FROM document") == [
+        {"title": "Final", "version": 2}
+    ]
+
+    with pytest.raises(ValueError, match="not a column"):
+        await User.query().update(db, {"email": "x"})
+
+
+async def test_where_with_f_expressions(db: Database) -> None:
+    await db.execute(
+        "INSERT INTO user (id, name, age, manager_id) VALUES (1, 'Ann', 30, 2), (2, 'Bob', 40, 1)"
+    )
+
+    users = await User.query().where_(F("manager_id") > F("id")).all(db)
+    assert [u.name for u in users] == ["Ann"]
+    users = await User.query(F("age") - F("manager_id") * 10 == 30).all(db)
+    assert [u.name for u in users] == ["Bob"]
+    users = await User.query(User.age > F("id") * 20).all(db)
+    assert [u.name for u in users] == ["Ann"]
+
+
+async def test_values_and_values_list(db: Database) -> None:
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30), (2, 'Bob', 40)")
+
+    assert await User.query(User.age > 35).values(db, User.name, "age") == [
+        {"name": "Bob", "age": 40}
+    ]
+    assert await User.query(User.id == 1).values(db) == [
+        {"id": 1, "name": "Ann", "age": 30, "manager_id": None}
+    ]
+    assert await select(User.name).values(db) == [{"name": "Ann"}, {"name": "Bob"}]
+    assert await User.query().values_list(db, User.age, User.id) == [(30, 1), (40, 2)]
+    assert await User.query().values_list(db, "name", flat=True) == ["Ann", "Bob"]
+
+    with pytest.raises(ValueError, match="exactly one column"):
+        await User.query().values_list(db, "id", "name", flat=True)
+    with pytest.raises(ValueError, match="can't be selected"):
+        await User.query().values(db, Product.id)
+
+
+async def test_result_limits(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30), (2, 'Bob', 40), (3, 'Cat', 50)")
+
+    with pytest.raises(DatabaseError, match=r"more than 2 rows \(max_rows\); add a LIMIT"):
+        await db.execute("SELECT * FROM user", max_rows=2)
+    assert len(await db.execute("SELECT * FROM user", max_rows=3)) == 3
+    with pytest.raises(DatabaseError, match="max_rows"):
+        await User.query().all(db, max_rows=1)
+    assert len(await User.query(User.id == 1).all(db, max_rows=1)) == 1
+    with pytest.raises(DatabaseError, match=r"more than 5 bytes in 2 rows \(max_result_bytes\)"):
+        await User.query().values_list(db, "name", flat=True, max_result_bytes=5)
+    with pytest.raises(DatabaseError, match="max_rows"):
+        await User.query().values(db, "name", max_rows=0)
+
+    limited = await Database.connect(f"sqlite://{tmp_path / 'limited.db'}?mode=rwc", max_rows=2)
+    await limited.execute("CREATE TABLE item (id INTEGER PRIMARY KEY)")
+    await limited.execute("INSERT INTO item (id) VALUES (1), (2), (3)")
+    with pytest.raises(DatabaseError, match="more than 2 rows"):
+        await limited.execute("SELECT id FROM item")
+    # A limit of the call overrides the one of the database.
+    assert len(await limited.execute("SELECT id FROM item", max_rows=10)) == 3
+    assert await limited.execute("SELECT COUNT(*) AS n FROM item") == [{"n": 3}]
+    await limited.close()
+
+
+async def test_annotate(db: Database) -> None:
+    article = Article(id=1, title="Rust")
+    await article.save(db)
+    tags = [Tag(id=1, name="systems"), Tag(id=2, name="fast")]
+    await Tag.bulk_create(db, tags)
+    await article.tags.add(db, *tags)
+    await Article(id=2, title="Go").save(db)
+
+    articles = await Article.query().annotate(
+        tag_count="SELECT count(*) FROM article_tags t WHERE t.article_id = article.id",
+        double_id=F("id") * 2,
+    ).all(db)
+    assert [(a.title, a.tag_count, a.double_id) for a in articles] == [
+        ("Rust", 2, 2),
+        ("Go", 0, 4),
+    ]
+
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30), (2, 'Bob', 12)")
+    rows = await (
+        select(User.name)
+        .annotate(age_group="CASE WHEN age >= 18 THEN 'adult' ELSE 'minor' END")
+        .values(db)
+    )
+    assert rows == [{"name": "Ann", "age_group": "adult"}, {"name": "Bob", "age_group": "minor"}]
+
+    with pytest.raises(ValueError, match="conflicts with a column"):
+        User.query().annotate(age=F("age") + 1)
+
+
+async def test_count_and_exists(db: Database) -> None:
+    assert await User.query().count(db) == 0
+    assert not await User.query().exists(db)
+
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30), (2, 'Bob', 40)")
+    assert await User.query().count(db) == 2
+    assert await User.query(User.age > 35).count(db) == 1
+    assert await User.query(User.age > 35).exists(db)
+    assert not await User.query(User.age > 50).exists(db)
+    assert await select("SELECT * FROM user WHERE age < ?;", 35).count(db) == 1
+
+
+async def test_paginate(db: Database) -> None:
+    await db.execute(
+        "INSERT INTO user (id, name, age) VALUES (5, 'Eve', 50), (1, 'Ann', 30), (3, 'Cat', 20), "
+        "(2, 'Bob', 40), (4, 'Dan', 60)"
+    )
+    page = await db.paginate(User.query(), 1, 2)
+    assert [u.name for u in page] == ["Ann", "Bob"] and isinstance(page.items[0], User)
+    assert (page.total, page.page, page.per_page, page.pages) == (5, 1, 2, 3)
+    assert (page.has_prev, page.has_next, len(page)) == (False, True, 2)
+    last = await db.paginate(User.query(), page=3, per_page=2)
+    assert [u.name for u in last] == ["Eve"] and (last.has_prev, last.has_next) == (True, False)
+    assert repr(last) == "<Page page=3 of 3 per_page=2 total=5>"
+
+    adults = await db.paginate(User.query(User.age >= 40), per_page=2)
+    assert [u.id for u in adults] == [2, 4] and adults.total == 3
+    raw = await db.paginate(select("SELECT name FROM user WHERE age < ? ORDER BY age;", 45), page=2, per_page=1)
+    assert raw.to_dict() == {"items": [{"name": "Ann"}], "total": 3, "page": 2, "per_page": 1, "pages": 3}
+    commented = await db.paginate("SELECT name FROM user ORDER BY age -- youngest first", per_page=1)
+    assert [row["name"] for row in commented] == ["Cat"] and commented.total == 5
+    assert await select("SELECT name FROM user -- everyone").count(db) == 5
+    empty = await db.paginate("SELECT * FROM user WHERE age > 100", page=4)
+    assert (empty.items, empty.total, empty.pages, empty.has_next) == ([], 0, 1, False)
+
+    with pytest.raises(ValueError, match="page and per_page must be at least 1"):
+        await db.paginate(User.query(), page=0)
+
+
+async def test_defer_and_only(db: Database) -> None:
+    await Document(id=1, title="Spec").save(db)
+
+    (doc,) = await Document.query().defer(Document.title).all(db)
+    assert doc.deferred_columns == ["title"]
+    assert doc.to_dict() == {"id": 1, "version": 1}
+    await doc.save(db)
+    with bind(db):
+        assert await doc.title == "Spec"
+    assert (doc.title, doc.version, doc.deferred_columns) == ("Spec", 2, [])
+    (doc,) = await Document.query().defer(Document.title).all(db)
+    await doc.load_deferred(db)
+    assert doc.title == "Spec"
+
+    (doc,) = await Document.query().only("title").all(db)
+    assert doc.deferred_columns == []
+
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30)")
+    (user,) = await User.query().only(User.name).all(db)
+    assert sorted(user.deferred_columns) == ["age", "manager_id"]
+    user.name = "Anna"
+    await user.save(db)
+    assert await db.execute("SELECT name, age FROM user") == [{"name": "Anna", "age": 30}]
+
+    with pytest.raises(ValueError, match="can't be deferred"):
+        Document.query().defer("version")
+
+
+async def test_for_update(db: Database) -> None:
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30)")
+
+    # SQLite has no row locks; the clause is left out.
+    users = await User.query(User.id == 1).for_update(skip_locked=True).all(db)
+    assert [u.name for u in users] == ["Ann"]
+    assert await User.query().for_update(nowait=True).values_list(db, "id", flat=True) == [1]
+
+    with pytest.raises(ValueError, match="not both"):
+        User.query().for_update(skip_locked=True, nowait=True)
+    with pytest.raises(ValueError, match="raw SQL"):
+        select("SELECT * FROM user").for_update()
+
+
+async def test_row_lock_modifiers(db: Database) -> None:
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30)")
+
+    users = await User.query(User.id == 1).for_share().nowait().all(db)
+    assert [u.name for u in users] == ["Ann"]
+    assert await User.query().for_update().skip_locked().values_list(db, "id", flat=True) == [1]
+    User.query().for_update(skip_locked=True).skip_locked()
+
+    with pytest.raises(ValueError, match="call for_update\\(\\) or for_share\\(\\) first"):
+        User.query().skip_locked()
+    with pytest.raises(ValueError, match="can't be combined with skip_locked"):
+        User.query().for_share().skip_locked().nowait()
+    with pytest.raises(ValueError, match="not both"):
+        User.query().for_share(skip_locked=True, nowait=True)
+    with pytest.raises(ValueError, match="for_share\\(\\) can't lock a raw SQL query"):
+        select("SELECT * FROM user").for_share()
+
+
+async def test_ilike_and_regex(db: Database) -> None:
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30), (2, 'bob', 25), (3, 'ANDY', 40)")
+
+    users = await User.query(User.name.ilike("an%")).all(db)
+    assert sorted(u.id for u in users) == [1, 3]
+    assert await User.query(User.name.regex("^[A-Z][a-z]+$")).values_list(db, "id", flat=True) == [1]
+    assert await User.query(User.name.iregex("^an")).count(db) == 2
+    assert await User.query(User.name.regex("b$")).update(db, {"age": 26}) == 1
+    assert await db.execute("SELECT id FROM user WHERE name REGEXP 'o'") == [{"id": 2}]
+    assert await db.execute("SELECT NULL REGEXP 'o' AS m") == [{"m": None}]
+
+    # A function of the same name replaces the built-in one.
+    db.create_function("regexp", 2, lambda pattern, value: pattern == value)
+    assert await User.query(User.name.regex("bob")).values_list(db, "id", flat=True) == [2]
+
+
+async def test_on_delete_behaviors(db: Database) -> None:
+    class Author(Model):
+        id: ColumnField[int]
+
+    class Book(Model):
+        id: ColumnField[int]
+        author_id: ColumnField[int] = ColumnField(references=Author, on_delete="cascade")
+
+    class Chapter(Model):
+        id: ColumnField[int]
+        book_id: ColumnField[int] = ColumnField(references=Book, on_delete="cascade")
+
+    class Review(Model):
+        id: ColumnField[int]
+        author_id: ColumnField[int] = ColumnField(references=Author, on_delete="python")
+
+    class Fan(Model):
+        id: ColumnField[int]
+        author_id: ColumnField[int] = ColumnField(references=Author, on_delete="set_null")
+
+    class Award(Model):
+        id: ColumnField[int]
+        author_id: ColumnField[int] = ColumnField(references=Author, on_delete="restrict")
+
+    assert Book.author_id.references is Author
+    assert ColumnField(references=Author).on_delete == "restrict"
+    assert Book.author_id.references_sql() == "REFERENCES author (id) ON DELETE CASCADE"
+    assert Review.author_id.references_sql() == "REFERENCES author (id)"
+    await db.execute("CREATE TABLE author (id INTEGER PRIMARY KEY)")
+    for model in (Book, Chapter, Review, Fan, Award):
+        column = model.__columns__[1]
+        fk = getattr(model, column)
+        await db.execute(
+            f"CREATE TABLE {model.__table_name__} (id INTEGER PRIMARY KEY, {column} INTEGER {fk.references_sql()})"
+        )
+
+    await Author.bulk_create(db, [Author(id=1), Author(id=2)])
+    await Book(id=1, author_id=1).save(db)
+    await Chapter(id=1, book_id=1).save(db)
+    await Review(id=1, author_id=1).save(db)
+    await Fan(id=1, author_id=1).save(db)
+    await Award(id=1, author_id=2).save(db)
+
+    deleted: list[Any] = []
+    handler = post_delete.connect(lambda sender, instance: deleted.append(instance), sender=Review)
+    try:
+        author = await Author.get(db, 1)
+        await author.delete(db)
+    finally:
+        post_delete.disconnect(handler)
+    assert [(type(r).__name__, r.id) for r in deleted] == [("Review", 1)]
+    assert await db.execute("SELECT count(*) AS n FROM book") == [{"n": 0}]
+    assert await db.execute("SELECT count(*) AS n FROM chapter") == [{"n": 0}]
+    assert await db.execute("SELECT author_id FROM fan") == [{"author_id": None}]
+
+    author = await Author.get(db, 2)
+    with pytest.raises(ValueError, match="referenced by 'award.author_id'"):
+        await author.delete(db)
+    assert await Author.query().count(db) == 1
+
+    # A failing child delete rolls back the ones before it.
+    await Author(id=3).save(db)
+    await Review.bulk_create(db, [Review(id=2, author_id=3), Review(id=3, author_id=3)])
+
+    def fail(sender: type, instance: Any) -> None:
+        if instance.id == 3:
+            raise RuntimeError("refused")
+
+    handler = pre_delete.connect(fail, sender=Review)
+    try:
+        with pytest.raises(RuntimeError, match="refused"):
+            await (await Author.get(db, 3)).delete(db)
+    finally:
+        pre_delete.disconnect(handler)
+    assert await Review.query().values_list(db, "id", flat=True) == [2, 3]
+    assert await Author.query().count(db) == 2
+
+
+def test_references_validation() -> None:
+    class Employee(Model):
+        id: ColumnField[int]
+        manager_id: ColumnField[int] = ColumnField(references="self", on_delete="set_null")
+
+    assert Employee.manager_id.references is Employee
+    assert Employee.manager_id.on_delete == "set_null"
+    with pytest.raises(ValueError, match="Unknown on_delete"):
+        ColumnField(references=Employee, on_delete="ignore")
+    with pytest.raises(ValueError, match="must be a Model class"):
+
+        class Broken(Model):
+            id: ColumnField[int]
+            owner_id: ColumnField[int] = ColumnField(references="users")
+
+
+async def test_in_bulk(db: Database) -> None:
+    await Tag.bulk_create(db, [Tag(id=i, name=f"tag{i}") for i in range(1, 1201)])
+
+    tags = await Tag.in_bulk(db, [3, 1, 3, 5000])
+    assert {pk: tag.name for pk, tag in tags.items()} == {1: "tag1", 3: "tag3"}
+    assert len(await Tag.in_bulk(db, range(1, 1201))) == 1200
+    assert await Tag.in_bulk(db, []) == {}
+
+    await Car(id=1, name="Beetle", doors=2).save(db)
+    await Truck(id=2, name="Actros", payload=18).save(db)
+    assert list(await Car.in_bulk(db, [1, 2])) == [1]
+
+    with pytest.raises(ValueError, match="single-column primary key"):
+        await Membership.in_bulk(db, [(1, 2)])
+
+
+async def test_migrations(db: Database, tmp_path: pathlib.Path) -> None:
+    (tmp_path / "0001_create_post.up.sql").write_text(
+        "CREATE TABLE post (id INTEGER PRIMARY KEY);\nCREATE INDEX post_id ON post (id);\n"
+        "CREATE TRIGGER post_check BEFORE INSERT ON post BEGIN SELECT 1; END;\n-- end;\n"
+    )
+    (tmp_path / "0001_create_post.down.sql").write_text("DROP TABLE post;")
+    (tmp_path / "0002_add_title.py").write_text(
+        'up = ["ALTER TABLE post ADD COLUMN title TEXT"]\n'
+        'down = "ALTER TABLE post DROP COLUMN title"\n'
+    )
+    (tmp_path / "0003_seed.sql").write_text("INSERT INTO post (id, title) VALUES (1, 'Hello; /* --');")
+    (tmp_path / "README.md").write_text("not a migration")
+
+    plan = await db.migrate(str(tmp_path), dry_run=True)
+    assert plan == [
+        ("0001_create_post", "CREATE TABLE post (id INTEGER PRIMARY KEY)"),
+        ("0001_create_post", "CREATE INDEX post_id ON post (id)"),
+        ("0001_create_post", "CREATE TRIGGER post_check BEFORE INSERT ON post BEGIN SELECT 1; END"),
+        ("0002_add_title", "ALTER TABLE post ADD COLUMN title TEXT"),
+        ("0003_seed", "INSERT INTO post (id, title) VALUES (1, 'Hello; /* --')"),
+    ]
+    assert await db.migration_status(str(tmp_path)) == [
+        {"version": v, "name": n, "applied": False, "applied_at": None}
+        for v, n in [(1, "create_post"), (2, "add_title"), (3, "seed")]
+    ]
+    assert await db.execute("SELECT name FROM sqlite_master WHERE name = '_fust_migrations'") == []
+
+    applied = await db.migrate(str(tmp_path))
+    assert [m["name"] for m in applied] == ["create_post", "add_title", "seed"]
+    assert await db.migrate(str(tmp_path), dry_run=True) == []
+    assert await db.migrate(str(tmp_path)) == []
+    assert await db.execute("SELECT title FROM post") == [{"title": "Hello; /* --"}]
+
+    status = await db.migration_status(str(tmp_path))
+    assert [(m["version"], m["applied"]) for m in status] == [(1, True), (2, True), (3, True)]
+    assert isinstance(status[0]["applied_at"], datetime.datetime)
+
+    with pytest.raises(ValueError, match="can't be rolled back"):
+        await db.rollback(str(tmp_path))
+
+    await db.execute("DELETE FROM post")
+    (tmp_path / "0003_seed.sql").unlink()
+    (tmp_path / "0003_seed.up.sql").write_text("INSERT INTO post (id, title) VALUES (1, 'Hello');")
+    (tmp_path / "0003_seed.down.sql").write_text("DELETE FROM post;")
+    rolled_back = await db.rollback(str(tmp_path), steps=3)
+    assert [m["version"] for m in rolled_back] == [3, 2, 1]
+    status = await db.migration_status(str(tmp_path))
+    assert [m["applied"] for m in status] == [False, False, False]
+    assert status[0]["applied_at"] is None
+
+
+async def test_data_migrations(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("CREATE TABLE person (id INTEGER PRIMARY KEY, full_name TEXT)")
+    await db.execute("INSERT INTO person (id, full_name) VALUES (1, 'Ada Lovelace'), (2, 'Alan Turing')")
+    (tmp_path / "0001_split_name.py").write_text(
+        "from fust_orm import select\n"
+        "\n"
+        "async def backfill(db):\n"
+        "    for row in await db.execute('SELECT id, full_name FROM person'):\n"
+        "        first, last = row['full_name'].split(' ', 1)\n"
+        "        sql = 'UPDATE person SET first_name = ?, last_name = ? WHERE id = ?'\n"
+        "        await db.execute(select(sql, first, last, row['id']))\n"
+        "\n"
+        "def join(db):\n"
+        "    return db.execute(\"UPDATE person SET full_name = first_name || ' ' || last_name\")\n"
+        "\n"
+        "up = [\n"
+        "    'ALTER TABLE person ADD COLUMN first_name TEXT',\n"
+        "    'ALTER TABLE person ADD COLUMN last_name TEXT',\n"
+        "    backfill,\n"
+        "    'ALTER TABLE person DROP COLUMN full_name',\n"
+        "]\n"
+        "down = [\n"
+        "    'ALTER TABLE person ADD COLUMN full_name TEXT',\n"
+        "    join,\n"
+        "    'ALTER TABLE person DROP COLUMN first_name',\n"
+        "    'ALTER TABLE person DROP COLUMN last_name',\n"
+        "]\n"
+    )
+    assert (await db.migrate(str(tmp_path), dry_run=True))[1:3] == [
+        ("0001_split_name", "ALTER TABLE person ADD COLUMN last_name TEXT"),
+        ("0001_split_name", "-- Python step: backfill(db)"),
+    ]
+    assert await db.migrate(str(tmp_path)) == [{"version": 1, "name": "split_name"}]
+    assert await db.execute("SELECT id, first_name, last_name FROM person ORDER BY id") == [
+        {"id": 1, "first_name": "Ada", "last_name": "Lovelace"},
+        {"id": 2, "first_name": "Alan", "last_name": "Turing"},
+    ]
+    await db.rollback(str(tmp_path))
+    assert await db.execute("SELECT id, full_name FROM person ORDER BY id") == [
+        {"id": 1, "full_name": "Ada Lovelace"},
+        {"id": 2, "full_name": "Alan Turing"},
+    ]
+
+    # A failing step rolls the whole migration back, the SQL and Python
+    # steps before it included.
+    (tmp_path / "0002_fail.py").write_text(
+        "async def fail(db):\n"
+        "    await db.execute(\"INSERT INTO scratch VALUES (1)\")\n"
+        "    await db.execute(\"UPDATE person SET first_name = 'gone'\")\n"
+        "    raise RuntimeError('backfill failed')\n"
+        "\n"
+        "up = ['CREATE TABLE scratch (id INTEGER)', fail]\n"
+    )
+    with pytest.raises(ValueError, match="Migration 2_fail failed: RuntimeError: backfill failed"):
+        await db.migrate(str(tmp_path))
+    assert [m["applied"] for m in await db.migration_status(str(tmp_path))] == [True, False]
+    assert await db.execute("SELECT name FROM sqlite_master WHERE name = 'scratch'") == []
+    assert await db.execute("SELECT first_name FROM person ORDER BY id") == [
+        {"first_name": "Ada"},
+        {"first_name": "Alan"},
+    ]
+    (tmp_path / "0003_bad.py").write_text("up = [42]\n")
+    with pytest.raises(ValueError, match="must be a SQL string, a function or a list of them"):
+        await db.migrate(str(tmp_path))
+
+
+async def test_concurrent_migrations(tmp_path: pathlib.Path) -> None:
+    # Two app instances deploying at once apply each migration once.
+    url = f"sqlite://{tmp_path / 'app.db'}?mode=rwc"
+    first, second = await Database.connect(url), await Database.connect(url)
+    migrations = tmp_path / "migrations"
+    migrations.mkdir()
+    (migrations / "0001_create_event.up.sql").write_text("CREATE TABLE event (id INTEGER PRIMARY KEY)")
+    (migrations / "0002_seed.py").write_text(
+        "import asyncio\n"
+        "\n"
+        "async def seed(db):\n"
+        "    await asyncio.sleep(0.2)\n"
+        "    await db.execute('INSERT INTO event (id) VALUES (1)')\n"
+        "\n"
+        "up = seed\n"
+        "down = 'DELETE FROM event'\n"
+    )
+    applied = await asyncio.gather(first.migrate(str(migrations)), second.migrate(str(migrations)))
+    assert sorted(applied, key=len) == [
+        [],
+        [{"version": 1, "name": "create_event"}, {"version": 2, "name": "seed"}],
+    ]
+    assert await first.execute("SELECT id FROM event") == [{"id": 1}]
+    assert await first.execute("SELECT id FROM _fust_migrations_lock") == []
+
+    # The lock is released when a migration fails.
+    (migrations / "0003_broken.sql").write_text("CREATE TABLE event (id INTEGER PRIMARY KEY)")
+    with pytest.raises(ValueError, match="already exists"):
+        await first.migrate(str(migrations))
+    assert await second.rollback(str(migrations)) == [{"version": 2, "name": "seed"}]
+
+    # Errors other than a held lock fail at once instead of waiting for it.
+    await first.execute("DROP TABLE _fust_migrations_lock")
+    await first.execute("CREATE TABLE _fust_migrations_lock (id INTEGER PRIMARY KEY)")
+    with pytest.raises(ProgrammingError, match="no column named locked_at"):
+        await asyncio.wait_for(first.migrate(str(migrations)), timeout=5)
+
+
+async def test_table_builder(db: Database) -> None:
+    table = (
+        Table("account")
+        .column("id", BigInteger, primary_key=True, autoincrement=True)
+        .column("email", Varchar(255), nullable=False, unique=True)
+        .column("is_active", Boolean, default=True)
+        .column("nickname", Text, default="it's me")
+        .column("created_at", Text, default_sql="CURRENT_TIMESTAMP")
+    )
+    assert table.column_names == ["id", "email", "is_active", "nickname", "created_at"]
+    assert table.to_sql("postgres").startswith(
+        "CREATE TABLE account (id BIGINT GENERATED BY DEFAULT AS IDENTITY NOT NULL PRIMARY KEY, "
+        "email VARCHAR(255) NOT NULL UNIQUE, is_active BOOLEAN DEFAULT TRUE, "
+    )
+    assert "id BIGINT NOT NULL AUTO_INCREMENT PRIMARY KEY" in table.to_sql("mysql")
+
+    await table.create(db)
+    await table.create(db, if_not_exists=True)
+    await db.execute("INSERT INTO account (email) VALUES ('a@example.com')")
+    [row] = await db.execute("SELECT * FROM account")
+    assert row["id"] == 1
+    assert row["is_active"] == 1
+    assert row["nickname"] == "it's me"
+    assert row["created_at"] is not None
+
+    link = (
+        Table("account_link")
+        .column("account_id", BigInteger, references="account.id", on_delete="cascade")
+        .column("position", BigInteger)
+        .primary_key("account_id", "position")
+    )
+    assert link.to_sql() == (
+        "CREATE TABLE account_link (account_id INTEGER NOT NULL REFERENCES account (id) "
+        "ON DELETE CASCADE, position INTEGER NOT NULL, PRIMARY KEY (account_id, position))"
+    )
+
+    with pytest.raises(ValueError, match="already has a column"):
+        table.column("email", Text)
+    with pytest.raises(ValueError, match="integer type"):
+        Table("t").column("id", Text, primary_key=True, autoincrement=True)
+    with pytest.raises(ValueError, match="not a column"):
+        Table("t").column("a", Text).unique("b")
+    with pytest.raises(ValueError, match="Unknown dialect"):
+        table.to_sql("oracle")
+
+
+async def test_alter_table(db: Database) -> None:
+    await db.execute(
+        "CREATE TABLE shelf (id INTEGER PRIMARY KEY AUTOINCREMENT, label TEXT NOT NULL, legacy TEXT)"
+    )
+    await db.execute(
+        "CREATE TABLE volume (id INTEGER PRIMARY KEY, "
+        "shelf_id INTEGER REFERENCES shelf (id) ON DELETE CASCADE, title TEXT)"
+    )
+    await db.execute("CREATE INDEX volume_title ON volume (title)")
+    await db.execute("INSERT INTO shelf (label, legacy) VALUES ('A', 'x')")
+    await db.execute("INSERT INTO volume (shelf_id, title) VALUES (1, 'Dune')")
+
+    change = (
+        alter_table("volume")
+        .add_column("pages", Integer, nullable=False, default=0)
+        .rename_column("pages", "page_count")
+        .alter_column("title", Varchar(100), nullable=False)
+    )
+    assert change.to_sql("postgres") == [
+        "ALTER TABLE volume ADD COLUMN pages INTEGER NOT NULL DEFAULT 0",
+        "ALTER TABLE volume RENAME COLUMN pages TO page_count",
+        "ALTER TABLE volume ALTER COLUMN title TYPE VARCHAR(100), ALTER COLUMN title SET NOT NULL",
+    ]
+    with pytest.raises(ValueError, match="can only be applied with apply"):
+        change.to_sql("sqlite")
+
+    # The type change rebuilds the table, keeping its rows, index and foreign key.
+    await change.apply(db)
+    assert await db.execute("SELECT id, shelf_id, title, page_count FROM volume") == [
+        {"id": 1, "shelf_id": 1, "title": "Dune", "page_count": 0}
+    ]
+    [table] = await db.execute("SELECT sql FROM sqlite_master WHERE name = 'volume'")
+    assert "title VARCHAR(100) NOT NULL" in table["sql"]
+    assert "REFERENCES shelf (id) ON DELETE CASCADE" in table["sql"]
+    assert await db.execute(
+        "SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = 'volume'"
+    ) == [{"name": "volume_title"}]
+
+    await alter_table("shelf").drop_column("legacy").apply(db)
+    assert await db.execute("SELECT id, label FROM shelf") == [{"id": 1, "label": "A"}]
+    with pytest.raises(ValueError, match="no such column"):
+        await db.execute("SELECT legacy FROM shelf")
+    [table] = await db.execute("SELECT sql FROM sqlite_master WHERE name = 'shelf'")
+    assert "PRIMARY KEY AUTOINCREMENT" in table["sql"]
+
+    with pytest.raises(ValueError, match="used by the index 'volume_title'"):
+        await alter_table("volume").drop_column("title").apply(db)
+    with pytest.raises(ValueError, match="primary key"):
+        await alter_table("volume").drop_column("id").apply(db)
+    with pytest.raises(ValueError, match="needs a column_type or nullable"):
+        alter_table("volume").alter_column("title")
+
+
+async def test_drop_table_and_truncate(db: Database) -> None:
+    counter = Table("counter").column("id", Integer, primary_key=True, autoincrement=True)
+    await counter.create(db)
+    await db.execute("INSERT INTO counter DEFAULT VALUES")
+    await db.execute("INSERT INTO counter DEFAULT VALUES")
+
+    await db.truncate(counter)
+    assert await db.execute("SELECT * FROM counter") == []
+    await db.execute("INSERT INTO counter DEFAULT VALUES")
+    assert await db.execute("SELECT id FROM counter") == [{"id": 1}]
+
+    await db.truncate(User)
+    assert await db.execute("SELECT * FROM user") == []
+
+    await db.drop_table("counter")
+    await db.drop_table("counter")
+    with pytest.raises(ValueError, match="no such table"):
+        await db.drop_table("counter", if_exists=False)
+    with pytest.raises(ValueError, match="a name, a Table or a Model class"):
+        await db.truncate(42)
+
+
+async def test_schema_helpers_join_the_transaction(db: Database, tmp_path: pathlib.Path) -> None:
+    counter = Table("counter").column("id", Integer, primary_key=True, autoincrement=True)
+    async with db.transaction():
+        await counter.create(db)
+        await db.execute("INSERT INTO counter DEFAULT VALUES")
+        assert await db.create_index("counter", "id") == "counter_id_idx"
+        await db.drop_index("counter_id_idx")
+        await db.truncate(counter)
+        await db.drop_table("counter")
+        with pytest.raises(ValueError, match="can't run in a transaction"):
+            await db.snapshot(tmp_path / "snapshot.db")
+    assert await db.execute("SELECT name FROM sqlite_master WHERE name LIKE 'counter%'") == []
+
+    with pytest.raises(RuntimeError):
+        async with db.transaction():
+            await counter.create(db)
+            await db.create_index("counter", "id")
+            raise RuntimeError("roll back")
+    assert await db.execute("SELECT name FROM sqlite_master WHERE name LIKE 'counter%'") == []
+
+
+async def test_index_management(db: Database) -> None:
+    await db.execute(
+        "CREATE TABLE account (id INTEGER PRIMARY KEY, name TEXT, email TEXT, active INTEGER)"
+    )
+
+    assert await db.create_index("account", "email") == "account_email_idx"
+    assert (
+        await db.create_index("account", ["lower(email)"], unique=True, where="active = 1")
+        == "account_lower_email_key"
+    )
+    assert await db.create_index("account", [User.name, "active DESC"], name="account_mixed") == (
+        "account_mixed"
+    )
+    await db.create_index("account", "email", if_not_exists=True)
+
+    assert await db.indexes("account") == [
+        {"name": "account_email_idx", "columns": ["email"], "unique": False, "partial": False},
+        {"name": "account_lower_email_key", "columns": [None], "unique": True, "partial": True},
+        {"name": "account_mixed", "columns": ["name", "active"], "unique": False, "partial": False},
+    ]
+    [index] = await db.execute(
+        "SELECT sql FROM sqlite_master WHERE name = 'account_lower_email_key'"
+    )
+    assert index["sql"] == (
+        "CREATE UNIQUE INDEX account_lower_email_key ON account ((lower(email))) WHERE active = 1"
+    )
+
+    await db.drop_index("account_mixed")
+    await db.drop_index("account_mixed")
+    with pytest.raises(ValueError, match="no such index"):
+        await db.drop_index("account_mixed", if_exists=False)
+    assert [index["name"] for index in await db.indexes("account")] == [
+        "account_email_idx",
+        "account_lower_email_key",
+    ]
+    with pytest.raises(ValueError, match="at least one column"):
+        await db.create_index("account", [])
+
+
+async def test_constraints(db: Database, tmp_path: pathlib.Path) -> None:
+    listing = (
+        Table("listing")
+        .column("id", Integer, primary_key=True)
+        .column("sku", Text, nullable=False)
+        .column("region", Text, nullable=False)
+        .column("price", Float, nullable=False)
+        .unique("sku", "region", name="listing_sku_region")
+        .check("listing_price_positive", "price > 0")
+    )
+    assert listing.to_sql("postgres") == (
+        "CREATE TABLE listing (id INTEGER NOT NULL PRIMARY KEY, sku TEXT NOT NULL, region TEXT NOT NULL, "
+        "price DOUBLE PRECISION NOT NULL, CONSTRAINT listing_sku_region UNIQUE (sku, region), "
+        "CONSTRAINT listing_price_positive CHECK (price > 0))"
+    )
+    with pytest.raises(ValueError, match="already has a constraint named 'listing_sku_region'"):
+        listing.check("listing_sku_region", "price < 100")
+    await listing.create(db)
+    assert await db.constraints("listing") == [
+        {"name": "listing_sku_region", "type": "unique", "columns": ["sku", "region"], "expression": None},
+        {"name": "listing_price_positive", "type": "check", "columns": [], "expression": "price > 0"},
+    ]
+    with pytest.raises(ValueError, match="CHECK constraint failed"):
+        await db.execute("INSERT INTO listing (id, sku, region, price) VALUES (1, 'a', 'eu', 0)")
+
+    # Rebuilding the table keeps its constraints, and alter_table() changes them.
+    await db.execute("INSERT INTO listing (id, sku, region, price) VALUES (1, 'a', 'eu', 5)")
+    change = alter_table("listing").drop_constraint("listing_price_positive").add_check(
+        "listing_price_cap", "price < 1000"
+    )
+    assert change.to_sql("postgres") == [
+        "ALTER TABLE listing DROP CONSTRAINT listing_price_positive",
+        "ALTER TABLE listing ADD CONSTRAINT listing_price_cap CHECK (price < 1000)",
+    ]
+    await change.alter_column("price", Numeric(10, 2)).apply(db)
+    assert [(c["name"], c["expression"]) for c in await db.constraints("listing")] == [
+        ("listing_sku_region", None),
+        ("listing_price_cap", "price < 1000"),
+    ]
+    assert await db.execute("SELECT id, price FROM listing") == [{"id": 1, "price": 5}]
+    with pytest.raises(ValueError, match="has no constraint named 'missing'"):
+        await alter_table("listing").drop_constraint("missing").apply(db)
+
+    # Meta.checks are created, validated and dropped by migrations.
+    class Listing(Model):
+        id: ColumnField[int]
+        sku: ColumnField[str]
+        region: ColumnField[str]
+        price: ColumnField[float]
+
+        class Meta:
+            unique_together = [("sku", "region")]
+            checks = {"listing_price_cap": "price < 1000", "listing_sku_set": "sku <> ''"}
+
+    with pytest.raises(ValueError, match="missing check constraint 'listing_sku_set'"):
+        await db.validate_schema([Listing])
+    assert await db.make_migration(str(tmp_path), [Listing], name="sku") == {"version": 1, "name": "sku"}
+    await db.migrate(str(tmp_path))
+    await db.validate_schema([Listing])
+    with pytest.raises(ValueError, match="CHECK constraint failed: listing_sku_set"):
+        await db.execute("INSERT INTO listing (id, sku, region, price) VALUES (2, '', 'eu', 5)")
+    await db.rollback(str(tmp_path))
+    assert [c["name"] for c in await db.constraints("listing")] == [
+        "listing_sku_region",
+        "listing_price_cap",
+    ]
+    with pytest.raises(ValueError, match="must map constraint names to SQL conditions"):
+
+        class Broken(Model):
+            id: ColumnField[int]
+
+            class Meta:
+                checks = ["id > 0"]
+
+
+async def test_generated_columns(db: Database) -> None:
+    basket = (
+        Table("basket")
+        .column("id", Integer, primary_key=True)
+        .column("price", Float, nullable=False)
+        .column("quantity", Integer, nullable=False)
+        .column("total", Float, generated="price * quantity")
+        .column("label", Text, generated="'x' || quantity", stored=True)
+    )
+    assert basket.to_sql("postgres") == (
+        "CREATE TABLE basket (id INTEGER NOT NULL PRIMARY KEY, price DOUBLE PRECISION NOT NULL, "
+        "quantity INTEGER NOT NULL, total DOUBLE PRECISION GENERATED ALWAYS AS (price * quantity) VIRTUAL, "
+        "label TEXT GENERATED ALWAYS AS ('x' || quantity) STORED)"
+    )
+    with pytest.raises(ValueError, match="is generated and can't be a primary key or have a default"):
+        basket.column("total_default", Float, generated="price", default=0)
+    with pytest.raises(ValueError, match="is stored but not generated"):
+        basket.column("cached", Float, stored=True)
+    await basket.create(db)
+
+    class Basket(Model):
+        id: ColumnField[int]
+        price: ColumnField[float]
+        quantity: ColumnField[int]
+        total: ColumnField[float | None] = ColumnField(generated="price * quantity")
+        label: ColumnField[str | None] = ColumnField(generated="'x' || quantity", stored=True)
+
+    await Basket(price=2.5, quantity=4).save(db)
+    await Basket.bulk_create(db, [Basket(price=1.0, quantity=3)])
+    loaded = await Basket.get(db, 1)
+    assert (loaded.total, loaded.label) == (10.0, "x4")
+    loaded.quantity = 2
+    await loaded.save(db)
+    assert (await Basket.get(db, 1)).total == 5.0
+    assert (await Basket.get(db, 2)).label == "x3"
+    with pytest.raises(ValueError, match="can't update the generated column 'total'"):
+        await Basket.bulk_update(db, [loaded], ["total"])
+
+    # Rebuilding the table keeps the generated columns and computes them again.
+    await alter_table("basket").alter_column("quantity", BigInteger).apply(db)
+    assert await db.execute("SELECT id, total, label FROM basket ORDER BY id") == [
+        {"id": 1, "total": 5.0, "label": "x2"},
+        {"id": 2, "total": 3.0, "label": "x3"},
+    ]
+    with pytest.raises(ValueError, match="is generated and can't be altered"):
+        await alter_table("basket").alter_column("total", Integer).apply(db)
+    source = await introspect_models(db, ["basket"])
+    assert 'total: ColumnField[float | None] = ColumnField(generated="price * quantity")' in source
+    assert "label: ColumnField[str | None] = ColumnField(generated=\"'x' || quantity\", stored=True)" in source
+
+
+async def test_explain(db: Database) -> None:
+    query = select(User.id, User.name == "Alice")
+    [scan] = await db.explain(query)
+    assert scan["detail"] == "SCAN user"
+    await db.create_index(User, User.name)
+    [search] = await db.explain(query)
+    assert search["detail"] == "SEARCH user USING COVERING INDEX user_name_idx (name=?)"
+    assert set(search) >= {"id", "parent", "detail"}
+    plan = await db.explain("SELECT * FROM user WHERE id = 1")
+    assert "USING INTEGER PRIMARY KEY" in plan[0]["detail"]
+    with pytest.raises(ValueError, match="not supported on SQLite"):
+        await db.explain(query, analyze=True)
+    with pytest.raises(TypeError, match="must be a string"):
+        await db.explain(42)  # type: ignore[arg-type]
+
+
+async def test_failover_requires_servers(tmp_path: pathlib.Path) -> None:
+    urls = [f"sqlite://{tmp_path / 'a.db'}?mode=rwc", f"sqlite://{tmp_path / 'b.db'}?mode=rwc"]
+    with pytest.raises(InterfaceError, match="Failover is only supported across PostgreSQL or MySQL"):
+        await Database.connect(urls)
+    with pytest.raises(InterfaceError, match="Failover is only supported"):
+        await Database.connect(["postgres://localhost/app", "mysql://localhost/app"])
+    with pytest.raises(InterfaceError, match="No database URL"):
+        await Database.connect([])
+    with pytest.raises(ValueError, match="failover_retry must be a positive number"):
+        await Database.connect(urls, failover_retry=0)
+    db = await Database.connect(urls[:1])
+    assert await db.execute("SELECT 1 AS one") == [{"one": 1}]
+
+
+async def test_circuit_breaker(tmp_path: pathlib.Path) -> None:
+    states: list[str] = []
+    breaker = CircuitBreaker(failures=2, cooldown=60, on_state_change=states.append)
+    db = await Database.connect(f"sqlite://{tmp_path / 'app.db'}?mode=rwc", circuit_breaker=breaker)
+    async with db.transaction() as tx:
+        await tx.execute("CREATE TABLE item (name TEXT)")
+    assert await db.execute("SELECT count(*) AS n FROM item") == [{"n": 0}]
+    assert (breaker.state, breaker.failures, states) == ("closed", 0, [])
+    breaker.reset()
+    assert states == []
+    with pytest.raises(ValueError, match="failures must be at least 1"):
+        CircuitBreaker(failures=0)
+    with pytest.raises(ValueError, match="cooldown must be a positive number"):
+        CircuitBreaker(cooldown=-1)
+
+
+async def test_connection_recycling(tmp_path: pathlib.Path) -> None:
+    kept = await Database.connect(f"sqlite://{tmp_path / 'kept.db'}?mode=rwc", test_before_acquire=False)
+    reaped = await Database.connect(
+        f"sqlite://{tmp_path / 'reaped.db'}?mode=rwc", max_lifetime=None, idle_timeout=0.2
+    )
+    for db in (kept, reaped):
+        # SQLite removes the write-ahead log once its last connection closes.
+        await db.execute("PRAGMA journal_mode = WAL")
+        await db.execute("CREATE TABLE item (name TEXT)")
+    assert (tmp_path / "kept.db-wal").exists() and (tmp_path / "reaped.db-wal").exists()
+    await asyncio.sleep(0.6)
+    assert (tmp_path / "kept.db-wal").exists() and not (tmp_path / "reaped.db-wal").exists()
+    assert await reaped.execute("SELECT count(*) AS n FROM item") == [{"n": 0}]
+    await kept.close()
+    await reaped.close()
+    with pytest.raises(ValueError, match="idle_timeout must be a positive number of seconds or None"):
+        await Database.connect(f"sqlite://{tmp_path / 'kept.db'}", idle_timeout=0)
+    with pytest.raises(ValueError, match="max_lifetime must be a positive number of seconds or None"):
+        await Database.connect(f"sqlite://{tmp_path / 'kept.db'}", max_lifetime=float("inf"))
+
+
+async def test_retry_policy(tmp_path: pathlib.Path) -> None:
+    calls: list[str] = []
+
+    def flaky(sql: str, params: list[str]) -> None:
+        calls.append(sql)
+        if len(calls) % 3 != 0:
+            raise ConnectionResetError("connection dropped")
+        return None
+
+    retried = await Database.connect(
+        f"sqlite://{tmp_path / 'app.db'}?mode=rwc",
+        retry=RetryPolicy(attempts=3, retry_on=[DatabaseError], backoff=0, jitter=0),
+    )
+    await retried.execute("CREATE TABLE user (id INTEGER PRIMARY KEY, name TEXT, age INTEGER, manager_id INTEGER)")
+    rewrite_statements(flaky)
+    try:
+        assert await retried.execute("SELECT 1 AS n") == [{"n": 1}]
+        assert len(calls) == 3
+        # Model methods and queries are retried alike.
+        assert await User.query().all(retried) == []
+        assert len(calls) == 6
+        # So are the statements of the helpers using the pool directly.
+        calls.clear()
+        assert await retried.indexes("user") == []
+        assert len(calls) == 3 and "pragma_index_list" in calls[0]
+        with pytest.raises(DatabaseError, match="connection dropped"):
+            await retried.execute("SELECT 1", retry=RetryPolicy(attempts=2, retry_on=[DatabaseError], backoff=0))
+        # The default policy retries OperationalError only.
+        calls.clear()
+        with pytest.raises(DatabaseError, match="connection dropped"):
+            await retried.execute("SELECT 1", retry=RetryPolicy(backoff=0))
+        assert len(calls) == 1
+        # Statements in a transaction aren't retried.
+        calls.clear()
+        with pytest.raises(DatabaseError, match="connection dropped"):
+            async with retried.transaction() as tx:
+                calls.append("BEGIN")
+                await tx.execute("SELECT 1")
+        assert calls == ["BEGIN", "SELECT 1"]
+    finally:
+        rewrite_statements(None)
+    await retried.close()
+
+    assert RetryPolicy().attempts == 3
+    with pytest.raises(ValueError, match="attempts must be at least 1"):
+        RetryPolicy(attempts=0)
+    with pytest.raises(ValueError, match="multiplier must be at least 1"):
+        RetryPolicy(multiplier=0.5)
+    with pytest.raises(ValueError, match="jitter must be from 0 to 1"):
+        RetryPolicy(jitter=2)
+    with pytest.raises(ValueError, match="retry_on must be exception classes"):
+        RetryPolicy(retry_on=[int])
+
+
+async def test_listen_and_notify_require_postgres(db: Database) -> None:
+    with pytest.raises(ValueError, match="only supported on PostgreSQL"):
+        await db.listen("jobs")
+    with pytest.raises(ValueError, match="only supported on PostgreSQL"):
+        await db.notify("jobs", "payload")
+
+
+async def test_copy_requires_postgres(db: Database) -> None:
+    with pytest.raises(ValueError, match="only supported on PostgreSQL"):
+        await db.copy_in("users", [(1, "Alice")])
+    with pytest.raises(ValueError, match="only supported on PostgreSQL"):
+        await db.copy_out("SELECT 1", io.BytesIO())
+    async with db.transaction() as tx:
+        with pytest.raises(ValueError, match="in a transaction"):
+            await tx.copy_in("users", [(1, "Alice")])
+        with pytest.raises(ValueError, match="in a transaction"):
+            await db.copy_in("users", [(1, "Alice")])
+
+
+async def test_advisory_lock_requires_postgres(db: Database) -> None:
+    with pytest.raises(ValueError, match="only supported on PostgreSQL"):
+        db.advisory_lock("jobs")
+
+
+async def test_server_cursor_requires_postgres(db: Database) -> None:
+    with pytest.raises(ValueError, match="only supported on PostgreSQL"):
+        db.server_cursor("SELECT 1")
+
+
+async def test_spool(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("CREATE TABLE event (id INTEGER PRIMARY KEY, kind TEXT, weight REAL, payload BLOB)")
+    await db.execute(
+        "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500) "
+        "INSERT INTO event SELECT i, 'kind ' || (i % 3), i / 4.0, CASE WHEN i % 2 THEN x'00ff' END FROM n"
+    )
+
+    rows = await db.spool(select("SELECT * FROM event ORDER BY id"), dir=tmp_path)
+    assert len(rows) == 500 and rows.columns == ["id", "kind", "weight", "payload"]
+    assert len(list(tmp_path.iterdir())) == 1
+    iterator = iter(rows)
+    assert next(iterator) == {"id": 1, "kind": "kind 1", "weight": 0.25, "payload": b"\x00\xff"}
+    assert next(iterator)["payload"] is None
+    # Each iteration starts over.
+    assert [row["id"] for row in rows] == list(range(1, 501))
+    with rows:
+        assert sum(1 for _ in iterator) == 498
+    assert list(tmp_path.iterdir()) == []
+    with pytest.raises(ValueError, match="closed"):
+        iter(rows)
+
+    async with db.transaction() as tx:
+        await tx.execute("INSERT INTO event (id, kind) VALUES (501, 'new')")
+        with await tx.spool("SELECT kind FROM event WHERE id > 499", dir=tmp_path) as rows:
+            assert list(rows) == [{"kind": "kind 2"}, {"kind": "new"}]
+    assert len(await db.spool("SELECT * FROM event WHERE id < 0")) == 0
+    with pytest.raises(DatabaseError):
+        await db.spool("SELECT * FROM missing", dir=tmp_path)
+    assert list(tmp_path.iterdir()) == []
+
+
+async def test_execute_dry_run(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("INSERT INTO user (name, age) VALUES ('Ann', 30)")
+    assert await db.execute("DELETE FROM user", dry_run=True) == []
+    await db.execute(select(User).where_(User.age > 20), dry_run=True)
+    with pytest.raises(DatabaseError, match="missing"):
+        await db.execute("DELETE FROM missing", dry_run=True)
+    async with db.transaction() as tx:
+        await tx.execute("CREATE TABLE note (id INTEGER PRIMARY KEY)")
+        await tx.execute("DROP TABLE note", dry_run=True)
+        assert await tx.execute("SELECT * FROM note") == []
+    assert len(await db.execute("SELECT * FROM user")) == 1
+
+    records: list[logging.LogRecord] = []
+    handler = logging.Handler()
+    handler.emit = records.append  # type: ignore[method-assign]
+    logging.getLogger("fust_orm.sql").addHandler(handler)
+    url = f"sqlite://{tmp_path / 'dry.db'}?mode=rwc"
+    try:
+        await (await Database.connect(url)).execute("CREATE TABLE note (id INTEGER PRIMARY KEY)")
+        dry = await Database.connect(url, dry_run=True)
+        await dry.execute("DROP TABLE note")
+        assert await dry.execute("SELECT * FROM note", dry_run=False) == []
+
+        class Note(Model):
+            id: ColumnField[int]
+
+        with pytest.raises(ValueError, match="dry_run=True"):
+            await Note(id=1).save(dry)
+        with pytest.raises(ValueError, match="dry_run=True"):
+            await Note.query().update(dry, {"id": 2})
+        with pytest.raises(ValueError, match="dry_run=True"):
+            await Note.bulk_create(dry, [Note(id=1)])
+        assert await Note.query().count(dry) == 0
+    finally:
+        logging.getLogger("fust_orm.sql").removeHandler(handler)
+    assert [r.getMessage() for r in records] == ["Dry run, not executed: DROP TABLE note; parameters: []"]
+
+
+async def test_cached(db: Database) -> None:
+    await db.execute("INSERT INTO user (name, age) VALUES ('Ann', 30), ('Bob', 40)")
+    query = User.query(User.age > 20)
+    with testing.capture_queries() as queries:
+        rows = await db.cached(query)
+        assert [row["name"] for row in rows] == ["Ann", "Bob"]
+        assert await db.cached(query) == rows
+        await db.cached(User.query(User.age > 35))
+    assert len(queries) == 2
+
+    await db.execute("UPDATE user SET age = 10 WHERE name = 'Ann'")
+    assert len(await db.cached(query)) == 2
+    assert db.invalidate_cache("product") == 0
+    assert db.invalidate_cache(User) == 2
+    assert [row["name"] for row in await db.cached(query)] == ["Bob"]
+
+    # Aliased and joined tables are invalidated too.
+    sql = "SELECT u.name FROM user AS u JOIN product p ON p.id = u.id"
+    await db.cached(sql)
+    assert db.invalidate_cache("Product") == 1
+
+    with testing.capture_queries() as queries:
+        await db.cached("SELECT 1 AS one", ttl=0.05)
+        await asyncio.sleep(0.1)
+        await db.cached("SELECT 1 AS one", ttl=0.05)
+        async with db.transaction() as tx:
+            await tx.cached(query)
+    assert len(queries) == 3
+    assert db.invalidate_cache() == 2
+
+    configure_query_cache(max_entries=1)
+    try:
+        await db.cached(query)
+        await db.cached("SELECT 2 AS two")
+        assert db.invalidate_cache() == 1
+        configure_query_cache(max_bytes=10)
+        await db.cached(query)
+        assert db.invalidate_cache() == 0
+    finally:
+        configure_query_cache()
+    with pytest.raises(ValueError, match="ttl"):
+        await db.cached(query, ttl=0)
+
+
+async def test_on_change(tmp_path: pathlib.Path) -> None:
+    url = f"sqlite://{tmp_path / 'changes.db'}?mode=rwc"
+    db, other = await Database.connect(url), await Database.connect(url)
+    changes: list[tuple[str, str, int]] = []
+    callback = db.on_change(lambda *change: changes.append(change))
+    assert callback is not None
+    await db.execute("CREATE TABLE note (id INTEGER PRIMARY KEY, body TEXT)")
+    await db.execute("INSERT INTO note (body) VALUES ('a'), ('b')")
+    await db.execute("UPDATE note SET body = 'c' WHERE id = 2")
+    await db.execute("DELETE FROM note WHERE id = 1")
+    await other.execute("INSERT INTO note (body) VALUES ('not through db')")
+    assert changes == [
+        ("insert", "note", 1),
+        ("insert", "note", 2),
+        ("update", "note", 2),
+        ("delete", "note", 1),
+    ]
+    # Views and triggers can't retag a connection.
+    await other.execute("CREATE VIEW tagged AS SELECT fust_orm_tag_connection(0) AS tag")
+    with pytest.raises(DatabaseError, match="unsafe use of fust_orm_tag_connection"):
+        await other.execute("SELECT tag FROM tagged")
+    db.on_change(None)
+    await db.execute("DELETE FROM note")
+    assert len(changes) == 4
+
+
+async def test_create_function(tmp_path: pathlib.Path) -> None:
+    class Total:
+        def __init__(self) -> None:
+            self.total = 0
+
+        def step(self, value: int) -> None:
+            self.total += value
+
+        def finalize(self) -> int:
+            return self.total
+
+    db = await Database.connect(f"sqlite://{tmp_path / 'functions.db'}?mode=rwc")
+    await db.execute("CREATE TABLE item (n INTEGER)")
+    await db.execute("INSERT INTO item VALUES (1), (2), (3)")
+    # Defined on the connection that's already open.
+    db.create_function("double", 1, lambda n: n * 2, deterministic=True)
+    assert await db.execute("SELECT double(n) AS d FROM item") == [{"d": 2}, {"d": 4}, {"d": 6}]
+    db.create_function("describe", -1, lambda *args: repr(args))
+    rows = await db.execute("SELECT describe(1, 2.5, 'x', x'00', NULL) AS d")
+    assert rows == [{"d": "(1, 2.5, 'x', b'\\x00', None)"}]
+    db.create_aggregate("total", 1, Total)
+    assert await db.execute("SELECT total(n) AS t FROM item") == [{"t": 6}]
+    assert await db.execute("SELECT total(n) AS t FROM item WHERE n > 3") == [{"t": 0}]
+    rows = await asyncio.gather(*(db.execute("SELECT double(21) AS d") for _ in range(10)))
+    assert rows == [[{"d": 42}]] * 10
+
+    db.create_function("fail", 0, lambda: 1 / 0)
+    with pytest.raises(ProgrammingError, match="ZeroDivisionError"):
+        await db.execute("SELECT fail()")
+    with pytest.raises(ValueError, match="nargs"):
+        db.create_function("many", 128, lambda *args: None)
+    with pytest.raises(TypeError):
+        db.create_function("not_callable", 0, 1)
+
+
+async def test_create_collation(db: Database) -> None:
+    db.create_collation("reverse", lambda a, b: (a < b) - (a > b))
+    await db.execute("CREATE TABLE word (text TEXT COLLATE reverse UNIQUE)")
+    await db.execute("INSERT INTO word VALUES ('b'), ('c'), ('a')")
+    assert await db.execute("SELECT text FROM word ORDER BY text") == [
+        {"text": "c"},
+        {"text": "b"},
+        {"text": "a"},
+    ]
+    rows = await db.execute("SELECT text FROM word ORDER BY text COLLATE BINARY")
+    assert [row["text"] for row in rows] == ["a", "b", "c"]
+
+    def by_initial(a: str, b: str) -> int:
+        a, b = a[:1].lower(), b[:1].lower()
+        return (a > b) - (a < b)
+
+    db.create_collation("initial", by_initial)
+    rows = await db.execute("SELECT 'Apple' = 'apricot' COLLATE initial AS same")
+    assert rows == [{"same": 1}]
+    db.create_collation("initial", None)
+    with pytest.raises(ProgrammingError, match="no such collation"):
+        await db.execute("SELECT 'a' = 'b' COLLATE initial")
+    with pytest.raises(TypeError):
+        db.create_collation("broken", 1)
+
+
+async def test_changesets(tmp_path: pathlib.Path) -> None:
+    local = await Database.connect(f"sqlite://{tmp_path / 'local.db'}?mode=rwc")
+    remote = await Database.connect(f"sqlite://{tmp_path / 'remote.db'}?mode=rwc")
+    for db in (local, remote):
+        await db.execute("CREATE TABLE note (id INTEGER PRIMARY KEY, body TEXT)")
+        await db.execute("CREATE TABLE audit (id INTEGER PRIMARY KEY, entry TEXT)")
+
+    changeset = local.start_changeset(["note"])
+    await local.execute("INSERT INTO note (body) VALUES ('one'), ('two')")
+    await asyncio.gather(*(local.execute(f"INSERT INTO note (body) VALUES ('n{i}')") for i in range(4)))
+    async with local.transaction() as tx:
+        await tx.execute("UPDATE note SET body = 'uno' WHERE id = 1")
+        await tx.execute("DELETE FROM note WHERE id = 2")
+    await local.execute("INSERT INTO audit (entry) VALUES ('not recorded')")
+    await remote.apply_changeset(changeset.to_bytes())
+    rows = await remote.execute("SELECT id, body FROM note ORDER BY id")
+    assert rows == await local.execute("SELECT id, body FROM note ORDER BY id")
+    assert len(rows) == 5 and rows[0] == {"id": 1, "body": "uno"}
+    assert await remote.execute("SELECT COUNT(*) AS n FROM audit") == [{"n": 0}]
+
+    await remote.execute("UPDATE note SET body = 'remote' WHERE id = 1")
+    await local.execute("UPDATE note SET body = 'local' WHERE id = 1")
+    data = changeset.to_bytes()
+    changeset.close()
+    assert changeset.closed
+    await local.execute("UPDATE note SET body = 'after' WHERE id = 3")
+    assert changeset.to_bytes() == data
+
+    # The rows inserted before are there already, and row 1 was changed.
+    with pytest.raises(IntegrityError, match="'conflict' conflict in table 'note'"):
+        await remote.apply_changeset(data)
+    assert await remote.execute("SELECT body FROM note WHERE id = 1") == [{"body": "remote"}]
+    conflicts: list[tuple[str, str]] = []
+
+    def keep_local(conflict: str, table: str) -> str:
+        conflicts.append((conflict, table))
+        return "replace"
+
+    await remote.apply_changeset(data, keep_local)
+    assert set(conflicts) == {("conflict", "note")}
+    assert await remote.execute("SELECT body FROM note WHERE id = 1") == [{"body": "local"}]
+    with pytest.raises(ValueError, match="conflict handler returned 'skip'"):
+        await remote.apply_changeset(data, lambda conflict, table: "skip")
+    with pytest.raises(ZeroDivisionError):
+        await remote.apply_changeset(data, lambda conflict, table: 1 / 0)
+
+
+async def test_load_extensions(tmp_path: pathlib.Path) -> None:
+    missing = str(tmp_path / "missing")
+    with pytest.raises(ValueError, match="couldn't load the SQLite extension .*missing"):
+        await Database.connect("sqlite::memory:", load_extensions=[missing])
+    with pytest.raises(ValueError, match="load_extensions is only supported on SQLite"):
+        await Database.connect("postgres://localhost/db", load_extensions=[missing])
+    db = await Database.connect("sqlite::memory:")
+    with pytest.raises(ProgrammingError, match="not authorized"):
+        await db.execute(f"SELECT load_extension('{missing}')")
+
+
+async def test_full_text_search(tmp_path: pathlib.Path) -> None:
+    class Post(Model):
+        id: ColumnField[int]
+        title: ColumnField[str]
+        body: ColumnField[str]
+
+    class PostSearch(Model):
+        title: ColumnField[str]
+        body: ColumnField[str]
+
+    db = await Database.connect(f"sqlite://{tmp_path / 'search.db'}?mode=rwc")
+    await db.execute("CREATE TABLE post (id INTEGER PRIMARY KEY, title TEXT, body TEXT)")
+    await db.execute("INSERT INTO post (title, body) VALUES ('Rust ORM', 'fast bindings'), ('Python', 'rust rust rust')")
+    index = FtsTable.from_model(PostSearch, content=Post, tokenize="porter", prefix=[2])
+    assert index.to_sql().startswith(
+        "CREATE VIRTUAL TABLE post_search USING fts5(title, body, content='post', "
+        "content_rowid='id', tokenize='porter', prefix='2')"
+    )
+    await index.create(db)
+    # Kept up to date by the triggers.
+    await db.execute("INSERT INTO post (title, body) VALUES ('Go', 'no match here')")
+    await db.execute("UPDATE post SET body = 'rust never sleeps' WHERE title = 'Go'")
+
+    query = PostSearch.query().search("rust")
+    titles = await query.values_list(db, PostSearch.title, flat=True)
+    assert titles[0] == "Python" and sorted(titles) == ["Go", "Python", "Rust ORM"]
+    by_title = PostSearch.query().search("rust", weights=[10.0, 1.0])
+    assert (await by_title.values_list(db, PostSearch.title, flat=True))[0] == "Rust ORM"
+    [post] = await (
+        PostSearch.query(PostSearch.title.match("rust"))
+        .annotate(
+            marked=highlight(PostSearch, 0, "[", "]"),
+            excerpt=snippet(index, 1, "[", "]", "...", 2),
+            score=bm25(PostSearch),
+        )
+        .all(db)
+    )
+    assert (post.title, post.marked, post.excerpt) == ("Rust ORM", "[Rust] ORM", "fast bindings")
+    assert post.score < 0
+    await db.execute("DELETE FROM post WHERE title = 'Python'")
+    assert await PostSearch.query().search("rus*").count(db) == 2
+    with pytest.raises(ValueError, match="search"):
+        select("SELECT 1").search("rust")
+
+
+async def test_json_paths(db: Database) -> None:
+    class Profile(Model):
+        id: ColumnField[int]
+        data: ColumnField[str]
+
+    await db.execute("CREATE TABLE profile (id INTEGER PRIMARY KEY, data TEXT)")
+    await db.execute(
+        """INSERT INTO profile (data) VALUES
+        ('{"name": "Ann", "age": 31, "tags": ["a", "b"], "address": {"city": "Oslo"}}'),
+        ('{"name": "Bob", "age": 19, "tags": [], "address": {"city": "Rome"}}')"""
+    )
+    [ann] = await (
+        Profile.query(Profile.data.json("$.age") > 30)
+        .annotate(
+            city=Profile.data.json("$.address.city"),
+            tags=Profile.data.json("tags", as_json=True),
+            first_tag=Profile.data.json("$.tags").json(0),
+        )
+        .all(db)
+    )
+    assert (ann.city, ann.tags, ann.first_tag) == ("Oslo", '["a","b"]', "a")
+    # Paths are bound, not inlined.
+    injected = Profile.data.json("$.name') OR 1=1 --") == "Ann"
+    assert await Profile.query().where_(injected).count(db) == 0
+    assert await Profile.query().where_(F("data").json("name") == "Bob").values_list(db, Profile.id, flat=True) == [2]
+    with pytest.raises(TypeError, match="JSON path"):
+        Profile.data.json(1.5)
+
+
+async def test_geospatial_queries(tmp_path: pathlib.Path) -> None:
+    class Place(Model):
+        id: ColumnField[int]
+        location: ColumnField[str]
+
+    class Shape:
+        def __init__(self, wkt: str) -> None:
+            self.wkt = wkt
+
+    def coordinates(wkt: str) -> list[tuple[float, float]]:
+        numbers = wkt.split("(")[-1].rstrip(")").split(",")
+        return [(float(x), float(y)) for x, y in (n.split() for n in numbers)]
+
+    def within(point: str, polygon: str) -> bool:
+        [(x, y)] = coordinates(point)
+        xs, ys = zip(*coordinates(polygon))
+        return min(xs) <= x <= max(xs) and min(ys) <= y <= max(ys)
+
+    # Stand-ins for SpatiaLite's functions, storing geometries as WKT.
+    db = await Database.connect(f"sqlite://{tmp_path / 'geo.db'}?mode=rwc")
+    db.create_function("ST_GeomFromText", -1, lambda wkt, srid=None: wkt)
+    db.create_function("ST_Within", 2, within)
+    db.create_function("ST_Intersects", 2, within)
+    db.create_function("ST_AsText", 1, lambda wkt: wkt)
+    await db.execute("CREATE TABLE place (id INTEGER PRIMARY KEY, location TEXT)")
+    await db.execute("INSERT INTO place (location) VALUES ('POINT (1 1)'), ('POINT (5 5)')")
+
+    area = "POLYGON ((0 0, 0 2, 2 2, 2 0, 0 0))"
+    assert await Place.query(Place.location.within(area, srid=4326)).values_list(db, Place.id, flat=True) == [1]
+    assert await Place.query(F("location").intersects(Shape(area))).count(db) == 1
+    [place] = await Place.query(Place.id == 2).annotate(wkt=as_wkt(Place.location)).all(db)
+    assert place.wkt == "POINT (5 5)"
+    await Place.query(Place.id == 2).update(db, {"location": to_geometry(Shape("POINT (0.5 0.5)"))})
+    assert await Place.query(Place.location.within(area)).count(db) == 2
+    with pytest.raises(TypeError, match="WKT"):
+        Place.location.within(42)
+
+    assert Geometry("point", srid=4326).sql("postgres") == "geometry(POINT, 4326)"
+    assert Geometry().sql("postgres") == "geometry"
+    assert Geometry("polygon", srid=4326).sql("mysql") == "POLYGON SRID 4326"
+    assert Geometry("point").sql("sqlite") == "BLOB"
+    assert repr(Geometry("point", 4326)) == "Geometry('POINT', 4326)"
+    with pytest.raises(ValueError, match="geometry kind"):
+        Geometry("circle")
+
+    assert load_geometry(None) is None
+    try:
+        import shapely  # noqa: F401
+    except ImportError:
+        with pytest.raises(ImportError, match="shapely"):
+            load_geometry("POINT (1 2)")
+    else:
+        assert load_geometry("POINT (1 2)").wkt == "POINT (1 2)"
+
+
+async def test_array_columns(tmp_path: pathlib.Path) -> None:
+    class Post(Model):
+        id: ColumnField[int]
+        tags: ColumnField[list[str]]
+        scores: ColumnField[list[int] | None]
+
+    table = Table.from_model(Post)
+    assert "tags TEXT[] NOT NULL" in table.to_sql("postgres")
+    assert "scores INTEGER[]" in table.to_sql("postgres")
+    assert "tags TEXT NOT NULL" in table.to_sql("sqlite")
+    assert Array(Integer).sql("postgres") == "INTEGER[]"
+    assert repr(Array(Varchar(20))) == "Array(Varchar(20))"
+    with pytest.raises(ValueError, match="type of the items"):
+        Array(Array(Integer))
+
+    db = await Database.connect(f"sqlite://{tmp_path / 'arrays.db'}?mode=rwc")
+    await table.create(db)
+    await Post(id=1, tags=["rust", 'say "hi"', "a,b"], scores=[[1, 2], [3, None]]).save(db)
+    await Post(id=2, tags=[], scores=None).save(db)
+    assert await db.execute("SELECT tags, scores FROM post WHERE id = 1") == [
+        {"tags": '{"rust","say \\"hi\\"","a,b"}', "scores": "{{1,2},{3,NULL}}"}
+    ]
+    post, empty = await Post.query().all(db)
+    assert post.tags == ["rust", 'say "hi"', "a,b"]
+    assert post.scores == [[1, 2], [3, None]]
+    assert (empty.tags, empty.scores) == ([], None)
+    assert await Post.query(Post.tags == ["rust", 'say "hi"', "a,b"]).count(db) == 1
+
+    # A stand-in for PostgreSQL's ANY() matching the first item of an array.
+    db.create_function("ANY", 1, lambda array: array.strip("{}").split(",")[0].strip('"'))
+    statements: list[str] = []
+    on_slow_query(0, lambda sql, params, ms, error: statements.append(sql))
+    try:
+        assert await Post.query(Post.tags.any("rust")).values_list(db, Post.id, flat=True) == [1]
+        assert await Post.query(Post.id.in_array([2])).count(db) == 1
+    finally:
+        on_slow_query(None)
+    assert "WHERE (? = ANY(tags))" in statements[0]
+    assert "WHERE (id = ANY(?))" in statements[1]
+
+
+async def test_registered_types(tmp_path: pathlib.Path) -> None:
+    @register_type
+    class Mood(enum.Enum):
+        HAPPY = "happy"
+        SAD = "it's sad"
+
+    @register_type
+    class Address(NamedTuple):
+        street: str
+        city: str
+        zip_code: int | None
+
+    @dataclasses.dataclass
+    class Stay:
+        since: datetime.date
+        nights: int
+
+    assert register_type(Stay, name="span") is Stay
+
+    class Person(Model):
+        id: ColumnField[int]
+        mood: ColumnField[Mood]
+        address: ColumnField[Address | None]
+        stay: ColumnField[Stay | None]
+
+    ddl = Table.from_model(Person).to_sql("postgres")
+    assert "mood mood NOT NULL" in ddl
+    assert "address address," in ddl
+    assert "stay span" in ddl
+    assert "address TEXT" in Table.from_model(Person).to_sql("sqlite")
+    with pytest.raises(TypeError, match="enum, a named tuple or a dataclass"):
+        register_type(int)
+    with pytest.raises(TypeError, match="text values"):
+        register_type(enum.Enum("Level", {"LOW": 1}))
+
+    db = await Database.connect(f"sqlite://{tmp_path / 'types.db'}?mode=rwc")
+    with pytest.raises(ValueError, match="only supported on PostgreSQL"):
+        await db.create_type(Mood)
+    await Table.from_model(Person).create(db)
+    address = Address('1 "Main" St', "Oslo, NO", None)
+    stay = Stay(datetime.date(2024, 5, 1), 3)
+    await Person(id=1, mood=Mood.SAD, address=address, stay=stay).save(db)
+    await Person(id=2, mood="happy", address=None, stay=None).save(db)
+    assert await db.execute("SELECT mood, address, stay FROM person WHERE id = 1") == [
+        {"mood": "it's sad", "address": '("1 ""Main"" St","Oslo, NO",)', "stay": '("2024-05-01","3")'}
+    ]
+    person, other = await Person.query().all(db)
+    assert (person.mood, person.address, person.stay) == (Mood.SAD, address, stay)
+    assert (other.mood, other.address, other.stay) == (Mood.HAPPY, None, None)
+    assert await Person.query(Person.address == address).count(db) == 1
+
+
+async def test_intervals_and_ranges(tmp_path: pathlib.Path) -> None:
+    class Booking(Model):
+        id: ColumnField[int]
+        nights: ColumnField[Range[int]]
+        stay: ColumnField[Range[datetime.datetime] | None]
+        cleaning: ColumnField[datetime.timedelta | None]
+
+    table = Table.from_model(Booking)
+    assert "nights int4range NOT NULL" in table.to_sql("postgres")
+    assert "stay tstzrange" in table.to_sql("postgres")
+    assert "cleaning INTERVAL" in table.to_sql("postgres")
+    assert "cleaning REAL" in table.to_sql("sqlite")
+    assert repr(TstzRange) == "TstzRange"
+
+    assert Range(1, 10) == Range(1, 10, "[)")
+    assert 1 in Range(1, 10) and 10 not in Range(1, 10) and 10 in Range(1, 10, "[]")
+    assert 5 in Range(None, 10) and 5 not in Range(empty=True)
+    assert repr(Range(None, 3, "[]")) == "Range(None, 3, '(]')"
+    with pytest.raises(ValueError, match="range bounds"):
+        Range(1, 2, "[[")
+
+    db = await Database.connect(f"sqlite://{tmp_path / 'ranges.db'}?mode=rwc")
+    await table.create(db)
+    check_in = datetime.datetime(2024, 5, 1, 14, tzinfo=datetime.timezone.utc)
+    stay = Range(check_in, check_in + datetime.timedelta(days=2))
+    cleaning = datetime.timedelta(hours=1, minutes=30, microseconds=250)
+    await Booking(id=1, nights=Range(1, 3, "[]"), stay=stay, cleaning=cleaning).save(db)
+    await Booking(id=2, nights=Range(empty=True), stay=None, cleaning=-cleaning).save(db)
+    assert await db.execute("SELECT nights, stay, cleaning FROM booking WHERE id = 1") == [
+        {
+            "nights": "[1,3]",
+            "stay": '["2024-05-01 14:00:00+00:00","2024-05-03 14:00:00+00:00")',
+            "cleaning": 5400.00025,
+        }
+    ]
+    booking, other = await Booking.query().all(db)
+    assert (booking.nights, booking.stay, booking.cleaning) == (Range(1, 3, "[]"), stay, cleaning)
+    assert check_in in booking.stay
+    assert (other.nights.is_empty, other.stay, other.cleaning) == (True, None, -cleaning)
+    over_an_hour = Booking.cleaning > datetime.timedelta(hours=1)
+    assert await Booking.query(over_an_hour).values_list(db, Booking.id, flat=True) == [1]
+    assert await db.execute(select("SELECT id FROM booking WHERE nights = ?", Range(1, 3, "[]"))) == [
+        {"id": 1}
+    ]
+
+    # Durations stored as ISO 8601 text load as well.
+    await db.execute("UPDATE booking SET cleaning = 'P1DT2H0.5S' WHERE id = 2")
+    (other,) = await Booking.query(Booking.id == 2).all(db)
+    assert other.cleaning == datetime.timedelta(days=1, hours=2, milliseconds=500)
+
+
+async def test_make_migration(db: Database, tmp_path: pathlib.Path) -> None:
+    class Shelf(Model):
+        id: ColumnField[int]
+        label: ColumnField[str | None]
+
+        class Meta:
+            indexes = ["label"]
+
+    class Volume(Model):
+        id: ColumnField[int]
+        shelf_id: ColumnField[int] = ColumnField(references=Shelf, on_delete="cascade")
+
+    created = await db.make_migration(str(tmp_path), [Volume, Shelf], name="create shelves")
+    assert created == {"version": 1, "name": "create_shelves"}
+    up = (tmp_path / "0001_create_shelves.up.sql").read_text()
+    assert up.index("CREATE TABLE shelf") < up.index("CREATE TABLE volume")
+    assert "CREATE INDEX shelf_label_idx ON shelf (label);" in up
+    await db.migrate(str(tmp_path))
+    assert await db.make_migration(str(tmp_path), [Shelf, Volume]) is None
+
+    class ShelfV2(Model):
+        id: ColumnField[int]
+        width: ColumnField[int | None]
+
+        class Meta:
+            table_name = "shelf"
+
+    await db.execute("INSERT INTO shelf (id, label) VALUES (1, 'A')")
+    await db.execute("INSERT INTO volume (id, shelf_id) VALUES (1, 1)")
+    assert await db.make_migration(str(tmp_path), [ShelfV2, Volume], name="width") == {
+        "version": 2,
+        "name": "width",
+    }
+    await db.migrate(str(tmp_path))
+    assert await db.execute("SELECT id, width FROM shelf") == [{"id": 1, "width": None}]
+    assert await db.execute("SELECT id, shelf_id FROM volume") == [{"id": 1, "shelf_id": 1}]
+
+    await db.rollback(str(tmp_path))
+    assert await db.execute("SELECT id, label FROM shelf") == [{"id": 1, "label": None}]
+    assert [index["name"] for index in await db.indexes("shelf")] == ["shelf_label_idx"]
+    assert await db.execute("SELECT id, shelf_id FROM volume") == [{"id": 1, "shelf_id": 1}]
+
+    (tmp_path / "0003_pending.sql").write_text("SELECT 1;")
+    with pytest.raises(ValueError, match="isn't applied yet"):
+        await db.make_migration(str(tmp_path), [ShelfV2])
+    with pytest.raises(ValueError, match="not a Model class"):
+        await db.make_migration(str(tmp_path), [Table("shelf")])
+
+
+async def test_views(db: Database, tmp_path: pathlib.Path) -> None:
+    adults = View("adults", "SELECT id, name FROM user WHERE age >= 18;\n")
+    assert adults.to_sql() == "CREATE VIEW adults AS SELECT id, name FROM user WHERE age >= 18"
+    assert adults.to_sql("sqlite", or_replace=True).startswith("DROP VIEW IF EXISTS adults;\n")
+    assert adults.to_sql("postgres", or_replace=True).startswith("CREATE OR REPLACE VIEW adults AS")
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30), (2, 'Tim', 9)")
+    await adults.create(db)
+    await View("adults", "SELECT id FROM user").create(db, or_replace=True)
+    assert await db.execute("SELECT id FROM adults ORDER BY id") == [{"id": 1}, {"id": 2}]
+    await db.drop_view(adults)
+    await db.drop_view("adults")
+    with pytest.raises(ValueError):
+        await db.drop_view("adults", if_exists=False)
+
+    class Pet(Model):
+        id: ColumnField[int]
+        name: ColumnField[str | None]
+        legacy: ColumnField[int | None]
+
+    class PetName(Model):
+        id: ColumnField[int]
+        name: ColumnField[str]
+
+        class Meta:
+            view = "SELECT id, name FROM pet WHERE name IS NOT NULL"
+
+    assert View.from_model(PetName).name == "pet_name"
+    with pytest.raises(ValueError, match="maps onto a view"):
+        Table.from_model(PetName)
+
+    await db.make_migration(str(tmp_path), [PetName, Pet], name="pets")
+    up = (tmp_path / "0001_pets.up.sql").read_text()
+    assert up.index("CREATE TABLE pet") < up.index("CREATE VIEW pet_name AS SELECT id, name FROM pet")
+    await db.migrate(str(tmp_path))
+    assert await db.make_migration(str(tmp_path), [Pet, PetName]) is None
+    await db.execute("INSERT INTO pet (id, name) VALUES (1, 'rex'), (2, NULL)")
+    [pet] = await PetName.query().all(db)
+    assert (pet.id, pet.name) == (1, "rex")
+
+    with pytest.raises(ValueError, match="read-only"):
+        await pet.save(db)
+    with pytest.raises(ValueError, match="read-only"):
+        await pet.delete(db)
+    with pytest.raises(ValueError, match="read-only"):
+        await PetName.query().update(db, {"name": "max"})
+    with pytest.raises(ValueError, match="read-only"):
+        await PetName.bulk_create(db, [PetName(id=3, name="max")])
+
+    class ShoutedPetName(Model):
+        id: ColumnField[int]
+        name: ColumnField[str]
+
+        class Meta:
+            table_name = "pet_name"
+            view = "SELECT id, upper(name) AS name FROM pet WHERE name IS NOT NULL"
+
+    with pytest.raises(ValueError, match="view 'pet_name' is out of date"):
+        await db.validate_schema([Pet, ShoutedPetName])
+    await db.make_migration(str(tmp_path), [Pet, ShoutedPetName], name="shout")
+    await db.migrate(str(tmp_path))
+    assert await db.execute("SELECT id, name FROM pet_name") == [{"id": 1, "name": "REX"}]
+    await db.rollback(str(tmp_path))
+    assert await db.execute("SELECT id, name FROM pet_name") == [{"id": 1, "name": "rex"}]
+
+    # Dropping a column rebuilds the table on SQLite, which the view would
+    # stand in the way of.
+    class PetV2(Model):
+        id: ColumnField[int]
+        name: ColumnField[str | None]
+
+        class Meta:
+            table_name = "pet"
+
+    (tmp_path / "0002_shout.up.sql").unlink()
+    (tmp_path / "0002_shout.down.sql").unlink()
+    await db.make_migration(str(tmp_path), [PetV2, PetName], name="drop legacy")
+    up = (tmp_path / "0002_drop_legacy.up.sql").read_text()
+    assert up.startswith("DROP VIEW pet_name;")
+    await db.migrate(str(tmp_path))
+    assert await db.execute("SELECT id, name FROM pet_name") == [{"id": 1, "name": "rex"}]
+    await db.rollback(str(tmp_path))
+    assert await db.execute("SELECT id, name, legacy FROM pet ORDER BY id") == [
+        {"id": 1, "name": "rex", "legacy": None},
+        {"id": 2, "name": None, "legacy": None},
+    ]
+    assert await db.execute("SELECT id, name FROM pet_name") == [{"id": 1, "name": "rex"}]
+
+    await db.drop_view(PetName)
+    with pytest.raises(ValueError, match="missing view 'pet_name'"):
+        await db.validate_schema([Pet, PetName])
+
+
+async def test_triggers(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("CREATE TABLE user_audit (user_id INTEGER, old_age INTEGER, new_age INTEGER)")
+    name = await db.create_trigger(
+        "user",
+        "after",
+        "update",
+        "INSERT INTO user_audit (user_id, old_age, new_age) VALUES (NEW.id, OLD.age, NEW.age)",
+        when="OLD.age IS NOT NEW.age",
+    )
+    assert name == "user_after_update_trg"
+    assert await db.create_trigger("user", "after", "update", "SELECT 1", if_not_exists=True) == name
+    assert await db.triggers("user") == [{"name": name, "timing": "after", "event": "update"}]
+    await db.execute("INSERT INTO user (id, name, age) VALUES (1, 'Ann', 30)")
+    await db.execute("UPDATE user SET age = 31 WHERE id = 1")
+    await db.execute("UPDATE user SET name = 'Anna' WHERE id = 1")
+    assert await db.execute("SELECT user_id, old_age, new_age FROM user_audit") == [
+        {"user_id": 1, "old_age": 30, "new_age": 31}
+    ]
+    await db.drop_trigger(name)
+    assert await db.triggers("user") == []
+    with pytest.raises(ValueError):
+        await db.drop_trigger(name, if_exists=False)
+
+    with pytest.raises(ValueError, match="Unknown trigger timing"):
+        Trigger("during", "insert", "SELECT 1;")
+    with pytest.raises(ValueError, match="INSTEAD OF"):
+        Trigger("instead of", "insert", "SELECT 1").to_sql("user", "mysql")
+    [function, trigger] = Trigger("before", "delete", "SELECT 1").to_sql("user", "postgres")
+    assert "RETURN OLD;" in function
+    assert trigger.endswith("EXECUTE FUNCTION user_before_delete_trg_fn()")
+
+    class Item(Model):
+        id: ColumnField[int]
+        price: ColumnField[int]
+        total: ColumnField[int | None]
+
+        class Meta:
+            triggers = [
+                Trigger("after", "insert", "UPDATE item SET total = NEW.price * 2 WHERE id = NEW.id")
+            ]
+
+    await db.make_migration(str(tmp_path), [Item], name="items")
+    up = (tmp_path / "0001_items.up.sql").read_text()
+    assert up.index("CREATE TABLE item") < up.index("CREATE TRIGGER item_after_insert_trg")
+    await db.migrate(str(tmp_path))
+    assert await db.make_migration(str(tmp_path), [Item]) is None
+    await db.validate_schema([Item])
+    await db.execute("INSERT INTO item (id, price) VALUES (1, 5)")
+    assert await db.execute("SELECT total FROM item") == [{"total": 10}]
+
+    class PricierItem(Model):
+        id: ColumnField[int]
+        price: ColumnField[int]
+        total: ColumnField[int | None]
+
+        class Meta:
+            table_name = "item"
+            triggers = [
+                Trigger("after", "insert", "UPDATE item SET total = NEW.price * 3 WHERE id = NEW.id")
+            ]
+
+    with pytest.raises(ValueError, match="trigger 'item_after_insert_trg' of 'item' is out of date"):
+        await db.validate_schema([PricierItem])
+    await db.make_migration(str(tmp_path), [PricierItem], name="pricier")
+    await db.migrate(str(tmp_path))
+    await db.execute("INSERT INTO item (id, price) VALUES (2, 5)")
+    await db.rollback(str(tmp_path))
+    await db.execute("INSERT INTO item (id, price) VALUES (3, 5)")
+    assert await db.execute("SELECT id, total FROM item ORDER BY id") == [
+        {"id": 1, "total": 10},
+        {"id": 2, "total": 15},
+        {"id": 3, "total": 10},
+    ]
+
+    class PlainItem(Model):
+        id: ColumnField[int]
+        price: ColumnField[int]
+        total: ColumnField[int | None]
+
+        class Meta:
+            table_name = "item"
+            triggers = []
+
+    (tmp_path / "0002_pricier.up.sql").unlink()
+    (tmp_path / "0002_pricier.down.sql").unlink()
+    await db.make_migration(str(tmp_path), [PlainItem], name="no trigger")
+    assert (tmp_path / "0002_no_trigger.up.sql").read_text() == "DROP TRIGGER item_after_insert_trg;\n"
+    await db.migrate(str(tmp_path))
+    assert await db.triggers(PlainItem) == []
+    with pytest.raises(ValueError, match="missing trigger 'item_after_insert_trg'"):
+        await db.validate_schema([Item])
+
+
+def test_cli(tmp_path: pathlib.Path, capsys: pytest.CaptureFixture[str]) -> None:
+    from fust_orm.fust_orm import main
+
+    migrations = tmp_path / "migrations"
+    migrations.mkdir()
+    (migrations / "0001_create_post.up.sql").write_text("CREATE TABLE post (id INTEGER PRIMARY KEY);")
+    (migrations / "0001_create_post.down.sql").write_text("DROP TABLE post;")
+    url = ["--database-url", f"sqlite://{tmp_path / 'app.db'}?mode=rwc"]
+    args = [*url, f"--migrations={migrations}"]
+
+    assert main([*args, "migrate", "--dry-run"]) == 0
+    assert capsys.readouterr().out.splitlines() == [
+        "-- 0001_create_post",
+        "CREATE TABLE post (id INTEGER PRIMARY KEY);",
+    ]
+    assert main([*args, "migrate"]) == 0
+    assert main([*args, "migrate"]) == 0
+    assert main([*args, "status"]) == 0
+    assert main([*args, "rollback", "--steps", "2"]) == 0
+    out = capsys.readouterr().out.splitlines()
+    assert out[:2] == ["Applied 0001_create_post", "No pending migrations."]
+    assert out[2].startswith("[x] 0001_create_post  (applied ")
+    assert out[3:] == ["Rolled back 0001_create_post"]
+
+    assert main([*url, "--migrations", str(tmp_path / "missing"), "status"]) == 1
+    assert "Can't read migrations directory" in capsys.readouterr().err
+    assert main([*args, "frobnicate"]) == 2
+    assert "unknown command 'frobnicate'" in capsys.readouterr().err
+    assert main([*args, "rollback", "--steps", "many"]) == 2
+
+    (migrations / "0002_seed.py").write_text(
+        "async def seed(db):\n"
+        "    await db.execute('INSERT INTO post (id) VALUES (1)')\n"
+        "\n"
+        "up = seed\n"
+        "down = 'DELETE FROM post'\n"
+    )
+    assert main([*args, "migrate"]) == 0
+    assert main([*url, "introspect", "--table", "post", "--output", str(tmp_path / "models.py")]) == 0
+    assert capsys.readouterr().out.splitlines()[-3:] == [
+        "Applied 0001_create_post",
+        "Applied 0002_seed",
+        f"Wrote {tmp_path / 'models.py'}",
+    ]
+    assert "class Post(Model):\n    id: ColumnField[int]\n" in (tmp_path / "models.py").read_text()
+
+
+async def test_introspect_models(db: Database) -> None:
+    await db.execute("CREATE TABLE author (id INTEGER PRIMARY KEY, name TEXT NOT NULL, born DATE)")
+    await db.execute(
+        """
+        CREATE TABLE Books (
+            isbn TEXT PRIMARY KEY,
+            author_id INTEGER REFERENCES author (id) ON DELETE SET NULL,
+            sequel_of TEXT REFERENCES Books (isbn),
+            price REAL,
+            published_at TIMESTAMP,
+            UNIQUE (author_id, price)
+        )
+        """
+    )
+    await db.create_index("Books", "price")
+    source = await introspect_models(db, ["Books", "author"])
+    assert source.index("class Author(Model):") < source.index("class Books(Model):")
+    assert "    born: ColumnField[datetime.date | None]\n" in source
+    assert "    isbn: ColumnField[str] = ColumnField(primary_key=True)\n" in source
+    assert (
+        '    author_id: ColumnField[int | None] = ColumnField(references=Author, on_delete="set_null")\n'
+        in source
+    )
+    assert 'sequel_of: ColumnField[str | None] = ColumnField(references="self")\n' in source
+    assert '        table_name = "Books"\n' in source
+    assert '        indexes = ["price"]\n' in source
+    assert '        unique_together = [("author_id", "price")]\n' in source
+
+    namespace: dict[str, Any] = {}
+    exec(source, namespace)
+    await db.validate_schema([namespace["Author"], namespace["Books"]])
+    with pytest.raises(ValueError, match="no table 'missing'"):
+        await introspect_models(db, ["missing"])
+
+
+async def test_validate_schema(db: Database) -> None:
+    class Invoice(Model):
+        id: ColumnField[int]
+        number: ColumnField[str]
+        total: ColumnField[float]
+
+        class Meta:
+            indexes = ["number"]
+            unique_together = [("number", "total")]
+
+    class InvoiceLine(Model):
+        id: ColumnField[int]
+        invoice_id: ColumnField[int] = ColumnField(references=Invoice)
+
+    await db.execute("CREATE TABLE invoice (id INTEGER PRIMARY KEY, number TEXT, legacy TEXT)")
+    with pytest.raises(ValueError) as excinfo:
+        await db.validate_schema([Invoice, InvoiceLine])
+    assert str(excinfo.value) == (
+        "Schema mismatch: the database doesn't match the models:\n"
+        "  - table 'invoice' is missing column 'total'\n"
+        "  - table 'invoice' is missing an index on (number)\n"
+        "  - table 'invoice' is missing a unique constraint on (number, total)\n"
+        "  - missing table 'invoice_line'"
+    )
+
+    await db.execute("ALTER TABLE invoice ADD COLUMN total REAL")
+    await db.create_index(Invoice, "number")
+    await db.create_index(Invoice, ["number", "total"], unique=True)
+    await Table.from_model(InvoiceLine).create(db)
+    await db.validate_schema([Invoice, InvoiceLine])
+
+
+async def test_load_fixtures(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("CREATE TABLE author (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+    await db.execute(
+        "CREATE TABLE book (id INTEGER PRIMARY KEY, "
+        "author_id INTEGER NOT NULL REFERENCES author (id), title TEXT, meta TEXT)"
+    )
+    fixture = tmp_path / "library.json"
+    fixture.write_text(
+        json.dumps(
+            {
+                "book": [
+                    {"id": 1, "author_id": 1, "title": "Dune", "meta": {"pages": 412}},
+                    {"id": 2, "author_id": 1, "title": None},
+                ],
+                "author": [{"id": 1, "name": "Frank Herbert"}],
+            }
+        )
+    )
+
+    assert await db.load_fixtures(str(fixture)) == {"author": 1, "book": 2}
+    assert await db.execute("SELECT id, title, meta FROM book ORDER BY id") == [
+        {"id": 1, "title": "Dune", "meta": '{"pages": 412}'},
+        {"id": 2, "title": None, "meta": None},
+    ]
+
+    (tmp_path / "more.json").write_text(
+        json.dumps({"author": [{"id": 2, "name": "Ursula K. Le Guin"}], "missing": []})
+    )
+    with pytest.raises(ValueError, match="Fixture table 'missing' doesn't exist"):
+        await db.load_fixtures(str(tmp_path / "more.json"))
+    extra = tmp_path / "extra"
+    extra.mkdir()
+    (extra / "1_authors.json").write_text(json.dumps({"author": [{"id": 2, "name": "Le Guin"}]}))
+    (extra / "2_books.json").write_text(json.dumps({"book": [{"id": 3, "author_id": 9}]}))
+    with pytest.raises(ValueError, match="FOREIGN KEY constraint failed"):
+        await db.load_fixtures(str(extra))
+    assert await db.execute("SELECT COUNT(*) AS n FROM author") == [{"n": 1}]
+    (extra / "2_books.json").write_text(json.dumps({"book": [{"id": 3, "author_id": 2}]}))
+    assert await db.load_fixtures(str(extra)) == {"author": 1, "book": 1}
+    (tmp_path / "more.json").write_text("[]")
+    with pytest.raises(ValueError, match="expected a mapping of table names to rows"):
+        await db.load_fixtures(str(tmp_path / "more.json"))
+
+
+async def test_create_test(tmp_path: pathlib.Path) -> None:
+    fixture = tmp_path / "users.json"
+    fixture.write_text(json.dumps({"user": [{"id": 1, "name": "Ann", "age": 30, "manager_id": 0}]}))
+    db = await Database.create_test([User], fixtures=fixture)
+    assert await select(User.name).values_list(db, "name", flat=True) == ["Ann"]
+    await User(name="Bob", age=31, manager_id=0).save(db)
+    # The connections of the pool share the database.
+    counts = await asyncio.gather(*(select(User).count(db) for _ in range(3)))
+    assert counts == [2, 2, 2]
+    assert await select(User).count(await Database.create_test([User])) == 0
+
+    migrations = tmp_path / "migrations"
+    migrations.mkdir()
+    (migrations / "0001_create_item.up.sql").write_text("CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT)")
+    db = await Database.create_test(str(migrations))
+    assert await db.execute("SELECT COUNT(*) AS n FROM item") == [{"n": 0}]
+    assert await db.execute("SELECT COUNT(*) AS n FROM _fust_migrations") == [{"n": 1}]
+
+    with pytest.raises(ValueError):
+        await Database.create_test([object])
+
+
+async def test_create_test_worker(tmp_path: pathlib.Path) -> None:
+    template = await Database.connect(f"sqlite://{tmp_path / 'template.db'}?mode=rwc")
+    await template.execute("CREATE TABLE item (id INTEGER PRIMARY KEY, name TEXT)")
+    await template.execute("INSERT INTO item (name) VALUES ('seeded')")
+    await template.close()
+
+    url = f"sqlite://{tmp_path / 'template.db'}"
+    gw0 = await Database.create_test_worker(url, "gw0")
+    gw1 = await Database.create_test_worker(url, "gw1")
+    assert (tmp_path / "template_gw0.db").exists() and (tmp_path / "template_gw1.db").exists()
+    await gw0.execute("INSERT INTO item (name) VALUES ('gw0')")
+    assert await gw0.execute("SELECT COUNT(*) AS n FROM item") == [{"n": 2}]
+    assert await gw1.execute("SELECT COUNT(*) AS n FROM item") == [{"n": 1}]
+    await gw0.close()
+    # A new copy replaces the one of an earlier run.
+    gw0 = await Database.create_test_worker(url, "gw0")
+    assert await gw0.execute("SELECT name FROM item") == [{"name": "seeded"}]
+    await gw0.close()
+    await gw1.close()
+
+    with pytest.raises(ValueError, match="in-memory"):
+        await Database.create_test_worker("sqlite::memory:", "gw0")
+    with pytest.raises(ValueError, match="worker id"):
+        await Database.create_test_worker(url, "../gw0")
+    with pytest.raises(ValueError, match="MySQL"):
+        await Database.create_test_worker("mysql://localhost/app", "gw0")
+
+
+async def test_load_yaml_fixtures(db: Database, tmp_path: pathlib.Path) -> None:
+    pytest.importorskip("yaml")
+    await db.execute("CREATE TABLE color (id INTEGER PRIMARY KEY, name TEXT, hex TEXT)")
+    (tmp_path / "colors.yaml").write_text(
+        "color:\n  - {id: 1, name: red, hex: '#ff0000'}\n  - {id: 2, name: green}\n"
+    )
+    assert await db.load_fixtures(str(tmp_path / "colors.yaml")) == {"color": 2}
+    assert await db.execute("SELECT name, hex FROM color ORDER BY id") == [
+        {"name": "red", "hex": "#ff0000"},
+        {"name": "green", "hex": None},
+    ]
+
+
+async def test_seed(db: Database) -> None:
+    await db.execute("CREATE TABLE author (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+    await db.execute(
+        "CREATE TABLE reader (id INTEGER PRIMARY KEY, email VARCHAR(40) NOT NULL UNIQUE, "
+        "first_name TEXT, age INTEGER, joined_at TEXT, "
+        "author_id INTEGER NOT NULL REFERENCES author (id), plan TEXT)"
+    )
+    with pytest.raises(ValueError, match="no rows"):
+        await db.seed("reader", 1)
+    assert await db.seed("author", 3) == 3
+    assert await db.seed("reader", 50, overrides={"plan": "free", "age": lambda i: 20 + i}) == 50
+
+    rows = await db.execute("SELECT * FROM reader ORDER BY id")
+    assert len(rows) == 50 and len({row["email"] for row in rows}) == 50
+    assert all(len(row["email"]) <= 40 and "@example." in row["email"] for row in rows)
+    assert [row["age"] for row in rows[:3]] == [20, 21, 22]
+    assert {row["plan"] for row in rows} == {"free"}
+    assert {row["author_id"] for row in rows} <= {1, 2, 3}
+    assert all("2023-01-01" <= row["joined_at"] < "2025-01-01" for row in rows)
+
+    # The same seed generates the same values, and later rows stay unique.
+    await db.execute("CREATE TABLE reader_copy AS SELECT * FROM reader WHERE 0")
+    await db.seed("reader_copy", 5)
+    copies = await db.execute("SELECT first_name, joined_at FROM reader_copy")
+    await db.execute("DELETE FROM reader_copy")
+    await db.seed("reader_copy", 5)
+    assert await db.execute("SELECT first_name, joined_at FROM reader_copy") == copies
+    assert await db.seed("reader", 10) == 10
+    assert await db.execute("SELECT COUNT(DISTINCT email) AS n FROM reader") == [{"n": 60}]
+
+    with pytest.raises(ValueError, match="doesn't exist"):
+        await db.seed("missing", 1)
+    with pytest.raises(ValueError, match="not a column"):
+        await db.seed("author", 1, overrides={"nickname": "x"})
+
+
+async def test_dump_and_restore(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("CREATE TABLE author (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL)")
+    await db.execute(
+        "CREATE TABLE book (id INTEGER PRIMARY KEY, author_id INTEGER REFERENCES author (id), "
+        "title TEXT, price REAL, cover BLOB)"
+    )
+    await db.execute("CREATE INDEX book_title_idx ON book (title)")
+    await db.execute(
+        "CREATE TRIGGER book_title AFTER INSERT ON book BEGIN\n"
+        "  UPDATE book SET title = trim(title) WHERE id = new.id;\n"
+        "END"
+    )
+    await db.execute("CREATE VIEW titles AS SELECT title FROM book")
+    await db.execute("INSERT INTO author (name) VALUES ('Frank'), ('Ursula')")
+    await db.execute("DELETE FROM author WHERE name = 'Ursula'")
+    await db.execute(
+        "INSERT INTO book (id, author_id, title, price, cover) "
+        "VALUES (1, 1, 'It''s;\n-- not a comment;', 9.5, X'00FF'), (2, NULL, NULL, NULL, NULL)"
+    )
+
+    path = tmp_path / "dump.sql"
+    counts = await db.dump(str(path))
+    assert (counts["author"], counts["book"]) == (1, 2)
+    assert list(counts).index("author") < list(counts).index("book")
+    assert path.read_text().startswith("-- fust-orm dump of a sqlite database\n")
+
+    restored = await Database.restore(str(path), f"sqlite://{tmp_path / 'copy.db'}?mode=rwc")
+    books = "SELECT id, author_id, title, price, hex(cover) AS cover FROM book ORDER BY id"
+    assert await restored.execute(books) == await db.execute(books)
+    assert await restored.execute("SELECT * FROM titles") == await db.execute("SELECT * FROM titles")
+    assert [index["name"] for index in await restored.indexes("book")] == ["book_title_idx"]
+    await restored.execute("INSERT INTO author (name) VALUES ('Ann')")
+    assert await restored.execute("SELECT id FROM author WHERE name = 'Ann'") == [{"id": 3}]
+    await restored.execute("INSERT INTO book (id, title) VALUES (3, '  padded  ')")
+    assert await restored.execute("SELECT title FROM book WHERE id = 3") == [{"title": "padded"}]
+
+
+async def test_maintenance(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("CREATE TABLE author (id INTEGER PRIMARY KEY, bio TEXT)")
+    await db.execute(
+        "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 500) "
+        "INSERT INTO author (id, bio) SELECT i, printf('%.500c', 'x') FROM n"
+    )
+    await db.execute("DELETE FROM author")
+
+    sizes = await db.vacuum()
+    assert sizes["size_after"] < sizes["size_before"]
+
+    assert await db.analyze("author") == ["author"]
+    assert "user" in await db.analyze()
+    assert await db.integrity_check() == {"ok": True, "errors": [], "foreign_key_violations": []}
+
+    # Foreign keys aren't enforced by the sqlite3 module, so it can write a
+    # dangling reference.
+    path = tmp_path / "dangling.db"
+    with sqlite3.connect(path) as conn:
+        conn.execute("CREATE TABLE author (id INTEGER PRIMARY KEY)")
+        conn.execute("CREATE TABLE book (id INTEGER PRIMARY KEY, author_id INTEGER REFERENCES author (id))")
+        conn.execute("INSERT INTO book (id, author_id) VALUES (7, 42)")
+    conn.close()
+    dangling = await Database.connect(f"sqlite://{path}")
+    report = await dangling.integrity_check()
+    assert report == {
+        "ok": False,
+        "errors": [],
+        "foreign_key_violations": [{"table": "book", "rowid": 7, "parent": "author"}],
+    }
+
+
+async def test_backup(tmp_path: pathlib.Path) -> None:
+    db = await Database.connect(f"sqlite://{tmp_path / 'live.db'}?mode=rwc")
+    await db.execute("CREATE TABLE item (id INTEGER PRIMARY KEY, payload TEXT)")
+    await db.execute(
+        "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 200) "
+        "INSERT INTO item (payload) SELECT printf('%.500c', 'x') FROM n"
+    )
+    progress: list[tuple[int, int]] = []
+    backup = db.backup(tmp_path / "copy.db", lambda remaining, total: progress.append((remaining, total)), pages=8, sleep=0)
+    # Writers go on while the copy is made.
+    await asyncio.gather(backup, *(db.execute("INSERT INTO item (payload) VALUES ('late')") for _ in range(5)))
+    assert len(progress) > 1 and progress[-1][0] == 0
+    with sqlite3.connect(tmp_path / "copy.db") as copy:
+        assert copy.execute("PRAGMA integrity_check").fetchone() == ("ok",)
+        assert 200 <= copy.execute("SELECT COUNT(*) FROM item").fetchone()[0] <= 205
+    copy.close()
+
+    await db.backup(str(tmp_path / "all.db"), pages=-1)
+    with sqlite3.connect(tmp_path / "all.db") as copy:
+        assert copy.execute("SELECT COUNT(*) FROM item").fetchone() == (205,)
+    copy.close()
+    with pytest.raises(ZeroDivisionError):
+        await db.backup(tmp_path / "failed.db", lambda remaining, total: 1 / 0)
+    with pytest.raises(OperationalError, match="Couldn't back up"):
+        await db.backup(tmp_path / "missing" / "copy.db")
+
+
+async def test_snapshot(db: Database, tmp_path: pathlib.Path) -> None:
+    await db.execute("CREATE TABLE reading (id INTEGER PRIMARY KEY, value REAL)")
+    await db.execute(
+        "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 300) "
+        "INSERT INTO reading (value) SELECT i * 0.5 FROM n"
+    )
+    await db.execute("DELETE FROM reading WHERE id > 100")
+    size = await db.snapshot(tmp_path / "snapshot.db")
+    assert size == (tmp_path / "snapshot.db").stat().st_size
+    with sqlite3.connect(tmp_path / "snapshot.db") as copy:
+        assert copy.execute("SELECT COUNT(*), MAX(value) FROM reading").fetchone() == (100, 50.0)
+        assert copy.execute("PRAGMA freelist_count").fetchone() == (0,)
+    copy.close()
+    with pytest.raises(ProgrammingError, match="output file already exists"):
+        await db.snapshot(str(tmp_path / "snapshot.db"))